@@ -0,0 +1,93 @@
+//! How long the list pages and API endpoints take against the seed data, plus a list with
+//! `BIG_LIST_ITEMS` items. See benches/README.md for the baselines and how to compare with them.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rocket::tokio::runtime::Runtime;
+use rocket_db_pools::sqlx;
+use wishlist_rs::testing::TestApp;
+
+/// About as many items as the longest lists on real instances.
+const BIG_LIST_ITEMS: usize = 200;
+
+/// Adds a public list of bob's with `BIG_LIST_ITEMS` items, and returns its key.
+async fn big_list(app: &TestApp) -> String {
+    sqlx::query(
+        r#"
+        INSERT INTO lists (key, user_id, privacy, title, description, created_at, updated_at)
+        SELECT 'big-list', id, 'public', 'Big list', '', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+        FROM users
+        WHERE username = 'bob'
+        "#,
+    )
+    .execute(app.pool())
+    .await
+    .expect("list can be added");
+
+    for n in 0..BIG_LIST_ITEMS {
+        sqlx::query(
+            r#"
+            INSERT INTO items (list_id, title, description, url, price_cents, price_currency, position, created_at, updated_at)
+            SELECT id, $1, $2, $3, 2499, 'USD', $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+            FROM lists
+            WHERE key = 'big-list'
+            "#,
+        )
+        .bind(format!("Item {}", n))
+        .bind("A **description** with [a link](https://example.com) in it")
+        .bind(format!("https://example.com/products/{}", n))
+        .bind(n as i64)
+        .execute(app.pool())
+        .await
+        .expect("item can be added");
+    }
+
+    app.list_key("Big list").await
+}
+
+/// Benchmarks GETs of each of the URIs, by name.
+fn get_all(
+    c: &mut Criterion,
+    runtime: &Runtime,
+    app: &TestApp,
+    group: &str,
+    uris: &[(&str, String)],
+) {
+    let mut group = c.benchmark_group(group);
+    for (name, uri) in uris {
+        group.bench_function(*name, |b| {
+            b.to_async(runtime).iter(|| async {
+                let response = app.client().get(uri.as_str()).dispatch().await;
+                assert!(response.status().class().is_success(), "{} is found", uri);
+                response.into_bytes().await
+            })
+        });
+    }
+    group.finish();
+}
+
+fn lists(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("runtime starts");
+    let app = runtime.block_on(TestApp::new());
+    let birthday = runtime.block_on(app.list_key("Alice's Birthday"));
+    let big = runtime.block_on(big_list(&app));
+
+    let uris = [
+        ("index", "/lists".to_string()),
+        ("show", format!("/lists/{}", birthday)),
+        ("show big", format!("/lists/{}", big)),
+        ("api index", "/api/v1/lists".to_string()),
+        ("api show", format!("/api/v1/lists/{}", birthday)),
+        ("api items", format!("/api/v1/lists/{}/items", birthday)),
+        ("api items big", format!("/api/v1/lists/{}/items", big)),
+    ];
+
+    // Anonymous visitors get list pages from the render cache after the first time
+    get_all(c, &runtime, &app, "anonymous", &uris);
+
+    // Logged in, every page is rendered and every query is run
+    runtime.block_on(app.login("alice"));
+    get_all(c, &runtime, &app, "logged in", &uris);
+}
+
+criterion_group!(benches, lists);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+-- Remove admin flag, suspension columns and the suspension_appeals table
+DROP TABLE suspension_appeals;
+ALTER TABLE users DROP COLUMN suspension_reason;
+ALTER TABLE users DROP COLUMN suspended_at;
+ALTER TABLE users DROP COLUMN is_admin;
@@ -0,0 +1,6 @@
+-- Remove device metadata from user_sessions and the user_devices table
+DROP TABLE user_devices;
+DROP INDEX user_sessions_revoke_token_uindex;
+ALTER TABLE user_sessions DROP COLUMN revoke_token;
+ALTER TABLE user_sessions DROP COLUMN ip_address;
+ALTER TABLE user_sessions DROP COLUMN user_agent;
@@ -0,0 +1,13 @@
+-- Create uploads table for chunked uploads
+CREATE TABLE uploads (
+    id BIGSERIAL PRIMARY KEY,
+    token VARCHAR(255) NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES users (id),
+    total_size BIGINT NOT NULL,
+    received_size BIGINT NOT NULL DEFAULT 0,
+    content_type VARCHAR(255),
+    is_complete BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX uploads_token_uindex ON uploads (token);
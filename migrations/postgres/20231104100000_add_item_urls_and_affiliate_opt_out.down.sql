@@ -0,0 +1,3 @@
+-- Remove 'url' from items and 'affiliate_opt_out' from lists
+ALTER TABLE lists DROP COLUMN affiliate_opt_out;
+ALTER TABLE items DROP COLUMN url;
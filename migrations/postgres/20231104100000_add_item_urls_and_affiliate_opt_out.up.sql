@@ -0,0 +1,3 @@
+-- Add 'url' to items and 'affiliate_opt_out' to lists
+ALTER TABLE items ADD COLUMN url TEXT;
+ALTER TABLE lists ADD COLUMN affiliate_opt_out BOOLEAN NOT NULL DEFAULT FALSE;
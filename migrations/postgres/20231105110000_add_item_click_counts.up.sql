@@ -0,0 +1,2 @@
+-- Add 'click_count' to items
+ALTER TABLE items ADD COLUMN click_count BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- Create item_prices table for price tracking
+CREATE TABLE item_prices (
+    id BIGSERIAL PRIMARY KEY,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    amount_cents BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX item_prices_item_id_index ON item_prices (item_id);
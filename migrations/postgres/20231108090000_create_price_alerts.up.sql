@@ -0,0 +1,12 @@
+-- Create price_alerts table for target price notifications
+CREATE TABLE price_alerts (
+    id BIGSERIAL PRIMARY KEY,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    target_cents BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    triggered_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX price_alerts_item_id_user_id_uindex ON price_alerts (item_id, user_id);
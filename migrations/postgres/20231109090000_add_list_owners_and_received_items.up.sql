@@ -0,0 +1,4 @@
+-- Add 'user_id' to lists and 'received_at' to items
+ALTER TABLE lists ADD COLUMN user_id BIGINT REFERENCES users (id) ON DELETE SET NULL;
+ALTER TABLE items ADD COLUMN received_at TIMESTAMP;
+CREATE INDEX lists_user_id_index ON lists (user_id);
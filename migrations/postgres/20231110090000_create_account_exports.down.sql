@@ -0,0 +1,2 @@
+-- Remove account_exports table
+DROP TABLE account_exports;
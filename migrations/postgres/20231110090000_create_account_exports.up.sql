@@ -0,0 +1,10 @@
+-- Create account_exports table for account data archives
+CREATE TABLE account_exports (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token VARCHAR(255) NOT NULL,
+    status VARCHAR(16) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX account_exports_token_uindex ON account_exports (token);
@@ -0,0 +1,5 @@
+-- Add pending email changes to users
+ALTER TABLE users ADD COLUMN pending_email VARCHAR(255);
+ALTER TABLE users ADD COLUMN email_change_token VARCHAR(255);
+ALTER TABLE users ADD COLUMN email_change_requested_at TIMESTAMP;
+CREATE UNIQUE INDEX users_email_change_token_uindex ON users (email_change_token);
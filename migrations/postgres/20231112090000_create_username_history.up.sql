@@ -0,0 +1,9 @@
+-- Create username_history table for renamed accounts
+CREATE TABLE username_history (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    username VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX username_history_username_index ON username_history (username);
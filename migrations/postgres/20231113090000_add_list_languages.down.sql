@@ -0,0 +1,2 @@
+-- Remove 'language' from lists
+ALTER TABLE lists DROP COLUMN language;
@@ -0,0 +1,2 @@
+-- Add 'language' to lists
+ALTER TABLE lists ADD COLUMN language VARCHAR(35);
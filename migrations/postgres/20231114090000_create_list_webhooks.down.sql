@@ -0,0 +1,2 @@
+-- Remove list_webhooks table
+DROP TABLE list_webhooks;
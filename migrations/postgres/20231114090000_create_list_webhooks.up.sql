@@ -0,0 +1,10 @@
+-- Create list_webhooks table for chat notifications
+CREATE TABLE list_webhooks (
+    id BIGSERIAL PRIMARY KEY,
+    list_id BIGINT NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    url VARCHAR(2048) NOT NULL,
+    format VARCHAR(16) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX list_webhooks_list_id_index ON list_webhooks (list_id);
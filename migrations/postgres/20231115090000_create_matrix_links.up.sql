@@ -0,0 +1,10 @@
+-- Create matrix_links table for Matrix direct message notifications
+CREATE TABLE matrix_links (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    matrix_id VARCHAR(255) NOT NULL,
+    room_id VARCHAR(255),
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX matrix_links_user_id_uindex ON matrix_links (user_id);
@@ -0,0 +1,2 @@
+-- Remove inbound_addresses table
+DROP TABLE inbound_addresses;
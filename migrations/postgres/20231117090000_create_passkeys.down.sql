@@ -0,0 +1,2 @@
+-- Remove passkeys table
+DROP TABLE passkeys;
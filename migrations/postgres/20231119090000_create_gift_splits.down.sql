@@ -0,0 +1,3 @@
+-- Remove gift_splits and gift_contributors tables
+DROP TABLE gift_contributors;
+DROP TABLE gift_splits;
@@ -0,0 +1,21 @@
+-- Create gift_splits and gift_contributors tables for splitting group gifts
+CREATE TABLE gift_splits (
+    id BIGSERIAL PRIMARY KEY,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    organizer_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    total_cents BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX gift_splits_item_id_uindex ON gift_splits (item_id);
+
+CREATE TABLE gift_contributors (
+    id BIGSERIAL PRIMARY KEY,
+    split_id BIGINT NOT NULL REFERENCES gift_splits (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    paid_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX gift_contributors_split_id_user_id_uindex ON gift_contributors (split_id, user_id);
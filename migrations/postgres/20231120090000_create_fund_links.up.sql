@@ -0,0 +1,10 @@
+-- Create fund_links table for users' external "fund this" links
+CREATE TABLE fund_links (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    url VARCHAR(2048) NOT NULL,
+    click_count BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX fund_links_user_id_uindex ON fund_links (user_id);
@@ -0,0 +1,5 @@
+-- Remove 'kind', 'amount_cents' and 'currency' from items, and item_contributions
+DROP TABLE item_contributions;
+ALTER TABLE items DROP COLUMN currency;
+ALTER TABLE items DROP COLUMN amount_cents;
+ALTER TABLE items DROP COLUMN kind;
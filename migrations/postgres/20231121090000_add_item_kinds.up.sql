@@ -0,0 +1,15 @@
+-- Add 'kind', 'amount_cents' and 'currency' to items, and item_contributions for cash funds
+ALTER TABLE items ADD COLUMN kind VARCHAR(16) NOT NULL DEFAULT 'physical';
+ALTER TABLE items ADD COLUMN amount_cents BIGINT;
+ALTER TABLE items ADD COLUMN currency VARCHAR(3);
+
+CREATE TABLE item_contributions (
+    id BIGSERIAL PRIMARY KEY,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    amount_cents BIGINT NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX item_contributions_item_id_index ON item_contributions (item_id);
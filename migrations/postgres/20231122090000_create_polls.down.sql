@@ -0,0 +1,4 @@
+-- Remove polls, poll_options and poll_votes tables
+DROP TABLE poll_votes;
+DROP TABLE poll_options;
+DROP TABLE polls;
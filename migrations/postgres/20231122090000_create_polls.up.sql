@@ -0,0 +1,29 @@
+-- Create polls, poll_options and poll_votes tables for polls scoped to an item
+CREATE TABLE polls (
+    id BIGSERIAL PRIMARY KEY,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    created_by BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    question VARCHAR(256) NOT NULL,
+    chosen_option_id BIGINT,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX polls_item_id_uindex ON polls (item_id);
+
+CREATE TABLE poll_options (
+    id BIGSERIAL PRIMARY KEY,
+    poll_id BIGINT NOT NULL REFERENCES polls (id) ON DELETE CASCADE,
+    label VARCHAR(256) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX poll_options_poll_id_index ON poll_options (poll_id);
+
+CREATE TABLE poll_votes (
+    id BIGSERIAL PRIMARY KEY,
+    option_id BIGINT NOT NULL REFERENCES poll_options (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX poll_votes_option_id_user_id_uindex ON poll_votes (option_id, user_id);
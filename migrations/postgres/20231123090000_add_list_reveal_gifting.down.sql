@@ -0,0 +1,2 @@
+-- Remove 'reveal_gifting' from lists
+ALTER TABLE lists DROP COLUMN reveal_gifting;
@@ -0,0 +1,2 @@
+-- Add 'reveal_gifting' to lists
+ALTER TABLE lists ADD COLUMN reveal_gifting BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Remove search index tables
+DROP TABLE search_tasks;
+DROP TABLE search_documents;
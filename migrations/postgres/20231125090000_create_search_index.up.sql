@@ -0,0 +1,25 @@
+-- Create search_documents table holding the search index, and search_tasks for what needs reindexing
+CREATE TABLE search_documents (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL,
+    target_id BIGINT NOT NULL,
+    list_id BIGINT NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    item_id BIGINT REFERENCES items (id) ON DELETE CASCADE,
+    title VARCHAR(256) NOT NULL,
+    content TEXT NOT NULL,
+    indexed_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX search_documents_kind_target_id_uindex ON search_documents (kind, target_id);
+CREATE INDEX search_documents_list_id_index ON search_documents (list_id);
+
+CREATE TABLE search_tasks (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL,
+    target_id BIGINT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX search_tasks_kind_target_id_uindex ON search_tasks (kind, target_id);
+
+-- Index everything that already exists
+INSERT INTO search_tasks (kind, target_id, created_at) SELECT 'list', id, CURRENT_TIMESTAMP FROM lists;
+INSERT INTO search_tasks (kind, target_id, created_at) SELECT 'item', id, CURRENT_TIMESTAMP FROM items;
@@ -0,0 +1,15 @@
+-- Create search_trigrams table for typo tolerant search, and add 'body' to search_documents for match snippets
+ALTER TABLE search_documents ADD COLUMN body TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE search_trigrams (
+    document_id BIGINT NOT NULL REFERENCES search_documents (id) ON DELETE CASCADE,
+    trigram VARCHAR(3) NOT NULL
+);
+CREATE UNIQUE INDEX search_trigrams_document_id_trigram_uindex ON search_trigrams (document_id, trigram);
+CREATE INDEX search_trigrams_trigram_index ON search_trigrams (trigram);
+
+-- Reindex everything so it gets trigrams
+INSERT INTO search_tasks (kind, target_id, created_at) SELECT 'list', id, CURRENT_TIMESTAMP FROM lists WHERE TRUE
+ON CONFLICT (kind, target_id) DO NOTHING;
+INSERT INTO search_tasks (kind, target_id, created_at) SELECT 'item', id, CURRENT_TIMESTAMP FROM items WHERE TRUE
+ON CONFLICT (kind, target_id) DO NOTHING;
@@ -0,0 +1,14 @@
+-- Create saved_searches table for emailing users about new items matching a search
+CREATE TABLE saved_searches (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    query VARCHAR(256) NOT NULL,
+    min_price_cents BIGINT,
+    max_price_cents BIGINT,
+    currency VARCHAR(3),
+    last_item_id BIGINT NOT NULL,
+    notified_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX saved_searches_user_id_index ON saved_searches (user_id);
@@ -0,0 +1,2 @@
+-- Remove api_tokens table
+DROP TABLE api_tokens;
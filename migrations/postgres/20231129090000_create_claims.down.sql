@@ -0,0 +1,2 @@
+-- Remove claims table
+DROP TABLE claims;
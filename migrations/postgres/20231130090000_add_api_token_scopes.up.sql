@@ -0,0 +1,4 @@
+-- Add 'scopes' and 'list_id' to api_tokens, giving existing tokens the access they had before
+ALTER TABLE api_tokens ADD COLUMN scopes VARCHAR(255) NOT NULL DEFAULT '';
+ALTER TABLE api_tokens ADD COLUMN list_id BIGINT REFERENCES lists (id) ON DELETE CASCADE;
+UPDATE api_tokens SET scopes = 'read:lists,write:lists,read:items,write:items';
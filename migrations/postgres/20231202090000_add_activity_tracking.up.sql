@@ -0,0 +1,4 @@
+-- Add 'request_count' to api_tokens, and 'last_used_at' and 'request_count' to user_sessions
+ALTER TABLE api_tokens ADD COLUMN request_count BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE user_sessions ADD COLUMN last_used_at TIMESTAMP;
+ALTER TABLE user_sessions ADD COLUMN request_count BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Remove events table
+DROP TABLE events;
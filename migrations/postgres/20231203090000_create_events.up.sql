@@ -0,0 +1,14 @@
+-- Create events table, the log of everything that happens to lists and items
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(64) NOT NULL,
+    list_id BIGINT,
+    item_id BIGINT,
+    user_id BIGINT,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    dispatched_at TIMESTAMP
+);
+CREATE INDEX events_list_id_index ON events (list_id, id);
+CREATE INDEX events_dispatched_at_index ON events (dispatched_at);
+CREATE INDEX events_created_at_index ON events (created_at);
@@ -0,0 +1,5 @@
+-- Remove 'price_cents', 'price_currency', 'quantity' and 'priority' from items
+ALTER TABLE items DROP COLUMN priority;
+ALTER TABLE items DROP COLUMN quantity;
+ALTER TABLE items DROP COLUMN price_currency;
+ALTER TABLE items DROP COLUMN price_cents;
@@ -0,0 +1,5 @@
+-- Add 'price_cents', 'price_currency', 'quantity' and 'priority' to items
+ALTER TABLE items ADD COLUMN price_cents BIGINT;
+ALTER TABLE items ADD COLUMN price_currency VARCHAR(3);
+ALTER TABLE items ADD COLUMN quantity BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE items ADD COLUMN priority VARCHAR(16) NOT NULL DEFAULT 'normal';
@@ -0,0 +1,2 @@
+-- Remove deliveries table
+DROP TABLE deliveries;
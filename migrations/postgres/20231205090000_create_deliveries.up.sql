@@ -0,0 +1,16 @@
+-- Create deliveries table, the outbox of webhook posts and emails waiting to be sent
+CREATE TABLE deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL,
+    webhook_id BIGINT REFERENCES list_webhooks (id) ON DELETE CASCADE,
+    target VARCHAR(2048) NOT NULL,
+    subject VARCHAR(255),
+    body TEXT NOT NULL,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP NOT NULL,
+    sent_at TIMESTAMP,
+    failed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX deliveries_next_attempt_at_index ON deliveries (next_attempt_at);
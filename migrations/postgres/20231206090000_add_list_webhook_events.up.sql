@@ -0,0 +1,2 @@
+-- Add 'events' to list_webhooks
+ALTER TABLE list_webhooks ADD COLUMN events VARCHAR(255) NOT NULL DEFAULT '';
@@ -0,0 +1,2 @@
+-- Remove hook_subscriptions table
+DROP TABLE hook_subscriptions;
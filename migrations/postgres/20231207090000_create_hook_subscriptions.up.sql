@@ -0,0 +1,10 @@
+-- Create hook_subscriptions table for REST hooks, e.g. from Zapier
+CREATE TABLE hook_subscriptions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    list_id BIGINT REFERENCES lists (id) ON DELETE CASCADE,
+    event VARCHAR(32) NOT NULL,
+    target_url VARCHAR(2048) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX hook_subscriptions_user_id_index ON hook_subscriptions (user_id);
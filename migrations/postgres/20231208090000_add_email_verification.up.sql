@@ -0,0 +1,13 @@
+-- Add 'email_verified_at' to users, and create email_verifications table for verification links
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP;
+-- Accounts from before verification existed keep everything they could do
+UPDATE users SET email_verified_at = created_at;
+CREATE TABLE email_verifications (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    token VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX email_verifications_user_id_index ON email_verifications (user_id);
+CREATE UNIQUE INDEX email_verifications_token_uindex ON email_verifications (token);
@@ -0,0 +1,2 @@
+-- Remove 'event_date' from lists
+ALTER TABLE lists DROP COLUMN event_date;
@@ -0,0 +1,2 @@
+-- Remove password_reset_tokens table
+DROP TABLE password_reset_tokens;
@@ -0,0 +1,2 @@
+-- Add 'stale_reminded_for' to lists, the event date the owner was last told their list was stale for
+ALTER TABLE lists ADD COLUMN stale_reminded_for DATE;
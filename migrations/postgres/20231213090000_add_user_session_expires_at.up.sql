@@ -0,0 +1,6 @@
+-- Add 'expires_at' to user_sessions
+ALTER TABLE user_sessions ADD COLUMN expires_at TIMESTAMP;
+-- Existing sessions keep the 7 days their cookie was given
+UPDATE user_sessions SET expires_at = created_at + INTERVAL '7 days';
+ALTER TABLE user_sessions ALTER COLUMN expires_at SET NOT NULL;
+CREATE INDEX user_sessions_expires_at_index ON user_sessions (expires_at);
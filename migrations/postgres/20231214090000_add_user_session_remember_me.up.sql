@@ -0,0 +1,4 @@
+-- Add 'remember_me' to user_sessions, for sessions that outlast the browser
+ALTER TABLE user_sessions ADD COLUMN remember_me BOOLEAN NOT NULL DEFAULT FALSE;
+-- Sessions from before this always had a cookie that outlasted the browser
+UPDATE user_sessions SET remember_me = TRUE;
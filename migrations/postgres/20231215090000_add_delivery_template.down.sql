@@ -0,0 +1,2 @@
+-- Remove 'template' from deliveries
+ALTER TABLE deliveries DROP COLUMN template;
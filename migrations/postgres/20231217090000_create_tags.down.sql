@@ -0,0 +1,3 @@
+-- Remove tags and item_tags tables
+DROP TABLE item_tags;
+DROP TABLE tags;
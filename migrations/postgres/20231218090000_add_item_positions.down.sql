@@ -0,0 +1,3 @@
+-- Remove 'position' from items
+DROP INDEX items_list_id_position_index;
+ALTER TABLE items DROP COLUMN position;
@@ -0,0 +1,2 @@
+-- Remove list_shares table
+DROP TABLE list_shares;
@@ -0,0 +1,12 @@
+-- Create list_shares table for secret links to lists
+CREATE TABLE list_shares (
+    id BIGSERIAL PRIMARY KEY,
+    list_id BIGINT NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    token VARCHAR(128) NOT NULL,
+    permission VARCHAR(16) NOT NULL,
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX list_shares_token_uindex ON list_shares (token);
+CREATE INDEX list_shares_list_id_index ON list_shares (list_id);
@@ -0,0 +1,2 @@
+-- Remove feature_flags table
+DROP TABLE feature_flags;
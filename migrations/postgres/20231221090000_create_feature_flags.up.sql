@@ -0,0 +1,10 @@
+-- Create feature_flags table for instance features an admin has turned on or off
+CREATE TABLE feature_flags (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    updated_by BIGINT REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX feature_flags_name_uindex ON feature_flags (name);
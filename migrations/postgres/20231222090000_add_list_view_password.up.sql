@@ -0,0 +1,2 @@
+-- Add 'view_password_hash' to lists
+ALTER TABLE lists ADD COLUMN view_password_hash VARCHAR(128);
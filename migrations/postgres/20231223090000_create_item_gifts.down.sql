@@ -0,0 +1,2 @@
+-- Remove item_gifts table
+DROP TABLE item_gifts;
@@ -0,0 +1,2 @@
+-- Remove 'price_max_cents' from items
+ALTER TABLE items DROP COLUMN price_max_cents;
@@ -0,0 +1,16 @@
+-- Create the notification_preferences and list_watchers tables, for activity emails
+CREATE TABLE notification_preferences (
+    user_id BIGINT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    claim_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    activity_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    unsubscribe_token VARCHAR(64) NOT NULL UNIQUE,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE TABLE list_watchers (
+    id BIGSERIAL PRIMARY KEY,
+    list_id BIGINT NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    share_id BIGINT REFERENCES list_shares (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    UNIQUE (list_id, user_id)
+);
@@ -0,0 +1,2 @@
+-- Remove rate_limit_counters table
+DROP TABLE rate_limit_counters;
@@ -0,0 +1,2 @@
+-- Remove claim_events table
+DROP TABLE claim_events;
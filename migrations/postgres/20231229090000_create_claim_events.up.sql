@@ -0,0 +1,11 @@
+-- Create claim_events table, an append-only history of claims for looking into disputes
+CREATE TABLE claim_events (
+    id BIGSERIAL PRIMARY KEY,
+    claim_id BIGINT NOT NULL,
+    item_id BIGINT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id BIGINT REFERENCES users (id) ON DELETE SET NULL,
+    actor_id BIGINT REFERENCES users (id) ON DELETE SET NULL,
+    kind VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX claim_events_item_id_index ON claim_events (item_id);
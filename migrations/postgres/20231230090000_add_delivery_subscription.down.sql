@@ -0,0 +1,2 @@
+-- Remove 'subscription_id' from deliveries
+ALTER TABLE deliveries DROP COLUMN subscription_id;
@@ -0,0 +1,8 @@
+-- Delete claim_events with their item again
+DELETE FROM claim_events WHERE item_id IS NULL;
+ALTER TABLE claim_events DROP CONSTRAINT claim_events_item_id_fkey;
+ALTER TABLE claim_events ALTER COLUMN item_id SET NOT NULL;
+ALTER TABLE claim_events ADD CONSTRAINT claim_events_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE CASCADE;
+ALTER TABLE claim_events DROP COLUMN list_key;
+ALTER TABLE claim_events DROP COLUMN item_title;
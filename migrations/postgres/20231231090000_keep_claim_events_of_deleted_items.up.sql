@@ -0,0 +1,15 @@
+-- Keep claim_events when their item is deleted, with the item's title and list key saved on each event
+ALTER TABLE claim_events ADD COLUMN item_title TEXT NOT NULL DEFAULT '';
+ALTER TABLE claim_events ADD COLUMN list_key TEXT NOT NULL DEFAULT '';
+UPDATE claim_events
+SET item_title = items.title,
+    list_key = lists.key
+FROM items
+JOIN lists ON lists.id = items.list_id
+WHERE items.id = claim_events.item_id;
+ALTER TABLE claim_events ALTER COLUMN item_title DROP DEFAULT;
+ALTER TABLE claim_events ALTER COLUMN list_key DROP DEFAULT;
+ALTER TABLE claim_events DROP CONSTRAINT claim_events_item_id_fkey;
+ALTER TABLE claim_events ALTER COLUMN item_id DROP NOT NULL;
+ALTER TABLE claim_events ADD CONSTRAINT claim_events_item_id_fkey
+    FOREIGN KEY (item_id) REFERENCES items (id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Add 'auth_source' to users, where they log in: 'local' with a password here, or 'directory'
+ALTER TABLE users ADD COLUMN auth_source VARCHAR(16) NOT NULL DEFAULT 'local';
+UPDATE users SET auth_source = 'directory' WHERE password_hash = '!directory';
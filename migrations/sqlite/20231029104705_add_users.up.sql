@@ -14,6 +14,6 @@ CREATE TABLE user_sessions (
     user_id INTEGER NOT NULL REFERENCES users (id),
     token VARCHAR(255) NOT NULL,
     created_at DATETIME NOT NULL,
-    updated_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
 );
 CREATE UNIQUE INDEX user_sessions_token_uindex ON user_sessions (token);
@@ -0,0 +1,15 @@
+-- Add device metadata to user_sessions and the user_devices table
+ALTER TABLE user_sessions ADD COLUMN user_agent TEXT;
+ALTER TABLE user_sessions ADD COLUMN ip_address TEXT;
+ALTER TABLE user_sessions ADD COLUMN revoke_token VARCHAR(255);
+CREATE UNIQUE INDEX user_sessions_revoke_token_uindex ON user_sessions (revoke_token);
+
+CREATE TABLE user_devices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    user_agent TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX user_devices_user_device_uindex ON user_devices (user_id, user_agent, ip_address);
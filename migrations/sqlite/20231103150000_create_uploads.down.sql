@@ -0,0 +1,2 @@
+-- Remove uploads table
+DROP TABLE uploads;
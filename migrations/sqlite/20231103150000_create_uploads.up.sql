@@ -0,0 +1,13 @@
+-- Create uploads table for chunked uploads
+CREATE TABLE uploads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token VARCHAR(255) NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    total_size INTEGER NOT NULL,
+    received_size INTEGER NOT NULL DEFAULT 0,
+    content_type VARCHAR(255),
+    is_complete BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX uploads_token_uindex ON uploads (token);
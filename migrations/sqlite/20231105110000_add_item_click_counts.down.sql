@@ -0,0 +1,2 @@
+-- Remove 'click_count' from items
+ALTER TABLE items DROP COLUMN click_count;
@@ -0,0 +1,3 @@
+-- Remove link check status from items
+ALTER TABLE items DROP COLUMN link_checked_at;
+ALTER TABLE items DROP COLUMN link_broken;
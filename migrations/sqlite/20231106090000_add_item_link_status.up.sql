@@ -0,0 +1,3 @@
+-- Add link check status to items
+ALTER TABLE items ADD COLUMN link_broken BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE items ADD COLUMN link_checked_at DATETIME;
@@ -0,0 +1,2 @@
+-- Remove item_prices table
+DROP TABLE item_prices;
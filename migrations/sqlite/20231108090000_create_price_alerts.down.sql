@@ -0,0 +1,2 @@
+-- Remove price_alerts table
+DROP TABLE price_alerts;
@@ -0,0 +1,12 @@
+-- Create price_alerts table for target price notifications
+CREATE TABLE price_alerts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    target_cents INTEGER NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    triggered_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX price_alerts_item_id_user_id_uindex ON price_alerts (item_id, user_id);
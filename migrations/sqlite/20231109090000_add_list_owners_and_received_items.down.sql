@@ -0,0 +1,4 @@
+-- Remove 'user_id' from lists and 'received_at' from items
+DROP INDEX lists_user_id_index;
+ALTER TABLE items DROP COLUMN received_at;
+ALTER TABLE lists DROP COLUMN user_id;
@@ -0,0 +1,4 @@
+-- Add 'user_id' to lists and 'received_at' to items
+ALTER TABLE lists ADD COLUMN user_id INTEGER;
+ALTER TABLE items ADD COLUMN received_at DATETIME;
+CREATE INDEX lists_user_id_index ON lists (user_id);
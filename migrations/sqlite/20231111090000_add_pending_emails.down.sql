@@ -0,0 +1,5 @@
+-- Remove pending email changes from users
+DROP INDEX users_email_change_token_uindex;
+ALTER TABLE users DROP COLUMN email_change_requested_at;
+ALTER TABLE users DROP COLUMN email_change_token;
+ALTER TABLE users DROP COLUMN pending_email;
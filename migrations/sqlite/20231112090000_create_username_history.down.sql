@@ -0,0 +1,2 @@
+-- Remove username_history table
+DROP TABLE username_history;
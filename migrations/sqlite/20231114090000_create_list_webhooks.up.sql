@@ -0,0 +1,10 @@
+-- Create list_webhooks table for chat notifications
+CREATE TABLE list_webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    list_id INTEGER NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    url VARCHAR(2048) NOT NULL,
+    format VARCHAR(16) NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX list_webhooks_list_id_index ON list_webhooks (list_id);
@@ -0,0 +1,2 @@
+-- Remove matrix_links table
+DROP TABLE matrix_links;
@@ -0,0 +1,10 @@
+-- Create inbound_addresses table for adding items by email
+CREATE TABLE inbound_addresses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token VARCHAR(255) NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX inbound_addresses_user_id_uindex ON inbound_addresses (user_id);
+CREATE UNIQUE INDEX inbound_addresses_token_uindex ON inbound_addresses (token);
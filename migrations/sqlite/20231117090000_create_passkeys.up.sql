@@ -0,0 +1,13 @@
+-- Create passkeys table for WebAuthn logins
+CREATE TABLE passkeys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    credential_id VARCHAR(1024) NOT NULL,
+    passkey TEXT NOT NULL,
+    last_used_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX passkeys_user_id_index ON passkeys (user_id);
+CREATE UNIQUE INDEX passkeys_credential_id_uindex ON passkeys (credential_id);
@@ -0,0 +1,3 @@
+-- Remove roles and user_roles tables
+DROP TABLE user_roles;
+DROP TABLE roles;
@@ -0,0 +1,21 @@
+-- Create roles and user_roles tables for permissions beyond the admin flag
+CREATE TABLE roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(64) NOT NULL,
+    permissions TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX roles_name_uindex ON roles (name);
+
+CREATE TABLE user_roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role_id INTEGER NOT NULL REFERENCES roles (id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX user_roles_user_id_role_id_uindex ON user_roles (user_id, role_id);
+
+INSERT INTO roles (name, permissions, created_at, updated_at)
+VALUES ('moderator', 'moderate_content', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP);
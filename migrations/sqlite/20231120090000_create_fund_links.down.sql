@@ -0,0 +1,2 @@
+-- Remove fund_links table
+DROP TABLE fund_links;
@@ -0,0 +1,15 @@
+-- Add 'kind', 'amount_cents' and 'currency' to items, and item_contributions for cash funds
+ALTER TABLE items ADD COLUMN kind VARCHAR(16) NOT NULL DEFAULT 'physical';
+ALTER TABLE items ADD COLUMN amount_cents INTEGER;
+ALTER TABLE items ADD COLUMN currency VARCHAR(3);
+
+CREATE TABLE item_contributions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    amount_cents INTEGER NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX item_contributions_item_id_index ON item_contributions (item_id);
@@ -0,0 +1,2 @@
+-- Remove quota_exemptions table
+DROP TABLE quota_exemptions;
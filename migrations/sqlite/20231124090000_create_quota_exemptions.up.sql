@@ -0,0 +1,9 @@
+-- Create quota_exemptions table for users an admin has freed from usage quotas
+CREATE TABLE quota_exemptions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    granted_by INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX quota_exemptions_user_id_uindex ON quota_exemptions (user_id);
@@ -0,0 +1,3 @@
+-- Remove search_trigrams table and 'body' from search_documents
+DROP TABLE search_trigrams;
+ALTER TABLE search_documents DROP COLUMN body;
@@ -0,0 +1,2 @@
+-- Remove saved_searches table
+DROP TABLE saved_searches;
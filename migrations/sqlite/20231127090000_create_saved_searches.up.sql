@@ -0,0 +1,14 @@
+-- Create saved_searches table for emailing users about new items matching a search
+CREATE TABLE saved_searches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    query VARCHAR(256) NOT NULL,
+    min_price_cents INTEGER,
+    max_price_cents INTEGER,
+    currency VARCHAR(3),
+    last_item_id INTEGER NOT NULL,
+    notified_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX saved_searches_user_id_index ON saved_searches (user_id);
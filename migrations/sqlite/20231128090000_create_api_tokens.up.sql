@@ -0,0 +1,13 @@
+-- Create api_tokens table for authenticating API calls
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL,
+    token_prefix VARCHAR(16) NOT NULL,
+    last_used_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX api_tokens_user_id_index ON api_tokens (user_id);
+CREATE UNIQUE INDEX api_tokens_token_hash_uindex ON api_tokens (token_hash);
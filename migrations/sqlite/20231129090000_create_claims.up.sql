@@ -0,0 +1,10 @@
+-- Create claims table for people to say they're getting an item
+CREATE TABLE claims (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX claims_item_id_uindex ON claims (item_id);
+CREATE INDEX claims_user_id_index ON claims (user_id);
@@ -0,0 +1,3 @@
+-- Remove 'scopes' and 'list_id' from api_tokens
+ALTER TABLE api_tokens DROP COLUMN list_id;
+ALTER TABLE api_tokens DROP COLUMN scopes;
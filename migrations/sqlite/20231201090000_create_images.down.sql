@@ -0,0 +1,3 @@
+-- Remove images and item_images tables
+DROP TABLE item_images;
+DROP TABLE images;
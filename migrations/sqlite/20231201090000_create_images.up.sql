@@ -0,0 +1,19 @@
+-- Create images table for uploaded photos, and item_images to put them on items
+CREATE TABLE images (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    content_type VARCHAR(255) NOT NULL,
+    size INTEGER NOT NULL,
+    source_url VARCHAR(2048),
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE INDEX images_user_id_index ON images (user_id);
+CREATE TABLE item_images (
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    image_id INTEGER NOT NULL REFERENCES images (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (item_id, image_id)
+);
+CREATE INDEX item_images_image_id_index ON item_images (image_id);
@@ -0,0 +1,4 @@
+-- Remove 'request_count' from api_tokens, and 'last_used_at' and 'request_count' from user_sessions
+ALTER TABLE user_sessions DROP COLUMN request_count;
+ALTER TABLE user_sessions DROP COLUMN last_used_at;
+ALTER TABLE api_tokens DROP COLUMN request_count;
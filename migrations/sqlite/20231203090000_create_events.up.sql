@@ -0,0 +1,14 @@
+-- Create events table, the log of everything that happens to lists and items
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind VARCHAR(64) NOT NULL,
+    list_id INTEGER,
+    item_id INTEGER,
+    user_id INTEGER,
+    payload TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    dispatched_at DATETIME
+);
+CREATE INDEX events_list_id_index ON events (list_id, id);
+CREATE INDEX events_dispatched_at_index ON events (dispatched_at);
+CREATE INDEX events_created_at_index ON events (created_at);
@@ -0,0 +1,16 @@
+-- Create deliveries table, the outbox of webhook posts and emails waiting to be sent
+CREATE TABLE deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind VARCHAR(16) NOT NULL,
+    webhook_id INTEGER REFERENCES list_webhooks (id) ON DELETE CASCADE,
+    target VARCHAR(2048) NOT NULL,
+    subject VARCHAR(255),
+    body TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at DATETIME NOT NULL,
+    sent_at DATETIME,
+    failed_at DATETIME,
+    created_at DATETIME NOT NULL
+);
+CREATE INDEX deliveries_next_attempt_at_index ON deliveries (next_attempt_at);
@@ -0,0 +1,2 @@
+-- Remove 'events' from list_webhooks
+ALTER TABLE list_webhooks DROP COLUMN events;
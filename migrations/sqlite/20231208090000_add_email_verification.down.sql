@@ -0,0 +1,3 @@
+-- Remove email_verifications table, and 'email_verified_at' from users
+DROP TABLE email_verifications;
+ALTER TABLE users DROP COLUMN email_verified_at;
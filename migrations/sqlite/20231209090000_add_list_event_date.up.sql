@@ -0,0 +1,2 @@
+-- Add 'event_date' to lists
+ALTER TABLE lists ADD COLUMN event_date DATE;
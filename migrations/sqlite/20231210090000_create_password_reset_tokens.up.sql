@@ -0,0 +1,10 @@
+-- Create password_reset_tokens table for password reset links
+CREATE TABLE password_reset_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL
+);
+CREATE INDEX password_reset_tokens_user_id_index ON password_reset_tokens (user_id);
+CREATE UNIQUE INDEX password_reset_tokens_token_hash_uindex ON password_reset_tokens (token_hash);
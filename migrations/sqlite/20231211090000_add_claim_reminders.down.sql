@@ -0,0 +1,4 @@
+-- Remove 'purchased_at' and 'reminded_at' from claims, and 'claim_reminders' from users
+ALTER TABLE claims DROP COLUMN purchased_at;
+ALTER TABLE claims DROP COLUMN reminded_at;
+ALTER TABLE users DROP COLUMN claim_reminders;
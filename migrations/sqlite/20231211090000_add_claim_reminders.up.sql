@@ -0,0 +1,4 @@
+-- Add 'purchased_at' and 'reminded_at' to claims, and 'claim_reminders' to users
+ALTER TABLE claims ADD COLUMN purchased_at DATETIME;
+ALTER TABLE claims ADD COLUMN reminded_at DATETIME;
+ALTER TABLE users ADD COLUMN claim_reminders BOOLEAN NOT NULL DEFAULT TRUE;
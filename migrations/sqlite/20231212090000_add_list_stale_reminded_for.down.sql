@@ -0,0 +1,2 @@
+-- Remove 'stale_reminded_for' from lists
+ALTER TABLE lists DROP COLUMN stale_reminded_for;
@@ -0,0 +1,3 @@
+-- Remove 'expires_at' from user_sessions
+DROP INDEX user_sessions_expires_at_index;
+ALTER TABLE user_sessions DROP COLUMN expires_at;
@@ -0,0 +1,5 @@
+-- Add 'expires_at' to user_sessions
+ALTER TABLE user_sessions ADD COLUMN expires_at DATETIME;
+-- Existing sessions keep the 7 days their cookie was given
+UPDATE user_sessions SET expires_at = datetime(created_at, '+7 days');
+CREATE INDEX user_sessions_expires_at_index ON user_sessions (expires_at);
@@ -0,0 +1,2 @@
+-- Remove 'remember_me' from user_sessions
+ALTER TABLE user_sessions DROP COLUMN remember_me;
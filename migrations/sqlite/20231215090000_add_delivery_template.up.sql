@@ -0,0 +1,2 @@
+-- Add 'template' to deliveries, for emails rendered from a template when they're sent
+ALTER TABLE deliveries ADD COLUMN template TEXT;
@@ -0,0 +1,2 @@
+-- Remove email_suppressions table
+DROP TABLE email_suppressions;
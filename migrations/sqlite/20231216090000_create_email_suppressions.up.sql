@@ -0,0 +1,10 @@
+-- Create email_suppressions table for addresses that bounced or complained, which aren't emailed
+CREATE TABLE email_suppressions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detail TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX email_suppressions_email_uindex ON email_suppressions (email);
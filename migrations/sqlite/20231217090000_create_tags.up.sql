@@ -0,0 +1,18 @@
+-- Create tags and item_tags tables for tagging items
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(32) NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX tags_name_uindex ON tags (name);
+
+CREATE TABLE item_tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX item_tags_item_id_tag_id_uindex ON item_tags (item_id, tag_id);
+CREATE INDEX item_tags_tag_id_index ON item_tags (tag_id);
@@ -0,0 +1,4 @@
+-- Add 'position' to items, so they can be put in order. Existing items keep the order they were added in
+ALTER TABLE items ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+UPDATE items SET position = id;
+CREATE INDEX items_list_id_position_index ON items (list_id, position);
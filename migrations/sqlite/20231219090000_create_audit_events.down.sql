@@ -0,0 +1,2 @@
+-- Remove audit_events table
+DROP TABLE audit_events;
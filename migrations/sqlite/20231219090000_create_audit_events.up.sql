@@ -0,0 +1,13 @@
+-- Create audit_events table, a record of who changed which lists and items
+CREATE TABLE audit_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    entity_type VARCHAR(16) NOT NULL,
+    entity_id INTEGER NOT NULL,
+    list_id INTEGER NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    old_values TEXT,
+    new_values TEXT,
+    created_at DATETIME NOT NULL
+);
+CREATE INDEX audit_events_list_id_index ON audit_events (list_id, id);
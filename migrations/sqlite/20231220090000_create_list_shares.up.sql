@@ -0,0 +1,12 @@
+-- Create list_shares table for secret links to lists
+CREATE TABLE list_shares (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    list_id INTEGER NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    token VARCHAR(128) NOT NULL,
+    permission VARCHAR(16) NOT NULL,
+    expires_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL
+);
+CREATE UNIQUE INDEX list_shares_token_uindex ON list_shares (token);
+CREATE INDEX list_shares_list_id_index ON list_shares (list_id);
@@ -0,0 +1,2 @@
+-- Remove 'view_password_hash' from lists
+ALTER TABLE lists DROP COLUMN view_password_hash;
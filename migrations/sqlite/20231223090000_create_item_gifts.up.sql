@@ -0,0 +1,8 @@
+-- Create the item_gifts table, for counting how many times consumable items were given
+CREATE TABLE item_gifts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    created_at DATETIME NOT NULL
+);
+CREATE INDEX item_gifts_item_id_index ON item_gifts (item_id);
@@ -0,0 +1,4 @@
+-- Remove 'secret' from list_webhooks and hook_subscriptions, and 'signature' from deliveries
+ALTER TABLE deliveries DROP COLUMN signature;
+ALTER TABLE hook_subscriptions DROP COLUMN secret;
+ALTER TABLE list_webhooks DROP COLUMN secret;
@@ -0,0 +1,4 @@
+-- Add 'secret' to list_webhooks and hook_subscriptions, and 'signature' to deliveries
+ALTER TABLE list_webhooks ADD COLUMN secret VARCHAR(64) NOT NULL DEFAULT '';
+ALTER TABLE hook_subscriptions ADD COLUMN secret VARCHAR(64) NOT NULL DEFAULT '';
+ALTER TABLE deliveries ADD COLUMN signature VARCHAR(80);
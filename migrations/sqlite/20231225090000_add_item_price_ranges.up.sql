@@ -0,0 +1,2 @@
+-- Add 'price_max_cents' to items, for items with a price range
+ALTER TABLE items ADD COLUMN price_max_cents INTEGER;
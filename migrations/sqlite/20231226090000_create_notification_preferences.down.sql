@@ -0,0 +1,3 @@
+-- Remove notification_preferences and list_watchers tables
+DROP TABLE list_watchers;
+DROP TABLE notification_preferences;
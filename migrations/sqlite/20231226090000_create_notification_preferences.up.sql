@@ -0,0 +1,16 @@
+-- Create the notification_preferences and list_watchers tables, for activity emails
+CREATE TABLE notification_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    claim_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    activity_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    unsubscribe_token VARCHAR(64) NOT NULL UNIQUE,
+    updated_at DATETIME NOT NULL
+);
+CREATE TABLE list_watchers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    list_id INTEGER NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    share_id INTEGER REFERENCES list_shares (id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL,
+    UNIQUE (list_id, user_id)
+);
@@ -0,0 +1,5 @@
+-- Replace 'privacy' on lists with 'is_private'. Only public lists stay public
+DROP INDEX lists_privacy_index;
+ALTER TABLE lists ADD COLUMN is_private BOOLEAN NOT NULL DEFAULT TRUE;
+UPDATE lists SET is_private = (privacy <> 'public');
+ALTER TABLE lists DROP COLUMN privacy;
@@ -0,0 +1,5 @@
+-- Replace 'is_private' on lists with 'privacy', one of public, unlisted, group or private
+ALTER TABLE lists ADD COLUMN privacy VARCHAR(16) NOT NULL DEFAULT 'private';
+UPDATE lists SET privacy = CASE WHEN is_private THEN 'private' ELSE 'public' END;
+ALTER TABLE lists DROP COLUMN is_private;
+CREATE INDEX lists_privacy_index ON lists (privacy);
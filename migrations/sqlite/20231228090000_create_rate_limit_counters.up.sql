@@ -0,0 +1,6 @@
+-- Create rate_limit_counters table for keeping rate limits across restarts
+CREATE TABLE rate_limit_counters (
+    key VARCHAR(255) PRIMARY KEY,
+    hits INTEGER NOT NULL,
+    window_ends_at DATETIME NOT NULL
+);
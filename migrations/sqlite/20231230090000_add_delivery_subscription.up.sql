@@ -0,0 +1,2 @@
+-- Add 'subscription_id' to deliveries, the REST hook a post is for
+ALTER TABLE deliveries ADD COLUMN subscription_id INTEGER;
@@ -0,0 +1,17 @@
+-- Delete claim_events with their item again
+CREATE TABLE claim_events_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    claim_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    actor_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    kind VARCHAR(32) NOT NULL,
+    created_at DATETIME NOT NULL
+);
+INSERT INTO claim_events_old (id, claim_id, item_id, user_id, actor_id, kind, created_at)
+SELECT id, claim_id, item_id, user_id, actor_id, kind, created_at
+FROM claim_events
+WHERE item_id IS NOT NULL;
+DROP TABLE claim_events;
+ALTER TABLE claim_events_old RENAME TO claim_events;
+CREATE INDEX claim_events_item_id_index ON claim_events (item_id);
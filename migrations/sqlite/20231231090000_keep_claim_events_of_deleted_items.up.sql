@@ -0,0 +1,20 @@
+-- Keep claim_events when their item is deleted, with the item's title and list key saved on each event
+CREATE TABLE claim_events_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    claim_id INTEGER NOT NULL,
+    item_id INTEGER REFERENCES items (id) ON DELETE SET NULL,
+    item_title TEXT NOT NULL,
+    list_key TEXT NOT NULL,
+    user_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    actor_id INTEGER REFERENCES users (id) ON DELETE SET NULL,
+    kind VARCHAR(32) NOT NULL,
+    created_at DATETIME NOT NULL
+);
+INSERT INTO claim_events_new (id, claim_id, item_id, item_title, list_key, user_id, actor_id, kind, created_at)
+SELECT e.id, e.claim_id, e.item_id, i.title, l.key, e.user_id, e.actor_id, e.kind, e.created_at
+FROM claim_events e
+JOIN items i ON i.id = e.item_id
+JOIN lists l ON l.id = i.list_id;
+DROP TABLE claim_events;
+ALTER TABLE claim_events_new RENAME TO claim_events;
+CREATE INDEX claim_events_item_id_index ON claim_events (item_id);
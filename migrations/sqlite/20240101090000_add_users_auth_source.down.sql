@@ -0,0 +1,2 @@
+-- Remove 'auth_source' from users
+ALTER TABLE users DROP COLUMN auth_source;
@@ -0,0 +1,34 @@
+#!/bin/sh
+# Hits the list show and index endpoints of a running server with oha
+# (https://github.com/hatoo/oha) and prints the latency of each. See benches/README.md.
+#
+# Usage: scripts/load-test.sh [base URL]
+set -eu
+
+BASE_URL="${1:-http://127.0.0.1:8000}"
+DURATION="${DURATION:-30s}"
+CONNECTIONS="${CONNECTIONS:-50}"
+
+if ! command -v oha >/dev/null; then
+    echo "oha isn't installed, see https://github.com/hatoo/oha" >&2
+    exit 1
+fi
+
+# The first public list on the instance
+KEY=$(curl -fsS "$BASE_URL/api/v1/lists" | grep -o '"key":"[^"]*"' | head -n 1 | cut -d '"' -f 4)
+if [ -z "$KEY" ]; then
+    echo "No public lists at $BASE_URL, seed it first" >&2
+    exit 1
+fi
+
+for path in \
+    "/lists" \
+    "/lists/$KEY" \
+    "/api/v1/lists" \
+    "/api/v1/lists/$KEY" \
+    "/api/v1/lists/$KEY/items"
+do
+    echo "== GET $path"
+    oha --no-tui -z "$DURATION" -c "$CONNECTIONS" "$BASE_URL$path" \
+        | grep -E 'Requests/sec|Slowest|Average|50\.00%|99\.00%|\[[0-9]{3}\]'
+done
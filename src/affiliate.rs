@@ -0,0 +1,171 @@
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use url::Url;
+
+use crate::config::AppConfig;
+use crate::db::models::{Item, List};
+use crate::sources::{amazon, ebay, host_matches};
+
+/// What to do with affiliate tags on outbound item links.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum AffiliateMode {
+    /// Leave links alone.
+    Off,
+    /// Remove affiliate tags from links.
+    Strip,
+    /// Replace affiliate tags with the instance's own tags, where one is configured.
+    Append,
+}
+
+/// An affiliate tag to add to links for a retailer.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct AffiliateTag {
+    /// The retailer's domain, subdomains also match.
+    pub domain: String,
+    /// The query parameter the retailer reads the tag from.
+    pub param: String,
+    /// The tag.
+    pub value: String,
+}
+
+/// A query parameter that's an affiliate tag on one retailer's links.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct SiteParam {
+    /// The retailer's domain, subdomains also match.
+    pub domain: String,
+    pub param: String,
+}
+
+/// Affiliate link configuration, read from the `affiliate` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AffiliateConfig {
+    pub mode: AffiliateMode,
+    /// Query parameters treated as affiliate tags on every link. Names like `tag` or `ref` mean
+    /// other things on most sites, so they only belong in `site_params`.
+    pub strip_params: Vec<String>,
+    /// Query parameters treated as affiliate tags on one retailer's links. The defaults cover
+    /// Amazon and eBay, setting this replaces them.
+    pub site_params: Vec<SiteParam>,
+    /// Tags added in `append` mode.
+    pub tags: Vec<AffiliateTag>,
+}
+
+impl Default for AffiliateConfig {
+    fn default() -> Self {
+        let site_params = |domains: &[&str], params: &[&str]| {
+            domains
+                .iter()
+                .flat_map(|domain| {
+                    params.iter().map(|param| SiteParam {
+                        domain: domain.to_string(),
+                        param: param.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let amazon_params = ["tag", "ref", "ref_", "linkCode", "linkId", "ascsubtag"];
+        let ebay_params = ["campid", "mkcid", "mkrid", "mkevt"];
+        Self {
+            mode: AffiliateMode::Off,
+            strip_params: ["affid", "aff_id", "irclickid"]
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            site_params: [
+                site_params(amazon::DOMAINS, &amazon_params),
+                site_params(ebay::DOMAINS, &ebay_params),
+            ]
+            .concat(),
+            tags: vec![],
+        }
+    }
+}
+
+/// Rewrites outbound item links according to the instance's affiliate policy. Links are
+/// rewritten when they're rendered, the stored URL is never changed. Available as managed state.
+pub struct AffiliatePolicy {
+    config: AffiliateConfig,
+}
+
+impl AffiliatePolicy {
+    pub fn from_config(config: AffiliateConfig) -> AffiliatePolicy {
+        AffiliatePolicy { config }
+    }
+
+    /// Returns the link to show for an item on the given list.
+    pub fn rewrite(&self, list: &List, url: &str) -> String {
+        if list.affiliate_opt_out || self.config.mode == AffiliateMode::Off {
+            return url.to_string();
+        }
+
+        let mut parsed = match Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(_) => return url.to_string(),
+        };
+
+        let tag = match self.config.mode {
+            AffiliateMode::Append => self
+                .config
+                .tags
+                .iter()
+                .find(|tag| host_matches(&parsed, &tag.domain)),
+            _ => None,
+        };
+
+        let site_params = self
+            .config
+            .site_params
+            .iter()
+            .filter(|site| host_matches(&parsed, &site.domain))
+            .map(|site| site.param.as_str())
+            .collect::<Vec<_>>();
+
+        let mut pairs = parsed
+            .query_pairs()
+            .filter(|(key, _)| {
+                !self.config.strip_params.iter().any(|p| p == key)
+                    && !site_params.iter().any(|p| p == key)
+                    && tag.is_none_or(|tag| tag.param != *key)
+            })
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+
+        if let Some(tag) = tag {
+            pairs.push((tag.param.clone(), tag.value.clone()));
+        }
+
+        if pairs.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+
+        parsed.to_string()
+    }
+
+    /// Rewrites the links of items on the given list in place.
+    pub fn rewrite_items(&self, list: &List, items: &mut [Item]) {
+        for item in items {
+            self.rewrite_item(list, item);
+        }
+    }
+
+    /// Rewrites the link of an item on the given list in place.
+    pub fn rewrite_item(&self, list: &List, item: &mut Item) {
+        if let Some(url) = &item.url {
+            item.url = Some(self.rewrite(list, url));
+        }
+    }
+}
+
+/// Reads the affiliate config and adds the `AffiliatePolicy` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).affiliate.clone();
+
+    Ok(rocket.manage(AffiliatePolicy::from_config(config)))
+}
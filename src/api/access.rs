@@ -0,0 +1,438 @@
+//! Who can call which API endpoints, and how often.
+//!
+//! Endpoints that work without logging in take an `ApiCaller`, which only rate limits. That's
+//! reading public lists, items and search, checking password strength, and making a list, which
+//! `crate::throttle` guards.
+//! Everything else takes an `ApiUser`, which answers callers who aren't logged in with a 401 and
+//! a `reason` they can act on, rather than a 404.
+//!
+//! Callers authenticate with an API token in an `Authorization: Bearer` header, see
+//! `crate::db::models::ApiToken`. A session cookie is enough for reads, but anything that changes
+//! something needs a token, so other sites can't make changes through a logged in browser.
+//!
+//! Both guards take one of the `scopes` marker types, the scope a token needs for the endpoint.
+//! Tokens can also be limited to one list, which handlers check with `allows_list`. Session
+//! cookies aren't scoped.
+//!
+//! Both also turn every caller away with a 403 while an admin has turned the API off, see
+//! `crate::features`.
+//!
+//! Inbound email is the exception, it's called by the mail provider and checks its own secret.
+
+use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+
+use crate::config::AppConfig;
+use crate::db::models::{ApiScope, ApiToken, List, User};
+use crate::features::{self, Feature};
+use crate::rate_limits::{self, RateLimiter};
+use crate::web::auth::{self, LoggedInUser, SuspendedUser};
+
+/// How long rate limits are counted over.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// API rate limits, read from the `api_limits` table in Rocket.toml. Each is a number of
+/// requests per minute, 0 turns the limit off.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ApiLimitsConfig {
+    /// Reads by each IP address that isn't logged in.
+    pub anonymous_reads_per_minute: usize,
+    /// Writes by each IP address that isn't logged in, which is only making lists and checking
+    /// password strength.
+    pub anonymous_writes_per_minute: usize,
+    /// Reads by each logged in user.
+    pub user_reads_per_minute: usize,
+    /// Writes by each logged in user.
+    pub user_writes_per_minute: usize,
+}
+
+impl Default for ApiLimitsConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_reads_per_minute: 60,
+            anonymous_writes_per_minute: 10,
+            user_reads_per_minute: 600,
+            user_writes_per_minute: 120,
+        }
+    }
+}
+
+/// What a request does, for rate limiting. `GET` and `HEAD` requests read, everything else
+/// writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Read,
+    Write,
+}
+
+impl Scope {
+    fn of(request: &Request<'_>) -> Scope {
+        match request.method() {
+            Method::Get | Method::Head => Scope::Read,
+            _ => Scope::Write,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+        }
+    }
+}
+
+/// Who a rate limit is counted against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CallerKey {
+    Anonymous(Option<IpAddr>),
+    User(i64),
+}
+
+/// The API's limits per caller and scope. Available as managed state.
+///
+/// Requests are counted by the `RateLimiter`, along with logins, see `crate::rate_limits`.
+pub struct ApiLimiter {
+    config: ApiLimitsConfig,
+}
+
+impl ApiLimiter {
+    pub fn from_config(config: ApiLimitsConfig) -> ApiLimiter {
+        ApiLimiter { config }
+    }
+
+    /// Counts a request, or returns how many seconds to wait if the caller is over their limit.
+    fn hit(&self, limiter: &RateLimiter, key: CallerKey, scope: Scope) -> Result<(), u64> {
+        let what = format!("api:{}", scope.as_str());
+        let (key, limit) = match (key, scope) {
+            (CallerKey::Anonymous(ip), Scope::Read) => (
+                rate_limits::ip_key(&what, ip),
+                self.config.anonymous_reads_per_minute,
+            ),
+            (CallerKey::Anonymous(ip), Scope::Write) => (
+                rate_limits::ip_key(&what, ip),
+                self.config.anonymous_writes_per_minute,
+            ),
+            (CallerKey::User(id), Scope::Read) => (
+                format!("{}:user:{}", what, id),
+                self.config.user_reads_per_minute,
+            ),
+            (CallerKey::User(id), Scope::Write) => (
+                format!("{}:user:{}", what, id),
+                self.config.user_writes_per_minute,
+            ),
+        };
+        limiter.hit(&key, limit, WINDOW)
+    }
+}
+
+/// Why a request was turned away, sent back so clients can tell what to do.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum DenyReason {
+    /// The endpoint needs a logged in user.
+    LoginRequired,
+    /// The user is logged in, but their account is suspended.
+    AccountSuspended,
+    /// The endpoint changes something, which needs an API token rather than a session cookie.
+    TokenRequired,
+    /// The API token is unknown, or was revoked.
+    InvalidToken,
+    /// The API token doesn't have the scope the endpoint needs.
+    InsufficientScope,
+    /// The caller made too many requests, see `retry_after_secs`.
+    RateLimited,
+    /// The API is turned off on this instance.
+    ApiDisabled,
+}
+
+impl DenyReason {
+    fn message(&self) -> &'static str {
+        match self {
+            DenyReason::LoginRequired => "You need to log in to do that",
+            DenyReason::AccountSuspended => "Your account is suspended",
+            DenyReason::TokenRequired => "Changes through the API need an API token",
+            DenyReason::InvalidToken => "That API token isn't valid, it may have been revoked",
+            DenyReason::InsufficientScope => "Your API token isn't allowed to do that",
+            DenyReason::RateLimited => "Too many requests, slow down",
+            DenyReason::ApiDisabled => "The API is turned off on this site",
+        }
+    }
+}
+
+/// The body of 401, 403 and 429 responses.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiDenial {
+    pub reason: DenyReason,
+    pub message: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Turns the request away, leaving the reason for the catcher.
+fn deny<T>(
+    request: &Request<'_>,
+    reason: DenyReason,
+    retry_after_secs: Option<u64>,
+) -> Outcome<T, DenyReason> {
+    let status = match reason {
+        DenyReason::RateLimited => Status::TooManyRequests,
+        DenyReason::InsufficientScope | DenyReason::ApiDisabled => Status::Forbidden,
+        _ => Status::Unauthorized,
+    };
+    request.local_cache(|| {
+        Some(ApiDenial {
+            reason,
+            message: reason.message(),
+            retry_after_secs,
+        })
+    });
+    Outcome::Failure((status, reason))
+}
+
+/// Counts the request against the caller's limit.
+fn check_limit<T>(request: &Request<'_>, key: CallerKey) -> Result<(), Outcome<T, DenyReason>> {
+    let rocket = request.rocket();
+    let limiters = (rocket.state::<ApiLimiter>(), rocket.state::<RateLimiter>());
+    let (api_limiter, limiter) = match limiters {
+        (Some(api_limiter), Some(limiter)) => (api_limiter, limiter),
+        _ => return Ok(()),
+    };
+    api_limiter
+        .hit(limiter, key, Scope::of(request))
+        .map_err(|wait| deny(request, DenyReason::RateLimited, Some(wait)))
+}
+
+/// An API scope a route needs, see `ApiCaller` and `ApiUser`.
+pub trait RequiredScope: Send + Sync + 'static {
+    /// The scope a token needs, or `None` if any token will do.
+    const SCOPE: Option<ApiScope>;
+}
+
+/// Marker types for the `ApiCaller` and `ApiUser` guards, one per `ApiScope`.
+pub mod scopes {
+    use super::RequiredScope;
+    use crate::db::models::ApiScope;
+
+    /// For endpoints that don't touch the caller's data.
+    pub struct Unscoped;
+    pub struct ReadLists;
+    pub struct WriteLists;
+    pub struct ReadItems;
+    pub struct WriteItems;
+    pub struct Admin;
+
+    impl RequiredScope for Unscoped {
+        const SCOPE: Option<ApiScope> = None;
+    }
+    impl RequiredScope for ReadLists {
+        const SCOPE: Option<ApiScope> = Some(ApiScope::ReadLists);
+    }
+    impl RequiredScope for WriteLists {
+        const SCOPE: Option<ApiScope> = Some(ApiScope::WriteLists);
+    }
+    impl RequiredScope for ReadItems {
+        const SCOPE: Option<ApiScope> = Some(ApiScope::ReadItems);
+    }
+    impl RequiredScope for WriteItems {
+        const SCOPE: Option<ApiScope> = Some(ApiScope::WriteItems);
+    }
+    impl RequiredScope for Admin {
+        const SCOPE: Option<ApiScope> = Some(ApiScope::Admin);
+    }
+}
+
+/// An authenticated caller, with the token they used if they used one.
+struct Caller<'r> {
+    user: &'r User,
+    token: Option<&'r ApiToken>,
+}
+
+/// Returns the caller, or `None` if they didn't authenticate.
+///
+/// Callers with a token are always authenticated by it, and the token needs scope `S`. Without
+/// one, a session cookie only counts for reads, and suspended users are treated as if they
+/// weren't logged in.
+async fn authenticate<'r, S: RequiredScope>(
+    request: &'r Request<'_>,
+) -> Result<Option<Caller<'r>>, DenyReason> {
+    if auth::bearer_token(request).is_some() {
+        return match auth::token_user(request).await {
+            Some(caller) if caller.user.is_suspended() => Err(DenyReason::AccountSuspended),
+            Some(caller) if !S::SCOPE.is_none_or(|scope| caller.token.has_scope(scope)) => {
+                Err(DenyReason::InsufficientScope)
+            }
+            Some(caller) => Ok(Some(Caller {
+                user: &caller.user,
+                token: Some(&caller.token),
+            })),
+            None => Err(DenyReason::InvalidToken),
+        };
+    }
+
+    match request.guard::<&LoggedInUser>().await {
+        Outcome::Success(_) if Scope::of(request) == Scope::Write => Err(DenyReason::TokenRequired),
+        Outcome::Success(caller) => Ok(Some(Caller {
+            user: &caller.user,
+            token: None,
+        })),
+        _ => Ok(None),
+    }
+}
+
+/// Anyone calling a public endpoint, logged in or not. Callers using a token need scope `S`.
+/// Fails with a 401 when their credentials don't work, a 403 when their token doesn't have the
+/// scope, or a 429 when they're over their rate limit.
+pub struct ApiCaller<'r, S> {
+    /// The caller's user, or `None` if they aren't logged in.
+    pub user: Option<&'r User>,
+    token: Option<&'r ApiToken>,
+    scope: PhantomData<S>,
+}
+
+impl<S> ApiCaller<'_, S> {
+    /// Whether the caller can reach the list. Tokens can be limited to one list.
+    pub fn allows_list(&self, list: &List) -> bool {
+        self.token.is_none_or(|token| token.allows_list(list.id))
+    }
+
+    /// The only list the caller can reach, if their token is limited to one.
+    pub fn only_list(&self) -> Option<i64> {
+        self.token.and_then(|token| token.list_id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for ApiCaller<'r, S> {
+    type Error = DenyReason;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if !features::is_enabled(request, Feature::Api) {
+            return deny(request, DenyReason::ApiDisabled, None);
+        }
+        let caller = match authenticate::<S>(request).await {
+            Ok(caller) => caller,
+            Err(reason) => return deny(request, reason, None),
+        };
+        let key = match &caller {
+            Some(caller) => CallerKey::User(caller.user.id),
+            None => CallerKey::Anonymous(request.client_ip()),
+        };
+        if let Err(outcome) = check_limit(request, key) {
+            return outcome;
+        }
+
+        Outcome::Success(ApiCaller {
+            user: caller.as_ref().map(|caller| caller.user),
+            token: caller.and_then(|caller| caller.token),
+            scope: PhantomData,
+        })
+    }
+}
+
+/// A logged in user in good standing calling a protected endpoint. Callers using a token need
+/// scope `S`. Fails with a 401 when there isn't one, a 403 when their token doesn't have the
+/// scope, or a 429 when they're over their rate limit.
+pub struct ApiUser<'r, S> {
+    pub user: &'r User,
+    token: Option<&'r ApiToken>,
+    scope: PhantomData<S>,
+}
+
+impl<S> ApiUser<'_, S> {
+    /// Whether the caller can reach the list. Tokens can be limited to one list.
+    pub fn allows_list(&self, list: &List) -> bool {
+        self.token.is_none_or(|token| token.allows_list(list.id))
+    }
+
+    /// The only list the caller can reach, if their token is limited to one.
+    pub fn only_list(&self) -> Option<i64> {
+        self.token.and_then(|token| token.list_id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for ApiUser<'r, S> {
+    type Error = DenyReason;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if !features::is_enabled(request, Feature::Api) {
+            return deny(request, DenyReason::ApiDisabled, None);
+        }
+        let caller = match authenticate::<S>(request).await {
+            Ok(Some(caller)) => caller,
+            Ok(None) => {
+                let suspended = request.guard::<SuspendedUser<'r>>().await.is_success();
+                let reason = if suspended {
+                    DenyReason::AccountSuspended
+                } else if Scope::of(request) == Scope::Write {
+                    DenyReason::TokenRequired
+                } else {
+                    DenyReason::LoginRequired
+                };
+                return deny(request, reason, None);
+            }
+            Err(reason) => return deny(request, reason, None),
+        };
+        if let Err(outcome) = check_limit(request, CallerKey::User(caller.user.id)) {
+            return outcome;
+        }
+
+        Outcome::Success(ApiUser {
+            user: caller.user,
+            token: caller.token,
+            scope: PhantomData,
+        })
+    }
+}
+
+fn denial(request: &Request<'_>, fallback: DenyReason) -> ApiDenial {
+    request
+        .local_cache(|| None::<ApiDenial>)
+        .clone()
+        .unwrap_or(ApiDenial {
+            reason: fallback,
+            message: fallback.message(),
+            retry_after_secs: None,
+        })
+}
+
+#[derive(Responder)]
+#[response(status = 429)]
+pub struct TooManyRequests {
+    body: Json<ApiDenial>,
+    retry_after: Header<'static>,
+}
+
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> Json<ApiDenial> {
+    Json(denial(request, DenyReason::LoginRequired))
+}
+
+#[catch(403)]
+pub fn forbidden(request: &Request<'_>) -> Json<ApiDenial> {
+    Json(denial(request, DenyReason::InsufficientScope))
+}
+
+#[catch(429)]
+pub fn too_many_requests(request: &Request<'_>) -> TooManyRequests {
+    let denial = denial(request, DenyReason::RateLimited);
+    let retry_after = denial.retry_after_secs.unwrap_or(WINDOW.as_secs());
+    TooManyRequests {
+        body: Json(denial),
+        retry_after: Header::new("Retry-After", retry_after.to_string()),
+    }
+}
+
+/// Reads the API limits config and adds the `ApiLimiter` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).api_limits.clone();
+
+    Ok(rocket.manage(ApiLimiter::from_config(config)))
+}
@@ -1,24 +1,33 @@
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
 use rocket::serde::json::Json;
 use rocket::serde::Serialize;
+use utoipa::ToSchema;
 use validator::ValidationErrors;
 
 use crate::db::DataError;
 
+pub mod access;
 pub mod v1;
 
 #[derive(Responder)]
 pub enum ApiError {
-    // #[response(status = 400)]
-    // Unauthorized(Json<ApiGenericError>),
+    #[response(status = 400)]
+    BadRequest(Json<ApiGenericError>),
     #[response(status = 422)]
     Invalid(Json<ValidationErrors>),
     #[response(status = 404)]
     NotFound(Json<ApiGenericError>),
+    #[response(status = 409)]
+    Conflict(Json<ApiGenericError>),
+    #[response(status = 413)]
+    TooLarge(Json<ApiGenericError>),
     #[response(status = 500)]
     Internal(Json<ApiGenericError>),
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ApiGenericError {
     pub message: String,
@@ -48,3 +57,31 @@ impl From<DataError> for ApiError {
         }
     }
 }
+
+/// A response with a `Warning` header if the request used something deprecated, so clients that
+/// log warnings find out before it's removed. Uses are logged here too.
+pub struct Warned<R> {
+    pub inner: R,
+    pub warning: Option<&'static str>,
+}
+
+impl<R> Warned<R> {
+    /// Warns with `message` if `used` is true.
+    pub fn deprecated(inner: R, used: bool, message: &'static str) -> Warned<R> {
+        Warned {
+            inner,
+            warning: used.then_some(message),
+        }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Warned<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = self.inner.respond_to(request)?;
+        if let Some(warning) = self.warning {
+            warn!("{} {}: {}", request.method(), request.uri(), warning);
+            response.set_header(Header::new("Warning", format!("299 - \"{}\"", warning)));
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,101 @@
+//! REST hooks, the way Zapier and similar services get events pushed to them: they subscribe a
+//! target URL to a kind of event when a user turns an automation on, and unsubscribe it when
+//! it's turned off. Events are posted like an automation webhook's, see
+//! `crate::notify::automation_payload`, and answering one with a 410 also unsubscribes. Posts are
+//! signed with the subscription's secret, which is only returned when subscribing, see
+//! `crate::notify::sign`.
+//!
+//! The polling endpoints in `super::triggers` give samples of the same data.
+
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+
+use crate::api::access::scopes::ReadItems;
+use crate::api::access::ApiUser;
+use crate::api::{ApiError, ApiGenericError};
+use crate::db::models::{HookSubscription, List};
+use crate::db::WishlistDb;
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Subscribe<'r> {
+    pub target_url: &'r str,
+    /// See `DomainEvent::kind`, e.g. "item_added".
+    pub event: &'r str,
+    /// Only send events on this list. Defaults to all of the user's lists, or the token's list if
+    /// it's limited to one.
+    #[serde(default)]
+    pub list_key: Option<&'r str>,
+}
+
+/// A subscription as it's returned, with the ID to unsubscribe with.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Subscription {
+    pub id: i64,
+    pub target_url: String,
+    pub event: String,
+    pub list_key: Option<String>,
+    /// The key posts are signed with.
+    pub secret: String,
+}
+
+fn not_found(message: &str) -> ApiError {
+    ApiError::NotFound(Json(ApiGenericError {
+        message: message.to_string(),
+    }))
+}
+
+#[post("/api/v1/hooks", data = "<subscribe>")]
+pub async fn subscribe(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, ReadItems>,
+    subscribe: Json<Subscribe<'_>>,
+) -> Result<Created<Json<Subscription>>, ApiError> {
+    let user_id = user.user.id;
+    let list = match subscribe.list_key {
+        Some(key) => Some(
+            List::find_by_key(&mut db, key)
+                .await?
+                .filter(|list| list.is_owned_by(Some(user_id)) && user.allows_list(list))
+                .ok_or_else(|| not_found("List not found"))?,
+        ),
+        None => None,
+    };
+    let list_id = list.as_ref().map(|list| list.id).or(user.only_list());
+
+    let subscription = HookSubscription::create(
+        &mut db,
+        user_id,
+        list_id,
+        subscribe.event,
+        subscribe.target_url,
+    )
+    .await?;
+
+    let id = subscription.id;
+    Ok(
+        Created::new(uri!(unsubscribe(id)).to_string()).body(Json(Subscription {
+            id,
+            target_url: subscription.target_url,
+            event: subscription.event,
+            list_key: list.map(|list| list.key),
+            secret: subscription.secret,
+        })),
+    )
+}
+
+#[delete("/api/v1/hooks/<id>")]
+pub async fn unsubscribe(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, ReadItems>,
+    id: i64,
+) -> Result<NoContent, ApiError> {
+    if !HookSubscription::destroy_by_user(&mut db, user.user.id, id).await? {
+        return Err(not_found("Subscription not found"));
+    }
+
+    Ok(NoContent)
+}
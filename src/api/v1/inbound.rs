@@ -0,0 +1,178 @@
+use rocket::data::{Data, ToByteUnit};
+use rocket::response::status::Created;
+use rocket::serde::json::{Json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+
+use crate::api::{ApiError, ApiGenericError};
+use crate::db::models::{EmailSuppression, InboundAddress, Item, Upload};
+use crate::db::{Transaction, WishlistDb};
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::images::{ImageScanner, UploadStore};
+use crate::inbound::{InboundConfig, InboundHook, InboundMessage};
+use crate::mail::bounces::BounceHook;
+use crate::notify::Dispatcher;
+use crate::quotas::Quotas;
+use crate::web;
+
+/// Used when an email has no subject.
+static UNTITLED: &str = "Emailed item";
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InboundResult {
+    pub item: Item,
+    pub list_key: String,
+    /// Tokens of the uploads made from the email's image attachments.
+    pub images: Vec<String>,
+    /// Attachments that weren't kept, and why.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BounceResult {
+    /// The addresses that won't be emailed anymore.
+    pub suppressed: Vec<String>,
+}
+
+/// Returns the first `max` characters of the text.
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// Adds an item from an email. Called by the mail server's hook with the raw message as the body.
+///
+/// The email's subject becomes the title, its text the description, and image attachments are
+/// stored as the user's uploads.
+#[post("/api/v1/inbound/email", data = "<raw>")]
+pub async fn email(
+    mut db: Connection<WishlistDb>,
+    _hook: InboundHook,
+    config: &State<InboundConfig>,
+    uploads: &State<UploadStore>,
+    scanner: &State<ImageScanner>,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    raw: Data<'_>,
+) -> Result<Created<Json<InboundResult>>, ApiError> {
+    let raw = raw
+        .open(config.max_message_size.bytes())
+        .into_bytes()
+        .await
+        .map_err(|e| ApiError::Internal(Json(ApiGenericError { message: e.to_string() })))?;
+    if !raw.is_complete() {
+        return Err(ApiError::Conflict(Json(ApiGenericError {
+            message: format!("Emails must be at most {} bytes", config.max_message_size),
+        })));
+    }
+
+    let message = InboundMessage::parse(&raw).ok_or_else(|| {
+        ApiError::Conflict(Json(ApiGenericError {
+            message: "Not an email".to_string(),
+        }))
+    })?;
+
+    let mut address = None;
+    for token in message
+        .recipients
+        .iter()
+        .filter_map(|recipient| config.token_for(recipient))
+    {
+        address = InboundAddress::find_by_token(&mut db, token).await?;
+        if address.is_some() {
+            break;
+        }
+    }
+    let address = address.ok_or_else(|| {
+        ApiError::NotFound(Json(ApiGenericError {
+            message: "No user has that address".to_string(),
+        }))
+    })?;
+
+    let list = web::quick::default_list(&mut db, address.user_id).await?;
+    if let Some(message) = quotas.check_item(&mut db, &list).await? {
+        return Err(ApiError::Conflict(Json(ApiGenericError { message })));
+    }
+    let title = match message.subject.as_str() {
+        "" => UNTITLED.to_string(),
+        subject => truncate(subject, 256),
+    };
+    let mut tx = Transaction::begin(db).await?;
+    let item = Item::create(
+        &mut tx,
+        list.id,
+        &title,
+        &truncate(&message.body, 4096),
+        None,
+        Some(address.user_id),
+    )
+    .await?;
+    let event = DomainEvent::ItemAdded {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&item),
+        by: Some(address.user_id),
+    };
+    dispatcher.dispatch(&mut tx, event).await?;
+    let mut db = dispatcher.commit(tx).await?;
+
+    let mut images = Vec::new();
+    let mut skipped = Vec::new();
+    for (n, data) in message.attachments.into_iter().enumerate() {
+        if data.len() as u64 > uploads.max_upload_size {
+            skipped.push(format!("Attachment {}: too large", n + 1));
+            continue;
+        }
+
+        let size = data.len() as i64;
+        if let Some(message) = quotas.check_upload(&mut db, address.user_id, size).await? {
+            skipped.push(format!("Attachment {}: {}", n + 1, message));
+            continue;
+        }
+        let mut upload = Upload::create(&mut db, address.user_id, size).await?;
+        match uploads.store(&upload.token, data, scanner).await {
+            Ok(content_type) => {
+                let upload = upload.set_received(&mut db, size).await?;
+                let upload = upload.complete(&mut db, content_type).await?;
+                images.push(upload.token);
+            }
+            Err(e) => {
+                upload.destroy(&mut db).await?;
+                skipped.push(format!("Attachment {}: {}", n + 1, e));
+            }
+        }
+    }
+
+    let location = uri!(crate::web::items::show(&list.key, item.id)).to_string();
+    Ok(Created::new(location).body(Json(InboundResult {
+        item,
+        list_key: list.key,
+        images,
+        skipped,
+    })))
+}
+
+/// Records bounces and spam complaints from the mail provider's webhook, so the addresses aren't
+/// emailed anymore. See `crate::mail::bounces::parse` for the notifications it understands.
+#[post("/api/v1/inbound/bounces", format = "json", data = "<notification>")]
+pub async fn bounces(
+    mut db: Connection<WishlistDb>,
+    _hook: BounceHook,
+    notification: Json<Value>,
+) -> Result<Json<BounceResult>, ApiError> {
+    let mut suppressed = Vec::new();
+    for report in crate::mail::bounces::parse(&notification) {
+        info!("Suppressing {} after a {}", report.email, report.reason);
+        EmailSuppression::record(
+            &mut db,
+            &report.email,
+            report.reason,
+            report.detail.as_deref(),
+        )
+        .await?;
+        suppressed.push(report.email);
+    }
+
+    Ok(Json(BounceResult { suppressed }))
+}
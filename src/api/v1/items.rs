@@ -0,0 +1,694 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::affiliate::AffiliatePolicy;
+use crate::api::access::scopes::{ReadItems, WriteItems};
+use crate::api::access::{ApiCaller, ApiUser};
+use crate::api::{ApiError, ApiGenericError};
+use crate::db::models::{Claim, Item, ItemFilter, ItemKind, ItemOrder, ItemPriority, List, Tag};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::list_import::{self, ListImportError, ListImportReport, MAX_FILE_SIZE};
+use crate::notify::Dispatcher;
+use crate::pagination::{Page, Paged, Pagination, SortDirection};
+use crate::plugins::PluginRegistry;
+use crate::quotas::Quotas;
+use crate::sources::Price;
+use crate::surprise::{self, Access};
+use crate::util::SiteUrl;
+use crate::views::{ItemLinks, ItemView};
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateItem<'r> {
+    pub title: &'r str,
+    pub description: &'r str,
+    #[serde(default)]
+    pub url: &'r str,
+    /// See `ItemKind::as_str`.
+    pub kind: &'r str,
+    /// The gift card's value or the cash fund's goal, e.g. "$50".
+    #[serde(default)]
+    pub amount: &'r str,
+    /// What the item costs, e.g. "$24.99" or "12.50 EUR". The low end of the range if
+    /// `price_max` is given.
+    #[serde(default)]
+    pub price: &'r str,
+    /// The high end of the item's price range, for items without an exact price. In the same
+    /// currency as `price` unless it ends in a currency code.
+    #[serde(default)]
+    pub price_max: &'r str,
+    /// How many are wanted. Defaults to one.
+    #[serde(default)]
+    pub quantity: Option<i64>,
+    /// See `ItemPriority::as_str`. Defaults to "normal".
+    #[serde(default)]
+    pub priority: &'r str,
+    /// Comma separated, like "books, under $50". See `Tag::parse_names`.
+    #[serde(default)]
+    pub tags: Option<&'r str>,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EditItem<'r> {
+    pub title: &'r str,
+    pub description: &'r str,
+    #[serde(default)]
+    pub url: &'r str,
+    /// See `ItemKind::as_str`.
+    pub kind: &'r str,
+    /// The gift card's value or the cash fund's goal, e.g. "$50".
+    #[serde(default)]
+    pub amount: &'r str,
+    /// What the item costs, e.g. "$24.99" or "12.50 EUR". The low end of the range if
+    /// `price_max` is given.
+    #[serde(default)]
+    pub price: &'r str,
+    /// The high end of the item's price range, for items without an exact price. In the same
+    /// currency as `price` unless it ends in a currency code.
+    #[serde(default)]
+    pub price_max: &'r str,
+    /// How many are wanted. Defaults to one.
+    #[serde(default)]
+    pub quantity: Option<i64>,
+    /// See `ItemPriority::as_str`. Defaults to "normal".
+    #[serde(default)]
+    pub priority: &'r str,
+    /// Comma separated, like "books, under $50". See `Tag::parse_names`. The item's tags are
+    /// left alone if it's missing.
+    #[serde(default)]
+    pub tags: Option<&'r str>,
+}
+
+/// Parses the kind fields of the item forms. The amount is optional, and ignored for kinds that
+/// don't have one.
+pub fn parse_kind(kind: &str, amount: &str) -> Result<(ItemKind, Option<Price>), DataError> {
+    let kind =
+        ItemKind::parse(kind).ok_or_else(|| DataError::Other("Unknown item kind".to_string()))?;
+
+    let amount = amount.trim();
+    if !kind.has_amount() || amount.is_empty() {
+        return Ok((kind, None));
+    }
+
+    let price = Price::parse(amount, None)
+        .ok_or_else(|| DataError::Other("Amount must be a number".to_string()))?;
+    Ok((kind, Some(price)))
+}
+
+/// An item's price, how many are wanted and how much, see `parse_details`.
+pub struct ItemDetails {
+    pub price: Option<Price>,
+    /// The high end of the price range, in the same currency as `price`.
+    pub price_max: Option<Price>,
+    pub quantity: i64,
+    pub priority: ItemPriority,
+}
+
+impl ItemDetails {
+    /// Sets the details on the item, without saving it.
+    pub fn apply(&self, item: &mut Item) {
+        item.set_details(
+            self.price.as_ref().map(|price| price.amount_cents),
+            self.price_max.as_ref().map(|price| price.amount_cents),
+            self.price.as_ref().map(|price| price.currency.as_str()),
+            self.quantity,
+            self.priority,
+        );
+    }
+}
+
+/// The currency code a price ends in, like the "EUR" in "12.50 EUR".
+fn currency_code(price: &str) -> Option<&str> {
+    price
+        .split_whitespace()
+        .last()
+        .filter(|code| code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Parses the price, quantity and priority fields of the item forms. All of them are optional.
+/// Prices can end in a currency code, like "12.50 EUR", otherwise the currency comes from the
+/// symbol. The high end of a price range is in the same currency as the price unless it has its
+/// own code. Problems are reported as a validation error on the field at fault.
+pub fn parse_details(
+    price: &str,
+    price_max: &str,
+    quantity: Option<i64>,
+    priority: &str,
+) -> Result<ItemDetails, DataError> {
+    let mut errors = ValidationErrors::new();
+
+    let price = price.trim();
+    let parsed_price = Price::parse(price, currency_code(price));
+    if parsed_price.is_none() && !price.is_empty() {
+        let mut err = ValidationError::new("price");
+        err.message = Some(Cow::from("Price must be a number"));
+        errors.add("price", err);
+    }
+
+    let price_max = price_max.trim();
+    let parsed_price_max = match (&parsed_price, price_max) {
+        (_, "") => None,
+        (Some(low), high) => {
+            let currency = currency_code(high).unwrap_or(low.currency.as_str());
+            Price::parse(high, Some(currency))
+        }
+        (None, high) => Price::parse(high, currency_code(high)),
+    };
+    let range_error = match (&parsed_price, &parsed_price_max) {
+        _ if price_max.is_empty() => None,
+        (_, None) => Some("Highest price must be a number"),
+        (None, Some(_)) if price.is_empty() => Some("Highest price needs a lowest price"),
+        (Some(low), Some(high)) if high.currency != low.currency => {
+            Some("Highest price must be in the same currency as the price")
+        }
+        (Some(low), Some(high)) if high.amount_cents <= low.amount_cents => {
+            Some("Highest price must be more than the price")
+        }
+        _ => None,
+    };
+    if let Some(message) = range_error {
+        let mut err = ValidationError::new("price_max");
+        err.message = Some(Cow::from(message));
+        errors.add("price_max", err);
+    }
+
+    let priority = priority.trim();
+    let parsed_priority = match priority {
+        "" => Some(ItemPriority::Normal),
+        priority => ItemPriority::parse(priority),
+    };
+    if parsed_priority.is_none() {
+        let mut err = ValidationError::new("priority");
+        err.message = Some(Cow::from("Unknown priority"));
+        errors.add("priority", err);
+    }
+
+    match parsed_priority {
+        Some(priority) if errors.is_empty() => Ok(ItemDetails {
+            price: parsed_price,
+            price_max: parsed_price_max,
+            quantity: quantity.unwrap_or(1),
+            priority,
+        }),
+        _ => Err(DataError::Validation(errors)),
+    }
+}
+
+/// Treats an empty form field as no URL.
+pub fn optional_url(url: &str) -> Option<&str> {
+    Some(url.trim()).filter(|url| !url.is_empty())
+}
+
+/// Like `parse_kind`, but reports problems as a validation error on the field at fault.
+fn parse_kind_field(kind: &str, amount: &str) -> Result<(ItemKind, Option<Price>), ApiError> {
+    parse_kind(kind, amount).map_err(|e| {
+        let field = if ItemKind::parse(kind).is_none() {
+            "kind"
+        } else {
+            "amount"
+        };
+        let mut err = ValidationError::new(field);
+        err.message = Some(Cow::from(match e {
+            DataError::Other(message) => message,
+            e => e.to_string(),
+        }));
+        let mut errors = ValidationErrors::new();
+        errors.add(field, err);
+        ApiError::Invalid(Json(errors))
+    })
+}
+
+/// An item with a link to its page and the names of its tags, as the API returns it.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TaggedItem {
+    #[serde(flatten)]
+    pub item: ItemView,
+    pub tags: Vec<String>,
+}
+
+impl TaggedItem {
+    /// Looks up the item's tags.
+    async fn load(
+        db: &mut Connection<WishlistDb>,
+        links: &ItemLinks,
+        item: Item,
+    ) -> Result<TaggedItem, ApiError> {
+        let tags = Tag::all_by_item(db, item.id)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        Ok(TaggedItem {
+            item: ItemView::new(links, item),
+            tags,
+        })
+    }
+
+    /// Gives each of the items their tags, from `Tag::names_by_list`.
+    fn all(
+        links: &ItemLinks,
+        items: Vec<Item>,
+        mut tags: HashMap<i64, Vec<String>>,
+    ) -> Vec<TaggedItem> {
+        ItemView::all(links, items)
+            .into_iter()
+            .map(|item| TaggedItem {
+                tags: tags.remove(&item.item.id).unwrap_or_default(),
+                item,
+            })
+            .collect()
+    }
+}
+
+/// Whether an item is claimed, for callers allowed to see gifting activity.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ClaimStatus {
+    pub claimed: bool,
+    pub by_you: bool,
+}
+
+fn not_found(message: &str) -> ApiError {
+    ApiError::NotFound(Json(ApiGenericError {
+        message: message.to_string(),
+    }))
+}
+
+/// Returns the list if the user can see it, and their token can reach it if it's limited to one
+/// list.
+async fn visible_list(
+    db: &mut Connection<WishlistDb>,
+    user_id: Option<i64>,
+    only_list: Option<i64>,
+    list_key: &str,
+) -> Result<List, ApiError> {
+    List::find_by_key(db, list_key)
+        .await?
+        .filter(|list| list.is_visible_to(user_id))
+        .filter(|list| only_list.is_none_or(|id| id == list.id))
+        .ok_or_else(|| not_found("List not found"))
+}
+
+/// Returns the list if the user owns it, so they can change its items, and their token can reach
+/// it. Other people's lists are a 404 like lists they can't see.
+async fn owned_list(
+    db: &mut Connection<WishlistDb>,
+    user_id: i64,
+    only_list: Option<i64>,
+    list_key: &str,
+) -> Result<List, ApiError> {
+    let list = visible_list(db, Some(user_id), only_list, list_key).await?;
+    if !list.is_owned_by(Some(user_id)) {
+        return Err(not_found("List not found"));
+    }
+    Ok(list)
+}
+
+/// Returns the item if it's on the list.
+async fn find_item(
+    db: &mut Connection<WishlistDb>,
+    list: &List,
+    id: i64,
+) -> Result<Item, ApiError> {
+    Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or_else(|| not_found("Item not found"))
+}
+
+/// Returns the item if the user is allowed the given access to gifting activity on its list. See
+/// `crate::surprise`.
+async fn gifting_item<S>(
+    db: &mut Connection<WishlistDb>,
+    user: &ApiUser<'_, S>,
+    list_key: &str,
+    id: i64,
+    access: Access,
+) -> Result<(List, Item), ApiError> {
+    let user_id = user.user.id;
+    let list = visible_list(db, Some(user_id), user.only_list(), list_key).await?;
+    let list = surprise::gifting_list(Some(list), user_id, access)
+        .ok_or_else(|| not_found("List not found"))?;
+    let item = find_item(db, &list, id).await?;
+    Ok((list, item))
+}
+
+/// Returns the list's items, or only the ones with `tag` if it's given, or whose title or
+/// description contains `q`. They're in the list's order unless `sort` is "price", "title" or
+/// "created_at" (see `ItemOrder`), and `order` is "asc" or "desc". Unknown values are ignored.
+#[get("/api/v1/lists/<list_key>/items?<tag>&<q>&<sort>&<order>&<page..>")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    affiliate: &State<AffiliatePolicy>,
+    site: &State<SiteUrl>,
+    caller: ApiCaller<'_, ReadItems>,
+    list_key: &str,
+    tag: Option<&str>,
+    q: Option<&str>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    page: Option<Page>,
+) -> Result<Paged<TaggedItem>, ApiError> {
+    let page = page.unwrap_or_default();
+    let user_id = caller.user.map(|u| u.id);
+    let list = visible_list(&mut db, user_id, caller.only_list(), list_key).await?;
+
+    let tag = tag.map(Tag::normalize).filter(|tag| !tag.is_empty());
+    let filter = ItemFilter {
+        tag: tag.as_deref(),
+        search: q.map(str::trim).filter(|q| !q.is_empty()),
+        order: sort.and_then(ItemOrder::parse).unwrap_or(ItemOrder::Position),
+        direction: order.and_then(SortDirection::parse).unwrap_or_default(),
+    };
+    let total = Item::count_filtered(&mut db, list.id, &filter).await?;
+    let mut items = Item::page_filtered(&mut db, list.id, &filter, &page).await?;
+
+    let sort = Some(filter.order.as_str()).filter(|_| filter.order != ItemOrder::Position);
+    let order = Some(filter.direction.as_str()).filter(|_| filter.direction != SortDirection::Asc);
+    let pagination = Pagination::new(&page, total)
+        .with_filter("tag", tag.as_deref())
+        .with_filter("q", filter.search)
+        .with_filter("sort", sort)
+        .with_filter("order", order);
+    affiliate.rewrite_items(&list, &mut items);
+
+    let tags = Tag::names_by_list(&mut db, list.id).await?;
+    let items = TaggedItem::all(&ItemLinks::new(Some(site), &list), items, tags);
+
+    Ok(Paged { items, pagination })
+}
+
+#[get("/api/v1/lists/<list_key>/items/<id>")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    affiliate: &State<AffiliatePolicy>,
+    site: &State<SiteUrl>,
+    caller: ApiCaller<'_, ReadItems>,
+    list_key: &str,
+    id: i64,
+) -> Result<Json<TaggedItem>, ApiError> {
+    let user_id = caller.user.map(|u| u.id);
+    let list = visible_list(&mut db, user_id, caller.only_list(), list_key).await?;
+
+    let mut item = find_item(&mut db, &list, id).await?;
+    affiliate.rewrite_item(&list, &mut item);
+
+    let links = ItemLinks::new(Some(site), &list);
+    Ok(Json(TaggedItem::load(&mut db, &links, item).await?))
+}
+
+#[post("/api/v1/lists/<list_key>/items", data = "<item>")]
+pub async fn create(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    item: Json<CreateItem<'_>>,
+) -> Result<Created<Json<TaggedItem>>, ApiError> {
+    let list = owned_list(&mut db, user.user.id, user.only_list(), list_key).await?;
+
+    if let Some(message) = quotas.check_item(&mut db, &list).await? {
+        return Err(ApiError::Conflict(Json(ApiGenericError { message })));
+    }
+
+    let (kind, amount) = parse_kind_field(item.kind, item.amount)?;
+    let details = parse_details(item.price, item.price_max, item.quantity, item.priority)?;
+    let tags = Tag::parse_names(item.tags.unwrap_or_default())?;
+    let mut new_item = Item::new(
+        list.id,
+        item.title.to_string(),
+        item.description.to_string(),
+        optional_url(item.url).map(|url| url.to_string()),
+    );
+    new_item.set_kind(
+        kind,
+        amount.as_ref().map(|price| price.amount_cents),
+        amount.as_ref().map(|price| price.currency.as_str()),
+    );
+    details.apply(&mut new_item);
+    let mut tx = Transaction::begin(db).await?;
+    let new_item = new_item.save(&mut tx, Some(user.user.id)).await?;
+    Tag::set_for_item(&mut tx, new_item.id, &tags).await?;
+
+    let event = DomainEvent::ItemAdded {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&new_item),
+        by: Some(user.user.id),
+    };
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
+
+    let location = uri!(show(&list.key, new_item.id)).to_string();
+    let links = ItemLinks::new(Some(site), &list);
+    Ok(Created::new(location).body(Json(TaggedItem {
+        item: ItemView::new(&links, new_item),
+        tags,
+    })))
+}
+
+/// Adds the items in a CSV or JSON file, sent as the request body, to one of the user's lists.
+/// If any row can't be imported, nothing is, and the report lists the rows' errors with a 422.
+#[post("/api/v1/lists/<list_key>/import", data = "<file>")]
+pub async fn import(
+    mut db: Connection<WishlistDb>,
+    quotas: &State<Quotas>,
+    plugins: &State<PluginRegistry>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    file: Data<'_>,
+) -> Result<(Status, Json<ListImportReport>), ApiError> {
+    let user_id = user.user.id;
+    let list = owned_list(&mut db, user_id, user.only_list(), list_key).await?;
+
+    let data = file
+        .open(MAX_FILE_SIZE.bytes())
+        .into_bytes()
+        .await
+        .map_err(|e| {
+            ApiError::Internal(Json(ApiGenericError {
+                message: e.to_string(),
+            }))
+        })?;
+    if !data.is_complete() {
+        return Err(ApiError::Conflict(Json(ApiGenericError {
+            message: format!("Files must be at most {} bytes", MAX_FILE_SIZE),
+        })));
+    }
+
+    let mut tx = Transaction::begin(db).await?;
+    let report = match list_import::import_items(&mut tx, quotas, plugins, &list, &data).await {
+        Ok(report) => report,
+        Err(ListImportError::Data(e)) => return Err(e.into()),
+        Err(e) => {
+            return Err(ApiError::Conflict(Json(ApiGenericError {
+                message: e.to_string(),
+            })))
+        }
+    };
+    if !report.errors.is_empty() {
+        return Ok((Status::UnprocessableEntity, Json(report)));
+    }
+    tx.commit().await?;
+
+    Ok((Status::Created, Json(report)))
+}
+
+#[put("/api/v1/lists/<list_key>/items/<id>", data = "<item>")]
+pub async fn update(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    id: i64,
+    item: Json<EditItem<'_>>,
+) -> Result<Json<TaggedItem>, ApiError> {
+    let list = owned_list(&mut db, user.user.id, user.only_list(), list_key).await?;
+    let mut old_item = find_item(&mut db, &list, id).await?;
+
+    let (kind, amount) = parse_kind_field(item.kind, item.amount)?;
+    let details = parse_details(item.price, item.price_max, item.quantity, item.priority)?;
+    let tags = item.tags.map(Tag::parse_names).transpose()?;
+    old_item.set_kind(
+        kind,
+        amount.as_ref().map(|price| price.amount_cents),
+        amount.as_ref().map(|price| price.currency.as_str()),
+    );
+    details.apply(&mut old_item);
+    let mut tx = Transaction::begin(db).await?;
+    let new_item = old_item
+        .update(
+            &mut tx,
+            item.title,
+            item.description,
+            optional_url(item.url),
+            Some(user.user.id),
+        )
+        .await?;
+    if let Some(tags) = tags {
+        Tag::set_for_item(&mut tx, new_item.id, &tags).await?;
+    }
+    let event = DomainEvent::ItemUpdated {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&new_item),
+        by: Some(user.user.id),
+    };
+    dispatcher.dispatch(&mut tx, event).await?;
+    let mut db = dispatcher.commit(tx).await?;
+
+    let links = ItemLinks::new(Some(site), &list);
+    Ok(Json(TaggedItem::load(&mut db, &links, new_item).await?))
+}
+
+/// Puts the list's items in the order of the item IDs in the request body, a JSON array. Items
+/// that aren't in it are kept in order after the ones that are. Returns the list's items in their
+/// new order.
+#[patch("/api/v1/lists/<list_key>/items/reorder", data = "<ids>")]
+pub async fn reorder(
+    mut db: Connection<WishlistDb>,
+    affiliate: &State<AffiliatePolicy>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    ids: Json<Vec<i64>>,
+) -> Result<Json<Vec<TaggedItem>>, ApiError> {
+    let list = owned_list(&mut db, user.user.id, user.only_list(), list_key).await?;
+
+    let mut tx = Transaction::begin(db).await?;
+    match Item::reorder(&mut tx, list.id, &ids).await {
+        Ok(()) => {}
+        Err(DataError::Other(message)) => {
+            return Err(ApiError::Conflict(Json(ApiGenericError { message })))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let (mut db, _) = tx.commit().await?;
+
+    let mut items = Item::all_by_list(&mut db, list.id).await?;
+    affiliate.rewrite_items(&list, &mut items);
+    let tags = Tag::names_by_list(&mut db, list.id).await?;
+    let items = TaggedItem::all(&ItemLinks::new(Some(site), &list), items, tags);
+
+    Ok(Json(items))
+}
+
+#[delete("/api/v1/lists/<list_key>/items/<id>")]
+pub async fn destroy(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    id: i64,
+) -> Result<NoContent, ApiError> {
+    let list = owned_list(&mut db, user.user.id, user.only_list(), list_key).await?;
+    let mut item = find_item(&mut db, &list, id).await?;
+
+    let event = DomainEvent::ItemDeleted {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&item),
+        by: Some(user.user.id),
+    };
+    let mut tx = Transaction::begin(db).await?;
+    item.destroy(&mut tx, Some(user.user.id)).await?;
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
+
+    Ok(NoContent)
+}
+
+/// Shows whether the item is claimed. The list owner gets a 404, unless they've turned on
+/// `List::reveal_gifting`.
+#[get("/api/v1/lists/<list_key>/items/<id>/claim")]
+pub async fn claim_status(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, ReadItems>,
+    list_key: &str,
+    id: i64,
+) -> Result<Json<ClaimStatus>, ApiError> {
+    let (_, item) = gifting_item(&mut db, &user, list_key, id, Access::View).await?;
+
+    let claim = Claim::find_by_item(&mut db, item.id).await?;
+    Ok(Json(ClaimStatus {
+        claimed: claim.is_some(),
+        by_you: claim.is_some_and(|claim| claim.user_id == user.user.id),
+    }))
+}
+
+/// Claims the item for the caller. Claiming an item they already claimed does nothing.
+#[post("/api/v1/lists/<list_key>/items/<id>/claim")]
+pub async fn claim(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    id: i64,
+) -> Result<Created<Json<Claim>>, ApiError> {
+    let (list, item) = gifting_item(&mut db, &user, list_key, id, Access::TakePart).await?;
+    if !item.is_claimable() {
+        return Err(ApiError::Conflict(Json(ApiGenericError {
+            message: "This item can't be claimed".to_string(),
+        })));
+    }
+
+    let claim = match Claim::find_by_item(&mut db, item.id).await? {
+        Some(claim) if claim.user_id == user.user.id => claim,
+        Some(_) => {
+            return Err(ApiError::Conflict(Json(ApiGenericError {
+                message: "Someone already claimed this item".to_string(),
+            })))
+        }
+        None => {
+            let mut tx = Transaction::begin(db).await?;
+            let claim = Claim::create(&mut tx, item.id, user.user.id).await?;
+            let event = DomainEvent::ItemClaimed {
+                list: ListRef::from(&list),
+                item: ItemRef::from(&item),
+                by: user.user.id,
+            };
+            dispatcher.dispatch(&mut tx, event).await?;
+            dispatcher.commit(tx).await?;
+            claim
+        }
+    };
+
+    Ok(Created::new(uri!(claim_status(&list.key, item.id)).to_string()).body(Json(claim)))
+}
+
+/// Gives up the caller's claim on the item.
+#[delete("/api/v1/lists/<list_key>/items/<id>/claim")]
+pub async fn unclaim(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: ApiUser<'_, WriteItems>,
+    list_key: &str,
+    id: i64,
+) -> Result<NoContent, ApiError> {
+    let (list, item) = gifting_item(&mut db, &user, list_key, id, Access::TakePart).await?;
+
+    let mut tx = Transaction::begin(db).await?;
+    if Claim::destroy_by_user(&mut tx, item.id, user.user.id).await? {
+        let event = DomainEvent::ItemUnclaimed {
+            list: ListRef::from(&list),
+            item: ItemRef::from(&item),
+            by: user.user.id,
+        };
+        dispatcher.dispatch(&mut tx, event).await?;
+    }
+    dispatcher.commit(tx).await?;
+
+    Ok(NoContent)
+}
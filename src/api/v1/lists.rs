@@ -1,85 +1,409 @@
+use std::net::IpAddr;
+
 use rocket::http::Status;
 use rocket::response::status::{self, Created, NoContent};
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
 use rocket_db_pools::Connection;
+use utoipa::ToSchema;
 
-use crate::api::ApiError;
-use crate::db::models::List;
-use crate::db::WishlistDb;
+use crate::api::access::scopes::{ReadLists, Unscoped, WriteLists};
+use crate::api::access::{ApiCaller, ApiUser};
+use crate::api::{ApiError, ApiGenericError, Warned};
+use crate::db::models::{List, ListFilter, ListOrder, ListPrivacy};
+use crate::db::{Transaction, WishlistDb};
+use crate::events::{DomainEvent, ListRef};
+use crate::features::{flags, Enabled};
+use crate::notify::Dispatcher;
+use crate::pagination::{Page, Paged, Pagination, SortDirection};
+use crate::quotas::Quotas;
+use crate::throttle::{Challenge, ChallengeAnswer, ListThrottle, ThrottleError};
+use crate::util::SiteUrl;
+use crate::views::ListView;
+use crate::web::auth;
 
-#[derive(FromForm, Deserialize, Serialize)]
+#[derive(FromForm, Deserialize, Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateList<'r> {
-    pub is_private: bool,
+    /// See `ListPrivacy::as_str`.
+    #[serde(default)]
+    pub privacy: Option<&'r str>,
+    /// Deprecated, see `requested_privacy`.
+    #[serde(default)]
+    pub is_private: Option<bool>,
     pub title: &'r str,
     pub description: &'r str,
+    #[serde(default)]
+    pub affiliate_opt_out: bool,
+    #[serde(default)]
+    pub language: Option<&'r str>,
+    /// The proof-of-work token, when making a list without logging in. See `crate::throttle`.
+    #[serde(default)]
+    pub challenge_token: Option<&'r str>,
+    /// The proof-of-work nonce or the captcha provider's response.
+    #[serde(default)]
+    pub challenge_response: Option<&'r str>,
+}
+
+impl<'r> CreateList<'r> {
+    pub fn privacy(&self) -> &'r str {
+        requested_privacy(self.privacy, self.is_private)
+    }
+
+    pub fn challenge_answer(&self) -> ChallengeAnswer<'r> {
+        ChallengeAnswer {
+            token: self.challenge_token,
+            response: self.challenge_response,
+        }
+    }
 }
 
-#[derive(FromForm, Deserialize, Serialize)]
+#[derive(FromForm, Deserialize, Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct EditList<'r> {
-    pub is_private: bool,
+    /// See `ListPrivacy::as_str`.
+    #[serde(default)]
+    pub privacy: Option<&'r str>,
+    /// Deprecated, see `requested_privacy`.
+    #[serde(default)]
+    pub is_private: Option<bool>,
     pub title: &'r str,
     pub description: &'r str,
+    #[serde(default)]
+    pub affiliate_opt_out: bool,
+    #[serde(default)]
+    pub language: Option<&'r str>,
+}
+
+impl<'r> EditList<'r> {
+    /// The privacy the list was asked to have, given what it has now. Clients still sending
+    /// `is_private` keep the list's privacy level if it's what they sent, so saving an unlisted
+    /// list doesn't make it private.
+    pub fn privacy(&self, current: ListPrivacy) -> &'r str {
+        match (self.privacy, self.is_private) {
+            (None, Some(is_private)) if is_private == current.is_private() => current.as_str(),
+            (privacy, is_private) => requested_privacy(privacy, is_private),
+        }
+    }
+}
+
+/// What API responses warn clients still sending `is_private`, see `Warned`.
+const IS_PRIVATE_DEPRECATED: &str = "is_private is deprecated, use privacy instead";
+
+/// The privacy a list was asked for. Clients from before lists had privacy levels send
+/// `is_private` instead of `privacy`, which is still accepted (see
+/// `ListPrivacy::from_is_private`) but `privacy` wins if both are sent. Lists are private if
+/// neither is.
+fn requested_privacy(privacy: Option<&str>, is_private: Option<bool>) -> &str {
+    match privacy {
+        Some(privacy) => privacy,
+        None => ListPrivacy::from_is_private(is_private.unwrap_or(true)).as_str(),
+    }
+}
+
+/// A list as the API returns it. `is_private` is still sent for clients from before lists had
+/// privacy levels, see `ListPrivacy::is_private`.
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiList {
+    #[serde(flatten)]
+    pub view: ListView,
+    /// Deprecated, use `privacy`.
+    pub is_private: bool,
+}
+
+impl From<ListView> for ApiList {
+    fn from(view: ListView) -> Self {
+        ApiList {
+            is_private: view.list.privacy().is_private(),
+            view,
+        }
+    }
+}
+
+/// Treats an empty language as no language, since forms always send the field.
+pub fn optional_language(language: Option<&str>) -> Option<&str> {
+    language.map(str::trim).filter(|language| !language.is_empty())
 }
 
-#[get("/api/v1/lists")]
-pub async fn index(mut db: Connection<WishlistDb>) -> Result<Json<Vec<List>>, ApiError> {
-    let list = List::all_public(&mut db).await?;
+/// Public lists, a page at a time.
+///
+/// Lists can be searched by title and description with `q`, and sorted with `sort` and `order`.
+/// Unknown `sort` and `order` values are ignored.
+#[utoipa::path(
+    tag = "lists",
+    params(
+        ("q" = Option<String>, Query, description = "Text the title or description contains"),
+        ("sort" = Option<String>, Query, description = "`id` (default), `title` or `created_at`"),
+        ("order" = Option<String>, Query, description = "`asc` (default) or `desc`"),
+        ("is_private" = Option<bool>, Query, description = "Whether the lists are private (index lists never are)"),
+        Page,
+    ),
+    responses(
+        (status = 200, description = "The page of lists", body = [ApiList]),
+        (status = 404, description = "The instance has no public list index"),
+    ),
+    security((), ("api_token" = []))
+)]
+#[get("/api/v1/lists?<q>&<sort>&<order>&<is_private>&<page..>")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    caller: ApiCaller<'_, ReadLists>,
+    _enabled: Enabled<flags::PublicIndex>,
+    q: Option<&str>,
+    sort: Option<&str>,
+    order: Option<&str>,
+    is_private: Option<bool>,
+    page: Option<Page>,
+) -> Result<Paged<ApiList>, ApiError> {
+    let page = page.unwrap_or_default();
+    let filter = ListFilter {
+        search: q.map(str::trim).filter(|q| !q.is_empty()),
+        is_private,
+        order: sort.and_then(ListOrder::parse).unwrap_or(ListOrder::Id),
+        direction: order.and_then(SortDirection::parse).unwrap_or_default(),
+    };
+
+    let sort = Some(filter.order.as_str()).filter(|_| filter.order != ListOrder::Id);
+    let order = Some(filter.direction.as_str()).filter(|_| filter.direction != SortDirection::Asc);
+    let is_private = is_private.map(|is_private| is_private.to_string());
+    let pagination = Pagination::new(&page, List::count_public_filtered(&mut db, &filter).await?)
+        .with_filter("q", filter.search)
+        .with_filter("sort", sort)
+        .with_filter("order", order)
+        .with_filter("is_private", is_private.as_deref());
+    let lists = List::page_public_filtered(&mut db, &filter, &page)
+        .await?
+        .into_iter()
+        .filter(|list| caller.allows_list(list))
+        .collect();
+    let items = ListView::all(Some(site), lists)
+        .into_iter()
+        .map(ApiList::from)
+        .collect();
 
-    Ok(Json(list))
+    Ok(Paged { items, pagination })
 }
 
+/// A list the caller can see.
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 200, description = "The list", body = ApiList),
+        (status = 404, description = "No list the caller can see has this key"),
+    ),
+    security((), ("api_token" = []))
+)]
 #[get("/api/v1/lists/<key>")]
 pub async fn show(
     mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    caller: ApiCaller<'_, ReadLists>,
     key: &str,
-) -> Result<Option<Json<List>>, ApiError> {
-    let list = List::find_by_key(&mut db, key).await?;
+) -> Result<Option<Json<ApiList>>, ApiError> {
+    let list = List::find_by_key(&mut db, key)
+        .await?
+        .filter(|list| list.is_visible_to(caller.user.map(|u| u.id)) && caller.allows_list(list));
 
-    Ok(list.map(Json))
+    Ok(list.map(|list| Json(ListView::new(Some(site), list).into())))
 }
 
+/// Returns the list if the user owns it and their token can reach it.
+async fn owned_list(
+    db: &mut Connection<WishlistDb>,
+    user: &ApiUser<'_, WriteLists>,
+    key: &str,
+) -> Result<List, ApiError> {
+    List::find_by_key(db, key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)) && user.allows_list(list))
+        .ok_or(ApiError::NotFound(Json(crate::api::ApiGenericError {
+            message: "List not found".to_string(),
+        })))
+}
+
+/// Returns the challenge to solve before making a list without logging in, or `null` if there
+/// isn't one.
+#[utoipa::path(
+    tag = "lists",
+    responses((status = 200, description = "The challenge", body = Option<Challenge>)),
+    security((), ("api_token" = []))
+)]
+#[get("/api/v1/lists/challenge")]
+pub fn challenge(
+    _caller: ApiCaller<'_, Unscoped>,
+    throttle: &State<ListThrottle>,
+) -> Json<Option<Challenge>> {
+    Json(throttle.challenge())
+}
+
+/// Makes a list. Without an API token the list has no owner, and the caller may have to solve
+/// the challenge first.
+#[utoipa::path(
+    tag = "lists",
+    request_body = CreateList,
+    responses(
+        (status = 201, description = "The new list", body = ApiList),
+        (status = 403, description = "The challenge wasn't solved"),
+        (status = 409, description = "Over quota, or the email address isn't verified yet"),
+        (status = 429, description = "Too many lists were made from the caller's address"),
+    ),
+    security((), ("api_token" = []))
+)]
 #[post("/api/v1/lists", data = "<list>")]
 pub async fn create(
     mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    throttle: &State<ListThrottle>,
+    site: &State<SiteUrl>,
+    ip: Option<IpAddr>,
+    caller: ApiCaller<'_, WriteLists>,
     list: Json<CreateList<'_>>,
-) -> Result<Created<Json<List>>, status::Custom<String>> {
-    List::create(&mut db, list.is_private, list.title, list.description)
+) -> Result<Warned<Created<Json<ApiList>>>, status::Custom<String>> {
+    if caller.only_list().is_some() {
+        return Err(status::Custom(
+            Status::Forbidden,
+            "This token is limited to one list, so it can't make new ones".to_string(),
+        ));
+    }
+
+    let user = caller.user;
+    if user.is_none() {
+        throttle
+            .check(ip, &list.challenge_answer())
+            .await
+            .map_err(|e| match e {
+                ThrottleError::TooMany(_) => status::Custom(Status::TooManyRequests, e.to_string()),
+                ThrottleError::ChallengeFailed => status::Custom(Status::Forbidden, e.to_string()),
+                e => status::Custom(Status::InternalServerError, e.to_string()),
+            })?;
+    }
+
+    let over_quota = quotas
+        .check_list(&mut db, user.map(|u| u.id))
         .await
-        .map(|new_list| Created::new(uri!(show(&new_list.key)).to_string()).body(Json(new_list)))
-        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+    if let Some(message) = over_quota {
+        return Err(status::Custom(Status::Conflict, message));
+    }
+    if let Some(user) = user {
+        auth::check_can_publish(user, list.privacy())
+            .map_err(|e| status::Custom(Status::Conflict, e.to_string()))?;
+    }
+
+    let mut tx = Transaction::begin(db)
+        .await
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+    let new_list = List::create(
+        &mut tx,
+        user.map(|u| u.id),
+        list.privacy(),
+        list.title,
+        list.description,
+        list.affiliate_opt_out,
+        optional_language(list.language),
+    )
+    .await
+    .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+
+    let event = DomainEvent::ListCreated {
+        list: ListRef::from(&new_list),
+        by: user.map(|u| u.id),
+    };
+    dispatcher
+        .dispatch(&mut tx, event)
+        .await
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+    dispatcher
+        .commit(tx)
+        .await
+        .map_err(|e| status::Custom(Status::InternalServerError, e.to_string()))?;
+
+    let location = uri!(show(&new_list.key)).to_string();
+    let body = Json(ListView::new(Some(site), new_list).into());
+    Ok(Warned::deprecated(
+        Created::new(location).body(body),
+        list.privacy.is_none() && list.is_private.is_some(),
+        IS_PRIVATE_DEPRECATED,
+    ))
 }
 
+/// Changes one of the user's lists.
+#[utoipa::path(
+    tag = "lists",
+    request_body = EditList,
+    responses(
+        (status = 200, description = "The changed list", body = ApiList),
+        (status = 404, description = "The user has no list with this key", body = ApiGenericError),
+        (status = 409, description = "The email address isn't verified", body = ApiGenericError),
+        (status = 422, description = "The list is invalid"),
+    ),
+    security(("api_token" = []))
+)]
 #[put("/api/v1/lists/<key>", data = "<list>")]
 pub async fn update(
     mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, WriteLists>,
     key: &str,
     list: Json<EditList<'_>>,
-) -> Result<Json<List>, ApiError> {
-    let mut old_list = List::find_by_key(&mut db, key)
-        .await?
-        .ok_or(ApiError::NotFound(Json(crate::api::ApiGenericError {
-            message: "List not found".to_string(),
-        })))?;
+) -> Result<Warned<Json<ApiList>>, ApiError> {
+    let mut old_list = owned_list(&mut db, &user, key).await?;
+    let privacy = list.privacy(old_list.privacy());
 
+    if let Err(e) = auth::check_can_publish(user.user, privacy) {
+        return Err(ApiError::Conflict(Json(ApiGenericError {
+            message: e.to_string(),
+        })));
+    }
     let new_list = old_list
-        .update(&mut db, list.is_private, list.title, list.description)
+        .update(
+            &mut db,
+            privacy,
+            list.title,
+            list.description,
+            list.affiliate_opt_out,
+            optional_language(list.language),
+            Some(user.user.id),
+        )
         .await?;
 
-    Ok(Json(new_list))
+    Ok(Warned::deprecated(
+        Json(ListView::new(Some(site), new_list).into()),
+        list.privacy.is_none() && list.is_private.is_some(),
+        IS_PRIVATE_DEPRECATED,
+    ))
 }
 
+/// Deletes one of the user's lists.
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 204, description = "The list was deleted"),
+        (status = 404, description = "The user has no list with this key", body = ApiGenericError),
+    ),
+    security(("api_token" = []))
+)]
 #[delete("/api/v1/lists/<key>")]
-pub async fn destroy(mut db: Connection<WishlistDb>, key: &str) -> Result<NoContent, ApiError> {
-    let mut list = List::find_by_key(&mut db, key)
-        .await?
-        .ok_or(ApiError::NotFound(Json(crate::api::ApiGenericError {
-            message: "List not found".to_string(),
-        })))?;
+pub async fn destroy(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: ApiUser<'_, WriteLists>,
+    key: &str,
+) -> Result<NoContent, ApiError> {
+    let mut list = owned_list(&mut db, &user, key).await?;
 
-    list.destroy(&mut db).await?;
+    let event = DomainEvent::ListDeleted {
+        list: ListRef::from(&list),
+        by: Some(user.user.id),
+    };
+    let mut tx = Transaction::begin(db).await?;
+    list.destroy(&mut tx, Some(user.user.id)).await?;
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
 
     Ok(NoContent)
 }
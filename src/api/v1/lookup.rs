@@ -0,0 +1,39 @@
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use validator::ValidationErrors;
+
+use crate::api::access::scopes::ReadItems;
+use crate::api::access::ApiUser;
+use crate::api::{ApiError, ApiGenericError};
+use crate::lookup::{Barcode, ProductInfo, ProductLookup};
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LookupBarcode<'r> {
+    /// An EAN, UPC or ISBN.
+    pub barcode: &'r str,
+}
+
+#[post("/api/v1/lookup/barcode", data = "<lookup>")]
+pub async fn barcode(
+    _user: ApiUser<'_, ReadItems>,
+    products: &State<ProductLookup>,
+    lookup: Json<LookupBarcode<'_>>,
+) -> Result<Json<ProductInfo>, ApiError> {
+    let barcode = Barcode::parse(lookup.barcode).map_err(|e| {
+        let mut errors = ValidationErrors::new();
+        errors.add("barcode", e);
+        ApiError::Invalid(Json(errors))
+    })?;
+
+    match products.lookup(&barcode).await {
+        Ok(Some(product)) => Ok(Json(product)),
+        Ok(None) => Err(ApiError::NotFound(Json(ApiGenericError {
+            message: "No product found for this barcode".to_string(),
+        }))),
+        Err(e) => Err(ApiError::Internal(Json(ApiGenericError {
+            message: e.to_string(),
+        }))),
+    }
+}
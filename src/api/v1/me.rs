@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+
+use chrono::Datelike;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use url::Url;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::api::access::scopes::{ReadItems, ReadLists, WriteLists};
+use crate::api::access::ApiUser;
+use crate::api::v1::lists::ApiList;
+use crate::api::{ApiError, ApiGenericError};
+use crate::calendar::{Calendar, CalendarEvent};
+use crate::db::models::{Claim, List, ListPrivacy, UserStats};
+use crate::db::{Transaction, WishlistDb};
+use crate::stats::StatsCache;
+use crate::util::SiteUrl;
+use crate::views::ListView;
+use crate::web;
+use crate::web::auth;
+
+/// How many days before a list's event date the reminder to get a claimed item is.
+const REMINDER_DAYS: i64 = 7;
+
+#[get("/api/v1/me/stats?<year>")]
+pub async fn stats(
+    mut db: Connection<WishlistDb>,
+    cache: &State<StatsCache>,
+    user: ApiUser<'_, ReadLists>,
+    year: Option<i32>,
+) -> Result<Json<UserStats>, ApiError> {
+    let year = year.unwrap_or_else(|| chrono::Utc::now().year());
+    let stats = cache.get(&mut db, user.user.id, year).await?;
+
+    Ok(Json(stats))
+}
+
+/// A calendar of reminders to get the items the user claimed, a week before each list's event
+/// date. Items that have been received, and lists without an upcoming date, are left out.
+#[get("/api/v1/me/claims/calendar.ics")]
+pub async fn claims_calendar(
+    mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, ReadItems>,
+) -> Result<(ContentType, String), ApiError> {
+    let now = chrono::Utc::now().naive_utc();
+    let claims = Claim::upcoming_by_user(&mut db, user.user.id, now.date()).await?;
+    let host = Url::parse(&site.url("/"))
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| "localhost".to_string());
+
+    let events = claims
+        .into_iter()
+        .filter(|claim| user.only_list().is_none_or(|id| id == claim.list_id))
+        .map(|claim| {
+            let remind_on = claim.event_date - chrono::Duration::days(REMINDER_DAYS);
+            CalendarEvent {
+                uid: format!("claim-{}@{}", claim.claim_id, host),
+                date: remind_on.max(now.date()),
+                summary: format!("Get {} for {}", claim.item_title, claim.list_title),
+                description: format!(
+                    "You claimed {} on {}, which is for {}.",
+                    claim.item_title,
+                    claim.list_title,
+                    claim.event_date.format("%B %-d, %Y")
+                ),
+                url: site.url(&uri!(web::items::show(&claim.list_key, claim.item_id)).to_string()),
+            }
+        })
+        .collect();
+
+    let calendar = Calendar {
+        name: "Gifts to get".to_string(),
+        events,
+        generated_at: now,
+    };
+
+    Ok((ContentType::Calendar, calendar.to_ics()))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChangeListsPrivacy<'r> {
+    /// See `ListPrivacy::as_str`.
+    pub privacy: &'r str,
+    /// Without this, nothing is changed and the lists that would be are returned.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ListsPrivacyChange {
+    pub privacy: ListPrivacy,
+    /// Whether the lists were changed, or would be if confirmed.
+    pub confirmed: bool,
+    pub lists: Vec<ApiList>,
+}
+
+/// Gives all of the user's lists the same privacy at once, like
+/// `web::account::change_lists_privacy`. Tokens for one list only change that list.
+#[post("/api/v1/me/lists/privacy", data = "<change>")]
+pub async fn change_lists_privacy(
+    mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, WriteLists>,
+    change: Json<ChangeListsPrivacy<'_>>,
+) -> Result<Json<ListsPrivacyChange>, ApiError> {
+    let privacy = ListPrivacy::parse(change.privacy).ok_or_else(|| {
+        let mut err = ValidationError::new("privacy");
+        err.message = Some(Cow::from("Privacy must be public, unlisted, group or private"));
+        let mut errors = ValidationErrors::new();
+        errors.add("privacy", err);
+        ApiError::Invalid(Json(errors))
+    })?;
+    if let Err(e) = auth::check_can_publish(user.user, privacy.as_str()) {
+        return Err(ApiError::Conflict(Json(ApiGenericError {
+            message: e.to_string(),
+        })));
+    }
+
+    let mut lists = List::all_by_user(&mut db, user.user.id)
+        .await?
+        .into_iter()
+        .filter(|list| list.privacy() != privacy && user.allows_list(list))
+        .collect::<Vec<_>>();
+
+    if change.confirm {
+        let mut tx = Transaction::begin(db).await?;
+        let mut changed = vec![];
+        for mut list in lists {
+            changed.push(list.set_privacy(&mut tx, privacy, Some(user.user.id)).await?);
+        }
+        tx.commit().await?;
+        lists = changed;
+    }
+
+    Ok(Json(ListsPrivacyChange {
+        privacy,
+        confirmed: change.confirm,
+        lists: ListView::all(Some(site), lists)
+            .into_iter()
+            .map(ApiList::from)
+            .collect(),
+    }))
+}
@@ -0,0 +1,43 @@
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+
+use crate::api::access::scopes::Admin;
+use crate::api::access::ApiUser;
+use crate::api::ApiError;
+use crate::db::models::{Event, EventCount, Item, List, SearchIndexHealth, SearchTask, User};
+use crate::db::WishlistDb;
+use crate::web::auth::permissions::ViewAnalytics;
+use crate::web::auth::Permission;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Metrics {
+    pub users: i64,
+    pub lists: i64,
+    pub items: i64,
+    pub search_index: SearchIndexHealth,
+    /// How many of each kind of event happened in the last `EVENT_WINDOW_DAYS` days.
+    pub events: Vec<EventCount>,
+}
+
+/// How far back event counts go.
+const EVENT_WINDOW_DAYS: i64 = 30;
+
+/// Returns instance-wide counts, recent activity and the health of the search index, for
+/// monitoring.
+#[get("/api/v1/metrics")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    _user: ApiUser<'_, Admin>,
+    _admin: Permission<'_, ViewAnalytics>,
+) -> Result<Json<Metrics>, ApiError> {
+    let since = (chrono::Utc::now() - chrono::Duration::days(EVENT_WINDOW_DAYS)).naive_utc();
+    Ok(Json(Metrics {
+        users: User::count(&mut db).await?,
+        lists: List::count(&mut db).await?,
+        items: Item::count(&mut db).await?,
+        search_index: SearchTask::health(&mut db).await?,
+        events: Event::counts_since(&mut db, since).await?,
+    }))
+}
@@ -1 +1,16 @@
+pub mod hooks;
+pub mod inbound;
+pub mod items;
 pub mod lists;
+pub mod lookup;
+pub mod me;
+pub mod metrics;
+pub mod openapi;
+pub mod passwords;
+pub mod scrape;
+pub mod search;
+#[cfg(all(test, debug_assertions))]
+mod tests;
+pub mod triggers;
+pub mod uploads;
+pub mod webhooks;
@@ -0,0 +1,58 @@
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::api::v1::lists::{self, ApiList, CreateList, EditList};
+use crate::api::ApiGenericError;
+use crate::db::models::List;
+use crate::throttle::{Challenge, ChallengeKind};
+use crate::views::ListView;
+
+/// The API's OpenAPI document. Schemas are derived from the types the endpoints take and
+/// return, so they stay in sync; new endpoints are added to `paths`, and the types they use to
+/// `schemas`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Universal Wishlist API"),
+    paths(
+        lists::index,
+        lists::show,
+        lists::challenge,
+        lists::create,
+        lists::update,
+        lists::destroy,
+    ),
+    components(schemas(
+        ApiList,
+        ListView,
+        List,
+        CreateList,
+        EditList,
+        Challenge,
+        ChallengeKind,
+        ApiGenericError,
+    )),
+    modifiers(&ApiTokens),
+    tags((name = "lists", description = "Wishlists"))
+)]
+pub struct ApiDoc;
+
+/// Adds the `api_token` security scheme the paths refer to: an API token sent as a bearer token,
+/// see `crate::api::access`.
+struct ApiTokens;
+
+impl Modify for ApiTokens {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+/// Serves the document at `/api/v1/openapi.json`, and Swagger UI for it at `/api/docs`.
+pub fn docs() -> SwaggerUi {
+    SwaggerUi::new("/api/docs/<_..>").url("/api/v1/openapi.json", ApiDoc::openapi())
+}
@@ -0,0 +1,27 @@
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::api::access::scopes::Unscoped;
+use crate::api::access::ApiCaller;
+use crate::passwords::{self, PasswordStrength};
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CheckPassword<'r> {
+    pub password: &'r str,
+    pub username: Option<&'r str>,
+    pub email: Option<&'r str>,
+}
+
+#[post("/api/v1/passwords/strength", data = "<check>")]
+pub fn strength(
+    _caller: ApiCaller<'_, Unscoped>,
+    check: Json<CheckPassword<'_>>,
+) -> Json<PasswordStrength> {
+    let user_inputs = [check.username, check.email]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    Json(passwords::estimate_strength(check.password, &user_inputs))
+}
@@ -0,0 +1,44 @@
+use std::borrow::Cow;
+
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::api::access::scopes::ReadItems;
+use crate::api::access::ApiUser;
+use crate::api::{ApiError, ApiGenericError};
+use crate::sources::{ItemMetadata, SourceError, SourceRegistry};
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScrapeUrl<'r> {
+    /// A product page.
+    pub url: &'r str,
+}
+
+/// Fetches the page at the URL and returns the item details found on it, for prefilling a new
+/// item.
+#[post("/api/v1/scrape", data = "<scrape>")]
+pub async fn fetch(
+    _user: ApiUser<'_, ReadItems>,
+    sources: &State<SourceRegistry>,
+    scrape: Json<ScrapeUrl<'_>>,
+) -> Result<Json<ItemMetadata>, ApiError> {
+    match sources.fetch_metadata(scrape.url.trim()).await {
+        Ok(metadata) => Ok(Json(metadata)),
+        Err(e @ (SourceError::Url(_) | SourceError::NotPublic)) => {
+            let mut err = ValidationError::new("url");
+            err.message = Some(Cow::from(e.to_string()));
+            let mut errors = ValidationErrors::new();
+            errors.add("url", err);
+            Err(ApiError::Invalid(Json(errors)))
+        }
+        Err(SourceError::NotFound) => Err(ApiError::NotFound(Json(ApiGenericError {
+            message: SourceError::NotFound.to_string(),
+        }))),
+        Err(e) => Err(ApiError::Internal(Json(ApiGenericError {
+            message: e.to_string(),
+        }))),
+    }
+}
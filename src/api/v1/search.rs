@@ -0,0 +1,30 @@
+use rocket::serde::json::Json;
+use rocket_db_pools::Connection;
+
+use crate::api::access::scopes::ReadLists;
+use crate::api::access::ApiCaller;
+use crate::api::ApiError;
+use crate::db::models::{List, SearchHit};
+use crate::db::WishlistDb;
+
+/// The most results a search returns.
+pub const MAX_RESULTS: i64 = 50;
+
+/// Searches public lists, and the user's own lists, for the query. Tokens limited to one list only
+/// search that list.
+#[get("/api/v1/search?<q>")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    caller: ApiCaller<'_, ReadLists>,
+    q: &str,
+) -> Result<Json<Vec<SearchHit>>, ApiError> {
+    let mut hits = SearchHit::search(&mut db, q, caller.user.map(|u| u.id), MAX_RESULTS).await?;
+    if let Some(list_id) = caller.only_list() {
+        let key = List::find_by_id(&mut db, list_id)
+            .await?
+            .map(|list| list.key);
+        hits.retain(|hit| Some(&hit.list_key) == key.as_ref());
+    }
+
+    Ok(Json(hits))
+}
@@ -0,0 +1,613 @@
+//! Snapshots of what every endpoint returns for the seed data (see `crate::testing`), so a renamed
+//! field or one that stops being skipped shows up in review. After changing a response on
+//! purpose, run the tests and accept the new snapshots with `cargo insta review`.
+
+use rocket::http::{ContentType, Header};
+use rocket::local::asynchronous::LocalResponse;
+use rocket::serde::json::{json, serde_json, Value};
+use rocket_db_pools::sqlx;
+
+use crate::testing::TestApp;
+
+/// Fields that are random on every run, besides timestamps.
+const RANDOM_FIELDS: &[&str] = &["secret", "token"];
+
+/// The response's status and body, with what changes from run to run redacted: timestamps,
+/// secrets and list keys, which are replaced with the list's title.
+async fn snapshot(app: &TestApp, response: LocalResponse<'_>) -> Value {
+    let status = response.status().code;
+    let body = response.into_string().await.unwrap_or_default();
+    let body = serde_json::from_str(&body).unwrap_or(Value::String(body));
+
+    let keys: Vec<(String, String)> = sqlx::query_as(r#"SELECT title, key FROM lists"#)
+        .fetch_all(app.pool())
+        .await
+        .expect("lists can be read");
+    json!({ "status": status, "body": redact(body, &keys) })
+}
+
+fn redact(value: Value, keys: &[(String, String)]) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Value::String(_)
+                            if name.ends_with("_at") || RANDOM_FIELDS.contains(&name.as_str()) =>
+                        {
+                            Value::String(format!("[{}]", name))
+                        }
+                        value => redact(value, keys),
+                    };
+                    (name, value)
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(|v| redact(v, keys)).collect()),
+        Value::String(text) => Value::String(keys.iter().fold(text, |text, (title, key)| {
+            text.replace(key.as_str(), &format!("[key of {}]", title))
+        })),
+        value => value,
+    }
+}
+
+fn json_body(value: Value) -> (ContentType, String) {
+    (ContentType::JSON, value.to_string())
+}
+
+#[rocket::async_test]
+async fn lists() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+    let ideas = app.list_key("Ideas for later").await;
+
+    let response = client.get("/api/v1/lists").dispatch().await;
+    insta::assert_json_snapshot!("lists_index", snapshot(&app, response).await);
+
+    let response = client.get(format!("/api/v1/lists/{}", birthday)).dispatch().await;
+    insta::assert_json_snapshot!("lists_show", snapshot(&app, response).await);
+
+    // Private lists are only there for their owner
+    let response = client.get(format!("/api/v1/lists/{}", ideas)).dispatch().await;
+    insta::assert_json_snapshot!("lists_show_private", snapshot(&app, response).await);
+    let response = client
+        .get(format!("/api/v1/lists/{}", ideas))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lists_show_private_owner", snapshot(&app, response).await);
+
+    let response = client.get("/api/v1/lists/challenge").dispatch().await;
+    insta::assert_json_snapshot!("lists_challenge", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({
+        "title": "Graduation",
+        "description": "For after the ceremony",
+        "privacy": "public",
+    }));
+    let response = client
+        .post("/api/v1/lists")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lists_create", snapshot(&app, response).await);
+    let graduation = app.list_key("Graduation").await;
+
+    let (content_type, body) = json_body(json!({
+        "title": "Graduation party",
+        "description": "For after the ceremony",
+    }));
+    let response = client
+        .put(format!("/api/v1/lists/{}", graduation))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lists_update", snapshot(&app, response).await);
+
+    let response = client
+        .delete(format!("/api/v1/lists/{}", graduation))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lists_destroy", snapshot(&app, response).await);
+
+    // Changes need a token, a session isn't enough
+    let (content_type, body) = json_body(json!({ "title": "Nope", "description": "" }));
+    let response = client
+        .post("/api/v1/lists")
+        .header(Header::new("Authorization", "Bearer wl_not-a-token"))
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lists_create_bad_token", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn items() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+    let dune = app.item_id("Dune").await;
+    let headphones = app.item_id("Headphones").await;
+    let houseplant = app.item_id("Houseplant").await;
+
+    let response = client.get(format!("/api/v1/lists/{}/items", birthday)).dispatch().await;
+    insta::assert_json_snapshot!("items_index", snapshot(&app, response).await);
+
+    let response = client
+        .get(format!("/api/v1/lists/{}/items/{}", birthday, dune))
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_show", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({
+        "title": "Desk lamp",
+        "description": "Warm white",
+        "kind": "physical",
+        "price": "$25.00",
+        "priority": "high",
+        "tags": "home, under $50",
+    }));
+    let response = client
+        .post(format!("/api/v1/lists/{}/items", birthday))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_create", snapshot(&app, response).await);
+    let lamp = app.item_id("Desk lamp").await;
+
+    let (content_type, body) = json_body(json!({ "title": "", "description": "", "kind": "rock" }));
+    let response = client
+        .post(format!("/api/v1/lists/{}/items", birthday))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_create_invalid", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({
+        "title": "Desk lamp",
+        "description": "Warm white, with a dimmer",
+        "kind": "physical",
+    }));
+    let response = client
+        .put(format!("/api/v1/lists/{}/items/{}", birthday, lamp))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_update", snapshot(&app, response).await);
+
+    let response = client
+        .get(format!("/api/v1/lists/{}/items?tag=home", birthday))
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_index_tagged", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!([lamp, houseplant, headphones, dune]));
+    let response = client
+        .patch(format!("/api/v1/lists/{}/items/reorder", birthday))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_reorder", snapshot(&app, response).await);
+
+    let response = client
+        .post(format!("/api/v1/lists/{}/import", birthday))
+        .header(token.clone())
+        .header(ContentType::CSV)
+        .body("title,description\nBookends,Heavy ones\n")
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_import", snapshot(&app, response).await);
+
+    let response = client
+        .delete(format!("/api/v1/lists/{}/items/{}", birthday, lamp))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_destroy", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn items_on_other_peoples_lists() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("bob").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+    let dune = app.item_id("Dune").await;
+
+    let (content_type, body) = json_body(json!({
+        "title": "Socks",
+        "description": "",
+        "kind": "physical",
+    }));
+    let response = client
+        .post(format!("/api/v1/lists/{}/items", birthday))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_create_not_owner", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({
+        "title": "Socks",
+        "description": "",
+        "kind": "physical",
+    }));
+    let response = client
+        .put(format!("/api/v1/lists/{}/items/{}", birthday, dune))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_update_not_owner", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!([dune]));
+    let response = client
+        .patch(format!("/api/v1/lists/{}/items/reorder", birthday))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_reorder_not_owner", snapshot(&app, response).await);
+
+    let response = client
+        .delete(format!("/api/v1/lists/{}/items/{}", birthday, dune))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("items_destroy_not_owner", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn claims() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let alice = app.api_token("alice").await;
+    let bob = app.api_token("bob").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+    let headphones = app.item_id("Headphones").await;
+    let claim = format!("/api/v1/lists/{}/items/{}/claim", birthday, headphones);
+
+    let response = client.post(claim.clone()).header(bob.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_claim", snapshot(&app, response).await);
+
+    let response = client.post(claim.clone()).header(bob.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_claim_again", snapshot(&app, response).await);
+
+    let response = client.get(claim.clone()).header(bob.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_status", snapshot(&app, response).await);
+
+    // The owner can't see or make claims, see `crate::surprise`
+    let response = client.get(claim.clone()).header(alice.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_status_owner", snapshot(&app, response).await);
+    let response = client.post(claim.clone()).header(alice.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_claim_owner", snapshot(&app, response).await);
+
+    let response = client.delete(claim.clone()).header(bob.clone()).dispatch().await;
+    insta::assert_json_snapshot!("claims_unclaim", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn hooks_and_webhooks() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+
+    // Hook targets have to be public, an address saves looking a name up
+    let (content_type, body) = json_body(json!({
+        "target_url": "https://93.184.216.34/hooks",
+        "event": "item_added",
+        "list_key": birthday,
+    }));
+    let response = client
+        .post("/api/v1/hooks")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    let subscribed = snapshot(&app, response).await;
+    insta::assert_json_snapshot!("hooks_subscribe", subscribed);
+
+    let (content_type, body) = json_body(json!({
+        "target_url": "http://127.0.0.1/hooks",
+        "event": "item_added",
+    }));
+    let response = client
+        .post("/api/v1/hooks")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("hooks_subscribe_private", snapshot(&app, response).await);
+
+    let id = subscribed["body"]["id"].as_i64().unwrap_or_default();
+    let response = client
+        .delete(format!("/api/v1/hooks/{}", id))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("hooks_unsubscribe", snapshot(&app, response).await);
+
+    let webhooks = format!("/api/v1/lists/{}/webhooks", birthday);
+    let (content_type, body) = json_body(json!({
+        "url": "https://93.184.216.34/webhook",
+        "format": "json",
+        "events": ["item_added"],
+    }));
+    let response = client
+        .post(webhooks.clone())
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    let created = snapshot(&app, response).await;
+    insta::assert_json_snapshot!("webhooks_create", created);
+
+    let response = client.get(webhooks.clone()).header(token.clone()).dispatch().await;
+    insta::assert_json_snapshot!("webhooks_index", snapshot(&app, response).await);
+
+    let id = created["body"]["id"].as_i64().unwrap_or_default();
+    let response = client
+        .delete(format!("{}/{}", webhooks, id))
+        .header(token.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("webhooks_destroy", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn uploads() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+
+    let (content_type, body) = json_body(json!({ "total_size": 0 }));
+    let response = client
+        .post("/api/v1/uploads")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("uploads_create_empty", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({ "total_size": 12 }));
+    let response = client
+        .post("/api/v1/uploads")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    let response = response.into_json::<Value>().await.expect("upload is returned");
+    let upload = format!("/api/v1/uploads/{}", response["token"].as_str().unwrap_or_default());
+
+    let response = client.get(upload.clone()).header(token.clone()).dispatch().await;
+    insta::assert_json_snapshot!("uploads_show", snapshot(&app, response).await);
+
+    let response = client
+        .patch(upload.clone())
+        .header(token.clone())
+        .header(Header::new("Upload-Offset", "6"))
+        .body("not an")
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("uploads_append_wrong_offset", snapshot(&app, response).await);
+
+    let response = client
+        .patch(upload.clone())
+        .header(token.clone())
+        .header(Header::new("Upload-Offset", "0"))
+        .body("not an")
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("uploads_append", snapshot(&app, response).await);
+
+    // It isn't an image, so it's thrown away once it's all there
+    let response = client
+        .patch(upload.clone())
+        .header(token.clone())
+        .header(Header::new("Upload-Offset", "6"))
+        .body(" image")
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("uploads_append_last", snapshot(&app, response).await);
+
+    let response = client.delete(upload.clone()).header(token.clone()).dispatch().await;
+    insta::assert_json_snapshot!("uploads_destroy", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn me() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+
+    let response = client.get("/api/v1/me/stats?year=2000").header(token.clone()).dispatch().await;
+    insta::assert_json_snapshot!("me_stats", snapshot(&app, response).await);
+
+    let response = client.get("/api/v1/me/stats").dispatch().await;
+    insta::assert_json_snapshot!("me_stats_logged_out", snapshot(&app, response).await);
+
+    let response = client
+        .get("/api/v1/me/claims/calendar.ics")
+        .header(token.clone())
+        .dispatch()
+        .await;
+    let calendar = response.into_string().await.unwrap_or_default();
+    let calendar = calendar
+        .lines()
+        .filter(|line| !line.starts_with("DTSTAMP"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    insta::assert_snapshot!("me_claims_calendar", calendar);
+
+    let (content_type, body) = json_body(json!({ "privacy": "unlisted" }));
+    let response = client
+        .post("/api/v1/me/lists/privacy")
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("me_lists_privacy", snapshot(&app, response).await);
+}
+
+#[rocket::async_test]
+async fn me_stats_top_tags() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let token = app.api_token("alice").await;
+    let birthday = app.list_key("Alice's Birthday").await;
+
+    for (title, tags) in [
+        ("Desk lamp", "home, office"),
+        ("Rug", "home"),
+        ("Stapler", "office"),
+    ] {
+        let (content_type, body) = json_body(json!({
+            "title": title,
+            "description": "",
+            "kind": "physical",
+            "tags": tags,
+        }));
+        client
+            .post(format!("/api/v1/lists/{}/items", birthday))
+            .header(token.clone())
+            .header(content_type)
+            .body(body)
+            .dispatch()
+            .await;
+    }
+    // Tags on other people's items aren't counted
+    let token = app.api_token("bob").await;
+    let housewarming = app.list_key("Bob's Housewarming").await;
+    let (content_type, body) = json_body(json!({
+        "title": "Doormat",
+        "description": "",
+        "kind": "physical",
+        "tags": "home",
+    }));
+    client
+        .post(format!("/api/v1/lists/{}/items", housewarming))
+        .header(token.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+
+    let response = client
+        .get("/api/v1/me/stats")
+        .header(app.api_token("alice").await)
+        .dispatch()
+        .await;
+    let stats: Value = serde_json::from_str(&response.into_string().await.unwrap_or_default())
+        .expect("stats are JSON");
+    assert_eq!(
+        stats["top_tags"],
+        json!([
+            { "name": "home", "item_count": 2 },
+            { "name": "office", "item_count": 2 },
+        ])
+    );
+
+    app.login("alice").await;
+    let page = client.get("/account/stats").dispatch().await;
+    let page = page.into_string().await.unwrap_or_default();
+    assert!(page.contains("Top tags") && page.contains("home"), "the stats page shows them");
+}
+#[rocket::async_test]
+async fn everything_else() {
+    let app = TestApp::new().await;
+    let client = app.client();
+    let admin = app.api_token("admin").await;
+    let alice = app.api_token("alice").await;
+
+    let response = client.get("/api/v1/metrics").header(admin.clone()).dispatch().await;
+    insta::assert_json_snapshot!("metrics_show", snapshot(&app, response).await);
+    let response = client.get("/api/v1/metrics").header(alice.clone()).dispatch().await;
+    insta::assert_json_snapshot!("metrics_show_not_admin", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({ "password": "correct horse battery staple" }));
+    let response = client
+        .post("/api/v1/passwords/strength")
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("passwords_strength", snapshot(&app, response).await);
+
+    let response = client.get("/api/v1/search?q=Dune").dispatch().await;
+    insta::assert_json_snapshot!("search_index", snapshot(&app, response).await);
+
+    let response = client
+        .get("/api/v1/triggers/new-items")
+        .header(alice.clone())
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("triggers_new_items", snapshot(&app, response).await);
+
+    // Only the validation errors, the rest would reach out to other sites
+    let (content_type, body) = json_body(json!({ "barcode": "123" }));
+    let response = client
+        .post("/api/v1/lookup/barcode")
+        .header(alice.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("lookup_barcode_invalid", snapshot(&app, response).await);
+
+    let (content_type, body) = json_body(json!({ "url": "not a url" }));
+    let response = client
+        .post("/api/v1/scrape")
+        .header(alice.clone())
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("scrape_invalid", snapshot(&app, response).await);
+
+    // Inbound email isn't set up, so both hooks are turned away
+    let response = client
+        .post("/api/v1/inbound/email")
+        .body("Subject: Hi\n\nHello")
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("inbound_email_off", snapshot(&app, response).await);
+    let (content_type, body) = json_body(json!({}));
+    let response = client
+        .post("/api/v1/inbound/bounces")
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .await;
+    insta::assert_json_snapshot!("inbound_bounces_off", snapshot(&app, response).await);
+
+    let response = client.get("/api/v1/openapi.json").dispatch().await;
+    insta::assert_json_snapshot!("openapi", snapshot(&app, response).await);
+}
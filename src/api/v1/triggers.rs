@@ -0,0 +1,78 @@
+//! Polling triggers, for automation services like Zapier that check for new things every few
+//! minutes. Each result has an `id` for the service to skip the ones it's seen, and is shaped
+//! like the event a REST hook gets, see `super::hooks`, so either can be used for a trigger.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use rocket::serde::json::{json, Json, Value};
+use rocket::State;
+use rocket_db_pools::Connection;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::api::access::scopes::ReadItems;
+use crate::api::access::ApiUser;
+use crate::api::ApiError;
+use crate::db::models::{Item, List};
+use crate::db::WishlistDb;
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::notify;
+use crate::util::SiteUrl;
+
+/// The most results a trigger returns at once.
+const TRIGGER_LIMIT: i64 = 100;
+
+/// Reads `since` as an RFC 3339 time, e.g. "2023-12-01T09:00:00Z", or as a UTC time without an
+/// offset.
+fn parse_since(since: &str) -> Result<chrono::NaiveDateTime, ApiError> {
+    chrono::DateTime::parse_from_rfc3339(since)
+        .map(|time| time.naive_utc())
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(since, "%Y-%m-%dT%H:%M:%S"))
+        .map_err(|_| {
+            let mut err = ValidationError::new("since");
+            err.message = Some(Cow::from("Since must be a time like 2023-12-01T09:00:00Z"));
+            let mut errors = ValidationErrors::new();
+            errors.add("since", err);
+            ApiError::Invalid(Json(errors))
+        })
+}
+
+/// Items added to the user's lists, newest first. Only items added after `since` are returned if
+/// it's given, and only items on the token's list if it's limited to one.
+#[get("/api/v1/triggers/new-items?<since>")]
+pub async fn new_items(
+    mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    user: ApiUser<'_, ReadItems>,
+    since: Option<&str>,
+) -> Result<Json<Vec<Value>>, ApiError> {
+    let since = match since {
+        Some(since) => parse_since(since)?,
+        None => chrono::NaiveDateTime::default(),
+    };
+    let user_id = user.user.id;
+    let items =
+        Item::all_new_by_user(&mut db, user_id, user.only_list(), since, TRIGGER_LIMIT).await?;
+    let lists = List::all_by_user(&mut db, user_id)
+        .await?
+        .into_iter()
+        .map(|list| (list.id, list))
+        .collect::<HashMap<_, _>>();
+
+    let results = items
+        .iter()
+        .filter_map(|item| {
+            let event = DomainEvent::ItemAdded {
+                list: ListRef::from(lists.get(&item.list_id)?),
+                item: ItemRef::from(item),
+                by: None,
+            };
+            let mut result = notify::automation_payload(&event, site);
+            result["id"] = json!(item.id);
+            result["created_at"] = json!(item.created_at);
+            Some(result)
+        })
+        .collect();
+
+    Ok(Json(results))
+}
@@ -0,0 +1,181 @@
+use rocket::data::Data;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use validator::ValidationErrors;
+
+use crate::api::access::scopes::{ReadItems, WriteItems};
+use crate::api::access::ApiUser;
+use crate::api::{ApiError, ApiGenericError};
+use crate::db::models::Upload;
+use crate::db::WishlistDb;
+use crate::features::{flags, Enabled};
+use crate::images::{ImageScanner, UploadError, UploadStore};
+use crate::quotas::Quotas;
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateUpload {
+    /// The size of the whole file in bytes.
+    pub total_size: i64,
+}
+
+/// The `Upload-Offset` header, which must match the number of bytes already received.
+pub struct UploadOffset(pub i64);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UploadOffset {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request
+            .headers()
+            .get_one("Upload-Offset")
+            .and_then(|offset| offset.parse().ok())
+        {
+            Some(offset) => Outcome::Success(UploadOffset(offset)),
+            None => Outcome::Failure((rocket::http::Status::BadRequest, ())),
+        }
+    }
+}
+
+fn not_found() -> ApiError {
+    ApiError::NotFound(Json(ApiGenericError {
+        message: "Upload not found".to_string(),
+    }))
+}
+
+#[post("/api/v1/uploads", data = "<upload>")]
+pub async fn create(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteItems>,
+    _enabled: Enabled<flags::ImageUploads>,
+    store: &State<UploadStore>,
+    quotas: &State<Quotas>,
+    upload: Json<CreateUpload>,
+) -> Result<Created<Json<Upload>>, ApiError> {
+    if upload.total_size < 1 {
+        return Err(ApiError::BadRequest(Json(ApiGenericError {
+            message: "Uploads must be at least 1 byte".to_string(),
+        })));
+    }
+    if upload.total_size as u64 > store.max_upload_size {
+        return Err(ApiError::TooLarge(Json(ApiGenericError {
+            message: format!("Uploads must be at most {} bytes", store.max_upload_size),
+        })));
+    }
+
+    if let Some(message) = quotas
+        .check_upload(&mut db, user.user.id, upload.total_size)
+        .await?
+    {
+        return Err(ApiError::Conflict(Json(ApiGenericError { message })));
+    }
+
+    let upload = Upload::create(&mut db, user.user.id, upload.total_size).await?;
+
+    Ok(Created::new(uri!(show(&upload.token)).to_string()).body(Json(upload)))
+}
+
+#[get("/api/v1/uploads/<token>")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, ReadItems>,
+    token: &str,
+) -> Result<Json<Upload>, ApiError> {
+    let upload = Upload::find_by_token(&mut db, token)
+        .await?
+        .filter(|upload| upload.user_id == user.user.id)
+        .ok_or_else(not_found)?;
+
+    Ok(Json(upload))
+}
+
+/// Appends a chunk to the upload. Once every byte has been received the file is validated,
+/// scanned and normalized like any other upload.
+#[patch("/api/v1/uploads/<token>", data = "<chunk>")]
+pub async fn append(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteItems>,
+    _enabled: Enabled<flags::ImageUploads>,
+    store: &State<UploadStore>,
+    scanner: &State<ImageScanner>,
+    token: &str,
+    offset: UploadOffset,
+    chunk: Data<'_>,
+) -> Result<Json<Upload>, ApiError> {
+    let conflict = |upload: &Upload| {
+        ApiError::Conflict(Json(ApiGenericError {
+            message: format!("Expected Upload-Offset {}", upload.received_size),
+        }))
+    };
+    // Two chunks for the same offset would both be written to the file, so the upload is locked
+    // before its offset is checked
+    let lock = store.lock(token);
+
+    let mut upload = Upload::find_by_token(&mut db, token)
+        .await?
+        .filter(|upload| upload.user_id == user.user.id)
+        .ok_or_else(not_found)?;
+
+    if lock.is_none() || upload.is_complete || offset.0 != upload.received_size {
+        return Err(conflict(&upload));
+    }
+
+    let remaining = (upload.total_size - upload.received_size) as u64;
+    let written = match store.append(&upload.token, chunk, remaining).await {
+        Ok(written) => written as i64,
+        Err(e) => {
+            return Err(ApiError::Internal(Json(ApiGenericError {
+                message: e.to_string(),
+            })))
+        }
+    };
+
+    // Only counts if nothing else was received since the upload was loaded
+    let upload_progress = match upload.advance(&mut db, written).await? {
+        Some(upload_progress) => upload_progress,
+        None => return Err(conflict(&upload)),
+    };
+    if upload_progress.received_size < upload_progress.total_size {
+        return Ok(Json(upload_progress));
+    }
+
+    match store.finish(&upload.token, scanner).await {
+        Ok(content_type) => Ok(Json(upload_progress.complete(&mut db, content_type).await?)),
+        Err(e) => {
+            store.remove(&upload.token).await;
+            upload.destroy(&mut db).await?;
+            match e {
+                UploadError::Invalid(e) => {
+                    let mut errors = ValidationErrors::new();
+                    errors.add("file", e);
+                    Err(ApiError::Invalid(Json(errors)))
+                }
+                e => Err(ApiError::Internal(Json(ApiGenericError {
+                    message: e.to_string(),
+                }))),
+            }
+        }
+    }
+}
+
+#[delete("/api/v1/uploads/<token>")]
+pub async fn destroy(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteItems>,
+    store: &State<UploadStore>,
+    token: &str,
+) -> Result<NoContent, ApiError> {
+    let mut upload = Upload::find_by_token(&mut db, token)
+        .await?
+        .filter(|upload| upload.user_id == user.user.id)
+        .ok_or_else(not_found)?;
+
+    store.remove(&upload.token).await;
+    upload.destroy(&mut db).await?;
+
+    Ok(NoContent)
+}
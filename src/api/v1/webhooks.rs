@@ -0,0 +1,119 @@
+//! A list's webhooks, the API side of the webhooks on the list's edit page. Automation webhooks'
+//! posts are signed with the webhook's secret, see `crate::notify::sign`.
+
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::Json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+
+use crate::api::access::scopes::WriteLists;
+use crate::api::access::ApiUser;
+use crate::api::{ApiError, ApiGenericError};
+use crate::db::models::{List, ListWebhook};
+use crate::db::WishlistDb;
+
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateWebhook<'r> {
+    pub url: &'r str,
+    /// Either `slack`, `discord` or `json`.
+    pub format: &'r str,
+    /// The events a `json` webhook is sent, see `DomainEvent::kind`. Empty means all of them.
+    #[serde(default)]
+    pub events: Vec<&'r str>,
+}
+
+/// A webhook as it's returned. The URL is masked, like on the edit page, but the secret isn't,
+/// so the caller can check posts.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub format: String,
+    pub events: Vec<String>,
+    pub secret: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl From<ListWebhook> for Webhook {
+    fn from(webhook: ListWebhook) -> Self {
+        Webhook {
+            id: webhook.id,
+            url: webhook.masked_url(),
+            events: webhook
+                .events
+                .split(',')
+                .filter(|kind| !kind.is_empty())
+                .map(str::to_string)
+                .collect(),
+            format: webhook.format,
+            secret: webhook.secret,
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+/// Returns the list if the user owns it and their token can reach it. Webhooks send the list's
+/// contents somewhere else, so only owned lists have them here.
+async fn owned_list(
+    db: &mut Connection<WishlistDb>,
+    user: &ApiUser<'_, WriteLists>,
+    key: &str,
+) -> Result<List, ApiError> {
+    List::find_by_key(db, key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)) && user.allows_list(list))
+        .ok_or(ApiError::NotFound(Json(ApiGenericError {
+            message: "List not found".to_string(),
+        })))
+}
+
+#[get("/api/v1/lists/<key>/webhooks")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteLists>,
+    key: &str,
+) -> Result<Json<Vec<Webhook>>, ApiError> {
+    let list = owned_list(&mut db, &user, key).await?;
+
+    let webhooks = ListWebhook::all_by_list(&mut db, list.id).await?;
+
+    Ok(Json(webhooks.into_iter().map(Webhook::from).collect()))
+}
+
+#[post("/api/v1/lists/<key>/webhooks", data = "<webhook>")]
+pub async fn create(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteLists>,
+    key: &str,
+    webhook: Json<CreateWebhook<'_>>,
+) -> Result<Created<Json<Webhook>>, ApiError> {
+    let list = owned_list(&mut db, &user, key).await?;
+
+    let webhook = ListWebhook::create(
+        &mut db,
+        list.id,
+        webhook.url,
+        webhook.format,
+        &webhook.events,
+    )
+    .await?;
+
+    let location = uri!(destroy(&list.key, webhook.id)).to_string();
+    Ok(Created::new(location).body(Json(Webhook::from(webhook))))
+}
+
+#[delete("/api/v1/lists/<key>/webhooks/<id>")]
+pub async fn destroy(
+    mut db: Connection<WishlistDb>,
+    user: ApiUser<'_, WriteLists>,
+    key: &str,
+    id: i64,
+) -> Result<NoContent, ApiError> {
+    let list = owned_list(&mut db, &user, key).await?;
+
+    ListWebhook::destroy_by_list(&mut db, list.id, id).await?;
+
+    Ok(NoContent)
+}
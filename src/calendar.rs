@@ -0,0 +1,92 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// An all-day event in an iCalendar file, with an alarm on the morning of the day.
+pub struct CalendarEvent {
+    pub uid: String,
+    pub date: NaiveDate,
+    pub summary: String,
+    pub description: String,
+    pub url: String,
+}
+
+/// A minimal iCalendar (RFC 5545) file.
+pub struct Calendar {
+    pub name: String,
+    pub events: Vec<CalendarEvent>,
+    /// When the file was generated, used as every event's DTSTAMP.
+    pub generated_at: NaiveDateTime,
+}
+
+impl Calendar {
+    /// Renders the calendar as an iCalendar document.
+    pub fn to_ics(&self) -> String {
+        let stamp = self.generated_at.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut ics = String::new();
+        push_line(&mut ics, "BEGIN:VCALENDAR");
+        push_line(&mut ics, "VERSION:2.0");
+        push_line(&mut ics, "PRODID:-//wishlist-rs//EN");
+        push_line(&mut ics, "CALSCALE:GREGORIAN");
+        push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape(&self.name)));
+
+        for event in &self.events {
+            let next_day = event.date.succ_opt().unwrap_or(event.date);
+            push_line(&mut ics, "BEGIN:VEVENT");
+            push_line(&mut ics, &format!("UID:{}", escape(&event.uid)));
+            push_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+            push_line(
+                &mut ics,
+                &format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")),
+            );
+            push_line(
+                &mut ics,
+                &format!("DTEND;VALUE=DATE:{}", next_day.format("%Y%m%d")),
+            );
+            push_line(&mut ics, &format!("SUMMARY:{}", escape(&event.summary)));
+            push_line(
+                &mut ics,
+                &format!("DESCRIPTION:{}", escape(&event.description)),
+            );
+            push_line(&mut ics, &format!("URL:{}", event.url));
+            push_line(&mut ics, "TRANSP:TRANSPARENT");
+            push_line(&mut ics, "BEGIN:VALARM");
+            push_line(&mut ics, "ACTION:DISPLAY");
+            push_line(&mut ics, &format!("DESCRIPTION:{}", escape(&event.summary)));
+            push_line(&mut ics, "TRIGGER:PT9H");
+            push_line(&mut ics, "END:VALARM");
+            push_line(&mut ics, "END:VEVENT");
+        }
+
+        push_line(&mut ics, "END:VCALENDAR");
+        ics
+    }
+}
+
+/// Adds a content line, folding it onto continuation lines so none is longer than 75 octets.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// Escapes text for use in an iCalendar TEXT value.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
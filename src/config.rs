@@ -0,0 +1,121 @@
+use std::borrow::Cow;
+
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Phase, Rocket};
+use url::Url;
+
+use crate::affiliate::AffiliateConfig;
+use crate::api::access::ApiLimitsConfig;
+use crate::directory::DirectoryConfig;
+use crate::exports::ExportConfig;
+use crate::features::FeaturesConfig;
+use crate::images::ImageConfig;
+use crate::inbound::InboundConfig;
+use crate::jobs::retention::RetentionConfig;
+use crate::jobs::JobsConfig;
+use crate::lookup::LookupConfig;
+use crate::mail::MailConfig;
+use crate::matrix::MatrixConfig;
+use crate::passkeys::PasskeysConfig;
+use crate::passwords::PasswordConfig;
+use crate::privacy::PrivacyConfig;
+use crate::quotas::QuotaConfig;
+use crate::rate_limits::RateLimitsConfig;
+use crate::render_cache::RenderCacheConfig;
+use crate::sources::SourcesConfig;
+use crate::stats::StatsConfig;
+use crate::throttle::ThrottleConfig;
+use crate::usernames::UsernamePolicy;
+use crate::util::KeyPolicy;
+
+/// Every setting the app reads from Rocket.toml, besides Rocket's own. It's read and checked once
+/// by `init`, then the other fairings set themselves up from their part of it.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AppConfig {
+    pub databases: DatabasesConfig,
+    /// The public URL of the instance, used for links in emails and feeds.
+    pub base_url: Option<String>,
+    pub keys: KeyPolicy,
+    pub mail: MailConfig,
+    pub inbound_email: InboundConfig,
+    pub matrix: MatrixConfig,
+    pub directory: DirectoryConfig,
+    pub passwords: PasswordConfig,
+    pub passkeys: PasskeysConfig,
+    pub usernames: UsernamePolicy,
+    pub images: ImageConfig,
+    pub lookup: LookupConfig,
+    pub sources: SourcesConfig,
+    pub affiliate: AffiliateConfig,
+    pub stats: StatsConfig,
+    pub render_cache: RenderCacheConfig,
+    pub quotas: QuotaConfig,
+    pub throttle: ThrottleConfig,
+    pub api_limits: ApiLimitsConfig,
+    pub rate_limits: RateLimitsConfig,
+    pub privacy: PrivacyConfig,
+    pub exports: ExportConfig,
+    pub jobs: JobsConfig,
+    pub retention: RetentionConfig,
+    pub features: FeaturesConfig,
+}
+
+/// The `databases` table in Rocket.toml. The pool reads its own settings, this is only what the
+/// app needs to prepare for it.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DatabasesConfig {
+    pub wishlists: DatabaseConfig,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+}
+
+impl AppConfig {
+    /// Returns the config in managed state. If it couldn't be read, launch has already failed,
+    /// so the defaults stand in to let the remaining fairings finish.
+    pub fn of<P: Phase>(rocket: &Rocket<P>) -> Cow<'_, AppConfig> {
+        match rocket.state::<AppConfig>() {
+            Some(config) => Cow::Borrowed(config),
+            None => Cow::Owned(AppConfig::default()),
+        }
+    }
+
+    /// Checks the settings that can be wrong without failing to parse.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(base_url) = &self.base_url {
+            match Url::parse(base_url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => return Err("base_url must be an http or https URL".to_string()),
+            }
+        }
+        self.keys.check().map_err(|e| format!("keys.{}", e))?;
+        self.usernames
+            .check_settings()
+            .map_err(|e| format!("usernames.{}", e))?;
+        Ok(())
+    }
+}
+
+/// Reads the app config and adds it to managed state. Settings that don't parse or don't make
+/// sense stop the launch, rather than quietly falling back to the defaults.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = match rocket.figment().extract::<AppConfig>() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to read the config: {}", e);
+            return Err(rocket);
+        }
+    };
+
+    if let Err(e) = config.check() {
+        error!("Invalid config: {}", e);
+        return Err(rocket);
+    }
+
+    Ok(rocket.manage(config))
+}
@@ -0,0 +1,184 @@
+//! Protects web forms from cross-site request forgery. Each browser session gets a random token
+//! in an encrypted cookie, forms send it back in a hidden field, and every web route that changes
+//! anything checks the two match with the `CsrfVerified` guard. Scripts send it in the
+//! `X-CSRF-Token` header instead, reading it from the page.
+//!
+//! The API isn't covered: changing anything through it needs an API token, which other sites
+//! can't send for a visitor, see `crate::api::access`.
+
+use std::io::Cursor;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Cookie, Method, SameSite, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use rocket_dyn_templates::handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+};
+use rocket_dyn_templates::Template;
+use subtle::ConstantTimeEq;
+
+use crate::util;
+
+/// The cookie that keeps the visitor's token for the browser session.
+static CSRF_COOKIE: &str = "csrf_token";
+
+/// The form field forms send the token in.
+static CSRF_FIELD: &str = "csrf_token";
+
+/// The header scripts send the token in.
+static CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// What the template helpers put in pages, filled in with the visitor's token as pages are sent.
+/// Only templates can make these tags, user content is always escaped or sanitized.
+static FIELD_PLACEHOLDER: &str = r#"<input type="hidden" name="csrf_token" value="">"#;
+static META_PLACEHOLDER: &str = r#"<meta name="csrf-token" content="">"#;
+
+/// How much of a form is searched for the token. Rocket can only peek at the start of a request
+/// body before the route reads it, so the field has to come first in forms, after `_method`.
+const PEEK_LIMIT: usize = 512;
+
+/// The visitor's token, see `Csrf`. Empty for API requests.
+#[derive(Debug, Clone, Default)]
+struct SessionToken(String);
+
+/// The token a request that changes something was sent with.
+#[derive(Debug, Clone, Default)]
+struct SentToken(Option<String>);
+
+/// Gives every browser session a token, reads the token requests were sent with for
+/// `CsrfVerified`, and fills the token in on pages.
+pub struct Csrf;
+
+#[rocket::async_trait]
+impl Fairing for Csrf {
+    fn info(&self) -> Info {
+        Info {
+            name: "CSRF Protection",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        if request.uri().path().starts_with("/api/") {
+            return;
+        }
+
+        let token = match request.cookies().get_private(CSRF_COOKIE) {
+            Some(cookie) => cookie.value().to_string(),
+            None => {
+                let token = util::secure_token();
+                let mut cookie = Cookie::new(CSRF_COOKIE, token.clone());
+                cookie.set_same_site(SameSite::Lax);
+                cookie.set_http_only(true);
+                request.cookies().add_private(cookie);
+                token
+            }
+        };
+        request.local_cache(|| SessionToken(token));
+
+        if matches!(request.method(), Method::Get | Method::Head | Method::Options) {
+            return;
+        }
+        let sent = match request.headers().get_one(CSRF_HEADER) {
+            Some(token) => Some(token.to_string()),
+            None => find_token(data.peek(PEEK_LIMIT).await),
+        };
+        request.local_cache(|| SentToken(sent));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let SessionToken(token) = request.local_cache(SessionToken::default);
+        if token.is_empty() || !response.content_type().is_some_and(|ct| ct.is_html()) {
+            return;
+        }
+
+        let body = match response.body_mut().to_string().await {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Couldn't read the response to add CSRF tokens: {}", e);
+                return;
+            }
+        };
+
+        let body = body
+            .replace(
+                FIELD_PLACEHOLDER,
+                &format!(r#"<input type="hidden" name="{}" value="{}">"#, CSRF_FIELD, token),
+            )
+            .replace(
+                META_PLACEHOLDER,
+                &format!(r#"<meta name="csrf-token" content="{}">"#, token),
+            );
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
+
+/// Finds the token field at the start of a form, either URL encoded or multipart. Tokens are URL
+/// safe, so they're never percent encoded.
+fn find_token(body: &[u8]) -> Option<String> {
+    let body = String::from_utf8_lossy(body);
+
+    let urlencoded = body
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(CSRF_FIELD)?.strip_prefix('='));
+    let multipart = || {
+        let start = body.find(&format!(r#"name="{}""#, CSRF_FIELD))?;
+        let value = &body[start..];
+        let value = &value[value.find("\r\n\r\n")? + 4..];
+        Some(&value[..value.find("\r\n")?])
+    };
+
+    urlencoded.or_else(multipart).map(|token| token.to_string())
+}
+
+/// A request sent with the visitor's token. Every web route that changes something takes this,
+/// requests without the token are turned away with `forbidden`.
+pub struct CsrfVerified;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfVerified {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let SessionToken(expected) = request.local_cache(SessionToken::default);
+        let SentToken(sent) = request.local_cache(SentToken::default);
+        // Compared in constant time, so response times don't give the token away
+        let matches = |sent: &str| bool::from(sent.as_bytes().ct_eq(expected.as_bytes()));
+        match sent {
+            Some(sent) if !expected.is_empty() && matches(sent) => Outcome::Success(CsrfVerified),
+            _ => Outcome::Failure((Status::Forbidden, ())),
+        }
+    }
+}
+
+/// Shows why a form was turned away by `CsrfVerified`.
+#[catch(403)]
+pub fn forbidden() -> Template {
+    Template::render("error/403", ())
+}
+
+/// The `csrf_field` template helper: `{{csrf_field}}` adds the visitor's token to a form, as its
+/// first field (see `PEEK_LIMIT`).
+pub fn field_helper(
+    _: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(FIELD_PLACEHOLDER)?;
+    Ok(())
+}
+
+/// The `csrf_meta` template helper: `{{csrf_meta}}` adds the visitor's token to a page's head,
+/// for scripts to send in the `X-CSRF-Token` header.
+pub fn meta_helper(
+    _: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(META_PLACEHOLDER)?;
+    Ok(())
+}
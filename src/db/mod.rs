@@ -1,8 +1,12 @@
+use std::ops::{Deref, DerefMut};
+
 use rocket::{fairing, Build, Rocket};
-use rocket_db_pools::{sqlx, Database};
+use rocket_db_pools::{sqlx, Connection, Database};
 use thiserror::Error;
 use validator::ValidationErrors;
 
+use crate::config::AppConfig;
+
 pub mod models;
 
 /// The database connection pool.
@@ -10,12 +14,11 @@ pub mod models;
 #[database("wishlists")]
 pub struct WishlistDb(sqlx::AnyPool);
 
-static DB_URL_CONFIG_KEY: &str = "databases.wishlists.url";
-
 /// Handles default database initialization.
 pub async fn default_db(rocket: Rocket<Build>) -> fairing::Result {
     // If the database is sqlite, make sure the file exists
-    if let Ok(url) = rocket.figment().extract_inner::<String>(DB_URL_CONFIG_KEY) {
+    let url = AppConfig::of(&rocket).databases.wishlists.url.clone();
+    if let Some(url) = url {
         if url.starts_with("sqlite:") {
             let path = std::path::Path::new(url.trim_start_matches("sqlite:"));
             match crate::util::ensure_file_exists(path, None) {
@@ -35,23 +38,26 @@ pub async fn default_db(rocket: Rocket<Build>) -> fairing::Result {
 /// Runs the database migrations.
 pub async fn run_migrations(rocket: Rocket<Build>) -> fairing::Result {
     match WishlistDb::fetch(&rocket) {
-        Some(db) => {
-            let mig = match db.any_kind() {
-                sqlx::any::AnyKind::Postgres => sqlx::migrate!("./migrations/postgres"),
-                sqlx::any::AnyKind::Sqlite => sqlx::migrate!("./migrations/sqlite"),
-            };
-            match mig.run(&**db).await {
-                Ok(_) => Ok(rocket),
-                Err(e) => {
-                    error!("Failed to initialize SQLx database: {}", e);
-                    Err(rocket)
-                }
+        Some(db) => match migrate(db).await {
+            Ok(_) => Ok(rocket),
+            Err(e) => {
+                error!("Failed to initialize SQLx database: {}", e);
+                Err(rocket)
             }
-        }
+        },
         None => Err(rocket),
     }
 }
 
+/// Brings the database's schema up to date.
+pub async fn migrate(pool: &sqlx::AnyPool) -> Result<(), sqlx::migrate::MigrateError> {
+    let mig = match pool.any_kind() {
+        sqlx::any::AnyKind::Postgres => sqlx::migrate!("./migrations/postgres"),
+        sqlx::any::AnyKind::Sqlite => sqlx::migrate!("./migrations/sqlite"),
+    };
+    mig.run(pool).await
+}
+
 #[derive(Error, Debug)]
 pub enum DataError {
     #[error("Invalid data: {0}")]
@@ -61,3 +67,76 @@ pub enum DataError {
     #[error("Data error: {0}")]
     Other(String),
 }
+
+/// A transaction on a request's connection.
+///
+/// Models take the transaction wherever they take a connection, and everything they do is kept
+/// or thrown away together when it's committed. Events dispatched in the transaction (see
+/// `crate::notify::Dispatcher`) are logged with it, so a change is never saved without its
+/// notifications or the other way around.
+///
+/// If it's dropped without being committed, e.g. on an early return, the connection is closed
+/// rather than put back in the pool, which rolls the transaction back.
+pub struct Transaction {
+    conn: Option<Connection<WishlistDb>>,
+    /// Events logged in the transaction, published once it's committed.
+    pub(crate) events: Vec<models::Event>,
+}
+
+impl Transaction {
+    /// Starts a transaction on the connection.
+    pub async fn begin(mut conn: Connection<WishlistDb>) -> Result<Transaction, sqlx::Error> {
+        sqlx::query("BEGIN").execute(&mut *conn).await?;
+        Ok(Transaction {
+            conn: Some(conn),
+            events: vec![],
+        })
+    }
+
+    /// Commits the transaction, handing back the connection and the events logged in it. See
+    /// `Dispatcher::commit`, which also publishes the events.
+    pub async fn commit(
+        mut self,
+    ) -> Result<(Connection<WishlistDb>, Vec<models::Event>), sqlx::Error> {
+        sqlx::query("COMMIT").execute(&mut **self).await?;
+        let events = std::mem::take(&mut self.events);
+        Ok((
+            self.conn.take().expect("transaction has a connection"),
+            events,
+        ))
+    }
+}
+
+impl Deref for Transaction {
+    type Target = Connection<WishlistDb>;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().expect("transaction has a connection")
+    }
+}
+
+impl DerefMut for Transaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().expect("transaction has a connection")
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            // Closing the connection rolls back whatever it was in the middle of
+            drop(conn.into_inner().detach());
+        }
+    }
+}
+
+/// A `LIKE` pattern for text containing `term`, with its wildcards escaped. Compare it with
+/// `LOWER(column) LIKE $1 ESCAPE '\'` so it matches regardless of case.
+pub fn contains_pattern(term: &str) -> String {
+    let escaped = term
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
@@ -0,0 +1,98 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A request for an archive of a user's data.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AccountExport {
+    pub id: i64,
+    pub user_id: i64,
+    /// The export's public identifier, used in its download link.
+    pub token: String,
+    /// One of `pending`, `ready` or `failed`.
+    pub status: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl AccountExport {
+    pub const PENDING: &'static str = "pending";
+    pub const READY: &'static str = "ready";
+    pub const FAILED: &'static str = "failed";
+
+    /// Starts a new export for the given user, returning the new export.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<AccountExport, DataError> {
+        let export = sqlx::query_as(
+            r#"
+            INSERT INTO account_exports (user_id, token, status, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, token, status, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(crate::util::random_token())
+        .bind(AccountExport::PENDING)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(export)
+    }
+
+    /// Returns the export with the given token, or `None` if no export with that token exists.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<AccountExport>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, token, status, created_at, updated_at
+            FROM account_exports
+            WHERE token = $1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the user's most recent export, or `None` if they haven't requested one.
+    pub async fn latest_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<AccountExport>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, token, status, created_at, updated_at
+            FROM account_exports
+            WHERE user_id = $1
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Whether the archive is still being generated.
+    pub fn is_pending(&self) -> bool {
+        self.status == AccountExport::PENDING
+    }
+
+    // ----- Jobs -----
+
+    /// Records whether the archive was generated.
+    pub async fn set_status(pool: &sqlx::AnyPool, id: i64, status: &str) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE account_exports SET status = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(status)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// The most API tokens each user can have.
+const MAX_API_TOKENS: i64 = 20;
+
+/// Something an API token can be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub enum ApiScope {
+    /// See lists, search and the user's stats.
+    #[serde(rename = "read:lists")]
+    ReadLists,
+    /// Make, change and delete lists.
+    #[serde(rename = "write:lists")]
+    WriteLists,
+    /// See items and whether they're claimed, and look up barcodes.
+    #[serde(rename = "read:items")]
+    ReadItems,
+    /// Add, change, delete and claim items, and upload images.
+    #[serde(rename = "write:items")]
+    WriteItems,
+    /// Everything, including admin endpoints the user has permission for.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 5] = [
+        ApiScope::ReadLists,
+        ApiScope::WriteLists,
+        ApiScope::ReadItems,
+        ApiScope::WriteItems,
+        ApiScope::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadLists => "read:lists",
+            ApiScope::WriteLists => "write:lists",
+            ApiScope::ReadItems => "read:items",
+            ApiScope::WriteItems => "write:items",
+            ApiScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ApiScope> {
+        ApiScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+    }
+
+    /// A description of the scope, for the tokens page.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApiScope::ReadLists => "See lists and search",
+            ApiScope::WriteLists => "Make, change and delete lists",
+            ApiScope::ReadItems => "See items",
+            ApiScope::WriteItems => "Add, change, delete and claim items",
+            ApiScope::Admin => "Everything, including admin tools",
+        }
+    }
+
+    /// Whether a token with this scope can do what needs `other`. Writing implies reading, and
+    /// admin implies everything.
+    pub fn covers(&self, other: ApiScope) -> bool {
+        match (self, other) {
+            (ApiScope::Admin, _) => true,
+            (ApiScope::WriteLists, ApiScope::ReadLists) => true,
+            (ApiScope::WriteItems, ApiScope::ReadItems) => true,
+            _ => *self == other,
+        }
+    }
+}
+
+/// Joins scopes for storing them.
+fn join_scopes(scopes: &[ApiScope]) -> String {
+    scopes
+        .iter()
+        .map(|scope| scope.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// What every token starts with, so they're easy to spot in config files and leaked secrets.
+pub const TOKEN_PREFIX: &str = "wl_";
+
+/// A personal access token a user can authenticate API calls with, sent as
+/// `Authorization: Bearer <token>`.
+///
+/// Only a hash of the token is stored, the token itself is shown to the user once when it's made.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    /// A name the user gave the token, e.g. "Phone app".
+    pub name: String,
+    /// The SHA-256 hash of the token, hex encoded.
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// The start of the token, to help the user tell their tokens apart.
+    pub token_prefix: String,
+    /// What the token can be used for, comma separated. See `ApiToken::scopes`.
+    pub scopes: String,
+    /// The only list the token can reach, or `None` if it can reach all of the user's lists.
+    pub list_id: Option<i64>,
+    /// When the token was last used to call the API.
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// How many API calls have been made with the token.
+    pub request_count: i64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl ApiToken {
+    /// Makes a new token for the user, returning it along with the token itself. Tokens limited
+    /// to one list should only be given one of the user's own lists.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        name: &str,
+        scopes: &[ApiScope],
+        list_id: Option<i64>,
+    ) -> Result<(ApiToken, String), DataError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 255 {
+            return Err(DataError::Other(
+                "Token name must be between 1 and 255 characters".to_string(),
+            ));
+        }
+        if scopes.is_empty() {
+            return Err(DataError::Other(
+                "Pick at least one thing the token can do".to_string(),
+            ));
+        }
+        if ApiToken::count_by_user(conn, user_id).await? >= MAX_API_TOKENS {
+            return Err(DataError::Other(format!(
+                "You can have at most {} tokens. Revoke one to make room.",
+                MAX_API_TOKENS
+            )));
+        }
+
+        let token = format!("{}{}", TOKEN_PREFIX, crate::util::random_token());
+        let api_token = sqlx::query_as(
+            r#"
+            INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, list_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, name, token_hash, token_prefix, scopes, list_id, last_used_at, request_count, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(crate::util::hash_token(&token))
+        .bind(&token[..TOKEN_PREFIX.len() + 6])
+        .bind(join_scopes(scopes))
+        .bind(list_id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok((api_token, token))
+    }
+
+    /// Returns all of the user's tokens.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<ApiToken>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, name, token_hash, token_prefix, scopes, list_id, last_used_at, request_count, created_at, updated_at
+            FROM api_tokens
+            WHERE user_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns how many tokens the user has.
+    pub async fn count_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM api_tokens WHERE user_id = $1"#)
+            .bind(user_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns the token matching the one a caller sent, or `None` if there isn't one.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, name, token_hash, token_prefix, scopes, list_id, last_used_at, request_count, created_at, updated_at
+            FROM api_tokens
+            WHERE token_hash = $1
+            "#,
+        )
+        .bind(crate::util::hash_token(token))
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// What the token can be used for. Unknown scopes, e.g. from a newer version, are ignored.
+    pub fn scopes(&self) -> Vec<ApiScope> {
+        self.scopes.split(',').filter_map(ApiScope::parse).collect()
+    }
+
+    /// Whether the token can do what needs the scope.
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes().iter().any(|granted| granted.covers(scope))
+    }
+
+    /// Whether the token can reach the list.
+    pub fn allows_list(&self, list_id: i64) -> bool {
+        self.list_id.is_none_or(|only| only == list_id)
+    }
+
+    /// Records a call made with the token.
+    pub async fn record_use(&self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        sqlx::query(
+            r#"UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP, request_count = request_count + 1 WHERE id = $1"#,
+        )
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Revokes one of the user's tokens.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM api_tokens WHERE id = $1 AND user_id = $2"#)
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Revokes tokens that haven't been used since the given time, counting tokens that were
+    /// never used from when they were made. Returns how many were revoked.
+    pub async fn destroy_unused_since(
+        pool: &sqlx::AnyPool,
+        since: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query(r#"DELETE FROM api_tokens WHERE COALESCE(last_used_at, created_at) < $1"#)
+                .bind(since)
+                .execute(pool)
+                .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,231 @@
+use rocket::serde::json::{serde_json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::models::{Item, List};
+use crate::db::{DataError, WishlistDb};
+use crate::pagination::Page;
+
+/// A change to a list or item, kept so owners and admins can see who changed what.
+///
+/// Events are written by the `List` and `Item` models whenever they save or delete, along with a
+/// JSON snapshot of the list or item before and after the change.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditEvent {
+    pub id: i64,
+    /// The user who made the change, or `None` if they weren't logged in.
+    pub user_id: Option<i64>,
+    /// The username of `user_id`, if their account still exists.
+    pub username: Option<String>,
+    /// What was changed, `AuditEvent::LIST` or `AuditEvent::ITEM`.
+    pub entity_type: String,
+    pub entity_id: i64,
+    /// The list that was changed, or the list the item is on.
+    pub list_id: i64,
+    /// `AuditEvent::CREATE`, `AuditEvent::UPDATE` or `AuditEvent::DELETE`.
+    pub action: String,
+    /// The list or item before the change, as JSON. `None` if it was just created.
+    pub old_values: Option<String>,
+    /// The list or item after the change, as JSON. `None` if it was deleted.
+    pub new_values: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A field an update changed, see `AuditEvent::changes`.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct AuditChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Fields that change on every save, so they're left out of `AuditEvent::changes`.
+const UNTRACKED_FIELDS: [&str; 2] = ["created_at", "updated_at"];
+
+/// Serializes a snapshot of a list or item for the log.
+fn snapshot<T: Serialize>(value: Option<&T>) -> Result<Option<String>, DataError> {
+    value
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| DataError::Other(e.to_string()))
+}
+
+impl AuditEvent {
+    pub const LIST: &'static str = "list";
+    pub const ITEM: &'static str = "item";
+
+    pub const CREATE: &'static str = "create";
+    pub const UPDATE: &'static str = "update";
+    pub const DELETE: &'static str = "delete";
+
+    /// Records a change to a list, made by `by` if they were logged in. `old` is the list before
+    /// the change and `new` is the list after it.
+    pub async fn record_list(
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+        action: &str,
+        old: Option<&List>,
+        new: Option<&List>,
+    ) -> Result<(), DataError> {
+        let id = new.or(old).map_or(0, |list| list.id);
+        let (old, new) = (snapshot(old)?, snapshot(new)?);
+        AuditEvent::new(by, AuditEvent::LIST, id, id, action, old, new)
+            .do_insert(conn)
+            .await
+    }
+
+    /// Records a change to an item, made by `by` if they were logged in. `old` is the item before
+    /// the change and `new` is the item after it.
+    pub async fn record_item(
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+        action: &str,
+        old: Option<&Item>,
+        new: Option<&Item>,
+    ) -> Result<(), DataError> {
+        let (id, list_id) = new.or(old).map_or((0, 0), |item| (item.id, item.list_id));
+        let (old, new) = (snapshot(old)?, snapshot(new)?);
+        AuditEvent::new(by, AuditEvent::ITEM, id, list_id, action, old, new)
+            .do_insert(conn)
+            .await
+    }
+
+    /// Returns a page of the changes to the list and its items, newest first.
+    pub async fn all_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        page: &Page,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT a.id, a.user_id, u.username, a.entity_type, a.entity_id, a.list_id, a.action, a.old_values, a.new_values, a.created_at
+            FROM audit_events a
+            LEFT JOIN users u ON u.id = a.user_id
+            WHERE a.list_id = $1
+            ORDER BY a.id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(list_id)
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns a page of the changes to every list and item, newest first.
+    pub async fn all(
+        conn: &mut Connection<WishlistDb>,
+        page: &Page,
+    ) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT a.id, a.user_id, u.username, a.entity_type, a.entity_id, a.list_id, a.action, a.old_values, a.new_values, a.created_at
+            FROM audit_events a
+            LEFT JOIN users u ON u.id = a.user_id
+            ORDER BY a.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    // ----- Misc -----
+
+    /// The fields an update changed, with their values before and after. Creations and deletions
+    /// have no changes.
+    pub fn changes(&self) -> Vec<AuditChange> {
+        let parse = |values: &Option<String>| {
+            values
+                .as_deref()
+                .and_then(|values| serde_json::from_str::<Value>(values).ok())
+        };
+        let (old, new) = match (parse(&self.old_values), parse(&self.new_values)) {
+            (Some(Value::Object(old)), Some(Value::Object(new))) => (old, new),
+            _ => return vec![],
+        };
+
+        new.into_iter()
+            .filter(|(field, _)| !UNTRACKED_FIELDS.contains(&field.as_str()))
+            .filter_map(|(field, new)| {
+                let old = old.get(&field).cloned().unwrap_or(Value::Null);
+                (old != new).then_some(AuditChange { field, old, new })
+            })
+            .collect()
+    }
+
+    /// The title of the list or item, as it was after the change, or before it if it was
+    /// deleted.
+    pub fn title(&self) -> Option<String> {
+        let values = self.new_values.as_ref().or(self.old_values.as_ref())?;
+        let values: Value = serde_json::from_str(values).ok()?;
+        values["title"].as_str().map(|title| title.to_string())
+    }
+
+    /// Returns the number of changes to the list and its items.
+    pub async fn count_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM audit_events WHERE list_id = $1"#)
+            .bind(list_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns the number of changes to every list and item.
+    pub async fn count(conn: &mut Connection<WishlistDb>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM audit_events"#)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    // ----- Internal -----
+
+    fn new(
+        by: Option<i64>,
+        entity_type: &str,
+        entity_id: i64,
+        list_id: i64,
+        action: &str,
+        old_values: Option<String>,
+        new_values: Option<String>,
+    ) -> AuditEvent {
+        AuditEvent {
+            id: 0,
+            user_id: by,
+            username: None,
+            entity_type: entity_type.to_string(),
+            entity_id,
+            list_id,
+            action: action.to_string(),
+            old_values,
+            new_values,
+            created_at: chrono::NaiveDateTime::default(),
+        }
+    }
+
+    async fn do_insert(self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_events (user_id, entity_type, entity_id, list_id, action, old_values, new_values, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(self.user_id)
+        .bind(self.entity_type)
+        .bind(self.entity_id)
+        .bind(self.list_id)
+        .bind(self.action)
+        .bind(self.old_values)
+        .bind(self.new_values)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+}
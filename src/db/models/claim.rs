@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::models::ClaimEvent;
+use crate::db::{DataError, WishlistDb};
+
+/// Someone saying they're getting an item, so other people don't buy it too.
+///
+/// Claims are gifting activity, so the list owner only sees them if they've turned on
+/// `List::reveal_gifting`, see `crate::surprise`. Every change to a claim is also recorded as a
+/// `ClaimEvent`, so callers should run these in a transaction.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Claim {
+    pub id: i64,
+    pub item_id: i64,
+    pub user_id: i64,
+    /// When the claimer said they bought the item, or `None` if they haven't yet.
+    pub purchased_at: Option<chrono::NaiveDateTime>,
+    /// When the claimer was emailed a reminder to get the item, see
+    /// `crate::jobs::claim_reminders`.
+    pub reminded_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// A claimed item on a list with an event date coming up, for reminding the claimer to get it.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ClaimReminder {
+    pub claim_id: i64,
+    pub item_id: i64,
+    pub item_title: String,
+    pub list_id: i64,
+    pub list_key: String,
+    pub list_title: String,
+    pub event_date: chrono::NaiveDate,
+}
+
+/// A claim due a reminder email, with what the email needs.
+#[derive(sqlx::FromRow, Debug)]
+pub struct DueClaimReminder {
+    pub claim_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    pub user_email: String,
+    pub item_id: i64,
+    pub item_title: String,
+    pub list_key: String,
+    pub list_title: String,
+    pub event_date: chrono::NaiveDate,
+}
+
+impl Claim {
+    /// Claims the item for the user. Each item can only be claimed by one person, so check
+    /// `find_by_item` first.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+    ) -> Result<Claim, DataError> {
+        let claim: Claim = sqlx::query_as(
+            r#"
+            INSERT INTO claims (item_id, user_id, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, user_id, purchased_at, reminded_at, created_at, updated_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        ClaimEvent::record(conn, claim.id, item_id, user_id, Some(user_id), ClaimEvent::CLAIMED)
+            .await?;
+
+        Ok(claim)
+    }
+
+    /// Returns the item's claim, or `None` if nobody has claimed it.
+    pub async fn find_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Option<Claim>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, user_id, purchased_at, reminded_at, created_at, updated_at
+            FROM claims
+            WHERE item_id = $1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns who claimed each claimed item on the list, by item ID.
+    pub async fn claimers_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<HashMap<i64, i64>, sqlx::Error> {
+        let claims: Vec<(i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT c.item_id, c.user_id
+            FROM claims c
+            JOIN items i ON i.id = c.item_id
+            WHERE i.list_id = $1
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await?;
+
+        Ok(claims.into_iter().collect())
+    }
+
+    /// Returns the user's claims on items that haven't been bought or received yet, on lists they
+    /// can still see whose event date is today or later, soonest first.
+    pub async fn upcoming_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        today: chrono::NaiveDate,
+    ) -> Result<Vec<ClaimReminder>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT c.id AS claim_id, i.id AS item_id, i.title AS item_title,
+                l.id AS list_id, l.key AS list_key, l.title AS list_title, l.event_date
+            FROM claims c
+            JOIN items i ON i.id = c.item_id
+            JOIN lists l ON l.id = i.list_id
+            WHERE c.user_id = $1 AND c.purchased_at IS NULL AND i.received_at IS NULL
+                AND l.event_date >= $2
+                AND (l.privacy <> 'private' OR l.user_id = $1)
+            ORDER BY l.event_date, c.id
+            "#,
+        )
+        .bind(user_id)
+        .bind(today)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Records that the user bought the item they claimed, or that they haven't after all.
+    /// Returns true if they have a claim on it.
+    pub async fn set_purchased(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+        purchased: bool,
+    ) -> Result<bool, DataError> {
+        let claim = match Claim::find_by_item(conn, item_id).await? {
+            Some(claim) if claim.user_id == user_id => claim,
+            _ => return Ok(false),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE claims
+            SET purchased_at = CASE WHEN $2 THEN COALESCE(purchased_at, CURRENT_TIMESTAMP) ELSE NULL END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+        )
+        .bind(claim.id)
+        .bind(purchased)
+        .execute(&mut **conn)
+        .await?;
+
+        let kind = if purchased {
+            ClaimEvent::PURCHASED
+        } else {
+            ClaimEvent::UNPURCHASED
+        };
+        ClaimEvent::record(conn, claim.id, item_id, user_id, Some(user_id), kind).await?;
+        Ok(true)
+    }
+
+    /// Removes the user's claim on the item, returning true if they had one.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+    ) -> Result<bool, DataError> {
+        let claim = match Claim::find_by_item(conn, item_id).await? {
+            Some(claim) if claim.user_id == user_id => claim,
+            _ => return Ok(false),
+        };
+
+        claim.do_delete(conn).await?;
+        ClaimEvent::record(conn, claim.id, item_id, user_id, Some(user_id), ClaimEvent::RELEASED)
+            .await?;
+        Ok(true)
+    }
+
+    /// Removes the item's claim, if it has one, recording that it expired because of `by`.
+    pub async fn destroy_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        by: Option<i64>,
+    ) -> Result<(), DataError> {
+        if let Some(claim) = Claim::find_by_item(conn, item_id).await? {
+            claim.do_delete(conn).await?;
+            ClaimEvent::record(conn, claim.id, item_id, claim.user_id, by, ClaimEvent::EXPIRED)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Returns claims that haven't been bought, received or reminded about yet, on lists whose
+    /// event date is between `today` and `until`, for claimers who want reminders.
+    pub async fn all_due_reminders(
+        pool: &sqlx::AnyPool,
+        today: chrono::NaiveDate,
+        until: chrono::NaiveDate,
+    ) -> Result<Vec<DueClaimReminder>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT c.id AS claim_id, u.id AS user_id, u.username, u.email AS user_email,
+                i.id AS item_id, i.title AS item_title, l.key AS list_key, l.title AS list_title,
+                l.event_date
+            FROM claims c
+            JOIN users u ON u.id = c.user_id
+            JOIN items i ON i.id = c.item_id
+            JOIN lists l ON l.id = i.list_id
+            WHERE c.purchased_at IS NULL AND c.reminded_at IS NULL AND i.received_at IS NULL
+                AND l.event_date >= $1 AND l.event_date <= $2
+                AND (l.privacy <> 'private' OR l.user_id = u.id)
+                AND u.claim_reminders IS TRUE AND u.suspended_at IS NULL
+            ORDER BY u.id, l.event_date, c.id
+            "#,
+        )
+        .bind(today)
+        .bind(until)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records that the claimer is being reminded about the claim. Returns false if they already
+    /// were, e.g. by another run of the job, so each claim is only reminded about once.
+    pub async fn mark_reminded(pool: &sqlx::AnyPool, id: i64) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"UPDATE claims SET reminded_at = CURRENT_TIMESTAMP WHERE id = $1 AND reminded_at IS NULL"#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Forgets that the claimer was reminded, so the reminder is tried again on the next run.
+    pub async fn unmark_reminded(pool: &sqlx::AnyPool, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE claims SET reminded_at = NULL WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Internal -----
+
+    async fn do_delete(&self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM claims WHERE id = $1"#)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+use crate::pagination::Page;
+
+/// Something that happened to a claim, kept so "someone took my claim" disputes can be looked
+/// into. Events are only ever added, by the `Claim` model whenever it changes a claim, and are
+/// kept when their item is deleted.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ClaimEvent {
+    pub id: i64,
+    /// The claim the event happened to. Claims are deleted when they end, so this can point to
+    /// a claim that's gone.
+    pub claim_id: i64,
+    /// The item, or `None` if it was deleted.
+    pub item_id: Option<i64>,
+    /// The item's title and its list's key when the event happened, so they're still known once
+    /// the item is gone.
+    pub item_title: String,
+    pub list_key: String,
+    /// Who the claim was for, or `None` if their account was deleted.
+    pub user_id: Option<i64>,
+    pub username: Option<String>,
+    /// Who did it, `None` if it was done by the site or their account was deleted. This is the
+    /// claimer, except for claims that expired because someone marked the item received.
+    pub actor_id: Option<i64>,
+    pub actor_username: Option<String>,
+    /// `ClaimEvent::CLAIMED`, `RELEASED`, `EXPIRED`, `PURCHASED` or `UNPURCHASED`.
+    pub kind: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ClaimEvent {
+    /// The item was claimed.
+    pub const CLAIMED: &'static str = "claimed";
+    /// The claimer gave the claim up.
+    pub const RELEASED: &'static str = "released";
+    /// The claim ended without the claimer giving it up, when a consumable was received.
+    pub const EXPIRED: &'static str = "expired";
+    /// The claimer said they bought the item.
+    pub const PURCHASED: &'static str = "purchased";
+    /// The claimer said they haven't bought the item after all.
+    pub const UNPURCHASED: &'static str = "unpurchased";
+
+    /// Records that `kind` happened to the claim, done by `by`.
+    pub async fn record(
+        conn: &mut Connection<WishlistDb>,
+        claim_id: i64,
+        item_id: i64,
+        user_id: i64,
+        by: Option<i64>,
+        kind: &str,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO claim_events (claim_id, item_id, item_title, list_key, user_id, actor_id, kind, created_at)
+            SELECT $1, i.id, i.title, l.key, $3, $4, $5, CURRENT_TIMESTAMP
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE i.id = $2
+            "#,
+        )
+        .bind(claim_id)
+        .bind(item_id)
+        .bind(user_id)
+        .bind(by)
+        .bind(kind)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the item's whole claim history, oldest first.
+    pub async fn all_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Vec<ClaimEvent>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT e.id, e.claim_id, e.item_id, e.item_title, e.list_key, e.user_id, u.username, e.actor_id, a.username AS actor_username, e.kind, e.created_at
+            FROM claim_events e
+            LEFT JOIN users u ON u.id = e.user_id
+            LEFT JOIN users a ON a.id = e.actor_id
+            WHERE e.item_id = $1
+            ORDER BY e.id
+            "#,
+        )
+        .bind(item_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns a page of every claim event, newest first.
+    pub async fn all(
+        conn: &mut Connection<WishlistDb>,
+        page: &Page,
+    ) -> Result<Vec<ClaimEvent>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT e.id, e.claim_id, e.item_id, e.item_title, e.list_key, e.user_id, u.username, e.actor_id, a.username AS actor_username, e.kind, e.created_at
+            FROM claim_events e
+            LEFT JOIN users u ON u.id = e.user_id
+            LEFT JOIN users a ON a.id = e.actor_id
+            ORDER BY e.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(page.limit())
+        .bind(page.offset())
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the number of claim events.
+    pub async fn count(conn: &mut Connection<WishlistDb>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM claim_events"#)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns whether the user has ever claimed the item.
+    pub async fn has_claimed(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM claim_events WHERE item_id = $1 AND user_id = $2"#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await?;
+        Ok(count > 0)
+    }
+}
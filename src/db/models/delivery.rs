@@ -0,0 +1,193 @@
+use rocket::serde::json::Value;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A webhook post or email waiting in the outbox.
+///
+/// Deliveries are written in the same transaction as whatever they're about, and the
+/// notification worker (see `crate::notify::Dispatcher`) sends them from there, retrying failures
+/// with a growing delay. So nothing is lost if the server stops mid-request or the target is
+/// down for a while.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Delivery {
+    pub id: i64,
+    /// Either `webhook` or `email`.
+    pub kind: String,
+    /// The list webhook being posted to, for webhook deliveries that aren't for a REST hook.
+    pub webhook_id: Option<i64>,
+    /// The REST hook being posted to, see `HookSubscription`. Not a foreign key, so posts that
+    /// were queued before it was removed still go out.
+    pub subscription_id: Option<i64>,
+    /// The URL to post to, or the address to email.
+    pub target: String,
+    /// The email's subject, for plain text email deliveries.
+    pub subject: Option<String>,
+    /// The email template to render when sending, see `crate::mail::EmailTemplates`.
+    pub template: Option<String>,
+    /// The JSON to post, the email template's JSON context, or a plain text email's body.
+    pub body: String,
+    /// The webhook post's signature, sent in the `X-Wishlist-Signature` header. See
+    /// `crate::notify::sign`.
+    pub signature: Option<String>,
+    /// How many times sending has failed.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// When to try sending next.
+    pub next_attempt_at: chrono::NaiveDateTime,
+    pub sent_at: Option<chrono::NaiveDateTime>,
+    /// When the delivery was given up on, after `MAX_ATTEMPTS` failures.
+    pub failed_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl Delivery {
+    pub const WEBHOOK: &'static str = "webhook";
+    pub const EMAIL: &'static str = "email";
+
+    /// How many times sending is tried before the delivery is given up on. With the delay
+    /// doubling from a minute, the last try is a couple of hours after the first.
+    pub const MAX_ATTEMPTS: i64 = 8;
+
+    /// Queues an email rendered from the named template with the given context, to be sent once
+    /// the connection's transaction is committed. It's rendered when it's sent, so template
+    /// changes apply to emails that are still waiting.
+    pub async fn queue_email(
+        conn: &mut Connection<WishlistDb>,
+        to: &str,
+        template: &str,
+        context: &Value,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO deliveries (kind, target, template, body, next_attempt_at, created_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(Delivery::EMAIL)
+        .bind(to)
+        .bind(template)
+        .bind(context.to_string())
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Queues a post to a webhook, to be sent once the connection's transaction is committed.
+    /// `webhook_id` is the list webhook being posted to, if it's one, and `subscription_id` the
+    /// REST hook if it's one of those instead.
+    pub async fn queue_webhook(
+        conn: &mut Connection<WishlistDb>,
+        webhook_id: Option<i64>,
+        subscription_id: Option<i64>,
+        url: &str,
+        body: &str,
+        signature: Option<&str>,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO deliveries (kind, webhook_id, subscription_id, target, body, signature, next_attempt_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(Delivery::WEBHOOK)
+        .bind(webhook_id)
+        .bind(subscription_id)
+        .bind(url)
+        .bind(body)
+        .bind(signature)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Queues an email rendered from the named template from a background job.
+    pub async fn enqueue_email(
+        pool: &sqlx::AnyPool,
+        to: &str,
+        template: &str,
+        context: &Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO deliveries (kind, target, template, body, next_attempt_at, created_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(Delivery::EMAIL)
+        .bind(to)
+        .bind(template)
+        .bind(context.to_string())
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the deliveries that are due to be sent, oldest first.
+    pub async fn next_due(pool: &sqlx::AnyPool, limit: i64) -> Result<Vec<Delivery>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, kind, webhook_id, subscription_id, target, subject, template, body,
+                signature, attempts, last_error, next_attempt_at, sent_at, failed_at, created_at
+            FROM deliveries
+            WHERE sent_at IS NULL AND failed_at IS NULL AND next_attempt_at <= CURRENT_TIMESTAMP
+            ORDER BY next_attempt_at, id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records that the delivery was sent.
+    pub async fn mark_sent(&self, pool: &sqlx::AnyPool) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE deliveries SET sent_at = CURRENT_TIMESTAMP WHERE id = $1"#)
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Gives up on the delivery without trying it again, e.g. because the address is suppressed.
+    pub async fn give_up(&self, pool: &sqlx::AnyPool, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE deliveries SET last_error = $1, failed_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(error)
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Records that sending failed, and either schedules the next try or gives up after
+    /// `MAX_ATTEMPTS` failures.
+    pub async fn mark_failed(&self, pool: &sqlx::AnyPool, error: &str) -> Result<(), sqlx::Error> {
+        let attempts = self.attempts + 1;
+        let now = chrono::Utc::now().naive_utc();
+        let (next_attempt_at, failed_at) = if attempts >= Delivery::MAX_ATTEMPTS {
+            (now, Some(now))
+        } else {
+            (now + chrono::Duration::minutes(1 << (attempts - 1)), None)
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE deliveries
+            SET attempts = $1, last_error = $2, next_attempt_at = $3, failed_at = $4
+            WHERE id = $5
+            "#,
+        )
+        .bind(attempts)
+        .bind(error)
+        .bind(next_attempt_at)
+        .bind(failed_at)
+        .bind(self.id)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// An address that isn't emailed anymore, because it bounced permanently or its owner marked an
+/// email as spam. See `crate::mail::bounces`.
+///
+/// Addresses are stored lowercase, so they match however they're written.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EmailSuppression {
+    pub id: i64,
+    pub email: String,
+    /// Either `bounce` or `complaint`.
+    pub reason: String,
+    /// What the provider said about it, like the receiving server's error.
+    pub detail: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl EmailSuppression {
+    pub const BOUNCE: &'static str = "bounce";
+    pub const COMPLAINT: &'static str = "complaint";
+
+    /// Suppresses the address, or updates why it's suppressed if it already is.
+    pub async fn record(
+        conn: &mut Connection<WishlistDb>,
+        email: &str,
+        reason: &str,
+        detail: Option<&str>,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO email_suppressions (email, reason, detail, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (email)
+            DO UPDATE SET reason = $2, detail = $3, updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(email.to_lowercase())
+        .bind(reason)
+        .bind(detail)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the suppression for the address, or `None` if it can be emailed.
+    pub async fn find_by_email(
+        conn: &mut Connection<WishlistDb>,
+        email: &str,
+    ) -> Result<Option<EmailSuppression>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, email, reason, detail, created_at, updated_at
+            FROM email_suppressions
+            WHERE email = $1
+            "#,
+        )
+        .bind(email.to_lowercase())
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Stops suppressing the address, e.g. once its owner has fixed their mailbox.
+    pub async fn destroy_by_email(
+        conn: &mut Connection<WishlistDb>,
+        email: &str,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM email_suppressions WHERE email = $1"#)
+            .bind(email.to_lowercase())
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Returns true if the address shouldn't be emailed.
+    pub async fn is_suppressed(pool: &sqlx::AnyPool, email: &str) -> Result<bool, sqlx::Error> {
+        let count: i64 =
+            sqlx::query_scalar(r#"SELECT COUNT(*) FROM email_suppressions WHERE email = $1"#)
+                .bind(email.to_lowercase())
+                .fetch_one(pool)
+                .await?;
+        Ok(count > 0)
+    }
+}
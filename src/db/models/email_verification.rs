@@ -0,0 +1,76 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A link sent to a new account's email address, proving the address is theirs once it's
+/// opened. See `User::email_verified_at`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EmailVerification {
+    pub id: i64,
+    pub user_id: i64,
+    /// The address the link was sent to. It only verifies the account while that's still the
+    /// account's address.
+    pub email: String,
+    #[serde(skip_serializing)]
+    pub token: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl EmailVerification {
+    /// Creates a verification for the user's address, returning the new verification with the
+    /// token for its link.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        email: &str,
+    ) -> Result<EmailVerification, DataError> {
+        let verification = sqlx::query_as(
+            r#"
+            INSERT INTO email_verifications (user_id, email, token, created_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, email, token, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(email)
+        .bind(crate::util::random_token())
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(verification)
+    }
+
+    /// Returns the verification with the given token, or `None` if it doesn't exist or was
+    /// created before `created_after`.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+        created_after: chrono::NaiveDateTime,
+    ) -> Result<Option<EmailVerification>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, email, token, created_at
+            FROM email_verifications
+            WHERE token = $1 AND created_at > $2
+            "#,
+        )
+        .bind(token)
+        .bind(created_after)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Removes all of the user's verifications, once one has been used.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM email_verifications WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+use crate::events::DomainEvent;
+
+/// An entry in the event log, see `DomainEvent`.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Event {
+    pub id: i64,
+    /// See `DomainEvent::kind`.
+    pub kind: String,
+    pub list_id: Option<i64>,
+    pub item_id: Option<i64>,
+    /// The user who made the change, if they were logged in.
+    pub user_id: Option<i64>,
+    /// The whole `DomainEvent`, as JSON.
+    pub payload: String,
+    pub created_at: chrono::NaiveDateTime,
+    /// When the event was posted to Matrix, or `None` if it hasn't been yet. Webhook posts are
+    /// queued as `Delivery`s when the event is logged.
+    pub dispatched_at: Option<chrono::NaiveDateTime>,
+}
+
+/// How many times something happened, for analytics.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EventCount {
+    pub kind: String,
+    pub count: i64,
+}
+
+impl Event {
+    /// Adds the event to the log, returning the new entry.
+    pub async fn record(
+        conn: &mut Connection<WishlistDb>,
+        event: &DomainEvent,
+    ) -> Result<Event, DataError> {
+        let payload = serde_json::to_string(event).map_err(|e| DataError::Other(e.to_string()))?;
+
+        let entry = sqlx::query_as(
+            r#"
+            INSERT INTO events (kind, list_id, item_id, user_id, payload, created_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)
+            RETURNING id, kind, list_id, item_id, user_id, payload, created_at, dispatched_at
+            "#,
+        )
+        .bind(event.kind())
+        .bind(event.list().id)
+        .bind(event.item().map(|item| item.id))
+        .bind(event.by())
+        .bind(payload)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// Returns the list's events after the given one, oldest first.
+    pub async fn all_by_list_after(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Event>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, kind, list_id, item_id, user_id, payload, created_at, dispatched_at
+            FROM events
+            WHERE list_id = $1 AND id > $2
+            ORDER BY id
+            LIMIT $3
+            "#,
+        )
+        .bind(list_id)
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns how many of each kind of event happened since the given time.
+    pub async fn counts_since(
+        conn: &mut Connection<WishlistDb>,
+        since: chrono::NaiveDateTime,
+    ) -> Result<Vec<EventCount>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT kind, COUNT(*) AS count
+            FROM events
+            WHERE created_at >= $1
+            GROUP BY kind
+            ORDER BY kind
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// The event itself, or `None` if it was logged by a version that had events this one
+    /// doesn't know about.
+    pub fn domain_event(&self) -> Option<DomainEvent> {
+        serde_json::from_str(&self.payload).ok()
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the oldest events whose notifications haven't been sent yet.
+    pub async fn next_undispatched(
+        pool: &sqlx::AnyPool,
+        limit: i64,
+    ) -> Result<Vec<Event>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, kind, list_id, item_id, user_id, payload, created_at, dispatched_at
+            FROM events
+            WHERE dispatched_at IS NULL
+            ORDER BY id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records that the event's notifications were sent.
+    pub async fn mark_dispatched(pool: &sqlx::AnyPool, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE events SET dispatched_at = CURRENT_TIMESTAMP WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// An admin's choice to turn an instance feature on or off, overriding Rocket.toml. See
+/// `crate::features`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeatureFlag {
+    pub id: i64,
+    /// See `Feature::as_str`.
+    pub name: String,
+    pub enabled: bool,
+    /// The admin who last changed it, or `None` if they've since been deleted.
+    pub updated_by: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl FeatureFlag {
+    /// Returns every feature an admin has changed, for loading at startup.
+    pub async fn all(pool: &sqlx::AnyPool) -> Result<Vec<FeatureFlag>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, name, enabled, updated_by, created_at, updated_at
+            FROM feature_flags
+            ORDER BY id
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Turns the feature on or off.
+    pub async fn set(
+        conn: &mut Connection<WishlistDb>,
+        name: &str,
+        enabled: bool,
+        updated_by: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO feature_flags (name, enabled, updated_by, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (name)
+            DO UPDATE SET enabled = $2, updated_by = $3, updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(name)
+        .bind(enabled)
+        .bind(updated_by)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use url::Url;
+use validator::{Validate, ValidationError};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A link to somewhere people can send the user money, like PayPal.me or Ko-fi. Shown as a
+/// "fund this" button on the user's items. Payments never go through the wishlist itself.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FundLink {
+    pub id: i64,
+    pub user_id: i64,
+    #[validate(
+        length(max = 2048, message = "Link must be less than 2048 characters"),
+        custom = "validate_fund_url"
+    )]
+    pub url: String,
+    /// How many times the link has been followed.
+    pub click_count: i64,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Only allows https links, since people will be paying through them.
+fn validate_fund_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "https" && url.has_host() && url.username().is_empty() => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("url");
+            err.message = Some(Cow::from("Link must be an https URL"));
+            Err(err)
+        }
+    }
+}
+
+impl FundLink {
+    /// Sets the user's fund link, replacing any previous one. The click count carries over.
+    pub async fn set(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        url: &str,
+    ) -> Result<FundLink, DataError> {
+        let link = FundLink {
+            id: 0,
+            user_id,
+            url: url.trim().to_string(),
+            click_count: 0,
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        link.validate()?;
+
+        let link = sqlx::query_as(
+            r#"
+            INSERT INTO fund_links (user_id, url, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id)
+            DO UPDATE SET url = $2, updated_at = CURRENT_TIMESTAMP
+            RETURNING id, user_id, url, click_count, created_at, updated_at
+            "#,
+        )
+        .bind(link.user_id)
+        .bind(link.url)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Returns the user's fund link, or `None` if they haven't set one.
+    pub async fn find_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<FundLink>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, url, click_count, created_at, updated_at
+            FROM fund_links
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Removes the user's fund link.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM fund_links WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Counts a visit to the link.
+    pub async fn record_click(conn: &mut Connection<WishlistDb>, id: i64) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE fund_links SET click_count = click_count + 1 WHERE id = $1"#)
+            .bind(id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A group gift, where several people chip in for one item and pay the organizer back.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GiftSplit {
+    pub id: i64,
+    pub item_id: i64,
+    /// The user who buys the item and collects everyone's shares.
+    pub organizer_id: i64,
+    /// The total to split, in the smallest unit of its currency (e.g. cents).
+    pub total_cents: i64,
+    /// An ISO 4217 currency code.
+    pub currency: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Someone chipping in on a group gift.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct GiftContributor {
+    pub id: i64,
+    pub split_id: i64,
+    pub user_id: i64,
+    pub username: String,
+    /// When the organizer was paid back, or `None` if they're still owed.
+    pub paid_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl GiftSplit {
+    /// Starts splitting the item, with the organizer as the first contributor. The organizer
+    /// doesn't owe themselves, so their share starts out paid.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        organizer_id: i64,
+        total_cents: i64,
+        currency: &str,
+    ) -> Result<GiftSplit, DataError> {
+        if total_cents <= 0 {
+            return Err(DataError::Other("Total must be more than zero".to_string()));
+        }
+
+        let split: GiftSplit = sqlx::query_as(
+            r#"
+            INSERT INTO gift_splits (item_id, organizer_id, total_cents, currency, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, organizer_id, total_cents, currency, created_at, updated_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(organizer_id)
+        .bind(total_cents)
+        .bind(currency)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO gift_contributors (split_id, user_id, paid_at, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(split.id)
+        .bind(organizer_id)
+        .execute(&mut **conn)
+        .await?;
+
+        Ok(split)
+    }
+
+    /// Returns the item's split, or `None` if nobody has started one.
+    pub async fn find_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Option<GiftSplit>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, organizer_id, total_cents, currency, created_at, updated_at
+            FROM gift_splits
+            WHERE item_id = $1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Changes the total to split, returning an updated copy of the split.
+    pub async fn set_total(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        total_cents: i64,
+    ) -> Result<GiftSplit, DataError> {
+        if total_cents <= 0 {
+            return Err(DataError::Other("Total must be more than zero".to_string()));
+        }
+
+        let split = sqlx::query_as(
+            r#"
+            UPDATE gift_splits
+            SET total_cents = $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, item_id, organizer_id, total_cents, currency, created_at, updated_at
+            "#,
+        )
+        .bind(total_cents)
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(split)
+    }
+
+    /// Deletes the split and everyone's contributions.
+    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        if self.id != 0 {
+            sqlx::query(r#"DELETE FROM gift_splits WHERE id = $1"#)
+                .bind(self.id)
+                .execute(&mut **conn)
+                .await?;
+            self.id = 0;
+        }
+        Ok(())
+    }
+
+    /// Returns the split's contributors, in the order they joined.
+    pub async fn contributors(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<Vec<GiftContributor>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT c.id, c.split_id, c.user_id, u.username, c.paid_at, c.created_at
+            FROM gift_contributors c
+            JOIN users u ON u.id = c.user_id
+            WHERE c.split_id = $1
+            ORDER BY c.created_at, c.id
+            "#,
+        )
+        .bind(self.id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Adds the user as a contributor. Does nothing if they already are one.
+    pub async fn join(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO gift_contributors (split_id, user_id, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (split_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the user from the split, unless they've already paid.
+    pub async fn leave(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"DELETE FROM gift_contributors WHERE split_id = $1 AND user_id = $2 AND paid_at IS NULL"#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Records whether the contributor has paid the organizer back.
+    pub async fn set_paid(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        paid: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            UPDATE gift_contributors
+            SET paid_at = CASE WHEN $1 THEN CURRENT_TIMESTAMP ELSE NULL END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE split_id = $2 AND user_id = $3
+            "#,
+        )
+        .bind(paid)
+        .bind(self.id)
+        .bind(user_id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    // ----- Misc -----
+
+    /// Splits the total into `count` shares. Shares differ by at most one cent, with the extra
+    /// cents going to the first contributors.
+    pub fn shares(&self, count: usize) -> Vec<i64> {
+        if count == 0 {
+            return vec![];
+        }
+        let count = count as i64;
+        let base = self.total_cents / count;
+        let extra = self.total_cents % count;
+        (0..count)
+            .map(|i| if i < extra { base + 1 } else { base })
+            .collect()
+    }
+}
@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use url::Url;
+use validator::{Validate, ValidationError};
+
+use crate::db::{DataError, WishlistDb};
+use crate::events::DomainEvent;
+use crate::sources;
+
+/// A REST hook, an automation service like Zapier asking to be sent one kind of event on the
+/// user's lists. Unlike a `ListWebhook`, it's made and removed through the API by the service
+/// itself, see `crate::api::v1::hooks`.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HookSubscription {
+    pub id: i64,
+    pub user_id: i64,
+    /// The only list the hook is sent events for, or `None` for all of the user's lists.
+    pub list_id: Option<i64>,
+    /// See `DomainEvent::kind`.
+    #[validate(custom = "validate_hook_event")]
+    pub event: String,
+    /// Where events are posted.
+    #[validate(
+        length(max = 2048, message = "Target URL must be less than 2048 characters"),
+        custom = "validate_target_url"
+    )]
+    pub target_url: String,
+    /// The key posts are signed with, see `crate::notify::sign`. Empty for hooks made before
+    /// posts were signed.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Only allows https targets, since the URL itself is the secret.
+fn validate_target_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "https" && url.has_host() => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("target_url");
+            err.message = Some(Cow::from("Target URL must be an https URL"));
+            Err(err)
+        }
+    }
+}
+
+fn validate_hook_event(event: &str) -> Result<(), ValidationError> {
+    if DomainEvent::KINDS.contains(&event) {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("event");
+        err.message = Some(Cow::from("Unknown event"));
+        Err(err)
+    }
+}
+
+impl HookSubscription {
+    /// Subscribes the target URL to the given kind of event on the user's lists, or on just one
+    /// of them. Returns the new subscription.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        list_id: Option<i64>,
+        event: &str,
+        target_url: &str,
+    ) -> Result<HookSubscription, DataError> {
+        let subscription = HookSubscription {
+            id: 0,
+            user_id,
+            list_id,
+            event: event.to_string(),
+            target_url: target_url.trim().to_string(),
+            secret: crate::util::secure_token(),
+            created_at: chrono::NaiveDateTime::default(),
+        };
+        subscription.validate()?;
+        sources::validate_public(
+            &subscription.target_url,
+            "target_url",
+            "Target URL must be on a public host",
+        )
+        .await?;
+
+        let subscription = sqlx::query_as(
+            r#"
+            INSERT INTO hook_subscriptions (user_id, list_id, event, target_url, secret, created_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, list_id, event, target_url, secret, created_at
+            "#,
+        )
+        .bind(subscription.user_id)
+        .bind(subscription.list_id)
+        .bind(subscription.event)
+        .bind(subscription.target_url)
+        .bind(subscription.secret)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    /// Returns the subscriptions for the given kind of event on the list, which is owned by the
+    /// given user.
+    pub async fn all_for_event(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        list_id: i64,
+        event: &str,
+    ) -> Result<Vec<HookSubscription>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, list_id, event, target_url, secret, created_at
+            FROM hook_subscriptions
+            WHERE user_id = $1 AND (list_id IS NULL OR list_id = $2) AND event = $3
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .bind(list_id)
+        .bind(event)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Removes one of the user's subscriptions. Returns whether there was one to remove.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        id: i64,
+    ) -> Result<bool, DataError> {
+        let result =
+            sqlx::query(r#"DELETE FROM hook_subscriptions WHERE id = $1 AND user_id = $2"#)
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut **conn)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ----- Jobs -----
+
+    /// Removes the subscription, for when the service says it's gone.
+    pub async fn destroy_gone(pool: &sqlx::AnyPool, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM hook_subscriptions WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -1,17 +1,189 @@
 use rocket::serde::{Deserialize, Serialize};
-use rocket_db_pools::sqlx;
+use rocket_db_pools::{sqlx, Connection};
 use validator::Validate;
 
+use crate::db::{DataError, WishlistDb};
 
 /// An image
+///
+/// The file itself is kept in the `ImageStore`, named by the image's ID. Images are put on items
+/// through the `item_images` table.
 #[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct Image {
     /// The image's unique ID.
     pub id: i64,
+    /// The id of the user who uploaded the image.
+    pub user_id: i64,
+    /// The content type detected from the file, see `crate::images::detect_content_type`.
+    pub content_type: String,
+    /// The size of the stored file in bytes.
+    pub size: i64,
 
     /// If the image was fetched from an external source, the URL of that source.
     pub source_url: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
+
+impl Image {
+    /// The most images each item can have.
+    pub const MAX_PER_ITEM: i64 = 10;
+
+    /// Records a new image, returning it. The file should be written to the `ImageStore` once
+    /// the image has an ID.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        content_type: &str,
+        size: i64,
+        source_url: Option<&str>,
+    ) -> Result<Image, DataError> {
+        let image = sqlx::query_as(
+            r#"
+            INSERT INTO images (user_id, content_type, size, source_url, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, content_type, size, source_url, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(content_type)
+        .bind(size)
+        .bind(source_url)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(image)
+    }
+
+    /// Returns the image with the given ID, or `None` if it doesn't exist.
+    pub async fn find_by_id(
+        conn: &mut Connection<WishlistDb>,
+        id: i64,
+    ) -> Result<Option<Image>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, content_type, size, source_url, created_at, updated_at
+            FROM images
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the image if it's on the item, or `None` if it isn't.
+    pub async fn find_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        id: i64,
+    ) -> Result<Option<Image>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT i.id, i.user_id, i.content_type, i.size, i.source_url, i.created_at, i.updated_at
+            FROM images i
+            JOIN item_images ii ON ii.image_id = i.id
+            WHERE ii.item_id = $1 AND i.id = $2
+            "#,
+        )
+        .bind(item_id)
+        .bind(id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the item's images, in the order they were added.
+    pub async fn all_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Vec<Image>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT i.id, i.user_id, i.content_type, i.size, i.source_url, i.created_at, i.updated_at
+            FROM images i
+            JOIN item_images ii ON ii.image_id = i.id
+            WHERE ii.item_id = $1
+            ORDER BY ii.position, i.id
+            "#,
+        )
+        .bind(item_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns how many images the item has.
+    pub async fn count_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM item_images WHERE item_id = $1"#)
+            .bind(item_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns how many bytes of storage the user's images take up.
+    pub async fn total_size_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"SELECT CAST(COALESCE(SUM(size), 0) AS BIGINT) FROM images WHERE user_id = $1"#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await
+    }
+
+    /// Puts the image on the item, after its other images.
+    pub async fn attach(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO item_images (item_id, image_id, position, created_at)
+            VALUES ($1, $2, (SELECT COALESCE(MAX(position), 0) + 1 FROM item_images WHERE item_id = $1), CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(item_id)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Takes the image off the item.
+    pub async fn detach(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM item_images WHERE item_id = $1 AND image_id = $2"#)
+            .bind(item_id)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the image from the database if it isn't on any items, returning true if it was
+    /// deleted. The caller should then remove its file from the `ImageStore`.
+    pub async fn destroy_if_unused(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<bool, DataError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM images
+            WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM item_images WHERE image_id = $1)
+            "#,
+        )
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,86 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A user's secret email address for adding items by email.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct InboundAddress {
+    pub id: i64,
+    pub user_id: i64,
+    /// The address's local part. Anyone who knows it can add items for the user.
+    pub token: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl InboundAddress {
+    /// Gives the user a new address, replacing any previous one.
+    pub async fn regenerate(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<InboundAddress, DataError> {
+        let address = sqlx::query_as(
+            r#"
+            INSERT INTO inbound_addresses (user_id, token, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id)
+            DO UPDATE SET token = $2, updated_at = CURRENT_TIMESTAMP
+            RETURNING id, user_id, token, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(crate::util::random_token().to_lowercase())
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(address)
+    }
+
+    /// Returns the user's address, or `None` if they haven't made one.
+    pub async fn find_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<InboundAddress>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, token, created_at, updated_at
+            FROM inbound_addresses
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the address with the given token, or `None` if no address has that token.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<InboundAddress>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, token, created_at, updated_at
+            FROM inbound_addresses
+            WHERE token = $1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Removes the user's address, so emails to it are no longer accepted.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM inbound_addresses WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
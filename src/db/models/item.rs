@@ -1,10 +1,15 @@
+use std::borrow::Cow;
+
 use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::sqlx;
 use rocket_db_pools::Connection;
-use validator::Validate;
+use url::Url;
+use validator::{Validate, ValidationError};
 
-use crate::db::DataError;
-use crate::db::WishlistDb;
+use crate::db::models::{AuditEvent, SearchKind, SearchTask};
+use crate::db::{self, DataError, WishlistDb};
+use crate::pagination::{Page, SortDirection};
+use crate::render_cache;
 
 /// A item of items.
 #[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
@@ -25,10 +30,300 @@ pub struct Item {
     /// A description of the item.
     #[validate(length(max = 4096, message = "Description must be less than 4096 characters"))]
     pub description: String,
+    /// A link to where the item can be bought.
+    #[validate(
+        length(max = 2048, message = "Link must be less than 2048 characters"),
+        custom = "validate_item_url"
+    )]
+    pub url: Option<String>,
+    /// What sort of gift the item is, see `ItemKind`.
+    #[validate(custom = "validate_item_kind")]
+    pub kind: String,
+    /// The card's value for gift cards, or the goal for cash funds, in the smallest unit of
+    /// `currency` (e.g. cents).
+    #[validate(range(min = 1, message = "Amount must be more than zero"))]
+    pub amount_cents: Option<i64>,
+    /// An ISO 4217 currency code for `amount_cents`.
+    pub currency: Option<String>,
+    /// What the item costs, in the smallest unit of `price_currency` (e.g. cents). The low end of
+    /// the range if `price_max_cents` is set.
+    #[validate(range(min = 0, message = "Price can't be negative"))]
+    pub price_cents: Option<i64>,
+    /// The high end of the item's price range, for items that don't have an exact price. Always
+    /// more than `price_cents` when it's set, see `Item::set_details`.
+    pub price_max_cents: Option<i64>,
+    /// An ISO 4217 currency code for `price_cents`.
+    pub price_currency: Option<String>,
+    /// How many of the item are wanted.
+    #[validate(range(min = 1, max = 999, message = "Quantity must be between 1 and 999"))]
+    pub quantity: i64,
+    /// How much the item is wanted, see `ItemPriority`.
+    #[validate(custom = "validate_item_priority")]
+    pub priority: String,
+    /// Where the item is on its list, lowest first. See `Item::reorder`.
+    pub position: i64,
+    /// How many times the item's link has been followed.
+    pub click_count: i64,
+    /// Whether the item's link looked dead the last time it was checked.
+    pub link_broken: bool,
+    /// When the item's link was last checked.
+    pub link_checked_at: Option<chrono::NaiveDateTime>,
+    /// When the item was marked as received, or `None` if it's still wanted.
+    pub received_at: Option<chrono::NaiveDateTime>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
 
+/// What sort of gift an item is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ItemKind {
+    /// Something to buy and give.
+    Physical,
+    /// A gift card, optionally for a set amount.
+    GiftCard,
+    /// Money towards a goal. Anyone can chip in, as many times as they like.
+    CashFund,
+    /// Something to do, like a concert or a trip.
+    Experience,
+    /// Something that runs out, like coffee beans or socks. It stays wanted after it's received,
+    /// so it can be claimed and given again, and counts how many times it's been given. See
+    /// `ItemGift`.
+    Consumable,
+}
+
+impl ItemKind {
+    pub const ALL: [ItemKind; 5] = [
+        ItemKind::Physical,
+        ItemKind::GiftCard,
+        ItemKind::CashFund,
+        ItemKind::Experience,
+        ItemKind::Consumable,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Physical => "physical",
+            ItemKind::GiftCard => "gift_card",
+            ItemKind::CashFund => "cash_fund",
+            ItemKind::Experience => "experience",
+            ItemKind::Consumable => "consumable",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ItemKind> {
+        ItemKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+    }
+
+    /// A name for the kind, for forms.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemKind::Physical => "Physical gift",
+            ItemKind::GiftCard => "Gift card",
+            ItemKind::CashFund => "Cash fund",
+            ItemKind::Experience => "Experience",
+            ItemKind::Consumable => "Consumable",
+        }
+    }
+
+    /// Whether items of the kind stay wanted after they're received.
+    pub fn is_recurring(&self) -> bool {
+        matches!(self, ItemKind::Consumable)
+    }
+
+    /// Whether the kind has an amount, the card's value or the fund's goal.
+    pub fn has_amount(&self) -> bool {
+        matches!(self, ItemKind::GiftCard | ItemKind::CashFund)
+    }
+}
+
+/// How much an item is wanted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ItemPriority {
+    Low,
+    Normal,
+    High,
+    /// The item the owner wants most.
+    MustHave,
+}
+
+impl ItemPriority {
+    pub const ALL: [ItemPriority; 4] = [
+        ItemPriority::Low,
+        ItemPriority::Normal,
+        ItemPriority::High,
+        ItemPriority::MustHave,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemPriority::Low => "low",
+            ItemPriority::Normal => "normal",
+            ItemPriority::High => "high",
+            ItemPriority::MustHave => "must_have",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ItemPriority> {
+        ItemPriority::ALL
+            .into_iter()
+            .find(|priority| priority.as_str() == value)
+    }
+
+    /// A name for the priority, for forms.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemPriority::Low => "Low",
+            ItemPriority::Normal => "Normal",
+            ItemPriority::High => "High",
+            ItemPriority::MustHave => "Must have",
+        }
+    }
+}
+
+/// The order a list's items are paged through in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemOrder {
+    /// The list's own order, see `Item::reorder`.
+    Position,
+    /// Cheapest first, by the middle of each item's price range (see
+    /// `Item::price_midpoint_cents`). Items without a price come last either way. Currencies
+    /// aren't converted, so lists in more than one currency are only sorted within each.
+    Price,
+    /// Alphabetically, ignoring case.
+    Title,
+    CreatedAt,
+}
+
+impl ItemOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemOrder::Position => "position",
+            ItemOrder::Price => "price",
+            ItemOrder::Title => "title",
+            ItemOrder::CreatedAt => "created_at",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ItemOrder> {
+        [
+            ItemOrder::Position,
+            ItemOrder::Price,
+            ItemOrder::Title,
+            ItemOrder::CreatedAt,
+        ]
+        .into_iter()
+        .find(|order| order.as_str() == value)
+    }
+
+    /// The `ORDER BY` clause for items, with columns prefixed by `table`, e.g. "i.".
+    fn sql(&self, table: &str, direction: SortDirection) -> String {
+        let d = direction.sql();
+        match self {
+            ItemOrder::Position => format!("{t}position {d}, {t}id {d}", t = table, d = d),
+            ItemOrder::Price => format!(
+                "{t}price_cents IS NULL, ({t}price_cents + COALESCE({t}price_max_cents, {t}price_cents)) / 2 {d}, {t}position, {t}id",
+                t = table,
+                d = d
+            ),
+            ItemOrder::Title => format!("LOWER({t}title) {d}, {t}id {d}", t = table, d = d),
+            ItemOrder::CreatedAt => format!("{t}created_at {d}, {t}id {d}", t = table, d = d),
+        }
+    }
+}
+
+/// Which of a list's items to page through, and in what order. See `Item::page_filtered`.
+#[derive(Debug, Clone, Copy)]
+pub struct ItemFilter<'a> {
+    /// Only items with this tag. It should be normalized, see `Tag::normalize`.
+    pub tag: Option<&'a str>,
+    /// Only items whose title or description contains this, ignoring case.
+    pub search: Option<&'a str>,
+    pub order: ItemOrder,
+    pub direction: SortDirection,
+}
+
+impl Default for ItemFilter<'_> {
+    fn default() -> Self {
+        Self {
+            tag: None,
+            search: None,
+            order: ItemOrder::Position,
+            direction: SortDirection::Asc,
+        }
+    }
+}
+
+impl ItemFilter<'_> {
+    /// The `WHERE` clause for the list's items matching the filter, for items aliased `i`. The
+    /// list's ID is `$1`, and the values for the other placeholders follow in order.
+    fn sql(&self) -> (String, Vec<String>) {
+        let mut conditions = vec!["i.list_id = $1".to_string()];
+        let mut binds = vec![];
+        if let Some(tag) = self.tag {
+            binds.push(tag.to_string());
+            conditions.push(format!(
+                r#"EXISTS (SELECT 1 FROM item_tags it JOIN tags t ON t.id = it.tag_id WHERE it.item_id = i.id AND t.name = ${})"#,
+                binds.len() + 1
+            ));
+        }
+        if let Some(search) = self.search {
+            binds.push(db::contains_pattern(search));
+            conditions.push(format!(
+                r#"(LOWER(i.title) LIKE ${n} ESCAPE '\' OR LOWER(i.description) LIKE ${n} ESCAPE '\')"#,
+                n = binds.len() + 1
+            ));
+        }
+        (conditions.join(" AND "), binds)
+    }
+}
+
+fn validate_item_priority(priority: &str) -> Result<(), ValidationError> {
+    match ItemPriority::parse(priority) {
+        Some(_) => Ok(()),
+        None => {
+            let mut err = ValidationError::new("priority");
+            err.message = Some(Cow::from("Unknown priority"));
+            Err(err)
+        }
+    }
+}
+
+fn validate_item_kind(kind: &str) -> Result<(), ValidationError> {
+    match ItemKind::parse(kind) {
+        Some(_) => Ok(()),
+        None => {
+            let mut err = ValidationError::new("kind");
+            err.message = Some(Cow::from("Unknown item kind"));
+            Err(err)
+        }
+    }
+}
+
+/// Only allows http and https links, so items can't carry `javascript:` or `data:` URLs.
+fn validate_item_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("url");
+            err.message = Some(Cow::from("Link must be an http or https URL"));
+            Err(err)
+        }
+    }
+}
+
+/// Who to tell about an item whose link broke: the owner of its list.
+#[derive(sqlx::FromRow, Debug)]
+pub struct LinkOwner {
+    pub list_key: String,
+    pub list_title: String,
+    pub username: String,
+    pub user_email: String,
+}
+
 impl Default for Item {
     fn default() -> Self {
         Self {
@@ -36,6 +331,20 @@ impl Default for Item {
             list_id: 0,
             title: String::default(),
             description: String::default(),
+            url: None,
+            kind: ItemKind::Physical.as_str().to_string(),
+            amount_cents: None,
+            currency: None,
+            price_cents: None,
+            price_max_cents: None,
+            price_currency: None,
+            quantity: 1,
+            priority: ItemPriority::Normal.as_str().to_string(),
+            position: 0,
+            click_count: 0,
+            link_broken: false,
+            link_checked_at: None,
+            received_at: None,
             created_at: chrono::NaiveDateTime::default(),
             updated_at: chrono::NaiveDateTime::default(),
         }
@@ -43,7 +352,7 @@ impl Default for Item {
 }
 
 impl Item {
-    /// Shorthand for `Item::new(...).save(conn)`.
+    /// Shorthand for `Item::new(...).save(conn, by)`.
     ///
     /// Creates a new item and saves it to the database, returning the new item.
     pub async fn create(
@@ -51,40 +360,111 @@ impl Item {
         list_id: i64,
         title: &str,
         description: &str,
+        url: Option<&str>,
+        by: Option<i64>,
     ) -> Result<Item, DataError> {
-        Item::new(list_id, title.to_string(), description.to_string())
-            .save(conn)
-            .await
+        Item::new(
+            list_id,
+            title.to_string(),
+            description.to_string(),
+            url.map(|u| u.to_string()),
+        )
+        .save(conn, by)
+        .await
     }
 
     /// Creates a new item without saving it to the database.
-    pub fn new(list_id: i64, title: String, description: String) -> Item {
+    pub fn new(list_id: i64, title: String, description: String, url: Option<String>) -> Item {
         Item {
             id: 0,
             list_id,
             title,
             description,
+            url: url.map(|u| crate::util::strip_tracking_params(&u)),
+            kind: ItemKind::Physical.as_str().to_string(),
+            amount_cents: None,
+            currency: None,
+            price_cents: None,
+            price_max_cents: None,
+            price_currency: None,
+            quantity: 1,
+            priority: ItemPriority::Normal.as_str().to_string(),
+            position: 0,
+            click_count: 0,
+            link_broken: false,
+            link_checked_at: None,
+            received_at: None,
             created_at: chrono::NaiveDateTime::default(),
             updated_at: chrono::NaiveDateTime::default(),
         }
     }
 
-    /// Saves the item to the database, returning an updated copy of the item.
-    pub async fn save(self, conn: &mut Connection<WishlistDb>) -> Result<Item, DataError> {
+    /// Saves the item to the database, returning an updated copy of the item. The change is
+    /// recorded as made by `by`, see `AuditEvent`.
+    pub async fn save(
+        self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<Item, DataError> {
         if self.id == 0 {
-            self.do_insert(conn).await
+            self.do_insert(conn, by).await
         } else {
-            self.do_update(conn).await
+            self.do_update(conn, by).await
         }
     }
 
-    /// Returns all items in the database.
+    /// Returns all of the list's items, in the list's order.
     pub async fn all_by_list(
         conn: &mut Connection<WishlistDb>,
         list_id: i64,
     ) -> Result<Vec<Item>, sqlx::Error> {
-        sqlx::query_as(r#"SELECT id, list_id, title, description, created_at, updated_at FROM items WHERE list_id = $1"#)
+        sqlx::query_as(r#"SELECT id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at FROM items WHERE list_id = $1 ORDER BY position, id"#)
+            .bind(list_id)
+            .fetch_all(&mut **conn)
+            .await
+    }
+
+    /// Returns the list's most recently added items, newest first.
+    pub async fn recent_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at FROM items WHERE list_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2"#)
             .bind(list_id)
+            .bind(limit)
+            .fetch_all(&mut **conn)
+            .await
+    }
+
+    /// Returns a page of the list's items matching the filter, in its order.
+    pub async fn page_filtered(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        filter: &ItemFilter<'_>,
+        page: &Page,
+    ) -> Result<Vec<Item>, sqlx::Error> {
+        let (conditions, binds) = filter.sql();
+        let sql = format!(
+            r#"
+            SELECT i.id, i.list_id, i.title, i.description, i.url, i.kind, i.amount_cents, i.currency, i.price_cents, i.price_max_cents, i.price_currency, i.quantity, i.priority, i.position, i.click_count, i.link_broken, i.link_checked_at, i.received_at, i.created_at, i.updated_at
+            FROM items i
+            WHERE {}
+            ORDER BY {}
+            LIMIT ${} OFFSET ${}
+            "#,
+            conditions,
+            filter.order.sql("i.", filter.direction),
+            binds.len() + 2,
+            binds.len() + 3,
+        );
+        let mut query = sqlx::query_as(&sql).bind(list_id);
+        for value in binds {
+            query = query.bind(value);
+        }
+        query
+            .bind(page.limit())
+            .bind(page.offset())
             .fetch_all(&mut **conn)
             .await
     }
@@ -94,35 +474,279 @@ impl Item {
         conn: &mut Connection<WishlistDb>,
         id: i64,
     ) -> Result<Option<Item>, sqlx::Error> {
-        sqlx::query_as(r#"SELECT id, list_id, title, description, created_at, updated_at FROM items WHERE id = $1"#)
+        sqlx::query_as(r#"SELECT id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at FROM items WHERE id = $1"#)
             .bind(id)
             .fetch_optional(&mut **conn)
             .await
     }
 
-    /// Updates the item in the database, returning an updated copy of the item.
+    /// Updates the item in the database, returning an updated copy of the item. The change is
+    /// recorded as made by `by`, see `AuditEvent`.
     pub async fn update(
         &mut self,
         conn: &mut Connection<WishlistDb>,
         title: &str,
         description: &str,
+        url: Option<&str>,
+        by: Option<i64>,
     ) -> Result<Item, DataError> {
+        let url = url.map(crate::util::strip_tracking_params);
+        if url != self.url {
+            self.link_broken = false;
+            self.link_checked_at = None;
+        }
         self.title = title.to_string();
         self.description = description.to_string();
-        self.do_update(conn).await
+        self.url = url;
+        self.do_update(conn, by).await
+    }
+
+    /// Changes what sort of gift the item is without saving it. The amount is dropped for kinds
+    /// that don't have one.
+    pub fn set_kind(&mut self, kind: ItemKind, amount_cents: Option<i64>, currency: Option<&str>) {
+        self.kind = kind.as_str().to_string();
+        if kind.has_amount() {
+            self.amount_cents = amount_cents;
+            self.currency = amount_cents.and(currency.map(|c| c.to_string()));
+        } else {
+            self.amount_cents = None;
+            self.currency = None;
+        }
     }
 
-    /// Deletes the item from the database.
-    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+    /// Sets the item's price, and how many are wanted and how much, without saving it. The price
+    /// is a range if `price_max_cents` is more than `price_cents`, otherwise it's exact.
+    pub fn set_details(
+        &mut self,
+        price_cents: Option<i64>,
+        price_max_cents: Option<i64>,
+        price_currency: Option<&str>,
+        quantity: i64,
+        priority: ItemPriority,
+    ) {
+        self.price_cents = price_cents;
+        self.price_max_cents = price_cents
+            .and(price_max_cents)
+            .filter(|max| Some(*max) > price_cents);
+        self.price_currency = price_cents.and(price_currency.map(|c| c.to_string()));
+        self.quantity = quantity;
+        self.priority = priority.as_str().to_string();
+    }
+
+    /// The middle of the item's price range, or its exact price. Items are compared by price with
+    /// this, e.g. when sorting them or matching saved searches.
+    pub fn price_midpoint_cents(&self) -> Option<i64> {
+        self.price_cents.map(|min| (min + self.price_max_cents.unwrap_or(min)) / 2)
+    }
+
+    /// Deletes the item from the database. The change is recorded as made by `by`, see
+    /// `AuditEvent`.
+    pub async fn destroy(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<(), DataError> {
         if self.id != 0 {
             Item::do_delete(conn, self.id).await?;
+            AuditEvent::record_item(conn, by, AuditEvent::DELETE, Some(self), None).await?;
+            render_cache::invalidate(self.list_id);
             self.id = 0;
         }
         Ok(())
     }
 
+    /// Marks the item as received, or as wanted again.
+    pub async fn set_received(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        received: bool,
+    ) -> Result<(), DataError> {
+        let received_at = if received {
+            Some(chrono::Utc::now().naive_utc())
+        } else {
+            None
+        };
+
+        sqlx::query(r#"UPDATE items SET received_at = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(received_at)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+
+        self.received_at = received_at;
+        render_cache::invalidate(self.list_id);
+        Ok(())
+    }
+
+    /// Puts the list's items in the given order. Items that aren't given keep their order, after
+    /// the ones that are. Run it in a transaction so the list is never left half reordered.
+    pub async fn reorder(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        ids: &[i64],
+    ) -> Result<(), DataError> {
+        let current: Vec<i64> =
+            sqlx::query_scalar(r#"SELECT id FROM items WHERE list_id = $1 ORDER BY position, id"#)
+                .bind(list_id)
+                .fetch_all(&mut **conn)
+                .await?;
+
+        for (i, id) in ids.iter().enumerate() {
+            if !current.contains(id) {
+                return Err(DataError::Other(format!("Item {} isn't on the list", id)));
+            }
+            if ids[..i].contains(id) {
+                return Err(DataError::Other(format!("Item {} is given twice", id)));
+            }
+        }
+
+        let rest = current.iter().filter(|id| !ids.contains(id));
+        for (position, id) in ids.iter().chain(rest).enumerate() {
+            sqlx::query(r#"UPDATE items SET position = $1 WHERE id = $2"#)
+                .bind(position as i64 + 1)
+                .bind(id)
+                .execute(&mut **conn)
+                .await?;
+        }
+        render_cache::invalidate(list_id);
+        Ok(())
+    }
+
+    /// Counts a click on the item's link.
+    pub async fn record_click(conn: &mut Connection<WishlistDb>, id: i64) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE items SET click_count = click_count + 1 WHERE id = $1"#)
+            .bind(id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns items added to the user's lists after the given time, newest first. Only items on
+    /// `list_id` are returned if it's given.
+    pub async fn all_new_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        list_id: Option<i64>,
+        since: chrono::NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT i.id, i.list_id, i.title, i.description, i.url, i.kind, i.amount_cents, i.currency, i.price_cents, i.price_max_cents, i.price_currency, i.quantity, i.priority, i.position, i.click_count, i.link_broken, i.link_checked_at, i.received_at, i.created_at, i.updated_at
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1 AND l.id = COALESCE($2, l.id) AND i.created_at > $3
+            ORDER BY i.created_at DESC, i.id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(list_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    // ----- Jobs -----
+
+    /// Returns all items with a link that hasn't been checked since the given time.
+    pub async fn all_with_unchecked_links(
+        pool: &sqlx::AnyPool,
+        checked_before: chrono::NaiveDateTime,
+    ) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at
+            FROM items
+            WHERE url IS NOT NULL AND (link_checked_at IS NULL OR link_checked_at < $1)
+            "#,
+        )
+        .bind(checked_before)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Returns all items that have a link.
+    pub async fn all_with_links(pool: &sqlx::AnyPool) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as(r#"SELECT id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at FROM items WHERE url IS NOT NULL"#)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Returns all items on the given user's lists, for account exports.
+    pub async fn export_by_user(
+        pool: &sqlx::AnyPool,
+        user_id: i64,
+    ) -> Result<Vec<Item>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT i.id, i.list_id, i.title, i.description, i.url, i.kind, i.amount_cents, i.currency, i.price_cents, i.price_max_cents, i.price_currency, i.quantity, i.priority, i.position, i.click_count, i.link_broken, i.link_checked_at, i.received_at, i.created_at, i.updated_at
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1
+            ORDER BY i.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records the result of checking the item's link.
+    pub async fn set_link_status(
+        pool: &sqlx::AnyPool,
+        id: i64,
+        link_broken: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE items SET link_broken = $1, link_checked_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(link_broken)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the owner of the item's list, unless it's anonymous or they're suspended.
+    pub async fn link_owner(
+        pool: &sqlx::AnyPool,
+        id: i64,
+    ) -> Result<Option<LinkOwner>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT l.key AS list_key, l.title AS list_title, u.username, u.email AS user_email
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            JOIN users u ON u.id = l.user_id
+            WHERE i.id = $1 AND u.suspended_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+    }
+
     // ----- Misc -----
 
+    /// What sort of gift the item is. Unknown kinds, e.g. from a newer version, are treated as
+    /// physical gifts.
+    pub fn kind(&self) -> ItemKind {
+        ItemKind::parse(&self.kind).unwrap_or(ItemKind::Physical)
+    }
+
+    /// How much the item is wanted. Unknown priorities, e.g. from a newer version, are treated as
+    /// normal.
+    pub fn priority(&self) -> ItemPriority {
+        ItemPriority::parse(&self.priority).unwrap_or(ItemPriority::Normal)
+    }
+
+    /// Whether someone can claim the item. Received items are done with, and cash funds take
+    /// contributions from everyone instead. Consumables are never received, so they can be
+    /// claimed again each time they're given.
+    pub fn is_claimable(&self) -> bool {
+        self.received_at.is_none() && self.kind() != ItemKind::CashFund
+    }
+
     /// Returns the number of items in the database.
     pub async fn count(conn: &mut Connection<WishlistDb>) -> Result<i64, sqlx::Error> {
         sqlx::query_scalar(r#"SELECT COUNT(*) FROM items"#)
@@ -130,47 +754,126 @@ impl Item {
             .await
     }
 
+    /// Returns the number of items on the list.
+    pub async fn count_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM items WHERE list_id = $1"#)
+            .bind(list_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns the number of the list's items matching the filter.
+    pub async fn count_filtered(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        filter: &ItemFilter<'_>,
+    ) -> Result<i64, sqlx::Error> {
+        let (conditions, binds) = filter.sql();
+        let sql = format!(r#"SELECT COUNT(*) FROM items i WHERE {}"#, conditions);
+        let mut query = sqlx::query_scalar(&sql).bind(list_id);
+        for value in binds {
+            query = query.bind(value);
+        }
+        query.fetch_one(&mut **conn).await
+    }
+
     // ----- Internal -----
 
-    async fn do_insert(self, conn: &mut Connection<WishlistDb>) -> Result<Item, DataError> {
+    async fn do_insert(
+        self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<Item, DataError> {
         self.validate()?;
 
-        let item = sqlx::query_as(
+        let item: Item = sqlx::query_as(
             r#"
-            INSERT INTO items (list_id, title, description, created_at, updated_at)
-            VALUES ($1, $2, $3, now(), now())
-            RETURNING id, list_id, title, description, created_at, updated_at
+            INSERT INTO items (list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, (SELECT COALESCE(MAX(position), 0) + 1 FROM items WHERE list_id = $1), CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at
         "#,
         )
-        .bind(&self.list_id)
+        .bind(self.list_id)
         .bind(&self.title)
         .bind(&self.description)
+        .bind(&self.url)
+        .bind(&self.kind)
+        .bind(self.amount_cents)
+        .bind(&self.currency)
+        .bind(self.price_cents)
+        .bind(self.price_max_cents)
+        .bind(&self.price_currency)
+        .bind(self.quantity)
+        .bind(&self.priority)
         .fetch_one(&mut **conn)
         .await?;
 
+        SearchTask::push(conn, SearchKind::Item, item.id).await?;
+        AuditEvent::record_item(conn, by, AuditEvent::CREATE, None, Some(&item)).await?;
+        render_cache::invalidate(item.list_id);
+
         Ok(item)
     }
 
-    async fn do_update(&self, conn: &mut Connection<WishlistDb>) -> Result<Item, DataError> {
+    async fn do_update(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<Item, DataError> {
         self.validate()?;
 
-        let item = sqlx::query_as(
+        // The item as it's saved, rather than `self`, which has already been changed
+        let old = Item::find_by_id(conn, self.id).await?;
+
+        let item: Item = sqlx::query_as(
             r#"
             UPDATE items
             SET list_id = $1, 
                 title = $2,
                 description = $3,
-                updated_at = now()
-            WHERE id = $4
-            RETURNING id, list_id, title, description, created_at, updated_at"#,
+                url = $4,
+                link_broken = $5,
+                link_checked_at = $6,
+                kind = $7,
+                amount_cents = $8,
+                currency = $9,
+                price_cents = $10,
+                price_max_cents = $11,
+                price_currency = $12,
+                quantity = $13,
+                priority = $14,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $15
+            RETURNING id, list_id, title, description, url, kind, amount_cents, currency, price_cents, price_max_cents, price_currency, quantity, priority, position, click_count, link_broken, link_checked_at, received_at, created_at, updated_at"#,
         )
-        .bind(&self.list_id)
+        .bind(self.list_id)
         .bind(&self.title)
         .bind(&self.description)
+        .bind(&self.url)
+        .bind(self.link_broken)
+        .bind(self.link_checked_at)
+        .bind(&self.kind)
+        .bind(self.amount_cents)
+        .bind(&self.currency)
+        .bind(self.price_cents)
+        .bind(self.price_max_cents)
+        .bind(&self.price_currency)
+        .bind(self.quantity)
+        .bind(&self.priority)
         .bind(self.id)
         .fetch_one(&mut **conn)
         .await?;
 
+        SearchTask::push(conn, SearchKind::Item, item.id).await?;
+        AuditEvent::record_item(conn, by, AuditEvent::UPDATE, old.as_ref(), Some(&item)).await?;
+        if let Some(old) = &old {
+            render_cache::invalidate(old.list_id);
+        }
+        render_cache::invalidate(item.list_id);
+
         Ok(item)
     }
 
@@ -182,3 +885,48 @@ impl Item {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn item(title: &str, url: Option<String>) -> Item {
+        Item::new(1, title.to_string(), String::new(), url)
+    }
+
+    proptest! {
+        #[test]
+        fn titles_between_2_and_256_characters_are_valid(title in "\\PC{2,256}") {
+            prop_assert!(item(&title, None).validate().is_ok());
+        }
+
+        #[test]
+        fn titles_outside_2_to_256_characters_are_invalid(
+            title in prop_oneof!["\\PC{0,1}", "\\PC{257,300}"],
+        ) {
+            prop_assert!(item(&title, None).validate().is_err());
+        }
+
+        #[test]
+        fn http_links_are_valid(scheme in "https?", path in "[a-z0-9/]{0,64}") {
+            let url = format!("{}://example.com/{}", scheme, path);
+            prop_assert!(item("Headphones", Some(url)).validate().is_ok());
+        }
+
+        #[test]
+        fn other_links_are_invalid(scheme in "[a-z]{1,10}", rest in "[a-z0-9/:]{0,64}") {
+            prop_assume!(scheme != "http" && scheme != "https");
+            let url = format!("{}:{}", scheme, rest);
+            prop_assert!(item("Headphones", Some(url)).validate().is_err());
+        }
+
+        #[test]
+        fn quantities_between_1_and_999_are_valid(quantity in any::<i64>()) {
+            let mut item = item("Headphones", None);
+            item.quantity = quantity;
+            prop_assert_eq!(item.validate().is_ok(), (1..=999).contains(&quantity));
+        }
+    }
+}
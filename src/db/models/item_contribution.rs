@@ -0,0 +1,80 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// Money someone put towards a cash fund item.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemContribution {
+    pub id: i64,
+    pub item_id: i64,
+    pub user_id: i64,
+    /// The amount in the smallest unit of its currency (e.g. cents).
+    pub amount_cents: i64,
+    /// An ISO 4217 currency code.
+    pub currency: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl ItemContribution {
+    /// Records a contribution. The same user can contribute more than once.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<ItemContribution, DataError> {
+        if amount_cents <= 0 {
+            return Err(DataError::Other("Amount must be more than zero".to_string()));
+        }
+
+        let contribution = sqlx::query_as(
+            r#"
+            INSERT INTO item_contributions (item_id, user_id, amount_cents, currency, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, user_id, amount_cents, currency, created_at, updated_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .bind(amount_cents)
+        .bind(currency)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(contribution)
+    }
+
+    /// Returns the total contributed to the item in the given currency.
+    pub async fn total_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        currency: &str,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT CAST(COALESCE(SUM(amount_cents), 0) AS BIGINT)
+            FROM item_contributions
+            WHERE item_id = $1 AND currency = $2
+            "#,
+        )
+        .bind(item_id)
+        .bind(currency)
+        .fetch_one(&mut **conn)
+        .await
+    }
+
+    /// Returns the number of contributions to the item.
+    pub async fn count_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM item_contributions WHERE item_id = $1"#)
+            .bind(item_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+}
@@ -0,0 +1,59 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::models::{Claim, Item};
+use crate::db::{DataError, WishlistDb};
+
+/// A consumable item being given once. Consumables stay on their list after they're received, so
+/// each time is recorded here instead of in `Item::received_at`. See `ItemKind::Consumable`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemGift {
+    pub id: i64,
+    pub item_id: i64,
+    /// Who had claimed the item when it was received, if anyone had.
+    pub user_id: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ItemGift {
+    /// Records that the consumable was received, from whoever claimed it, and clears its claim so
+    /// it can be claimed again for next time. `by` is who marked it received, if they were logged
+    /// in.
+    pub async fn receive(
+        conn: &mut Connection<WishlistDb>,
+        item: &Item,
+        by: Option<i64>,
+    ) -> Result<ItemGift, DataError> {
+        let claimer = Claim::find_by_item(conn, item.id)
+            .await?
+            .map(|claim| claim.user_id);
+
+        let gift = sqlx::query_as(
+            r#"
+            INSERT INTO item_gifts (item_id, user_id, created_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, user_id, created_at
+            "#,
+        )
+        .bind(item.id)
+        .bind(claimer)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Claim::destroy_by_item(conn, item.id, by).await?;
+
+        Ok(gift)
+    }
+
+    /// Returns how many times the item has been given.
+    pub async fn count_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM item_gifts WHERE item_id = $1"#)
+            .bind(item_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+}
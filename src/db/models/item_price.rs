@@ -0,0 +1,126 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A price recorded for an item by the price tracker.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemPrice {
+    pub id: i64,
+    pub item_id: i64,
+    /// The price in the smallest unit of its currency (e.g. cents).
+    pub amount_cents: i64,
+    /// An ISO 4217 currency code.
+    pub currency: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// A tracked price that fell below the item's baseline, which is the first price recorded for it,
+/// or reached a target price set by a user.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PriceDrop {
+    pub id: i64,
+    pub item_id: i64,
+    pub item_title: String,
+    pub amount_cents: i64,
+    pub baseline_cents: i64,
+    pub currency: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl ItemPrice {
+    /// Records a price for the given item, returning the new price.
+    pub async fn create(
+        pool: &sqlx::AnyPool,
+        item_id: i64,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<ItemPrice, DataError> {
+        let price = sqlx::query_as(
+            r#"
+            INSERT INTO item_prices (item_id, amount_cents, currency, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, amount_cents, currency, created_at, updated_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(amount_cents)
+        .bind(currency)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(price)
+    }
+
+    /// Returns the most recent price recorded for the given item.
+    pub async fn find_latest(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Option<ItemPrice>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, amount_cents, currency, created_at, updated_at
+            FROM item_prices
+            WHERE item_id = $1
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the most recent price drops for items on the given list, newest first.
+    pub async fn drops_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<Vec<PriceDrop>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT p.id, p.item_id, i.title AS item_title, p.amount_cents,
+                   b.amount_cents AS baseline_cents, p.currency, p.created_at
+            FROM item_prices p
+            JOIN items i ON i.id = p.item_id
+            JOIN item_prices b ON b.id = (SELECT MIN(id) FROM item_prices WHERE item_id = p.item_id)
+            WHERE i.list_id = $1
+              AND (
+                (p.currency = b.currency AND p.amount_cents < b.amount_cents)
+                OR EXISTS (
+                    SELECT 1 FROM price_alerts a
+                    WHERE a.item_id = p.item_id AND a.currency = p.currency AND a.target_cents >= p.amount_cents
+                )
+              )
+            ORDER BY p.created_at DESC
+            LIMIT 50
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the most recent price recorded for the given item.
+    pub async fn latest_by_item(
+        pool: &sqlx::AnyPool,
+        item_id: i64,
+    ) -> Result<Option<ItemPrice>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, amount_cents, currency, created_at, updated_at
+            FROM item_prices
+            WHERE item_id = $1
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(pool)
+        .await
+    }
+}
@@ -1,21 +1,30 @@
+use std::borrow::Cow;
+
 use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::sqlx;
 use rocket_db_pools::Connection;
-use validator::Validate;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError, ValidationErrors};
 
-use crate::db::DataError;
-use crate::db::WishlistDb;
+use crate::db::models::{AuditEvent, SearchKind, SearchTask};
+use crate::db::{self, DataError, WishlistDb};
+use crate::pagination::{Page, SortDirection};
+use crate::render_cache;
 
 /// A list of items.
-#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct List {
     /// The list's unique ID.
     pub id: i64,
     /// The list's url key.
     pub key: String,
-    /// Whether the list is private.
-    pub is_private: bool,
+    /// The user who created the list, or `None` if it was created anonymously.
+    pub user_id: Option<i64>,
+    /// Who can see the list, see `ListPrivacy::as_str`.
+    #[validate(custom = "validate_list_privacy")]
+    #[schema(example = "private")]
+    pub privacy: String,
     /// The title of the list.
     #[validate(length(
         min = 2,
@@ -26,18 +35,212 @@ pub struct List {
     /// A description of the list.
     #[validate(length(max = 4096, message = "Description must be less than 4096 characters"))]
     pub description: String,
+    /// Whether item links on this list are left alone by the instance's affiliate policy.
+    pub affiliate_opt_out: bool,
+    /// The language the list is written in, as a BCP 47 tag like `he` or `pt-BR`.
+    #[validate(custom = "crate::locale::validate_language")]
+    pub language: Option<String>,
+    /// Whether the owner has opted in to seeing gifting activity on the list, e.g. for a
+    /// registry. See `crate::surprise`.
+    pub reveal_gifting: bool,
+    /// The day the list is for, like a birthday, so people who claimed items can be reminded to
+    /// get them in time. See `Claim::upcoming_by_user`.
+    pub event_date: Option<chrono::NaiveDate>,
+    /// The bcrypt hash of the password visitors need to see the list, if the owner set one. Only
+    /// private lists ask for it, see `crate::web::list_passwords`.
+    #[serde(skip)]
+    pub view_password_hash: Option<String>,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
 
+/// Who can see a list. Only public lists are listed, in the list index, search and on their
+/// owner's profile; the rest can only be found through their link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ListPrivacy {
+    /// Anyone can see the list, and it's listed.
+    Public,
+    /// Anyone with the link can see the list.
+    Unlisted,
+    /// Anyone with the link who's signed in to the site can see the list, e.g. on an instance
+    /// run for a family.
+    Group,
+    /// Only the owner can see the list, and people they share it with, see `ListShare`.
+    Private,
+}
+
+impl ListPrivacy {
+    pub const ALL: [ListPrivacy; 4] = [
+        ListPrivacy::Public,
+        ListPrivacy::Unlisted,
+        ListPrivacy::Group,
+        ListPrivacy::Private,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListPrivacy::Public => "public",
+            ListPrivacy::Unlisted => "unlisted",
+            ListPrivacy::Group => "group",
+            ListPrivacy::Private => "private",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ListPrivacy> {
+        ListPrivacy::ALL
+            .into_iter()
+            .find(|privacy| privacy.as_str() == value)
+    }
+
+    /// A name for the privacy, for forms.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ListPrivacy::Public => "Public",
+            ListPrivacy::Unlisted => "Anyone with the link",
+            ListPrivacy::Group => "Signed-in users with the link",
+            ListPrivacy::Private => "Private",
+        }
+    }
+
+    /// Whether anyone with the link can see lists with this privacy, even without signing in.
+    pub fn is_open(&self) -> bool {
+        matches!(self, ListPrivacy::Public | ListPrivacy::Unlisted)
+    }
+
+    /// The privacy for `is_private` from before lists had privacy levels, when they were either
+    /// public or private.
+    pub fn from_is_private(is_private: bool) -> ListPrivacy {
+        if is_private {
+            ListPrivacy::Private
+        } else {
+            ListPrivacy::Public
+        }
+    }
+
+    /// `is_private` as it was before lists had privacy levels. Lists that aren't listed count as
+    /// private.
+    pub fn is_private(&self) -> bool {
+        *self != ListPrivacy::Public
+    }
+}
+
+fn validate_list_privacy(privacy: &str) -> Result<(), ValidationError> {
+    match ListPrivacy::parse(privacy) {
+        Some(_) => Ok(()),
+        None => {
+            let mut err = ValidationError::new("privacy");
+            err.message = Some(Cow::from("Privacy must be public, unlisted, group or private"));
+            Err(err)
+        }
+    }
+}
+
+/// The order the public list index can be sorted in, see `ListFilter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOrder {
+    /// The order lists were added in.
+    Id,
+    /// Alphabetically, ignoring case.
+    Title,
+    CreatedAt,
+}
+
+impl ListOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListOrder::Id => "id",
+            ListOrder::Title => "title",
+            ListOrder::CreatedAt => "created_at",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ListOrder> {
+        [ListOrder::Id, ListOrder::Title, ListOrder::CreatedAt]
+            .into_iter()
+            .find(|order| order.as_str() == value)
+    }
+
+    /// The `ORDER BY` clause for lists. Ties are broken by ID, so pages don't overlap.
+    fn sql(&self, direction: SortDirection) -> String {
+        let d = direction.sql();
+        match self {
+            ListOrder::Id => format!("id {}", d),
+            ListOrder::Title => format!("LOWER(title) {d}, id {d}", d = d),
+            ListOrder::CreatedAt => format!("created_at {d}, id {d}", d = d),
+        }
+    }
+}
+
+/// Which public lists to page through, and in what order. See `List::page_public_filtered`.
+#[derive(Debug, Clone, Copy)]
+pub struct ListFilter<'a> {
+    /// Only lists whose title or description contains this, ignoring case.
+    pub search: Option<&'a str>,
+    /// Only lists that are or aren't private, see `ListPrivacy::is_private`.
+    pub is_private: Option<bool>,
+    pub order: ListOrder,
+    pub direction: SortDirection,
+}
+
+impl Default for ListFilter<'_> {
+    fn default() -> Self {
+        Self {
+            search: None,
+            is_private: None,
+            order: ListOrder::Id,
+            direction: SortDirection::Asc,
+        }
+    }
+}
+
+impl ListFilter<'_> {
+    /// The `WHERE` clause for public lists matching the filter, with the values for its
+    /// placeholders in order.
+    fn sql(&self) -> (String, Vec<String>) {
+        let mut conditions = vec!["privacy = 'public'".to_string()];
+        let mut binds = vec![];
+        // Only public lists are in the index, so asking for private ones finds nothing
+        if self.is_private == Some(true) {
+            conditions.push("privacy <> 'public'".to_string());
+        }
+        if let Some(search) = self.search {
+            binds.push(db::contains_pattern(search));
+            conditions.push(format!(
+                r#"(LOWER(title) LIKE ${n} ESCAPE '\' OR LOWER(description) LIKE ${n} ESCAPE '\')"#,
+                n = binds.len()
+            ));
+        }
+        (conditions.join(" AND "), binds)
+    }
+}
+
+/// A list with an event coming up that hasn't changed in a long time, for reminding its owner to
+/// look it over.
+#[derive(sqlx::FromRow, Debug)]
+pub struct StaleList {
+    pub id: i64,
+    pub key: String,
+    pub title: String,
+    pub event_date: chrono::NaiveDate,
+    pub username: String,
+    pub user_email: String,
+}
+
 impl Default for List {
     fn default() -> Self {
         Self {
             id: 0,
             key: crate::util::random_key(),
-            is_private: true,
+            user_id: None,
+            privacy: ListPrivacy::Private.as_str().to_string(),
             title: String::default(),
             description: String::default(),
+            affiliate_opt_out: false,
+            language: None,
+            reveal_gifting: false,
+            event_date: None,
+            view_password_hash: None,
             created_at: chrono::NaiveDateTime::default(),
             updated_at: chrono::NaiveDateTime::default(),
         }
@@ -45,55 +248,212 @@ impl Default for List {
 }
 
 impl List {
-    /// Shorthand for `List::new(...).save(conn)`.
+    /// Shorthand for `List::new(...).save(conn, user_id)`.
     ///
-    /// Creates a new list and saves it to the database, returning the new list.
+    /// Creates a new list and saves it to the database, returning the new list. The change is
+    /// recorded as made by the list's owner.
     pub async fn create(
         conn: &mut Connection<WishlistDb>,
-        is_private: bool,
+        user_id: Option<i64>,
+        privacy: &str,
         title: &str,
         description: &str,
+        affiliate_opt_out: bool,
+        language: Option<&str>,
     ) -> Result<List, DataError> {
-        List::new(is_private, title.to_string(), description.to_string())
-            .save(conn)
-            .await
+        List::new(
+            user_id,
+            privacy.to_string(),
+            title.to_string(),
+            description.to_string(),
+            affiliate_opt_out,
+            language.map(|l| l.to_string()),
+        )
+        .save(conn, user_id)
+        .await
     }
 
     /// Creates a new list without saving it to the database.
-    pub fn new(is_private: bool, title: String, description: String) -> List {
+    pub fn new(
+        user_id: Option<i64>,
+        privacy: String,
+        title: String,
+        description: String,
+        affiliate_opt_out: bool,
+        language: Option<String>,
+    ) -> List {
         List {
             id: 0,
             key: crate::util::random_key(),
-            is_private,
+            user_id,
+            privacy,
             title,
             description,
+            affiliate_opt_out,
+            language,
+            reveal_gifting: false,
+            event_date: None,
+            view_password_hash: None,
             created_at: chrono::NaiveDateTime::default(),
             updated_at: chrono::NaiveDateTime::default(),
         }
     }
 
-    /// Saves the list to the database, returning an updated copy of the list.
-    pub async fn save(self, conn: &mut Connection<WishlistDb>) -> Result<List, DataError> {
+    /// Whether the given user owns the list. Lists made without logging in have no owner.
+    pub fn is_owned_by(&self, user_id: Option<i64>) -> bool {
+        self.user_id.is_some() && self.user_id == user_id
+    }
+
+    /// Who can see the list. Lists saved with an unknown privacy are treated as private.
+    pub fn privacy(&self) -> ListPrivacy {
+        ListPrivacy::parse(&self.privacy).unwrap_or(ListPrivacy::Private)
+    }
+
+    /// Whether the given user can see the list, see `ListPrivacy`. Lists made without logging in
+    /// are shown to anyone with the link whatever their privacy, since there's no owner to show
+    /// them to.
+    pub fn is_visible_to(&self, user_id: Option<i64>) -> bool {
+        if self.user_id.is_none() || self.is_owned_by(user_id) {
+            return true;
+        }
+        match self.privacy() {
+            ListPrivacy::Public | ListPrivacy::Unlisted => true,
+            ListPrivacy::Group => user_id.is_some(),
+            ListPrivacy::Private => false,
+        }
+    }
+
+    /// Whether visitors need a password to see the list, see `crate::web::list_passwords`.
+    pub fn is_password_protected(&self) -> bool {
+        self.privacy() == ListPrivacy::Private && self.view_password_hash.is_some()
+    }
+
+    /// Whether the password is the one visitors need to see the list.
+    pub fn check_view_password(&self, password: &str) -> bool {
+        match &self.view_password_hash {
+            Some(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Saves the list to the database, returning an updated copy of the list. The change is
+    /// recorded as made by `by`, see `AuditEvent`.
+    pub async fn save(
+        self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<List, DataError> {
         if self.id == 0 {
-            self.do_insert(conn).await
+            self.do_insert(conn, by).await
         } else {
-            self.do_update(conn).await
+            self.do_update(conn, by).await
         }
     }
 
-    /// Returns all public lists in the database.
-    pub async fn all_public(conn: &mut Connection<WishlistDb>) -> Result<Vec<List>, sqlx::Error> {
+    /// Returns a page of the public lists, oldest first.
+    pub async fn all_public(
+        conn: &mut Connection<WishlistDb>,
+        page: &Page,
+    ) -> Result<Vec<List>, sqlx::Error> {
         sqlx::query_as(
             r#"
-            SELECT id, key, is_private, title, description, created_at, updated_at
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
             FROM lists
-            WHERE is_private IS FALSE
+            WHERE privacy = 'public'
+            ORDER BY id
+            LIMIT $1 OFFSET $2
             "#,
         )
+        .bind(page.limit())
+        .bind(page.offset())
         .fetch_all(&mut **conn)
         .await
     }
 
+    /// Returns a page of the public lists matching the filter, in its order.
+    pub async fn page_public_filtered(
+        conn: &mut Connection<WishlistDb>,
+        filter: &ListFilter<'_>,
+        page: &Page,
+    ) -> Result<Vec<List>, sqlx::Error> {
+        let (conditions, binds) = filter.sql();
+        let sql = format!(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE {}
+            ORDER BY {}
+            LIMIT ${} OFFSET ${}
+            "#,
+            conditions,
+            filter.order.sql(filter.direction),
+            binds.len() + 1,
+            binds.len() + 2,
+        );
+        let mut query = sqlx::query_as(&sql);
+        for value in binds {
+            query = query.bind(value);
+        }
+        query
+            .bind(page.limit())
+            .bind(page.offset())
+            .fetch_all(&mut **conn)
+            .await
+    }
+
+    /// Returns all public lists created by the given user.
+    pub async fn all_public_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<List>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE user_id = $1 AND privacy = 'public'
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns all lists created by the given user.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<List>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the list the given user most recently created or edited.
+    pub async fn latest_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<List>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE user_id = $1
+            ORDER BY updated_at DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
     /// Returns the list with the given Key, or `None` if no list with that Key exists.
     pub async fn find_by_key(
         conn: &mut Connection<WishlistDb>,
@@ -101,7 +461,7 @@ impl List {
     ) -> Result<Option<List>, sqlx::Error> {
         sqlx::query_as(
             r#"
-            SELECT id, key, is_private, title, description, created_at, updated_at
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
             FROM lists
             WHERE key = $1
             "#,
@@ -111,29 +471,280 @@ impl List {
         .await
     }
 
-    /// Updates the list in the database, returning an updated copy of the list.
+    /// Returns the list with the given ID, or `None` if no list with that ID exists.
+    pub async fn find_by_id(
+        conn: &mut Connection<WishlistDb>,
+        id: i64,
+    ) -> Result<Option<List>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Updates the list in the database, returning an updated copy of the list. The change is
+    /// recorded as made by `by`, see `AuditEvent`.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update(
         &mut self,
         conn: &mut Connection<WishlistDb>,
-        is_private: bool,
+        privacy: &str,
         title: &str,
         description: &str,
+        affiliate_opt_out: bool,
+        language: Option<&str>,
+        by: Option<i64>,
     ) -> Result<List, DataError> {
-        self.is_private = is_private;
+        self.privacy = privacy.to_string();
         self.title = title.to_string();
         self.description = description.to_string();
-        self.do_update(conn).await
+        self.affiliate_opt_out = affiliate_opt_out;
+        self.language = language.map(|l| l.to_string());
+        self.do_update(conn, by).await
+    }
+
+    /// Changes who can see the list, leaving everything else as it is. See `update`.
+    pub async fn set_privacy(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        privacy: ListPrivacy,
+        by: Option<i64>,
+    ) -> Result<List, DataError> {
+        self.privacy = privacy.as_str().to_string();
+        self.do_update(conn, by).await
+    }
+
+    /// Lets the owner see gifting activity on the list, or hides it from them again.
+    pub async fn set_reveal_gifting(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        reveal_gifting: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE lists SET reveal_gifting = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(reveal_gifting)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+
+        self.reveal_gifting = reveal_gifting;
+        Ok(())
     }
 
-    /// Deletes the list from the database.
-    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+    /// Sets the password visitors need to see the list, or clears it if `None`.
+    pub async fn set_view_password(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        password: Option<&str>,
+    ) -> Result<(), DataError> {
+        let hash = match password {
+            Some(password) => {
+                if password.len() < 4 || password.len() > 128 {
+                    let mut err = ValidationError::new("view_password");
+                    err.message = Some(Cow::from(
+                        "List passwords must be between 4 and 128 characters",
+                    ));
+                    let mut errors = ValidationErrors::new();
+                    errors.add("view_password", err);
+                    return Err(DataError::Validation(errors));
+                }
+                Some(
+                    bcrypt::hash(password, bcrypt::DEFAULT_COST)
+                        .map_err(|e| DataError::Other(e.to_string()))?,
+                )
+            }
+            None => None,
+        };
+
+        sqlx::query(r#"UPDATE lists SET view_password_hash = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(&hash)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+
+        self.view_password_hash = hash;
+        render_cache::invalidate(self.id);
+        Ok(())
+    }
+
+    /// Sets the day the list is for, or clears it.
+    pub async fn set_event_date(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        event_date: Option<chrono::NaiveDate>,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE lists SET event_date = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(event_date)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+
+        self.event_date = event_date;
+        Ok(())
+    }
+
+    /// Deletes the list from the database. The change is recorded as made by `by`, see
+    /// `AuditEvent`.
+    pub async fn destroy(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<(), DataError> {
         if self.id != 0 {
             List::do_delete(conn, self.id).await?;
+            AuditEvent::record_list(conn, by, AuditEvent::DELETE, Some(self), None).await?;
+            render_cache::invalidate(self.id);
             self.id = 0;
         }
         Ok(())
     }
 
+    // ----- Jobs -----
+
+    /// Returns all lists created by the given user, for account exports.
+    pub async fn export_by_user(
+        pool: &sqlx::AnyPool,
+        user_id: i64,
+    ) -> Result<Vec<List>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
+            FROM lists
+            WHERE user_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Returns lists whose event date is between `today` and `until`, where neither the list nor
+    /// any of its items changed after `changed_before`, and whose owner hasn't been reminded about
+    /// that event date yet.
+    pub async fn all_stale(
+        pool: &sqlx::AnyPool,
+        today: chrono::NaiveDate,
+        until: chrono::NaiveDate,
+        changed_before: chrono::NaiveDateTime,
+    ) -> Result<Vec<StaleList>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT l.id, l.key, l.title, l.event_date, u.username,
+                u.email AS user_email
+            FROM lists l
+            JOIN users u ON u.id = l.user_id
+            WHERE l.event_date >= $1 AND l.event_date <= $2 AND l.updated_at < $3
+                AND (l.stale_reminded_for IS NULL OR l.stale_reminded_for <> l.event_date)
+                AND NOT EXISTS (
+                    SELECT 1 FROM items i WHERE i.list_id = l.id AND i.updated_at >= $3
+                )
+                AND u.suspended_at IS NULL
+            ORDER BY l.event_date, l.id
+            "#,
+        )
+        .bind(today)
+        .bind(until)
+        .bind(changed_before)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Records that the owner is being reminded about the list before the given event date.
+    /// Returns false if they already were, e.g. by another run of the job, so each event is only
+    /// reminded about once.
+    pub async fn mark_stale_reminded(
+        pool: &sqlx::AnyPool,
+        id: i64,
+        event_date: chrono::NaiveDate,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE lists
+            SET stale_reminded_for = $2
+            WHERE id = $1 AND (stale_reminded_for IS NULL OR stale_reminded_for <> $2)
+            "#,
+        )
+        .bind(id)
+        .bind(event_date)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Forgets that the owner was reminded, so the reminder is tried again on the next run.
+    pub async fn unmark_stale_reminded(pool: &sqlx::AnyPool, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"UPDATE lists SET stale_reminded_for = NULL WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Counts anonymous lists where neither the list nor any of its items changed after
+    /// `changed_before`, for `crate::jobs::retention`.
+    pub async fn count_anonymous_idle(
+        pool: &sqlx::AnyPool,
+        changed_before: chrono::NaiveDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM lists l
+            WHERE l.user_id IS NULL AND l.updated_at < $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM items i WHERE i.list_id = l.id AND i.updated_at >= $1
+                )
+            "#,
+        )
+        .bind(changed_before)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Deletes the lists `count_anonymous_idle` counts, along with their items, in one
+    /// transaction. Returns how many lists were deleted.
+    pub async fn destroy_anonymous_idle(
+        pool: &sqlx::AnyPool,
+        changed_before: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            r#"
+            DELETE FROM items
+            WHERE list_id IN (
+                SELECT l.id
+                FROM lists l
+                WHERE l.user_id IS NULL AND l.updated_at < $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM items i WHERE i.list_id = l.id AND i.updated_at >= $1
+                    )
+            )
+            "#,
+        )
+        .bind(changed_before)
+        .execute(&mut tx)
+        .await?;
+        // Their items are gone, so this only has to check the lists themselves
+        let result = sqlx::query(
+            r#"
+            DELETE FROM lists
+            WHERE user_id IS NULL AND updated_at < $1
+                AND NOT EXISTS (SELECT 1 FROM items i WHERE i.list_id = lists.id)
+            "#,
+        )
+        .bind(changed_before)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
     // ----- Misc -----
 
     /// Returns the number of lists in the database.
@@ -143,49 +754,106 @@ impl List {
             .await
     }
 
+    /// Returns the number of public lists, see `all_public`.
+    pub async fn count_public(conn: &mut Connection<WishlistDb>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM lists WHERE privacy = 'public'"#)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Returns the number of public lists matching the filter.
+    pub async fn count_public_filtered(
+        conn: &mut Connection<WishlistDb>,
+        filter: &ListFilter<'_>,
+    ) -> Result<i64, sqlx::Error> {
+        let (conditions, binds) = filter.sql();
+        let sql = format!(r#"SELECT COUNT(*) FROM lists WHERE {}"#, conditions);
+        let mut query = sqlx::query_scalar(&sql);
+        for value in binds {
+            query = query.bind(value);
+        }
+        query.fetch_one(&mut **conn).await
+    }
+
+    /// Returns the number of lists the user owns.
+    pub async fn count_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM lists WHERE user_id = $1"#)
+            .bind(user_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
     // ----- Internal -----
 
-    async fn do_insert(self, conn: &mut Connection<WishlistDb>) -> Result<List, DataError> {
+    async fn do_insert(
+        self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<List, DataError> {
         self.validate()?;
 
-        let list = sqlx::query_as(
+        let list: List = sqlx::query_as(
             r#"
-            INSERT INTO lists (key, is_private, title, description, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, now(), now())
-            RETURNING id, key, is_private, title, description, created_at, updated_at
+            INSERT INTO lists (key, user_id, privacy, title, description, affiliate_opt_out, language, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
             "#,
         )
         .bind(&self.key)
-        .bind(&self.is_private)
+        .bind(self.user_id)
+        .bind(&self.privacy)
         .bind(&self.title)
         .bind(&self.description)
+        .bind(self.affiliate_opt_out)
+        .bind(&self.language)
         .fetch_one(&mut **conn)
         .await?;
 
+        SearchTask::push(conn, SearchKind::List, list.id).await?;
+        AuditEvent::record_list(conn, by, AuditEvent::CREATE, None, Some(&list)).await?;
+
         Ok(list)
     }
 
-    async fn do_update(&self, conn: &mut Connection<WishlistDb>) -> Result<List, DataError> {
+    async fn do_update(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        by: Option<i64>,
+    ) -> Result<List, DataError> {
         self.validate()?;
 
-        let list = sqlx::query_as(
+        // The list as it's saved, rather than `self`, which has already been changed
+        let old = List::find_by_id(conn, self.id).await?;
+
+        let list: List = sqlx::query_as(
             r#"
             UPDATE lists
-            SET is_private = $1,
+            SET privacy = $1,
                 title = $2,
                 description = $3,
-                updated_at = now()
-            WHERE id = $4
-            RETURNING id, key, is_private, title, description, created_at, updated_at
+                affiliate_opt_out = $4,
+                language = $5,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $6
+            RETURNING id, key, user_id, privacy, title, description, affiliate_opt_out, language, reveal_gifting, event_date, view_password_hash, created_at, updated_at
             "#,
         )
-        .bind(&self.is_private)
+        .bind(&self.privacy)
         .bind(&self.title)
         .bind(&self.description)
+        .bind(self.affiliate_opt_out)
+        .bind(&self.language)
         .bind(self.id)
         .fetch_one(&mut **conn)
         .await?;
 
+        SearchTask::push(conn, SearchKind::List, list.id).await?;
+        AuditEvent::record_list(conn, by, AuditEvent::UPDATE, old.as_ref(), Some(&list)).await?;
+        render_cache::invalidate(list.id);
+
         Ok(list)
     }
 
@@ -0,0 +1,179 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::{Validate, ValidationError};
+
+use crate::db::{DataError, WishlistDb};
+
+/// What a share lets people do on a list. Each permission includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum SharePermission {
+    /// See the list and its items.
+    View,
+    /// Also claim items, like a guest on a public list.
+    Claim,
+    /// Also add, change and delete items.
+    Edit,
+}
+
+impl SharePermission {
+    pub const ALL: [SharePermission; 3] = [
+        SharePermission::View,
+        SharePermission::Claim,
+        SharePermission::Edit,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharePermission::View => "view",
+            SharePermission::Claim => "claim",
+            SharePermission::Edit => "edit",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SharePermission> {
+        SharePermission::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == value)
+    }
+
+    /// A name for the permission, for forms.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SharePermission::View => "View only",
+            SharePermission::Claim => "Can claim",
+            SharePermission::Edit => "Can edit",
+        }
+    }
+}
+
+/// A secret link to a list, `/s/<token>`. Anyone with the link gets the share's permission on
+/// the list, even if it's private, until it expires or the owner revokes it.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ListShare {
+    pub id: i64,
+    pub list_id: i64,
+    pub token: String,
+    /// See `SharePermission::as_str`.
+    #[validate(custom = "validate_share_permission")]
+    pub permission: String,
+    /// When the link stops working, or `None` if it doesn't.
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+fn validate_share_permission(permission: &str) -> Result<(), ValidationError> {
+    match SharePermission::parse(permission) {
+        Some(_) => Ok(()),
+        None => {
+            let mut err = ValidationError::new("permission");
+            err.message = Some(Cow::from("Permission must be view, claim or edit"));
+            Err(err)
+        }
+    }
+}
+
+impl ListShare {
+    /// Makes a new secret link to the list, returning the new share.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        permission: &str,
+        expires_at: Option<chrono::NaiveDateTime>,
+    ) -> Result<ListShare, DataError> {
+        let share = ListShare {
+            id: 0,
+            list_id,
+            token: crate::util::random_token(),
+            permission: permission.to_string(),
+            expires_at,
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        share.validate()?;
+
+        let share = sqlx::query_as(
+            r#"
+            INSERT INTO list_shares (list_id, token, permission, expires_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, list_id, token, permission, expires_at, created_at, updated_at
+            "#,
+        )
+        .bind(share.list_id)
+        .bind(share.token)
+        .bind(share.permission)
+        .bind(share.expires_at)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(share)
+    }
+
+    /// Returns the share with the given token, or `None` if it doesn't exist or has expired.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<ListShare>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, list_id, token, permission, expires_at, created_at, updated_at
+            FROM list_shares
+            WHERE token = $1 AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns all of the list's shares, including expired ones.
+    pub async fn all_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<Vec<ListShare>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, list_id, token, permission, expires_at, created_at, updated_at
+            FROM list_shares
+            WHERE list_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Revokes one of the list's shares.
+    pub async fn destroy_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM list_shares WHERE id = $1 AND list_id = $2"#)
+            .bind(id)
+            .bind(list_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Misc -----
+
+    /// What the share lets people do. Unknown permissions, e.g. from a newer version, are
+    /// treated as view only.
+    pub fn permission(&self) -> SharePermission {
+        SharePermission::parse(&self.permission).unwrap_or(SharePermission::View)
+    }
+
+    /// Whether the link has stopped working.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| {
+            expires_at <= chrono::Utc::now().naive_utc()
+        })
+    }
+}
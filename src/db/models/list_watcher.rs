@@ -0,0 +1,107 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A user who's emailed when items are added to a list, see `crate::notify`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ListWatcher {
+    pub id: i64,
+    pub list_id: i64,
+    pub user_id: i64,
+    /// The share the user saw the list through, for private lists. The user stops getting emails
+    /// once it expires, and stops watching if it's revoked.
+    pub share_id: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// A watcher to email about a new item, see `ListWatcher::recipients`.
+#[derive(sqlx::FromRow, Debug)]
+pub struct WatchingUser {
+    pub user_id: i64,
+    pub username: String,
+    pub email: String,
+}
+
+impl ListWatcher {
+    /// Starts emailing the user about new items on the list. Watching it again just updates the
+    /// share they see it through.
+    pub async fn watch(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        user_id: i64,
+        share_id: Option<i64>,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO list_watchers (list_id, user_id, share_id, created_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+            ON CONFLICT (list_id, user_id) DO UPDATE SET share_id = $3
+            "#,
+        )
+        .bind(list_id)
+        .bind(user_id)
+        .bind(share_id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Stops emailing the user about the list.
+    pub async fn unwatch(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM list_watchers WHERE list_id = $1 AND user_id = $2"#)
+            .bind(list_id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns whether the user is watching the list.
+    pub async fn is_watching(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        user_id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM list_watchers WHERE list_id = $1 AND user_id = $2"#,
+        )
+        .bind(list_id)
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Returns the watchers to email about a new item on the list: ones who can still see it and
+    /// haven't turned activity emails off, see `NotificationPreferences`.
+    pub async fn recipients(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<Vec<WatchingUser>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT w.user_id, u.username, u.email
+            FROM list_watchers w
+            JOIN lists l ON l.id = w.list_id
+            JOIN users u ON u.id = w.user_id
+            LEFT JOIN list_shares s ON s.id = w.share_id
+            LEFT JOIN notification_preferences p ON p.user_id = w.user_id
+            WHERE w.list_id = $1
+              AND u.suspended_at IS NULL
+              AND COALESCE(p.activity_emails, TRUE) IS TRUE
+              AND (l.privacy <> 'private' OR l.user_id IS NULL OR l.user_id = w.user_id
+                   OR (s.id IS NOT NULL AND (s.expires_at IS NULL OR s.expires_at > CURRENT_TIMESTAMP)))
+            ORDER BY w.id
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+}
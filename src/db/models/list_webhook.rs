@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use url::Url;
+use validator::{Validate, ValidationError};
+
+use crate::db::{DataError, WishlistDb};
+use crate::events::DomainEvent;
+use crate::sources;
+
+/// A webhook that's told about changes to a list: either a chat channel, which gets a message
+/// when an item is added, or an automation, which gets each event as JSON.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ListWebhook {
+    pub id: i64,
+    pub list_id: i64,
+    /// The incoming webhook URL to post to.
+    #[validate(
+        length(max = 2048, message = "Webhook URL must be less than 2048 characters"),
+        custom = "validate_webhook_url"
+    )]
+    pub url: String,
+    /// How messages are formatted, either `slack`, `discord` or `json`.
+    #[validate(custom = "validate_webhook_format")]
+    pub format: String,
+    /// The events a `json` webhook is sent, as a comma-separated list of `DomainEvent::kind`s.
+    /// Empty means all of them.
+    #[validate(custom = "validate_webhook_events")]
+    pub events: String,
+    /// The key posts are signed with, see `crate::notify::sign`. Empty for webhooks made before
+    /// posts were signed.
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Only allows https webhooks, since the URL itself is the secret.
+fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "https" && url.has_host() => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("url");
+            err.message = Some(Cow::from("Webhook URL must be an https URL"));
+            Err(err)
+        }
+    }
+}
+
+fn validate_webhook_format(format: &str) -> Result<(), ValidationError> {
+    match format {
+        ListWebhook::SLACK | ListWebhook::DISCORD | ListWebhook::JSON => Ok(()),
+        _ => {
+            let mut err = ValidationError::new("format");
+            err.message = Some(Cow::from("Format must be Slack, Discord or JSON"));
+            Err(err)
+        }
+    }
+}
+
+fn validate_webhook_events(events: &str) -> Result<(), ValidationError> {
+    if events.is_empty()
+        || events
+            .split(',')
+            .all(|kind| DomainEvent::KINDS.contains(&kind))
+    {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("events");
+        err.message = Some(Cow::from("Unknown event"));
+        Err(err)
+    }
+}
+
+impl ListWebhook {
+    pub const SLACK: &'static str = "slack";
+    pub const DISCORD: &'static str = "discord";
+    pub const JSON: &'static str = "json";
+
+    /// Adds a webhook to the given list, returning the new webhook. `events` is only kept for
+    /// `json` webhooks, see `ListWebhook::events`.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        url: &str,
+        format: &str,
+        events: &[&str],
+    ) -> Result<ListWebhook, DataError> {
+        let events = match format {
+            ListWebhook::JSON => events.join(","),
+            _ => String::new(),
+        };
+        let webhook = ListWebhook {
+            id: 0,
+            list_id,
+            url: url.trim().to_string(),
+            format: format.to_string(),
+            events,
+            secret: crate::util::secure_token(),
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        webhook.validate()?;
+        sources::validate_public(&webhook.url, "url", "Webhook URL must be on a public host")
+            .await?;
+
+        let webhook = sqlx::query_as(
+            r#"
+            INSERT INTO list_webhooks (list_id, url, format, events, secret, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, list_id, url, format, events, secret, created_at, updated_at
+            "#,
+        )
+        .bind(webhook.list_id)
+        .bind(webhook.url)
+        .bind(webhook.format)
+        .bind(webhook.events)
+        .bind(webhook.secret)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Returns all webhooks for the given list.
+    pub async fn all_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<Vec<ListWebhook>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, list_id, url, format, events, secret, created_at, updated_at
+            FROM list_webhooks
+            WHERE list_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Removes a webhook from the given list.
+    pub async fn destroy_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+        id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM list_webhooks WHERE id = $1 AND list_id = $2"#)
+            .bind(id)
+            .bind(list_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Misc -----
+
+    /// Whether a `json` webhook is sent the given kind of event.
+    pub fn wants(&self, kind: &str) -> bool {
+        self.events.is_empty() || self.events.split(',').any(|wanted| wanted == kind)
+    }
+
+    /// The webhook's URL with everything after the host hidden, for showing in the UI.
+    pub fn masked_url(&self) -> String {
+        match Url::parse(&self.url) {
+            Ok(url) => format!("{}://{}/…", url.scheme(), url.host_str().unwrap_or_default()),
+            Err(_) => "…".to_string(),
+        }
+    }
+}
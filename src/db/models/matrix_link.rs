@@ -0,0 +1,142 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::{Validate, ValidationError};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A user's Matrix account, which notifications are sent to as direct messages.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MatrixLink {
+    pub id: i64,
+    pub user_id: i64,
+    /// The user's Matrix ID, e.g. `@alice:example.org`.
+    #[validate(
+        length(max = 255, message = "Matrix ID must be less than 255 characters"),
+        custom = "validate_matrix_id"
+    )]
+    pub matrix_id: String,
+    /// The direct message room with the user, once one has been created.
+    pub room_id: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Checks for the `@localpart:server` form of a Matrix user ID.
+fn validate_matrix_id(matrix_id: &str) -> Result<(), ValidationError> {
+    let valid = match matrix_id.strip_prefix('@').and_then(|id| id.split_once(':')) {
+        Some((localpart, server)) => {
+            !localpart.is_empty()
+                && !server.is_empty()
+                && localpart.chars().all(|c| {
+                    c.is_ascii_lowercase() || c.is_ascii_digit() || "._=-/+".contains(c)
+                })
+        }
+        None => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("matrix_id");
+        err.message = Some(Cow::from("Matrix ID must look like @name:example.org"));
+        Err(err)
+    }
+}
+
+impl MatrixLink {
+    /// Links the user's Matrix account, replacing any previous one.
+    pub async fn set(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        matrix_id: &str,
+    ) -> Result<MatrixLink, DataError> {
+        let link = MatrixLink {
+            id: 0,
+            user_id,
+            matrix_id: matrix_id.trim().to_string(),
+            room_id: None,
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        link.validate()?;
+
+        // A new Matrix ID needs a new direct message room
+        let link = sqlx::query_as(
+            r#"
+            INSERT INTO matrix_links (user_id, matrix_id, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id)
+            DO UPDATE SET matrix_id = $2, room_id = NULL, updated_at = CURRENT_TIMESTAMP
+            RETURNING id, user_id, matrix_id, room_id, created_at, updated_at
+            "#,
+        )
+        .bind(link.user_id)
+        .bind(link.matrix_id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Returns the user's linked Matrix account, or `None` if they haven't linked one.
+    pub async fn find_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<MatrixLink>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, matrix_id, room_id, created_at, updated_at
+            FROM matrix_links
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Unlinks the user's Matrix account.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM matrix_links WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the user's linked Matrix account, or `None` if they haven't linked one.
+    pub async fn find_for_user(
+        pool: &sqlx::AnyPool,
+        user_id: i64,
+    ) -> Result<Option<MatrixLink>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, matrix_id, room_id, created_at, updated_at
+            FROM matrix_links
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Remembers the direct message room created for this link.
+    pub async fn set_room_id(&mut self, pool: &sqlx::AnyPool, room_id: &str) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE matrix_links SET room_id = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(room_id)
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+        self.room_id = Some(room_id.to_string());
+        Ok(())
+    }
+}
@@ -1,11 +1,88 @@
+mod account_export;
+mod api_token;
+mod audit_event;
+mod claim;
+mod claim_event;
+mod delivery;
+mod email_suppression;
+mod email_verification;
+mod event;
+mod feature_flag;
+mod fund_link;
+mod gift_split;
+mod hook_subscription;
 mod image;
+mod inbound_address;
 mod item;
+mod item_contribution;
+mod item_gift;
+mod item_price;
 mod list;
+mod list_share;
+mod list_watcher;
+mod list_webhook;
+mod matrix_link;
+mod notification_preferences;
+mod passkey;
+mod password_reset_token;
+mod poll;
+mod price_alert;
+mod quota_exemption;
+mod rate_limit_counter;
+mod role;
+mod saved_search;
+mod search;
+mod suspension_appeal;
+mod tag;
+mod upload;
 mod user;
+mod username_history;
+mod user_device;
 mod user_session;
+mod user_stats;
 
+pub use account_export::AccountExport;
+pub use api_token::{ApiScope, ApiToken};
+// For making tokens in tests, see `crate::testing`
+#[cfg(any(all(test, debug_assertions), feature = "testing"))]
+pub use api_token::TOKEN_PREFIX;
+pub use audit_event::AuditEvent;
+pub use claim::{Claim, DueClaimReminder};
+pub use claim_event::ClaimEvent;
+pub use delivery::Delivery;
+pub use email_suppression::EmailSuppression;
+pub use email_verification::EmailVerification;
+pub use event::{Event, EventCount};
+pub use feature_flag::FeatureFlag;
+pub use fund_link::FundLink;
+pub use gift_split::GiftSplit;
+pub use hook_subscription::HookSubscription;
 pub use image::Image;
-pub use item::Item;
-pub use list::List;
-pub use user::User;
+pub use inbound_address::InboundAddress;
+pub use item::{Item, ItemFilter, ItemKind, ItemOrder, ItemPriority};
+pub use item_contribution::ItemContribution;
+pub use item_gift::ItemGift;
+pub use item_price::ItemPrice;
+pub use list::{List, ListFilter, ListOrder, ListPrivacy, StaleList};
+pub use list_share::{ListShare, SharePermission};
+pub use list_watcher::ListWatcher;
+pub use list_webhook::ListWebhook;
+pub use matrix_link::MatrixLink;
+pub use notification_preferences::NotificationPreferences;
+pub use passkey::Passkey;
+pub use password_reset_token::PasswordResetToken;
+pub use poll::Poll;
+pub use price_alert::PriceAlert;
+pub use quota_exemption::QuotaExemption;
+pub use rate_limit_counter::RateLimitCounter;
+pub use role::{PermissionKind, Role};
+pub use saved_search::{ActiveSavedSearch, NewItem, SavedSearch};
+pub use search::{SearchHit, SearchIndexHealth, SearchKind, SearchTask};
+pub use suspension_appeal::SuspensionAppeal;
+pub use tag::Tag;
+pub use upload::Upload;
+pub use user::{User, DIRECTORY_AUTH_SOURCE};
+pub use username_history::UsernameHistory;
+pub use user_device::UserDevice;
 pub use user_session::UserSession;
+pub use user_stats::UserStats;
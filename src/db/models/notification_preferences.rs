@@ -0,0 +1,110 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// Which activity emails a user gets, see `crate::notify`. Users start out getting all of them,
+/// and the row is only made when it's first needed, see `NotificationPreferences::for_user`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NotificationPreferences {
+    pub user_id: i64,
+    /// Whether the user is emailed when someone claims an item on one of their lists. Only lists
+    /// that show the owner gifting activity send these, see `List::reveal_gifting`.
+    pub claim_emails: bool,
+    /// Whether the user is emailed when items are added to lists they watch, see `ListWatcher`.
+    pub activity_emails: bool,
+    /// Lets the links at the bottom of the emails unsubscribe without logging in.
+    #[serde(skip_serializing)]
+    pub unsubscribe_token: String,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl NotificationPreferences {
+    pub const CLAIMS: &'static str = "claims";
+    pub const ACTIVITY: &'static str = "activity";
+
+    /// Returns the user's preferences, making them with everything turned on if they don't have
+    /// any yet.
+    pub async fn for_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<NotificationPreferences, sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO notification_preferences (user_id, unsubscribe_token, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(crate::util::secure_token())
+        .execute(&mut **conn)
+        .await?;
+
+        sqlx::query_as(
+            r#"
+            SELECT user_id, claim_emails, activity_emails, unsubscribe_token, updated_at
+            FROM notification_preferences
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await
+    }
+
+    /// Returns the preferences an unsubscribe link is for, or `None` if the token is unknown.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<NotificationPreferences>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT user_id, claim_emails, activity_emails, unsubscribe_token, updated_at
+            FROM notification_preferences
+            WHERE unsubscribe_token = $1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Turns both kinds of activity emails on or off.
+    pub async fn update(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        claim_emails: bool,
+        activity_emails: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            UPDATE notification_preferences
+            SET claim_emails = $1, activity_emails = $2, updated_at = CURRENT_TIMESTAMP
+            WHERE user_id = $3
+            "#,
+        )
+        .bind(claim_emails)
+        .bind(activity_emails)
+        .bind(self.user_id)
+        .execute(&mut **conn)
+        .await?;
+        self.claim_emails = claim_emails;
+        self.activity_emails = activity_emails;
+        Ok(())
+    }
+
+    /// Turns off one kind of email, either `CLAIMS` or `ACTIVITY`, for an unsubscribe link.
+    pub async fn unsubscribe(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        kind: &str,
+    ) -> Result<(), DataError> {
+        match kind {
+            NotificationPreferences::CLAIMS => self.update(conn, false, self.activity_emails).await,
+            NotificationPreferences::ACTIVITY => self.update(conn, self.claim_emails, false).await,
+            _ => Err(DataError::Other("Unknown kind of email".to_string())),
+        }
+    }
+}
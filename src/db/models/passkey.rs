@@ -0,0 +1,123 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A WebAuthn credential a user can log in with instead of their password.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Passkey {
+    pub id: i64,
+    pub user_id: i64,
+    /// A name the user gave the passkey, e.g. "Phone".
+    pub name: String,
+    /// The credential's ID, base64url encoded.
+    pub credential_id: String,
+    /// The serialized `webauthn_rs` passkey, including its public key and signature counter.
+    #[serde(skip_serializing)]
+    pub passkey: String,
+    /// When the passkey was last used to log in.
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Passkey {
+    /// Saves a newly registered passkey for the user, returning the new passkey.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        name: &str,
+        credential_id: &str,
+        passkey: &str,
+    ) -> Result<Passkey, DataError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 255 {
+            return Err(DataError::Other(
+                "Passkey name must be between 1 and 255 characters".to_string(),
+            ));
+        }
+
+        let passkey = sqlx::query_as(
+            r#"
+            INSERT INTO passkeys (user_id, name, credential_id, passkey, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, name, credential_id, passkey, last_used_at, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(credential_id)
+        .bind(passkey)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(passkey)
+    }
+
+    /// Returns all of the user's passkeys.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<Passkey>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, name, credential_id, passkey, last_used_at, created_at, updated_at
+            FROM passkeys
+            WHERE user_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the passkey with the given credential ID, or `None` if it isn't registered.
+    pub async fn find_by_credential_id(
+        conn: &mut Connection<WishlistDb>,
+        credential_id: &str,
+    ) -> Result<Option<Passkey>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, name, credential_id, passkey, last_used_at, created_at, updated_at
+            FROM passkeys
+            WHERE credential_id = $1
+            "#,
+        )
+        .bind(credential_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Records a login with the passkey, storing its updated signature counter.
+    pub async fn record_use(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        passkey: &str,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"UPDATE passkeys SET passkey = $1, last_used_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#,
+        )
+        .bind(passkey)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        self.passkey = passkey.to_string();
+        Ok(())
+    }
+
+    /// Removes one of the user's passkeys.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM passkeys WHERE id = $1 AND user_id = $2"#)
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
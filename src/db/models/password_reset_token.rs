@@ -0,0 +1,87 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A link sent to a user who forgot their password, letting them set a new one until it expires.
+///
+/// Only the token's hash is stored, so the database can't be used to reset anyone's password.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PasswordResetToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl PasswordResetToken {
+    /// Makes a new reset token for the user that expires at the given time, returning it along
+    /// with the token itself.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        expires_at: chrono::NaiveDateTime,
+    ) -> Result<(PasswordResetToken, String), DataError> {
+        let token = crate::util::secure_token();
+        let reset = sqlx::query_as(
+            r#"
+            INSERT INTO password_reset_tokens (user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, expires_at, created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(crate::util::hash_token(&token))
+        .bind(expires_at)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok((reset, token))
+    }
+
+    /// Returns the reset with the given token, or `None` if it doesn't exist or has expired.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, expires_at, created_at
+            FROM password_reset_tokens
+            WHERE token_hash = $1 AND expires_at > CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(crate::util::hash_token(token))
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns true if a reset was sent to the user after the given time.
+    pub async fn sent_since(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        since: chrono::NaiveDateTime,
+    ) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM password_reset_tokens WHERE user_id = $1 AND created_at > $2"#,
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_one(&mut **conn)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Removes all of the user's reset tokens, once one has been used.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM password_reset_tokens WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
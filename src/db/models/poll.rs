@@ -0,0 +1,224 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::Validate;
+
+use crate::db::{DataError, WishlistDb};
+
+/// A poll about an item, where people vote for any of the options that work for them.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Poll {
+    pub id: i64,
+    pub item_id: i64,
+    /// The user who started the poll, and who gets to close it.
+    pub created_by: i64,
+    #[validate(length(
+        min = 1,
+        max = 256,
+        message = "Question must be between 1 and 256 characters"
+    ))]
+    pub question: String,
+    /// The option that was picked when the poll was closed, or `None` while it's open.
+    pub chosen_option_id: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// One of a poll's options, with how many people voted for it.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PollOption {
+    pub id: i64,
+    pub poll_id: i64,
+    pub label: String,
+    pub vote_count: i64,
+}
+
+impl Poll {
+    /// Starts a poll on the item with the given options.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        created_by: i64,
+        question: &str,
+        options: &[String],
+    ) -> Result<Poll, DataError> {
+        if options.len() < 2 {
+            return Err(DataError::Other(
+                "A poll needs at least two options".to_string(),
+            ));
+        }
+
+        let poll = Poll {
+            id: 0,
+            item_id,
+            created_by,
+            question: question.trim().to_string(),
+            chosen_option_id: None,
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        poll.validate()?;
+
+        let poll: Poll = sqlx::query_as(
+            r#"
+            INSERT INTO polls (item_id, created_by, question, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, item_id, created_by, question, chosen_option_id, created_at, updated_at
+            "#,
+        )
+        .bind(poll.item_id)
+        .bind(poll.created_by)
+        .bind(poll.question)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        for label in options {
+            sqlx::query(
+                r#"
+                INSERT INTO poll_options (poll_id, label, created_at, updated_at)
+                VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                "#,
+            )
+            .bind(poll.id)
+            .bind(label)
+            .execute(&mut **conn)
+            .await?;
+        }
+
+        Ok(poll)
+    }
+
+    /// Returns the item's poll, or `None` if nobody has started one.
+    pub async fn find_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Option<Poll>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, created_by, question, chosen_option_id, created_at, updated_at
+            FROM polls
+            WHERE item_id = $1
+            "#,
+        )
+        .bind(item_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the poll's options in the order they were added, with their vote counts.
+    pub async fn options(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<Vec<PollOption>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT o.id, o.poll_id, o.label, COUNT(v.id) AS vote_count
+            FROM poll_options o
+            LEFT JOIN poll_votes v ON v.option_id = o.id
+            WHERE o.poll_id = $1
+            GROUP BY o.id, o.poll_id, o.label
+            ORDER BY o.id
+            "#,
+        )
+        .bind(self.id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the IDs of the options the user voted for.
+    pub async fn votes_by_user(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT v.option_id
+            FROM poll_votes v
+            JOIN poll_options o ON o.id = v.option_id
+            WHERE o.poll_id = $1 AND v.user_id = $2
+            "#,
+        )
+        .bind(self.id)
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Replaces the user's votes. Options that aren't part of this poll are ignored.
+    pub async fn set_votes(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        option_ids: &[i64],
+    ) -> Result<(), DataError> {
+        if self.chosen_option_id.is_some() {
+            return Err(DataError::Other("This poll is closed".to_string()));
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM poll_votes
+            WHERE user_id = $1 AND option_id IN (SELECT id FROM poll_options WHERE poll_id = $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+
+        for option_id in option_ids {
+            sqlx::query(
+                r#"
+                INSERT INTO poll_votes (option_id, user_id, created_at, updated_at)
+                SELECT id, $1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+                FROM poll_options
+                WHERE id = $2 AND poll_id = $3
+                ON CONFLICT (option_id, user_id) DO NOTHING
+                "#,
+            )
+            .bind(user_id)
+            .bind(option_id)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Closes the poll with one of its options as the outcome, or reopens it with `None`.
+    pub async fn set_chosen_option(
+        &mut self,
+        conn: &mut Connection<WishlistDb>,
+        option_id: Option<i64>,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            UPDATE polls
+            SET chosen_option_id = $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            "#,
+        )
+        .bind(option_id)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+
+        self.chosen_option_id = option_id;
+        Ok(())
+    }
+
+    /// Deletes the poll, its options and everyone's votes.
+    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        if self.id != 0 {
+            sqlx::query(r#"DELETE FROM polls WHERE id = $1"#)
+                .bind(self.id)
+                .execute(&mut **conn)
+                .await?;
+            self.id = 0;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A user's request to be notified when an item's tracked price reaches a target.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PriceAlert {
+    pub id: i64,
+    pub item_id: i64,
+    pub user_id: i64,
+    /// The target price in the smallest unit of its currency (e.g. cents).
+    pub target_cents: i64,
+    /// An ISO 4217 currency code.
+    pub currency: String,
+    /// When the user was notified, or `None` if the target hasn't been reached yet.
+    pub triggered_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// An alert whose target was reached, with what's needed to notify the user.
+#[derive(sqlx::FromRow, Debug)]
+pub struct ReachedAlert {
+    pub id: i64,
+    pub item_id: i64,
+    pub item_title: String,
+    pub list_key: String,
+    pub user_email: String,
+    pub target_cents: i64,
+    pub currency: String,
+}
+
+impl PriceAlert {
+    /// Sets the user's target price for the item, replacing any previous alert and re-arming it.
+    pub async fn set(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+        target_cents: i64,
+        currency: &str,
+    ) -> Result<PriceAlert, DataError> {
+        if target_cents <= 0 {
+            return Err(DataError::Other("Target price must be more than zero".to_string()));
+        }
+
+        let alert = sqlx::query_as(
+            r#"
+            INSERT INTO price_alerts (item_id, user_id, target_cents, currency, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (item_id, user_id)
+            DO UPDATE SET target_cents = $3, currency = $4, triggered_at = NULL, updated_at = CURRENT_TIMESTAMP
+            RETURNING id, item_id, user_id, target_cents, currency, triggered_at, created_at, updated_at
+            "#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .bind(target_cents)
+        .bind(currency)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(alert)
+    }
+
+    /// Returns the user's alert for the item, or `None` if they haven't set one.
+    pub async fn find_by_item_and_user(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+    ) -> Result<Option<PriceAlert>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, item_id, user_id, target_cents, currency, triggered_at, created_at, updated_at
+            FROM price_alerts
+            WHERE item_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(item_id)
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Removes the user's alert for the item.
+    pub async fn destroy_by_item_and_user(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM price_alerts WHERE item_id = $1 AND user_id = $2"#)
+            .bind(item_id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the untriggered alerts for the item whose target the given price has reached.
+    pub async fn all_reached(
+        pool: &sqlx::AnyPool,
+        item_id: i64,
+        amount_cents: i64,
+        currency: &str,
+    ) -> Result<Vec<ReachedAlert>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT a.id, a.item_id, i.title AS item_title, l.key AS list_key, u.email AS user_email,
+                   a.target_cents, a.currency
+            FROM price_alerts a
+            JOIN items i ON i.id = a.item_id
+            JOIN lists l ON l.id = i.list_id
+            JOIN users u ON u.id = a.user_id
+            WHERE a.item_id = $1 AND a.currency = $2 AND a.target_cents >= $3
+              AND a.triggered_at IS NULL AND u.suspended_at IS NULL
+            "#,
+        )
+        .bind(item_id)
+        .bind(currency)
+        .bind(amount_cents)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Marks the alert as triggered so the user is only notified once.
+    pub async fn mark_triggered(pool: &sqlx::AnyPool, id: i64) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE price_alerts SET triggered_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1"#)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// Frees a user from the instance's usage quotas. See `crate::quotas`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct QuotaExemption {
+    pub id: i64,
+    pub user_id: i64,
+    /// The admin who exempted the user, or `None` if they've since been deleted.
+    pub granted_by: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl QuotaExemption {
+    /// Exempts the user from quotas. Does nothing if they already are.
+    pub async fn grant(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        granted_by: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO quota_exemptions (user_id, granted_by, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(granted_by)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the user's exemption, or `None` if quotas apply to them.
+    pub async fn find_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Option<QuotaExemption>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, granted_by, created_at, updated_at
+            FROM quota_exemptions
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Makes quotas apply to the user again.
+    pub async fn revoke(conn: &mut Connection<WishlistDb>, user_id: i64) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM quota_exemptions WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
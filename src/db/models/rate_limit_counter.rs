@@ -0,0 +1,56 @@
+use rocket_db_pools::sqlx;
+
+/// A rate limit's count, saved so limits survive restarts when `rate_limits.persist` is on. See
+/// `crate::rate_limits`.
+#[derive(sqlx::FromRow, Debug)]
+pub struct RateLimitCounter {
+    /// What's counted, like `login:ip:127.0.0.1`.
+    pub key: String,
+    pub hits: i64,
+    /// When the count starts over.
+    pub window_ends_at: chrono::NaiveDateTime,
+}
+
+impl RateLimitCounter {
+    /// Returns the counters whose window hasn't ended yet.
+    pub async fn all_current(
+        pool: &sqlx::AnyPool,
+        now: chrono::NaiveDateTime,
+    ) -> Result<Vec<RateLimitCounter>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT key, hits, window_ends_at
+            FROM rate_limit_counters
+            WHERE window_ends_at > $1
+            "#,
+        )
+        .bind(now)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Replaces the saved counters with `counters`, in one transaction.
+    pub async fn replace_all(
+        pool: &sqlx::AnyPool,
+        counters: &[RateLimitCounter],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        sqlx::query(r#"DELETE FROM rate_limit_counters"#)
+            .execute(&mut tx)
+            .await?;
+        for counter in counters {
+            sqlx::query(
+                r#"
+                INSERT INTO rate_limit_counters (key, hits, window_ends_at)
+                VALUES ($1, $2, $3)
+                "#,
+            )
+            .bind(&counter.key)
+            .bind(counter.hits)
+            .bind(counter.window_ends_at)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+}
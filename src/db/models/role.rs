@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::Validate;
+
+use crate::db::{DataError, WishlistDb};
+
+/// Something a role can allow its users to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum PermissionKind {
+    /// Hide or remove other users' lists and items.
+    ModerateContent,
+    /// Change site-wide settings, including roles.
+    ManageSettings,
+    /// Suspend users and assign roles.
+    ManageUsers,
+    /// See site-wide statistics.
+    ViewAnalytics,
+}
+
+impl PermissionKind {
+    pub const ALL: [PermissionKind; 4] = [
+        PermissionKind::ModerateContent,
+        PermissionKind::ManageSettings,
+        PermissionKind::ManageUsers,
+        PermissionKind::ViewAnalytics,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionKind::ModerateContent => "moderate_content",
+            PermissionKind::ManageSettings => "manage_settings",
+            PermissionKind::ManageUsers => "manage_users",
+            PermissionKind::ViewAnalytics => "view_analytics",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<PermissionKind> {
+        PermissionKind::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == value)
+    }
+
+    /// A name for the permission, for the admin pages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PermissionKind::ModerateContent => "Moderate content",
+            PermissionKind::ManageSettings => "Manage settings",
+            PermissionKind::ManageUsers => "Manage users",
+            PermissionKind::ViewAnalytics => "View analytics",
+        }
+    }
+}
+
+/// A named set of permissions that can be given to users.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Role {
+    pub id: i64,
+    #[validate(length(
+        min = 1,
+        max = 64,
+        message = "Name must be between 1 and 64 characters"
+    ))]
+    pub name: String,
+    /// The role's permissions, comma separated. See `Role::permissions`.
+    pub permissions: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// Joins permissions for storing them.
+fn join_permissions(permissions: &[PermissionKind]) -> String {
+    permissions
+        .iter()
+        .map(|permission| permission.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Role {
+    /// Creates a new role, returning the new role.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        name: &str,
+        permissions: &[PermissionKind],
+    ) -> Result<Role, DataError> {
+        let role = Role {
+            id: 0,
+            name: name.trim().to_string(),
+            permissions: join_permissions(permissions),
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        role.validate()?;
+
+        let role = sqlx::query_as(
+            r#"
+            INSERT INTO roles (name, permissions, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, name, permissions, created_at, updated_at
+            "#,
+        )
+        .bind(role.name)
+        .bind(role.permissions)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(role)
+    }
+
+    /// Returns all roles.
+    pub async fn all(conn: &mut Connection<WishlistDb>) -> Result<Vec<Role>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, name, permissions, created_at, updated_at
+            FROM roles
+            ORDER BY name
+            "#,
+        )
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the role with the given ID, or `None` if no role with that ID exists.
+    pub async fn find_by_id(
+        conn: &mut Connection<WishlistDb>,
+        id: i64,
+    ) -> Result<Option<Role>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, name, permissions, created_at, updated_at
+            FROM roles
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the roles given to the user.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<Role>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT r.id, r.name, r.permissions, r.created_at, r.updated_at
+            FROM roles r
+            JOIN user_roles ur ON ur.role_id = r.id
+            WHERE ur.user_id = $1
+            ORDER BY r.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Replaces the role's permissions, returning an updated copy of the role.
+    pub async fn set_permissions(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        permissions: &[PermissionKind],
+    ) -> Result<Role, DataError> {
+        let role = sqlx::query_as(
+            r#"
+            UPDATE roles
+            SET permissions = $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, name, permissions, created_at, updated_at
+            "#,
+        )
+        .bind(join_permissions(permissions))
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(role)
+    }
+
+    /// Deletes the role, taking it away from everyone who had it.
+    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        if self.id != 0 {
+            sqlx::query(r#"DELETE FROM roles WHERE id = $1"#)
+                .bind(self.id)
+                .execute(&mut **conn)
+                .await?;
+            self.id = 0;
+        }
+        Ok(())
+    }
+
+    /// Gives the role to the user. Does nothing if they already have it.
+    pub async fn assign(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_roles (user_id, role_id, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            ON CONFLICT (user_id, role_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Takes the role away from the user.
+    pub async fn unassign(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2"#)
+            .bind(user_id)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Misc -----
+
+    /// The role's permissions. Unknown names, e.g. from a newer version, are ignored.
+    pub fn permissions(&self) -> Vec<PermissionKind> {
+        self.permissions
+            .split(',')
+            .filter_map(PermissionKind::parse)
+            .collect()
+    }
+
+    /// Everything the user is allowed to do through their roles.
+    pub async fn permissions_for_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<HashSet<PermissionKind>, sqlx::Error> {
+        Ok(Role::all_by_user(conn, user_id)
+            .await?
+            .iter()
+            .flat_map(|role| role.permissions())
+            .collect())
+    }
+}
@@ -0,0 +1,226 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// The most searches each user can save.
+const MAX_SAVED_SEARCHES: i64 = 20;
+
+/// A search a user wants to be emailed about when new items match it.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SavedSearch {
+    pub id: i64,
+    pub user_id: i64,
+    pub query: String,
+    /// The lowest price matching items can have, in the smallest unit of `currency` (e.g. cents).
+    pub min_price_cents: Option<i64>,
+    /// The highest price matching items can have.
+    pub max_price_cents: Option<i64>,
+    /// An ISO 4217 currency code for the price range, or `None` if there isn't one.
+    pub currency: Option<String>,
+    /// The newest item that existed when the search was last checked. Only items after it are
+    /// new.
+    pub last_item_id: i64,
+    /// When the user was last emailed about new matches.
+    pub notified_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// A saved search to check, with where to email the user.
+#[derive(sqlx::FromRow, Debug)]
+pub struct ActiveSavedSearch {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_email: String,
+    pub query: String,
+    pub min_price_cents: Option<i64>,
+    pub max_price_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub last_item_id: i64,
+}
+
+/// An item added since a saved search was last checked.
+#[derive(sqlx::FromRow, Debug)]
+pub struct NewItem {
+    pub id: i64,
+    pub list_key: String,
+    pub title: String,
+    pub description: String,
+    /// The latest tracked price, the price the item was added with (the middle of its range, see
+    /// `Item::price_midpoint_cents`), or the amount for gift cards and cash funds.
+    pub price_cents: Option<i64>,
+    pub price_currency: Option<String>,
+}
+
+impl SavedSearch {
+    /// Saves a search for the user. Only items added from now on are matched.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        query: &str,
+        min_price_cents: Option<i64>,
+        max_price_cents: Option<i64>,
+        currency: Option<&str>,
+    ) -> Result<SavedSearch, DataError> {
+        let query = query.trim();
+        if query.is_empty() || query.len() > 256 {
+            return Err(DataError::Other(
+                "Search must be between 1 and 256 characters".to_string(),
+            ));
+        }
+        if let (Some(min), Some(max)) = (min_price_cents, max_price_cents) {
+            if min > max {
+                return Err(DataError::Other(
+                    "Lowest price can't be more than the highest price".to_string(),
+                ));
+            }
+        }
+        if SavedSearch::count_by_user(conn, user_id).await? >= MAX_SAVED_SEARCHES {
+            return Err(DataError::Other(format!(
+                "You can save at most {} searches. Delete one to make room.",
+                MAX_SAVED_SEARCHES
+            )));
+        }
+
+        let search = sqlx::query_as(
+            r#"
+            INSERT INTO saved_searches (user_id, query, min_price_cents, max_price_cents, currency, last_item_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, (SELECT COALESCE(MAX(id), 0) FROM items), CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, query, min_price_cents, max_price_cents, currency, last_item_id, notified_at, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(query)
+        .bind(min_price_cents)
+        .bind(max_price_cents)
+        .bind(currency)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(search)
+    }
+
+    /// Returns the user's saved searches, oldest first.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<SavedSearch>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, query, min_price_cents, max_price_cents, currency, last_item_id, notified_at, created_at, updated_at
+            FROM saved_searches
+            WHERE user_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns how many searches the user has saved.
+    pub async fn count_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM saved_searches WHERE user_id = $1"#)
+            .bind(user_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+
+    /// Removes one of the user's saved searches.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        id: i64,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM saved_searches WHERE id = $1 AND user_id = $2"#)
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Returns every saved search whose user isn't suspended.
+    pub async fn all_active(pool: &sqlx::AnyPool) -> Result<Vec<ActiveSavedSearch>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT s.id, s.user_id, u.email AS user_email, s.query, s.min_price_cents,
+                   s.max_price_cents, s.currency, s.last_item_id
+            FROM saved_searches s
+            JOIN users u ON u.id = s.user_id
+            WHERE u.suspended_at IS NULL
+            ORDER BY s.id
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Moves the search past the given item, recording that the user was emailed if they were.
+    pub async fn mark_checked(
+        pool: &sqlx::AnyPool,
+        id: i64,
+        last_item_id: i64,
+        notified: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            UPDATE saved_searches
+            SET last_item_id = $2,
+                notified_at = CASE WHEN $3 THEN CURRENT_TIMESTAMP ELSE notified_at END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(last_item_id)
+        .bind(notified)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+}
+
+impl NewItem {
+    /// Returns items still wanted on public lists that were added after the given item, oldest
+    /// first. Items on the user's own lists are left out.
+    pub async fn all_after(
+        pool: &sqlx::AnyPool,
+        after_id: i64,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<NewItem>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT i.id, l.key AS list_key, i.title, i.description,
+                   COALESCE((SELECT p.amount_cents FROM item_prices p WHERE p.item_id = i.id ORDER BY p.id DESC LIMIT 1), (i.price_cents + COALESCE(i.price_max_cents, i.price_cents)) / 2, i.amount_cents) AS price_cents,
+                   COALESCE((SELECT p.currency FROM item_prices p WHERE p.item_id = i.id ORDER BY p.id DESC LIMIT 1), i.price_currency, i.currency) AS price_currency
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE i.id > $1 AND i.received_at IS NULL
+              AND l.privacy = 'public' AND (l.user_id IS NULL OR l.user_id <> $2)
+            ORDER BY i.id
+            LIMIT $3
+            "#,
+        )
+        .bind(after_id)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Returns the newest item's ID, or 0 if there are no items.
+    pub async fn latest_id(pool: &sqlx::AnyPool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COALESCE(MAX(id), 0) FROM items"#)
+            .fetch_one(pool)
+            .await
+    }
+}
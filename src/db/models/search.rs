@@ -0,0 +1,380 @@
+use std::collections::HashSet;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+use crate::fuzzy;
+
+/// The most words a search query can have. Extra ones are ignored.
+const MAX_TERMS: usize = 8;
+
+/// How many documents sharing trigrams with the query are scored.
+const MAX_CANDIDATES: i64 = 500;
+
+/// How many words of the description go in a snippet.
+const SNIPPET_WORDS: usize = 24;
+
+/// How many trigrams are written per insert, keeping under SQLite's bind parameter limit.
+const TRIGRAMS_PER_INSERT: usize = 200;
+
+/// What a search document or task is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchKind {
+    List,
+    Item,
+}
+
+impl SearchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchKind::List => "list",
+            SearchKind::Item => "item",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SearchKind> {
+        match value {
+            "list" => Some(SearchKind::List),
+            "item" => Some(SearchKind::Item),
+            _ => None,
+        }
+    }
+}
+
+/// A list or item that matched a search.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SearchHit {
+    pub list_key: String,
+    pub list_title: String,
+    /// The item that matched, or `None` if it was the list itself.
+    pub item_id: Option<i64>,
+    pub title: String,
+    /// How well it matched, from 0 to 1. Hits are sorted by this.
+    pub score: f64,
+    /// The title as HTML, with matching words wrapped in `<mark>`.
+    pub title_html: String,
+    /// A few words of the description around the first match as HTML, with matching words
+    /// wrapped in `<mark>`, or `None` if there's no description.
+    pub snippet_html: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SearchCandidate {
+    list_key: String,
+    list_title: String,
+    item_id: Option<i64>,
+    title: String,
+    content: String,
+    body: String,
+}
+
+/// A list or item waiting to be (re)indexed. Written whenever a list or item changes, and worked
+/// through by the search indexer job.
+#[derive(sqlx::FromRow, Debug)]
+pub struct SearchTask {
+    pub id: i64,
+    pub kind: String,
+    pub target_id: i64,
+}
+
+/// How far behind the search index is, for the metrics endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SearchIndexHealth {
+    /// How many lists and items are indexed.
+    pub documents: i64,
+    /// How many lists and items are waiting to be indexed.
+    pub queued: i64,
+    /// When the longest waiting task was queued, or `None` if the index is up to date.
+    pub oldest_queued_at: Option<chrono::NaiveDateTime>,
+}
+
+impl SearchHit {
+    /// Searches the index for lists and items matching every word in the query, allowing for
+    /// typos. Only public lists and the user's own lists are searched.
+    ///
+    /// Documents sharing the most trigrams with the query are fetched first, then scored and
+    /// sorted by how well they match.
+    pub async fn search(
+        conn: &mut Connection<WishlistDb>,
+        query: &str,
+        user_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let mut query_words = fuzzy::words(query);
+        query_words.truncate(MAX_TERMS);
+        let trigrams = query_words
+            .iter()
+            .flat_map(|word| fuzzy::word_trigrams(word))
+            .collect::<HashSet<_>>();
+        if trigrams.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let placeholders = (0..trigrams.len())
+            .map(|n| format!("${}", n + 3))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"
+            SELECT l.key AS list_key, l.title AS list_title, d.item_id, d.title, d.content, d.body
+            FROM search_trigrams t
+            JOIN search_documents d ON d.id = t.document_id
+            JOIN lists l ON l.id = d.list_id
+            WHERE (l.privacy = 'public' OR l.user_id = $1)
+            AND t.trigram IN ({})
+            GROUP BY d.id, l.key, l.title, d.item_id, d.title, d.content, d.body
+            ORDER BY COUNT(*) DESC
+            LIMIT $2
+            "#,
+            placeholders
+        );
+
+        let mut candidates = sqlx::query_as::<_, SearchCandidate>(&sql)
+            .bind(user_id)
+            .bind(MAX_CANDIDATES);
+        for trigram in &trigrams {
+            candidates = candidates.bind(trigram);
+        }
+        let candidates = candidates.fetch_all(&mut **conn).await?;
+
+        let mut hits = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let content = candidate
+                    .content
+                    .split(' ')
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                let score = fuzzy::score(&query_words, &content);
+                if score == 0.0 {
+                    return None;
+                }
+                // Matches in the title count for a bit more
+                let title_score = fuzzy::score(&query_words, &fuzzy::words(&candidate.title));
+                let snippet_html = if candidate.body.trim().is_empty() {
+                    None
+                } else {
+                    Some(fuzzy::highlight(
+                        &candidate.body,
+                        &query_words,
+                        Some(SNIPPET_WORDS),
+                    ))
+                };
+
+                Some(SearchHit {
+                    score: score * 0.75 + title_score * 0.25,
+                    title_html: fuzzy::highlight(&candidate.title, &query_words, None),
+                    snippet_html,
+                    list_key: candidate.list_key,
+                    list_title: candidate.list_title,
+                    item_id: candidate.item_id,
+                    title: candidate.title,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.item_id.is_some().cmp(&b.item_id.is_some()))
+                .then_with(|| a.title.cmp(&b.title))
+        });
+        hits.truncate(limit.max(0) as usize);
+        Ok(hits)
+    }
+}
+
+impl SearchTask {
+    /// Queues the list or item for indexing. Does nothing if it's already queued.
+    pub async fn push(
+        conn: &mut Connection<WishlistDb>,
+        kind: SearchKind,
+        target_id: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO search_tasks (kind, target_id, created_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP)
+            ON CONFLICT (kind, target_id) DO NOTHING
+            "#,
+        )
+        .bind(kind.as_str())
+        .bind(target_id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Queues every list and item for indexing.
+    pub async fn push_all(conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        for (kind, table) in [(SearchKind::List, "lists"), (SearchKind::Item, "items")] {
+            sqlx::query(&format!(
+                r#"
+                INSERT INTO search_tasks (kind, target_id, created_at)
+                SELECT $1, id, CURRENT_TIMESTAMP FROM {} WHERE TRUE
+                ON CONFLICT (kind, target_id) DO NOTHING
+                "#,
+                table
+            ))
+            .bind(kind.as_str())
+            .execute(&mut **conn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the state of the index.
+    pub async fn health(
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<SearchIndexHealth, sqlx::Error> {
+        let documents = sqlx::query_scalar(r#"SELECT COUNT(*) FROM search_documents"#)
+            .fetch_one(&mut **conn)
+            .await?;
+        let (queued, oldest_queued_at) =
+            sqlx::query_as(r#"SELECT COUNT(*), MIN(created_at) FROM search_tasks"#)
+                .fetch_one(&mut **conn)
+                .await?;
+
+        Ok(SearchIndexHealth {
+            documents,
+            queued,
+            oldest_queued_at,
+        })
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the longest waiting tasks.
+    pub async fn next_batch(
+        pool: &sqlx::AnyPool,
+        limit: i64,
+    ) -> Result<Vec<SearchTask>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, kind, target_id
+            FROM search_tasks
+            ORDER BY id
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Removes the task from the queue and indexes its list or item.
+    ///
+    /// The task is removed first, so changes made while indexing queue a new task rather than
+    /// being lost.
+    pub async fn run(&self, pool: &sqlx::AnyPool) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM search_tasks WHERE id = $1"#)
+            .bind(self.id)
+            .execute(pool)
+            .await?;
+
+        match SearchKind::parse(&self.kind) {
+            Some(SearchKind::List) => index_list(pool, self.target_id).await,
+            Some(SearchKind::Item) => index_item(pool, self.target_id).await,
+            None => Ok(()),
+        }
+    }
+}
+
+// ----- Internal -----
+
+async fn index_list(pool: &sqlx::AnyPool, id: i64) -> Result<(), DataError> {
+    let list: Option<(String, String)> =
+        sqlx::query_as(r#"SELECT title, description FROM lists WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+    // Deleted lists take their documents with them
+    let (title, description) = match list {
+        Some(list) => list,
+        None => return Ok(()),
+    };
+
+    write_document(pool, SearchKind::List, id, id, None, &title, &description).await
+}
+
+async fn index_item(pool: &sqlx::AnyPool, id: i64) -> Result<(), DataError> {
+    let item: Option<(i64, String, String)> =
+        sqlx::query_as(r#"SELECT list_id, title, description FROM items WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+    let (list_id, title, description) = match item {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+
+    write_document(
+        pool,
+        SearchKind::Item,
+        id,
+        list_id,
+        Some(id),
+        &title,
+        &description,
+    )
+    .await
+}
+
+/// Writes the document and its trigrams.
+async fn write_document(
+    pool: &sqlx::AnyPool,
+    kind: SearchKind,
+    target_id: i64,
+    list_id: i64,
+    item_id: Option<i64>,
+    title: &str,
+    body: &str,
+) -> Result<(), DataError> {
+    let content = fuzzy::words(&format!("{} {}", title, body)).join(" ");
+    let document_id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO search_documents (kind, target_id, list_id, item_id, title, content, body, indexed_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP)
+        ON CONFLICT (kind, target_id)
+        DO UPDATE SET list_id = $3, title = $5, content = $6, body = $7, indexed_at = CURRENT_TIMESTAMP
+        RETURNING id
+        "#,
+    )
+    .bind(kind.as_str())
+    .bind(target_id)
+    .bind(list_id)
+    .bind(item_id)
+    .bind(title)
+    .bind(&content)
+    .bind(body)
+    .fetch_one(pool)
+    .await?;
+
+    sqlx::query(r#"DELETE FROM search_trigrams WHERE document_id = $1"#)
+        .bind(document_id)
+        .execute(pool)
+        .await?;
+
+    let trigrams = fuzzy::trigrams(&content).into_iter().collect::<Vec<_>>();
+    for chunk in trigrams.chunks(TRIGRAMS_PER_INSERT) {
+        let values = (0..chunk.len())
+            .map(|n| format!("($1, ${})", n + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            r#"INSERT INTO search_trigrams (document_id, trigram) VALUES {}"#,
+            values
+        );
+        let mut query = sqlx::query(&sql).bind(document_id);
+        for trigram in chunk {
+            query = query.bind(trigram);
+        }
+        query.execute(pool).await?;
+    }
+    Ok(())
+}
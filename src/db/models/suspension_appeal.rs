@@ -0,0 +1,85 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::Validate;
+
+use crate::db::{DataError, WishlistDb};
+
+/// An appeal submitted by a suspended user.
+#[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SuspensionAppeal {
+    pub id: i64,
+    /// The id of the suspended user who submitted the appeal.
+    pub user_id: i64,
+    /// The user's explanation.
+    #[validate(length(
+        min = 1,
+        max = 4096,
+        message = "Appeal must be between 1 and 4096 characters"
+    ))]
+    pub message: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl SuspensionAppeal {
+    /// Creates a new appeal and saves it to the database, returning the new appeal.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        message: &str,
+    ) -> Result<SuspensionAppeal, DataError> {
+        let appeal = SuspensionAppeal {
+            id: 0,
+            user_id,
+            message: message.to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+        };
+        appeal.validate()?;
+
+        let appeal = sqlx::query_as(
+            r#"
+            INSERT INTO suspension_appeals (user_id, message, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, message, created_at, updated_at
+            "#,
+        )
+        .bind(appeal.user_id)
+        .bind(&appeal.message)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(appeal)
+    }
+
+    /// Returns all appeals submitted by the given user.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<SuspensionAppeal>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, message, created_at, updated_at
+            FROM suspension_appeals
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Deletes all appeals submitted by the given user.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM suspension_appeals WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+}
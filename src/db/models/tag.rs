@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use validator::{ValidationError, ValidationErrors};
+
+use crate::db::{DataError, WishlistDb};
+
+/// The longest a tag's name can be.
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// The most tags an item can have.
+pub const MAX_TAGS_PER_ITEM: usize = 10;
+
+/// A label for grouping items, like "books" or "under $50". Tags are shared by every list, and
+/// are given to items through `item_tags`.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Tag {
+    pub id: i64,
+    /// The tag's name, lowercase with single spaces. See `Tag::normalize`.
+    pub name: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// A validation error on the `tags` field.
+fn tags_error(message: String) -> DataError {
+    let mut err = ValidationError::new("tags");
+    err.message = Some(Cow::from(message));
+    let mut errors = ValidationErrors::new();
+    errors.add("tags", err);
+    DataError::Validation(errors)
+}
+
+impl Tag {
+    /// Returns the item's tags, by name.
+    pub async fn all_by_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+    ) -> Result<Vec<Tag>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT t.id, t.name, t.created_at, t.updated_at
+            FROM tags t
+            JOIN item_tags it ON it.tag_id = t.id
+            WHERE it.item_id = $1
+            ORDER BY t.name
+            "#,
+        )
+        .bind(item_id)
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns the names of the tags on each of the list's items, by item ID. Items without tags
+    /// are left out.
+    pub async fn names_by_list(
+        conn: &mut Connection<WishlistDb>,
+        list_id: i64,
+    ) -> Result<HashMap<i64, Vec<String>>, sqlx::Error> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            r#"
+            SELECT it.item_id, t.name
+            FROM item_tags it
+            JOIN tags t ON t.id = it.tag_id
+            JOIN items i ON i.id = it.item_id
+            WHERE i.list_id = $1
+            ORDER BY t.name
+            "#,
+        )
+        .bind(list_id)
+        .fetch_all(&mut **conn)
+        .await?;
+
+        let mut names: HashMap<i64, Vec<String>> = HashMap::new();
+        for (item_id, name) in rows {
+            names.entry(item_id).or_default().push(name);
+        }
+        Ok(names)
+    }
+
+    /// Replaces the item's tags with the named ones, creating any that don't exist yet. The names
+    /// should come from `Tag::parse_names`.
+    pub async fn set_for_item(
+        conn: &mut Connection<WishlistDb>,
+        item_id: i64,
+        names: &[String],
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM item_tags WHERE item_id = $1"#)
+            .bind(item_id)
+            .execute(&mut **conn)
+            .await?;
+
+        for name in names {
+            sqlx::query(
+                r#"
+                INSERT INTO tags (name, created_at, updated_at)
+                VALUES ($1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+                ON CONFLICT (name) DO NOTHING
+                "#,
+            )
+            .bind(name)
+            .execute(&mut **conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO item_tags (item_id, tag_id, created_at, updated_at)
+                SELECT $1, id, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP FROM tags WHERE name = $2
+                ON CONFLICT (item_id, tag_id) DO NOTHING
+                "#,
+            )
+            .bind(item_id)
+            .bind(name)
+            .execute(&mut **conn)
+            .await?;
+        }
+        Ok(())
+    }
+
+    // ----- Misc -----
+
+    /// Puts a tag's name in the form it's stored in: trimmed, lowercase, and with single spaces,
+    /// so "Under  $50" and "under $50" are the same tag.
+    pub fn normalize(name: &str) -> String {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
+
+    /// Parses the comma separated tags from the item forms, e.g. "books, under $50". Empty and
+    /// repeated tags are dropped. Problems are reported as a validation error on the `tags` field.
+    pub fn parse_names(input: &str) -> Result<Vec<String>, DataError> {
+        let mut names: Vec<String> = vec![];
+        for name in input.split(',').map(Tag::normalize) {
+            if name.is_empty() || names.contains(&name) {
+                continue;
+            }
+            if name.chars().count() > MAX_TAG_LENGTH {
+                return Err(tags_error(format!(
+                    "Tags must be at most {} characters",
+                    MAX_TAG_LENGTH
+                )));
+            }
+            names.push(name);
+        }
+
+        if names.len() > MAX_TAGS_PER_ITEM {
+            return Err(tags_error(format!(
+                "Items can have at most {} tags",
+                MAX_TAGS_PER_ITEM
+            )));
+        }
+        Ok(names)
+    }
+}
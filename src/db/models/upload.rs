@@ -0,0 +1,184 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A chunked upload in progress (or finished and waiting to be attached to an item).
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Upload {
+    pub id: i64,
+    /// The upload's public identifier.
+    pub token: String,
+    /// The id of the user uploading the file.
+    pub user_id: i64,
+    /// The size of the whole file in bytes.
+    pub total_size: i64,
+    /// How many bytes have been received so far.
+    pub received_size: i64,
+    /// The detected content type, set once the upload is complete.
+    pub content_type: Option<String>,
+    /// Whether all chunks have been received and the file passed validation.
+    pub is_complete: bool,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl Upload {
+    /// Starts a new upload, returning the new upload.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        total_size: i64,
+    ) -> Result<Upload, DataError> {
+        let upload = sqlx::query_as(
+            r#"
+            INSERT INTO uploads (token, user_id, total_size, received_size, is_complete, created_at, updated_at)
+            VALUES ($1, $2, $3, 0, FALSE, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            "#,
+        )
+        .bind(crate::util::random_token())
+        .bind(user_id)
+        .bind(total_size)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(upload)
+    }
+
+    /// Returns the upload with the given token, or `None` if no upload with that token exists.
+    pub async fn find_by_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+    ) -> Result<Option<Upload>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            FROM uploads
+            WHERE token = $1
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Records how many bytes have been received, returning an updated copy of the upload.
+    pub async fn set_received(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        received_size: i64,
+    ) -> Result<Upload, DataError> {
+        let upload = sqlx::query_as(
+            r#"
+            UPDATE uploads
+            SET received_size = $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            "#,
+        )
+        .bind(received_size)
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(upload)
+    }
+
+    /// Records that `written` more bytes were received after the first `received_size`, returning
+    /// an updated copy of the upload, or `None` if another chunk was recorded first.
+    pub async fn advance(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        written: i64,
+    ) -> Result<Option<Upload>, DataError> {
+        let upload = sqlx::query_as(
+            r#"
+            UPDATE uploads
+            SET received_size = received_size + $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2 AND received_size = $3 AND is_complete IS FALSE
+            RETURNING id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            "#,
+        )
+        .bind(written)
+        .bind(self.id)
+        .bind(self.received_size)
+        .fetch_optional(&mut **conn)
+        .await?;
+
+        Ok(upload)
+    }
+
+    /// Marks the upload as complete, returning an updated copy of the upload.
+    pub async fn complete(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        content_type: &str,
+    ) -> Result<Upload, DataError> {
+        let upload = sqlx::query_as(
+            r#"
+            UPDATE uploads
+            SET content_type = $1,
+                is_complete = TRUE,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            "#,
+        )
+        .bind(content_type)
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(upload)
+    }
+
+    /// Deletes the upload from the database.
+    pub async fn destroy(&mut self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        if self.id != 0 {
+            sqlx::query(r#"DELETE FROM uploads WHERE id = $1"#)
+                .bind(self.id)
+                .execute(&mut **conn)
+                .await?;
+            self.id = 0;
+        }
+        Ok(())
+    }
+
+    /// Returns how many bytes of storage the user's uploads take up, counting unfinished ones at
+    /// their full size.
+    pub async fn total_size_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"SELECT CAST(COALESCE(SUM(total_size), 0) AS BIGINT) FROM uploads WHERE user_id = $1"#,
+        )
+        .bind(user_id)
+        .fetch_one(&mut **conn)
+        .await
+    }
+
+    // ----- Jobs -----
+
+    /// Returns the user's completed uploads, for account exports.
+    pub async fn export_by_user(
+        pool: &sqlx::AnyPool,
+        user_id: i64,
+    ) -> Result<Vec<Upload>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, token, user_id, total_size, received_size, content_type, is_complete, created_at, updated_at
+            FROM uploads
+            WHERE user_id = $1 AND is_complete IS TRUE
+            ORDER BY id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+    }
+}
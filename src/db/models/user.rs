@@ -2,17 +2,40 @@ use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::{sqlx, Connection};
 use validator::Validate;
 
+use crate::db::models::UsernameHistory;
 use crate::db::{DataError, WishlistDb};
 
+/// The `User::auth_source` of users who log in with a password kept here.
+pub const LOCAL_AUTH_SOURCE: &str = "local";
+/// The `User::auth_source` of users made by logging in through the directory.
+pub const DIRECTORY_AUTH_SOURCE: &str = "directory";
+
 /// A user
 #[derive(sqlx::FromRow, Debug, Validate, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct User {
     pub id: i64,
+    #[validate(custom = "crate::usernames::validate_username")]
     pub username: String,
     pub email: String,
+    /// A new email address waiting to be confirmed, see `User::request_email_change`.
+    pub pending_email: Option<String>,
     #[serde(skip_serializing)]
-    pub password_hash: String, 
+    pub password_hash: String,
+    /// Whether the user can access the admin pages.
+    pub is_admin: bool,
+    /// When the user was suspended, or `None` if they are in good standing.
+    pub suspended_at: Option<chrono::NaiveDateTime>,
+    /// The reason given by the admin who suspended the user.
+    pub suspension_reason: Option<String>,
+    /// When the user proved they own their email address, or `None` if they haven't yet. See
+    /// `EmailVerification`.
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+    /// Whether the user is emailed ahead of an event about items they claimed but haven't bought
+    /// yet. See `crate::jobs::claim_reminders`.
+    pub claim_reminders: bool,
+    /// Where the user logs in, `LOCAL_AUTH_SOURCE` or `DIRECTORY_AUTH_SOURCE`.
+    pub auth_source: String,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -42,7 +65,14 @@ impl User {
             id: 0,
             username,
             email,
+            pending_email: None,
             password_hash,
+            is_admin: false,
+            suspended_at: None,
+            suspension_reason: None,
+            email_verified_at: None,
+            claim_reminders: true,
+            auth_source: LOCAL_AUTH_SOURCE.to_string(),
             created_at: chrono::NaiveDateTime::default(),
             updated_at: chrono::NaiveDateTime::default(),
         }
@@ -61,7 +91,7 @@ impl User {
     pub async fn all(conn: &mut Connection<WishlistDb>) -> Result<Vec<User>, sqlx::Error> {
         sqlx::query_as(
             r#"
-            SELECT id, username, email, '' as password_hash, created_at, updated_at
+            SELECT id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
             FROM users
             "#,
         )
@@ -76,7 +106,7 @@ impl User {
     ) -> Result<Option<User>, sqlx::Error> {
         sqlx::query_as(
             r#"
-            SELECT id, username, email, password_hash, created_at, updated_at
+            SELECT id, username, email, pending_email, password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
             FROM users
             WHERE id = $1
             "#,
@@ -93,7 +123,7 @@ impl User {
     ) -> Result<Option<User>, sqlx::Error> {
         sqlx::query_as(
             r#"
-            SELECT id, username, email, password_hash, created_at, updated_at
+            SELECT id, username, email, pending_email, password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
             FROM users
             WHERE username = $1
             "#,
@@ -103,6 +133,23 @@ impl User {
         .await
     }
 
+    /// Returns the user with the given email address, ignoring case, or `None` if no user has it.
+    pub async fn find_by_email(
+        conn: &mut Connection<WishlistDb>,
+        email: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, username, email, pending_email, password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            FROM users
+            WHERE LOWER(email) = LOWER($1)
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
     /// Updates the user in the database, returning an updated copy of the user.
     pub async fn update(
         &mut self,
@@ -124,6 +171,309 @@ impl User {
         Ok(())
     }
 
+    /// Returns all suspended users in the database.
+    pub async fn all_suspended(conn: &mut Connection<WishlistDb>) -> Result<Vec<User>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            FROM users
+            WHERE suspended_at IS NOT NULL
+            "#,
+        )
+        .fetch_all(&mut **conn)
+        .await
+    }
+
+    /// Returns true if the user is currently suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    /// Whether the user was made by logging in through the directory, see `crate::directory`.
+    /// Only the directory can log them in, and it can't log in anyone else.
+    pub fn is_from_directory(&self) -> bool {
+        self.auth_source == DIRECTORY_AUTH_SOURCE
+    }
+
+    /// Suspends the user with the given reason, returning an updated copy of the user.
+    pub async fn suspend(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        reason: &str,
+    ) -> Result<User, DataError> {
+        let user = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET suspended_at = CURRENT_TIMESTAMP,
+                suspension_reason = $1,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            "#,
+        )
+        .bind(reason)
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Lifts the user's suspension, returning an updated copy of the user.
+    pub async fn unsuspend(&self, conn: &mut Connection<WishlistDb>) -> Result<User, DataError> {
+        let user = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET suspended_at = NULL,
+                suspension_reason = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            "#,
+        )
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Changes the user's username, recording the old one so links to it keep working for a while.
+    /// Returns an updated copy of the user.
+    pub async fn rename(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        username: &str,
+    ) -> Result<User, DataError> {
+        let mut user = User::find_by_id(conn, self.id)
+            .await?
+            .ok_or(DataError::Sqlx(sqlx::Error::RowNotFound))?;
+        user.username = username.to_string();
+        user.validate()?;
+
+        UsernameHistory::create(conn, self.id, &self.username).await?;
+        user.do_update(conn).await
+    }
+
+    /// Returns true if a user other than this one has the given email address.
+    pub async fn email_taken(
+        conn: &mut Connection<WishlistDb>,
+        email: &str,
+        except_id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let count: i64 = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM users WHERE LOWER(email) = LOWER($1) AND id != $2"#,
+        )
+        .bind(email)
+        .bind(except_id)
+        .fetch_one(&mut **conn)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Starts changing the user's email address, returning the token that confirms the change.
+    ///
+    /// The current address stays in use until the new one is confirmed with
+    /// `User::confirm_email_change`. Requesting again replaces any pending change.
+    pub async fn request_email_change(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        new_email: &str,
+    ) -> Result<String, DataError> {
+        let token = crate::util::random_token();
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET pending_email = $1,
+                email_change_token = $2,
+                email_change_requested_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $3
+            "#,
+        )
+        .bind(new_email)
+        .bind(&token)
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Returns the user with a pending email change for the given token, or `None` if the token
+    /// doesn't exist or was issued before `requested_after`.
+    pub async fn find_by_email_change_token(
+        conn: &mut Connection<WishlistDb>,
+        token: &str,
+        requested_after: chrono::NaiveDateTime,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            FROM users
+            WHERE email_change_token = $1 AND email_change_requested_at > $2
+            "#,
+        )
+        .bind(token)
+        .bind(requested_after)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Switches the user to their pending email address, returning an updated copy of the user.
+    /// The new address counts as verified, since the confirmation link was sent to it.
+    pub async fn confirm_email_change(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<User, DataError> {
+        let user = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET email = pending_email,
+                email_verified_at = CURRENT_TIMESTAMP,
+                pending_email = NULL,
+                email_change_token = NULL,
+                email_change_requested_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1 AND pending_email IS NOT NULL
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            "#,
+        )
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Cancels the user's pending email change.
+    pub async fn cancel_email_change(&self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET pending_email = NULL,
+                email_change_token = NULL,
+                email_change_requested_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            "#,
+        )
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Records that the user owns their email address, returning an updated copy of the user.
+    pub async fn mark_email_verified(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+    ) -> Result<User, DataError> {
+        let user = sqlx::query_as(
+            r#"
+            UPDATE users
+            SET email_verified_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
+            "#,
+        )
+        .bind(self.id)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Returns true if the user has verified their email address.
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    /// Turns claim reminder emails on or off for the user.
+    pub async fn set_claim_reminders(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        claim_reminders: bool,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE users SET claim_reminders = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(claim_reminders)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Replaces the user's password with a new bcrypt hash.
+    pub async fn set_password_hash(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        password_hash: &str,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"UPDATE users SET password_hash = $1, updated_at = CURRENT_TIMESTAMP WHERE id = $2"#)
+            .bind(password_hash)
+            .bind(self.id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    // ----- Jobs -----
+
+    /// Counts accounts made before `before` that never verified their email address, for
+    /// `crate::jobs::retention`. Admins are left alone.
+    pub async fn count_unverified_before(
+        pool: &sqlx::AnyPool,
+        before: chrono::NaiveDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM users
+            WHERE email_verified_at IS NULL AND created_at < $1 AND is_admin = FALSE
+            "#,
+        )
+        .bind(before)
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Deletes the accounts `count_unverified_before` counts in one transaction, along with their
+    /// lists, items and everything else of theirs the database doesn't delete with them. Returns
+    /// how many accounts were deleted.
+    pub async fn destroy_unverified_before(
+        pool: &sqlx::AnyPool,
+        before: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        const UNVERIFIED: &str = r#"
+            SELECT id
+            FROM users
+            WHERE email_verified_at IS NULL AND created_at < $1 AND is_admin = FALSE
+        "#;
+
+        let mut tx = pool.begin().await?;
+        let owned = [
+            "DELETE FROM items WHERE list_id IN (SELECT id FROM lists WHERE user_id IN ({}))",
+            "DELETE FROM lists WHERE user_id IN ({})",
+            "DELETE FROM user_sessions WHERE user_id IN ({})",
+            "DELETE FROM user_devices WHERE user_id IN ({})",
+            "DELETE FROM suspension_appeals WHERE user_id IN ({})",
+            "DELETE FROM uploads WHERE user_id IN ({})",
+        ];
+        for query in owned {
+            sqlx::query(&query.replace("{}", UNVERIFIED))
+                .bind(before)
+                .execute(&mut tx)
+                .await?;
+        }
+        let result = sqlx::query(&format!("DELETE FROM users WHERE id IN ({})", UNVERIFIED))
+            .bind(before)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
     // ----- Misc -----
 
     /// Returns the number of users in the database.
@@ -140,14 +490,15 @@ impl User {
 
         let list = sqlx::query_as(
             r#"
-            INSERT INTO users (username, email, password_hash, created_at, updated_at)
-            VALUES ($1, $2, $3, now(), now())
-            RETURNING id, username, email, '' as password_hash, created_at, updated_at
+            INSERT INTO users (username, email, password_hash, auth_source, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
             "#,
         )
         .bind(&self.username)
         .bind(&self.email)
         .bind(&self.password_hash)
+        .bind(&self.auth_source)
         .fetch_one(&mut **conn)
         .await?;
 
@@ -162,9 +513,9 @@ impl User {
             UPDATE users
             SET username = $1,
                 email = $2,
-                updated_at = now()
+                updated_at = CURRENT_TIMESTAMP
             WHERE id = $3
-            RETURNING id, username, email, '' as password_hash, created_at, updated_at
+            RETURNING id, username, email, pending_email, '' as password_hash, is_admin, suspended_at, suspension_reason, email_verified_at, claim_reminders, auth_source, created_at, updated_at
             "#,
         )
         .bind(&self.username)
@@ -0,0 +1,73 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A device (user agent + IP address) a user has signed in from before.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserDevice {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_agent: String,
+    pub ip_address: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl UserDevice {
+    /// Records a device for the given user, returning the new device.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        user_agent: &str,
+        ip_address: &str,
+    ) -> Result<UserDevice, DataError> {
+        let device = sqlx::query_as(
+            r#"
+            INSERT INTO user_devices (user_id, user_agent, ip_address, created_at, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, user_agent, ip_address, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(device)
+    }
+
+    /// Returns the matching device for the given user, or `None` if it hasn't been seen before.
+    pub async fn find(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        user_agent: &str,
+        ip_address: &str,
+    ) -> Result<Option<UserDevice>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, updated_at
+            FROM user_devices
+            WHERE user_id = $1 AND user_agent = $2 AND ip_address = $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+
+    /// Returns the number of devices recorded for the given user.
+    pub async fn count_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM user_devices WHERE user_id = $1"#)
+            .bind(user_id)
+            .fetch_one(&mut **conn)
+            .await
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{Days, Utc};
+use chrono::Utc;
 use rocket::serde::{Deserialize, Serialize};
 use rocket_db_pools::{sqlx, Connection};
 
@@ -11,6 +11,23 @@ pub struct UserSession {
     pub id: i64,
     pub token: String,
     pub user_id: i64,
+    /// The user agent of the client that created the session.
+    pub user_agent: Option<String>,
+    /// The IP address of the client that created the session.
+    pub ip_address: Option<String>,
+    /// A secret used to revoke the session from a link without being logged in.
+    #[serde(skip_serializing)]
+    pub revoke_token: Option<String>,
+    /// When the session was last used, not counting the request that created it.
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    /// How many requests have been made with the session.
+    pub request_count: i64,
+    /// Whether the user asked to stay logged in. Otherwise the session's cookie is dropped when
+    /// the browser closes.
+    pub remember_me: bool,
+    /// When the session stops logging the user in. Expired sessions are removed by
+    /// `crate::jobs::session_cleanup`.
+    pub expires_at: chrono::NaiveDateTime,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
 }
@@ -20,16 +37,25 @@ impl UserSession {
         conn: &mut Connection<WishlistDb>,
         token: &str,
         user_id: i64,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+        remember_me: bool,
+        expires_at: chrono::NaiveDateTime,
     ) -> Result<UserSession, DataError> {
         let user_session = sqlx::query_as(
             r#"
-            INSERT INTO user_sessions (token, user_id, created_at, updated_at)
-            VALUES ($1, $2, now(), now())
-            RETURNING id, token, user_id, created_at, updated_at
+            INSERT INTO user_sessions (token, user_id, user_agent, ip_address, revoke_token, remember_me, expires_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, token, user_id, user_agent, ip_address, revoke_token, last_used_at, request_count, remember_me, expires_at, created_at, updated_at
             "#,
         )
         .bind(token)
         .bind(user_id)
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(crate::util::random_token())
+        .bind(remember_me)
+        .bind(expires_at)
         .fetch_one(&mut **conn)
         .await?;
 
@@ -40,7 +66,7 @@ impl UserSession {
         conn: &mut Connection<WishlistDb>,
         token: &str,
     ) -> Result<Option<UserSession>, DataError> {
-        let session = sqlx::query_as(r#"SELECT id, token, user_id, created_at, updated_at FROM user_sessions WHERE token = $1"#)
+        let session = sqlx::query_as(r#"SELECT id, token, user_id, user_agent, ip_address, revoke_token, last_used_at, request_count, remember_me, expires_at, created_at, updated_at FROM user_sessions WHERE token = $1"#)
             .bind(token)
             .fetch_optional(&mut **conn)
             .await?;
@@ -48,6 +74,80 @@ impl UserSession {
         Ok(session)
     }
 
+    pub async fn find_by_revoke_token(
+        conn: &mut Connection<WishlistDb>,
+        revoke_token: &str,
+    ) -> Result<Option<UserSession>, DataError> {
+        let session = sqlx::query_as(r#"SELECT id, token, user_id, user_agent, ip_address, revoke_token, last_used_at, request_count, remember_me, expires_at, created_at, updated_at FROM user_sessions WHERE revoke_token = $1"#)
+            .bind(revoke_token)
+            .fetch_optional(&mut **conn)
+            .await?;
+
+        Ok(session)
+    }
+
+    /// Returns all of the user's unexpired sessions, most recently used first.
+    pub async fn all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<Vec<UserSession>, DataError> {
+        let sessions = sqlx::query_as(
+            r#"
+            SELECT id, token, user_id, user_agent, ip_address, revoke_token, last_used_at, request_count, remember_me, expires_at, created_at, updated_at
+            FROM user_sessions
+            WHERE user_id = $1 AND expires_at > CURRENT_TIMESTAMP
+            ORDER BY COALESCE(last_used_at, created_at) DESC, id DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut **conn)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Returns true if the session no longer logs the user in.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now().naive_utc()
+    }
+
+    /// Records a request made with the session.
+    pub async fn record_use(&self, conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
+        sqlx::query(
+            r#"UPDATE user_sessions SET last_used_at = CURRENT_TIMESTAMP, request_count = request_count + 1 WHERE id = $1"#,
+        )
+        .bind(self.id)
+        .execute(&mut **conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Logs out one of the user's sessions.
+    pub async fn destroy_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM user_sessions WHERE id = $1 AND user_id = $2"#)
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Logs out all of the user's sessions, e.g. after their password is reset.
+    pub async fn destroy_all_by_user(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+    ) -> Result<(), DataError> {
+        sqlx::query(r#"DELETE FROM user_sessions WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut **conn)
+            .await?;
+        Ok(())
+    }
+
     pub async fn destroy_by_token(
         conn: &mut Connection<WishlistDb>,
         token: &str,
@@ -59,12 +159,38 @@ impl UserSession {
         Ok(())
     }
 
-    pub async fn destroy_outdated(conn: &mut Connection<WishlistDb>) -> Result<(), DataError> {
-        let remove_before = Utc::now().checked_sub_days(Days::new(7)).unwrap();
-        sqlx::query(r#"DELETE FROM user_sessions WHERE created_at < $1"#)
-            .bind(remove_before)
-            .execute(&mut **conn)
+    // ----- Jobs -----
+
+    /// Removes sessions that have expired, returning how many were removed.
+    pub async fn destroy_expired(pool: &sqlx::AnyPool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(r#"DELETE FROM user_sessions WHERE expires_at <= $1"#)
+            .bind(Utc::now().naive_utc())
+            .execute(pool)
             .await?;
-        Ok(())
+        Ok(result.rows_affected())
+    }
+
+    /// Counts sessions made before `before`, however long they were meant to last, for
+    /// `crate::jobs::retention`.
+    pub async fn count_created_before(
+        pool: &sqlx::AnyPool,
+        before: chrono::NaiveDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM user_sessions WHERE created_at < $1"#)
+            .bind(before)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Removes sessions made before `before`, logging them out.
+    pub async fn destroy_created_before(
+        pool: &sqlx::AnyPool,
+        before: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(r#"DELETE FROM user_sessions WHERE created_at < $1"#)
+            .bind(before)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
     }
 }
@@ -0,0 +1,160 @@
+use chrono::NaiveDate;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A summary of a user's wishes for one year.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserStats {
+    pub year: i32,
+    /// Lists the user created during the year.
+    pub lists_created: i64,
+    /// Items added to the user's lists during the year.
+    pub items_added: i64,
+    /// Items on the user's lists that were marked as received during the year.
+    pub items_received: i64,
+    /// How many times consumables on the user's lists were given during the year. Consumables
+    /// stay wanted, so they aren't in `items_received`.
+    #[serde(default)]
+    pub consumables_given: i64,
+    /// The latest tracked price of the received items, per currency.
+    pub received_value: Vec<CurrencyTotal>,
+    /// The tags on the most items added during the year, most used first.
+    #[serde(default)]
+    pub top_tags: Vec<TagCount>,
+}
+
+/// An amount of money in one currency.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CurrencyTotal {
+    pub currency: String,
+    pub amount_cents: i64,
+}
+
+/// How many items a tag is on.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TagCount {
+    pub name: String,
+    pub item_count: i64,
+}
+
+impl UserStats {
+    /// Computes the user's stats for the given year, with up to `top_tags` of their tags.
+    pub async fn compute(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        year: i32,
+        top_tags: i64,
+    ) -> Result<UserStats, DataError> {
+        let (start, end) = match (
+            NaiveDate::from_ymd_opt(year, 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)),
+            NaiveDate::from_ymd_opt(year + 1, 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err(DataError::Other(format!("Invalid year: {}", year))),
+        };
+
+        let lists_created = sqlx::query_scalar(
+            r#"SELECT COUNT(*) FROM lists WHERE user_id = $1 AND created_at >= $2 AND created_at < $3"#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        let items_added = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1 AND i.created_at >= $2 AND i.created_at < $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        let items_received = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1 AND i.received_at >= $2 AND i.received_at < $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        let consumables_given = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM item_gifts g
+            JOIN items i ON i.id = g.item_id
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1 AND g.created_at >= $2 AND g.created_at < $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        let received_value = sqlx::query_as(
+            r#"
+            SELECT p.currency, CAST(SUM(p.amount_cents) AS BIGINT) AS amount_cents
+            FROM items i
+            JOIN lists l ON l.id = i.list_id
+            JOIN item_prices p ON p.id = (SELECT MAX(id) FROM item_prices WHERE item_id = i.id)
+            WHERE l.user_id = $1 AND i.received_at >= $2 AND i.received_at < $3
+            GROUP BY p.currency
+            ORDER BY p.currency
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&mut **conn)
+        .await?;
+
+        let top_tags = sqlx::query_as(
+            r#"
+            SELECT t.name, COUNT(*) AS item_count
+            FROM item_tags it
+            JOIN tags t ON t.id = it.tag_id
+            JOIN items i ON i.id = it.item_id
+            JOIN lists l ON l.id = i.list_id
+            WHERE l.user_id = $1 AND i.created_at >= $2 AND i.created_at < $3
+            GROUP BY t.name
+            ORDER BY item_count DESC, t.name
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(start)
+        .bind(end)
+        .bind(top_tags)
+        .fetch_all(&mut **conn)
+        .await?;
+
+        Ok(UserStats {
+            year,
+            lists_created,
+            items_added,
+            items_received,
+            consumables_given,
+            received_value,
+            top_tags,
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::db::{DataError, WishlistDb};
+
+/// A username a user had before renaming their account.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UsernameHistory {
+    pub id: i64,
+    pub user_id: i64,
+    /// The old username.
+    pub username: String,
+    /// When the user stopped using this username.
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl UsernameHistory {
+    /// Records that the user stopped using the given username.
+    pub async fn create(
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        username: &str,
+    ) -> Result<UsernameHistory, DataError> {
+        let history = sqlx::query_as(
+            r#"
+            INSERT INTO username_history (user_id, username, created_at, updated_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            RETURNING id, user_id, username, created_at, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(username)
+        .fetch_one(&mut **conn)
+        .await?;
+
+        Ok(history)
+    }
+
+    /// Returns the most recent use of the given username that ended after `since`, or `None` if
+    /// nobody has given it up in that time.
+    pub async fn find_recent(
+        conn: &mut Connection<WishlistDb>,
+        username: &str,
+        since: chrono::NaiveDateTime,
+    ) -> Result<Option<UsernameHistory>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, user_id, username, created_at, updated_at
+            FROM username_history
+            WHERE username = $1 AND created_at > $2
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(username)
+        .bind(since)
+        .fetch_optional(&mut **conn)
+        .await
+    }
+}
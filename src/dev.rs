@@ -0,0 +1,199 @@
+//! Tools for working on the app locally. Only compiled into debug builds, and only turned on when
+//! running with the debug profile, so they can never be reached on a real instance. Builds with
+//! the `testing` feature get the seed data too, for `crate::testing`.
+
+use rocket::response::{Debug, Redirect};
+use rocket::{fairing, Build, Config, Rocket};
+use rocket_db_pools::{sqlx, Database};
+use thiserror::Error;
+
+use crate::db::models::ListPrivacy;
+use crate::db::WishlistDb;
+
+/// Pass this on the command line to reset the database when the server starts, see `reset`.
+pub static RESET_FLAG: &str = "--reset-db";
+
+/// The password of every seeded user.
+pub static SEED_PASSWORD: &str = "password";
+
+#[derive(Error, Debug)]
+pub enum DevError {
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+    #[error(transparent)]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error(transparent)]
+    Bcrypt(#[from] bcrypt::BcryptError),
+}
+
+/// Mounts the dev routes, and resets the database if the server was started with `--reset-db`.
+/// Does nothing outside the debug profile.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let reset_requested = std::env::args().any(|arg| arg == RESET_FLAG);
+    if rocket.figment().profile() != Config::DEBUG_PROFILE {
+        if reset_requested {
+            error!("{} only works with the debug profile", RESET_FLAG);
+            return Err(rocket);
+        }
+        return Ok(rocket);
+    }
+
+    if reset_requested {
+        let db = match WishlistDb::fetch(&rocket) {
+            Some(db) => db,
+            None => return Err(rocket),
+        };
+        if let Err(e) = reset(db).await {
+            error!("Failed to reset the database: {}", e);
+            return Err(rocket);
+        }
+    }
+
+    warn!("Dev tools are on, anyone can reset the database with POST /_dev/reset");
+    Ok(rocket.mount("/", routes![reset_route]))
+}
+
+/// Drops everything in the database and starts over with the seed data, see `reset`.
+#[post("/_dev/reset")]
+pub async fn reset_route(db: &WishlistDb) -> Result<Redirect, Debug<DevError>> {
+    reset(db).await?;
+    Ok(Redirect::to("/"))
+}
+
+/// Drops every table, runs the migrations again, and adds the seed data.
+pub async fn reset(pool: &sqlx::AnyPool) -> Result<(), DevError> {
+    info!("Resetting the database");
+    drop_schema(pool).await?;
+    crate::db::migrate(pool).await?;
+    seed(pool).await?;
+    Ok(())
+}
+
+async fn drop_schema(pool: &sqlx::AnyPool) -> Result<(), sqlx::Error> {
+    // Pragmas only apply to the connection they're run on, so keep to one
+    let mut conn = pool.acquire().await?;
+    match pool.any_kind() {
+        sqlx::any::AnyKind::Postgres => {
+            sqlx::query("DROP SCHEMA public CASCADE")
+                .execute(&mut conn)
+                .await?;
+            sqlx::query("CREATE SCHEMA public")
+                .execute(&mut conn)
+                .await?;
+        }
+        sqlx::any::AnyKind::Sqlite => {
+            // Virtual tables go first, since dropping them also drops their shadow tables
+            let tables: Vec<String> = sqlx::query_scalar(
+                r#"
+                SELECT name FROM sqlite_master
+                WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+                ORDER BY sql LIKE 'CREATE VIRTUAL%' DESC
+                "#,
+            )
+            .fetch_all(&mut conn)
+            .await?;
+
+            sqlx::query("PRAGMA foreign_keys = OFF")
+                .execute(&mut conn)
+                .await?;
+            for table in tables {
+                sqlx::query(&format!("DROP TABLE IF EXISTS \"{}\"", table))
+                    .execute(&mut conn)
+                    .await?;
+            }
+            sqlx::query("PRAGMA foreign_keys = ON")
+                .execute(&mut conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds a few users with lists and items to click around. Every user's password is
+/// `SEED_PASSWORD`, and `admin` is an admin.
+pub async fn seed(pool: &sqlx::AnyPool) -> Result<(), DevError> {
+    let password_hash = bcrypt::hash(SEED_PASSWORD, bcrypt::DEFAULT_COST)?;
+
+    let admin = seed_user(pool, "admin", &password_hash, true).await?;
+    let alice = seed_user(pool, "alice", &password_hash, false).await?;
+    let bob = seed_user(pool, "bob", &password_hash, false).await?;
+
+    let birthday = seed_list(pool, alice, ListPrivacy::Public, "Alice's Birthday").await?;
+    seed_item(pool, birthday, "Dune", "The paperback").await?;
+    seed_item(pool, birthday, "Headphones", "Over-ear, in black").await?;
+    seed_item(pool, birthday, "Houseplant", "Something hard to kill").await?;
+
+    let ideas = seed_list(pool, alice, ListPrivacy::Private, "Ideas for later").await?;
+    seed_item(pool, ideas, "Standing desk", "").await?;
+
+    let housewarming = seed_list(pool, bob, ListPrivacy::Public, "Bob's Housewarming").await?;
+    seed_item(pool, housewarming, "Cast iron skillet", "12 inch").await?;
+    seed_item(pool, housewarming, "Board games", "Anything for 4+ players").await?;
+
+    let admin_list = seed_list(pool, admin, ListPrivacy::Public, "Admin's List").await?;
+    seed_item(pool, admin_list, "Coffee", "Whole beans").await?;
+
+    Ok(())
+}
+
+async fn seed_user(
+    pool: &sqlx::AnyPool,
+    username: &str,
+    password_hash: &str,
+    is_admin: bool,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO users (username, email, password_hash, is_admin, email_verified_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+        RETURNING id
+        "#,
+    )
+    .bind(username)
+    .bind(format!("{}@example.com", username))
+    .bind(password_hash)
+    .bind(is_admin)
+    .fetch_one(pool)
+    .await
+}
+
+async fn seed_list(
+    pool: &sqlx::AnyPool,
+    user_id: i64,
+    privacy: ListPrivacy,
+    title: &str,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO lists (key, user_id, privacy, title, description, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, '', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+        RETURNING id
+        "#,
+    )
+    .bind(crate::util::random_key())
+    .bind(user_id)
+    .bind(privacy.as_str())
+    .bind(title)
+    .fetch_one(pool)
+    .await
+}
+
+async fn seed_item(
+    pool: &sqlx::AnyPool,
+    list_id: i64,
+    title: &str,
+    description: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO items (list_id, title, description, created_at, updated_at)
+        VALUES ($1, $2, $3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+        "#,
+    )
+    .bind(list_id)
+    .bind(title)
+    .bind(description)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
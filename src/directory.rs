@@ -0,0 +1,103 @@
+use ldap3::{dn_escape, LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+
+use crate::config::AppConfig;
+
+/// The LDAP result code for a failed bind.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// Directory configuration, read from the `directory` table in Rocket.toml. When an LDAP URL is
+/// set, users log in with their directory account and open registration is turned off.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct DirectoryConfig {
+    /// The LDAP server, e.g. `ldaps://ldap.example.com`.
+    pub url: Option<String>,
+    /// The DN users bind as, with `{username}` replaced by the (escaped) username they log in
+    /// with, e.g. `uid={username},ou=people,dc=example,dc=com`.
+    pub user_dn: String,
+    /// The attribute holding the user's email address.
+    pub email_attribute: String,
+    /// Upgrade `ldap://` connections with StartTLS.
+    pub starttls: bool,
+}
+
+impl Default for DirectoryConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            user_dn: "uid={username},ou=people,dc=example,dc=com".to_string(),
+            email_attribute: "mail".to_string(),
+            starttls: false,
+        }
+    }
+}
+
+/// A user's directory entry.
+pub struct DirectoryEntry {
+    pub email: Option<String>,
+}
+
+/// Checks logins against an LDAP directory. Available as managed state, whether or not a
+/// directory is configured.
+pub struct Directory {
+    config: DirectoryConfig,
+}
+
+impl Directory {
+    /// Whether users come from the directory instead of registering.
+    pub fn enabled(&self) -> bool {
+        self.config.url.is_some()
+    }
+
+    /// Binds as the user to check their password, returning their entry, or `None` if the
+    /// username or password is wrong.
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<DirectoryEntry>, LdapError> {
+        let url = match &self.config.url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        // An empty password is an unauthenticated bind, which most servers accept
+        if username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+
+        let settings = LdapConnSettings::new().set_starttls(self.config.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, url).await?;
+        ldap3::drive!(conn);
+
+        let dn = self.config.user_dn.replace("{username}", &dn_escape(username));
+        let bind = ldap.simple_bind(&dn, password).await?;
+        if bind.rc == INVALID_CREDENTIALS {
+            let _ = ldap.unbind().await;
+            return Ok(None);
+        }
+        bind.success()?;
+
+        let attribute = self.config.email_attribute.as_str();
+        let (entries, _) = ldap
+            .search(&dn, Scope::Base, "(objectClass=*)", vec![attribute])
+            .await?
+            .success()?;
+        let email = entries
+            .into_iter()
+            .next()
+            .map(SearchEntry::construct)
+            .and_then(|entry| entry.attrs.get(attribute).and_then(|values| values.first().cloned()));
+
+        let _ = ldap.unbind().await;
+        Ok(Some(DirectoryEntry { email }))
+    }
+}
+
+/// Reads the directory config and adds the `Directory` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).directory.clone();
+
+    Ok(rocket.manage(Directory { config }))
+}
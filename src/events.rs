@@ -0,0 +1,173 @@
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::db::models::{Item, List};
+
+/// Something that happened to a list or its items.
+///
+/// Handlers hand events to the `Dispatcher`, which writes them to the event log (see
+/// `crate::db::models::Event`). Everything that reacts to changes reads them from there:
+/// notifications and webhooks, live updates on list pages, and analytics. Since the log is kept,
+/// events can be replayed, e.g. by a live update client catching up after a dropped connection.
+///
+/// Events carry a snapshot of what they're about, so they still make sense after the list or
+/// item is gone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    /// A list was made, by `by` if they were logged in.
+    ListCreated { list: ListRef, by: Option<i64> },
+    /// A list was deleted by its owner.
+    ListDeleted { list: ListRef, by: Option<i64> },
+    /// An item was added, by `by` if they were logged in.
+    ItemAdded {
+        list: ListRef,
+        item: ItemRef,
+        by: Option<i64>,
+    },
+    /// An item's details were changed.
+    ItemUpdated {
+        list: ListRef,
+        item: ItemRef,
+        by: Option<i64>,
+    },
+    /// An item was deleted.
+    ItemDeleted {
+        list: ListRef,
+        item: ItemRef,
+        by: Option<i64>,
+    },
+    /// An item was marked as received, or as wanted again.
+    ItemReceived {
+        list: ListRef,
+        item: ItemRef,
+        received: bool,
+        by: Option<i64>,
+    },
+    /// Someone claimed an item. This is gifting activity, see `crate::surprise`.
+    ItemClaimed {
+        list: ListRef,
+        item: ItemRef,
+        by: i64,
+    },
+    /// Someone gave up their claim on an item. This is gifting activity, see `crate::surprise`.
+    ItemUnclaimed {
+        list: ListRef,
+        item: ItemRef,
+        by: i64,
+    },
+}
+
+/// The list an event is about, as it was when the event happened.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ListRef {
+    pub id: i64,
+    pub key: String,
+    pub title: String,
+    pub owner_id: Option<i64>,
+}
+
+impl From<&List> for ListRef {
+    fn from(list: &List) -> Self {
+        ListRef {
+            id: list.id,
+            key: list.key.clone(),
+            title: list.title.clone(),
+            owner_id: list.user_id,
+        }
+    }
+}
+
+/// The item an event is about, as it was when the event happened.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemRef {
+    pub id: i64,
+    pub title: String,
+}
+
+impl From<&Item> for ItemRef {
+    fn from(item: &Item) -> Self {
+        ItemRef {
+            id: item.id,
+            title: item.title.clone(),
+        }
+    }
+}
+
+impl DomainEvent {
+    /// Every event's name in the log, see `kind`.
+    pub const KINDS: [&'static str; 8] = [
+        "list_created",
+        "list_deleted",
+        "item_added",
+        "item_updated",
+        "item_deleted",
+        "item_received",
+        "item_claimed",
+        "item_unclaimed",
+    ];
+
+    /// The event's name in the log, e.g. "item_added".
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DomainEvent::ListCreated { .. } => "list_created",
+            DomainEvent::ListDeleted { .. } => "list_deleted",
+            DomainEvent::ItemAdded { .. } => "item_added",
+            DomainEvent::ItemUpdated { .. } => "item_updated",
+            DomainEvent::ItemDeleted { .. } => "item_deleted",
+            DomainEvent::ItemReceived { .. } => "item_received",
+            DomainEvent::ItemClaimed { .. } => "item_claimed",
+            DomainEvent::ItemUnclaimed { .. } => "item_unclaimed",
+        }
+    }
+
+    pub fn list(&self) -> &ListRef {
+        match self {
+            DomainEvent::ListCreated { list, .. }
+            | DomainEvent::ListDeleted { list, .. }
+            | DomainEvent::ItemAdded { list, .. }
+            | DomainEvent::ItemUpdated { list, .. }
+            | DomainEvent::ItemDeleted { list, .. }
+            | DomainEvent::ItemReceived { list, .. }
+            | DomainEvent::ItemClaimed { list, .. }
+            | DomainEvent::ItemUnclaimed { list, .. } => list,
+        }
+    }
+
+    pub fn item(&self) -> Option<&ItemRef> {
+        match self {
+            DomainEvent::ListCreated { .. } | DomainEvent::ListDeleted { .. } => None,
+            DomainEvent::ItemAdded { item, .. }
+            | DomainEvent::ItemUpdated { item, .. }
+            | DomainEvent::ItemDeleted { item, .. }
+            | DomainEvent::ItemReceived { item, .. }
+            | DomainEvent::ItemClaimed { item, .. }
+            | DomainEvent::ItemUnclaimed { item, .. } => Some(item),
+        }
+    }
+
+    /// The user who made the change, if they were logged in.
+    pub fn by(&self) -> Option<i64> {
+        match self {
+            DomainEvent::ListCreated { by, .. }
+            | DomainEvent::ListDeleted { by, .. }
+            | DomainEvent::ItemAdded { by, .. }
+            | DomainEvent::ItemUpdated { by, .. }
+            | DomainEvent::ItemDeleted { by, .. }
+            | DomainEvent::ItemReceived { by, .. } => *by,
+            DomainEvent::ItemClaimed { by, .. } | DomainEvent::ItemUnclaimed { by, .. } => {
+                Some(*by)
+            }
+        }
+    }
+
+    /// Whether the event is gifting activity, which is hidden from the list owner unless they've
+    /// turned on `List::reveal_gifting`.
+    pub fn is_gifting(&self) -> bool {
+        matches!(
+            self,
+            DomainEvent::ItemClaimed { .. } | DomainEvent::ItemUnclaimed { .. }
+        )
+    }
+}
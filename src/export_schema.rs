@@ -0,0 +1,102 @@
+//! JSON Schemas for the list export format (see `crate::web::list_export`), so other tools can
+//! read and write it. They're published at /.well-known/wishlist-schema/v1/, and JSON imports are
+//! checked against them before anything is added, see `crate::list_import`.
+//!
+//! The schemas live in `schemas/v1`. Changes that would turn away files that used to be accepted
+//! need a new version rather than an edit.
+
+use std::sync::OnceLock;
+
+use jsonschema::JSONSchema;
+use rocket::serde::json::{serde_json, Value};
+
+/// The published schemas, by file name.
+const DOCUMENTS: [(&str, &str); 2] = [
+    ("export.json", include_str!("../schemas/v1/export.json")),
+    ("item.json", include_str!("../schemas/v1/item.json")),
+];
+
+/// Where schemas find each other by their relative `$ref`s, when there's no `$id`.
+const BASE_URI: &str = "json-schema:///";
+
+static EXPORT: OnceLock<JSONSchema> = OnceLock::new();
+static ITEM: OnceLock<JSONSchema> = OnceLock::new();
+
+/// A published schema.
+#[derive(Debug, Clone, Copy)]
+pub enum Schema {
+    /// A whole list export, with the list and its items.
+    Export,
+    /// One item, in an export or a file of items.
+    Item,
+}
+
+/// A way a document doesn't match its schema.
+#[derive(Debug)]
+pub struct SchemaError {
+    /// Where in the document, as a JSON Pointer like `/items/2/quantity`. Empty for the document
+    /// itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{}: {}", path, self.message),
+        }
+    }
+}
+
+impl Schema {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Schema::Export => "export.json",
+            Schema::Item => "item.json",
+        }
+    }
+
+    /// Returns every way the document doesn't match the schema, or nothing if it does.
+    pub fn errors(&self, document: &Value) -> Vec<SchemaError> {
+        let cell = match self {
+            Schema::Export => &EXPORT,
+            Schema::Item => &ITEM,
+        };
+        let schema = cell.get_or_init(|| compile(self.file_name()));
+        match schema.validate(document) {
+            Ok(()) => vec![],
+            Err(errors) => errors
+                .map(|error| SchemaError {
+                    path: error.instance_path.to_string(),
+                    message: error.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the text of the published schema with the file name, if there is one.
+pub fn document(name: &str) -> Option<&'static str> {
+    DOCUMENTS
+        .into_iter()
+        .find(|(file_name, _)| *file_name == name)
+        .map(|(_, text)| text)
+}
+
+/// Compiles the schema, with the others available to its `$ref`s. The schemas are part of the
+/// source, so one that doesn't compile is a bug.
+fn compile(name: &str) -> JSONSchema {
+    let parse = |text: &str| -> Value {
+        serde_json::from_str(text).expect("published schemas are valid JSON")
+    };
+
+    let mut options = JSONSchema::options();
+    for (file_name, text) in DOCUMENTS {
+        options.with_document(format!("{}{}", BASE_URI, file_name), parse(text));
+    }
+    let schema = parse(document(name).expect("schema is published"));
+    options
+        .compile(&schema)
+        .expect("published schemas are valid")
+}
@@ -0,0 +1,194 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use rocket::serde::json::{self, json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::fs;
+use rocket::{fairing, tokio, Build, Rocket};
+use rocket_db_pools::sqlx;
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use crate::config::AppConfig;
+use crate::db::models::{AccountExport, Delivery, Item, List, Upload};
+use crate::images::UploadStore;
+use crate::util::SiteUrl;
+
+/// Identifies the archive format, so imports can reject anything else.
+pub static ARCHIVE_FORMAT: &str = "wishlist-rs-export";
+/// The archive format version. Bump this when the layout of the archive changes.
+pub static ARCHIVE_VERSION: u32 = 1;
+
+/// Account export configuration, read from the `exports` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ExportConfig {
+    /// Where generated archives are stored.
+    pub export_dir: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            export_dir: "./data/exports".to_string(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Database error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("JSON error: {0}")]
+    Json(#[from] json::serde_json::Error),
+}
+
+/// Describes the archive. Written to `manifest.json`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: chrono::NaiveDateTime,
+}
+
+/// An image in the archive. The file itself is stored at `images/<token>`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ArchiveImage {
+    pub token: String,
+    pub content_type: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Stores generated archives on disk. Available as managed state.
+#[derive(Clone)]
+pub struct ExportStore {
+    dir: PathBuf,
+}
+
+impl ExportStore {
+    pub fn from_config(config: &ExportConfig) -> ExportStore {
+        ExportStore {
+            dir: PathBuf::from(&config.export_dir),
+        }
+    }
+
+    /// The path of the export's archive.
+    pub fn file_path(&self, token: &str) -> PathBuf {
+        self.dir.join(format!("{}.zip", token))
+    }
+}
+
+/// Everything needed to generate an archive in the background.
+pub struct ExportJob {
+    pub pool: sqlx::AnyPool,
+    pub store: ExportStore,
+    pub uploads: UploadStore,
+    pub site: SiteUrl,
+}
+
+impl ExportJob {
+    /// Generates the export's archive in the background and emails the user when it's ready.
+    ///
+    /// `profile` is the user's profile, already serialized so the password hash is left out.
+    pub fn spawn(self, export: AccountExport, email: String, profile: Value) {
+        tokio::spawn(async move {
+            let status = match self.write_archive(&export, profile).await {
+                Ok(()) => AccountExport::READY,
+                Err(e) => {
+                    error!("Failed to generate export {}: {}", export.id, e);
+                    AccountExport::FAILED
+                }
+            };
+
+            if let Err(e) = AccountExport::set_status(&self.pool, export.id, status).await {
+                error!("Failed to update export {}: {}", export.id, e);
+                return;
+            }
+
+            // Without a link, the email says the export failed
+            let link = (status == AccountExport::READY).then(|| {
+                self.site.url(
+                    &uri!(crate::web::account::download_export(export.token.as_str())).to_string(),
+                )
+            });
+            let context = json!({ "link": link });
+            if let Err(e) =
+                Delivery::enqueue_email(&self.pool, &email, "account_export", &context).await
+            {
+                warn!(
+                    "Failed to queue export email for export {}: {}",
+                    export.id, e
+                );
+            }
+        });
+    }
+
+    async fn write_archive(&self, export: &AccountExport, profile: Value) -> Result<(), ExportError> {
+        let lists = List::export_by_user(&self.pool, export.user_id).await?;
+        let items = Item::export_by_user(&self.pool, export.user_id).await?;
+        let uploads = Upload::export_by_user(&self.pool, export.user_id).await?;
+
+        let mut images = vec![];
+        let mut files = vec![];
+        for upload in uploads {
+            // Skip files that have been cleaned up, the rest of the archive is still useful
+            match fs::read(self.uploads.file_path(&upload.token)).await {
+                Ok(data) => files.push((format!("images/{}", upload.token), data)),
+                Err(e) => {
+                    warn!("Skipping upload {} in export {}: {}", upload.id, export.id, e);
+                    continue;
+                }
+            }
+            images.push(ArchiveImage {
+                token: upload.token,
+                content_type: upload.content_type,
+                created_at: upload.created_at,
+            });
+        }
+
+        let manifest = Manifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            exported_at: chrono::Utc::now().naive_utc(),
+        };
+
+        files.push(("manifest.json".to_string(), json::serde_json::to_vec_pretty(&manifest)?));
+        files.push(("profile.json".to_string(), json::serde_json::to_vec_pretty(&profile)?));
+        files.push(("lists.json".to_string(), json::serde_json::to_vec_pretty(&lists)?));
+        files.push(("items.json".to_string(), json::serde_json::to_vec_pretty(&items)?));
+        files.push(("images.json".to_string(), json::serde_json::to_vec_pretty(&images)?));
+
+        // Zipping is CPU bound, so keep it off the async workers
+        let archive = tokio::task::spawn_blocking(move || zip_files(files))
+            .await
+            .map_err(std::io::Error::other)??;
+
+        fs::create_dir_all(&self.store.dir).await?;
+        fs::write(self.store.file_path(&export.token), archive).await?;
+
+        Ok(())
+    }
+}
+
+fn zip_files(files: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>, ExportError> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, data) in files {
+        zip.start_file(name, FileOptions::default())?;
+        zip.write_all(&data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Reads the export config and adds the `ExportStore` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).exports.clone();
+
+    Ok(rocket.manage(ExportStore::from_config(&config)))
+}
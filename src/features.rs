@@ -0,0 +1,255 @@
+//! Instance features that can be turned off, like registration or the API.
+//!
+//! Each starts out as set in the `features` table in Rocket.toml, on unless it says otherwise
+//! (only `claim_history` starts out off).
+//! Admins can turn them on or off at runtime on /admin/features, which is saved in the database
+//! and wins over Rocket.toml from then on.
+//!
+//! Web routes for a feature take an `Enabled` guard, which answers with a 404 while it's off. API
+//! callers are turned away by `crate::api::access` instead, with a 403 and a reason.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::RwLock;
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+use rocket_db_pools::{Connection, Database};
+
+use crate::config::AppConfig;
+use crate::db::models::FeatureFlag;
+use crate::db::{DataError, WishlistDb};
+
+/// An instance feature that can be turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum Feature {
+    /// Signing up for an account. Directory logins aren't affected.
+    Registration,
+    /// The index of every public list.
+    PublicIndex,
+    /// Adding photos to items, on the web or through the API.
+    ImageUploads,
+    /// The JSON API, apart from inbound email.
+    Api,
+    /// Letting people see the claim history of items they've claimed, see `ClaimEvent`. Admins
+    /// can always see it.
+    ClaimHistory,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::Registration,
+        Feature::PublicIndex,
+        Feature::ImageUploads,
+        Feature::Api,
+        Feature::ClaimHistory,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::Registration => "registration",
+            Feature::PublicIndex => "public_index",
+            Feature::ImageUploads => "image_uploads",
+            Feature::Api => "api",
+            Feature::ClaimHistory => "claim_history",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Feature> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == value)
+    }
+
+    /// A name for the feature, for the admin page.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Feature::Registration => "Registration",
+            Feature::PublicIndex => "Public list index",
+            Feature::ImageUploads => "Image uploads",
+            Feature::Api => "API",
+            Feature::ClaimHistory => "Claim history for claimers",
+        }
+    }
+}
+
+/// Which features are on, read from the `features` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct FeaturesConfig {
+    pub registration: bool,
+    pub public_index: bool,
+    pub image_uploads: bool,
+    pub api: bool,
+    pub claim_history: bool,
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        Self {
+            registration: true,
+            public_index: true,
+            image_uploads: true,
+            api: true,
+            claim_history: false,
+        }
+    }
+}
+
+impl FeaturesConfig {
+    fn enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Registration => self.registration,
+            Feature::PublicIndex => self.public_index,
+            Feature::ImageUploads => self.image_uploads,
+            Feature::Api => self.api,
+            Feature::ClaimHistory => self.claim_history,
+        }
+    }
+}
+
+/// Whether each feature is on right now.
+pub struct FeatureFlags {
+    enabled: RwLock<HashMap<Feature, bool>>,
+}
+
+impl FeatureFlags {
+    pub fn from_config(config: &FeaturesConfig) -> FeatureFlags {
+        FeatureFlags {
+            enabled: RwLock::new(
+                Feature::ALL
+                    .into_iter()
+                    .map(|feature| (feature, config.enabled(feature)))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled
+            .read()
+            .unwrap()
+            .get(&feature)
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Turns the feature on or off, saving the admin's choice so it outlasts a restart.
+    pub async fn set(
+        &self,
+        db: &mut Connection<WishlistDb>,
+        feature: Feature,
+        enabled: bool,
+        admin_id: i64,
+    ) -> Result<(), DataError> {
+        FeatureFlag::set(db, feature.as_str(), enabled, admin_id).await?;
+        self.enabled.write().unwrap().insert(feature, enabled);
+        Ok(())
+    }
+
+    /// Every feature with whether it's on, for the admin page.
+    pub fn all(&self) -> Vec<FeatureState> {
+        Feature::ALL
+            .into_iter()
+            .map(|feature| FeatureState {
+                name: feature.as_str(),
+                label: feature.label(),
+                enabled: self.is_enabled(feature),
+            })
+            .collect()
+    }
+}
+
+/// A feature on the admin page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeatureState {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub enabled: bool,
+}
+
+/// Whether the feature is on for the request. Features are on if the flags aren't set up.
+pub fn is_enabled(request: &Request<'_>, feature: Feature) -> bool {
+    request
+        .rocket()
+        .state::<FeatureFlags>()
+        .is_none_or(|flags| flags.is_enabled(feature))
+}
+
+/// A feature a route needs, see `Enabled`.
+pub trait RequiredFeature: Send + Sync + 'static {
+    const FEATURE: Feature;
+}
+
+/// Marker types for the `Enabled` guard, one per `Feature` that web routes need.
+pub mod flags {
+    use super::{Feature, RequiredFeature};
+
+    pub struct Registration;
+    pub struct PublicIndex;
+    pub struct ImageUploads;
+    pub struct ClaimHistory;
+
+    impl RequiredFeature for Registration {
+        const FEATURE: Feature = Feature::Registration;
+    }
+    impl RequiredFeature for PublicIndex {
+        const FEATURE: Feature = Feature::PublicIndex;
+    }
+    impl RequiredFeature for ImageUploads {
+        const FEATURE: Feature = Feature::ImageUploads;
+    }
+    impl RequiredFeature for ClaimHistory {
+        const FEATURE: Feature = Feature::ClaimHistory;
+    }
+}
+
+/// Succeeds if feature `F` is on, and fails with a 404 if it's off.
+pub struct Enabled<F>(PhantomData<F>);
+
+#[rocket::async_trait]
+impl<'r, F: RequiredFeature> FromRequest<'r> for Enabled<F> {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if is_enabled(request, F::FEATURE) {
+            Outcome::Success(Enabled(PhantomData))
+        } else {
+            Outcome::Failure((Status::NotFound, ()))
+        }
+    }
+}
+
+/// Reads the features config and the admins' changes to it, and adds the `FeatureFlags` to
+/// managed state. Needs the database.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let flags = FeatureFlags::from_config(&AppConfig::of(&rocket).features);
+
+    let pool = match WishlistDb::fetch(&rocket) {
+        Some(db) => (**db).clone(),
+        None => {
+            error!("Feature flags need the database");
+            return Err(rocket);
+        }
+    };
+
+    match FeatureFlag::all(&pool).await {
+        Ok(changes) => {
+            let mut enabled = flags.enabled.write().unwrap();
+            for change in changes {
+                if let Some(feature) = Feature::parse(&change.name) {
+                    enabled.insert(feature, change.enabled);
+                }
+            }
+        }
+        Err(e) => {
+            error!("Failed to load feature flags: {}", e);
+            return Err(rocket);
+        }
+    }
+
+    Ok(rocket.manage(flags))
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// An entry in an RSS or Atom feed.
+pub struct FeedEntry {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub summary: String,
+    pub published: NaiveDateTime,
+    /// When the entry last changed. RSS only has `published`.
+    pub updated: NaiveDateTime,
+}
+
+/// A minimal RSS 2.0 or Atom feed.
+pub struct Feed {
+    pub title: String,
+    pub link: String,
+    pub description: String,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    /// Renders the feed as an RSS 2.0 document.
+    pub fn to_rss(&self) -> String {
+        let mut rss = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        rss.push_str(r#"<rss version="2.0"><channel>"#);
+        push_element(&mut rss, "title", &self.title);
+        push_element(&mut rss, "link", &self.link);
+        push_element(&mut rss, "description", &self.description);
+
+        for entry in &self.entries {
+            rss.push_str("<item>");
+            push_element(&mut rss, "guid", &entry.id);
+            push_element(&mut rss, "title", &entry.title);
+            push_element(&mut rss, "link", &entry.link);
+            push_element(&mut rss, "description", &entry.summary);
+            push_element(&mut rss, "pubDate", &utc(entry.published).to_rfc2822());
+            rss.push_str("</item>");
+        }
+
+        rss.push_str("</channel></rss>");
+        rss
+    }
+
+    /// Renders the feed as an Atom document served from `feed_url`. Atom needs ids that don't
+    /// change, so entries' ids should be URLs or `tag:` URIs, and an author, which is `author` for
+    /// every entry.
+    pub fn to_atom(&self, feed_url: &str, author: &str) -> String {
+        let updated = self
+            .entries
+            .iter()
+            .map(|entry| entry.updated)
+            .max()
+            .map(utc)
+            .unwrap_or_else(Utc::now);
+
+        let mut atom = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        atom.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+        push_element(&mut atom, "id", feed_url);
+        push_element(&mut atom, "title", &self.title);
+        push_element(&mut atom, "subtitle", &self.description);
+        push_element(&mut atom, "updated", &updated.to_rfc3339());
+        push_link(&mut atom, "alternate", &self.link);
+        push_link(&mut atom, "self", feed_url);
+        atom.push_str("<author>");
+        push_element(&mut atom, "name", author);
+        atom.push_str("</author>");
+
+        for entry in &self.entries {
+            atom.push_str("<entry>");
+            push_element(&mut atom, "id", &entry.id);
+            push_element(&mut atom, "title", &entry.title);
+            push_link(&mut atom, "alternate", &entry.link);
+            push_element(&mut atom, "summary", &entry.summary);
+            push_element(&mut atom, "published", &utc(entry.published).to_rfc3339());
+            push_element(&mut atom, "updated", &utc(entry.updated).to_rfc3339());
+            atom.push_str("</entry>");
+        }
+
+        atom.push_str("</feed>");
+        atom
+    }
+}
+
+fn utc(time: NaiveDateTime) -> DateTime<Utc> {
+    DateTime::from_naive_utc_and_offset(time, Utc)
+}
+
+fn push_element(out: &mut String, name: &str, content: &str) {
+    out.push('<');
+    out.push_str(name);
+    out.push('>');
+    out.push_str(&escape(content));
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+fn push_link(out: &mut String, rel: &str, href: &str) {
+    out.push_str(r#"<link rel=""#);
+    out.push_str(rel);
+    out.push_str(r#"" href=""#);
+    out.push_str(&escape(href));
+    out.push_str(r#""/>"#);
+}
+
+/// Escapes text for use in XML content and attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,132 @@
+//! Typo tolerant matching for search, based on trigrams like Postgres' `pg_trgm`.
+
+use std::collections::HashSet;
+
+use crate::feeds::escape;
+
+/// How similar two words have to be to count as a match, from 0 to 1.
+pub const MATCH_THRESHOLD: f64 = 0.35;
+
+/// Splits text into lowercase words, dropping punctuation.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Returns the word's trigrams. Words are padded with two spaces in front and one behind, so
+/// short words have trigrams and matching starts of words count for more.
+pub fn word_trigrams(word: &str) -> HashSet<String> {
+    let padded = format!("  {} ", word).chars().collect::<Vec<_>>();
+    padded
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+/// Returns the trigrams of every word in the text.
+pub fn trigrams(text: &str) -> HashSet<String> {
+    words(text)
+        .iter()
+        .flat_map(|word| word_trigrams(word))
+        .collect()
+}
+
+/// How similar two words are, from 0 for nothing in common to 1 for the same word.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (word_trigrams(a), word_trigrams(b));
+    let shared = a.intersection(&b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
+
+/// How well the word matches the query word. Words starting with the query word, e.g. while
+/// someone is still typing, count as a match.
+fn word_score(query_word: &str, word: &str) -> f64 {
+    if word.starts_with(query_word) {
+        if word.len() == query_word.len() {
+            1.0
+        } else {
+            0.9
+        }
+    } else {
+        similarity(query_word, word)
+    }
+}
+
+/// Scores words against a query, from 0 to 1: how well each query word matches its best word,
+/// averaged. Returns 0 if any query word doesn't match at all, so every word has to be found.
+pub fn score(query_words: &[String], words: &[String]) -> f64 {
+    if query_words.is_empty() {
+        return 0.0;
+    }
+
+    let mut total = 0.0;
+    for query_word in query_words {
+        let best = words
+            .iter()
+            .map(|word| word_score(query_word, word))
+            .fold(0.0, f64::max);
+        if best < MATCH_THRESHOLD {
+            return 0.0;
+        }
+        total += best;
+    }
+    total / query_words.len() as f64
+}
+
+fn matches(query_words: &[String], word: &str) -> bool {
+    let word = word.to_lowercase();
+    query_words
+        .iter()
+        .any(|query_word| word_score(query_word, &word) >= MATCH_THRESHOLD)
+}
+
+/// Escapes the text for HTML and wraps words matching the query in `<mark>`.
+///
+/// With `max_words`, only that many words around the first match are kept, with an ellipsis
+/// where text was cut.
+pub fn highlight(text: &str, query_words: &[String], max_words: Option<usize>) -> String {
+    let tokens = text.split_whitespace().collect::<Vec<_>>();
+    let marked = tokens
+        .iter()
+        .map(|token| words(token).iter().any(|word| matches(query_words, word)))
+        .collect::<Vec<_>>();
+
+    let (start, end) = match max_words {
+        Some(max) if tokens.len() > max => {
+            let first = marked.iter().position(|m| *m).unwrap_or(0);
+            let start = first.saturating_sub(max / 3).min(tokens.len() - max);
+            (start, start + max)
+        }
+        _ => (0, tokens.len()),
+    };
+
+    let mut html = tokens[start..end]
+        .iter()
+        .zip(&marked[start..end])
+        .map(|(token, marked)| {
+            if *marked {
+                format!("<mark>{}</mark>", escape(token))
+            } else {
+                escape(token)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    if start > 0 {
+        html.insert_str(0, "… ");
+    }
+    if end < tokens.len() {
+        html.push_str(" …");
+    }
+    html
+}
@@ -0,0 +1,546 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::{AnimationDecoder, DynamicImage, ImageFormat, ImageOutputFormat};
+use rocket::data::{Data, ToByteUnit};
+use rocket::serde::Deserialize;
+use rocket::tokio::fs;
+use rocket::tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use rocket::{fairing, Build, Rocket};
+use sha2::Sha256;
+use thiserror::Error;
+use validator::ValidationError;
+
+use crate::config::AppConfig;
+
+/// Image configuration, read from the `images` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ImageConfig {
+    /// The key used to sign image URLs. If unset, a random key is generated at startup and
+    /// signed URLs stop working when the server restarts.
+    pub signing_key: Option<String>,
+    /// How long signed image URLs stay valid, in seconds.
+    pub signed_url_ttl_secs: i64,
+    /// The content types uploads may have, detected from the file's magic bytes.
+    pub allowed_types: Vec<String>,
+    /// How uploads are scanned for malware.
+    pub scanner: ScannerKind,
+    /// The ClamAV daemon's unix socket, used when `scanner = "clamav"`.
+    pub clamav_socket: Option<String>,
+    /// The ClamAV daemon's TCP address, used when `scanner = "clamav"` and no socket is set.
+    pub clamav_address: Option<String>,
+    /// A command that receives the upload on stdin and exits with 0 if it's clean, used when
+    /// `scanner = "command"`.
+    pub scan_command: Option<String>,
+    /// Where chunked uploads are assembled.
+    pub upload_dir: String,
+    /// The largest file that can be uploaded, in bytes.
+    pub max_upload_size: u64,
+    /// Where item images are stored.
+    pub storage_dir: String,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            signing_key: None,
+            signed_url_ttl_secs: 60 * 60,
+            allowed_types: vec![
+                "image/jpeg".to_string(),
+                "image/png".to_string(),
+                "image/gif".to_string(),
+                "image/webp".to_string(),
+            ],
+            scanner: ScannerKind::None,
+            clamav_socket: None,
+            clamav_address: None,
+            scan_command: None,
+            upload_dir: "./data/uploads".to_string(),
+            max_upload_size: 10 * 1024 * 1024,
+            storage_dir: "./data/images".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum ScannerKind {
+    None,
+    Clamav,
+    Command,
+}
+
+/// Signs and verifies expiring image URLs, so images on private lists can't be fetched by ID
+/// alone. Available as managed state.
+pub struct ImageSigner {
+    key: Vec<u8>,
+    ttl_secs: i64,
+}
+
+impl ImageSigner {
+    /// Creates a new signer from the given config.
+    pub fn from_config(config: ImageConfig) -> ImageSigner {
+        let key = match config.signing_key {
+            Some(key) => key,
+            None => {
+                warn!("No images.signing_key configured, signed image URLs will not survive a restart");
+                crate::util::random_token()
+            }
+        };
+
+        ImageSigner {
+            key: key.into_bytes(),
+            ttl_secs: config.signed_url_ttl_secs,
+        }
+    }
+
+    /// Returns the signature and expiry timestamp for the given image.
+    pub fn sign(&self, image_id: i64) -> (String, i64) {
+        let expires = Utc::now().timestamp() + self.ttl_secs;
+        (hex::encode(self.mac(image_id, expires).finalize().into_bytes()), expires)
+    }
+
+    /// Returns true if the signature is valid for the image and hasn't expired.
+    pub fn verify(&self, image_id: i64, expires: i64, signature: &str) -> bool {
+        if expires < Utc::now().timestamp() {
+            return false;
+        }
+
+        match hex::decode(signature) {
+            Ok(signature) => self.mac(image_id, expires).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, image_id: i64, expires: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take a key of any size");
+        mac.update(format!("{}:{}", image_id, expires).as_bytes());
+        mac
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ScanError {
+    #[error("Scanner is not configured: {0}")]
+    Config(&'static str),
+    #[error("Scanner IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unexpected scanner response: {0}")]
+    Response(String),
+}
+
+/// Validates and scans uploaded files before they're stored. Available as managed state.
+pub struct ImageScanner {
+    allowed_types: Vec<String>,
+    scanner: ScannerKind,
+    clamav_socket: Option<String>,
+    clamav_address: Option<String>,
+    scan_command: Option<String>,
+}
+
+impl ImageScanner {
+    /// Creates a new scanner from the given config.
+    pub fn from_config(config: &ImageConfig) -> ImageScanner {
+        ImageScanner {
+            allowed_types: config.allowed_types.clone(),
+            scanner: config.scanner,
+            clamav_socket: config.clamav_socket.clone(),
+            clamav_address: config.clamav_address.clone(),
+            scan_command: config.scan_command.clone(),
+        }
+    }
+
+    /// Checks an upload, returning its content type if it's an allowed, clean image.
+    ///
+    /// The content type comes from the file's magic bytes, never from what the client claimed.
+    pub async fn check(&self, data: &[u8]) -> Result<&'static str, ValidationError> {
+        let content_type = match detect_content_type(data) {
+            Some(content_type) if self.allowed_types.iter().any(|t| t == content_type) => {
+                content_type
+            }
+            _ => {
+                let mut err = ValidationError::new("content_type");
+                err.message = Some(Cow::from(format!(
+                    "Unsupported file type, allowed types are: {}",
+                    self.allowed_types.join(", ")
+                )));
+                return Err(err);
+            }
+        };
+
+        match self.scan(data).await {
+            Ok(None) => Ok(content_type),
+            Ok(Some(signature)) => {
+                let mut err = ValidationError::new("infected");
+                err.message = Some(Cow::from(format!(
+                    "This file was rejected by the virus scanner ({})",
+                    signature
+                )));
+                Err(err)
+            }
+            Err(e) => {
+                error!("Failed to scan upload: {}", e);
+                let mut err = ValidationError::new("scan_failed");
+                err.message = Some(Cow::from("This file could not be scanned, please try again later"));
+                Err(err)
+            }
+        }
+    }
+
+    /// Scans the data, returning the name of the detected threat if it's infected.
+    async fn scan(&self, data: &[u8]) -> Result<Option<String>, ScanError> {
+        match self.scanner {
+            ScannerKind::None => Ok(None),
+            ScannerKind::Clamav => {
+                if let Some(path) = &self.clamav_socket {
+                    let stream = rocket::tokio::net::UnixStream::connect(path).await?;
+                    clamav_instream(stream, data).await
+                } else if let Some(address) = &self.clamav_address {
+                    let stream = rocket::tokio::net::TcpStream::connect(address).await?;
+                    clamav_instream(stream, data).await
+                } else {
+                    Err(ScanError::Config("clamav_socket or clamav_address must be set"))
+                }
+            }
+            ScannerKind::Command => {
+                let command = self
+                    .scan_command
+                    .as_ref()
+                    .ok_or(ScanError::Config("scan_command must be set"))?;
+
+                let mut child = rocket::tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(data).await?;
+                }
+
+                if child.wait().await?.success() {
+                    Ok(None)
+                } else {
+                    Ok(Some("rejected by scan command".to_string()))
+                }
+            }
+        }
+    }
+}
+
+/// Streams data to a ClamAV daemon with the INSTREAM command.
+async fn clamav_instream<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    data: &[u8],
+) -> Result<Option<String>, ScanError> {
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(64 * 1024) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let response = response.trim_end_matches('\0').trim();
+
+    // Responses look like "stream: OK" or "stream: Eicar-Signature FOUND"
+    match response.strip_prefix("stream: ") {
+        Some("OK") => Ok(None),
+        Some(result) if result.ends_with(" FOUND") => {
+            Ok(Some(result.trim_end_matches(" FOUND").to_string()))
+        }
+        _ => Err(ScanError::Response(response.to_string())),
+    }
+}
+
+/// Detects an image's content type from its magic bytes.
+pub fn detect_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProcessError {
+    #[error("Image could not be processed: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Image processing was interrupted: {0}")]
+    Join(#[from] rocket::tokio::task::JoinError),
+    #[error("Images of type {0} can't be processed")]
+    Unsupported(String),
+}
+
+/// Strips metadata (EXIF, including GPS location) from an image by re-encoding it, and rotates
+/// photos upright. Returns the new data and its content type.
+///
+/// JPEG and PNG images are rotated according to their EXIF orientation and re-encoded in the same
+/// format, which drops all metadata. GIFs are re-encoded frame by frame, so animations still play.
+/// WebP images are re-encoded as PNG, since WebP can only be decoded here. Anything else can't be
+/// cleaned, so it's rejected.
+pub async fn normalize(
+    data: Vec<u8>,
+    content_type: &'static str,
+) -> Result<(Vec<u8>, &'static str), ProcessError> {
+    if content_type == "image/gif" {
+        return rocket::tokio::task::spawn_blocking(move || {
+            let frames = GifDecoder::new(Cursor::new(&data))?
+                .into_frames()
+                .collect_frames()?;
+            let mut output = Vec::new();
+            {
+                let mut encoder = GifEncoder::new(&mut output);
+                encoder.set_repeat(Repeat::Infinite)?;
+                encoder.encode_frames(frames)?;
+            }
+            Ok((output, content_type))
+        })
+        .await?;
+    }
+
+    let (format, output_format, output_type) = match content_type {
+        "image/jpeg" => (ImageFormat::Jpeg, ImageOutputFormat::Jpeg(90), "image/jpeg"),
+        "image/png" => (ImageFormat::Png, ImageOutputFormat::Png, "image/png"),
+        "image/webp" => (ImageFormat::WebP, ImageOutputFormat::Png, "image/png"),
+        other => return Err(ProcessError::Unsupported(other.to_string())),
+    };
+
+    rocket::tokio::task::spawn_blocking(move || {
+        let orientation = exif_orientation(&data);
+        let image = image::load_from_memory_with_format(&data, format)?;
+        let image = apply_orientation(image, orientation);
+
+        let mut output = Vec::new();
+        image.write_to(&mut Cursor::new(&mut output), output_format)?;
+        Ok((output, output_type))
+    })
+    .await?
+}
+
+/// Reads the EXIF orientation tag, defaulting to 1 (upright).
+fn exif_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+/// Rotates and flips the image so it's upright for the given EXIF orientation.
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UploadError {
+    #[error("Invalid upload: {0}")]
+    Invalid(ValidationError),
+    #[error("Upload IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Process(#[from] ProcessError),
+}
+
+/// Assembles chunked uploads on disk. Available as managed state.
+#[derive(Clone)]
+pub struct UploadStore {
+    dir: PathBuf,
+    pub max_upload_size: u64,
+    /// The uploads a chunk is being appended to right now.
+    appending: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Held while a chunk is appended to an upload, see `UploadStore::lock`.
+pub struct AppendLock {
+    token: String,
+    appending: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Drop for AppendLock {
+    fn drop(&mut self) {
+        self.appending.lock().unwrap().remove(&self.token);
+    }
+}
+
+impl UploadStore {
+    /// Creates a new upload store from the given config.
+    pub fn from_config(config: &ImageConfig) -> UploadStore {
+        UploadStore {
+            dir: PathBuf::from(&config.upload_dir),
+            max_upload_size: config.max_upload_size,
+            appending: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Locks the upload so only one chunk is appended to it at a time, or returns `None` if a
+    /// chunk is already being appended.
+    pub fn lock(&self, token: &str) -> Option<AppendLock> {
+        if !self.appending.lock().unwrap().insert(token.to_string()) {
+            return None;
+        }
+        Some(AppendLock {
+            token: token.to_string(),
+            appending: self.appending.clone(),
+        })
+    }
+
+    /// The path chunks are appended to while the upload is in progress.
+    fn part_path(&self, token: &str) -> PathBuf {
+        self.dir.join(format!("{}.part", token))
+    }
+
+    /// The path of the assembled and validated file.
+    pub fn file_path(&self, token: &str) -> PathBuf {
+        self.dir.join(token)
+    }
+
+    /// Appends a chunk to the upload, reading at most `limit` bytes. Returns the number of bytes
+    /// written.
+    pub async fn append(&self, token: &str, chunk: Data<'_>, limit: u64) -> std::io::Result<u64> {
+        fs::create_dir_all(&self.dir).await?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.part_path(token))
+            .await?;
+
+        let written = chunk.open(limit.bytes()).stream_to(&mut file).await?;
+        Ok(written.written)
+    }
+
+    /// Validates, scans and normalizes the assembled upload, returning its content type.
+    ///
+    /// The partial file is always removed, the finished file is only kept if it's valid.
+    pub async fn finish(
+        &self,
+        token: &str,
+        scanner: &ImageScanner,
+    ) -> Result<&'static str, UploadError> {
+        let part_path = self.part_path(token);
+        let data = fs::read(&part_path).await?;
+        fs::remove_file(&part_path).await?;
+
+        self.store(token, data, scanner).await
+    }
+
+    /// Validates, scans and normalizes a whole file and stores it as the upload's finished file,
+    /// returning its content type.
+    pub async fn store(
+        &self,
+        token: &str,
+        data: Vec<u8>,
+        scanner: &ImageScanner,
+    ) -> Result<&'static str, UploadError> {
+        let content_type = scanner.check(&data).await.map_err(UploadError::Invalid)?;
+        let (data, content_type) = normalize(data, content_type).await?;
+        fs::create_dir_all(&self.dir).await?;
+        fs::write(self.file_path(token), data).await?;
+
+        Ok(content_type)
+    }
+
+    /// Removes any files belonging to the upload.
+    pub async fn remove(&self, token: &str) {
+        let _ = fs::remove_file(self.part_path(token)).await;
+        let _ = fs::remove_file(self.file_path(token)).await;
+    }
+}
+
+/// Stores item images on disk, named by their ID. Available as managed state.
+#[derive(Clone)]
+pub struct ImageStore {
+    dir: PathBuf,
+    pub max_upload_size: u64,
+}
+
+impl ImageStore {
+    /// Creates a new image store from the given config.
+    pub fn from_config(config: &ImageConfig) -> ImageStore {
+        ImageStore {
+            dir: PathBuf::from(&config.storage_dir),
+            max_upload_size: config.max_upload_size,
+        }
+    }
+
+    /// The path of the image's file.
+    pub fn file_path(&self, image_id: i64) -> PathBuf {
+        self.dir.join(image_id.to_string())
+    }
+
+    /// Validates, scans and normalizes an image before it's stored, returning the data to store
+    /// and its content type.
+    pub async fn prepare(
+        &self,
+        data: Vec<u8>,
+        scanner: &ImageScanner,
+    ) -> Result<(Vec<u8>, &'static str), UploadError> {
+        if data.len() as u64 > self.max_upload_size {
+            let mut err = ValidationError::new("size");
+            err.message = Some(Cow::from(format!(
+                "Images must be at most {} bytes",
+                self.max_upload_size
+            )));
+            return Err(UploadError::Invalid(err));
+        }
+
+        let content_type = scanner.check(&data).await.map_err(UploadError::Invalid)?;
+        Ok(normalize(data, content_type).await?)
+    }
+
+    /// Writes a prepared image's file.
+    pub async fn write(&self, image_id: i64, data: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+        fs::write(self.file_path(image_id), data).await
+    }
+
+    /// Removes the image's file.
+    pub async fn remove(&self, image_id: i64) {
+        let _ = fs::remove_file(self.file_path(image_id)).await;
+    }
+}
+
+/// Reads the image config and adds the `ImageSigner`, `ImageScanner`, `UploadStore` and
+/// `ImageStore` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).images.clone();
+
+    let scanner = ImageScanner::from_config(&config);
+    let uploads = UploadStore::from_config(&config);
+    let images = ImageStore::from_config(&config);
+    Ok(rocket
+        .manage(ImageSigner::from_config(config))
+        .manage(scanner)
+        .manage(uploads)
+        .manage(images))
+}
@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio;
+use rocket_db_pools::Connection;
+use thiserror::Error;
+use validator::Validate;
+use zip::ZipArchive;
+
+use crate::db::models::{Item, List, ListPrivacy, Upload};
+use crate::db::{DataError, WishlistDb};
+use crate::exports::{ArchiveImage, Manifest, ARCHIVE_FORMAT, ARCHIVE_VERSION};
+use crate::images::{ImageScanner, UploadStore};
+use crate::quotas::Quotas;
+
+/// The most files an archive can have.
+const MAX_ENTRIES: usize = 2_000;
+
+/// The largest the JSON files in an archive can be, in bytes.
+const MAX_JSON_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The most an archive can unzip to, in bytes.
+const MAX_TOTAL_SIZE: u64 = 512 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Not a valid archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Not a valid archive: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Not a valid archive: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("This archive wasn't exported from a compatible wishlist instance")]
+    UnsupportedFormat,
+    #[error("The archive is too large: {0}")]
+    TooLarge(String),
+    #[error("{0}")]
+    Data(#[from] DataError),
+}
+
+/// A list in the archive. Only the fields needed to recreate it are read.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ArchiveList {
+    id: i64,
+    /// See `ListPrivacy::as_str`. Archives from before lists had privacy levels only have
+    /// `is_private` instead.
+    #[serde(default)]
+    privacy: Option<String>,
+    #[serde(default)]
+    is_private: Option<bool>,
+    title: String,
+    description: String,
+    #[serde(default)]
+    affiliate_opt_out: bool,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+impl ArchiveList {
+    fn privacy(&self) -> &str {
+        match &self.privacy {
+            Some(privacy) => privacy.as_str(),
+            None => ListPrivacy::from_is_private(self.is_private.unwrap_or(true)).as_str(),
+        }
+    }
+}
+
+/// An item in the archive. Only the fields needed to recreate it are read.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ArchiveItem {
+    list_id: i64,
+    title: String,
+    description: String,
+    url: Option<String>,
+    received_at: Option<chrono::NaiveDateTime>,
+}
+
+/// The contents of an export archive. Images are only unzipped one at a time as they're
+/// imported, so they're never all in memory at once.
+pub struct ImportArchive {
+    zip: ZipArchive<Cursor<Vec<u8>>>,
+    lists: Vec<ArchiveList>,
+    items: Vec<ArchiveItem>,
+    images: Vec<ArchiveImage>,
+    /// The largest an image can be, the same as for uploads.
+    max_image_size: u64,
+}
+
+/// What an import created, or would create in a dry run.
+#[derive(Serialize, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub lists: Vec<ImportedList>,
+    pub items_created: usize,
+    pub images_created: usize,
+    /// Entries that couldn't be imported, and why.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportedList {
+    pub title: String,
+    /// The list's title in the archive, if it was renamed to avoid clashing with an existing list.
+    pub renamed_from: Option<String>,
+    /// The new list's key, or `None` in a dry run.
+    pub key: Option<String>,
+}
+
+impl ImportArchive {
+    /// Reads an export archive, checking that it's a format this instance understands and that
+    /// it isn't too large once unzipped. Images can be at most `max_image_size` bytes.
+    pub async fn read(data: Vec<u8>, max_image_size: u64) -> Result<ImportArchive, ImportError> {
+        // Unzipping is CPU bound, so keep it off the async workers
+        tokio::task::spawn_blocking(move || ImportArchive::read_blocking(data, max_image_size))
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    fn read_blocking(data: Vec<u8>, max_image_size: u64) -> Result<ImportArchive, ImportError> {
+        let mut zip = ZipArchive::new(Cursor::new(data))?;
+
+        // Sizes are only what the archive claims, so `read_entry` checks them again while reading
+        if zip.len() > MAX_ENTRIES {
+            return Err(ImportError::TooLarge(format!("more than {} files", MAX_ENTRIES)));
+        }
+        let mut total_size: u64 = 0;
+        for i in 0..zip.len() {
+            total_size = total_size.saturating_add(zip.by_index_raw(i)?.size());
+        }
+        if total_size > MAX_TOTAL_SIZE {
+            return Err(ImportError::TooLarge(format!(
+                "more than {} bytes unzipped",
+                MAX_TOTAL_SIZE
+            )));
+        }
+
+        let manifest: Manifest =
+            serde_json::from_slice(&read_entry(&mut zip, "manifest.json", MAX_JSON_SIZE)?)?;
+        if manifest.format != ARCHIVE_FORMAT || manifest.version > ARCHIVE_VERSION {
+            return Err(ImportError::UnsupportedFormat);
+        }
+
+        let lists = serde_json::from_slice(&read_entry(&mut zip, "lists.json", MAX_JSON_SIZE)?)?;
+        let items = serde_json::from_slice(&read_entry(&mut zip, "items.json", MAX_JSON_SIZE)?)?;
+        let images: Vec<ArchiveImage> =
+            serde_json::from_slice(&read_entry(&mut zip, "images.json", MAX_JSON_SIZE)?)?;
+
+        for image in &images {
+            let name = image_entry(image);
+            if zip.by_name(&name)?.size() > max_image_size {
+                return Err(ImportError::TooLarge(name));
+            }
+        }
+
+        Ok(ImportArchive {
+            zip,
+            lists,
+            items,
+            images,
+            max_image_size,
+        })
+    }
+
+    /// Recreates the archive's lists, items and images for the given user, with new keys.
+    ///
+    /// Lists whose title clashes with one of the user's existing lists are renamed. In a dry run
+    /// everything is validated but nothing is saved. Anything over the user's quotas is skipped.
+    /// Lists are all made private if `can_publish` is false, see
+    /// `crate::web::auth::check_can_publish`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn import(
+        mut self,
+        conn: &mut Connection<WishlistDb>,
+        uploads: &UploadStore,
+        scanner: &ImageScanner,
+        quotas: &Quotas,
+        user_id: i64,
+        can_publish: bool,
+        dry_run: bool,
+    ) -> Result<ImportReport, ImportError> {
+        let mut report = ImportReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        let mut titles = List::all_by_user(conn, user_id)
+            .await
+            .map_err(DataError::from)?
+            .into_iter()
+            .map(|list| list.title)
+            .collect::<HashSet<_>>();
+
+        // Maps list IDs in the archive to the new lists
+        let mut list_ids = HashMap::new();
+        let mut saved_lists = HashMap::new();
+        for archived in self.lists {
+            let title = unique_title(&titles, &archived.title);
+            let privacy = if can_publish {
+                archived.privacy()
+            } else {
+                ListPrivacy::Private.as_str()
+            };
+            let list = List::new(
+                Some(user_id),
+                privacy.to_string(),
+                title.clone(),
+                archived.description,
+                archived.affiliate_opt_out,
+                archived.language,
+            );
+
+            // In a dry run, map to the archive's own ID so items can still be checked
+            let (id, key) = if dry_run {
+                if let Err(e) = list.validate() {
+                    report.skipped.push(format!("List \"{}\": {}", archived.title, e));
+                    continue;
+                }
+                (archived.id, None)
+            } else {
+                if let Some(message) = quotas.check_list(conn, Some(user_id)).await? {
+                    report.skipped.push(format!("List \"{}\": {}", archived.title, message));
+                    continue;
+                }
+                match list.save(conn, Some(user_id)).await {
+                    Ok(list) => {
+                        let (id, key) = (list.id, list.key.clone());
+                        saved_lists.insert(id, list);
+                        (id, Some(key))
+                    }
+                    Err(DataError::Validation(e)) => {
+                        report.skipped.push(format!("List \"{}\": {}", archived.title, e));
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            };
+
+            list_ids.insert(archived.id, id);
+            titles.insert(title.clone());
+            report.lists.push(ImportedList {
+                renamed_from: Some(archived.title).filter(|original| *original != title),
+                title,
+                key,
+            });
+        }
+
+        for archived in self.items {
+            let list_id = match list_ids.get(&archived.list_id) {
+                Some(list_id) => *list_id,
+                None => {
+                    report
+                        .skipped
+                        .push(format!("Item \"{}\": its list wasn't imported", archived.title));
+                    continue;
+                }
+            };
+
+            let item = Item::new(list_id, archived.title.clone(), archived.description, archived.url);
+            if dry_run {
+                match item.validate() {
+                    Ok(()) => report.items_created += 1,
+                    Err(e) => report.skipped.push(format!("Item \"{}\": {}", archived.title, e)),
+                }
+                continue;
+            }
+
+            if let Some(list) = saved_lists.get(&list_id) {
+                if let Some(message) = quotas.check_item(conn, list).await? {
+                    report.skipped.push(format!("Item \"{}\": {}", archived.title, message));
+                    continue;
+                }
+            }
+
+            match item.save(conn, Some(user_id)).await {
+                Ok(mut item) => {
+                    if archived.received_at.is_some() {
+                        item.set_received(conn, true).await?;
+                    }
+                    report.items_created += 1;
+                }
+                Err(DataError::Validation(e)) => {
+                    report.skipped.push(format!("Item \"{}\": {}", archived.title, e));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        for image in self.images {
+            let name = image_entry(&image);
+            let data = tokio::task::block_in_place(|| {
+                read_entry(&mut self.zip, &name, self.max_image_size)
+            });
+            let data = match data {
+                Ok(data) => data,
+                Err(e) => {
+                    report.skipped.push(format!("Image {}: {}", image.token, e));
+                    continue;
+                }
+            };
+
+            // Imported images go through the same checks as uploads
+            if dry_run {
+                match scanner.check(&data).await {
+                    Ok(_) => report.images_created += 1,
+                    Err(e) => report.skipped.push(format!("Image {}: {}", image.token, e)),
+                }
+                continue;
+            }
+
+            let size = data.len() as i64;
+            if let Some(message) = quotas.check_upload(conn, user_id, size).await? {
+                report.skipped.push(format!("Image {}: {}", image.token, message));
+                continue;
+            }
+            let mut upload = Upload::create(conn, user_id, size).await?;
+            match uploads.store(&upload.token, data, scanner).await {
+                Ok(content_type) => {
+                    let upload = upload.set_received(conn, size).await?;
+                    upload.complete(conn, content_type).await?;
+                    report.images_created += 1;
+                }
+                Err(e) => {
+                    upload.destroy(conn).await?;
+                    report.skipped.push(format!("Image {}: {}", image.token, e));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// The name of the image's file in the archive.
+fn image_entry(image: &ArchiveImage) -> String {
+    format!("images/{}", image.token)
+}
+
+/// Unzips the named file, as long as it's no larger than `limit` bytes, whatever size the archive
+/// says it is.
+fn read_entry(
+    zip: &mut ZipArchive<Cursor<Vec<u8>>>,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, ImportError> {
+    let entry = zip.by_name(name)?;
+    if entry.size() > limit {
+        return Err(ImportError::TooLarge(name.to_string()));
+    }
+    let mut data = vec![];
+    entry.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(ImportError::TooLarge(name.to_string()));
+    }
+    Ok(data)
+}
+
+/// Returns the title, with " (imported)" and a number appended if the user already has a list
+/// with that title.
+fn unique_title(titles: &HashSet<String>, title: &str) -> String {
+    if !titles.contains(title) {
+        return title.to_string();
+    }
+
+    let mut candidate = format!("{} (imported)", title);
+    let mut n = 2;
+    while titles.contains(&candidate) {
+        candidate = format!("{} (imported {})", title, n);
+        n += 1;
+    }
+    candidate
+}
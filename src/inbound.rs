@@ -0,0 +1,137 @@
+use mail_parser::{Address, MessageParser, MimeHeaders};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket, State};
+use sha2::{Digest, Sha256};
+
+use crate::config::AppConfig;
+
+/// The header the mail server's hook sends the shared secret in.
+pub static SECRET_HEADER: &str = "X-Inbound-Secret";
+
+/// Inbound email configuration, read from the `inbound_email` table in Rocket.toml. Adding items
+/// by email is only turned on when a domain and secret are both set.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct InboundConfig {
+    /// The domain users' secret addresses are on, e.g. `in.wishlist.example.com`.
+    pub domain: Option<String>,
+    /// Shared with the mail server's hook, which must send it in the `X-Inbound-Secret` header.
+    pub secret: Option<String>,
+    /// The largest email that's accepted, in bytes.
+    pub max_message_size: u64,
+}
+
+impl Default for InboundConfig {
+    fn default() -> Self {
+        Self {
+            domain: None,
+            secret: None,
+            max_message_size: 25 * 1024 * 1024,
+        }
+    }
+}
+
+impl InboundConfig {
+    /// Whether adding items by email is turned on.
+    pub fn enabled(&self) -> bool {
+        self.domain.is_some() && self.secret.is_some()
+    }
+
+    /// The full address for a user's token.
+    pub fn address(&self, token: &str) -> Option<String> {
+        self.domain
+            .as_ref()
+            .map(|domain| format!("{}@{}", token, domain))
+    }
+
+    /// Returns the token from a recipient address on our domain.
+    pub fn token_for<'a>(&self, recipient: &'a str) -> Option<&'a str> {
+        let domain = self.domain.as_ref()?;
+        let (local, host) = recipient.rsplit_once('@')?;
+        if !host.eq_ignore_ascii_case(domain) {
+            return None;
+        }
+        // Allow tagged addresses like "me+books@", the token is the part before the tag
+        Some(local.split('+').next().unwrap_or(local))
+    }
+}
+
+/// A request from the mail server's hook, carrying the right shared secret.
+pub struct InboundHook;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for InboundHook {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = match request.guard::<&State<InboundConfig>>().await.succeeded() {
+            Some(config) => config,
+            None => return Outcome::Forward(()),
+        };
+
+        let expected = match &config.secret {
+            Some(secret) if config.domain.is_some() => secret,
+            _ => return Outcome::Forward(()),
+        };
+
+        // Compare digests so the comparison doesn't leak how much of the secret matched
+        match request.headers().get_one(SECRET_HEADER) {
+            Some(secret) if Sha256::digest(secret) == Sha256::digest(expected) => {
+                Outcome::Success(InboundHook)
+            }
+            _ => Outcome::Failure((rocket::http::Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// The parts of an email that become an item.
+pub struct InboundMessage {
+    pub recipients: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    /// The contents of every attachment, in order.
+    pub attachments: Vec<Vec<u8>>,
+}
+
+impl InboundMessage {
+    /// Parses a raw RFC 5322 message, or returns `None` if it isn't one.
+    pub fn parse(raw: &[u8]) -> Option<InboundMessage> {
+        let message = MessageParser::default().parse(raw)?;
+
+        let mut recipients = Vec::new();
+        for address in [message.to(), message.cc()].into_iter().flatten() {
+            collect_addresses(address, &mut recipients);
+        }
+
+        let attachments = message
+            .attachments()
+            .filter(|part| part.content_type().is_none_or(|ct| ct.ctype() == "image"))
+            .map(|part| part.contents().to_vec())
+            .collect();
+
+        Some(InboundMessage {
+            recipients,
+            subject: message.subject().unwrap_or_default().trim().to_string(),
+            body: message
+                .body_text(0)
+                .map(|body| body.trim().to_string())
+                .unwrap_or_default(),
+            attachments,
+        })
+    }
+}
+
+fn collect_addresses(address: &Address, out: &mut Vec<String>) {
+    for addr in address.iter() {
+        if let Some(email) = addr.address() {
+            out.push(email.to_lowercase());
+        }
+    }
+}
+
+/// Reads the inbound email config and adds it to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).inbound_email.clone();
+
+    Ok(rocket.manage(config))
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{Days, Utc};
+use rocket::serde::json::json;
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::{JobsConfig, Notifier};
+use crate::db::models::{Claim, Delivery, DueClaimReminder};
+use crate::db::DataError;
+
+/// How often claims are checked for reminders.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically emails people about items they claimed but haven't bought yet, once a list's
+/// event date is `claim_reminder_days` away. Each claim is only reminded about once, and users can
+/// turn reminders off on their email settings page.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig, notifier: Notifier) {
+    if config.claim_reminder_days == 0 {
+        return;
+    }
+
+    let days = Days::new(config.claim_reminder_days);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(REMINDER_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = send_reminders(&pool, &notifier, days).await {
+                error!("Sending claim reminders failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn send_reminders(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    days: Days,
+) -> Result<(), DataError> {
+    let today = Utc::now().date_naive();
+    let until = match today.checked_add_days(days) {
+        Some(until) => until,
+        None => return Ok(()),
+    };
+
+    let mut by_user = BTreeMap::<i64, Vec<DueClaimReminder>>::new();
+    for claim in Claim::all_due_reminders(pool, today, until).await? {
+        by_user.entry(claim.user_id).or_default().push(claim);
+    }
+
+    for claims in by_user.values() {
+        // Claim each reminder before sending it, so a claim is never emailed about twice
+        let mut reminded = Vec::new();
+        for claim in claims {
+            if Claim::mark_reminded(pool, claim.claim_id).await? {
+                reminded.push(claim);
+            }
+        }
+        if reminded.is_empty() {
+            continue;
+        }
+
+        // Once it's queued, the notification worker retries sending it. If it can't be queued,
+        // the claims are tried again on the next run.
+        if let Err(e) = notify(pool, notifier, &reminded).await {
+            warn!(
+                "Failed to queue claim reminder for user {}: {}",
+                reminded[0].user_id, e
+            );
+            for claim in reminded {
+                Claim::unmark_reminded(pool, claim.claim_id).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn notify(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    claims: &[&DueClaimReminder],
+) -> Result<(), sqlx::Error> {
+    let claim_contexts: Vec<_> = claims
+        .iter()
+        .map(|claim| {
+            let list_key = claim.list_key.as_str();
+            let link = notifier
+                .site
+                .url(&uri!(crate::web::items::show(list_key, claim.item_id)).to_string());
+            json!({
+                "item_title": claim.item_title,
+                "list_title": claim.list_title,
+                "event_date": claim.event_date.format("%B %-d").to_string(),
+                "link": link,
+            })
+        })
+        .collect();
+    let context = json!({
+        "username": claims[0].username,
+        "claims": claim_contexts,
+        "settings_link": notifier.site.url(&uri!(crate::web::account::email).to_string()),
+    });
+
+    Delivery::enqueue_email(pool, &claims[0].user_email, "claim_reminder", &context).await
+}
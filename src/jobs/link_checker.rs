@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::serde::json::json;
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+use url::Url;
+
+use super::{JobsConfig, Notifier};
+use crate::db::models::{Delivery, Item};
+use crate::db::DataError;
+use crate::render_cache;
+use crate::sources::{PublicClient, SourceError, SourcesConfig};
+
+/// Periodically checks item links and marks the ones that look dead, emailing the list's owner
+/// when one breaks.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig, sources: &SourcesConfig, notifier: Notifier) {
+    if config.link_check_interval_secs == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.link_check_interval_secs);
+    // Item links are user input, so they're only fetched from public hosts, like item sources
+    let client = PublicClient::new(
+        Duration::from_secs(config.link_check_timeout_secs),
+        &sources.user_agent,
+    );
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = check_links(&pool, &client, &notifier, interval).await {
+                error!("Link check failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn check_links(
+    pool: &sqlx::AnyPool,
+    client: &PublicClient,
+    notifier: &Notifier,
+    interval: Duration,
+) -> Result<(), DataError> {
+    let interval = chrono::Duration::from_std(interval).unwrap_or_else(|_| chrono::Duration::days(1));
+    let checked_before = (Utc::now() - interval).naive_utc();
+
+    for item in Item::all_with_unchecked_links(pool, checked_before).await? {
+        if let Some(url) = &item.url {
+            let link_broken = is_broken(client, url).await;
+            Item::set_link_status(pool, item.id, link_broken).await?;
+            if link_broken != item.link_broken {
+                render_cache::invalidate(item.list_id);
+            }
+
+            // Once it's queued, the notification worker retries sending it
+            if link_broken && !item.link_broken {
+                if let Err(e) = notify(pool, notifier, &item).await {
+                    warn!("Failed to queue broken link email for item {}: {}", item.id, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Emails the owner of the item's list that its link broke. Anonymous lists have nobody to tell.
+async fn notify(pool: &sqlx::AnyPool, notifier: &Notifier, item: &Item) -> Result<(), sqlx::Error> {
+    let owner = match Item::link_owner(pool, item.id).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let key = owner.list_key.as_str();
+    let context = json!({
+        "username": owner.username,
+        "list_title": owner.list_title,
+        "item_title": item.title,
+        "link": notifier
+            .site
+            .url(&uri!(crate::web::items::edit(key, item.id)).to_string()),
+    });
+
+    Delivery::enqueue_email(pool, &owner.user_email, "broken_link", &context).await
+}
+
+/// Returns true if the link is gone or the site can't be reached.
+///
+/// Only "not found" style responses count as broken, stores that block bots or rate limit us
+/// aren't the link's fault. Links to hosts that aren't public are never fetched, and aren't
+/// marked broken either.
+async fn is_broken(client: &PublicClient, url: &str) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return true,
+    };
+
+    let response = match client.head(&url).await {
+        // Some stores don't support HEAD, so retry with GET
+        Ok(response) if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+            client.get(&url).await
+        }
+        response => response,
+    };
+
+    match response {
+        Ok(response) => matches!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE
+        ),
+        Err(SourceError::Request(e)) => e.is_connect() || e.is_request(),
+        Err(SourceError::NotFound) => true,
+        Err(_) => false,
+    }
+}
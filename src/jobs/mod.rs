@@ -0,0 +1,114 @@
+use rocket::fairing::AdHoc;
+use rocket::serde::Deserialize;
+use rocket_db_pools::Database;
+
+use crate::config::AppConfig;
+use crate::db::WishlistDb;
+use crate::notify::Dispatcher;
+use crate::util::SiteUrl;
+
+mod claim_reminders;
+mod link_checker;
+mod price_tracker;
+pub mod retention;
+mod saved_searches;
+mod search_indexer;
+mod session_cleanup;
+mod stale_lists;
+mod token_cleanup;
+
+/// Background job configuration, read from the `jobs` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct JobsConfig {
+    /// How often item links are checked for dead links, in seconds. 0 disables the check.
+    pub link_check_interval_secs: u64,
+    /// How long to wait for each link, in seconds.
+    pub link_check_timeout_secs: u64,
+    /// How often item prices are refreshed, in seconds. 0 disables price tracking.
+    pub price_check_interval_secs: u64,
+    /// How often changed lists and items are added to the search index, in seconds. 0 disables
+    /// indexing, so search only finds what was indexed before.
+    pub search_index_interval_secs: u64,
+    /// How often new items are checked against users' saved searches, in seconds. 0 disables
+    /// saved search emails.
+    pub saved_search_interval_secs: u64,
+    /// How many days an API token can go unused before it's revoked. 0 keeps tokens until
+    /// they're revoked by hand.
+    pub api_token_max_idle_days: u64,
+    /// How many days before a list's event date people are reminded about items they claimed but
+    /// haven't bought yet. 0 disables claim reminder emails.
+    pub claim_reminder_days: u64,
+    /// How many days before a list's event date its owner is reminded to look it over, if it
+    /// hasn't changed in `stale_list_age_days`. 0 disables stale list reminders.
+    pub stale_list_reminder_days: u64,
+    /// How many days a list has to go unchanged before its owner is reminded about it.
+    pub stale_list_age_days: u64,
+}
+
+/// What job notifications need to know. Emails are queued in the outbox (see
+/// `crate::db::models::Delivery`) and sent by the notification worker.
+#[derive(Clone)]
+pub struct Notifier {
+    pub site: SiteUrl,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            link_check_interval_secs: 24 * 60 * 60,
+            link_check_timeout_secs: 15,
+            price_check_interval_secs: 6 * 60 * 60,
+            search_index_interval_secs: 10,
+            saved_search_interval_secs: 60 * 60,
+            api_token_max_idle_days: 0,
+            claim_reminder_days: 7,
+            stale_list_reminder_days: 30,
+            stale_list_age_days: 180,
+        }
+    }
+}
+
+/// Starts the background jobs once the server is running.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Background Jobs", |rocket| {
+        Box::pin(async move {
+            let pool = match WishlistDb::fetch(rocket) {
+                Some(db) => (**db).clone(),
+                None => {
+                    error!("Background jobs need the database, not starting them");
+                    return;
+                }
+            };
+
+            let config = AppConfig::of(rocket).jobs.clone();
+            let sources = AppConfig::of(rocket).sources.clone();
+            let retention = AppConfig::of(rocket).retention.clone();
+
+            match rocket.state::<Dispatcher>() {
+                Some(dispatcher) => dispatcher.spawn_worker(),
+                None => error!("Notifications aren't set up, not sending them"),
+            }
+
+            search_indexer::spawn(pool.clone(), &config);
+            token_cleanup::spawn(pool.clone(), &config);
+            session_cleanup::spawn(pool.clone());
+            retention::spawn(pool.clone(), &retention);
+
+            match rocket.state::<SiteUrl>() {
+                Some(site) => {
+                    let notifier = Notifier { site: site.clone() };
+                    link_checker::spawn(pool.clone(), &config, &sources, notifier.clone());
+                    saved_searches::spawn(pool.clone(), &config, notifier.clone());
+                    claim_reminders::spawn(pool.clone(), &config, notifier.clone());
+                    stale_lists::spawn(pool.clone(), &config, notifier.clone());
+                    price_tracker::spawn(pool, &config, &sources, notifier);
+                }
+                None => error!(
+                    "Link checks, price tracking, saved searches and reminders need the site URL, \
+                    not starting them"
+                ),
+            }
+        })
+    })
+}
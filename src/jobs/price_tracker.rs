@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use rocket::serde::json::json;
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::{JobsConfig, Notifier};
+use crate::db::models::{Delivery, Item, ItemPrice, PriceAlert};
+use crate::db::DataError;
+use crate::sources::{Price, SourceRegistry, SourcesConfig};
+use crate::util;
+
+/// Periodically fetches the current price of every item with a link, records any changes, and
+/// notifies users whose target price has been reached.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig, sources: &SourcesConfig, notifier: Notifier) {
+    if config.price_check_interval_secs == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.price_check_interval_secs);
+    let registry = match SourceRegistry::with_defaults(sources) {
+        Ok(registry) => registry,
+        Err(e) => {
+            error!("Failed to start the price tracker: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = refresh_prices(&pool, &registry, &notifier).await {
+                error!("Price refresh failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn refresh_prices(
+    pool: &sqlx::AnyPool,
+    registry: &SourceRegistry,
+    notifier: &Notifier,
+) -> Result<(), DataError> {
+    for item in Item::all_with_links(pool).await? {
+        let url = match &item.url {
+            Some(url) => url,
+            None => continue,
+        };
+
+        // One store being down shouldn't stop the rest from being checked
+        let price = match registry.fetch_price(url).await {
+            Ok(Some(price)) => price,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to fetch the price of item {}: {}", item.id, e);
+                continue;
+            }
+        };
+
+        // Only record changes, so the history stays small
+        let latest = ItemPrice::latest_by_item(pool, item.id).await?;
+        let changed = match &latest {
+            Some(latest) => {
+                latest.amount_cents != price.amount_cents || latest.currency != price.currency
+            }
+            None => true,
+        };
+        if changed {
+            ItemPrice::create(pool, item.id, price.amount_cents, &price.currency).await?;
+        }
+
+        notify_alerts(pool, notifier, item.id, &price).await?;
+    }
+
+    Ok(())
+}
+
+/// Emails every user whose target price for the item has been reached.
+async fn notify_alerts(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    item_id: i64,
+    price: &Price,
+) -> Result<(), DataError> {
+    for alert in PriceAlert::all_reached(pool, item_id, price.amount_cents, &price.currency).await? {
+        let link = notifier.site.url(
+            &uri!(crate::web::items::show(alert.list_key.as_str(), alert.item_id)).to_string(),
+        );
+        let context = json!({
+            "item_title": alert.item_title,
+            "price": util::format_price(price.amount_cents, &price.currency),
+            "target_price": util::format_price(alert.target_cents, &alert.currency),
+            "link": link,
+        });
+
+        // Leave the alert armed if the email can't be queued, so it's retried on the next
+        // refresh. Once it's queued, the notification worker retries sending it.
+        match Delivery::enqueue_email(pool, &alert.user_email, "price_alert", &context).await {
+            Ok(()) => PriceAlert::mark_triggered(pool, alert.id).await?,
+            Err(e) => warn!("Failed to queue price alert {}: {}", alert.id, e),
+        }
+    }
+
+    Ok(())
+}
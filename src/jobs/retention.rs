@@ -0,0 +1,203 @@
+//! Retention policies: anonymous lists nobody has touched in a while, old sessions and accounts
+//! that never verified their email address are deleted. Each policy is off until it's given an
+//! age, and the job only reports what it would delete until `dry_run` is turned off, so admins
+//! can check on /admin/retention first.
+
+use std::time::Duration;
+
+use chrono::{Days, Months, Utc};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use crate::db::models::{List, User, UserSession};
+
+/// How often the policies are applied.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Retention configuration, read from the `retention` table in Rocket.toml. Each age of 0 turns
+/// its policy off.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RetentionConfig {
+    /// How many months an anonymous list can go without it or its items changing before it's
+    /// deleted.
+    pub anonymous_list_max_idle_months: u32,
+    /// How many days a session lasts at most, however long it was meant to.
+    pub session_max_age_days: u64,
+    /// How many days an account has to verify its email address before it's deleted, along
+    /// with its lists. Admins are never deleted.
+    pub unverified_account_max_age_days: u64,
+    /// Only log what would be deleted, without deleting it.
+    pub dry_run: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_list_max_idle_months: 0,
+            session_max_age_days: 0,
+            unverified_account_max_age_days: 0,
+            dry_run: true,
+        }
+    }
+}
+
+/// One of the retention policies.
+#[derive(Debug, Clone, Copy)]
+enum Policy {
+    AnonymousLists,
+    Sessions,
+    UnverifiedAccounts,
+}
+
+const POLICIES: [Policy; 3] = [
+    Policy::AnonymousLists,
+    Policy::Sessions,
+    Policy::UnverifiedAccounts,
+];
+
+impl Policy {
+    /// What the policy deletes, for logs and the admin page.
+    fn name(self) -> &'static str {
+        match self {
+            Policy::AnonymousLists => "anonymous lists",
+            Policy::Sessions => "sessions",
+            Policy::UnverifiedAccounts => "unverified accounts",
+        }
+    }
+
+    /// The configured age, for the admin page, or `None` if the policy is off.
+    fn setting(self, config: &RetentionConfig) -> Option<String> {
+        match self {
+            Policy::AnonymousLists => match config.anonymous_list_max_idle_months {
+                0 => None,
+                months => Some(format!("untouched for {} months", months)),
+            },
+            Policy::Sessions => match config.session_max_age_days {
+                0 => None,
+                days => Some(format!("older than {} days", days)),
+            },
+            Policy::UnverifiedAccounts => match config.unverified_account_max_age_days {
+                0 => None,
+                days => Some(format!("unverified after {} days", days)),
+            },
+        }
+    }
+
+    /// Anything older than this is deleted, or `None` if the policy is off.
+    fn cutoff(self, config: &RetentionConfig) -> Option<chrono::NaiveDateTime> {
+        let now = Utc::now().naive_utc();
+        match self {
+            Policy::AnonymousLists => match config.anonymous_list_max_idle_months {
+                0 => None,
+                months => now.checked_sub_months(Months::new(months)),
+            },
+            Policy::Sessions => match config.session_max_age_days {
+                0 => None,
+                days => now.checked_sub_days(Days::new(days)),
+            },
+            Policy::UnverifiedAccounts => match config.unverified_account_max_age_days {
+                0 => None,
+                days => now.checked_sub_days(Days::new(days)),
+            },
+        }
+    }
+
+    async fn count(
+        self,
+        pool: &sqlx::AnyPool,
+        cutoff: chrono::NaiveDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        match self {
+            Policy::AnonymousLists => List::count_anonymous_idle(pool, cutoff).await,
+            Policy::Sessions => UserSession::count_created_before(pool, cutoff).await,
+            Policy::UnverifiedAccounts => User::count_unverified_before(pool, cutoff).await,
+        }
+    }
+
+    async fn apply(
+        self,
+        pool: &sqlx::AnyPool,
+        cutoff: chrono::NaiveDateTime,
+    ) -> Result<u64, sqlx::Error> {
+        match self {
+            Policy::AnonymousLists => List::destroy_anonymous_idle(pool, cutoff).await,
+            Policy::Sessions => UserSession::destroy_created_before(pool, cutoff).await,
+            Policy::UnverifiedAccounts => User::destroy_unverified_before(pool, cutoff).await,
+        }
+    }
+}
+
+/// What a policy would delete if it ran now, for /admin/retention.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PolicyReport {
+    pub name: &'static str,
+    /// The configured age, `None` if the policy is off.
+    pub setting: Option<String>,
+    pub cutoff: Option<chrono::NaiveDateTime>,
+    /// How many would be deleted, 0 if the policy is off.
+    pub count: i64,
+}
+
+/// Counts what each policy would delete if it ran now, without deleting anything.
+pub async fn report(
+    pool: &sqlx::AnyPool,
+    config: &RetentionConfig,
+) -> Result<Vec<PolicyReport>, sqlx::Error> {
+    let mut reports = Vec::with_capacity(POLICIES.len());
+    for policy in POLICIES {
+        let cutoff = policy.cutoff(config);
+        let count = match cutoff {
+            Some(cutoff) => policy.count(pool, cutoff).await?,
+            None => 0,
+        };
+        reports.push(PolicyReport {
+            name: policy.name(),
+            setting: policy.setting(config),
+            cutoff,
+            count,
+        });
+    }
+    Ok(reports)
+}
+
+/// Periodically applies the policies that are on, or logs what they would delete with
+/// `dry_run` on.
+pub fn spawn(pool: sqlx::AnyPool, config: &RetentionConfig) {
+    if POLICIES.iter().all(|policy| policy.cutoff(config).is_none()) {
+        return;
+    }
+
+    let config = config.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            ticker.tick().await;
+            for policy in POLICIES {
+                let cutoff = match policy.cutoff(&config) {
+                    Some(cutoff) => cutoff,
+                    None => continue,
+                };
+                if config.dry_run {
+                    match policy.count(&pool, cutoff).await {
+                        Ok(0) => {}
+                        Ok(count) => info!(
+                            "Retention dry run: would delete {} {}",
+                            count,
+                            policy.name()
+                        ),
+                        Err(e) => error!("Counting old {} failed: {}", policy.name(), e),
+                    }
+                    continue;
+                }
+                match policy.apply(&pool, cutoff).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Deleted {} old {}", deleted, policy.name()),
+                    Err(e) => error!("Deleting old {} failed: {}", policy.name(), e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+use rocket::serde::json::json;
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::{JobsConfig, Notifier};
+use crate::db::models::{ActiveSavedSearch, Delivery, NewItem, SavedSearch};
+use crate::db::DataError;
+use crate::fuzzy;
+
+/// How many new items are checked against each saved search per run. Any more are checked on
+/// the next run.
+const BATCH_SIZE: i64 = 1000;
+
+/// How many matches are listed in each email.
+const MAX_LISTED: usize = 20;
+
+/// Periodically checks items added since the last run against users' saved searches, and emails
+/// users about new matches.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig, notifier: Notifier) {
+    if config.saved_search_interval_secs == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.saved_search_interval_secs);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = check_searches(&pool, &notifier).await {
+                error!("Checking saved searches failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn check_searches(pool: &sqlx::AnyPool, notifier: &Notifier) -> Result<(), DataError> {
+    let latest_id = NewItem::latest_id(pool).await?;
+
+    for search in SavedSearch::all_active(pool).await? {
+        if search.last_item_id >= latest_id {
+            continue;
+        }
+
+        let items =
+            NewItem::all_after(pool, search.last_item_id, search.user_id, BATCH_SIZE).await?;
+        let checked_to = match items.last() {
+            Some(item) if items.len() as i64 == BATCH_SIZE => item.id,
+            Some(item) => item.id.max(latest_id),
+            None => latest_id,
+        };
+
+        let query_words = fuzzy::words(&search.query);
+        let matches = items
+            .iter()
+            .filter(|item| is_match(&search, &query_words, item))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            SavedSearch::mark_checked(pool, search.id, checked_to, false).await?;
+            continue;
+        }
+
+        // Leave the search where it was if the email can't be queued, so it's retried on the
+        // next run. Once it's queued, the notification worker retries sending it.
+        match notify(pool, notifier, &search, &matches).await {
+            Ok(()) => SavedSearch::mark_checked(pool, search.id, checked_to, true).await?,
+            Err(e) => warn!("Failed to queue saved search alert {}: {}", search.id, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the item matches the search. Items without a known price don't match searches with a
+/// price range.
+fn is_match(search: &ActiveSavedSearch, query_words: &[String], item: &NewItem) -> bool {
+    if search.min_price_cents.is_some() || search.max_price_cents.is_some() {
+        let price = match (&item.price_cents, &item.price_currency) {
+            (Some(price), Some(currency)) if Some(currency) == search.currency.as_ref() => *price,
+            _ => return false,
+        };
+        if search.min_price_cents.is_some_and(|min| price < min)
+            || search.max_price_cents.is_some_and(|max| price > max)
+        {
+            return false;
+        }
+    }
+
+    let words = fuzzy::words(&format!("{} {}", item.title, item.description));
+    fuzzy::score(query_words, &words) > 0.0
+}
+
+async fn notify(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    search: &ActiveSavedSearch,
+    matches: &[&NewItem],
+) -> Result<(), sqlx::Error> {
+    let items: Vec<_> = matches
+        .iter()
+        .take(MAX_LISTED)
+        .map(|item| {
+            let link = notifier
+                .site
+                .url(&uri!(crate::web::items::show(item.list_key.as_str(), item.id)).to_string());
+            json!({ "title": item.title, "link": link })
+        })
+        .collect();
+    let context = json!({
+        "query": search.query,
+        "items": items,
+        "more": matches.len().saturating_sub(MAX_LISTED),
+        "manage_link": notifier
+            .site
+            .url(&uri!(crate::web::saved_searches::index).to_string()),
+    });
+
+    Delivery::enqueue_email(pool, &search.user_email, "saved_search_matches", &context).await
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::JobsConfig;
+use crate::db::models::SearchTask;
+use crate::db::DataError;
+
+/// How many tasks are worked through each time the indexer wakes up.
+const BATCH_SIZE: i64 = 500;
+
+/// Periodically indexes lists and items that changed since the last run.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig) {
+    if config.search_index_interval_secs == 0 {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.search_index_interval_secs);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = index_changes(&pool).await {
+                error!("Search indexing failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn index_changes(pool: &sqlx::AnyPool) -> Result<(), DataError> {
+    loop {
+        let tasks = SearchTask::next_batch(pool, BATCH_SIZE).await?;
+        if tasks.is_empty() {
+            return Ok(());
+        }
+        for task in tasks {
+            task.run(pool).await?;
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use crate::db::models::UserSession;
+
+/// How often expired sessions are looked for.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically removes sessions that have expired. They already stop logging anyone in when
+/// they expire, this keeps them from piling up.
+pub fn spawn(pool: sqlx::AnyPool) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            match UserSession::destroy_expired(&pool).await {
+                Ok(0) => {}
+                Ok(removed) => info!("Removed {} expired sessions", removed),
+                Err(e) => error!("Removing expired sessions failed: {}", e),
+            }
+        }
+    });
+}
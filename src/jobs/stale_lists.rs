@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use chrono::{Days, Utc};
+use rocket::serde::json::json;
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::{JobsConfig, Notifier};
+use crate::db::models::{Delivery, List, StaleList};
+use crate::db::DataError;
+
+/// How often lists are checked for reminders.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically emails owners whose list has an event `stale_list_reminder_days` away but hasn't
+/// changed in `stale_list_age_days`, like a birthday list that still has last year's items. Each
+/// event date is only reminded about once.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig, notifier: Notifier) {
+    if config.stale_list_reminder_days == 0 || config.stale_list_age_days == 0 {
+        return;
+    }
+
+    let ahead = Days::new(config.stale_list_reminder_days);
+    let age_days = config.stale_list_age_days;
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(REMINDER_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = send_reminders(&pool, &notifier, ahead, age_days).await {
+                error!("Sending stale list reminders failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn send_reminders(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    ahead: Days,
+    age_days: u64,
+) -> Result<(), DataError> {
+    let now = Utc::now().naive_utc();
+    let today = now.date();
+    let until = today.checked_add_days(ahead);
+    let changed_before = now.checked_sub_days(Days::new(age_days));
+    let (until, changed_before) = match (until, changed_before) {
+        (Some(until), Some(changed_before)) => (until, changed_before),
+        _ => return Ok(()),
+    };
+
+    for list in List::all_stale(pool, today, until, changed_before).await? {
+        // Claim the reminder before sending it, so the owner is never emailed about it twice
+        if !List::mark_stale_reminded(pool, list.id, list.event_date).await? {
+            continue;
+        }
+
+        // Once it's queued, the notification worker retries sending it. If it can't be queued,
+        // the list is tried again on the next run.
+        if let Err(e) = notify(pool, notifier, &list, age_days).await {
+            warn!("Failed to queue stale list reminder {}: {}", list.id, e);
+            List::unmark_stale_reminded(pool, list.id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn notify(
+    pool: &sqlx::AnyPool,
+    notifier: &Notifier,
+    list: &StaleList,
+    age_days: u64,
+) -> Result<(), sqlx::Error> {
+    let key = list.key.as_str();
+    let context = json!({
+        "username": list.username,
+        "list_title": list.title,
+        "event_date": list.event_date.format("%B %-d, %Y").to_string(),
+        "age_days": age_days,
+        "link": notifier
+            .site
+            .url(&uri!(crate::web::lists::show(key)).to_string()),
+    });
+
+    Delivery::enqueue_email(pool, &list.user_email, "stale_list", &context).await
+}
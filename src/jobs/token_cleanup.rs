@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+use chrono::{Days, Utc};
+use rocket::tokio;
+use rocket_db_pools::sqlx;
+
+use super::JobsConfig;
+use crate::db::models::ApiToken;
+
+/// How often unused tokens are looked for.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically revokes API tokens that haven't been used for `api_token_max_idle_days`.
+pub fn spawn(pool: sqlx::AnyPool, config: &JobsConfig) {
+    if config.api_token_max_idle_days == 0 {
+        return;
+    }
+
+    let max_idle = Days::new(config.api_token_max_idle_days);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let since = match Utc::now().naive_utc().checked_sub_days(max_idle) {
+                Some(since) => since,
+                None => continue,
+            };
+            match ApiToken::destroy_unused_since(&pool, since).await {
+                Ok(0) => {}
+                Ok(revoked) => info!("Revoked {} unused API tokens", revoked),
+                Err(e) => error!("Revoking unused API tokens failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,407 @@
+// Rocket 0.5.0-rc.3's macros trip lints from newer compilers: every route exports a `uri!` macro
+// that counts as an unused import until something links to the route, and forms allow a lint
+// that's since been removed.
+#![allow(unused_imports, renamed_and_removed_lints)]
+// `uri!` rebinds each argument, which clippy takes for redundant locals, and route handlers take a
+// guard for everything they need
+#![allow(clippy::redundant_locals, clippy::too_many_arguments)]
+
+#[macro_use]
+extern crate rocket;
+
+use std::path::Path;
+
+use rocket::fairing;
+use rocket::fairing::AdHoc;
+use rocket::Rocket;
+use rocket_db_pools::Connection;
+use rocket_db_pools::Database;
+use rocket_dyn_templates::{context, Template};
+
+mod affiliate;
+mod api;
+mod calendar;
+mod config;
+mod csrf;
+mod db;
+// Tests and benchmarks seed their databases with the dev data, in any build
+#[cfg(any(debug_assertions, feature = "testing"))]
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+mod dev;
+mod directory;
+mod events;
+mod export_schema;
+mod exports;
+mod features;
+mod feeds;
+mod fuzzy;
+mod images;
+mod imports;
+mod inbound;
+mod jobs;
+mod list_import;
+mod locale;
+mod lookup;
+mod mail;
+mod markdown;
+mod matrix;
+mod notify;
+mod pagination;
+mod passkeys;
+mod passwords;
+mod plain;
+mod plugins;
+mod privacy;
+mod quotas;
+mod rate_limits;
+mod render_cache;
+mod sources;
+mod stats;
+mod surprise;
+#[cfg(any(all(test, debug_assertions), feature = "testing"))]
+pub mod testing;
+mod throttle;
+mod usernames;
+mod util;
+mod views;
+mod web;
+
+use db::models::{Item, List};
+use db::WishlistDb;
+
+//--------------------
+// Web Pages
+//--------------------
+
+#[get("/")]
+pub async fn web_index(mut db: Connection<WishlistDb>, user: Option<&'_ web::auth::LoggedInUser>) -> Template {
+    Template::render(
+        "index",
+        context! {
+            list_count: List::count(&mut db).await.unwrap_or(0),
+            item_count: Item::count(&mut db).await.unwrap_or(0),
+            user
+        },
+    )
+}
+
+async fn default_config(mut rocket: Rocket<rocket::Build>) -> fairing::Result {
+    // Make sure the Rocket.toml file exists
+    match util::ensure_file_exists(
+        Path::new("./Rocket.toml"),
+        Some(include_str!("../Rocket.template.toml")),
+    ) {
+        Ok(created) => {
+            if created {
+                // Reload the config
+                rocket = rocket.configure(rocket::Config::figment());
+            }
+            Ok(rocket)
+        }
+        Err(e) => {
+            eprintln!("Error creating Rocket.toml: {}", e);
+            Err(rocket)
+        }
+    }
+}
+
+/// The app, configured from Rocket.toml, which is made from the template if there isn't one.
+pub fn rocket() -> Rocket<rocket::Build> {
+    let figment = rocket::Config::figment();
+
+    app(rocket::custom(figment).attach(AdHoc::try_on_ignite("Default Config", default_config)))
+}
+
+/// Attaches everything the app is made of. Tests start it with their own config, without a
+/// Rocket.toml, see `testing`.
+pub fn app(rocket: Rocket<rocket::Build>) -> Rocket<rocket::Build> {
+    let rocket = rocket
+        .attach(AdHoc::try_on_ignite("Config", config::init))
+        .attach(AdHoc::try_on_ignite("Site URL", util::init_site_url))
+        .attach(AdHoc::try_on_ignite("Keys", util::init_keys))
+        .attach(AdHoc::try_on_ignite("Default DB", db::default_db))
+        .attach(WishlistDb::init())
+        .attach(AdHoc::try_on_ignite("Migrations", db::run_migrations))
+        .attach(AdHoc::try_on_ignite("Feature Flags", features::init))
+        .attach(AdHoc::try_on_ignite("Mailer", mail::init))
+        .attach(AdHoc::try_on_ignite("Plugins", plugins::init))
+        .attach(AdHoc::try_on_ignite("Notifications", notify::init))
+        .attach(AdHoc::try_on_ignite("Password Checker", passwords::init))
+        .attach(AdHoc::try_on_ignite("Passkeys", passkeys::init))
+        .attach(AdHoc::try_on_ignite("Directory", directory::init))
+        .attach(AdHoc::try_on_ignite("Username Policy", usernames::init))
+        .attach(AdHoc::try_on_ignite("Images", images::init))
+        .attach(AdHoc::try_on_ignite("Product Lookup", lookup::init))
+        .attach(AdHoc::try_on_ignite("Item Sources", sources::init))
+        .attach(AdHoc::try_on_ignite("Affiliate Links", affiliate::init))
+        .attach(AdHoc::try_on_ignite("User Stats", stats::init))
+        .attach(AdHoc::try_on_ignite("Render Cache", render_cache::init))
+        .attach(AdHoc::try_on_ignite("Quotas", quotas::init))
+        .attach(AdHoc::try_on_ignite("List Throttle", throttle::init))
+        .attach(AdHoc::try_on_ignite("Rate Limits", rate_limits::init))
+        .attach(AdHoc::try_on_ignite("API Limits", api::access::init))
+        .attach(AdHoc::try_on_ignite("Privacy", privacy::init))
+        .attach(AdHoc::try_on_ignite("Account Exports", exports::init))
+        .attach(AdHoc::try_on_ignite("Inbound Email", inbound::init))
+        .attach(plain::PlainHtml)
+        .attach(csrf::Csrf)
+        .attach(jobs::fairing())
+        .attach(rate_limits::persistence())
+        .attach(Template::custom(|engines| {
+            engines
+                .handlebars
+                .register_helper("markdown", Box::new(markdown::helper));
+            engines
+                .handlebars
+                .register_helper("csrf_field", Box::new(csrf::field_helper));
+            engines
+                .handlebars
+                .register_helper("csrf_meta", Box::new(csrf::meta_helper));
+        }))
+        .register(
+            "/api",
+            catchers![
+                api::access::unauthorized,
+                api::access::forbidden,
+                api::access::too_many_requests
+            ],
+        )
+        .register("/", catchers![web::list_passwords::locked, csrf::forbidden])
+        .mount(
+            "/",
+            routes![
+                // Web Misc
+                web_index,
+                // Web Lists
+                web::lists::index,
+                web::lists::new,
+                web::lists::create,
+                web::lists::show,
+                web::lists::price_drops,
+                web::lists::feed,
+                web::lists::history,
+                web::lists::edit,
+                web::lists::edit_2,
+                web::lists::update,
+                web::lists::reveal,
+                web::lists::event_date,
+                web::lists::destroy,
+                // Web List Export
+                web::list_export::json,
+                web::list_export::csv,
+                web::list_export::schema,
+                // Web List Import
+                web::list_import::new,
+                web::list_import::create,
+                // Web List Webhooks
+                web::webhooks::create,
+                web::webhooks::destroy,
+                // Web List Shares
+                web::shares::open,
+                web::shares::create,
+                web::shares::destroy,
+                // Web List Passwords
+                web::list_passwords::unlock,
+                web::list_passwords::update,
+                // Web Items
+                web::items::index,
+                web::items::new,
+                web::items::create,
+                web::items::show,
+                web::items::edit,
+                web::items::update,
+                web::items::destroy,
+                web::items::received,
+                web::items::out,
+                web::items::fund,
+                web::items::contribute,
+                // Web Claims
+                web::claims::claim,
+                web::claims::unclaim,
+                web::claims::purchased,
+                web::claims::history,
+                // Web Images
+                web::images::show,
+                web::images::create,
+                web::images::destroy,
+                // Web Live Updates
+                web::live::show,
+                // Web Quick Add
+                web::quick::new,
+                web::quick::create,
+                // Web Search
+                web::search::index,
+                // Web Saved Searches
+                web::saved_searches::index,
+                web::saved_searches::create,
+                web::saved_searches::destroy,
+                // Web Price Alerts
+                web::price_alerts::create,
+                web::price_alerts::destroy,
+                // Web Notifications
+                web::notifications::watch,
+                web::notifications::unwatch,
+                web::notifications::edit,
+                web::notifications::update,
+                web::notifications::unsubscribe,
+                web::notifications::do_unsubscribe,
+                web::gift_splits::show,
+                web::gift_splits::create,
+                web::gift_splits::destroy,
+                web::gift_splits::join,
+                web::gift_splits::leave,
+                web::gift_splits::paid,
+                web::date_polls::show,
+                web::date_polls::create,
+                web::date_polls::vote,
+                web::date_polls::choose,
+                web::date_polls::destroy,
+                // Web Account
+                web::account::show,
+                web::account::show_2,
+                web::account::stats,
+                web::account::export,
+                web::account::create_export,
+                web::account::download_export,
+                web::account::import,
+                web::account::do_import,
+                web::account::email,
+                web::account::change_email,
+                web::account::cancel_email_change,
+                web::account::claim_reminders,
+                web::account::confirm_email,
+                web::account::resend_verification,
+                web::account::verify_email,
+                web::account::forgot_password,
+                web::account::do_forgot_password,
+                web::account::reset_password,
+                web::account::do_reset_password,
+                web::account::username,
+                web::account::change_username,
+                web::account::matrix,
+                web::account::link_matrix,
+                web::account::inbound,
+                web::account::create_inbound,
+                web::account::destroy_inbound,
+                web::account::fund,
+                web::account::set_fund,
+                web::account::lists_privacy,
+                web::account::change_lists_privacy,
+                // Web Privacy
+                web::privacy::show,
+                web::privacy::consent,
+                // Web Display
+                web::display::show,
+                web::display::update,
+                // Web Users
+                web::users::show,
+                web::account::new,
+                web::account::new_2,
+                web::account::create,
+                web::account::create_2,
+                web::account::login,
+                web::account::login_2,
+                web::account::do_login,
+                web::account::do_login_2,
+                web::account::logout,
+                web::account::logout_suspended,
+                web::account::logout_2,
+                web::account::suspended,
+                web::account::suspended_2,
+                web::account::appeal,
+                web::account::appeal_2,
+                web::account::sessions,
+                web::account::destroy_session,
+                web::account::revoke_session,
+                web::account::do_revoke_session,
+                // Web Passkeys
+                web::passkeys::index,
+                web::passkeys::register_start,
+                web::passkeys::register_finish,
+                web::passkeys::destroy,
+                web::passkeys::login_start,
+                web::passkeys::login_finish,
+                // Web API Tokens
+                web::api_tokens::index,
+                web::api_tokens::create,
+                web::api_tokens::destroy,
+                // Web Admin
+                web::admin::users,
+                web::admin::suspend,
+                web::admin::unsuspend,
+                web::admin::assign_role,
+                web::admin::unassign_role,
+                web::admin::exempt_from_quotas,
+                web::admin::apply_quotas,
+                web::admin::unsuppress_email,
+                web::admin::roles,
+                web::admin::create_role,
+                web::admin::update_role,
+                web::admin::destroy_role,
+                web::admin::search,
+                web::admin::reindex,
+                web::admin::retention,
+                web::admin::audit,
+                web::admin::claims,
+                web::admin::features,
+                web::admin::set_feature,
+                // API Hooks
+                api::v1::hooks::subscribe,
+                api::v1::hooks::unsubscribe,
+                // API Inbound Email
+                api::v1::inbound::email,
+                api::v1::inbound::bounces,
+                // API Lists
+                api::v1::lists::index,
+                api::v1::lists::create,
+                api::v1::lists::challenge,
+                api::v1::lists::show,
+                api::v1::lists::update,
+                api::v1::lists::destroy,
+                // API Items
+                api::v1::items::index,
+                api::v1::items::show,
+                api::v1::items::create,
+                api::v1::items::update,
+                api::v1::items::reorder,
+                api::v1::items::destroy,
+                api::v1::items::import,
+                api::v1::items::claim_status,
+                api::v1::items::claim,
+                api::v1::items::unclaim,
+                // API Lookup
+                api::v1::lookup::barcode,
+                // API Me
+                api::v1::me::stats,
+                api::v1::me::claims_calendar,
+                api::v1::me::change_lists_privacy,
+                // API Metrics
+                api::v1::metrics::show,
+                // API Passwords
+                api::v1::passwords::strength,
+                // API Scrape
+                api::v1::scrape::fetch,
+                // API Search
+                api::v1::search::index,
+                // API Triggers
+                api::v1::triggers::new_items,
+                // API Uploads
+                api::v1::uploads::create,
+                api::v1::uploads::show,
+                api::v1::uploads::append,
+                api::v1::uploads::destroy,
+                // API Webhooks
+                api::v1::webhooks::index,
+                api::v1::webhooks::create,
+                api::v1::webhooks::destroy,
+            ],
+        )
+        // API Docs
+        .mount("/", api::v1::openapi::docs());
+
+    // Only in debug builds, the fairing also checks for the debug profile
+    #[cfg(debug_assertions)]
+    let rocket = rocket.attach(AdHoc::try_on_ignite("Dev Tools", dev::init));
+
+    rocket
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use rocket::serde::json::{serde_json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use thiserror::Error;
+
+use crate::db::models::{Item, ItemKind, ItemPriority, List};
+use crate::db::{DataError, WishlistDb};
+use crate::export_schema::Schema;
+use crate::plugins::PluginRegistry;
+use crate::quotas::Quotas;
+
+/// The most items a single file can add.
+pub const MAX_ROWS: usize = 1000;
+
+/// The largest file that can be imported, in bytes.
+pub const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ListImportError {
+    #[error("Not a valid JSON file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Doesn't match the export schema: {0}")]
+    Schema(String),
+    #[error("Not a valid CSV file: {0}")]
+    Csv(String),
+    #[error("Not a valid {0} file: {1}")]
+    Importer(&'static str, String),
+    #[error("Files can have at most {} items", MAX_ROWS)]
+    TooManyRows,
+    #[error("{0}")]
+    Data(#[from] DataError),
+}
+
+/// An item in an imported file. Uses the same fields as a list export, see
+/// `crate::web::list_export` and its schema in `crate::export_schema`. Anything else, like photo
+/// links, is ignored. Plugins' importers
+/// read their formats into these too, see `crate::plugins::Importer`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ImportRow {
+    pub title: String,
+    pub description: String,
+    pub url: Option<String>,
+    pub kind: Option<String>,
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub price_cents: Option<i64>,
+    pub price_max_cents: Option<i64>,
+    pub price_currency: Option<String>,
+    pub quantity: Option<i64>,
+    pub priority: Option<String>,
+}
+
+/// What an import added, or why it didn't.
+#[derive(Serialize, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct ListImportReport {
+    /// How many items were added. Nothing is added if any row has an error.
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// A row that couldn't be imported.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct RowError {
+    /// The row's position in the file, counting from 1 and not counting a CSV header.
+    pub row: usize,
+    pub title: String,
+    pub message: String,
+}
+
+/// Adds the items in a CSV or JSON file, or one a plugin can read, to the list, returning what
+/// was added. Plugins' importers are tried first, then JSON is told apart from CSV by its first
+/// character.
+///
+/// Every row is tried, so all of a file's errors are reported at once. The caller should only
+/// commit the connection's transaction if there weren't any, so a file is imported whole or not
+/// at all.
+pub async fn import_items(
+    conn: &mut Connection<WishlistDb>,
+    quotas: &Quotas,
+    plugins: &PluginRegistry,
+    list: &List,
+    data: &[u8],
+) -> Result<ListImportReport, ListImportError> {
+    if let Some(importer) = plugins.importer_for(data) {
+        let rows = importer
+            .parse(data)
+            .map_err(|e| ListImportError::Importer(importer.name(), e))?;
+        return add_rows(conn, quotas, list, rows.into_iter().map(Ok).collect()).await;
+    }
+
+    let is_json = data
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'{' || *byte == b'[');
+    let rows = if is_json {
+        json_rows(data)?
+    } else {
+        csv_rows(data)?
+    };
+    add_rows(conn, quotas, list, rows).await
+}
+
+/// Adds the rows to the list. Rows that couldn't be read are passed as errors, so they're
+/// reported with the rest.
+async fn add_rows(
+    conn: &mut Connection<WishlistDb>,
+    quotas: &Quotas,
+    list: &List,
+    rows: Vec<Result<ImportRow, String>>,
+) -> Result<ListImportReport, ListImportError> {
+    if rows.len() > MAX_ROWS {
+        return Err(ListImportError::TooManyRows);
+    }
+
+    let mut report = ListImportReport::default();
+    for (n, row) in rows.into_iter().enumerate() {
+        let row_error = |title: &str, message: String| RowError {
+            row: n + 1,
+            title: title.to_string(),
+            message,
+        };
+
+        let row = match row {
+            Ok(row) => row,
+            Err(message) => {
+                report.errors.push(row_error("", message));
+                continue;
+            }
+        };
+        let title = row.title.clone();
+        let item = match row_item(list.id, row) {
+            Ok(item) => item,
+            Err(message) => {
+                report.errors.push(row_error(&title, message));
+                continue;
+            }
+        };
+
+        if let Some(message) = quotas.check_item(conn, list).await? {
+            report.errors.push(row_error(&title, message));
+            break;
+        }
+        match item.save(conn, list.user_id).await {
+            Ok(_) => report.imported += 1,
+            Err(DataError::Validation(e)) => report.errors.push(row_error(&title, e.to_string())),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if !report.errors.is_empty() {
+        report.imported = 0;
+    }
+    Ok(report)
+}
+
+/// Turns a row into a new item on the list, without saving it. Kinds and priorities are the
+/// names used in exports, e.g. `gift_card` and `must_have`.
+fn row_item(list_id: i64, row: ImportRow) -> Result<Item, String> {
+    let given = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+
+    let kind = match given(row.kind) {
+        Some(kind) => ItemKind::parse(kind.trim()).ok_or("Unknown item kind")?,
+        None => ItemKind::Physical,
+    };
+    let priority = match given(row.priority) {
+        Some(priority) => ItemPriority::parse(priority.trim()).ok_or("Unknown priority")?,
+        None => ItemPriority::Normal,
+    };
+
+    if let (Some(low), Some(high)) = (row.price_cents, row.price_max_cents) {
+        if high <= low {
+            return Err("price_max_cents must be more than price_cents".to_string());
+        }
+    }
+
+    let mut item = Item::new(list_id, row.title, row.description, given(row.url));
+    item.set_kind(kind, row.amount_cents, given(row.currency).as_deref());
+    item.set_details(
+        row.price_cents,
+        row.price_max_cents,
+        given(row.price_currency).as_deref(),
+        row.quantity.unwrap_or(1),
+        priority,
+    );
+    Ok(item)
+}
+
+/// Reads the rows of a JSON file, either a whole list export or just its items. The file is
+/// checked against the published schemas first. Items that don't match are returned as errors,
+/// anything else that doesn't match fails the whole file.
+fn json_rows(data: &[u8]) -> Result<Vec<Result<ImportRow, String>>, ListImportError> {
+    let items = match serde_json::from_slice(data)? {
+        Value::Array(items) => items,
+        file => {
+            // Items are checked one at a time below, so their errors are reported with their rows
+            let error = Schema::Export
+                .errors(&file)
+                .into_iter()
+                .find(|error| !error.path.starts_with("/items/"));
+            if let Some(error) = error {
+                return Err(ListImportError::Schema(error.to_string()));
+            }
+            match file {
+                Value::Object(mut file) => match file.remove("items") {
+                    Some(Value::Array(items)) => items,
+                    _ => vec![],
+                },
+                _ => vec![],
+            }
+        }
+    };
+
+    let rows = items
+        .into_iter()
+        .map(|item| {
+            let errors = Schema::Item.errors(&item);
+            if !errors.is_empty() {
+                let errors = errors.iter().map(|error| error.to_string());
+                return Err(errors.collect::<Vec<_>>().join(", "));
+            }
+            serde_json::from_value(item).map_err(|e| e.to_string())
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Reads the rows of a CSV file with a header, matching columns by name. Rows whose numbers
+/// can't be read are returned as errors.
+fn csv_rows(data: &[u8]) -> Result<Vec<Result<ImportRow, String>>, ListImportError> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| ListImportError::Csv("it must be UTF-8".to_string()))?;
+    let mut records = parse_csv(text.trim_start_matches('\u{feff}'))?.into_iter();
+
+    let columns = match records.next() {
+        Some(header) => header
+            .into_iter()
+            .enumerate()
+            .map(|(n, name)| (name.trim().to_lowercase(), n))
+            .collect::<HashMap<_, _>>(),
+        None => return Ok(vec![]),
+    };
+    if !columns.contains_key("title") {
+        return Err(ListImportError::Csv(
+            "it needs a header row with a title column".to_string(),
+        ));
+    }
+
+    let rows = records
+        // Spreadsheets often save trailing blank lines
+        .filter(|record| record.iter().any(|field| !field.is_empty()))
+        .map(|record| {
+            let field = |name: &str| {
+                columns
+                    .get(name)
+                    .and_then(|n| record.get(*n))
+                    .map(|value| unescape_formula(value))
+                    .filter(|value| !value.is_empty())
+            };
+            let number = |name: &str| match field(name) {
+                Some(value) => value
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| format!("{} must be a whole number", name)),
+                None => Ok(None),
+            };
+
+            Ok(ImportRow {
+                title: field("title").unwrap_or_default(),
+                description: field("description").unwrap_or_default(),
+                url: field("url"),
+                kind: field("kind"),
+                amount_cents: number("amount_cents")?,
+                currency: field("currency"),
+                price_cents: number("price_cents")?,
+                price_max_cents: number("price_max_cents")?,
+                price_currency: field("price_currency"),
+                quantity: number("quantity")?,
+                priority: field("priority"),
+            })
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Takes off the apostrophe exports put before text a spreadsheet would run as a formula.
+fn unescape_formula(value: &str) -> String {
+    match value.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(['=', '+', '-', '@', '\t', '\r']) => rest.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// Splits CSV text into records of fields, as described by RFC 4180. Quoted fields can contain
+/// commas, line breaks and doubled quotes.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, ListImportError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(ListImportError::Csv(
+            "a quoted field isn't closed".to_string(),
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+
+use chrono::NaiveDateTime;
+use validator::ValidationError;
+
+use crate::util;
+
+/// Languages written right to left.
+static RTL_LANGUAGES: &[&str] = &["ar", "ckb", "dv", "fa", "he", "ps", "sd", "ug", "ur", "yi"];
+
+/// Formatting rules for a list's language. Only the primary language subtag is used, so
+/// `pt-BR` formats the same as `pt`.
+pub struct Locale<'a> {
+    tag: &'a str,
+}
+
+impl<'a> Locale<'a> {
+    /// Creates a locale for the given language tag, or English if there isn't one.
+    pub fn new(tag: Option<&'a str>) -> Locale<'a> {
+        Locale {
+            tag: tag.unwrap_or("en"),
+        }
+    }
+
+    /// The language tag, for the HTML `lang` attribute.
+    pub fn tag(&self) -> &str {
+        self.tag
+    }
+
+    fn language(&self) -> String {
+        self.tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    }
+
+    /// Whether the language is written right to left.
+    pub fn is_rtl(&self) -> bool {
+        RTL_LANGUAGES.contains(&self.language().as_str())
+    }
+
+    /// The value for the HTML `dir` attribute.
+    pub fn dir(&self) -> &'static str {
+        if self.is_rtl() {
+            "rtl"
+        } else {
+            "ltr"
+        }
+    }
+
+    /// Formats a date the way it's usually written in the language.
+    pub fn format_date(&self, date: NaiveDateTime) -> String {
+        // Plain "en" is treated as US English, other English regions put the day first
+        let is_us = self.tag.eq_ignore_ascii_case("en") || self.tag.eq_ignore_ascii_case("en-US");
+        let format = match self.language().as_str() {
+            "en" if is_us => "%m/%d/%Y",
+            "de" | "fi" | "nb" | "no" | "pl" | "ru" | "tr" | "uk" | "cs" => "%d.%m.%Y",
+            "ja" | "ko" | "lt" | "sv" | "zh" => "%Y-%m-%d",
+            "nl" => "%d-%m-%Y",
+            _ => "%d/%m/%Y",
+        };
+        date.format(format).to_string()
+    }
+
+    /// Formats a price in the smallest unit of its currency, using the language's decimal and
+    /// thousands separators.
+    pub fn format_price(&self, amount_cents: i64, currency: &str) -> String {
+        let (decimal, thousands) = match self.language().as_str() {
+            "de" | "es" | "id" | "it" | "nl" | "pt" | "tr" | "da" | "el" => (',', '.'),
+            "cs" | "fi" | "fr" | "nb" | "no" | "pl" | "ru" | "sv" | "uk" => (',', '\u{a0}'),
+            _ => ('.', ','),
+        };
+
+        // Currencies without a minor unit, like JPY, are stored in whole units
+        let zero_decimal = util::currency_decimals(currency) == 0;
+        let amount = amount_cents.unsigned_abs();
+        let whole = if zero_decimal { amount } else { amount / 100 }.to_string();
+        let mut grouped = String::new();
+        for (i, c) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push(thousands);
+            }
+            grouped.push(c);
+        }
+
+        let sign = if amount_cents < 0 { "-" } else { "" };
+        if zero_decimal {
+            return format!("{}{} {}", sign, grouped, currency);
+        }
+        format!("{}{}{}{:02} {}", sign, grouped, decimal, amount % 100, currency)
+    }
+}
+
+/// Checks that the value looks like a BCP 47 language tag, e.g. `he` or `pt-BR`.
+pub fn validate_language(tag: &str) -> Result<(), ValidationError> {
+    let mut subtags = tag.split('-');
+    let valid = subtags
+        .next()
+        .map(|language| {
+            (2..=3).contains(&language.len()) && language.chars().all(|c| c.is_ascii_alphabetic())
+        })
+        .unwrap_or(false)
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        let mut err = ValidationError::new("language");
+        err.message = Some(Cow::from("Language must be a language code like \"en\" or \"pt-BR\""));
+        Err(err)
+    }
+}
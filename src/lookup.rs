@@ -0,0 +1,232 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+use thiserror::Error;
+use validator::ValidationError;
+
+use crate::config::AppConfig;
+
+/// Product lookup configuration, read from the `lookup` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct LookupConfig {
+    /// The OpenLibrary instance used to look up books by ISBN.
+    pub openlibrary_url: String,
+    /// The OpenLibrary covers instance used for book images.
+    pub openlibrary_covers_url: String,
+    /// A UPCitemdb-compatible API used to look up other products. Only books can be looked up
+    /// if this is unset.
+    pub upc_api_url: Option<String>,
+    /// How long to wait for a product data source, in seconds.
+    pub timeout_secs: u64,
+}
+
+impl Default for LookupConfig {
+    fn default() -> Self {
+        Self {
+            openlibrary_url: "https://openlibrary.org".to_string(),
+            openlibrary_covers_url: "https://covers.openlibrary.org".to_string(),
+            upc_api_url: None,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Product details found for a barcode, used to prefill a new item.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ProductInfo {
+    pub barcode: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub price: Option<f64>,
+    /// The data source the details came from.
+    pub source: &'static str,
+}
+
+#[derive(Error, Debug)]
+pub enum LookupError {
+    #[error("Product data source request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// A normalized barcode.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Barcode {
+    /// A book, as a 13 digit ISBN.
+    Isbn(String),
+    /// Any other product, as an 8, 12 or 13 digit EAN/UPC.
+    Ean(String),
+}
+
+impl Barcode {
+    /// Parses and checksums an EAN-8, UPC-A, EAN-13 or ISBN-10 code. Spaces and dashes are
+    /// ignored.
+    pub fn parse(code: &str) -> Result<Barcode, ValidationError> {
+        let code = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_ascii_uppercase();
+
+        if code.len() == 10 && is_valid_isbn10(&code) {
+            // Convert to ISBN-13 so books are always looked up the same way
+            let isbn = format!("978{}", &code[..9]);
+            let check = ean_check_digit(&isbn);
+            return Ok(Barcode::Isbn(format!("{}{}", isbn, check)));
+        }
+
+        if matches!(code.len(), 8 | 12 | 13)
+            && code.chars().all(|c| c.is_ascii_digit())
+            && ean_check_digit(&code[..code.len() - 1]) == code.as_bytes()[code.len() - 1] - b'0'
+        {
+            if code.len() == 13 && (code.starts_with("978") || code.starts_with("979")) {
+                return Ok(Barcode::Isbn(code));
+            }
+            return Ok(Barcode::Ean(code));
+        }
+
+        let mut err = ValidationError::new("barcode");
+        err.message = Some(Cow::from("Not a valid EAN, UPC or ISBN"));
+        Err(err)
+    }
+}
+
+/// Computes the EAN/UPC check digit for the given digits (without the check digit).
+fn ean_check_digit(digits: &str) -> u8 {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| (d - b'0') as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+fn is_valid_isbn10(code: &str) -> bool {
+    let mut sum = 0;
+    for (i, c) in code.chars().enumerate() {
+        let value = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'X' if i == 9 => 10,
+            _ => return false,
+        };
+        sum += value * (10 - i as u32);
+    }
+    sum % 11 == 0
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct OpenLibraryBook {
+    title: String,
+    subtitle: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct UpcResponse {
+    #[serde(default)]
+    items: Vec<UpcItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct UpcItem {
+    title: String,
+    description: Option<String>,
+    #[serde(default)]
+    images: Vec<String>,
+    lowest_recorded_price: Option<f64>,
+}
+
+/// Looks up product details by barcode. Available as managed state.
+pub struct ProductLookup {
+    client: reqwest::Client,
+    config: LookupConfig,
+}
+
+impl ProductLookup {
+    /// Creates a new product lookup from the given config.
+    pub fn from_config(config: LookupConfig) -> Result<ProductLookup, LookupError> {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(config.timeout_secs))
+            .user_agent(concat!("wishlist-rs/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(ProductLookup { client, config })
+    }
+
+    /// Returns the product details for the barcode, or `None` if no data source knows it.
+    pub async fn lookup(&self, barcode: &Barcode) -> Result<Option<ProductInfo>, LookupError> {
+        match barcode {
+            Barcode::Isbn(isbn) => self.lookup_book(isbn).await,
+            Barcode::Ean(ean) => self.lookup_product(ean).await,
+        }
+    }
+
+    async fn lookup_book(&self, isbn: &str) -> Result<Option<ProductInfo>, LookupError> {
+        let response = self
+            .client
+            .get(format!("{}/isbn/{}.json", self.config.openlibrary_url, isbn))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let book = response.error_for_status()?.json::<OpenLibraryBook>().await?;
+        Ok(Some(ProductInfo {
+            barcode: isbn.to_string(),
+            title: book.title,
+            description: book.subtitle,
+            image_url: Some(format!(
+                "{}/b/isbn/{}-L.jpg",
+                self.config.openlibrary_covers_url, isbn
+            )),
+            price: None,
+            source: "openlibrary",
+        }))
+    }
+
+    async fn lookup_product(&self, ean: &str) -> Result<Option<ProductInfo>, LookupError> {
+        let url = match &self.config.upc_api_url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let response = self
+            .client
+            .get(url)
+            .query(&[("upc", ean)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<UpcResponse>()
+            .await?;
+
+        Ok(response.items.into_iter().next().map(|item| ProductInfo {
+            barcode: ean.to_string(),
+            title: item.title,
+            description: item.description,
+            image_url: item.images.into_iter().next(),
+            price: item.lowest_recorded_price,
+            source: "upcitemdb",
+        }))
+    }
+}
+
+/// Reads the lookup config and adds the `ProductLookup` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).lookup.clone();
+
+    match ProductLookup::from_config(config) {
+        Ok(lookup) => Ok(rocket.manage(lookup)),
+        Err(e) => {
+            error!("Failed to configure product lookup: {}", e);
+            Err(rocket)
+        }
+    }
+}
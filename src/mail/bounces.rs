@@ -0,0 +1,151 @@
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::{self, Value};
+use rocket::State;
+use sha2::{Digest, Sha256};
+
+use super::Mailer;
+use crate::db::models::EmailSuppression;
+
+/// The query parameter the provider's webhook sends the shared secret in. Providers can only be
+/// given a URL for their webhooks, not headers, so it's part of the URL.
+pub static SECRET_PARAM: &str = "secret";
+
+/// A request from the mail provider's bounce webhook, carrying the right shared secret.
+pub struct BounceHook;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BounceHook {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let mailer = match request.guard::<&State<Mailer>>().await.succeeded() {
+            Some(mailer) => mailer,
+            None => return Outcome::Forward(()),
+        };
+
+        let expected = match &mailer.bounce_secret {
+            Some(secret) => secret,
+            None => return Outcome::Forward(()),
+        };
+
+        // Compare digests so the comparison doesn't leak how much of the secret matched
+        let secret = request
+            .query_value::<&str>(SECRET_PARAM)
+            .and_then(Result::ok);
+        match secret {
+            Some(secret) if Sha256::digest(secret) == Sha256::digest(expected) => {
+                Outcome::Success(BounceHook)
+            }
+            _ => Outcome::Failure((rocket::http::Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// An address the provider says shouldn't be emailed anymore.
+#[derive(Debug)]
+pub struct BounceReport {
+    pub email: String,
+    /// Either `EmailSuppression::BOUNCE` or `EmailSuppression::COMPLAINT`.
+    pub reason: &'static str,
+    pub detail: Option<String>,
+}
+
+/// Reads the addresses to suppress from a provider's notification. Understands:
+///
+/// - Amazon SES notifications, on their own or wrapped in an SNS message
+/// - Mailgun webhook events
+/// - `{"email": "...", "reason": "bounce" | "complaint", "detail": "..."}`, for anything else
+///
+/// Only permanent bounces are reported, temporary ones are left to the outbox's retries. Anything
+/// that isn't a bounce or complaint gives no reports.
+pub fn parse(body: &Value) -> Vec<BounceReport> {
+    // SNS wraps the SES notification in a JSON string
+    if body["Type"] == "Notification" {
+        return match body["Message"].as_str().map(json::from_str::<Value>) {
+            Some(Ok(message)) => parse(&message),
+            _ => vec![],
+        };
+    }
+    if body["Type"] == "SubscriptionConfirmation" {
+        info!(
+            "Confirm the SNS subscription for bounces by opening {}",
+            body["SubscribeURL"].as_str().unwrap_or_default()
+        );
+        return vec![];
+    }
+
+    let ses_type = body["notificationType"]
+        .as_str()
+        .or_else(|| body["eventType"].as_str());
+    if let Some(kind) = ses_type {
+        return parse_ses(kind, body);
+    }
+
+    if body["event-data"].is_object() {
+        return parse_mailgun(&body["event-data"]).into_iter().collect();
+    }
+
+    let reason = match body["reason"].as_str() {
+        Some(EmailSuppression::BOUNCE) => EmailSuppression::BOUNCE,
+        Some(EmailSuppression::COMPLAINT) => EmailSuppression::COMPLAINT,
+        _ => return vec![],
+    };
+    report(&body["email"], reason, &body["detail"])
+        .into_iter()
+        .collect()
+}
+
+fn parse_ses(kind: &str, body: &Value) -> Vec<BounceReport> {
+    let (reason, recipients, detail_key) = match kind {
+        "Bounce" if body["bounce"]["bounceType"] == "Permanent" => (
+            EmailSuppression::BOUNCE,
+            &body["bounce"]["bouncedRecipients"],
+            "diagnosticCode",
+        ),
+        "Complaint" => (
+            EmailSuppression::COMPLAINT,
+            &body["complaint"]["complainedRecipients"],
+            "complaintFeedbackType",
+        ),
+        _ => return vec![],
+    };
+
+    recipients
+        .as_array()
+        .map(|recipients| {
+            recipients
+                .iter()
+                .filter_map(|recipient| {
+                    report(&recipient["emailAddress"], reason, &recipient[detail_key])
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_mailgun(event: &Value) -> Option<BounceReport> {
+    let reason = match event["event"].as_str()? {
+        "failed" if event["severity"] == "permanent" => EmailSuppression::BOUNCE,
+        "complained" => EmailSuppression::COMPLAINT,
+        _ => return None,
+    };
+    report(
+        &event["recipient"],
+        reason,
+        &event["delivery-status"]["message"],
+    )
+}
+
+fn report(email: &Value, reason: &'static str, detail: &Value) -> Option<BounceReport> {
+    let email = email.as_str()?.trim();
+    if email.is_empty() {
+        return None;
+    }
+    Some(BounceReport {
+        email: email.to_string(),
+        reason,
+        detail: detail
+            .as_str()
+            .filter(|detail| !detail.is_empty())
+            .map(|detail| detail.chars().take(1024).collect()),
+    })
+}
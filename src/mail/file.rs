@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use lettre::Message;
+use rocket::tokio::fs;
+
+use super::{MailConfig, MailError, MailTransport};
+
+/// Writes each email to its own `.eml` file instead of sending it, for development and testing.
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn from_config(config: &MailConfig) -> FileTransport {
+        FileTransport {
+            dir: PathBuf::from(&config.file_dir),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl MailTransport for FileTransport {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), MailError> {
+        fs::create_dir_all(&self.dir).await?;
+        let name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().timestamp_millis(),
+            crate::util::random_key()
+        );
+        fs::write(self.dir.join(name), message.formatted()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use lettre::Message;
+use reqwest::multipart::{Form, Part};
+
+use super::{MailConfig, MailError, MailTransport};
+
+/// Sends emails through an HTTP API that accepts raw MIME messages, like Mailgun's
+/// `messages.mime` endpoint or a compatible gateway in front of SES.
+///
+/// Each email is posted as a multipart form with a `to` field per recipient and the message
+/// itself in a `message` file field, authenticated with basic auth.
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    username: String,
+    password: Option<String>,
+}
+
+impl HttpTransport {
+    pub fn from_config(config: &MailConfig) -> Result<HttpTransport, MailError> {
+        let url = config
+            .http_url
+            .clone()
+            .ok_or_else(|| MailError::Config("http_url is required".to_string()))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .pool_max_idle_per_host(config.max_connections.max(1) as usize)
+            .build()?;
+
+        Ok(HttpTransport {
+            client,
+            url,
+            username: config.http_username.clone(),
+            password: config.http_password.clone(),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl MailTransport for HttpTransport {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), MailError> {
+        let mut form = Form::new();
+        for to in message.envelope().to() {
+            form = form.text("to", to.to_string());
+        }
+        let mime = Part::bytes(message.formatted())
+            .file_name("message.mime")
+            .mime_str("message/rfc822")?;
+        form = form.part("message", mime);
+
+        self.client
+            .post(&self.url)
+            .basic_auth(&self.username, self.password.as_ref())
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use lettre::message::{Mailbox, MultiPart};
+use lettre::Message;
+use rocket::futures::stream::{FuturesOrdered, StreamExt};
+use rocket::serde::json::Value;
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+
+pub mod bounces;
+mod file;
+mod http;
+mod sendmail;
+mod smtp;
+mod templates;
+
+pub use file::FileTransport;
+pub use http::HttpTransport;
+pub use sendmail::SendmailTransport;
+pub use smtp::SmtpTransport;
+pub use templates::{EmailTemplates, RenderedEmail};
+
+/// How long to wait before retrying an email that failed with a temporary error, multiplied by
+/// the attempt number.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Outgoing email configuration, read from the `mail` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct MailConfig {
+    /// How emails are sent: "smtp", "sendmail", "http", "file" or "log". Defaults to "smtp" if
+    /// an SMTP host is set, otherwise "log", which writes emails to the log instead.
+    pub transport: Option<String>,
+    /// The SMTP relay to send through.
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    /// The sendmail binary, which is given each email on stdin.
+    pub sendmail_command: String,
+    /// The endpoint of an HTTP email API taking raw MIME messages, like Mailgun's
+    /// `https://api.mailgun.net/v3/<domain>/messages.mime`.
+    pub http_url: Option<String>,
+    /// The username for the HTTP API's basic auth, "api" for Mailgun.
+    pub http_username: String,
+    /// The HTTP API's key.
+    pub http_password: Option<String>,
+    /// Where the "file" transport writes emails, one `.eml` file each, for development.
+    pub file_dir: String,
+    /// How many emails are sent at once, and how many SMTP connections are kept open for it.
+    pub max_connections: u32,
+    /// How many times an email that failed with a temporary error is retried right away. Emails
+    /// that still fail are retried later by the notification worker.
+    pub retries: u32,
+    /// How long to wait for the mail server or API, in seconds.
+    pub timeout_secs: u64,
+    /// Shared with the mail provider's bounce webhook, which must send it in the `secret` query
+    /// parameter. Bounce and complaint notifications are only accepted when it's set.
+    pub bounce_secret: Option<String>,
+    /// A directory of email templates that replace the built-in ones in `mail_templates`, see
+    /// `EmailTemplates`.
+    pub template_dir: Option<String>,
+    /// The address emails are sent from.
+    pub from: String,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            transport: None,
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            sendmail_command: "/usr/sbin/sendmail".to_string(),
+            http_url: None,
+            http_username: "api".to_string(),
+            http_password: None,
+            file_dir: "mail".to_string(),
+            max_connections: 4,
+            retries: 2,
+            timeout_secs: 30,
+            bounce_secret: None,
+            template_dir: None,
+            from: "Universal Wishlist <noreply@localhost>".to_string(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("Invalid email address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("Invalid email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("sendmail failed: {0}")]
+    Sendmail(String),
+    #[error("Invalid mail config: {0}")]
+    Config(String),
+    #[error("Email template error: {0}")]
+    Template(String),
+}
+
+impl MailError {
+    /// Returns true if sending again soon might work, e.g. the server was busy or unreachable.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            MailError::Smtp(e) => e.is_transient() || e.is_timeout(),
+            MailError::Http(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|status| {
+                        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            MailError::Io(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A way of sending emails.
+///
+/// Implement this to add a new backend, then add it to `Mailer::from_config`.
+#[rocket::async_trait]
+pub trait MailTransport: Send + Sync {
+    /// A short name for the backend, e.g. "smtp".
+    fn name(&self) -> &'static str;
+
+    /// Sends one email.
+    async fn send(&self, message: &Message) -> Result<(), MailError>;
+}
+
+/// Writes emails to the log, for when no way of sending them is set up.
+pub struct LogTransport;
+
+#[rocket::async_trait]
+impl MailTransport for LogTransport {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), MailError> {
+        info!("Email:\n{}", String::from_utf8_lossy(&message.formatted()));
+        Ok(())
+    }
+}
+
+/// An email to send.
+pub struct OutgoingEmail<'a> {
+    pub to: &'a str,
+    pub subject: &'a str,
+    pub text: &'a str,
+    /// An HTML version of the text, sent as an alternative to it.
+    pub html: Option<&'a str>,
+}
+
+/// Sends emails. Available as managed state once the mail fairing has run.
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Arc<dyn MailTransport>,
+    templates: Arc<EmailTemplates>,
+    from: Mailbox,
+    max_connections: usize,
+    retries: u32,
+    bounce_secret: Option<String>,
+}
+
+impl Mailer {
+    /// Creates a new mailer from the given config.
+    pub fn from_config(config: MailConfig) -> Result<Mailer, MailError> {
+        let from = config.from.parse()?;
+        let transport = match config.transport.as_deref() {
+            Some(transport) => transport,
+            None if config.smtp_host.is_some() => "smtp",
+            None => "log",
+        };
+        let transport: Arc<dyn MailTransport> = match transport {
+            "smtp" => Arc::new(SmtpTransport::from_config(&config)?),
+            "sendmail" => Arc::new(SendmailTransport::from_config(&config)),
+            "http" => Arc::new(HttpTransport::from_config(&config)?),
+            "file" => Arc::new(FileTransport::from_config(&config)),
+            "log" => Arc::new(LogTransport),
+            other => return Err(MailError::Config(format!("Unknown transport {}", other))),
+        };
+
+        Ok(Mailer {
+            transport,
+            templates: Arc::new(EmailTemplates::load(config.template_dir.as_deref())?),
+            from,
+            max_connections: config.max_connections.max(1) as usize,
+            retries: config.retries,
+            bounce_secret: config.bounce_secret,
+        })
+    }
+
+    /// Renders the named email template with the given context, see `EmailTemplates`.
+    pub fn render(&self, template: &str, context: &Value) -> Result<RenderedEmail, MailError> {
+        self.templates.render(template, context)
+    }
+
+    /// Sends an email, as plain text or, if it has an HTML version, as both.
+    pub async fn send(&self, email: &OutgoingEmail<'_>) -> Result<(), MailError> {
+        let builder = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(email.subject);
+        let message = match email.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+                email.text.to_string(),
+                html.to_string(),
+            ))?,
+            None => builder.body(email.text.to_string())?,
+        };
+
+        let mut attempt = 0;
+        loop {
+            match self.transport.send(&message).await {
+                Err(e) if e.is_transient() && attempt < self.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Retrying email to {} over {} after: {}",
+                        email.to,
+                        self.transport.name(),
+                        e
+                    );
+                    rocket::tokio::time::sleep(RETRY_DELAY * attempt).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends the emails, up to `max_connections` at once, returning each one's result in order.
+    pub async fn send_batch(&self, emails: &[OutgoingEmail<'_>]) -> Vec<Result<(), MailError>> {
+        // Queued by hand rather than with `map` and `buffered`, the compiler can't tell the
+        // closure's future is `Send` when the batch runs on the notification worker
+        let mut results = Vec::with_capacity(emails.len());
+        let mut sending = FuturesOrdered::new();
+        for email in emails {
+            if sending.len() >= self.max_connections {
+                results.extend(sending.next().await);
+            }
+            sending.push_back(self.send(email));
+        }
+        while let Some(result) = sending.next().await {
+            results.push(result);
+        }
+        results
+    }
+}
+
+/// Reads the mail config and adds the `Mailer` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).mail.clone();
+
+    match Mailer::from_config(config) {
+        Ok(mailer) => {
+            info!("Sending email over {}", mailer.transport.name());
+            Ok(rocket.manage(mailer))
+        }
+        Err(e) => {
+            error!("Failed to configure mailer: {}", e);
+            Err(rocket)
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::process::Stdio;
+
+use lettre::Message;
+use rocket::tokio::io::AsyncWriteExt;
+use rocket::tokio::process::Command;
+
+use super::{MailConfig, MailError, MailTransport};
+
+/// Sends emails by piping them to a local sendmail-compatible binary, like the one from Postfix
+/// or msmtp.
+pub struct SendmailTransport {
+    command: String,
+}
+
+impl SendmailTransport {
+    pub fn from_config(config: &MailConfig) -> SendmailTransport {
+        SendmailTransport {
+            command: config.sendmail_command.clone(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl MailTransport for SendmailTransport {
+    fn name(&self) -> &'static str {
+        "sendmail"
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), MailError> {
+        let envelope = message.envelope();
+        let mut command = Command::new(&self.command);
+        command.arg("-i");
+        if let Some(from) = envelope.from() {
+            command.arg("-f").arg(from);
+        }
+        command
+            .arg("--")
+            .args(envelope.to().iter().map(|to| to.to_string()))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        let mut child = command.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&message.formatted()).await?;
+        }
+
+        let output = child.wait_with_output().await?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(MailError::Sendmail(format!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::PoolConfig;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{MailConfig, MailError, MailTransport};
+
+/// Sends emails through an SMTP relay, keeping a pool of connections open between emails.
+pub struct SmtpTransport {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn from_config(config: &MailConfig) -> Result<SmtpTransport, MailError> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .ok_or_else(|| MailError::Config("smtp_host is required".to_string()))?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+            .timeout(Some(Duration::from_secs(config.timeout_secs)))
+            .pool_config(PoolConfig::new().max_size(config.max_connections.max(1)));
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(SmtpTransport {
+            transport: builder.build(),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl MailTransport for SmtpTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, message: &Message) -> Result<(), MailError> {
+        self.transport.send(message.clone()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::Path;
+
+use rocket::serde::json::Value;
+use rocket_dyn_templates::handlebars::{self, Handlebars};
+
+use super::MailError;
+
+/// Where the built-in email templates are, relative to the working directory like Rocket's
+/// `templates`.
+pub const TEMPLATE_DIR: &str = "mail_templates";
+
+/// An email rendered from its templates.
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    /// The HTML version, if the email has one. It's sent alongside the plain text one.
+    pub html: Option<String>,
+}
+
+/// Renders emails from Handlebars templates.
+///
+/// Each email is a set of files named after it: `<name>.subject.hbs` and `<name>.txt.hbs`, and
+/// optionally `<name>.html.hbs`. Like the site's templates, HTML templates define an inline
+/// `body` partial and render the shared `layout.html.hbs` around it. Files in the override
+/// directory replace the built-in ones with the same name, so an instance can change its emails
+/// without rebuilding.
+pub struct EmailTemplates {
+    /// Subject and plain text templates, which aren't HTML escaped.
+    text: Handlebars<'static>,
+    html: Handlebars<'static>,
+}
+
+impl EmailTemplates {
+    /// Loads the built-in templates, then any overrides.
+    pub fn load(override_dir: Option<&str>) -> Result<EmailTemplates, MailError> {
+        let mut text = Handlebars::new();
+        text.register_escape_fn(handlebars::no_escape);
+        let mut templates = EmailTemplates {
+            text,
+            html: Handlebars::new(),
+        };
+
+        templates.load_dir(Path::new(TEMPLATE_DIR))?;
+        if let Some(dir) = override_dir {
+            templates.load_dir(Path::new(dir))?;
+        }
+
+        Ok(templates)
+    }
+
+    fn load_dir(&mut self, dir: &Path) -> Result<(), MailError> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => match name.strip_suffix(".hbs") {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                },
+                None => continue,
+            };
+
+            let source = fs::read_to_string(&path)?;
+            let registered = match name.strip_suffix(".html") {
+                Some(name) => self.html.register_template_string(name, source),
+                None => self.text.register_template_string(&name, source),
+            };
+            registered.map_err(|e| MailError::Template(format!("{}: {}", path.display(), e)))?;
+        }
+        Ok(())
+    }
+
+    /// Renders the named email with the given context.
+    pub fn render(&self, name: &str, context: &Value) -> Result<RenderedEmail, MailError> {
+        let subject_name = format!("{}.subject", name);
+        let text_name = format!("{}.txt", name);
+        if !self.text.has_template(&subject_name) || !self.text.has_template(&text_name) {
+            return Err(MailError::Template(format!(
+                "Unknown email template {}",
+                name
+            )));
+        }
+
+        let subject = self.text.render(&subject_name, context)?;
+        let text = self.text.render(&text_name, context)?;
+        let html = if self.html.has_template(name) {
+            Some(self.html.render(name, context)?)
+        } else {
+            None
+        };
+
+        Ok(RenderedEmail {
+            // Subjects are one line, so ignore the file's trailing newline
+            subject: subject.trim().to_string(),
+            text,
+            html,
+        })
+    }
+}
+
+impl From<handlebars::RenderError> for MailError {
+    fn from(e: handlebars::RenderError) -> Self {
+        MailError::Template(e.to_string())
+    }
+}
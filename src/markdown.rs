@@ -0,0 +1,44 @@
+//! Markdown in list and item descriptions.
+//!
+//! Descriptions are saved as the Markdown they were written in, and only rendered to HTML on the
+//! way into a page, by the `markdown` template helper. The rendered HTML is sanitized, so
+//! descriptions can't add scripts, styles or anything else a list owner shouldn't control. The
+//! API and feeds send descriptions as they were written.
+
+use rocket_dyn_templates::handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+};
+
+/// Renders Markdown to sanitized HTML. Links open without sending a referrer and are marked
+/// `nofollow`, since anyone can write them.
+pub fn render(text: &str) -> String {
+    let options = pulldown_cmark::Options::ENABLE_STRIKETHROUGH
+        | pulldown_cmark::Options::ENABLE_TABLES
+        | pulldown_cmark::Options::ENABLE_TASKLISTS;
+    let parser = pulldown_cmark::Parser::new_ext(text, options);
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+
+    ammonia::Builder::default()
+        .link_rel(Some("nofollow noopener noreferrer"))
+        .clean(&html)
+        .to_string()
+}
+
+/// The `markdown` template helper: `{{markdown list.description}}` renders the description. Its
+/// output is already sanitized, so it isn't escaped again.
+pub fn helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let text = h
+        .param(0)
+        .and_then(|param| param.value().as_str())
+        .unwrap_or_default();
+    out.write(&render(text))?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use rocket::serde::json::{json, Value};
+use rocket::serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+/// Matrix configuration, read from the `matrix` table in Rocket.toml. Notifications are only sent
+/// to Matrix when a homeserver and access token are both set.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+#[derive(Default)]
+pub struct MatrixConfig {
+    /// The homeserver of the account notifications are sent from, e.g. `https://matrix.org`.
+    pub homeserver: Option<String>,
+    /// An access token for that account.
+    pub access_token: Option<String>,
+    /// A room that gets every notification, e.g. a family's shared room. The account must
+    /// already be a member.
+    pub room_id: Option<String>,
+}
+
+
+#[derive(Error, Debug)]
+pub enum MatrixError {
+    #[error("Invalid homeserver URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Matrix request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Unexpected response from the homeserver: {0}")]
+    Response(String),
+}
+
+/// A minimal Matrix client-server API client that can send messages as one account.
+#[derive(Clone)]
+pub struct MatrixClient {
+    client: reqwest::Client,
+    homeserver: Url,
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct CreatedRoom {
+    room_id: String,
+}
+
+impl MatrixClient {
+    /// Creates a client from the given config, or returns `None` if Matrix isn't set up.
+    pub fn from_config(
+        config: &MatrixConfig,
+        client: reqwest::Client,
+    ) -> Result<Option<MatrixClient>, MatrixError> {
+        match (&config.homeserver, &config.access_token) {
+            (Some(homeserver), Some(access_token)) => Ok(Some(MatrixClient {
+                client,
+                homeserver: Url::parse(homeserver)?,
+                access_token: access_token.clone(),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Sends a message with a plain text body and an HTML body to a room.
+    pub async fn send_message(&self, room_id: &str, text: &str, html: &str) -> Result<(), MatrixError> {
+        let txn_id = crate::util::random_token();
+        let url = self.endpoint(&["rooms", room_id, "send", "m.room.message", &txn_id])?;
+        let body = json!({
+            "msgtype": "m.notice",
+            "body": text,
+            "format": "org.matrix.custom.html",
+            "formatted_body": html,
+        });
+
+        self.request(self.client.put(url).json(&body)).await?;
+        Ok(())
+    }
+
+    /// Creates a direct message room with the given user, returning the new room's ID.
+    pub async fn create_direct_room(&self, matrix_id: &str) -> Result<String, MatrixError> {
+        let url = self.endpoint(&["createRoom"])?;
+        let body = json!({
+            "is_direct": true,
+            "invite": [matrix_id],
+            "preset": "trusted_private_chat",
+            "name": "Universal Wishlist",
+        });
+
+        let response = self.request(self.client.post(url).json(&body)).await?;
+        let room: CreatedRoom = rocket::serde::json::from_value(response)
+            .map_err(|e| MatrixError::Response(e.to_string()))?;
+        Ok(room.room_id)
+    }
+
+    // ----- Internal -----
+
+    /// Builds a client-server API URL, percent-encoding each path segment.
+    fn endpoint(&self, segments: &[&str]) -> Result<Url, MatrixError> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| MatrixError::Response("Homeserver URL can't have a path".to_string()))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn request(&self, request: reqwest::RequestBuilder) -> Result<Value, MatrixError> {
+        let response = request.bearer_auth(&self.access_token).send().await?;
+        let status = response.status();
+        let body: Value = response.json().await?;
+        if !status.is_success() {
+            return Err(MatrixError::Response(format!("{} {}", status, body)));
+        }
+        Ok(body)
+    }
+}
@@ -0,0 +1,643 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use rocket::serde::json::{self, json, Value};
+use rocket::tokio::sync::{broadcast, Notify};
+use rocket::{fairing, tokio, Build, Rocket};
+use rocket_db_pools::{sqlx, Connection, Database};
+use sha2::Sha256;
+use thiserror::Error;
+use url::Url;
+
+use crate::config::AppConfig;
+use crate::db::models::{
+    Delivery, EmailSuppression, Event, HookSubscription, List, ListWatcher, ListWebhook,
+    MatrixLink, NotificationPreferences, User,
+};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::DomainEvent;
+use crate::feeds::escape;
+use crate::mail::{MailError, Mailer, OutgoingEmail, RenderedEmail};
+use crate::matrix::{MatrixClient, MatrixConfig, MatrixError};
+use crate::plugins::PluginRegistry;
+use crate::render_cache;
+use crate::sources::{PublicClient, SourceError};
+use crate::surprise::Viewer;
+use crate::util::SiteUrl;
+
+/// How many logged events or queued deliveries the worker sends at a time.
+const BATCH_SIZE: i64 = 100;
+
+/// How often the worker looks for events it missed, e.g. ones logged before a restart.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many events live update subscribers can fall behind by before they miss some.
+const LIVE_CAPACITY: usize = 1024;
+
+/// The header webhook posts carry their signature in, see `sign`.
+const SIGNATURE_HEADER: &str = "X-Wishlist-Signature";
+
+#[derive(Error, Debug)]
+enum NotifyError {
+    #[error(transparent)]
+    Matrix(#[from] MatrixError),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Source(#[from] SourceError),
+    #[error(transparent)]
+    Mail(#[from] MailError),
+    #[error(transparent)]
+    Data(#[from] DataError),
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+}
+
+/// A notification, before it's formatted for a particular target.
+struct Message {
+    /// A short summary, e.g. "New item on Birthday".
+    summary: String,
+    /// The name of the thing the message is about.
+    subject: String,
+    /// Where to see it on the site.
+    link: String,
+}
+
+impl Message {
+    fn text(&self) -> String {
+        format!("{}: {} {}", self.summary, self.subject, self.link)
+    }
+
+    fn html(&self) -> String {
+        format!(
+            "{}: <a href=\"{}\">{}</a>",
+            escape(&self.summary),
+            escape(&self.link),
+            escape(&self.subject)
+        )
+    }
+}
+
+/// The chat notification for the event, if people following the list are told about it. Gifting
+/// activity never is, since the list's chat webhooks and Matrix room belong to its owner.
+/// Automation webhooks get every event they ask for, see `automation_payload`.
+fn message(event: &DomainEvent, site: &SiteUrl) -> Option<Message> {
+    match event {
+        DomainEvent::ItemAdded { list, item, .. } => Some(Message {
+            summary: format!("New item on {}", list.title),
+            subject: item.title.clone(),
+            link: site.url(&uri!(crate::web::items::show(&list.key, item.id)).to_string()),
+        }),
+        _ => None,
+    }
+}
+
+/// The user who should get a direct message about the event, if anyone. People aren't told
+/// about their own changes.
+fn recipient(event: &DomainEvent) -> Option<i64> {
+    event
+        .list()
+        .owner_id
+        .filter(|owner| Some(*owner) != event.by())
+}
+
+/// The link at the bottom of an activity email that turns off that `kind` of email without
+/// logging in, see `crate::web::notifications::unsubscribe`.
+fn unsubscribe_link(site: &SiteUrl, preferences: &NotificationPreferences, kind: &str) -> String {
+    let token = &preferences.unsubscribe_token;
+    site.url(&uri!(crate::web::notifications::unsubscribe(token, Some(kind))).to_string())
+}
+
+/// Logs events and sends notifications about them. Available as managed state.
+///
+/// Events are dispatched in the transaction making the change they're about: they're written to
+/// the event log, and a webhook post for each of the list's webhooks is queued in the outbox (see
+/// `Delivery`), so either all of it is saved or none of it is. Once the transaction is committed
+/// (see `commit`), a background worker sends the queued deliveries, retrying ones that fail, and
+/// posts to Matrix from the event log (see `spawn_worker`). So a slow or broken target doesn't
+/// hold up the request and nothing is lost if the server stops first. Live updates get each
+/// event once it's committed, see `subscribe`, and so do plugins' hooks, see
+/// `crate::plugins`.
+pub struct Dispatcher {
+    sender: Sender,
+    wake: Arc<Notify>,
+    live: broadcast::Sender<Event>,
+    plugins: PluginRegistry,
+}
+
+/// Sends queued deliveries and Matrix notifications.
+#[derive(Clone)]
+struct Sender {
+    /// Webhook targets are user input, so they're only posted to on public hosts.
+    public: PublicClient,
+    site: SiteUrl,
+    pool: sqlx::AnyPool,
+    mailer: Mailer,
+    matrix: Option<MatrixClient>,
+    matrix_room: Option<String>,
+}
+
+impl Dispatcher {
+    pub fn new(
+        site: SiteUrl,
+        pool: sqlx::AnyPool,
+        mailer: Mailer,
+        matrix_config: &MatrixConfig,
+        plugins: PluginRegistry,
+    ) -> Result<Dispatcher, MatrixError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent(concat!("wishlist-rs/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        let matrix = MatrixClient::from_config(matrix_config, client)?;
+        let public = PublicClient::new(
+            Duration::from_secs(10),
+            concat!("wishlist-rs/", env!("CARGO_PKG_VERSION")),
+        );
+
+        Ok(Dispatcher {
+            sender: Sender {
+                public,
+                site,
+                pool,
+                mailer,
+                matrix,
+                matrix_room: matrix_config.room_id.clone(),
+            },
+            wake: Arc::new(Notify::new()),
+            live: broadcast::channel(LIVE_CAPACITY).0,
+            plugins,
+        })
+    }
+
+    /// Logs the event and queues posts to its list's webhooks and its owner's REST hooks, and
+    /// activity emails (see `queue_emails`), in the transaction. Nothing is sent until the transaction is committed with `commit`.
+    ///
+    /// Automation webhooks and REST hooks only get gifting activity if the list's owner can see
+    /// it, see `crate::surprise`.
+    pub async fn dispatch(
+        &self,
+        tx: &mut Transaction,
+        event: DomainEvent,
+    ) -> Result<(), DataError> {
+        let entry = Event::record(tx, &event).await?;
+
+        let site = &self.sender.site;
+        let message = message(&event, site);
+        let shown_to_owner = if event.is_gifting() {
+            List::find_by_id(tx, event.list().id)
+                .await?
+                .map(|list| Viewer::of(&list, list.user_id).sees_gifting())
+                .unwrap_or(false)
+        } else {
+            true
+        };
+
+        for webhook in ListWebhook::all_by_list(tx, event.list().id).await? {
+            let body = match (webhook.format.as_str(), &message) {
+                (ListWebhook::JSON, _) if shown_to_owner && webhook.wants(event.kind()) => {
+                    automation_payload(&event, site)
+                }
+                (ListWebhook::JSON, _) | (_, None) => continue,
+                (format, Some(message)) => webhook_payload(format, message),
+            };
+            let body = body.to_string();
+            let signature = sign(&webhook.secret, &body);
+            let signature = signature.as_deref();
+            Delivery::queue_webhook(tx, Some(webhook.id), None, &webhook.url, &body, signature)
+                .await?;
+        }
+
+        if let Some(owner) = event.list().owner_id.filter(|_| shown_to_owner) {
+            let subscriptions =
+                HookSubscription::all_for_event(tx, owner, event.list().id, event.kind()).await?;
+            for subscription in subscriptions {
+                let mut body = automation_payload(&event, site);
+                body["id"] = json!(entry.id);
+                let body = body.to_string();
+                let signature = sign(&subscription.secret, &body);
+                let signature = signature.as_deref();
+                let target = &subscription.target_url;
+                Delivery::queue_webhook(tx, None, Some(subscription.id), target, &body, signature)
+                    .await?;
+            }
+        }
+
+        self.queue_emails(tx, &event, shown_to_owner).await?;
+
+        tx.events.push(entry);
+        Ok(())
+    }
+
+    /// Queues activity emails about the event, in the transaction: the list's owner hears about
+    /// claims if they can see them, and the list's watchers hear about new items, see
+    /// `ListWatcher`. Nobody's emailed about their own changes, or about things they've turned
+    /// off in their `NotificationPreferences`.
+    async fn queue_emails(
+        &self,
+        tx: &mut Transaction,
+        event: &DomainEvent,
+        shown_to_owner: bool,
+    ) -> Result<(), DataError> {
+        let site = &self.sender.site;
+        let settings_link = site.url(&uri!(crate::web::notifications::edit).to_string());
+        match event {
+            DomainEvent::ItemClaimed { list, item, .. } if shown_to_owner => {
+                let owner = match recipient(event) {
+                    Some(owner) => User::find_by_id(tx, owner).await?,
+                    None => None,
+                };
+                let owner = match owner.filter(|owner| owner.suspended_at.is_none()) {
+                    Some(owner) => owner,
+                    None => return Ok(()),
+                };
+                let preferences = NotificationPreferences::for_user(tx, owner.id).await?;
+                if !preferences.claim_emails {
+                    return Ok(());
+                }
+                let link = uri!(crate::web::items::show(&list.key, item.id)).to_string();
+                let context = json!({
+                    "username": owner.username,
+                    "list_title": list.title,
+                    "item_title": item.title,
+                    "link": site.url(&link),
+                    "settings_link": settings_link,
+                    "unsubscribe_link":
+                        unsubscribe_link(site, &preferences, NotificationPreferences::CLAIMS),
+                });
+                Delivery::queue_email(tx, &owner.email, "item_claimed", &context).await
+            }
+            DomainEvent::ItemAdded { list, item, by } => {
+                let link = uri!(crate::web::items::show(&list.key, item.id)).to_string();
+                let list_link = uri!(crate::web::lists::show(&list.key)).to_string();
+                for watcher in ListWatcher::recipients(tx, list.id).await? {
+                    if Some(watcher.user_id) == *by {
+                        continue;
+                    }
+                    let preferences =
+                        NotificationPreferences::for_user(tx, watcher.user_id).await?;
+                    let context = json!({
+                        "username": watcher.username,
+                        "list_title": list.title,
+                        "item_title": item.title,
+                        "link": site.url(&link),
+                        "list_link": site.url(&list_link),
+                        "settings_link": settings_link,
+                        "unsubscribe_link":
+                            unsubscribe_link(site, &preferences, NotificationPreferences::ACTIVITY),
+                    });
+                    Delivery::queue_email(tx, &watcher.email, "item_added", &context).await?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Commits the transaction, then publishes its events to live updates and plugins and wakes
+    /// the worker to send what was queued. Cached pages of the events' lists are dropped, since claims
+    /// change them without saving the list or item. Returns the connection for the rest of the
+    /// request.
+    pub async fn commit(&self, tx: Transaction) -> Result<Connection<WishlistDb>, sqlx::Error> {
+        let (conn, events) = tx.commit().await?;
+        for event in events {
+            if let Some(list_id) = event.list_id {
+                render_cache::invalidate(list_id);
+            }
+            if !self.plugins.is_empty() {
+                if let Some(domain_event) = event.domain_event() {
+                    self.plugins.on_event(domain_event);
+                }
+            }
+            // Nobody listening isn't an error
+            let _ = self.live.send(event);
+        }
+        self.wake.notify_one();
+        Ok(conn)
+    }
+
+    /// Returns a receiver that gets every event once it's committed, for live updates.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.live.subscribe()
+    }
+
+    /// Starts sending queued deliveries and notifications for logged events in the background.
+    pub fn spawn_worker(&self) {
+        let sender = self.sender.clone();
+        let wake = self.wake.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = sender.send_pending().await {
+                    error!("Failed to send notifications: {}", e);
+                }
+                if let Err(e) = sender.send_deliveries().await {
+                    error!("Failed to send deliveries: {}", e);
+                }
+                tokio::select! {
+                    _ = wake.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+        });
+    }
+}
+
+impl Sender {
+    /// Sends notifications for every event that hasn't had them sent yet.
+    async fn send_pending(&self) -> Result<(), sqlx::Error> {
+        loop {
+            let events = Event::next_undispatched(&self.pool, BATCH_SIZE).await?;
+            if events.is_empty() {
+                return Ok(());
+            }
+            for event in events {
+                if let Some(domain_event) = event.domain_event() {
+                    self.send(&domain_event);
+                }
+                Event::mark_dispatched(&self.pool, event.id).await?;
+            }
+        }
+    }
+
+    /// Posts the event to Matrix. Webhooks were queued when the event was dispatched.
+    fn send(&self, event: &DomainEvent) {
+        let message = match message(event, &self.site) {
+            Some(message) => message,
+            None => return,
+        };
+        if let Some(matrix) = &self.matrix {
+            self.send_matrix(matrix.clone(), recipient(event), &message);
+        }
+    }
+
+    /// Posts the message to the configured Matrix room and to the recipient's direct message
+    /// room.
+    fn send_matrix(&self, matrix: MatrixClient, recipient: Option<i64>, message: &Message) {
+        let pool = self.pool.clone();
+        let room = self.matrix_room.clone();
+        let (text, html) = (message.text(), message.html());
+
+        tokio::spawn(async move {
+            if let Some(room) = room {
+                if let Err(e) = matrix.send_message(&room, &text, &html).await {
+                    warn!("Failed to send Matrix notification to {}: {}", room, e);
+                }
+            }
+            if let Some(user_id) = recipient {
+                if let Err(e) = send_direct(&pool, &matrix, user_id, &text, &html).await {
+                    warn!("Failed to send Matrix notification to user {}: {}", user_id, e);
+                }
+            }
+        });
+    }
+
+    /// Sends every queued delivery that's due. Emails are sent together through the mailer, so
+    /// it can use several connections at once. Failures are retried later, see
+    /// `Delivery::mark_failed`.
+    async fn send_deliveries(&self) -> Result<(), sqlx::Error> {
+        loop {
+            let deliveries = Delivery::next_due(&self.pool, BATCH_SIZE).await?;
+            if deliveries.is_empty() {
+                return Ok(());
+            }
+
+            let (emails, others): (Vec<_>, Vec<_>) = deliveries
+                .into_iter()
+                .partition(|delivery| delivery.kind == Delivery::EMAIL);
+
+            let mut sending = Vec::new();
+            let mut rendered = Vec::new();
+            for delivery in &emails {
+                // Addresses that bounced or complained aren't emailed again, see
+                // `crate::mail::bounces`
+                if EmailSuppression::is_suppressed(&self.pool, &delivery.target).await? {
+                    let error = "The address bounced or complained";
+                    delivery.give_up(&self.pool, error).await?;
+                    continue;
+                }
+
+                match self.render_email(delivery) {
+                    Ok(email) => {
+                        sending.push(delivery);
+                        rendered.push(email);
+                    }
+                    Err(e) => self.record(delivery, Err(e.into())).await?,
+                }
+            }
+
+            let outgoing: Vec<_> = sending
+                .iter()
+                .zip(&rendered)
+                .map(|(delivery, email)| OutgoingEmail {
+                    to: &delivery.target,
+                    subject: &email.subject,
+                    text: &email.text,
+                    html: email.html.as_deref(),
+                })
+                .collect();
+            let results = self.mailer.send_batch(&outgoing).await;
+            for (delivery, result) in sending.into_iter().zip(results) {
+                self.record(delivery, result.map_err(NotifyError::from))
+                    .await?;
+            }
+
+            for delivery in &others {
+                let result = self.deliver(delivery).await;
+                self.record(delivery, result).await?;
+            }
+        }
+    }
+
+    /// Renders an email delivery from its template, or as the plain text it was queued with.
+    fn render_email(&self, delivery: &Delivery) -> Result<RenderedEmail, MailError> {
+        match &delivery.template {
+            Some(template) => {
+                let context: Value = json::from_str(&delivery.body)
+                    .map_err(|e| MailError::Template(format!("Invalid context: {}", e)))?;
+                self.mailer.render(template, &context)
+            }
+            None => Ok(RenderedEmail {
+                subject: delivery.subject.clone().unwrap_or_default(),
+                text: delivery.body.clone(),
+                html: None,
+            }),
+        }
+    }
+
+    /// Marks the delivery as sent, or as failed so it's retried later.
+    async fn record(
+        &self,
+        delivery: &Delivery,
+        result: Result<(), NotifyError>,
+    ) -> Result<(), sqlx::Error> {
+        match result {
+            Ok(()) => delivery.mark_sent(&self.pool).await,
+            Err(e) => {
+                if delivery.attempts + 1 >= Delivery::MAX_ATTEMPTS {
+                    error!("Giving up on delivery {}: {}", delivery.id, e);
+                } else {
+                    warn!("Failed to send delivery {}: {}", delivery.id, e);
+                }
+                delivery.mark_failed(&self.pool, &e.to_string()).await
+            }
+        }
+    }
+
+    /// Sends a queued webhook post. Emails are sent in batches by `send_deliveries` instead.
+    ///
+    /// The target is checked again when it's posted to, since its name can point somewhere else
+    /// by now, and redirects aren't followed.
+    async fn deliver(&self, delivery: &Delivery) -> Result<(), NotifyError> {
+        match delivery.kind.as_str() {
+            Delivery::WEBHOOK => {
+                let url = Url::parse(&delivery.target).map_err(SourceError::from)?;
+                let mut request = self
+                    .public
+                    .pinned(&url)
+                    .await?
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(delivery.body.clone());
+                if let Some(signature) = &delivery.signature {
+                    request = request.header(SIGNATURE_HEADER, signature);
+                }
+                let response = request.send().await?;
+                // REST hooks answer 410 Gone once they've been turned off on the other end
+                if let Some(subscription_id) = delivery.subscription_id {
+                    if response.status() == reqwest::StatusCode::GONE {
+                        HookSubscription::destroy_gone(&self.pool, subscription_id).await?;
+                        return Ok(());
+                    }
+                }
+                response.error_for_status()?;
+            }
+            kind => return Err(DataError::Other(format!("Unknown delivery kind {}", kind)).into()),
+        }
+        Ok(())
+    }
+}
+
+/// Sends a direct message to the user's linked Matrix account, creating the room the first time.
+async fn send_direct(
+    pool: &sqlx::AnyPool,
+    matrix: &MatrixClient,
+    user_id: i64,
+    text: &str,
+    html: &str,
+) -> Result<(), NotifyError> {
+    let mut link = match MatrixLink::find_for_user(pool, user_id).await? {
+        Some(link) => link,
+        None => return Ok(()),
+    };
+
+    let room_id = match &link.room_id {
+        Some(room_id) => room_id.clone(),
+        None => {
+            let room_id = matrix.create_direct_room(&link.matrix_id).await?;
+            link.set_room_id(pool, &room_id).await?;
+            room_id
+        }
+    };
+
+    matrix.send_message(&room_id, text, html).await?;
+    Ok(())
+}
+
+/// Signs a webhook post's body with the webhook's secret, as `sha256=` and the hex HMAC-SHA256,
+/// so the receiver can check it came from here. Returns `None` for webhooks without a secret.
+pub fn sign(secret: &str, body: &str) -> Option<String> {
+    if secret.is_empty() {
+        return None;
+    }
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(body.as_bytes());
+    Some(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+}
+
+/// Formats a message for a Slack or Discord incoming webhook.
+fn webhook_payload(format: &str, message: &Message) -> Value {
+    match format {
+        ListWebhook::DISCORD => json!({
+            "content": format!(
+                "{}: [{}]({})",
+                message.summary,
+                escape_discord(&message.subject),
+                message.link
+            ),
+        }),
+        _ => json!({
+            "text": format!(
+                "{}: <{}|{}>",
+                escape_slack(&message.summary),
+                message.link,
+                escape_slack(&message.subject)
+            ),
+        }),
+    }
+}
+
+/// Formats an event for an automation webhook, e.g. Home Assistant, or a REST hook. Who made the
+/// change is left out.
+pub fn automation_payload(event: &DomainEvent, site: &SiteUrl) -> Value {
+    let list = event.list();
+    let mut payload = json!({
+        "type": event.kind(),
+        "list": {
+            "key": list.key,
+            "title": list.title,
+            "url": site.url(&uri!(crate::web::lists::show(&list.key)).to_string()),
+        },
+        "item": event.item().map(|item| json!({
+            "id": item.id,
+            "title": item.title,
+            "url": site.url(&uri!(crate::web::items::show(&list.key, item.id)).to_string()),
+        })),
+    });
+    if let DomainEvent::ItemReceived { received, .. } = event {
+        payload["received"] = json!(received);
+    }
+    payload
+}
+
+/// Escapes the characters Slack treats as control sequences.
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Escapes the characters that would break out of a Discord masked link.
+fn escape_discord(text: &str) -> String {
+    text.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Sets up the dispatcher and adds it to managed state. Needs the site URL, the database, the
+/// mailer and the plugins.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let (site, pool, mailer, plugins) = match (
+        rocket.state::<SiteUrl>(),
+        WishlistDb::fetch(&rocket),
+        rocket.state::<Mailer>(),
+        rocket.state::<PluginRegistry>(),
+    ) {
+        (Some(site), Some(db), Some(mailer), Some(plugins)) => {
+            (site.clone(), (**db).clone(), mailer.clone(), plugins.clone())
+        }
+        _ => {
+            error!("Notifications need the site URL, the database, the mailer and the plugins");
+            return Err(rocket);
+        }
+    };
+
+    let matrix_config = AppConfig::of(&rocket).matrix.clone();
+
+    match Dispatcher::new(site, pool, mailer, &matrix_config, plugins) {
+        Ok(dispatcher) => Ok(rocket.manage(dispatcher)),
+        Err(e) => {
+            error!("Failed to configure notifications: {}", e);
+            Err(rocket)
+        }
+    }
+}
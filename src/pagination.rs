@@ -0,0 +1,173 @@
+use rocket::http::Header;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use utoipa::IntoParams;
+
+/// How many entries a page has when `per_page` isn't given. A multiple of the four columns the
+/// index pages show.
+pub const DEFAULT_PER_PAGE: i64 = 24;
+
+/// The most entries a page can have.
+pub const MAX_PER_PAGE: i64 = 100;
+
+/// Which page of an index to show, from the `page` and `per_page` query parameters. Pages are
+/// numbered from 1. Values out of range are clamped rather than rejected, so a stale link still
+/// shows something. Routes take an `Option<Page>` and fall back to the first page, so they can be
+/// linked to without a page and unparseable values are ignored.
+#[derive(FromForm, Debug, Default, Clone, Copy, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Page {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl Page {
+    pub fn number(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page
+            .unwrap_or(DEFAULT_PER_PAGE)
+            .clamp(1, MAX_PER_PAGE)
+    }
+
+    /// The `LIMIT` for the page's query.
+    pub fn limit(&self) -> i64 {
+        self.per_page()
+    }
+
+    /// The `OFFSET` for the page's query.
+    pub fn offset(&self) -> i64 {
+        (self.number() - 1).saturating_mul(self.per_page())
+    }
+}
+
+/// Which way an index is sorted, from the `order` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SortDirection> {
+        [SortDirection::Asc, SortDirection::Desc]
+            .into_iter()
+            .find(|direction| direction.as_str() == value)
+    }
+
+    /// The keyword for an `ORDER BY` clause.
+    pub fn sql(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+/// Where a page is in the whole index, for pagination controls and API headers.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+    /// The previous page's number, if there is one.
+    pub prev: Option<i64>,
+    /// The next page's number, if there is one.
+    pub next: Option<i64>,
+    /// The filters the index was shown with, as query parameters to keep in the links to other
+    /// pages. Each one ends in `&`, so it can go right before `page`. See `with_filter`.
+    pub query: String,
+}
+
+impl Pagination {
+    /// Describes the page of an index with `total` entries.
+    pub fn new(page: &Page, total: i64) -> Pagination {
+        let per_page = page.per_page();
+        let total_pages = ((total + per_page - 1) / per_page).max(1);
+        let number = page.number();
+        Pagination {
+            page: number,
+            per_page,
+            total,
+            total_pages,
+            prev: (number > 1).then_some((number - 1).min(total_pages)),
+            next: (number < total_pages).then_some(number + 1),
+            query: String::new(),
+        }
+    }
+
+    /// Keeps the filter in the links to other pages, if it's set.
+    pub fn with_filter(mut self, name: &str, value: Option<&str>) -> Pagination {
+        if let Some(value) = value {
+            self.query.push_str(
+                &url::form_urlencoded::Serializer::new(String::new())
+                    .append_pair(name, value)
+                    .finish(),
+            );
+            self.query.push('&');
+        }
+        self
+    }
+
+    /// A `Link` header value (RFC 8288) pointing to the first, previous, next, and last pages of
+    /// the index at `path`.
+    fn link_header(&self, path: &str) -> String {
+        let link = |page: i64, rel: &str| {
+            format!(
+                "<{}?{}page={}&per_page={}>; rel=\"{}\"",
+                path, self.query, page, self.per_page, rel
+            )
+        };
+
+        let mut links = vec![link(1, "first")];
+        if let Some(prev) = self.prev {
+            links.push(link(prev, "prev"));
+        }
+        if let Some(next) = self.next {
+            links.push(link(next, "next"));
+        }
+        links.push(link(self.total_pages, "last"));
+        links.join(", ")
+    }
+}
+
+/// A page of an API index. It's sent as a JSON array like the whole index would be, with a `Link`
+/// header to the other pages and the page's position in `X-Total-Count`, `X-Page`, `X-Per-Page`
+/// and `X-Total-Pages` headers.
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub pagination: Pagination,
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Paged<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let pagination = self.pagination;
+        let mut response = Json(self.items).respond_to(request)?;
+        response.set_header(Header::new(
+            "Link",
+            pagination.link_header(request.uri().path().as_str()),
+        ));
+        response.set_header(Header::new("X-Total-Count", pagination.total.to_string()));
+        response.set_header(Header::new("X-Page", pagination.page.to_string()));
+        response.set_header(Header::new("X-Per-Page", pagination.per_page.to_string()));
+        response.set_header(Header::new(
+            "X-Total-Pages",
+            pagination.total_pages.to_string(),
+        ));
+        Ok(response)
+    }
+}
@@ -0,0 +1,77 @@
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::serde::json;
+use rocket::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use rocket::time::Duration;
+use rocket::{fairing, Build, Rocket};
+use url::Url;
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+use crate::config::AppConfig;
+use crate::util::SiteUrl;
+
+/// The private cookie that holds a registration ceremony's state between its two requests.
+pub static REGISTRATION_COOKIE: &str = "passkey_registration";
+/// The private cookie that holds a login ceremony's state between its two requests.
+pub static AUTHENTICATION_COOKIE: &str = "passkey_authentication";
+
+/// Passkey configuration, read from the `passkeys` table in Rocket.toml. Passkeys are bound to
+/// the host of `base_url`, so changing it invalidates every registered passkey.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct PasskeysConfig {
+    /// The name shown by browsers and authenticators when creating a passkey.
+    pub rp_name: String,
+}
+
+impl Default for PasskeysConfig {
+    fn default() -> Self {
+        Self {
+            rp_name: "Universal Wishlist".to_string(),
+        }
+    }
+}
+
+/// Keeps a ceremony's state in an encrypted cookie for five minutes.
+pub fn store_state<T: Serialize>(cookies: &CookieJar<'_>, name: &'static str, state: &T) {
+    if let Ok(value) = json::to_string(state) {
+        let mut cookie = Cookie::new(name, value);
+        cookie.set_max_age(Duration::minutes(5));
+        cookie.set_same_site(SameSite::Strict);
+        cookie.set_http_only(true);
+        cookies.add_private(cookie);
+    }
+}
+
+/// Takes a ceremony's state back out of its cookie. Each state can only be used once.
+pub fn take_state<T: DeserializeOwned>(cookies: &CookieJar<'_>, name: &'static str) -> Option<T> {
+    let cookie = cookies.get_private(name)?;
+    cookies.remove_private(Cookie::named(name));
+    json::from_str(cookie.value()).ok()
+}
+
+/// Sets up WebAuthn for the site URL and adds it to managed state. Needs the site URL.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).passkeys.clone();
+
+    let origin = match rocket.state::<SiteUrl>().map(|site| Url::parse(&site.url("/"))) {
+        Some(Ok(origin)) => origin,
+        _ => {
+            error!("Passkeys need a valid base_url");
+            return Err(rocket);
+        }
+    };
+
+    let webauthn = origin
+        .host_str()
+        .ok_or(webauthn_rs::prelude::WebauthnError::Configuration)
+        .and_then(|rp_id| WebauthnBuilder::new(rp_id, &origin))
+        .and_then(|builder| builder.rp_name(&config.rp_name).build());
+
+    match webauthn {
+        Ok(webauthn) => Ok(rocket.manage::<Webauthn>(webauthn)),
+        Err(e) => {
+            error!("Failed to configure passkeys: {}", e);
+            Err(rocket)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use crate::config::AppConfig;
+
+/// A list of commonly used passwords, checked locally before (or instead of) HaveIBeenPwned.
+static COMMON_PASSWORDS: &str = include_str!("../data/common-passwords.txt");
+
+/// Password checking configuration, read from the `passwords` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct PasswordConfig {
+    /// Whether to check new passwords against the HaveIBeenPwned range API.
+    /// Turn this off for instances without internet access.
+    pub hibp_enabled: bool,
+    /// The base URL of the HaveIBeenPwned range API.
+    pub hibp_api_url: String,
+    /// How long to wait for the HaveIBeenPwned API before falling back to the local list.
+    pub hibp_timeout_secs: u64,
+    /// The minimum strength score (0-4) new passwords must reach.
+    pub min_strength: u8,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            hibp_enabled: true,
+            hibp_api_url: "https://api.pwnedpasswords.com".to_string(),
+            hibp_timeout_secs: 5,
+            min_strength: 2,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PasswordCheckError {
+    #[error("HaveIBeenPwned request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Checks passwords against known breaches. Available as managed state.
+pub struct PasswordChecker {
+    client: Option<reqwest::Client>,
+    hibp_api_url: String,
+    min_strength: u8,
+}
+
+impl PasswordChecker {
+    /// Creates a new password checker from the given config.
+    pub fn from_config(config: PasswordConfig) -> Result<PasswordChecker, PasswordCheckError> {
+        let client = if config.hibp_enabled {
+            Some(
+                reqwest::Client::builder()
+                    .timeout(std::time::Duration::from_secs(config.hibp_timeout_secs))
+                    .build()?,
+            )
+        } else {
+            None
+        };
+
+        Ok(PasswordChecker {
+            client,
+            hibp_api_url: config.hibp_api_url.trim_end_matches('/').to_string(),
+            min_strength: config.min_strength,
+        })
+    }
+
+    /// Returns true if the password appears in a known breach.
+    ///
+    /// Uses the k-anonymity range API, so only the first 5 characters of the password's SHA-1
+    /// hash ever leave the server. Returns `false` when HaveIBeenPwned is disabled.
+    pub async fn is_pwned(&self, password: &str) -> Result<bool, PasswordCheckError> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => return Ok(false),
+        };
+
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let body = client
+            .get(format!("{}/range/{}", self.hibp_api_url, prefix))
+            .header("Add-Padding", "true")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        // Each line is "SUFFIX:COUNT", padding entries have a count of 0
+        Ok(body.lines().any(|line| match line.trim().split_once(':') {
+            Some((s, count)) => s.eq_ignore_ascii_case(suffix) && count != "0",
+            None => false,
+        }))
+    }
+
+    /// Validates the password against known breaches, falling back to the local list if
+    /// HaveIBeenPwned can't be reached.
+    pub async fn validate(&self, password: &str) -> Result<(), validator::ValidationError> {
+        match self.is_pwned(password).await {
+            Ok(false) => Ok(()),
+            Ok(true) => {
+                let mut err = validator::ValidationError::new("pwned");
+                err.message = Some(Cow::from(
+                    "This password has appeared in a data breach, please choose another one.",
+                ));
+                Err(err)
+            }
+            Err(e) => {
+                warn!("{}, falling back to the local password list", e);
+                validate_not_common(password)
+            }
+        }
+    }
+
+    /// Validates that the password is strong enough, using the user's other details as hints.
+    pub fn validate_strength(
+        &self,
+        password: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), validator::ValidationError> {
+        let strength = estimate_strength(password, user_inputs);
+        if strength.score >= self.min_strength {
+            return Ok(());
+        }
+
+        let mut err = validator::ValidationError::new("weak");
+        err.message = Some(Cow::from(match strength.warning {
+            Some(warning) => format!("This password is too weak. {}", warning),
+            None => "This password is too weak.".to_string(),
+        }));
+        Err(err)
+    }
+}
+
+/// An estimate of how hard a password is to guess.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PasswordStrength {
+    /// The strength score, from 0 (trivially guessable) to 4 (very hard to guess).
+    pub score: u8,
+    /// An explanation of what makes the password weak, if anything.
+    pub warning: Option<String>,
+    /// Suggestions for making the password stronger.
+    pub suggestions: Vec<String>,
+}
+
+/// Estimates the strength of a password with zxcvbn.
+///
+/// `user_inputs` are other details the user entered (username, email) that shouldn't be part of
+/// their password.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    match zxcvbn::zxcvbn(password, user_inputs) {
+        Ok(entropy) => {
+            let feedback = entropy.feedback().as_ref();
+            PasswordStrength {
+                score: entropy.score(),
+                warning: feedback
+                    .and_then(|f| f.warning())
+                    .map(|w| w.to_string()),
+                suggestions: feedback
+                    .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+                    .unwrap_or_default(),
+            }
+        }
+        // zxcvbn refuses to score blank passwords
+        Err(_) => PasswordStrength {
+            score: 0,
+            warning: None,
+            suggestions: vec!["Enter a password.".to_string()],
+        },
+    }
+}
+
+/// Validates that the password isn't in the local list of common passwords.
+pub fn validate_not_common(password: &str) -> Result<(), validator::ValidationError> {
+    let is_common = COMMON_PASSWORDS
+        .lines()
+        .any(|common| common.eq_ignore_ascii_case(password));
+
+    if is_common {
+        let mut err = validator::ValidationError::new("common");
+        err.message = Some(Cow::from(
+            "This password is too common, please choose another one.",
+        ));
+        Err(err)
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads the password config and adds the `PasswordChecker` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).passwords.clone();
+
+    match PasswordChecker::from_config(config) {
+        Ok(checker) => Ok(rocket.manage(checker)),
+        Err(e) => {
+            error!("Failed to configure password checker: {}", e);
+            Err(rocket)
+        }
+    }
+}
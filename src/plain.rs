@@ -0,0 +1,215 @@
+use std::io::Cursor;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::time::Duration;
+use rocket::{Data, Request, Response};
+
+/// The cookie that remembers a visitor's plain HTML preference.
+pub static PLAIN_COOKIE: &str = "plain";
+
+/// The query parameter that turns plain HTML mode on (`?plain=1`) or off (`?plain=0`).
+static PLAIN_PARAM: &str = "plain";
+
+/// Marks the parts of pages that are left out in plain HTML mode: the main layout's stylesheets
+/// and scripts, and pages' own scripts. Everything else on a page is ordinary HTML and forms, so
+/// it works without them.
+static ENHANCEMENTS_START: &str = "<!-- enhancements -->";
+static ENHANCEMENTS_END: &str = "<!-- /enhancements -->";
+
+/// Whether this request is rendered in plain HTML mode.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainMode(pub bool);
+
+/// Reads the visitor's plain HTML preference from their cookies.
+pub fn preference(cookies: &CookieJar<'_>) -> bool {
+    cookies
+        .get(PLAIN_COOKIE)
+        .map(|cookie| cookie.value() == "on")
+        .unwrap_or(false)
+}
+
+/// Remembers the visitor's plain HTML preference for a year.
+pub fn set_preference(cookies: &CookieJar<'_>, plain: bool) {
+    if plain {
+        let mut cookie = Cookie::new(PLAIN_COOKIE, "on");
+        cookie.set_max_age(Duration::days(365));
+        cookie.set_same_site(SameSite::Lax);
+        cookie.set_http_only(true);
+        cookies.add(cookie);
+    } else {
+        cookies.remove(Cookie::named(PLAIN_COOKIE));
+    }
+}
+
+/// Serves pages without stylesheets or scripts to visitors who asked for plain HTML, either
+/// with the `plain` query parameter or from the display settings page.
+pub struct PlainHtml;
+
+#[rocket::async_trait]
+impl Fairing for PlainHtml {
+    fn info(&self) -> Info {
+        Info {
+            name: "Plain HTML",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let requested = match request.query_value::<&str>(PLAIN_PARAM) {
+            Some(Ok("1")) | Some(Ok("on")) => Some(true),
+            Some(Ok("0")) | Some(Ok("off")) => Some(false),
+            _ => None,
+        };
+
+        let plain = match requested {
+            Some(plain) => {
+                set_preference(request.cookies(), plain);
+                plain
+            }
+            None => preference(request.cookies()),
+        };
+
+        request.local_cache(|| PlainMode(plain));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let PlainMode(plain) = *request.local_cache(PlainMode::default);
+        if !plain || !response.content_type().is_some_and(|ct| ct.is_html()) {
+            return;
+        }
+
+        let body = match response.body_mut().to_string().await {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Couldn't read the response for plain HTML mode: {}", e);
+                return;
+            }
+        };
+
+        let body = strip_enhancements(&body);
+        response.set_sized_body(body.len(), Cursor::new(body));
+    }
+}
+
+/// Removes every marked enhancements block from a rendered page.
+fn strip_enhancements(page: &str) -> String {
+    let mut out = String::with_capacity(page.len());
+    let mut rest = page;
+    while let Some(start) = rest.find(ENHANCEMENTS_START) {
+        out.push_str(&rest[..start]);
+        match rest[start..].find(ENHANCEMENTS_END) {
+            Some(end) => rest = &rest[start + end + ENHANCEMENTS_END.len()..],
+            None => {
+                // Unterminated, leave the page as it was from here on
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket::http::Status;
+
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn strips_every_block() {
+        let page = "<head><!-- enhancements --><script></script><!-- /enhancements --></head>\
+                    <body>Hi<!-- enhancements --><script></script><!-- /enhancements --></body>";
+        assert_eq!(strip_enhancements(page), "<head></head><body>Hi</body>");
+    }
+
+    #[test]
+    fn keeps_unterminated_blocks() {
+        let page = "<p>Hi</p><!-- enhancements --><script></script>";
+        assert_eq!(strip_enhancements(page), page);
+    }
+
+    async fn get(app: &TestApp, uri: &str) -> String {
+        let response = app.client().get(uri).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{} is found", uri);
+        let page = response.into_string().await.unwrap_or_default();
+        assert!(!page.contains("<script"), "{} has no scripts", uri);
+        assert!(!page.contains("stylesheet"), "{} has no stylesheets", uri);
+        page
+    }
+
+    /// Posts the form on the page with the action, the only way to change anything without
+    /// scripts, and returns where it redirects to.
+    async fn submit(app: &TestApp, page: &str, action: &str, form: &str) -> String {
+        let form_tag = format!(r#"<form action="{}" method="POST""#, action);
+        assert!(page.contains(&form_tag), "the page has a form for {}", action);
+        let (status, location, _) = app.post_form(action, form).await;
+        assert_eq!(status, Status::SeeOther, "{} redirects", action);
+        location.expect("redirects have a location")
+    }
+
+    #[rocket::async_test]
+    async fn plain_mode_is_remembered() {
+        let app = TestApp::new().await;
+        let key = app.list_key("Alice's Birthday").await;
+
+        get(&app, "/?plain=1").await;
+        get(&app, &format!("/lists/{}", key)).await;
+        app.login("alice").await;
+        get(&app, "/account/passkeys").await;
+
+        let response = app.client().get("/?plain=0").dispatch().await;
+        let page = response.into_string().await.unwrap_or_default();
+        assert!(page.contains("<script"), "scripts are back");
+    }
+
+    #[rocket::async_test]
+    async fn lists_work_without_scripts() {
+        let app = TestApp::new().await;
+        get(&app, "/login?plain=1").await;
+        app.login("alice").await;
+
+        let page = get(&app, "/lists/new").await;
+        let form = "title=Graduation&description=&privacy=public";
+        let list = submit(&app, &page, "/lists", form).await;
+        let key = list.trim_start_matches("/lists/").to_string();
+        get(&app, &list).await;
+
+        let items = format!("/lists/{}/items", key);
+        let page = get(&app, &format!("{}/new", items)).await;
+        let form = "title=Desk+lamp&description=Warm+white&url=&kind=physical&amount=&price=&\
+                    price_max=&priority=normal";
+        let item = submit(&app, &page, &items, form).await;
+        assert!(get(&app, &items).await.contains("Desk lamp"));
+
+        // Someone else claims it, buys it, changes their mind and claims it again
+        app.login("bob").await;
+        let claim = format!("{}/claim", item);
+        let page = get(&app, &item).await;
+        submit(&app, &page, &claim, "").await;
+        let page = get(&app, &item).await;
+        assert!(page.contains("You claimed this item"));
+        submit(&app, &page, &format!("{}/purchased", claim), "purchased=true").await;
+        let page = get(&app, &item).await;
+        assert!(page.contains("You've bought it."));
+        submit(&app, &page, &claim, "_method=DELETE").await;
+        let page = get(&app, &item).await;
+        assert!(!page.contains("You claimed this item"));
+        submit(&app, &page, &claim, "").await;
+
+        // The owner gets it, then cleans up
+        app.login("alice").await;
+        let page = get(&app, &item).await;
+        submit(&app, &page, &format!("{}/received", item), "received=true").await;
+        let page = get(&app, &item).await;
+        assert!(page.contains("Received on"));
+        submit(&app, &page, &item, "_method=DELETE").await;
+        let page = get(&app, &list).await;
+        assert!(!page.contains("Desk lamp"));
+        submit(&app, &page, &list, "_method=DELETE").await;
+        let response = app.client().get(list.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
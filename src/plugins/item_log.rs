@@ -0,0 +1,25 @@
+//! An example plugin that logs every item added to a list. Turned on with the `plugin-item-log`
+//! feature.
+
+use crate::events::{ItemRef, ListRef};
+
+use super::Plugin;
+
+pub struct ItemLog;
+
+#[rocket::async_trait]
+impl Plugin for ItemLog {
+    fn name(&self) -> &'static str {
+        "item_log"
+    }
+
+    async fn on_item_created(&self, list: &ListRef, item: &ItemRef, by: Option<i64>) {
+        match by {
+            Some(user_id) => info!(
+                "Item {} added to list {} by user {}",
+                item.id, list.key, user_id
+            ),
+            None => info!("Item {} added to list {} anonymously", item.id, list.key),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Extension points for downstream forks.
+//!
+//! A plugin implements `Plugin`, overriding the hooks it needs, and is compiled in behind a
+//! cargo feature named `plugin-<name>` (see `compiled_in`). The `PluginRegistry` holding them is
+//! built at startup and added to managed state, so a fork can add behavior by adding a module and
+//! a line to `compiled_in`, without touching handlers.
+//!
+//! The hooks are:
+//!
+//! - `on_item_created`, run in the background once an item is saved and its event committed.
+//! - `on_list_rendered`, which adds values to a list page's template context under
+//!   `plugins.<name>`. Public list pages are cached for anonymous visitors, so the values should
+//!   only depend on the list.
+//! - `importers`, for reading more file formats into a list's items, tried before CSV and JSON.
+
+use std::sync::Arc;
+
+use rocket::serde::json::serde_json::Map;
+use rocket::serde::json::Value;
+use rocket::{fairing, Build, Rocket};
+
+use crate::db::models::List;
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::list_import::ImportRow;
+
+#[cfg(feature = "plugin-item-log")]
+mod item_log;
+
+/// Something that can be added to the instance at compile time. Every hook does nothing unless
+/// it's overridden.
+#[rocket::async_trait]
+pub trait Plugin: Send + Sync {
+    /// A short name for the plugin, e.g. "item_log". Its template values are under this name.
+    fn name(&self) -> &'static str;
+
+    /// Called after an item is added to a list, by `by` if they were logged in.
+    async fn on_item_created(&self, _list: &ListRef, _item: &ItemRef, _by: Option<i64>) {}
+
+    /// Returns values to add to the list's page, or `None` to add nothing.
+    async fn on_list_rendered(&self, _list: &List) -> Option<Value> {
+        None
+    }
+
+    /// Returns the file formats the plugin can import items from.
+    fn importers(&self) -> Vec<Box<dyn Importer>> {
+        vec![]
+    }
+}
+
+/// A file format items can be imported from, see `crate::list_import`.
+pub trait Importer: Send + Sync {
+    /// A short name for the format, e.g. "pinterest".
+    fn name(&self) -> &'static str;
+
+    /// Returns true if this importer knows how to read the file.
+    fn matches(&self, data: &[u8]) -> bool;
+
+    /// Reads the file's items, or returns why it couldn't be read.
+    fn parse(&self, data: &[u8]) -> Result<Vec<ImportRow>, String>;
+}
+
+/// The plugins compiled into the instance. Available as managed state.
+#[derive(Clone, Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn Plugin>>,
+    importers: Vec<Arc<dyn Importer>>,
+}
+
+impl PluginRegistry {
+    /// Creates a registry with every plugin whose feature is turned on.
+    pub fn with_compiled_in() -> PluginRegistry {
+        let mut registry = PluginRegistry::default();
+        for plugin in compiled_in() {
+            registry.register(plugin);
+        }
+        registry
+    }
+
+    /// Adds a plugin. Plugins registered first run first, and their importers are tried first.
+    pub fn register(&mut self, plugin: Arc<dyn Plugin>) {
+        self.importers
+            .extend(plugin.importers().into_iter().map(Arc::from));
+        self.plugins.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// The names of the registered plugins, for logging.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.plugins.iter().map(|plugin| plugin.name()).collect()
+    }
+
+    /// Runs the hooks for a committed event in the background, so a slow plugin doesn't hold up
+    /// the request.
+    pub fn on_event(&self, event: DomainEvent) {
+        let (list, item, by) = match event {
+            DomainEvent::ItemAdded { list, item, by } => (list, item, by),
+            _ => return,
+        };
+        for plugin in &self.plugins {
+            let plugin = plugin.clone();
+            let (list, item) = (list.clone(), item.clone());
+            rocket::tokio::spawn(async move {
+                plugin.on_item_created(&list, &item, by).await;
+            });
+        }
+    }
+
+    /// Returns the values the plugins add to the list's page, by plugin name.
+    pub async fn list_context(&self, list: &List) -> Map<String, Value> {
+        let mut context = Map::new();
+        for plugin in &self.plugins {
+            if let Some(value) = plugin.on_list_rendered(list).await {
+                context.insert(plugin.name().to_string(), value);
+            }
+        }
+        context
+    }
+
+    /// Returns the first importer that can read the file.
+    pub fn importer_for(&self, data: &[u8]) -> Option<&dyn Importer> {
+        self.importers
+            .iter()
+            .find(|importer| importer.matches(data))
+            .map(|importer| importer.as_ref())
+    }
+}
+
+/// The plugins turned on with cargo features. Forks add theirs here.
+#[allow(unused_mut)]
+fn compiled_in() -> Vec<Arc<dyn Plugin>> {
+    let mut plugins: Vec<Arc<dyn Plugin>> = vec![];
+    #[cfg(feature = "plugin-item-log")]
+    plugins.push(Arc::new(item_log::ItemLog));
+    plugins
+}
+
+/// Builds the plugin registry and adds it to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let registry = PluginRegistry::with_compiled_in();
+    if !registry.is_empty() {
+        info!("Plugins: {}", registry.names().join(", "));
+    }
+    Ok(rocket.manage(registry))
+}
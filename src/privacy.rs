@@ -0,0 +1,96 @@
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::time::Duration;
+use rocket::{fairing, Build, Rocket, State};
+
+use crate::config::AppConfig;
+
+/// The cookie that remembers a visitor's consent choice.
+pub static CONSENT_COOKIE: &str = "consent";
+
+/// Privacy configuration, read from the `privacy` table in Rocket.toml.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+#[derive(Default)]
+pub struct PrivacyConfig {
+    /// Turns off everything that isn't needed for the site to work, like click counting,
+    /// regardless of what visitors choose. Only the session and consent cookies are used.
+    pub minimal_mode: bool,
+    /// Only track visitors who have opted in on the consent page. Otherwise tracking is on
+    /// unless they opt out.
+    pub require_consent: bool,
+}
+
+
+/// What a visitor chose on the consent page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consent {
+    Accepted,
+    Rejected,
+}
+
+impl Consent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Consent::Accepted => "accepted",
+            Consent::Rejected => "rejected",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Consent> {
+        match value {
+            "accepted" => Some(Consent::Accepted),
+            "rejected" => Some(Consent::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the visitor's consent choice from their cookies.
+pub fn consent(cookies: &CookieJar<'_>) -> Option<Consent> {
+    cookies
+        .get(CONSENT_COOKIE)
+        .and_then(|cookie| Consent::parse(cookie.value()))
+}
+
+/// Remembers the visitor's consent choice for a year.
+pub fn set_consent(cookies: &CookieJar<'_>, consent: Consent) {
+    let mut cookie = Cookie::new(CONSENT_COOKIE, consent.as_str());
+    cookie.set_max_age(Duration::days(365));
+    cookie.set_same_site(SameSite::Lax);
+    cookie.set_http_only(true);
+    cookies.add(cookie);
+}
+
+/// Whether non-essential data, like click counts, may be recorded for this request.
+/// Always succeeds.
+pub struct Tracking {
+    pub allowed: bool,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Tracking {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = match request.guard::<&State<PrivacyConfig>>().await.succeeded() {
+            Some(config) => config,
+            None => return Outcome::Success(Tracking { allowed: false }),
+        };
+
+        let allowed = !config.minimal_mode
+            && match consent(request.cookies()) {
+                Some(consent) => consent == Consent::Accepted,
+                None => !config.require_consent,
+            };
+
+        Outcome::Success(Tracking { allowed })
+    }
+}
+
+/// Reads the privacy config and adds it to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).privacy.clone();
+
+    Ok(rocket.manage(config))
+}
@@ -0,0 +1,142 @@
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use rocket_db_pools::{sqlx, Connection};
+
+use crate::config::AppConfig;
+use crate::db::models::{Image, Item, List, QuotaExemption, Upload};
+use crate::db::{DataError, WishlistDb};
+
+/// Usage quotas, read from the `quotas` table in Rocket.toml. Quotas that aren't set don't apply,
+/// which is the default.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct QuotaConfig {
+    /// How many lists each user can own.
+    pub max_lists: Option<i64>,
+    /// How many items a list can have.
+    pub max_items_per_list: Option<i64>,
+    /// How many bytes each user's uploads and item images can take up in total.
+    pub max_upload_bytes: Option<i64>,
+}
+
+/// Checks what users are about to create against the instance's quotas. Admins can exempt users,
+/// see `QuotaExemption`. Available as managed state.
+///
+/// Each check returns a message for the user when they're over the quota, or `None` if they
+/// aren't.
+pub struct Quotas {
+    config: QuotaConfig,
+}
+
+impl Quotas {
+    pub fn from_config(config: QuotaConfig) -> Quotas {
+        Quotas { config }
+    }
+
+    /// Checks the user can own another list. Lists made without logging in aren't counted.
+    pub async fn check_list(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: Option<i64>,
+    ) -> Result<Option<String>, DataError> {
+        let (max, user_id) = match (self.config.max_lists, user_id) {
+            (Some(max), Some(user_id)) => (max, user_id),
+            _ => return Ok(None),
+        };
+        if is_exempt(conn, Some(user_id)).await? {
+            return Ok(None);
+        }
+
+        if List::count_by_user(conn, user_id).await? >= max {
+            return Ok(Some(format!(
+                "You can have at most {} lists here. Delete one to make room, or ask an admin to lift the limit.",
+                max
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Checks another item can be added to the list. The list owner's exemption applies, no
+    /// matter who adds the item.
+    pub async fn check_item(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        list: &List,
+    ) -> Result<Option<String>, DataError> {
+        let max = match self.config.max_items_per_list {
+            Some(max) => max,
+            None => return Ok(None),
+        };
+        if is_exempt(conn, list.user_id).await? {
+            return Ok(None);
+        }
+
+        if Item::count_by_list(conn, list.id).await? >= max {
+            return Ok(Some(format!(
+                "A list can have at most {} items here. Remove one to make room, or ask an admin to lift the limit.",
+                max
+            )));
+        }
+        Ok(None)
+    }
+
+    /// Checks the user has room for an upload of the given size.
+    pub async fn check_upload(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        size: i64,
+    ) -> Result<Option<String>, DataError> {
+        let max = match self.config.max_upload_bytes {
+            Some(max) => max,
+            None => return Ok(None),
+        };
+        if is_exempt(conn, Some(user_id)).await? {
+            return Ok(None);
+        }
+
+        let used = Upload::total_size_by_user(conn, user_id).await?
+            + Image::total_size_by_user(conn, user_id).await?;
+        if used.saturating_add(size) > max {
+            return Ok(Some(format!(
+                "Your images can take up at most {} here, and you've used {}. Delete some to make room, or ask an admin to lift the limit.",
+                format_bytes(max),
+                format_bytes(used)
+            )));
+        }
+        Ok(None)
+    }
+}
+
+async fn is_exempt(
+    conn: &mut Connection<WishlistDb>,
+    user_id: Option<i64>,
+) -> Result<bool, sqlx::Error> {
+    match user_id {
+        Some(user_id) => Ok(QuotaExemption::find_by_user(conn, user_id).await?.is_some()),
+        None => Ok(false),
+    }
+}
+
+/// Formats a size for quota messages, e.g. "2.5 MB".
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["bytes", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Reads the quota config and adds `Quotas` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).quotas.clone();
+
+    Ok(rocket.manage(Quotas::from_config(config)))
+}
@@ -0,0 +1,216 @@
+//! Rate limits on logging in, registering, unlocking password protected lists and the API (see
+//! `crate::api::access`), so passwords can't be guessed quickly and accounts can't be made in
+//! bulk.
+//!
+//! Counts are kept in memory in fixed windows, keyed by what's counted: an IP address, or the
+//! account or list someone is trying to get into. With `persist` on, they're also saved to the
+//! database now and then and read back at launch, so restarting doesn't reset them.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::fairing::AdHoc;
+use rocket::serde::Deserialize;
+use rocket::tokio;
+use rocket::{fairing, Build, Rocket};
+use rocket_db_pools::{sqlx, Database};
+
+use crate::config::AppConfig;
+use crate::db::models::RateLimitCounter;
+use crate::db::WishlistDb;
+
+/// How often counts are saved, with `persist` on.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Login, registration and list unlock limits, read from the `rate_limits` table in Rocket.toml.
+/// Each is a number of attempts per `window_secs`, 0 turns the limit off. The API's limits are in
+/// `api_limits`, see `crate::api::access::ApiLimitsConfig`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RateLimitsConfig {
+    /// Login attempts from each IP address.
+    pub login_per_ip: usize,
+    /// Login attempts for each username or email address, from anywhere.
+    pub login_per_account: usize,
+    /// Registration attempts from each IP address.
+    pub register_per_ip: usize,
+    /// List password attempts from each IP address.
+    pub unlock_per_ip: usize,
+    /// List password attempts for each list, from anywhere.
+    pub unlock_per_list: usize,
+    /// The length of the window, in seconds.
+    pub window_secs: u64,
+    /// Whether counts are saved to the database, so they survive restarts.
+    pub persist: bool,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            login_per_ip: 20,
+            login_per_account: 10,
+            register_per_ip: 5,
+            unlock_per_ip: 20,
+            unlock_per_list: 10,
+            window_secs: 15 * 60,
+            persist: false,
+        }
+    }
+}
+
+/// How many times something was done in the current window.
+#[derive(Debug, Clone, Copy)]
+struct Count {
+    hits: usize,
+    window_ends_at: chrono::NaiveDateTime,
+}
+
+/// Counts attempts against every rate limit. Available as managed state.
+pub struct RateLimiter {
+    config: RateLimitsConfig,
+    counts: Arc<Mutex<HashMap<String, Count>>>,
+}
+
+impl RateLimiter {
+    pub fn from_config(config: RateLimitsConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Counts an attempt at `key`, or returns how many seconds to wait if there have already
+    /// been `limit` in the window. A `limit` of 0 is no limit.
+    pub fn hit(&self, key: &str, limit: usize, window: Duration) -> Result<(), u64> {
+        if limit == 0 {
+            return Ok(());
+        }
+
+        let now = Utc::now().naive_utc();
+        let mut counts = self.counts.lock().unwrap();
+        counts.retain(|_, count| count.window_ends_at > now);
+        let count = counts.entry(key.to_string()).or_insert_with(|| Count {
+            hits: 0,
+            window_ends_at: now + chrono::Duration::seconds(window.as_secs() as i64),
+        });
+        if count.hits >= limit {
+            let wait = (count.window_ends_at - now).num_seconds().max(0) as u64;
+            return Err(wait + 1);
+        }
+        count.hits += 1;
+        Ok(())
+    }
+
+    /// Counts a login attempt from `ip` for `username`, which may also be an email address.
+    pub fn check_login(&self, ip: Option<IpAddr>, username: &str) -> Result<(), u64> {
+        let window = Duration::from_secs(self.config.window_secs);
+        self.hit(&ip_key("login", ip), self.config.login_per_ip, window)?;
+        let account = format!("login:account:{}", username.trim().to_lowercase());
+        self.hit(&account, self.config.login_per_account, window)
+    }
+
+    /// Counts a registration attempt from `ip`.
+    pub fn check_register(&self, ip: Option<IpAddr>) -> Result<(), u64> {
+        let window = Duration::from_secs(self.config.window_secs);
+        self.hit(&ip_key("register", ip), self.config.register_per_ip, window)
+    }
+
+    /// Counts an attempt from `ip` at the password of the list with the key.
+    pub fn check_unlock(&self, ip: Option<IpAddr>, list_key: &str) -> Result<(), u64> {
+        let window = Duration::from_secs(self.config.window_secs);
+        self.hit(&ip_key("unlock", ip), self.config.unlock_per_ip, window)?;
+        let list = format!("unlock:list:{}", list_key);
+        self.hit(&list, self.config.unlock_per_list, window)
+    }
+
+    /// Reads back the counts saved by `spawn_persister`.
+    async fn restore(&self, pool: &sqlx::AnyPool) -> Result<(), sqlx::Error> {
+        let saved = RateLimitCounter::all_current(pool, Utc::now().naive_utc()).await?;
+        let mut counts = self.counts.lock().unwrap();
+        for counter in saved {
+            let count = Count {
+                hits: counter.hits.max(0) as usize,
+                window_ends_at: counter.window_ends_at,
+            };
+            counts.insert(counter.key, count);
+        }
+        Ok(())
+    }
+
+    /// Saves the counts to the database every `PERSIST_INTERVAL`.
+    fn spawn_persister(&self, pool: sqlx::AnyPool) {
+        let counts = self.counts.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(PERSIST_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let now = Utc::now().naive_utc();
+                let snapshot = counts
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, count)| count.window_ends_at > now)
+                    .map(|(key, count)| RateLimitCounter {
+                        key: key.clone(),
+                        hits: count.hits as i64,
+                        window_ends_at: count.window_ends_at,
+                    })
+                    .collect::<Vec<_>>();
+                if let Err(e) = RateLimitCounter::replace_all(&pool, &snapshot).await {
+                    error!("Saving rate limit counts failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// The key for something counted per IP address. Requests whose address is unknown share one.
+pub fn ip_key(what: &str, ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(ip) => format!("{}:ip:{}", what, ip),
+        None => format!("{}:ip:unknown", what),
+    }
+}
+
+/// How long to wait, for people rather than `Retry-After` headers.
+pub fn wait_message(wait_secs: u64) -> String {
+    let minutes = wait_secs.div_ceil(60);
+    if minutes <= 1 {
+        "Too many attempts, try again in a minute".to_string()
+    } else {
+        format!("Too many attempts, try again in {} minutes", minutes)
+    }
+}
+
+/// Reads the rate limits config and adds the `RateLimiter` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).rate_limits.clone();
+
+    Ok(rocket.manage(RateLimiter::from_config(config)))
+}
+
+/// With `persist` on, reads the saved counts back once the server is running and starts saving
+/// them.
+pub fn persistence() -> AdHoc {
+    AdHoc::on_liftoff("Rate Limit Persistence", |rocket| {
+        Box::pin(async move {
+            if !AppConfig::of(rocket).rate_limits.persist {
+                return;
+            }
+            let (limiter, pool) = match (rocket.state::<RateLimiter>(), WishlistDb::fetch(rocket)) {
+                (Some(limiter), Some(db)) => (limiter, (**db).clone()),
+                _ => {
+                    error!("Saving rate limits needs the database, not saving them");
+                    return;
+                }
+            };
+            if let Err(e) = limiter.restore(&pool).await {
+                error!("Reading saved rate limit counts failed: {}", e);
+            }
+            limiter.spawn_persister(pool);
+        })
+    })
+}
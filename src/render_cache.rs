@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use rocket::response::content::RawHtml;
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use rocket_dyn_templates::Template;
+
+use crate::config::AppConfig;
+
+/// The instance's render cache, set once at startup.
+static CACHE: OnceLock<RenderCache> = OnceLock::new();
+
+/// Render cache configuration, read from the `render_cache` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct RenderCacheConfig {
+    /// How long a rendered page is reused before it's rendered again, in seconds. 0 turns the
+    /// cache off.
+    pub ttl_secs: u64,
+    /// The most pages kept at once. Once it's full, the oldest page is dropped.
+    pub max_entries: usize,
+}
+
+impl Default for RenderCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 5 * 60,
+            max_entries: 1000,
+        }
+    }
+}
+
+/// Caches the rendered HTML of public list pages as anonymous visitors see them, since a widely
+/// shared list gets the same page rendered over and over.
+///
+/// Pages are dropped whenever their list or its items change, see `invalidate`. Changes are
+/// seen before they're committed, so a page rendered in between can be stale, but only until it
+/// expires.
+pub struct RenderCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<i64, (Instant, String)>>,
+}
+
+/// A page that's either rendered for this request or taken from the `RenderCache`.
+// Only ever returned from a route, so its size doesn't matter, and Rocket can't respond with a
+// boxed `Template`
+#[allow(clippy::large_enum_variant)]
+#[derive(Responder)]
+pub enum CachedPage {
+    Rendered(Template),
+    Cached(RawHtml<String>),
+}
+
+impl RenderCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> RenderCache {
+        RenderCache {
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the instance's cache, or `None` if it's turned off.
+    pub fn current() -> Option<&'static RenderCache> {
+        CACHE.get()
+    }
+
+    /// Returns the list's cached page, if it's cached and hasn't expired.
+    pub fn get(&self, list_id: i64) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(&list_id)
+            .filter(|(rendered_at, _)| rendered_at.elapsed() < self.ttl)
+            .map(|(_, html)| html.clone())
+    }
+
+    /// Caches the list's rendered page.
+    pub fn insert(&self, list_id: i64, html: String) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (rendered_at, _)| rendered_at.elapsed() < self.ttl);
+        if entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (rendered_at, _))| *rendered_at)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(list_id, (Instant::now(), html));
+    }
+
+    /// Drops the list's cached page.
+    pub fn remove(&self, list_id: i64) {
+        self.entries.lock().unwrap().remove(&list_id);
+    }
+}
+
+/// Drops the list's cached page, if there's a cache. Called whenever a list or its items change.
+pub fn invalidate(list_id: i64) {
+    if let Some(cache) = RenderCache::current() {
+        cache.remove(list_id);
+    }
+}
+
+/// Reads the render cache config and sets up the cache, unless it's turned off.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).render_cache.clone();
+
+    if config.ttl_secs > 0 && config.max_entries > 0 {
+        let ttl = Duration::from_secs(config.ttl_secs);
+        // Ignore the error if the cache was already set, e.g. by an earlier launch in the same process
+        let _ = CACHE.set(RenderCache::new(ttl, config.max_entries));
+    }
+    Ok(rocket)
+}
@@ -0,0 +1,59 @@
+use scraper::Html;
+use url::Url;
+
+use super::generic::{attr, extract_metadata, text};
+use super::{
+    fetch_html, host_matches, ItemMetadata, ItemSource, Price, PublicClient, SourceError,
+};
+
+/// Reads Amazon product pages, which don't include usable Open Graph or JSON-LD data.
+pub struct Amazon;
+
+pub(crate) const DOMAINS: &[&str] = &[
+    "amazon.com",
+    "amazon.ca",
+    "amazon.co.uk",
+    "amazon.de",
+    "amazon.fr",
+    "amazon.it",
+    "amazon.es",
+    "amazon.co.jp",
+    "amazon.com.au",
+    "amazon.in",
+];
+
+#[rocket::async_trait]
+impl ItemSource for Amazon {
+    fn name(&self) -> &'static str {
+        "amazon"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        DOMAINS.iter().any(|domain| host_matches(url, domain))
+    }
+
+    async fn fetch_metadata(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<ItemMetadata, SourceError> {
+        let html = fetch_html(client, url).await?;
+        let document = Html::parse_document(&html);
+        let fallback = extract_metadata(&document, url);
+
+        Ok(ItemMetadata {
+            title: text(&document, "#productTitle").or(fallback.title),
+            description: text(&document, "#productDescription")
+                .or_else(|| text(&document, "#feature-bullets"))
+                .or(fallback.description),
+            image_url: attr(&document, "#landingImage", "data-old-hires")
+                .or_else(|| attr(&document, "#landingImage", "src"))
+                .or_else(|| attr(&document, "#imgBlkFront", "src"))
+                .or(fallback.image_url),
+            price: text(&document, "#corePrice_feature_div .a-offscreen")
+                .or_else(|| text(&document, ".a-price .a-offscreen"))
+                .and_then(|price| Price::parse(&price, None))
+                .or(fallback.price),
+        })
+    }
+}
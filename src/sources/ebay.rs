@@ -0,0 +1,50 @@
+use scraper::Html;
+use url::Url;
+
+use super::generic::{attr, extract_metadata, text};
+use super::{
+    fetch_html, host_matches, ItemMetadata, ItemSource, Price, PublicClient, SourceError,
+};
+
+/// Reads eBay listings.
+pub struct Ebay;
+
+pub(crate) const DOMAINS: &[&str] = &[
+    "ebay.com",
+    "ebay.ca",
+    "ebay.co.uk",
+    "ebay.de",
+    "ebay.com.au",
+];
+
+#[rocket::async_trait]
+impl ItemSource for Ebay {
+    fn name(&self) -> &'static str {
+        "ebay"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        DOMAINS.iter().any(|domain| host_matches(url, domain))
+    }
+
+    async fn fetch_metadata(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<ItemMetadata, SourceError> {
+        let html = fetch_html(client, url).await?;
+        let document = Html::parse_document(&html);
+        let fallback = extract_metadata(&document, url);
+
+        let currency = attr(&document, "[itemprop='priceCurrency']", "content");
+        Ok(ItemMetadata {
+            title: text(&document, "h1.x-item-title__mainTitle").or(fallback.title),
+            description: fallback.description,
+            image_url: attr(&document, ".ux-image-carousel-item img", "src").or(fallback.image_url),
+            price: attr(&document, "[itemprop='price']", "content")
+                .or_else(|| text(&document, ".x-price-primary"))
+                .and_then(|price| Price::parse(&price, currency.as_deref()))
+                .or(fallback.price),
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use scraper::Html;
+use url::Url;
+
+use super::generic::{extract_metadata, text};
+use super::{
+    fetch_html, host_matches, ItemMetadata, ItemSource, Price, PublicClient, SourceError,
+};
+
+/// Reads Etsy listings.
+pub struct Etsy;
+
+#[rocket::async_trait]
+impl ItemSource for Etsy {
+    fn name(&self) -> &'static str {
+        "etsy"
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        host_matches(url, "etsy.com")
+    }
+
+    async fn fetch_metadata(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<ItemMetadata, SourceError> {
+        let html = fetch_html(client, url).await?;
+        let document = Html::parse_document(&html);
+        let fallback = extract_metadata(&document, url);
+
+        Ok(ItemMetadata {
+            title: text(&document, "h1[data-buy-box-listing-title]").or(fallback.title),
+            description: fallback.description,
+            image_url: fallback.image_url,
+            // The JSON-LD offer is the most reliable, the buy box price includes sale text
+            price: fallback.price.or_else(|| {
+                text(&document, "[data-buy-box-region='price'] .wt-text-title-larger")
+                    .and_then(|price| Price::parse(&price, None))
+            }),
+        })
+    }
+}
@@ -0,0 +1,164 @@
+use rocket::serde::json::Value;
+use scraper::{Html, Selector};
+use url::Url;
+
+use super::{fetch_html, ItemMetadata, ItemSource, Price, PublicClient, SourceError};
+
+/// Reads any page with Open Graph tags, `<meta>` tags or JSON-LD product data.
+///
+/// This is the fallback for URLs no retailer-specific source matches.
+pub struct Generic;
+
+#[rocket::async_trait]
+impl ItemSource for Generic {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn matches(&self, _url: &Url) -> bool {
+        true
+    }
+
+    async fn fetch_metadata(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<ItemMetadata, SourceError> {
+        let html = fetch_html(client, url).await?;
+        Ok(extract_metadata(&Html::parse_document(&html), url))
+    }
+}
+
+/// Extracts item details from the page's JSON-LD product data, Open Graph tags and `<meta>` tags,
+/// in that order of preference.
+pub(crate) fn extract_metadata(document: &Html, url: &Url) -> ItemMetadata {
+    let product = json_ld_product(document);
+    let from_product = |key: &str| {
+        product
+            .as_ref()
+            .and_then(|p| p.get(key))
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+    };
+
+    let title = from_product("name")
+        .or_else(|| meta(document, "og:title"))
+        .or_else(|| meta(document, "twitter:title"))
+        .or_else(|| text(document, "title"));
+
+    let description = from_product("description")
+        .or_else(|| meta(document, "og:description"))
+        .or_else(|| meta(document, "description"));
+
+    let image_url = product
+        .as_ref()
+        .and_then(|p| p.get("image"))
+        .and_then(json_ld_image)
+        .or_else(|| meta(document, "og:image"))
+        .or_else(|| meta(document, "twitter:image"))
+        .and_then(|image| url.join(&image).ok())
+        .map(|image| image.to_string());
+
+    let price = product
+        .as_ref()
+        .and_then(|p| p.get("offers"))
+        .and_then(json_ld_price)
+        .or_else(|| {
+            let amount = meta(document, "product:price:amount")
+                .or_else(|| meta(document, "og:price:amount"))?;
+            let currency = meta(document, "product:price:currency")
+                .or_else(|| meta(document, "og:price:currency"));
+            Price::parse(&amount, currency.as_deref())
+        });
+
+    ItemMetadata {
+        title,
+        description,
+        image_url,
+        price,
+    }
+}
+
+/// Returns the content of the `<meta>` tag with the given property or name.
+pub(crate) fn meta(document: &Html, name: &str) -> Option<String> {
+    let selector =
+        Selector::parse(&format!("meta[property='{0}'], meta[name='{0}']", name)).ok()?;
+    document
+        .select(&selector)
+        .find_map(|element| element.value().attr("content"))
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+/// Returns the text of the first element matching the selector.
+pub(crate) fn text(document: &Html, selector: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .next()
+        .map(|element| element.text().collect::<String>().trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Returns the value of an attribute of the first element matching the selector.
+pub(crate) fn attr(document: &Html, selector: &str, attr: &str) -> Option<String> {
+    let selector = Selector::parse(selector).ok()?;
+    document
+        .select(&selector)
+        .find_map(|element| element.value().attr(attr))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Finds the first JSON-LD object with a `@type` of `Product`.
+fn json_ld_product(document: &Html) -> Option<Value> {
+    let selector = Selector::parse("script[type='application/ld+json']").ok()?;
+    document.select(&selector).find_map(|script| {
+        let json = rocket::serde::json::from_str::<Value>(&script.text().collect::<String>()).ok()?;
+        find_product(json)
+    })
+}
+
+fn find_product(value: Value) -> Option<Value> {
+    match value {
+        Value::Array(values) => values.into_iter().find_map(find_product),
+        Value::Object(mut object) => {
+            let is_product = match object.get("@type") {
+                Some(Value::String(t)) => t == "Product",
+                Some(Value::Array(types)) => types.iter().any(|t| t == "Product"),
+                _ => false,
+            };
+            if is_product {
+                Some(Value::Object(object))
+            } else {
+                object.remove("@graph").and_then(find_product)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn json_ld_image(image: &Value) -> Option<String> {
+    match image {
+        Value::String(url) => Some(url.clone()),
+        Value::Array(images) => images.iter().find_map(json_ld_image),
+        Value::Object(object) => object.get("url").and_then(json_ld_image),
+        _ => None,
+    }
+}
+
+fn json_ld_price(offers: &Value) -> Option<Price> {
+    match offers {
+        Value::Array(offers) => offers.iter().find_map(json_ld_price),
+        Value::Object(offer) => {
+            let amount = match offer.get("price").or_else(|| offer.get("lowPrice"))? {
+                Value::String(price) => price.clone(),
+                Value::Number(price) => format!("{:.2}", price.as_f64()?),
+                _ => return None,
+            };
+            let currency = offer.get("priceCurrency").and_then(|c| c.as_str());
+            Price::parse(&amount, currency)
+        }
+        _ => None,
+    }
+}
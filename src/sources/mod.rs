@@ -0,0 +1,444 @@
+use std::borrow::Cow;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use url::Url;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+use thiserror::Error;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::config::AppConfig;
+use crate::util;
+
+pub(crate) mod amazon;
+pub(crate) mod ebay;
+mod etsy;
+mod generic;
+
+pub use amazon::Amazon;
+pub use ebay::Ebay;
+pub use etsy::Etsy;
+pub use generic::Generic;
+
+/// Item source configuration, read from the `sources` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SourcesConfig {
+    /// How long to wait for a retailer's page, in seconds.
+    pub timeout_secs: u64,
+    /// The user agent sent to retailers.
+    pub user_agent: String,
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 10,
+            user_agent: concat!("Mozilla/5.0 (compatible; wishlist-rs/", env!("CARGO_PKG_VERSION"), ")")
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SourceError {
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Invalid URL: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("No item found at this URL")]
+    NotFound,
+    #[error("Only public http and https URLs can be fetched")]
+    NotPublic,
+    #[error("The file is too large")]
+    TooLarge,
+    #[error("Too many redirects")]
+    TooManyRedirects,
+}
+
+/// A price in the smallest unit of its currency (e.g. cents, or yen for JPY).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct Price {
+    pub amount_cents: i64,
+    /// An ISO 4217 currency code.
+    pub currency: String,
+}
+
+impl Price {
+    /// Parses a displayed price like "$1,299.99" or "12,50 €".
+    ///
+    /// The currency comes from `currency_hint` if given, otherwise from the symbol in the text.
+    pub fn parse(text: &str, currency_hint: Option<&str>) -> Option<Price> {
+        let currency = match currency_hint {
+            Some(currency) => currency.to_ascii_uppercase(),
+            None if text.contains('£') => "GBP".to_string(),
+            None if text.contains('€') => "EUR".to_string(),
+            None if text.contains('¥') => "JPY".to_string(),
+            None => "USD".to_string(),
+        };
+
+        let number = text
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+            .collect::<String>();
+
+        // Treat the last separator as the decimal point if it's followed by one or two digits
+        let (whole, fraction) = match number.rfind(['.', ',']) {
+            Some(i) if number.len() - i <= 3 => (&number[..i], &number[i + 1..]),
+            _ => (number.as_str(), ""),
+        };
+        let whole = whole.replace(['.', ','], "");
+        if whole.is_empty() && fraction.is_empty() {
+            return None;
+        }
+
+        let whole = if whole.is_empty() { 0 } else { whole.parse::<i64>().ok()? };
+        // Currencies without a minor unit, like JPY, are stored as they are. Any fraction is
+        // dropped, e.g. "¥1299.00".
+        if util::currency_decimals(&currency) == 0 {
+            return Some(Price {
+                amount_cents: whole,
+                currency,
+            });
+        }
+        let fraction = match fraction.len() {
+            0 => 0,
+            1 => fraction.parse::<i64>().ok()? * 10,
+            _ => fraction.parse::<i64>().ok()?,
+        };
+        Some(Price {
+            amount_cents: whole.checked_mul(100)?.checked_add(fraction)?,
+            currency,
+        })
+    }
+}
+
+/// Details about an item found at a URL, used to prefill items and refresh prices.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub price: Option<Price>,
+}
+
+/// A site items can be imported from.
+///
+/// Implement this to teach the scraper and price tracker about a new retailer, then add it to
+/// the `SourceRegistry`.
+#[rocket::async_trait]
+pub trait ItemSource: Send + Sync {
+    /// A short name for the source, e.g. "amazon".
+    fn name(&self) -> &'static str;
+
+    /// Returns true if this source knows how to read the URL.
+    fn matches(&self, url: &Url) -> bool;
+
+    /// Fetches the item's details from the URL.
+    async fn fetch_metadata(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<ItemMetadata, SourceError>;
+
+    /// Fetches the item's current price from the URL.
+    async fn fetch_price(
+        &self,
+        client: &PublicClient,
+        url: &Url,
+    ) -> Result<Option<Price>, SourceError> {
+        Ok(self.fetch_metadata(client, url).await?.price)
+    }
+}
+
+/// Fetches a page's HTML.
+pub(crate) async fn fetch_html(client: &PublicClient, url: &Url) -> Result<String, SourceError> {
+    let response = client.get(url).await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(SourceError::NotFound);
+    }
+    Ok(response.error_for_status()?.text().await?)
+}
+
+/// Checks that the URL is http(s) and its host only resolves to public addresses, so users can't
+/// have the server fetch pages from its own network. Returns the addresses, so the request can be
+/// made to the ones that were checked, see `PublicClient`.
+pub(crate) async fn check_public(url: &Url) -> Result<Vec<SocketAddr>, SourceError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(SourceError::NotPublic);
+    }
+    let host = url.host_str().ok_or(SourceError::NotPublic)?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs = rocket::tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| SourceError::NotFound)?
+        .collect::<Vec<_>>();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(SourceError::NotPublic);
+    }
+    Ok(addrs)
+}
+
+/// Checks a URL the server will send requests to, like a webhook's, with `check_public` when
+/// it's saved, so the problem is reported as a validation error on `field`. The validator can't
+/// look names up, since that's async.
+pub async fn validate_public(
+    url: &str,
+    field: &'static str,
+    message: &'static str,
+) -> Result<(), ValidationErrors> {
+    let public = match Url::parse(url) {
+        Ok(url) => check_public(&url).await.is_ok(),
+        Err(_) => false,
+    };
+    if public {
+        return Ok(());
+    }
+    let mut err = ValidationError::new(field);
+    err.message = Some(Cow::from(message));
+    let mut errors = ValidationErrors::new();
+    errors.add(field, err);
+    Err(errors)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                // Shared address space, used for carrier-grade NAT
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local and link-local addresses
+                    || (ip.segments()[0] & 0xfe00) == 0xfc00
+                    || (ip.segments()[0] & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Returns true if the URL's host is `domain` or one of its subdomains.
+pub(crate) fn host_matches(url: &Url, domain: &str) -> bool {
+    match url.host_str() {
+        Some(host) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => false,
+    }
+}
+
+/// The most redirects `PublicClient` follows for one request.
+const MAX_REDIRECTS: usize = 10;
+
+/// An HTTP client that only talks to public hosts, for fetching URLs users give us.
+///
+/// Every hop, redirects included, is checked with `check_public`, and the request is sent to
+/// the addresses that were checked rather than looking the name up again, so a host can't pass
+/// the check and then resolve to the server's own network.
+#[derive(Clone, Debug)]
+pub struct PublicClient {
+    timeout: Duration,
+    user_agent: String,
+}
+
+impl PublicClient {
+    pub fn new(timeout: Duration, user_agent: &str) -> PublicClient {
+        PublicClient {
+            timeout,
+            user_agent: user_agent.to_string(),
+        }
+    }
+
+    pub async fn get(&self, url: &Url) -> Result<reqwest::Response, SourceError> {
+        self.send(reqwest::Method::GET, url).await
+    }
+
+    pub async fn head(&self, url: &Url) -> Result<reqwest::Response, SourceError> {
+        self.send(reqwest::Method::HEAD, url).await
+    }
+
+    /// Sends the request, following redirects by hand.
+    async fn send(
+        &self,
+        mut method: reqwest::Method,
+        url: &Url,
+    ) -> Result<reqwest::Response, SourceError> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self
+                .pinned(&url)
+                .await?
+                .request(method.clone(), url.clone())
+                .send()
+                .await?;
+
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok());
+            let next = match location {
+                Some(location) if response.status().is_redirection() => url.join(location)?,
+                _ => return Ok(response),
+            };
+
+            // Like browsers, only 307 and 308 keep the method
+            if !matches!(response.status().as_u16(), 307 | 308) && method != reqwest::Method::HEAD {
+                method = reqwest::Method::GET;
+            }
+            url = next;
+        }
+        Err(SourceError::TooManyRedirects)
+    }
+
+    /// Returns a client that can only reach the URL's host at its checked addresses, and doesn't
+    /// follow redirects, for requests that shouldn't be sent anywhere else, like webhook posts.
+    pub async fn pinned(&self, url: &Url) -> Result<reqwest::Client, SourceError> {
+        let addrs = check_public(url).await?;
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .user_agent(&self.user_agent)
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(url::Host::Domain(host)) = url.host() {
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// The sources items can be imported from. Available as managed state.
+pub struct SourceRegistry {
+    client: PublicClient,
+    sources: Vec<Box<dyn ItemSource>>,
+    fallback: Generic,
+}
+
+impl SourceRegistry {
+    /// Creates an empty registry that only understands generic Open Graph pages.
+    pub fn new(config: &SourcesConfig) -> Result<SourceRegistry, SourceError> {
+        let client = PublicClient::new(
+            Duration::from_secs(config.timeout_secs),
+            &config.user_agent,
+        );
+
+        Ok(SourceRegistry {
+            client,
+            sources: vec![],
+            fallback: Generic,
+        })
+    }
+
+    /// Creates a registry with all the built-in retailers.
+    pub fn with_defaults(config: &SourcesConfig) -> Result<SourceRegistry, SourceError> {
+        let mut registry = SourceRegistry::new(config)?;
+        registry.register(Box::new(Amazon));
+        registry.register(Box::new(Ebay));
+        registry.register(Box::new(Etsy));
+        Ok(registry)
+    }
+
+    /// Adds a source. Sources registered first take priority.
+    pub fn register(&mut self, source: Box<dyn ItemSource>) {
+        self.sources.push(source);
+    }
+
+    /// The names of the registered sources, in priority order.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sources.iter().map(|source| source.name()).collect()
+    }
+
+    /// Returns the source for the URL, falling back to the generic source.
+    pub fn find(&self, url: &Url) -> &dyn ItemSource {
+        self.sources
+            .iter()
+            .find(|source| source.matches(url))
+            .map(|source| source.as_ref())
+            .unwrap_or(&self.fallback)
+    }
+
+    /// Fetches the item's details using the matching source.
+    pub async fn fetch_metadata(&self, url: &str) -> Result<ItemMetadata, SourceError> {
+        let url = Url::parse(url)?;
+        self.find(&url).fetch_metadata(&self.client, &url).await
+    }
+
+    /// Fetches the item's current price using the matching source.
+    pub async fn fetch_price(&self, url: &str) -> Result<Option<Price>, SourceError> {
+        let url = Url::parse(url)?;
+        self.find(&url).fetch_price(&self.client, &url).await
+    }
+
+    /// Downloads a file found by `fetch_metadata`, like an item's image, giving up once it's
+    /// bigger than `max_size` bytes.
+    pub async fn fetch_file(&self, url: &str, max_size: u64) -> Result<Vec<u8>, SourceError> {
+        let url = Url::parse(url)?;
+        let mut response = self.client.get(&url).await?.error_for_status()?;
+        if response.content_length().unwrap_or(0) > max_size {
+            return Err(SourceError::TooLarge);
+        }
+        let mut data = vec![];
+        while let Some(chunk) = response.chunk().await? {
+            if (data.len() + chunk.len()) as u64 > max_size {
+                return Err(SourceError::TooLarge);
+            }
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+/// Reads the sources config and adds the `SourceRegistry` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).sources.clone();
+
+    match SourceRegistry::with_defaults(&config) {
+        Ok(registry) => {
+            info!("Reading items from {}", registry.names().join(", "));
+            Ok(rocket.manage(registry))
+        }
+        Err(e) => {
+            error!("Failed to configure item sources: {}", e);
+            Err(rocket)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, currency: Option<&str>) -> Option<i64> {
+        Price::parse(text, currency).map(|price| price.amount_cents)
+    }
+
+    #[test]
+    fn prices_are_parsed_in_cents() {
+        assert_eq!(parse("$1,299.99", None), Some(129_999));
+        assert_eq!(parse("12,50 €", None), Some(1250));
+        assert_eq!(parse("£5", None), Some(500));
+        assert_eq!(parse("7.5", Some("usd")), Some(750));
+    }
+
+    #[test]
+    fn zero_decimal_currencies_are_parsed_in_whole_units() {
+        assert_eq!(parse("¥1,299", None), Some(1299));
+        assert_eq!(parse("¥1299.00", None), Some(1299));
+        assert_eq!(parse("₩15,000", Some("KRW")), Some(15_000));
+    }
+
+    #[test]
+    fn prices_too_large_to_store_are_rejected() {
+        assert_eq!(parse("$92233720368547758.08", None), None);
+        assert_eq!(parse("$999999999999999999999", None), None);
+        assert_eq!(parse("¥9223372036854775807", None), Some(i64::MAX));
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use rocket_db_pools::Connection;
+
+use crate::config::AppConfig;
+use crate::db::models::UserStats;
+use crate::db::{DataError, WishlistDb};
+
+/// User stats configuration, read from the `stats` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct StatsConfig {
+    /// How long computed stats are reused before they're recomputed, in seconds.
+    pub cache_ttl_secs: u64,
+    /// How many of the user's most used tags the stats show.
+    pub top_tags: i64,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: 60 * 60,
+            top_tags: 5,
+        }
+    }
+}
+
+/// Caches computed user stats, since the aggregate queries scan all of a user's items.
+/// Available as managed state.
+pub struct StatsCache {
+    ttl: Duration,
+    top_tags: i64,
+    entries: Mutex<HashMap<(i64, i32), (Instant, UserStats)>>,
+}
+
+impl StatsCache {
+    pub fn new(ttl: Duration, top_tags: i64) -> StatsCache {
+        StatsCache {
+            ttl,
+            top_tags,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the user's stats for the year, computing them if they aren't cached or are stale.
+    pub async fn get(
+        &self,
+        conn: &mut Connection<WishlistDb>,
+        user_id: i64,
+        year: i32,
+    ) -> Result<UserStats, DataError> {
+        if let Some((computed_at, stats)) = self.entries.lock().unwrap().get(&(user_id, year)) {
+            if computed_at.elapsed() < self.ttl {
+                return Ok(stats.clone());
+            }
+        }
+
+        let stats = UserStats::compute(conn, user_id, year, self.top_tags).await?;
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (computed_at, _)| computed_at.elapsed() < self.ttl);
+        entries.insert((user_id, year), (Instant::now(), stats.clone()));
+
+        Ok(stats)
+    }
+}
+
+/// Reads the stats config and adds the `StatsCache` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).stats.clone();
+
+    let ttl = Duration::from_secs(config.cache_ttl_secs);
+
+    Ok(rocket.manage(StatsCache::new(ttl, config.top_tags)))
+}
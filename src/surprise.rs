@@ -0,0 +1,253 @@
+use rocket::serde::{Serialize, Serializer};
+
+use crate::db::models::{List, SharePermission};
+
+/// Who is looking at a list, for deciding whether they can see what people are doing about its
+/// items: gift splits, cash fund contributions and date polls. List owners can't, so their gifts
+/// stay a surprise, unless they've turned on `List::reveal_gifting`.
+///
+/// Every handler that shows or changes that data goes through here rather than comparing user
+/// IDs itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewer {
+    /// The list's owner.
+    Owner,
+    /// The list's owner, on a list where they've asked to see gifting activity.
+    RevealedOwner,
+    /// Anyone else, logged in or not.
+    Guest,
+}
+
+/// What a handler does with gifting activity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Only shows it.
+    View,
+    /// Joins in or changes it, which owners never can.
+    TakePart,
+}
+
+impl Viewer {
+    /// Works out who the user is to the list.
+    pub fn of(list: &List, user_id: Option<i64>) -> Viewer {
+        match (list.user_id, user_id) {
+            (Some(owner_id), Some(user_id)) if owner_id == user_id => {
+                if list.reveal_gifting {
+                    Viewer::RevealedOwner
+                } else {
+                    Viewer::Owner
+                }
+            }
+            _ => Viewer::Guest,
+        }
+    }
+
+    /// Whether the viewer can see gifting activity on the list.
+    pub fn sees_gifting(&self) -> bool {
+        *self != Viewer::Owner
+    }
+
+    /// Whether the viewer is allowed the given access.
+    pub fn allows(&self, access: Access) -> bool {
+        match access {
+            Access::View => self.sees_gifting(),
+            Access::TakePart => *self == Viewer::Guest,
+        }
+    }
+
+    /// Wraps gifting data so it's only ever shown to viewers allowed to see it.
+    pub fn conceal<T>(&self, value: T) -> Concealed<T> {
+        Concealed(Some(value).filter(|_| self.sees_gifting()))
+    }
+}
+
+impl Access {
+    /// What a share has to allow for the access, on lists the user couldn't see without one.
+    /// Taking part is like claiming an item.
+    pub fn share_permission(&self) -> SharePermission {
+        match self {
+            Access::View => SharePermission::View,
+            Access::TakePart => SharePermission::Claim,
+        }
+    }
+}
+
+/// Returns the list if the user is allowed the given access to gifting activity on it, so
+/// handlers can 404 otherwise.
+pub fn gifting_list(list: Option<List>, user_id: i64, access: Access) -> Option<List> {
+    list.filter(|list| Viewer::of(list, Some(user_id)).allows(access))
+}
+
+/// Gifting data for a page. Serializes to `null` for viewers who can't see it.
+pub struct Concealed<T>(Option<T>);
+
+impl<T: Serialize> Serialize for Concealed<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use std::io::{Cursor, Read};
+    use std::time::Duration;
+
+    use rocket::http::{Header, Status};
+    use rocket::local::asynchronous::LocalResponse;
+    use rocket::tokio::io::AsyncReadExt;
+    use rocket::tokio::time;
+    use rocket_db_pools::sqlx;
+
+    use crate::db::models::AccountExport;
+    use crate::testing::TestApp;
+
+    /// Reads what the response sends straight away. Event streams never end, so they can't be
+    /// read to the end.
+    async fn read_for_a_moment(response: LocalResponse<'_>) -> String {
+        let mut response = Box::pin(response);
+        let mut body = vec![];
+        let _ = time::timeout(Duration::from_millis(500), response.read_to_end(&mut body)).await;
+        String::from_utf8_lossy(&body).into_owned()
+    }
+
+    async fn get(app: &TestApp, uri: &str) -> String {
+        let response = app.client().get(uri).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{} is found", uri);
+        response.into_string().await.unwrap_or_default()
+    }
+
+    fn mentions_claims(body: &str) -> bool {
+        body.to_lowercase().contains("claim")
+    }
+
+    /// Alice's Birthday, with Headphones claimed by bob.
+    async fn claimed_list(app: &TestApp) -> (String, i64) {
+        let key = app.list_key("Alice's Birthday").await;
+        let id = app.item_id("Headphones").await;
+        let response = app
+            .client()
+            .post(format!("/api/v1/lists/{}/items/{}/claim", key, id))
+            .header(app.api_token("bob").await)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Created, "bob can claim the item");
+        (key, id)
+    }
+
+    /// The list's pages that show claims to everyone but the owner, by name.
+    async fn claim_pages(app: &TestApp, key: &str, id: i64) -> Vec<(&'static str, String)> {
+        let events = app
+            .client()
+            .get(format!("/lists/{}/events", key))
+            .header(Header::new("Last-Event-ID", "0"))
+            .dispatch()
+            .await;
+        vec![
+            ("list", get(app, &format!("/lists/{}", key)).await),
+            ("items", get(app, &format!("/lists/{}/items", key)).await),
+            ("item", get(app, &format!("/lists/{}/items/{}", key, id)).await),
+            ("events", read_for_a_moment(events).await),
+        ]
+    }
+
+    #[rocket::async_test]
+    async fn owner_pages_leave_out_claims() {
+        let app = TestApp::new().await;
+        let (key, id) = claimed_list(&app).await;
+
+        // Everyone else sees the claim, so the pages are checking something
+        app.login("admin").await;
+        for (name, body) in claim_pages(&app, &key, id).await {
+            assert!(mentions_claims(&body), "the {} shows claims to guests", name);
+        }
+
+        app.login("alice").await;
+        for (name, body) in claim_pages(&app, &key, id).await {
+            assert!(!mentions_claims(&body), "the {} hides claims from the owner", name);
+        }
+        let response = app
+            .client()
+            .get(format!("/lists/{}/items/{}/claim/history", key, id))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn owner_api_leaves_out_claims() {
+        let app = TestApp::new().await;
+        let (key, id) = claimed_list(&app).await;
+        let alice = app.api_token("alice").await;
+
+        for uri in [
+            format!("/api/v1/lists/{}", key),
+            format!("/api/v1/lists/{}/items", key),
+            format!("/api/v1/lists/{}/items/{}", key, id),
+        ] {
+            let response = app.client().get(uri.as_str()).header(alice.clone()).dispatch().await;
+            assert_eq!(response.status(), Status::Ok, "{} is found", uri);
+            let body = response.into_string().await.unwrap_or_default();
+            assert!(!mentions_claims(&body), "{} hides claims from the owner", uri);
+        }
+
+        let status = format!("/api/v1/lists/{}/items/{}/claim", key, id);
+        let response = app.client().get(status.as_str()).header(alice.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let response = app.client().post(status.as_str()).header(alice.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[rocket::async_test]
+    async fn owner_feeds_and_exports_leave_out_claims() {
+        let app = TestApp::new().await;
+        let (key, _) = claimed_list(&app).await;
+        app.login("alice").await;
+
+        for uri in [
+            format!("/lists/{}/feed.atom", key),
+            format!("/lists/{}/price-drops.rss", key),
+            format!("/lists/{}/export.json", key),
+            format!("/lists/{}/export.csv", key),
+        ] {
+            let body = get(&app, &uri).await;
+            assert!(!mentions_claims(&body), "{} hides claims from the owner", uri);
+        }
+
+        // The account archive is made in the background
+        let (status, _, _) = app.post_form("/account/export", "").await;
+        assert_eq!(status, Status::SeeOther);
+        let mut token = None;
+        for _ in 0..50 {
+            token = sqlx::query_scalar(
+                r#"
+                SELECT e.token
+                FROM account_exports e
+                JOIN users u ON u.id = e.user_id
+                WHERE u.username = 'alice' AND e.status = $1
+                "#,
+            )
+            .bind(AccountExport::READY)
+            .fetch_optional(app.pool())
+            .await
+            .expect("exports can be read");
+            if token.is_some() {
+                break;
+            }
+            time::sleep(Duration::from_millis(100)).await;
+        }
+        let token: String = token.expect("the archive is made");
+
+        let response = app.client().get(format!("/account/export/{}", token)).dispatch().await;
+        let archive = response.into_bytes().await.expect("the archive is downloaded");
+        let mut archive = zip::ZipArchive::new(Cursor::new(archive)).expect("the archive is a zip");
+        for name in ["lists.json", "items.json"] {
+            let mut body = String::new();
+            archive
+                .by_name(name)
+                .expect("the archive has the file")
+                .read_to_string(&mut body)
+                .expect("the file is text");
+            assert!(!mentions_claims(&body), "{} hides claims from the owner", name);
+        }
+    }
+}
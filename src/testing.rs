@@ -0,0 +1,160 @@
+//! Runs the whole app for tests, on a database of its own with the seed data from `crate::dev`.
+//! Requests go through Rocket's local client, which keeps cookies between them like a browser.
+//! The benchmarks use it too, through the `testing` feature.
+
+use std::path::PathBuf;
+
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+use rocket::Config;
+use rocket_db_pools::{sqlx, Database};
+
+use crate::db::models::TOKEN_PREFIX;
+use crate::db::WishlistDb;
+use crate::dev;
+use crate::util;
+
+/// The app, started on an empty database in a directory of its own and filled with the seed
+/// data. The directory is removed when it's dropped.
+pub struct TestApp {
+    client: Client,
+    dir: PathBuf,
+}
+
+impl TestApp {
+    pub async fn new() -> TestApp {
+        let dir = std::env::temp_dir().join(format!("wishlist-test-{}", util::random_token()));
+        std::fs::create_dir_all(&dir).expect("test directory can be made");
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        // Jobs would race the tests for the database, and nothing here needs them
+        let figment = Figment::from(Config::debug_default())
+            .merge(("log_level", "off"))
+            .merge(("databases.wishlists.url", format!("sqlite:{}", path("wishlists.sqlite"))))
+            .merge(("images.upload_dir", path("uploads")))
+            .merge(("images.storage_dir", path("images")))
+            .merge(("exports.export_dir", path("exports")))
+            .merge(("jobs.link_check_interval_secs", 0))
+            .merge(("jobs.price_check_interval_secs", 0))
+            .merge(("jobs.search_index_interval_secs", 0))
+            .merge(("jobs.saved_search_interval_secs", 0));
+
+        let client = Client::tracked(crate::app(rocket::custom(figment)))
+            .await
+            .expect("app starts");
+        let app = TestApp { client, dir };
+        dev::seed(app.pool()).await.expect("seed data can be added");
+        app
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn pool(&self) -> &sqlx::AnyPool {
+        WishlistDb::fetch(self.client.rocket()).expect("database is attached")
+    }
+
+    /// The key of the seeded list with the title.
+    pub async fn list_key(&self, title: &str) -> String {
+        sqlx::query_scalar(r#"SELECT key FROM lists WHERE title = $1"#)
+            .bind(title)
+            .fetch_one(self.pool())
+            .await
+            .expect("list is seeded")
+    }
+
+    /// The ID of the seeded item with the title.
+    pub async fn item_id(&self, title: &str) -> i64 {
+        sqlx::query_scalar(r#"SELECT id FROM items WHERE title = $1"#)
+            .bind(title)
+            .fetch_one(self.pool())
+            .await
+            .expect("item is seeded")
+    }
+
+    /// The visitor's CSRF token, read from a page like a script would.
+    pub async fn csrf_token(&self) -> String {
+        let page = self
+            .client
+            .get("/")
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .expect("page has a body");
+        let start = page
+            .find(r#"<meta name="csrf-token" content=""#)
+            .expect("page has a CSRF token")
+            + r#"<meta name="csrf-token" content=""#.len();
+        let len = page[start..].find('"').expect("token is quoted");
+        page[start..start + len].to_string()
+    }
+
+    /// Posts a form the way a browser does, with the visitor's CSRF token in the form. Rocket only
+    /// reads `_method` as the first field, so the token goes after it.
+    pub async fn post_form(&self, uri: &str, form: &str) -> (Status, Option<String>, String) {
+        let token = format!("csrf_token={}", self.csrf_token().await);
+        let (method, form) = match form.strip_prefix("_method=") {
+            Some(rest) => match rest.split_once('&') {
+                Some((method, form)) => (Some(method), form),
+                None => (Some(rest), ""),
+            },
+            None => (None, form),
+        };
+        let body = method
+            .map(|method| format!("_method={}", method))
+            .into_iter()
+            .chain(Some(token))
+            .chain(Some(form.to_string()).filter(|form| !form.is_empty()))
+            .collect::<Vec<_>>()
+            .join("&");
+        let response = self
+            .client
+            .post(uri)
+            .header(ContentType::Form)
+            .body(body)
+            .dispatch()
+            .await;
+        let status = response.status();
+        let location = response.headers().get_one("Location").map(str::to_string);
+        let body = response.into_string().await.unwrap_or_default();
+        (status, location, body)
+    }
+
+    /// Logs in as the seeded user, logging out whoever was logged in first.
+    pub async fn login(&self, username: &str) {
+        self.post_form("/logout", "").await;
+        let form = format!("username={}&password={}", username, dev::SEED_PASSWORD);
+        let (status, _, _) = self.post_form("/login", &form).await;
+        assert_eq!(status, Status::SeeOther, "{} can log in", username);
+    }
+
+    /// Makes an API token for the seeded user that can do everything they can, for
+    /// `Authorization` headers.
+    pub async fn api_token(&self, username: &str) -> Header<'static> {
+        let token = format!("{}{}", TOKEN_PREFIX, util::random_token());
+        sqlx::query(
+            r#"
+            INSERT INTO api_tokens (user_id, name, token_hash, token_prefix, scopes, created_at, updated_at)
+            SELECT id, 'tests', $2, $3, 'admin', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+            FROM users
+            WHERE username = $1
+            "#,
+        )
+        .bind(username)
+        .bind(util::hash_token(&token))
+        .bind(&token[..TOKEN_PREFIX.len() + 6])
+        .execute(self.pool())
+        .await
+        .expect("token can be added");
+        Header::new("Authorization", format!("Bearer {}", token))
+    }
+}
+
+impl Drop for TestApp {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::{fairing, Build, Rocket};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::config::AppConfig;
+
+/// How long a proof-of-work challenge can be solved for, in seconds.
+const CHALLENGE_TTL_SECS: i64 = 10 * 60;
+
+/// Configuration for throttling lists made without logging in, read from the `throttle` table in
+/// Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ThrottleConfig {
+    /// How many lists one IP address can make without logging in per window. 0 turns the limit
+    /// off.
+    pub anonymous_lists: usize,
+    /// The length of the window, in seconds.
+    pub window_secs: u64,
+    /// What visitors who aren't logged in have to pass to make a list.
+    pub challenge: ChallengeKind,
+    /// How many leading zero bits proof-of-work hashes need, used when
+    /// `challenge = "proof_of_work"`. Each extra bit doubles the work.
+    pub pow_difficulty: u32,
+    /// The captcha provider's public site key, used when `challenge = "captcha"`.
+    pub captcha_site_key: Option<String>,
+    /// The captcha provider's secret key.
+    pub captcha_secret: Option<String>,
+    /// The provider's verification endpoint, like `https://hcaptcha.com/siteverify`. hCaptcha,
+    /// Turnstile and reCAPTCHA all work, since they take the same request.
+    pub captcha_verify_url: Option<String>,
+    /// The provider's widget script, like `https://js.hcaptcha.com/1/api.js`.
+    pub captcha_script_url: Option<String>,
+    /// The class the provider's widget looks for, like `h-captcha`, `cf-turnstile` or
+    /// `g-recaptcha`.
+    pub captcha_widget_class: String,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            anonymous_lists: 5,
+            window_secs: 60 * 60,
+            challenge: ChallengeKind::None,
+            pow_difficulty: 18,
+            captcha_site_key: None,
+            captcha_secret: None,
+            captcha_verify_url: None,
+            captcha_script_url: None,
+            captcha_widget_class: "h-captcha".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ChallengeKind {
+    None,
+    ProofOfWork,
+    Captcha,
+}
+
+#[derive(Error, Debug)]
+pub enum ThrottleError {
+    #[error(
+        "Too many lists have been made from your address. Log in, or try again in {0} minutes."
+    )]
+    TooMany(u64),
+    #[error("Couldn't confirm you're not a bot, please try again")]
+    ChallengeFailed,
+    #[error("Couldn't reach the captcha provider, please try again")]
+    Captcha(#[from] reqwest::Error),
+    #[error("Captcha is not configured: {0}")]
+    Config(&'static str),
+}
+
+/// What the list form needs to show the challenge.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Challenge {
+    pub kind: ChallengeKind,
+    /// The proof-of-work token to find a nonce for.
+    pub token: Option<String>,
+    pub difficulty: Option<u32>,
+    pub site_key: Option<String>,
+    pub script_url: Option<String>,
+    pub widget_class: Option<String>,
+}
+
+/// The visitor's answer to a challenge: the proof-of-work token and nonce, or just the captcha
+/// provider's response.
+pub struct ChallengeAnswer<'a> {
+    pub token: Option<&'a str>,
+    pub response: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct CaptchaVerification {
+    success: bool,
+}
+
+/// Limits how many lists each IP address can make without logging in, and checks the configured
+/// challenge. Logged in users aren't throttled. Available as managed state.
+///
+/// Counts are kept in memory, so they reset when the server restarts.
+pub struct ListThrottle {
+    config: ThrottleConfig,
+    client: reqwest::Client,
+    key: Vec<u8>,
+    hits: Mutex<HashMap<Option<IpAddr>, Vec<Instant>>>,
+    /// Solved proof-of-work tokens and when they expire, so each can only be used once.
+    spent: Mutex<HashMap<String, i64>>,
+}
+
+impl ListThrottle {
+    /// Creates a new throttle from the given config.
+    pub fn from_config(config: ThrottleConfig) -> Result<ListThrottle, ThrottleError> {
+        if config.challenge == ChallengeKind::Captcha {
+            if config.captcha_site_key.is_none() {
+                return Err(ThrottleError::Config("captcha_site_key is missing"));
+            }
+            if config.captcha_secret.is_none() {
+                return Err(ThrottleError::Config("captcha_secret is missing"));
+            }
+            if config.captcha_verify_url.is_none() {
+                return Err(ThrottleError::Config("captcha_verify_url is missing"));
+            }
+            if config.captcha_script_url.is_none() {
+                return Err(ThrottleError::Config("captcha_script_url is missing"));
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?;
+
+        Ok(ListThrottle {
+            config,
+            client,
+            key: crate::util::random_token().into_bytes(),
+            hits: Mutex::new(HashMap::new()),
+            spent: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the challenge to show visitors who aren't logged in, or `None` if there isn't one.
+    pub fn challenge(&self) -> Option<Challenge> {
+        match self.config.challenge {
+            ChallengeKind::None => None,
+            ChallengeKind::ProofOfWork => {
+                let expires = Utc::now().timestamp() + CHALLENGE_TTL_SECS;
+                let payload = format!("{}.{}", expires, crate::util::random_token());
+                let signature = hex::encode(self.mac(&payload).finalize().into_bytes());
+                Some(Challenge {
+                    kind: ChallengeKind::ProofOfWork,
+                    token: Some(format!("{}.{}", payload, signature)),
+                    difficulty: Some(self.config.pow_difficulty),
+                    site_key: None,
+                    script_url: None,
+                    widget_class: None,
+                })
+            }
+            ChallengeKind::Captcha => Some(Challenge {
+                kind: ChallengeKind::Captcha,
+                token: None,
+                difficulty: None,
+                site_key: self.config.captcha_site_key.clone(),
+                script_url: self.config.captcha_script_url.clone(),
+                widget_class: Some(self.config.captcha_widget_class.clone()),
+            }),
+        }
+    }
+
+    /// Checks a visitor who isn't logged in can make a list, counting it if they can.
+    pub async fn check(
+        &self,
+        ip: Option<IpAddr>,
+        answer: &ChallengeAnswer<'_>,
+    ) -> Result<(), ThrottleError> {
+        let window = Duration::from_secs(self.config.window_secs);
+        if self.config.anonymous_lists > 0 {
+            let mut hits = self.hits.lock().unwrap();
+            hits.retain(|_, times| {
+                times.retain(|time| time.elapsed() < window);
+                !times.is_empty()
+            });
+            if let Some(times) = hits.get(&ip) {
+                if times.len() >= self.config.anonymous_lists {
+                    let wait = window.saturating_sub(times[0].elapsed());
+                    return Err(ThrottleError::TooMany(wait.as_secs() / 60 + 1));
+                }
+            }
+        }
+
+        match self.config.challenge {
+            ChallengeKind::None => {}
+            ChallengeKind::ProofOfWork => self.verify_work(answer)?,
+            ChallengeKind::Captcha => self.verify_captcha(ip, answer).await?,
+        }
+
+        if self.config.anonymous_lists > 0 {
+            self.hits
+                .lock()
+                .unwrap()
+                .entry(ip)
+                .or_default()
+                .push(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Checks the token is one we issued and hasn't been used, and that hashing it with the nonce
+    /// gives enough leading zero bits.
+    fn verify_work(&self, answer: &ChallengeAnswer<'_>) -> Result<(), ThrottleError> {
+        let (token, nonce) = match (answer.token, answer.response) {
+            (Some(token), Some(nonce)) => (token, nonce),
+            _ => return Err(ThrottleError::ChallengeFailed),
+        };
+
+        let (payload, signature) = token
+            .rsplit_once('.')
+            .ok_or(ThrottleError::ChallengeFailed)?;
+        let signature = hex::decode(signature).map_err(|_| ThrottleError::ChallengeFailed)?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| ThrottleError::ChallengeFailed)?;
+
+        let now = Utc::now().timestamp();
+        let expires = payload
+            .split_once('.')
+            .and_then(|(expires, _)| expires.parse::<i64>().ok())
+            .filter(|expires| *expires >= now)
+            .ok_or(ThrottleError::ChallengeFailed)?;
+
+        let hash = Sha256::digest(format!("{}:{}", token, nonce).as_bytes());
+        if leading_zero_bits(&hash) < self.config.pow_difficulty {
+            return Err(ThrottleError::ChallengeFailed);
+        }
+
+        let mut spent = self.spent.lock().unwrap();
+        spent.retain(|_, expires| *expires >= now);
+        if spent.insert(token.to_string(), expires).is_some() {
+            return Err(ThrottleError::ChallengeFailed);
+        }
+        Ok(())
+    }
+
+    /// Asks the captcha provider whether the response is valid.
+    async fn verify_captcha(
+        &self,
+        ip: Option<IpAddr>,
+        answer: &ChallengeAnswer<'_>,
+    ) -> Result<(), ThrottleError> {
+        let response = answer.response.ok_or(ThrottleError::ChallengeFailed)?;
+        let (verify_url, secret) =
+            match (&self.config.captcha_verify_url, &self.config.captcha_secret) {
+                (Some(url), Some(secret)) => (url, secret),
+                _ => {
+                    return Err(ThrottleError::Config(
+                        "captcha_verify_url or captcha_secret is missing",
+                    ))
+                }
+            };
+
+        let mut form = vec![
+            ("secret", secret.clone()),
+            ("response", response.to_string()),
+        ];
+        if let Some(ip) = ip {
+            form.push(("remoteip", ip.to_string()));
+        }
+
+        let verification = self
+            .client
+            .post(verify_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<CaptchaVerification>()
+            .await?;
+
+        if verification.success {
+            Ok(())
+        } else {
+            Err(ThrottleError::ChallengeFailed)
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take a key of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// Reads the throttle config and adds the `ListThrottle` to managed state.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let config = AppConfig::of(&rocket).throttle.clone();
+
+    match ListThrottle::from_config(config) {
+        Ok(throttle) => Ok(rocket.manage(throttle)),
+        Err(e) => {
+            error!("Failed to configure list throttle: {}", e);
+            Err(rocket)
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use validator::ValidationError;
+
+use crate::config::AppConfig;
+
+/// The instance's username policy, set once at startup.
+static POLICY: OnceLock<UsernamePolicy> = OnceLock::new();
+
+/// Username rules, read from the `usernames` table in Rocket.toml.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct UsernamePolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Which letters usernames may contain, along with '_', '-' and '.'.
+    pub charset: Charset,
+    /// Usernames nobody can register, compared case-insensitively. These usually clash with
+    /// routes or could be used to impersonate staff.
+    pub reserved: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Charset {
+    /// ASCII letters and digits.
+    Ascii,
+    /// Letters and digits in any script.
+    Unicode,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 32,
+            charset: Charset::Ascii,
+            reserved: [
+                "account", "admin", "administrator", "api", "help", "lists", "login", "logout",
+                "mod", "moderator", "out", "root", "settings", "staff", "support", "system",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl UsernamePolicy {
+    /// Returns the instance's policy, or the default policy if it hasn't been configured.
+    pub fn current() -> &'static UsernamePolicy {
+        POLICY.get_or_init(UsernamePolicy::default)
+    }
+
+    /// Checks that the rules themselves make sense.
+    pub fn check_settings(&self) -> Result<(), String> {
+        if self.min_length == 0 || self.min_length > self.max_length {
+            return Err("min_length must be between 1 and max_length".to_string());
+        }
+        Ok(())
+    }
+
+    /// Checks the username against each rule, returning an error for the first one it breaks.
+    pub fn check(&self, username: &str) -> Result<(), ValidationError> {
+        let length = username.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Err(error(
+                "username_length",
+                format!(
+                    "Username must be between {} and {} characters",
+                    self.min_length, self.max_length
+                ),
+            ));
+        }
+
+        let allowed = |c: char| {
+            matches!(c, '_' | '-' | '.')
+                || match self.charset {
+                    Charset::Ascii => c.is_ascii_alphanumeric(),
+                    Charset::Unicode => c.is_alphanumeric(),
+                }
+        };
+        if !username.chars().all(allowed) {
+            return Err(error(
+                "username_charset",
+                match self.charset {
+                    Charset::Ascii => {
+                        "Username can only contain letters, numbers, '_', '-' and '.'".to_string()
+                    }
+                    Charset::Unicode => {
+                        "Username can only contain letters, numbers, '_', '-' and '.' (in any language)"
+                            .to_string()
+                    }
+                },
+            ));
+        }
+
+        if !username.starts_with(|c: char| c.is_alphanumeric()) {
+            return Err(error(
+                "username_start",
+                "Username must start with a letter or number".to_string(),
+            ));
+        }
+
+        if self
+            .reserved
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(username))
+        {
+            return Err(error(
+                "username_reserved",
+                format!("\"{}\" is reserved, pick another username", username),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn error(code: &'static str, message: String) -> ValidationError {
+    let mut err = ValidationError::new(code);
+    err.message = Some(Cow::from(message));
+    err
+}
+
+/// Validates a username against the instance's policy. Used by the `User` model.
+pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+    UsernamePolicy::current().check(username)
+}
+
+/// Sets the username policy. It's global rather than managed state so the `User` model can
+/// validate against it. It's already been checked by `AppConfig::check`.
+pub async fn init(rocket: Rocket<Build>) -> fairing::Result {
+    let policy = AppConfig::of(&rocket).usernames.clone();
+
+    // Ignore the error if the policy was already set, e.g. by an earlier launch in the same process
+    let _ = POLICY.set(policy);
+    Ok(rocket)
+}
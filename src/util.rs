@@ -1,15 +1,31 @@
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
+use std::sync::OnceLock;
 
-use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rocket::fs::TempFile;
+use rocket::serde::Deserialize;
+use rocket::{fairing, Build, Rocket};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::config::AppConfig;
+
+/// The instance's key settings, set once at startup.
+static KEY_POLICY: OnceLock<KeyPolicy> = OnceLock::new();
+
+/// How much randomness keys need, so they can't be guessed. See `KeyPolicy::check`.
+const MIN_KEY_BITS: f64 = 64.0;
 
 pub fn ensure_file_exists(
     path: &Path,
     default_content: Option<&str>,
 ) -> Result<bool, std::io::Error> {
     // Make sure the path exists
-    path.parent().map(|p| std::fs::create_dir_all(p));
+    path.parent().map(std::fs::create_dir_all);
 
     // Make sure the file exists
     match OpenOptions::new().create_new(true).write(true).open(path) {
@@ -31,10 +47,324 @@ pub fn ensure_file_exists(
     }
 }
 
+/// Reads an uploaded file into memory. Rocket keeps small text fields in the form instead of a
+/// temporary file, those are read too.
+pub async fn read_temp_file(file: &TempFile<'_>) -> std::io::Result<Vec<u8>> {
+    match file {
+        TempFile::Buffered { content } => Ok(content.as_bytes().to_vec()),
+        file => match file.path() {
+            Some(path) => rocket::tokio::fs::read(path).await,
+            None => Ok(Vec::new()),
+        },
+    }
+}
+
+/// How random keys and tokens are made, read from the `keys` table in Rocket.toml. Keys name
+/// things in URLs, like lists, so they're short. Tokens grant access, like sessions and API
+/// tokens, so they're long.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct KeyPolicy {
+    pub key_length: usize,
+    pub token_length: usize,
+    /// The characters keys and tokens are made of. They end up in URLs, so only ASCII letters,
+    /// digits, '-' and '_' are allowed.
+    pub alphabet: String,
+}
+
+impl Default for KeyPolicy {
+    fn default() -> Self {
+        Self {
+            key_length: 16,
+            token_length: 64,
+            alphabet: "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789".to_string(),
+        }
+    }
+}
+
+impl KeyPolicy {
+    /// Returns the instance's settings, or the default ones if they haven't been configured.
+    pub fn current() -> &'static KeyPolicy {
+        KEY_POLICY.get_or_init(KeyPolicy::default)
+    }
+
+    /// Checks that keys and tokens made with the settings are safe to use, returning why not if
+    /// they aren't.
+    pub fn check(&self) -> Result<(), String> {
+        if self.key_length > 64 {
+            return Err("key_length must be at most 64".to_string());
+        }
+        // Some tokens are stored in VARCHAR(64) columns
+        if !(32..=64).contains(&self.token_length) {
+            return Err("token_length must be between 32 and 64".to_string());
+        }
+        if !self
+            .alphabet
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err("alphabet can only contain ASCII letters, digits, '-' and '_'".to_string());
+        }
+        let mut chars = self.alphabet.chars().collect::<Vec<_>>();
+        chars.sort_unstable();
+        chars.dedup();
+        if chars.len() != self.alphabet.len() || chars.len() < 16 {
+            return Err("alphabet must have at least 16 characters, each only once".to_string());
+        }
+        // Keys are all that stands between a stranger and an unlisted list
+        if (self.key_length as f64) * (chars.len() as f64).log2() < MIN_KEY_BITS {
+            return Err(format!(
+                "key_length is too short for the alphabet, keys need at least {} bits of randomness",
+                MIN_KEY_BITS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns a string of `length` characters drawn from the alphabet.
+    fn generate<R: Rng + ?Sized>(&self, rng: &mut R, length: usize) -> String {
+        let alphabet = self.alphabet.as_bytes();
+        (0..length)
+            .map(|_| *alphabet.choose(rng).expect("alphabet isn't empty") as char)
+            .collect()
+    }
+}
+
+/// Returns a random key, for naming things in URLs. See `KeyPolicy`.
 pub fn random_key() -> String {
-    Alphanumeric.sample_string(&mut rand::thread_rng(), 16)
+    let policy = KeyPolicy::current();
+    policy.generate(&mut rand::thread_rng(), policy.key_length)
 }
 
+/// Returns a random token, for granting access. See `KeyPolicy`.
 pub fn random_token() -> String {
-    Alphanumeric.sample_string(&mut rand::thread_rng(), 64)
+    let policy = KeyPolicy::current();
+    policy.generate(&mut rand::thread_rng(), policy.token_length)
+}
+
+/// Returns a token for a link that grants access to an account, like a password reset, drawn
+/// straight from the operating system's random number generator.
+pub fn secure_token() -> String {
+    let policy = KeyPolicy::current();
+    policy.generate(&mut OsRng, policy.token_length)
+}
+
+/// Sets the key settings. They're global rather than managed state so models can make keys.
+/// They've already been checked by `AppConfig::check`.
+pub async fn init_keys(rocket: Rocket<Build>) -> fairing::Result {
+    let policy = AppConfig::of(&rocket).keys.clone();
+
+    // Ignore the error if the settings were already set, e.g. by an earlier launch in the same
+    // process
+    let _ = KEY_POLICY.set(policy);
+    Ok(rocket)
+}
+
+/// Returns the SHA-256 hash of a token, hex encoded, for storing tokens that can't be read back
+/// out of the database.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Query parameters that only exist to track where a click came from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "mc_cid", "mc_eid", "igshid",
+    "yclid", "_hsenc", "_hsmi", "ref_src",
+];
+
+/// Removes tracking parameters (`utm_*`, `fbclid`, ...) from a URL.
+///
+/// Anything that doesn't parse as a URL is returned unchanged, so validation can reject it.
+pub fn strip_tracking_params(url: &str) -> String {
+    let mut parsed = match Url::parse(url.trim()) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+
+    let pairs = parsed
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+
+    if pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+
+    parsed.to_string()
+}
+
+/// The public URL of this instance, used to build absolute links for emails and feeds.
+/// Available as managed state.
+#[derive(Clone)]
+pub struct SiteUrl(String);
+
+impl SiteUrl {
+    /// Returns an absolute URL for the given path on this instance.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.0, path)
+    }
+}
+
+/// Adds the `SiteUrl` for the `base_url` setting to managed state.
+pub async fn init_site_url(rocket: Rocket<Build>) -> fairing::Result {
+    let base_url = AppConfig::of(&rocket)
+        .base_url
+        .clone()
+        .unwrap_or_else(|| "http://localhost:8000".to_string());
+
+    Ok(rocket.manage(SiteUrl(base_url.trim_end_matches('/').to_string())))
+}
+
+/// ISO 4217 currencies without a minor unit, whose amounts are stored in whole units.
+const ZERO_DECIMAL_CURRENCIES: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "UYI", "VND",
+    "VUV", "XAF", "XOF", "XPF",
+];
+
+/// How many decimal places the currency's amounts have: 0 for currencies like JPY and KRW, 2 for
+/// everything else.
+pub fn currency_decimals(currency: &str) -> u32 {
+    if ZERO_DECIMAL_CURRENCIES
+        .iter()
+        .any(|code| code.eq_ignore_ascii_case(currency))
+    {
+        0
+    } else {
+        2
+    }
+}
+
+/// Formats a price stored in cents, e.g. "12.34 USD", or in whole units for currencies without a
+/// minor unit, e.g. "1299 JPY".
+pub fn format_price(amount_cents: i64, currency: &str) -> String {
+    let sign = if amount_cents < 0 { "-" } else { "" };
+    if currency_decimals(currency) == 0 {
+        return format!("{}{} {}", sign, amount_cents.unsigned_abs(), currency);
+    }
+    format!(
+        "{}{}.{:02} {}",
+        sign,
+        amount_cents.unsigned_abs() / 100,
+        amount_cents.unsigned_abs() % 100,
+        currency
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Every character a key alphabet can have.
+    const ALLOWED: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    /// Alphabets that pass `KeyPolicy::check`: 16 or more allowed characters, each only once.
+    fn alphabets() -> impl Strategy<Value = String> {
+        proptest::sample::subsequence(ALLOWED.chars().collect::<Vec<_>>(), 16..=ALLOWED.len())
+            .prop_map(|chars| chars.into_iter().collect())
+    }
+
+    /// The shortest keys from an alphabet of the size with enough randomness, see `MIN_KEY_BITS`.
+    fn min_key_length(alphabet: &str) -> usize {
+        (MIN_KEY_BITS / (alphabet.len() as f64).log2()).ceil() as usize
+    }
+
+    fn policies() -> impl Strategy<Value = KeyPolicy> {
+        alphabets()
+            .prop_flat_map(|alphabet| {
+                (min_key_length(&alphabet)..=64, 32..=64usize, Just(alphabet))
+            })
+            .prop_map(|(key_length, token_length, alphabet)| KeyPolicy {
+                key_length,
+                token_length,
+                alphabet,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn valid_policies_pass_the_check(policy in policies()) {
+            prop_assert_eq!(policy.check(), Ok(()));
+        }
+
+        #[test]
+        fn keys_have_the_length_and_alphabet(policy in policies(), seed in any::<u64>()) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for length in [policy.key_length, policy.token_length] {
+                let key = policy.generate(&mut rng, length);
+                prop_assert_eq!(key.len(), length);
+                prop_assert!(key.chars().all(|c| policy.alphabet.contains(c)));
+            }
+        }
+
+        #[test]
+        fn short_keys_are_rejected(
+            (alphabet, key_length) in alphabets()
+                .prop_flat_map(|alphabet| (0..min_key_length(&alphabet), Just(alphabet)))
+                .prop_map(|(key_length, alphabet)| (alphabet, key_length)),
+        ) {
+            let policy = KeyPolicy { key_length, alphabet, ..KeyPolicy::default() };
+            prop_assert!(policy.check().is_err());
+        }
+
+        #[test]
+        fn tokens_too_long_for_the_database_are_rejected(token_length in 65..1024usize) {
+            let policy = KeyPolicy { token_length, ..KeyPolicy::default() };
+            prop_assert!(policy.check().is_err());
+        }
+
+        #[test]
+        fn alphabets_with_other_characters_are_rejected(
+            alphabet in alphabets(),
+            other in any::<char>().prop_filter("not allowed", |c| !ALLOWED.contains(*c)),
+        ) {
+            let policy = KeyPolicy {
+                alphabet: format!("{}{}", alphabet, other),
+                ..KeyPolicy::default()
+            };
+            prop_assert!(policy.check().is_err());
+        }
+
+        #[test]
+        fn tracking_params_are_stripped(value in "[a-z0-9]{1,16}") {
+            let url = format!("https://example.com/item?id={0}&utm_source={0}&fbclid={0}", value);
+            prop_assert_eq!(
+                strip_tracking_params(&url),
+                format!("https://example.com/item?id={}", value)
+            );
+        }
+    }
+
+    #[test]
+    fn default_policy_passes_the_check() {
+        assert_eq!(KeyPolicy::default().check(), Ok(()));
+    }
+
+    /// The smallest allowed key has 16^8 possibilities, so a few thousand shouldn't collide.
+    #[test]
+    fn keys_dont_collide() {
+        // The shortest keys allowed
+        let policy = KeyPolicy {
+            key_length: 16,
+            alphabet: ALLOWED[..16].to_string(),
+            ..KeyPolicy::default()
+        };
+        assert_eq!(policy.check(), Ok(()));
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys = (0..2_000)
+            .map(|_| policy.generate(&mut rng, policy.key_length))
+            .collect::<HashSet<_>>();
+        assert_eq!(keys.len(), 2_000);
+
+        let tokens = (0..10_000).map(|_| random_token()).collect::<HashSet<_>>();
+        assert_eq!(tokens.len(), 10_000);
+    }
 }
@@ -0,0 +1,95 @@
+use rocket::serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::models::{Item, List};
+use crate::pagination::Page;
+use crate::util::SiteUrl;
+use crate::web;
+
+/// Builds the path on `site`, or leaves it relative for links on our own pages.
+fn link_to(site: Option<&SiteUrl>, path: String) -> String {
+    match site {
+        Some(site) => site.url(&path),
+        None => path,
+    }
+}
+
+/// A list along with a link to its page, as templates, the API, feeds and exports show it.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ListView {
+    #[serde(flatten)]
+    pub list: List,
+    pub link: String,
+}
+
+impl ListView {
+    pub fn new(site: Option<&SiteUrl>, list: List) -> ListView {
+        ListView {
+            link: link_to(site, uri!(web::lists::show(&list.key)).to_string()),
+            list,
+        }
+    }
+
+    /// Links to each of the lists' pages, absolute if `site` is given. The route is only built
+    /// once, list keys are added to it as they are since they're always URL safe (see
+    /// `crate::util::KeyPolicy`).
+    pub fn all(site: Option<&SiteUrl>, lists: Vec<List>) -> Vec<ListView> {
+        let prefix = link_to(site, uri!(web::lists::index(_)).to_string());
+        lists
+            .into_iter()
+            .map(|list| ListView {
+                link: format!("{}/{}", prefix, list.key),
+                list,
+            })
+            .collect()
+    }
+}
+
+/// Builds links to a list's items, absolute if `site` is given. The list's route is only built
+/// once, item IDs are added to it.
+pub struct ItemLinks {
+    prefix: String,
+}
+
+impl ItemLinks {
+    pub fn new(site: Option<&SiteUrl>, list: &List) -> ItemLinks {
+        let list_link = uri!(web::lists::show(&list.key)).to_string();
+        ItemLinks {
+            prefix: link_to(site, format!("{}/items/", list_link)),
+        }
+    }
+
+    /// The link to the item's page.
+    pub fn link(&self, item_id: i64) -> String {
+        format!("{}{}", self.prefix, item_id)
+    }
+}
+
+/// An item along with a link to its page, as templates, the API, feeds and exports show it.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemView {
+    #[serde(flatten)]
+    pub item: Item,
+    pub link: String,
+    /// What the item is sorted and searched by price with, see `Item::price_midpoint_cents`.
+    pub price_midpoint_cents: Option<i64>,
+}
+
+impl ItemView {
+    pub fn new(links: &ItemLinks, item: Item) -> ItemView {
+        ItemView {
+            link: links.link(item.id),
+            price_midpoint_cents: item.price_midpoint_cents(),
+            item,
+        }
+    }
+
+    pub fn all(links: &ItemLinks, items: Vec<Item>) -> Vec<ItemView> {
+        items
+            .into_iter()
+            .map(|item| ItemView::new(links, item))
+            .collect()
+    }
+}
@@ -1,11 +1,31 @@
+use std::net::IpAddr;
+
+use chrono::Datelike;
 use rocket::form::Form;
-use rocket::http::CookieJar;
+use rocket::fs::{NamedFile, TempFile};
+use rocket::http::{CookieJar, Header};
 use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
 use rocket_db_pools::Connection;
 use rocket_dyn_templates::{context, Template};
 
-use crate::db::{DataError, WishlistDb};
-use crate::web::auth::{self, NewUser, UserLogin};
+use crate::csrf::CsrfVerified;
+use crate::db::models::{AccountExport, EmailVerification, FundLink, InboundAddress, List, ListPrivacy, MatrixLink, PasswordResetToken, SuspensionAppeal, User, UserSession};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::directory::Directory;
+use crate::exports::{ExportJob, ExportStore};
+use crate::features::{flags, Enabled};
+use crate::images::{ImageScanner, UploadStore};
+use crate::imports::{ImportArchive, ImportError};
+use crate::inbound::InboundConfig;
+use crate::notify::Dispatcher;
+use crate::passwords::{self, PasswordChecker};
+use crate::quotas::Quotas;
+use crate::rate_limits::{self, RateLimiter};
+use crate::stats::StatsCache;
+use crate::util::{self, SiteUrl};
+use crate::web::auth::{self, ChangeEmail, ChangeUsername, DeviceInfo, ForgotPassword, NewUser, ResetPassword, SuspendedUser, UserLogin};
 use crate::web::WebError;
 
 use super::auth::LoggedInUser;
@@ -20,14 +40,41 @@ pub fn show_2() -> Redirect {
     Redirect::to(uri!(login))
 }
 
+#[get("/account/stats?<year>")]
+pub async fn stats(
+    mut db: Connection<WishlistDb>,
+    cache: &State<StatsCache>,
+    user: &LoggedInUser,
+    year: Option<i32>,
+) -> Result<Template, WebError<Template>> {
+    let year = year.unwrap_or_else(|| chrono::Utc::now().year());
+    let stats = cache.get(&mut db, user.user.id, year).await?;
+    let received_value = stats
+        .received_value
+        .iter()
+        .map(|total| util::format_price(total.amount_cents, &total.currency))
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "account/stats",
+        context! {
+            user,
+            stats,
+            received_value,
+            previous_year: year - 1,
+            next_year: year + 1,
+        },
+    ))
+}
+
 #[get("/account/register")]
 pub fn new(_user: &'_ LoggedInUser) -> Redirect {
     Redirect::to(uri!(crate::web_index))
 }
 
 #[get("/account/register", rank = 2)]
-pub fn new_2() -> Template {
-    Template::render("account/register", context! {})
+pub fn new_2(_enabled: Enabled<flags::Registration>, directory: &State<Directory>) -> Template {
+    Template::render("account/register", context! { closed: directory.enabled() })
 }
 
 #[post("/account/register")]
@@ -37,12 +84,50 @@ pub async fn create(_user: &'_ LoggedInUser) -> Redirect {
 
 #[post("/account/register", format = "form", data = "<user>", rank = 2)]
 pub async fn create_2(
-    mut db: Connection<WishlistDb>,
+    _csrf: CsrfVerified,
+    db: Connection<WishlistDb>,
+    _enabled: Enabled<flags::Registration>,
+    checker: &State<PasswordChecker>,
+    directory: &State<Directory>,
+    dispatcher: &State<Dispatcher>,
+    limiter: &State<RateLimiter>,
+    site: &State<SiteUrl>,
+    ip: Option<IpAddr>,
     user: Form<NewUser<'_>>,
 ) -> Result<Redirect, WebError<Template>> {
+    // Accounts come from the directory instead
+    if directory.enabled() {
+        return Err(WebError::NotFound(Template::render(
+            "account/register",
+            context! { closed: true },
+        )));
+    }
+
     let user = user.into_inner();
-    match auth::register_new_user(&mut db, &user).await {
-        Ok(_) => Ok(Redirect::to(uri!(crate::web_index))),
+    let strength = passwords::estimate_strength(user.password, &[user.username, user.email]);
+    if let Err(wait) = limiter.check_register(ip) {
+        return Err(WebError::too_many_requests(
+            Template::render(
+                "account/register",
+                context! {
+                    register: context! {
+                        username: user.username,
+                        email: user.email,
+                    },
+                    strength,
+                    error_message: rate_limits::wait_message(wait),
+                },
+            ),
+            wait,
+        ));
+    }
+    let mut tx = Transaction::begin(db).await?;
+    match auth::register_new_user(&mut tx, checker, &user).await {
+        Ok(new_user) => {
+            auth::send_email_verification(&mut tx, site, &new_user).await?;
+            dispatcher.commit(tx).await?;
+            Ok(Redirect::to(uri!(crate::web_index)))
+        }
         Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
             "account/register",
             context! {
@@ -52,6 +137,7 @@ pub async fn create_2(
                     password: user.password,
                     password_confirm: user.password_confirm,
                 },
+                strength,
                 error_message: "Fix your errors",
                 errors: e,
             },
@@ -65,6 +151,7 @@ pub async fn create_2(
                     password: user.password,
                     password_confirm: user.password_confirm,
                 },
+                strength,
                 error_message: e.to_string()
             },
         ))),
@@ -88,16 +175,49 @@ pub fn do_login(_user: &'_ LoggedInUser) -> Redirect {
 
 #[post("/login", format = "form", data = "<login>", rank = 2)]
 pub async fn do_login_2(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
     cookies: &CookieJar<'_>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    directory: &State<Directory>,
+    limiter: &State<RateLimiter>,
+    ip: Option<IpAddr>,
+    device: DeviceInfo,
     login: Form<UserLogin<'_>>,
 ) -> Result<Redirect, WebError<Template>> {
     // TODO: Redirect user if they're already logged in
     let login = login.into_inner();
-    match auth::verify_user_login(&mut db, &login).await {
+    if let Err(wait) = limiter.check_login(ip, login.username) {
+        return Err(WebError::too_many_requests(
+            Template::render(
+                "account/login",
+                context! {
+                    login: context! {
+                        username: login.username,
+                        remember_me: login.remember_me,
+                    },
+                    error_message: rate_limits::wait_message(wait),
+                },
+            ),
+            wait,
+        ));
+    }
+    match auth::verify_user_login(&mut db, directory, &login).await {
         Ok(user) => {
-            auth::create_user_session(&mut db, cookies, &user).await?;
-            Ok(Redirect::to(uri!(crate::web_index)))
+            let remember_for = login
+                .remember_me
+                .then(|| chrono::Duration::days(auth::REMEMBER_ME_TTL_DAYS));
+            let session =
+                auth::create_user_session(&mut db, cookies, &user, &device, remember_for).await?;
+            let mut tx = Transaction::begin(db).await?;
+            auth::notify_new_device(&mut tx, site, &user, &session).await?;
+            dispatcher.commit(tx).await?;
+            if user.is_suspended() {
+                Ok(Redirect::to(uri!(suspended)))
+            } else {
+                Ok(Redirect::to(uri!(crate::web_index)))
+            }
         },
         Err(e) => Err(WebError::Invalid(Template::render(
             "account/login",
@@ -105,6 +225,7 @@ pub async fn do_login_2(
                 login: context! {
                     username: login.username,
                     password: login.password,
+                    remember_me: login.remember_me,
                 },
                 error_message: e.to_string()
             },
@@ -114,6 +235,7 @@ pub async fn do_login_2(
 
 #[post("/logout")]
 pub async fn logout(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
     cookies: &CookieJar<'_>,
     _user: &'_ LoggedInUser,
@@ -124,6 +246,746 @@ pub async fn logout(
 }
 
 #[post("/logout", rank = 2)]
+pub async fn logout_suspended(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    cookies: &CookieJar<'_>,
+    _suspended_user: SuspendedUser<'_>,
+) -> Result<Redirect, WebError<Template>> {
+    auth::destroy_user_session(&mut db, cookies).await?;
+
+    Ok(Redirect::to(uri!(crate::web_index)))
+}
+
+#[post("/logout", rank = 3)]
 pub async fn logout_2() -> Result<Redirect, WebError<Template>> {
     Ok(Redirect::to(uri!(crate::web_index)))
 }
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewAppeal<'r> {
+    pub message: &'r str,
+}
+
+#[get("/account/suspended")]
+pub async fn suspended(
+    mut db: Connection<WishlistDb>,
+    suspended_user: SuspendedUser<'_>,
+) -> Result<Template, WebError<Template>> {
+    let appeals = SuspensionAppeal::all_by_user(&mut db, suspended_user.user.id).await?;
+
+    Ok(Template::render(
+        "account/suspended",
+        context! { user: suspended_user.user, appeals },
+    ))
+}
+
+#[get("/account/suspended", rank = 2)]
+pub fn suspended_2() -> Redirect {
+    Redirect::to(uri!(crate::web_index))
+}
+
+#[post("/account/suspended/appeal", format = "form", data = "<appeal>")]
+pub async fn appeal(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    suspended_user: SuspendedUser<'_>,
+    appeal: Form<NewAppeal<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    match SuspensionAppeal::create(&mut db, suspended_user.user.id, appeal.message).await {
+        Ok(_) => Ok(Redirect::to(uri!(suspended))),
+        Err(DataError::Validation(e)) => {
+            let appeals = SuspensionAppeal::all_by_user(&mut db, suspended_user.user.id).await?;
+            Err(WebError::Invalid(Template::render(
+                "account/suspended",
+                context! {
+                    user: suspended_user.user,
+                    appeals,
+                    appeal: context! { message: appeal.message },
+                    error_message: "Fix your errors",
+                    errors: e,
+                },
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[post("/account/suspended/appeal", rank = 2)]
+pub fn appeal_2() -> Redirect {
+    Redirect::to(uri!(crate::web_index))
+}
+
+/// A session on the sessions page. The session's token is left out so it never ends up in a page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionSummary {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub request_count: i64,
+    pub remember_me: bool,
+    pub expires_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    /// Whether this is the session the page was loaded with.
+    pub current: bool,
+}
+
+#[get("/account/sessions")]
+pub async fn sessions(
+    mut db: Connection<WishlistDb>,
+    cookies: &CookieJar<'_>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let current_token = cookies.get("session_id").map(|cookie| cookie.value());
+    let sessions = UserSession::all_by_user(&mut db, user.user.id)
+        .await?
+        .into_iter()
+        .map(|session| SessionSummary {
+            current: Some(session.token.as_str()) == current_token,
+            id: session.id,
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            last_used_at: session.last_used_at,
+            request_count: session.request_count,
+            remember_me: session.remember_me,
+            expires_at: session.expires_at,
+            created_at: session.created_at,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render("account/sessions", context! { user, sessions }))
+}
+
+/// Signs out one of the user's other sessions.
+#[delete("/account/sessions/<id>")]
+pub async fn destroy_session(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    UserSession::destroy_by_user(&mut db, user.user.id, id).await?;
+
+    Ok(Redirect::to(uri!(sessions)))
+}
+
+#[get("/account/sessions/revoke/<token>")]
+pub async fn revoke_session(
+    mut db: Connection<WishlistDb>,
+    token: &str,
+) -> Result<Template, WebError<Template>> {
+    let session = UserSession::find_by_revoke_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(Template::render(
+        "account/revoke_session",
+        context! { session, token },
+    ))
+}
+
+#[post("/account/sessions/revoke/<token>")]
+pub async fn do_revoke_session(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    token: &str,
+) -> Result<Redirect, WebError<Template>> {
+    let session = UserSession::find_by_revoke_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    UserSession::destroy_by_token(&mut db, &session.token).await?;
+
+    Ok(Redirect::to(uri!(login)))
+}
+
+/// A generated account archive, sent as a download.
+#[derive(Responder)]
+#[response(content_type = "application/zip")]
+pub struct ExportDownload {
+    file: NamedFile,
+    disposition: Header<'static>,
+}
+
+#[get("/account/export")]
+pub async fn export(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let export = AccountExport::latest_by_user(&mut db, user.user.id).await?;
+
+    Ok(Template::render("account/export", context! { user, export }))
+}
+
+#[post("/account/export")]
+pub async fn create_export(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    pool: &State<WishlistDb>,
+    store: &State<ExportStore>,
+    uploads: &State<UploadStore>,
+    site: &State<SiteUrl>,
+    user: &LoggedInUser,
+) -> Result<Redirect, WebError<Template>> {
+    // Only generate one archive at a time per user
+    let latest = AccountExport::latest_by_user(&mut db, user.user.id).await?;
+    if latest.map(|export| export.is_pending()).unwrap_or(false) {
+        return Ok(Redirect::to(uri!(export)));
+    }
+
+    let export = AccountExport::create(&mut db, user.user.id).await?;
+    let profile = rocket::serde::json::serde_json::to_value(&user.user)
+        .map_err(|e| DataError::Other(e.to_string()))?;
+
+    let job = ExportJob {
+        pool: (***pool).clone(),
+        store: store.inner().clone(),
+        uploads: uploads.inner().clone(),
+        site: site.inner().clone(),
+    };
+    job.spawn(export, user.user.email.clone(), profile);
+
+    Ok(Redirect::to(uri!(export)))
+}
+
+#[get("/account/export/<token>")]
+pub async fn download_export(
+    mut db: Connection<WishlistDb>,
+    store: &State<ExportStore>,
+    user: &LoggedInUser,
+    token: &str,
+) -> Result<ExportDownload, WebError<Template>> {
+    let export = AccountExport::find_by_token(&mut db, token)
+        .await?
+        .filter(|export| export.user_id == user.user.id && export.status == AccountExport::READY)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let file = NamedFile::open(store.file_path(&export.token))
+        .await
+        .map_err(|_| WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(ExportDownload {
+        file,
+        disposition: Header::new(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"wishlist-export-{}.zip\"",
+                export.created_at.format("%Y-%m-%d")
+            ),
+        ),
+    })
+}
+
+#[derive(FromForm)]
+pub struct ImportUpload<'r> {
+    pub archive: TempFile<'r>,
+    pub dry_run: bool,
+}
+
+#[get("/account/import")]
+pub fn import(user: &LoggedInUser) -> Template {
+    Template::render("account/import", context! { user })
+}
+
+#[post("/account/import", format = "multipart/form-data", data = "<upload>")]
+pub async fn do_import(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    uploads: &State<UploadStore>,
+    scanner: &State<ImageScanner>,
+    quotas: &State<Quotas>,
+    user: &LoggedInUser,
+    upload: Form<ImportUpload<'_>>,
+) -> Result<Template, WebError<Template>> {
+    let data = util::read_temp_file(&upload.archive)
+        .await
+        .map_err(|e| import_error(user, e.to_string()))?;
+
+    let archive = ImportArchive::read(data, uploads.max_upload_size)
+        .await
+        .map_err(|e| import_error(user, e.to_string()))?;
+
+    match archive
+        .import(
+            &mut db,
+            uploads,
+            scanner,
+            quotas,
+            user.user.id,
+            user.user.is_email_verified(),
+            upload.dry_run,
+        )
+        .await
+    {
+        Ok(report) => Ok(Template::render("account/import", context! { user, report })),
+        Err(ImportError::Data(e)) => Err(e.into()),
+        Err(e) => Err(import_error(user, e.to_string())),
+    }
+}
+
+fn import_error(user: &LoggedInUser, error_message: String) -> WebError<Template> {
+    WebError::Invalid(Template::render(
+        "account/import",
+        context! { user, error_message },
+    ))
+}
+
+#[get("/account/email")]
+pub fn email(user: &LoggedInUser) -> Template {
+    Template::render("account/email", context! { user })
+}
+
+#[post("/account/email", format = "form", data = "<change>")]
+pub async fn change_email(
+    _csrf: CsrfVerified,
+    db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    user: &LoggedInUser,
+    change: Form<ChangeEmail<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let mut tx = Transaction::begin(db).await?;
+    match auth::request_email_change(&mut tx, site, &user.user, &change).await {
+        Ok(()) => {
+            dispatcher.commit(tx).await?;
+            Ok(Redirect::to(uri!(email)))
+        }
+        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
+            "account/email",
+            context! {
+                user,
+                change: context! { new_email: change.new_email },
+                error_message: "Fix your errors",
+                errors: e,
+            },
+        ))),
+        Err(e) => Err(WebError::Invalid(Template::render(
+            "account/email",
+            context! {
+                user,
+                change: context! { new_email: change.new_email },
+                error_message: e.to_string(),
+            },
+        ))),
+    }
+}
+
+#[derive(FromForm)]
+pub struct ClaimReminders {
+    pub claim_reminders: bool,
+}
+
+/// Turns reminder emails about claimed items on or off, see `crate::jobs::claim_reminders`.
+#[post("/account/email/claim-reminders", format = "form", data = "<reminders>")]
+pub async fn claim_reminders(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    reminders: Form<ClaimReminders>,
+) -> Result<Redirect, WebError<Template>> {
+    user.user
+        .set_claim_reminders(&mut db, reminders.claim_reminders)
+        .await?;
+
+    Ok(Redirect::to(uri!(email)))
+}
+
+#[post("/account/email/cancel")]
+pub async fn cancel_email_change(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Redirect, WebError<Template>> {
+    user.user.cancel_email_change(&mut db).await?;
+
+    Ok(Redirect::to(uri!(email)))
+}
+
+#[get("/account/email/confirm/<token>")]
+pub async fn confirm_email(
+    mut db: Connection<WishlistDb>,
+    token: &str,
+) -> Result<Template, WebError<Template>> {
+    let requested_after = (chrono::Utc::now()
+        - chrono::Duration::hours(auth::EMAIL_CHANGE_TTL_HOURS))
+    .naive_utc();
+
+    let user = User::find_by_email_change_token(&mut db, token, requested_after)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    // Someone else may have taken the address since the change was requested
+    if let Some(pending_email) = &user.pending_email {
+        if User::email_taken(&mut db, pending_email, user.id).await? {
+            user.cancel_email_change(&mut db).await?;
+            return Err(WebError::Invalid(Template::render(
+                "account/email_confirmed",
+                context! { error_message: "That email address is already in use" },
+            )));
+        }
+    }
+
+    let user = user.confirm_email_change(&mut db).await?;
+
+    Ok(Template::render("account/email_confirmed", context! { email: user.email }))
+}
+
+/// Sends a new verification link, e.g. when the first one expired.
+#[post("/account/verify")]
+pub async fn resend_verification(
+    _csrf: CsrfVerified,
+    db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    user: &LoggedInUser,
+) -> Result<Redirect, WebError<Template>> {
+    if !user.user.is_email_verified() {
+        let mut tx = Transaction::begin(db).await?;
+        auth::send_email_verification(&mut tx, site, &user.user).await?;
+        dispatcher.commit(tx).await?;
+    }
+
+    Ok(Redirect::to(uri!(email)))
+}
+
+#[get("/account/verify/<token>")]
+pub async fn verify_email(
+    mut db: Connection<WishlistDb>,
+    token: &str,
+) -> Result<Template, WebError<Template>> {
+    let created_after = (chrono::Utc::now()
+        - chrono::Duration::hours(auth::EMAIL_VERIFICATION_TTL_HOURS))
+    .naive_utc();
+
+    let verification = EmailVerification::find_by_token(&mut db, token, created_after)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let user = User::find_by_id(&mut db, verification.user_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    // The link only counts for the address it was sent to
+    if !verification.email.eq_ignore_ascii_case(&user.email) {
+        return Err(WebError::Invalid(Template::render(
+            "account/email_verified",
+            context! { error_message: "Your email address changed since this link was sent" },
+        )));
+    }
+
+    let user = user.mark_email_verified(&mut db).await?;
+    EmailVerification::destroy_by_user(&mut db, user.id).await?;
+
+    Ok(Template::render(
+        "account/email_verified",
+        context! { email: user.email },
+    ))
+}
+
+#[get("/account/forgot")]
+pub fn forgot_password() -> Template {
+    Template::render("account/forgot", context! {})
+}
+
+#[post("/account/forgot", format = "form", data = "<forgot>")]
+pub async fn do_forgot_password(
+    _csrf: CsrfVerified,
+    db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    forgot: Form<ForgotPassword<'_>>,
+) -> Result<Template, WebError<Template>> {
+    let mut tx = Transaction::begin(db).await?;
+    auth::request_password_reset(&mut tx, site, forgot.email).await?;
+    dispatcher.commit(tx).await?;
+
+    Ok(Template::render(
+        "account/forgot",
+        context! { sent: true, email: forgot.email },
+    ))
+}
+
+#[get("/account/reset/<token>")]
+pub async fn reset_password(
+    mut db: Connection<WishlistDb>,
+    token: &str,
+) -> Result<Template, WebError<Template>> {
+    PasswordResetToken::find_by_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(Template::render("account/reset", context! { token }))
+}
+
+#[post("/account/reset/<token>", format = "form", data = "<reset>")]
+pub async fn do_reset_password(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    checker: &State<PasswordChecker>,
+    token: &str,
+    reset: Form<ResetPassword<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let reset_token = PasswordResetToken::find_by_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let mut tx = Transaction::begin(db).await?;
+    match auth::reset_password(&mut tx, checker, &reset_token, &reset).await {
+        Ok(_) => {
+            tx.commit().await?;
+            Ok(Redirect::to(uri!(login_2)))
+        }
+        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
+            "account/reset",
+            context! {
+                token,
+                error_message: "Fix your errors",
+                errors: e,
+            },
+        ))),
+        Err(e) => Err(WebError::Invalid(Template::render(
+            "account/reset",
+            context! { token, error_message: e.to_string() },
+        ))),
+    }
+}
+
+#[get("/account/username")]
+pub fn username(user: &LoggedInUser) -> Template {
+    Template::render("account/username", context! { user })
+}
+
+#[post("/account/username", format = "form", data = "<change>")]
+pub async fn change_username(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    change: Form<ChangeUsername<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    match auth::change_username(&mut db, &user.user, &change).await {
+        Ok(user) => Ok(Redirect::to(format!("/@{}", user.username))),
+        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
+            "account/username",
+            context! {
+                user,
+                change: context! { new_username: change.new_username },
+                error_message: "Fix your errors",
+                errors: e,
+            },
+        ))),
+        Err(e) => Err(WebError::Invalid(Template::render(
+            "account/username",
+            context! {
+                user,
+                change: context! { new_username: change.new_username },
+                error_message: e.to_string(),
+            },
+        ))),
+    }
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LinkMatrix<'r> {
+    pub matrix_id: &'r str,
+}
+
+#[get("/account/matrix")]
+pub async fn matrix(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let link = MatrixLink::find_by_user(&mut db, user.user.id).await?;
+
+    Ok(Template::render("account/matrix", context! { user, link }))
+}
+
+/// Links a Matrix account, or unlinks it if the ID is left empty.
+#[post("/account/matrix", format = "form", data = "<link>")]
+pub async fn link_matrix(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    link: Form<LinkMatrix<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    if link.matrix_id.trim().is_empty() {
+        MatrixLink::destroy_by_user(&mut db, user.user.id).await?;
+        return Ok(Redirect::to(uri!(matrix)));
+    }
+
+    match MatrixLink::set(&mut db, user.user.id, link.matrix_id).await {
+        Ok(_) => Ok(Redirect::to(uri!(matrix))),
+        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
+            "account/matrix",
+            context! {
+                user,
+                link: context! { matrix_id: link.matrix_id },
+                error_message: "Fix your errors",
+                errors: e,
+            },
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[get("/account/inbound")]
+pub async fn inbound(
+    mut db: Connection<WishlistDb>,
+    config: &State<InboundConfig>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let address = InboundAddress::find_by_user(&mut db, user.user.id)
+        .await?
+        .and_then(|address| config.address(&address.token));
+
+    Ok(Template::render(
+        "account/inbound",
+        context! { user, enabled: config.enabled(), address },
+    ))
+}
+
+/// Gives the user a new secret address. The old one stops working.
+#[post("/account/inbound")]
+pub async fn create_inbound(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Redirect, WebError<Template>> {
+    InboundAddress::regenerate(&mut db, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(inbound)))
+}
+
+#[delete("/account/inbound")]
+pub async fn destroy_inbound(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Redirect, WebError<Template>> {
+    InboundAddress::destroy_by_user(&mut db, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(inbound)))
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SetFundLink<'r> {
+    pub url: &'r str,
+}
+
+#[get("/account/fund")]
+pub async fn fund(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let link = FundLink::find_by_user(&mut db, user.user.id).await?;
+
+    Ok(Template::render("account/fund", context! { user, link }))
+}
+
+/// Sets the user's fund link, or removes it if the URL is left empty.
+#[post("/account/fund", format = "form", data = "<link>")]
+pub async fn set_fund(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    link: Form<SetFundLink<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    if link.url.trim().is_empty() {
+        FundLink::destroy_by_user(&mut db, user.user.id).await?;
+        return Ok(Redirect::to(uri!(fund)));
+    }
+
+    match FundLink::set(&mut db, user.user.id, link.url).await {
+        Ok(_) => Ok(Redirect::to(uri!(fund))),
+        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
+            "account/fund",
+            context! {
+                user,
+                link: context! { url: link.url },
+                error_message: "Fix your errors",
+                errors: e,
+            },
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(FromForm)]
+pub struct ChangeListsPrivacy<'r> {
+    /// See `ListPrivacy::as_str`.
+    pub privacy: &'r str,
+    /// Left out the first time the form is sent, to show which lists would change first.
+    pub confirm: bool,
+}
+
+#[get("/account/lists/privacy")]
+pub fn lists_privacy(user: &LoggedInUser) -> Template {
+    Template::render("account/privacy", context! { user })
+}
+
+/// Gives all of the user's lists the same privacy at once, e.g. to make them all private. Lists
+/// that already have it are left alone. Asks to confirm first, listing the lists that would
+/// change, and then changes them all or none of them.
+#[post("/account/lists/privacy", format = "form", data = "<change>")]
+pub async fn change_lists_privacy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    change: Form<ChangeListsPrivacy<'_>>,
+) -> Result<Template, WebError<Template>> {
+    let privacy = match ListPrivacy::parse(change.privacy) {
+        Some(privacy) => privacy,
+        None => {
+            let message = "Choose who can see your lists";
+            return Err(lists_privacy_error(user, change.privacy, message));
+        }
+    };
+    if let Err(e) = auth::check_can_publish(&user.user, privacy.as_str()) {
+        return Err(lists_privacy_error(user, change.privacy, &e.to_string()));
+    }
+
+    let lists = List::all_by_user(&mut db, user.user.id)
+        .await?
+        .into_iter()
+        .filter(|list| list.privacy() != privacy)
+        .collect::<Vec<_>>();
+
+    if !change.confirm {
+        return Ok(Template::render(
+            "account/privacy",
+            context! {
+                user,
+                privacy: privacy.as_str(),
+                label: privacy.label(),
+                lists,
+                confirming: true,
+            },
+        ));
+    }
+
+    let mut tx = Transaction::begin(db).await?;
+    let mut changed = vec![];
+    for mut list in lists {
+        changed.push(list.set_privacy(&mut tx, privacy, Some(user.user.id)).await?);
+    }
+    tx.commit().await?;
+
+    Ok(Template::render(
+        "account/privacy",
+        context! { user, privacy: privacy.as_str(), label: privacy.label(), changed },
+    ))
+}
+
+fn lists_privacy_error(
+    user: &LoggedInUser,
+    privacy: &str,
+    error_message: &str,
+) -> WebError<Template> {
+    WebError::Invalid(Template::render(
+        "account/privacy",
+        context! { user, privacy, error_message },
+    ))
+}
@@ -0,0 +1,421 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::config::AppConfig;
+use crate::csrf::CsrfVerified;
+use crate::db::models::{
+    AuditEvent, ClaimEvent, EmailSuppression, PermissionKind, QuotaExemption, Role, SearchTask,
+    SuspensionAppeal, User,
+};
+use crate::db::{DataError, WishlistDb};
+use crate::features::{Feature, FeatureFlags};
+use crate::pagination::{Page, Pagination};
+use crate::web::auth::permissions::{ManageSettings, ManageUsers, ModerateContent};
+use crate::web::auth::{Permission, Permissions};
+use crate::web::{lists, WebError};
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SuspendUser<'r> {
+    pub reason: &'r str,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EditRole<'r> {
+    pub name: &'r str,
+    /// The names of the permissions to give the role, see `PermissionKind::as_str`.
+    pub permissions: Vec<&'r str>,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AssignRole {
+    pub role_id: i64,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SetFeature {
+    pub enabled: bool,
+}
+
+fn parse_permissions(names: &[&str]) -> Vec<PermissionKind> {
+    names
+        .iter()
+        .filter_map(|name| PermissionKind::parse(name))
+        .collect()
+}
+
+/// A permission on the roles page, with whether the role has it.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PermissionOption {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub granted: bool,
+}
+
+impl PermissionOption {
+    fn all(role: Option<&Role>) -> Vec<PermissionOption> {
+        let granted = role.map(|role| role.permissions()).unwrap_or_default();
+        PermissionKind::ALL
+            .iter()
+            .map(|permission| PermissionOption {
+                name: permission.as_str(),
+                label: permission.label(),
+                granted: granted.contains(permission),
+            })
+            .collect()
+    }
+}
+
+#[get("/admin/users")]
+pub async fn users(
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ManageUsers>,
+) -> Result<Template, WebError<Template>> {
+    let mut users = vec![];
+    for user in User::all(&mut db).await? {
+        let appeals = if user.is_suspended() {
+            SuspensionAppeal::all_by_user(&mut db, user.id).await?
+        } else {
+            vec![]
+        };
+        let roles = Role::all_by_user(&mut db, user.id).await?;
+        let quota_exempt = QuotaExemption::find_by_user(&mut db, user.id).await?.is_some();
+        let suppression = EmailSuppression::find_by_email(&mut db, &user.email).await?;
+        users.push(context! { user, appeals, roles, quota_exempt, suppression });
+    }
+    let roles = Role::all(&mut db).await?;
+
+    Ok(Template::render(
+        "admin/users",
+        context! { user: admin.user, users, roles },
+    ))
+}
+
+#[post("/admin/users/<id>/suspend", format = "form", data = "<suspension>")]
+pub async fn suspend(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ManageUsers>,
+    id: i64,
+    suspension: Form<SuspendUser<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let user = User::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    if user.id == admin.user.id {
+        return Err(DataError::Other("You cannot suspend yourself".to_string()).into());
+    }
+
+    user.suspend(&mut db, suspension.reason).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+#[post("/admin/users/<id>/unsuspend")]
+pub async fn unsuspend(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageUsers>,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let user = User::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    user.unsuspend(&mut db).await?;
+    SuspensionAppeal::destroy_by_user(&mut db, user.id).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+/// Gives a user a role. Only roles whose permissions the admin has themselves can be given, so
+/// nobody can hand out more than they're allowed.
+#[post("/admin/users/<id>/roles", format = "form", data = "<assignment>")]
+pub async fn assign_role(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageUsers>,
+    granted: Permissions,
+    id: i64,
+    assignment: Form<AssignRole>,
+) -> Result<Redirect, WebError<Template>> {
+    let user = User::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let role = Role::find_by_id(&mut db, assignment.role_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    if !role
+        .permissions()
+        .iter()
+        .all(|permission| granted.0.contains(permission))
+    {
+        return Err(DataError::Other(
+            "You can only give roles with permissions you have yourself".to_string(),
+        )
+        .into());
+    }
+
+    role.assign(&mut db, user.id).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+#[delete("/admin/users/<id>/roles/<role_id>")]
+pub async fn unassign_role(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageUsers>,
+    id: i64,
+    role_id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let role = Role::find_by_id(&mut db, role_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    role.unassign(&mut db, id).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+/// Frees a user from the instance's quotas. See `crate::quotas`.
+#[post("/admin/users/<id>/quota-exemption")]
+pub async fn exempt_from_quotas(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ManageUsers>,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let user = User::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    QuotaExemption::grant(&mut db, user.id, admin.user.id).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+#[delete("/admin/users/<id>/quota-exemption")]
+pub async fn apply_quotas(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageUsers>,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    QuotaExemption::revoke(&mut db, id).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+/// Lets the user's address be emailed again after a bounce or complaint, e.g. once they've fixed
+/// their mailbox. See `crate::mail::bounces`.
+#[delete("/admin/users/<id>/email-suppression")]
+pub async fn unsuppress_email(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageUsers>,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let user = User::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    EmailSuppression::destroy_by_email(&mut db, &user.email).await?;
+
+    Ok(Redirect::to(uri!(users)))
+}
+
+#[get("/admin/roles")]
+pub async fn roles(
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ManageSettings>,
+) -> Result<Template, WebError<Template>> {
+    let roles = Role::all(&mut db)
+        .await?
+        .into_iter()
+        .map(|role| {
+            let permissions = PermissionOption::all(Some(&role));
+            context! { role, permissions }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "admin/roles",
+        context! { user: admin.user, roles, permissions: PermissionOption::all(None) },
+    ))
+}
+
+#[post("/admin/roles", format = "form", data = "<role>")]
+pub async fn create_role(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageSettings>,
+    role: Form<EditRole<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    Role::create(&mut db, role.name, &parse_permissions(&role.permissions)).await?;
+
+    Ok(Redirect::to(uri!(roles)))
+}
+
+#[put("/admin/roles/<id>", format = "form", data = "<edit>")]
+pub async fn update_role(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageSettings>,
+    id: i64,
+    edit: Form<EditRole<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let role = Role::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    role.set_permissions(&mut db, &parse_permissions(&edit.permissions))
+        .await?;
+
+    Ok(Redirect::to(uri!(roles)))
+}
+
+#[delete("/admin/roles/<id>")]
+pub async fn destroy_role(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageSettings>,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let mut role = Role::find_by_id(&mut db, id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    role.destroy(&mut db).await?;
+
+    Ok(Redirect::to(uri!(roles)))
+}
+
+#[get("/admin/search")]
+pub async fn search(
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ManageSettings>,
+) -> Result<Template, WebError<Template>> {
+    let health = SearchTask::health(&mut db).await?;
+
+    Ok(Template::render(
+        "admin/search",
+        context! { user: admin.user, health },
+    ))
+}
+
+/// Shows what each retention policy would delete if it ran now. See `crate::jobs::retention`.
+#[get("/admin/retention")]
+pub async fn retention(
+    pool: &State<WishlistDb>,
+    config: &State<AppConfig>,
+    admin: Permission<'_, ManageSettings>,
+) -> Result<Template, WebError<Template>> {
+    let policies = crate::jobs::retention::report(pool, &config.retention).await?;
+
+    Ok(Template::render(
+        "admin/retention",
+        context! { user: admin.user, policies, dry_run: config.retention.dry_run },
+    ))
+}
+
+/// Queues every list and item to be indexed again.
+#[post("/admin/search/reindex")]
+pub async fn reindex(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _admin: Permission<'_, ManageSettings>,
+) -> Result<Redirect, WebError<Template>> {
+    SearchTask::push_all(&mut db).await?;
+
+    Ok(Redirect::to(uri!(search)))
+}
+
+/// Every change to every list and item, newest first.
+#[get("/admin/audit?<page..>")]
+pub async fn audit(
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ModerateContent>,
+    page: Option<Page>,
+) -> Result<Template, WebError<Template>> {
+    let page = page.unwrap_or_default();
+    let pagination = Pagination::new(&page, AuditEvent::count(&mut db).await?);
+    let events = AuditEvent::all(&mut db, &page)
+        .await?
+        .into_iter()
+        .map(lists::audit_entry)
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "admin/audit",
+        context! { user: admin.user, events, pagination, show_list: true },
+    ))
+}
+
+/// Every claim event, newest first, or one item's whole claim history. For looking into
+/// disputes over claims, see `ClaimEvent`.
+#[get("/admin/claims?<item>&<page..>")]
+pub async fn claims(
+    mut db: Connection<WishlistDb>,
+    admin: Permission<'_, ModerateContent>,
+    item: Option<i64>,
+    page: Option<Page>,
+) -> Result<Template, WebError<Template>> {
+    if let Some(item) = item {
+        let events = ClaimEvent::all_by_item(&mut db, item).await?;
+        return Ok(Template::render(
+            "admin/claims",
+            context! { user: admin.user, events, item },
+        ));
+    }
+
+    let page = page.unwrap_or_default();
+    let pagination = Pagination::new(&page, ClaimEvent::count(&mut db).await?);
+    let events = ClaimEvent::all(&mut db, &page).await?;
+
+    Ok(Template::render(
+        "admin/claims",
+        context! { user: admin.user, events, pagination },
+    ))
+}
+
+#[get("/admin/features")]
+pub fn features(
+    flags: &State<FeatureFlags>,
+    admin: Permission<'_, ManageSettings>,
+) -> Template {
+    Template::render(
+        "admin/features",
+        context! { user: admin.user, features: flags.all() },
+    )
+}
+
+/// Turns an instance feature on or off right away. See `crate::features`.
+#[post("/admin/features/<name>", format = "form", data = "<change>")]
+pub async fn set_feature(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    flags: &State<FeatureFlags>,
+    admin: Permission<'_, ManageSettings>,
+    name: &str,
+    change: Form<SetFeature>,
+) -> Result<Redirect, WebError<Template>> {
+    let feature =
+        Feature::parse(name).ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    flags
+        .set(&mut db, feature, change.enabled, admin.user.id)
+        .await?;
+
+    Ok(Redirect::to(uri!(features)))
+}
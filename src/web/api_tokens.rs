@@ -0,0 +1,122 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{ApiScope, ApiToken, List};
+use crate::db::{DataError, WishlistDb};
+use crate::web::auth::LoggedInUser;
+use crate::web::WebError;
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NewApiToken<'r> {
+    pub name: &'r str,
+    /// The names of the scopes to give the token, see `ApiScope::as_str`.
+    pub scopes: Vec<&'r str>,
+    /// The only list the token can reach, or empty for all of the user's lists.
+    pub list_id: Option<i64>,
+}
+
+/// A scope on the tokens page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ScopeOption {
+    pub name: &'static str,
+    pub label: &'static str,
+}
+
+/// A token on the tokens page, with what it can do.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TokenSummary {
+    pub token: ApiToken,
+    pub scopes: Vec<&'static str>,
+    /// The title of the only list the token can reach, if it's limited to one.
+    pub list_title: Option<String>,
+}
+
+/// Renders the tokens page, with the new token if one was just made.
+async fn render(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    new_token: Option<String>,
+) -> Result<Template, WebError<Template>> {
+    let lists = List::all_by_user(db, user.user.id).await?;
+    let tokens = ApiToken::all_by_user(db, user.user.id)
+        .await?
+        .into_iter()
+        .map(|token| TokenSummary {
+            scopes: token.scopes().iter().map(|scope| scope.as_str()).collect(),
+            list_title: token.list_id.and_then(|list_id| {
+                lists
+                    .iter()
+                    .find(|list| list.id == list_id)
+                    .map(|list| list.title.clone())
+            }),
+            token,
+        })
+        .collect::<Vec<_>>();
+    let scopes = ApiScope::ALL
+        .iter()
+        .map(|scope| ScopeOption {
+            name: scope.as_str(),
+            label: scope.label(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "account/tokens",
+        context! { user, tokens, scopes, lists, new_token },
+    ))
+}
+
+#[get("/account/tokens")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    render(&mut db, user, None).await
+}
+
+/// Makes a new token, showing it to the user this one time.
+#[post("/account/tokens", format = "form", data = "<token>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    token: Form<NewApiToken<'_>>,
+) -> Result<Template, WebError<Template>> {
+    let scopes = token
+        .scopes
+        .iter()
+        .filter_map(|name| ApiScope::parse(name))
+        .collect::<Vec<_>>();
+
+    // Tokens can only be limited to the user's own lists
+    if let Some(list_id) = token.list_id {
+        List::find_by_id(&mut db, list_id)
+            .await?
+            .filter(|list| list.is_owned_by(Some(user.user.id)))
+            .ok_or_else(|| DataError::Other("Pick one of your own lists".to_string()))?;
+    }
+
+    let (_, new_token) =
+        ApiToken::create(&mut db, user.user.id, token.name, &scopes, token.list_id).await?;
+
+    render(&mut db, user, Some(new_token)).await
+}
+
+#[delete("/account/tokens/<id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    ApiToken::destroy_by_user(&mut db, user.user.id, id).await?;
+
+    Ok(Redirect::to(uri!(index)))
+}
@@ -1,17 +1,25 @@
-use std::borrow::Cow;
+use std::collections::HashSet;
+use std::marker::PhantomData;
 
 use bcrypt::BcryptError;
 use rocket::http::{Cookie, CookieJar};
 use rocket::outcome::IntoOutcome;
 use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::json;
 use rocket::serde::{Deserialize, Serialize};
 use rocket::time::Duration;
 use rocket_db_pools::Connection;
 use thiserror::Error;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
-use crate::db::models::{User, UserSession};
+use crate::db::models::{
+    ApiScope, ApiToken, Delivery, EmailVerification, ListPrivacy, PasswordResetToken,
+    PermissionKind, Role, User, UserDevice, UserSession, UsernameHistory, DIRECTORY_AUTH_SOURCE,
+};
 use crate::db::{DataError, WishlistDb};
+use crate::directory::Directory;
+use crate::passwords::PasswordChecker;
+use crate::util::SiteUrl;
 
 #[derive(FromForm, Validate, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -38,6 +46,9 @@ pub struct NewUser<'r> {
 pub struct UserLogin<'r> {
     pub username: &'r str,
     pub password: &'r str,
+    /// Stay logged in for `REMEMBER_ME_TTL_DAYS` instead of until the browser closes.
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 impl From<BcryptError> for DataError {
@@ -47,26 +58,32 @@ impl From<BcryptError> for DataError {
 }
 
 pub fn validate_password(password: &str) -> Result<(), validator::ValidationError> {
-    if password == "password" {
-        let mut err = validator::ValidationError::new("insecure");
-        err.message = Some(Cow::from("Password cannot be 'password'".to_string()));
-        Err(err)
-    } else if password == "hunter2" {
-        let mut err = validator::ValidationError::new("insecure");
-        err.message = Some(Cow::from("Nice try, but no.".to_string()));
-        Err(err)
-    } else {
-        Ok(())
-    }
+    crate::passwords::validate_not_common(password)
 }
 
 pub async fn register_new_user(
     conn: &mut Connection<WishlistDb>,
+    checker: &PasswordChecker,
     user: &NewUser<'_>,
 ) -> Result<User, DataError> {
     // Validate the new user form
     user.validate()?;
 
+    // Check the password strength and against known breaches
+    let strength = checker.validate_strength(user.password, &[user.username, user.email]);
+    if let Err(e) = strength {
+        let mut errors = ValidationErrors::new();
+        errors.add("password", e);
+        return Err(errors.into());
+    }
+    if let Err(e) = checker.validate(user.password).await {
+        let mut errors = ValidationErrors::new();
+        errors.add("password", e);
+        return Err(errors.into());
+    }
+
+    check_username_available(conn, user.username, None).await?;
+
     // Hash password
     let password_hash = bcrypt::hash(user.password, bcrypt::DEFAULT_COST)?;
 
@@ -77,22 +94,67 @@ pub async fn register_new_user(
     Ok(user)
 }
 
+/// How long an email verification link stays valid.
+pub const EMAIL_VERIFICATION_TTL_HOURS: i64 = 48;
+
+/// Sends the user a link to verify their email address. The email is queued in the outbox (see
+/// `Delivery`), so it's sent once the connection's transaction is committed.
+pub async fn send_email_verification(
+    conn: &mut Connection<WishlistDb>,
+    site: &SiteUrl,
+    user: &User,
+) -> Result<(), DataError> {
+    let verification = EmailVerification::create(conn, user.id, &user.email).await?;
+    let token = verification.token.as_str();
+    let verify_link = site.url(&uri!(crate::web::account::verify_email(token)).to_string());
+
+    let context = json!({
+        "username": user.username,
+        "link": verify_link,
+        "ttl_hours": EMAIL_VERIFICATION_TTL_HOURS,
+    });
+    Delivery::queue_email(conn, &user.email, "verify_email", &context).await?;
+
+    Ok(())
+}
+
+/// Returns an error if the user can't give a list the privacy yet. Lists other people can see
+/// need a verified email address, so throwaway accounts can't use them for spam.
+pub fn check_can_publish(user: &User, privacy: &str) -> Result<(), DataError> {
+    if privacy == ListPrivacy::Private.as_str() || user.is_email_verified() {
+        Ok(())
+    } else {
+        Err(DataError::Other("Verify your email address before sharing lists".to_string()))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AuthError {
     #[error("Incorrect username or password")]
     InvalidLogin,
+    #[error("This username belongs to an account that doesn't log in through the directory")]
+    DirectoryConflict,
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
     #[error("Bcrypt error: {0}")]
     Bcrypt(#[from] bcrypt::BcryptError),
+    #[error("Directory error: {0}")]
+    Directory(#[from] ldap3::LdapError),
+    #[error("{0}")]
+    Account(#[from] DataError),
     // #[error("Unknown error: {0}")]
     // Unknown(String),
 }
 
 pub async fn verify_user_login(
     conn: &mut Connection<WishlistDb>,
+    directory: &Directory,
     login: &UserLogin<'_>,
 ) -> Result<User, AuthError> {
+    if directory.enabled() {
+        return verify_directory_login(conn, directory, login).await;
+    }
+
     // Get the user from the database
     let user = User::find_by_username(conn, login.username)
         .await?
@@ -106,6 +168,40 @@ pub async fn verify_user_login(
     }
 }
 
+/// The password hash of users created from the directory. It never matches a password, since
+/// their passwords are only checked by the directory.
+pub const DIRECTORY_PASSWORD_HASH: &str = "!directory";
+
+/// Checks the login against the directory, creating the local user the first time they log in.
+async fn verify_directory_login(
+    conn: &mut Connection<WishlistDb>,
+    directory: &Directory,
+    login: &UserLogin<'_>,
+) -> Result<User, AuthError> {
+    let entry = directory
+        .authenticate(login.username, login.password)
+        .await?
+        .ok_or(AuthError::InvalidLogin)?;
+
+    match User::find_by_username(conn, login.username).await? {
+        Some(user) if user.is_from_directory() => Ok(user),
+        // Someone here already has the name, and the directory doesn't vouch for them
+        Some(_) => Err(AuthError::DirectoryConflict),
+        None => {
+            let email = entry.email.unwrap_or_default();
+            let mut user = User::new(
+                login.username.to_string(),
+                email,
+                DIRECTORY_PASSWORD_HASH.to_string(),
+            );
+            user.auth_source = DIRECTORY_AUTH_SOURCE.to_string();
+            let user = user.save(conn).await?;
+            // The directory vouches for its users' addresses
+            Ok(user.mark_email_verified(conn).await?)
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct LoggedInUser {
@@ -118,59 +214,304 @@ impl LoggedInUser {
     }
 }
 
+/// Looks up the user for the request's session cookie, caching the result for the request.
+///
+/// This returns the user regardless of their standing, the request guards decide what to do
+/// with suspended users.
+async fn session_user<'r>(request: &'r Request<'_>) -> &'r Option<LoggedInUser> {
+    request
+        .local_cache_async(async {
+            // Get the session cookie
+            let session_cookie = request.cookies().get("session_id")?;
+
+            // Get the session token
+            let session_token = session_cookie.value();
+
+            // Get the database connection
+            let mut db = request
+                .guard::<Connection<WishlistDb>>()
+                .await
+                .succeeded()?;
+
+            // Get the user session from the database, ignoring it once it's expired even if the
+            // cleanup job hasn't removed it yet
+            let user_session = UserSession::find_by_token(&mut db, session_token)
+                .await
+                .ok()??;
+            if user_session.is_expired() {
+                return None;
+            }
+            if let Err(e) = user_session.record_use(&mut db).await {
+                warn!("Failed to record use of session {}: {}", user_session.id, e);
+            }
+
+            // Get the user from the database
+            User::find_by_id(&mut db, user_session.user_id)
+                .await
+                .ok()?
+                .map(LoggedInUser::new)
+        })
+        .await
+}
+
+/// A logged in user in good standing. Suspended users are forwarded.
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for &'r LoggedInUser {
     type Error = ();
     async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        let user_result = request
-            .local_cache_async(async {
-                // Get the session cookie
-                let session_cookie = request.cookies().get("session_id")?;
+        session_user(request)
+            .await
+            .as_ref()
+            .filter(|u| !u.user.is_suspended())
+            .or_forward(())
+    }
+}
 
-                // Get the session token
-                let session_token = session_cookie.value();
+/// A logged in user whose account is suspended.
+///
+/// Suspended users can only see their suspension notice and submit an appeal.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SuspendedUser<'r> {
+    pub user: &'r User,
+}
 
-                // Get the database connection
-                let mut db = request
-                    .guard::<Connection<WishlistDb>>()
-                    .await
-                    .succeeded()?;
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SuspendedUser<'r> {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        session_user(request)
+            .await
+            .as_ref()
+            .filter(|u| u.user.is_suspended())
+            .map(|u| SuspendedUser { user: &u.user })
+            .or_forward(())
+    }
+}
 
-                // Get the user session from the database
-                let user_session = UserSession::find_by_token(&mut db, session_token)
-                    .await
-                    .ok()??;
+/// A user calling the API with one of their tokens, see `ApiToken`.
+pub struct TokenUser {
+    pub user: User,
+    pub token: ApiToken,
+}
+
+/// Returns the token from the request's `Authorization: Bearer` header, if it has one.
+pub fn bearer_token<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    let header = request.headers().get_one("Authorization")?;
+    let (scheme, token) = header.trim().split_once(' ')?;
+    Some(token.trim()).filter(|_| scheme.eq_ignore_ascii_case("Bearer"))
+}
+
+/// Looks up the user for the request's API token, caching the result for the request.
+///
+/// Like `session_user`, this returns the user regardless of their standing.
+pub async fn token_user<'r>(request: &'r Request<'_>) -> &'r Option<TokenUser> {
+    request
+        .local_cache_async(async {
+            let token = bearer_token(request)?;
 
-                // Get the user from the database
-                User::find_by_id(&mut db, user_session.user_id)
+            let mut db = request
+                .guard::<Connection<WishlistDb>>()
+                .await
+                .succeeded()?;
+
+            let token = ApiToken::find_by_token(&mut db, token).await.ok()??;
+            if let Err(e) = token.record_use(&mut db).await {
+                warn!("Failed to record use of API token {}: {}", token.id, e);
+            }
+
+            User::find_by_id(&mut db, token.user_id)
+                .await
+                .ok()?
+                .map(|user| TokenUser { user, token })
+        })
+        .await
+}
+
+/// A user in good standing authenticated by an `Authorization: Bearer` token. Suspended users
+/// and unknown tokens are forwarded.
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r TokenUser {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        token_user(request)
+            .await
+            .as_ref()
+            .filter(|u| !u.user.is_suspended())
+            .or_forward(())
+    }
+}
+
+/// A permission a route needs, see `Permission`.
+pub trait RequiredPermission: Send + Sync + 'static {
+    const KIND: PermissionKind;
+}
+
+/// Marker types for the `Permission` guard, one per `PermissionKind`.
+pub mod permissions {
+    use super::RequiredPermission;
+    use crate::db::models::PermissionKind;
+
+    pub struct ModerateContent;
+    pub struct ManageSettings;
+    pub struct ManageUsers;
+    pub struct ViewAnalytics;
+
+    impl RequiredPermission for ModerateContent {
+        const KIND: PermissionKind = PermissionKind::ModerateContent;
+    }
+    impl RequiredPermission for ManageSettings {
+        const KIND: PermissionKind = PermissionKind::ManageSettings;
+    }
+    impl RequiredPermission for ManageUsers {
+        const KIND: PermissionKind = PermissionKind::ManageUsers;
+    }
+    impl RequiredPermission for ViewAnalytics {
+        const KIND: PermissionKind = PermissionKind::ViewAnalytics;
+    }
+}
+
+/// Everything the request's user may do, cached for the request.
+struct UserPermissions(HashSet<PermissionKind>);
+
+/// Returns the user whose permissions apply to the request. That's the session's user, or the
+/// API token's user if the token has the admin scope. Suspended users are included.
+async fn permission_user<'r>(request: &'r Request<'_>) -> Option<&'r User> {
+    if let Some(user) = session_user(request).await {
+        return Some(&user.user);
+    }
+
+    token_user(request)
+        .await
+        .as_ref()
+        .filter(|u| u.token.has_scope(ApiScope::Admin))
+        .map(|u| &u.user)
+}
+
+/// Looks up the permissions of the request's user. Admins have every permission.
+async fn session_permissions<'r>(request: &'r Request<'_>) -> &'r UserPermissions {
+    request
+        .local_cache_async(async {
+            let user = match permission_user(request).await {
+                Some(user) if !user.is_suspended() => user,
+                _ => return UserPermissions(HashSet::new()),
+            };
+            if user.is_admin {
+                return UserPermissions(PermissionKind::ALL.into_iter().collect());
+            }
+
+            let permissions = match request.guard::<Connection<WishlistDb>>().await.succeeded() {
+                Some(mut db) => Role::permissions_for_user(&mut db, user.id)
                     .await
-                    .ok()?
-                    .map(|u| LoggedInUser::new(u))
+                    .unwrap_or_default(),
+                None => HashSet::new(),
+            };
+            UserPermissions(permissions)
+        })
+        .await
+}
+
+/// A logged in user in good standing who has permission `P` through one of their roles, or is
+/// an admin. API tokens with the admin scope count as their user. Anyone else is forwarded.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Permission<'r, P> {
+    pub user: &'r User,
+    #[serde(skip)]
+    permission: PhantomData<P>,
+}
+
+#[rocket::async_trait]
+impl<'r, P: RequiredPermission> FromRequest<'r> for Permission<'r, P> {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if !session_permissions(request).await.0.contains(&P::KIND) {
+            return Outcome::Forward(());
+        }
+
+        permission_user(request)
+            .await
+            .map(|user| Permission {
+                user,
+                permission: PhantomData,
             })
-            .await;
+            .or_forward(())
+    }
+}
 
-        user_result.as_ref().or_forward(())
+/// Everything the request's user may do. Always succeeds.
+pub struct Permissions(pub HashSet<PermissionKind>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Permissions {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Permissions(session_permissions(request).await.0.clone()))
     }
 }
 
+/// Information about the client making the request, stored with new sessions.
+pub struct DeviceInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DeviceInfo {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(DeviceInfo {
+            user_agent: request.headers().get_one("User-Agent").map(|ua| ua.to_string()),
+            ip_address: request.client_ip().map(|ip| ip.to_string()),
+        })
+    }
+}
+
+/// How long a session logs the user in for, at most, when its cookie lasts until the browser
+/// closes.
+pub const SESSION_TTL_DAYS: i64 = 7;
+
+/// How long a session logs the user in for when they ask to be remembered.
+pub const REMEMBER_ME_TTL_DAYS: i64 = 30;
+
+/// Logs the user in with a new session and sets its cookie.
+///
+/// The session lasts for `remember_for` if it's given, and the cookie is kept that long too.
+/// Otherwise the cookie is dropped when the browser closes, and the session expires after
+/// `SESSION_TTL_DAYS` in case the browser keeps it.
 pub async fn create_user_session(
     conn: &mut Connection<WishlistDb>,
     cookies: &CookieJar<'_>,
     user: &User,
+    device: &DeviceInfo,
+    remember_for: Option<chrono::Duration>,
 ) -> Result<UserSession, DataError> {
     // Generate a new session token
     let session_token = crate::util::random_token();
 
     // Create the new session
-    let session = UserSession::create(conn, &session_token, user.id).await?;
+    let lifetime = remember_for.unwrap_or_else(|| chrono::Duration::days(SESSION_TTL_DAYS));
+    let expires_at = (chrono::Utc::now() + lifetime).naive_utc();
+    let session = UserSession::create(
+        conn,
+        &session_token,
+        user.id,
+        device.user_agent.as_deref(),
+        device.ip_address.as_deref(),
+        remember_for.is_some(),
+        expires_at,
+    )
+    .await?;
 
     // Set the session cookie
-    let cookie = Cookie::build("session_id", session_token)
+    let mut cookie = Cookie::build("session_id", session_token)
         .path("/")
         .http_only(true)
-        .max_age(Duration::days(7))
         .same_site(rocket::http::SameSite::Strict)
         .finish();
+    if let Some(remember_for) = remember_for {
+        cookie.set_max_age(Duration::seconds(remember_for.num_seconds()));
+    }
 
     cookies.add(cookie);
 
@@ -198,3 +539,300 @@ pub async fn destroy_user_session(
 
     Ok(())
 }
+
+/// Records the session's device and emails the user if it's one they haven't signed in from before.
+/// The email is queued in the outbox (see `Delivery`), so it's sent once the connection's
+/// transaction is committed.
+///
+/// The very first sign-in for an account doesn't send a notification.
+pub async fn notify_new_device(
+    conn: &mut Connection<WishlistDb>,
+    site: &SiteUrl,
+    user: &User,
+    session: &UserSession,
+) -> Result<(), DataError> {
+    let user_agent = session.user_agent.as_deref().unwrap_or("Unknown");
+    let ip_address = session.ip_address.as_deref().unwrap_or("Unknown");
+
+    if UserDevice::find(conn, user.id, user_agent, ip_address)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let is_first_device = UserDevice::count_by_user(conn, user.id).await? == 0;
+    UserDevice::create(conn, user.id, user_agent, ip_address).await?;
+    if is_first_device {
+        return Ok(());
+    }
+
+    let revoke_link = match &session.revoke_token {
+        Some(token) => site.url(&uri!(crate::web::account::revoke_session(token.as_str())).to_string()),
+        None => return Ok(()),
+    };
+
+    let context = json!({
+        "username": user.username,
+        "device": user_agent,
+        "ip_address": ip_address,
+        "time": session.created_at.to_string(),
+        "revoke_link": revoke_link,
+    });
+
+    Delivery::queue_email(conn, &user.email, "new_sign_in", &context).await?;
+
+    Ok(())
+}
+
+#[derive(FromForm, Validate, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChangeEmail<'r> {
+    #[validate(email(message = "Enter a valid email address"))]
+    pub new_email: &'r str,
+    pub password: &'r str,
+}
+
+/// How long an email change confirmation link stays valid.
+pub const EMAIL_CHANGE_TTL_HOURS: i64 = 24;
+
+/// Starts changing the user's email address.
+///
+/// A confirmation link is sent to the new address, and the old address is told about the change
+/// so the owner can cancel it if they didn't ask for it. Both emails are queued in the outbox (see
+/// `Delivery`), so they're sent once the connection's transaction is committed.
+pub async fn request_email_change(
+    conn: &mut Connection<WishlistDb>,
+    site: &SiteUrl,
+    user: &User,
+    change: &ChangeEmail<'_>,
+) -> Result<(), DataError> {
+    change.validate()?;
+
+    if user.is_from_directory() {
+        return Err(DataError::Other(
+            "Your email address is managed by your organization's directory".to_string(),
+        ));
+    }
+    if !bcrypt::verify(change.password, &user.password_hash)? {
+        return Err(DataError::Other("Incorrect password".to_string()));
+    }
+    if change.new_email.eq_ignore_ascii_case(&user.email) {
+        return Err(DataError::Other("That's already your email address".to_string()));
+    }
+    if User::email_taken(conn, change.new_email, user.id).await? {
+        return Err(DataError::Other("That email address is already in use".to_string()));
+    }
+
+    let token = user.request_email_change(conn, change.new_email).await?;
+    let confirm_link = site.url(&uri!(crate::web::account::confirm_email(token.as_str())).to_string());
+
+    let confirm_context = json!({
+        "username": user.username,
+        "link": confirm_link,
+        "ttl_hours": EMAIL_CHANGE_TTL_HOURS,
+    });
+    Delivery::queue_email(
+        conn,
+        change.new_email,
+        "confirm_email_change",
+        &confirm_context,
+    )
+    .await?;
+
+    let notice_context = json!({
+        "username": user.username,
+        "new_email": change.new_email,
+        "link": site.url(&uri!(crate::web::account::email).to_string()),
+    });
+    Delivery::queue_email(conn, &user.email, "email_change_notice", &notice_context).await?;
+
+    Ok(())
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ForgotPassword<'r> {
+    pub email: &'r str,
+}
+
+#[derive(FromForm, Validate, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResetPassword<'r> {
+    #[validate(
+        length(
+            min = 8,
+            max = 128,
+            message = "Password must be longer than 8 characters."
+        ),
+        custom = "validate_password"
+    )]
+    pub password: &'r str,
+    #[validate(must_match(other = "password", message = "Passwords must match"))]
+    pub password_confirm: &'r str,
+}
+
+/// How long a password reset link stays valid.
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+
+/// How long to wait before sending another reset link to the same account, so the form can't be
+/// used to flood someone's inbox.
+pub const PASSWORD_RESET_INTERVAL_MINUTES: i64 = 5;
+
+/// Emails a password reset link to the account with the given address, if there is one. The
+/// email is queued in the outbox (see `Delivery`), so it's sent once the connection's transaction
+/// is committed.
+///
+/// Nothing says whether an account was found, so the form can't be used to find out who has one.
+/// Accounts from the directory can't be reset here, their passwords are managed there.
+pub async fn request_password_reset(
+    conn: &mut Connection<WishlistDb>,
+    site: &SiteUrl,
+    email: &str,
+) -> Result<(), DataError> {
+    let user = match User::find_by_email(conn, email.trim()).await? {
+        Some(user) if !user.is_from_directory() => user,
+        _ => return Ok(()),
+    };
+
+    let now = chrono::Utc::now();
+    let recently = (now - chrono::Duration::minutes(PASSWORD_RESET_INTERVAL_MINUTES)).naive_utc();
+    if PasswordResetToken::sent_since(conn, user.id, recently).await? {
+        return Ok(());
+    }
+
+    let expires_at = (now + chrono::Duration::minutes(PASSWORD_RESET_TTL_MINUTES)).naive_utc();
+    let (_, token) = PasswordResetToken::create(conn, user.id, expires_at).await?;
+    let reset_link =
+        site.url(&uri!(crate::web::account::reset_password(token.as_str())).to_string());
+
+    let context = json!({
+        "username": user.username,
+        "link": reset_link,
+        "ttl_minutes": PASSWORD_RESET_TTL_MINUTES,
+    });
+    Delivery::queue_email(conn, &user.email, "password_reset", &context).await?;
+
+    Ok(())
+}
+
+/// Sets a new password for the user a reset token belongs to, returning the user. The token and
+/// any others the user has are used up, and all of their sessions are logged out.
+pub async fn reset_password(
+    conn: &mut Connection<WishlistDb>,
+    checker: &PasswordChecker,
+    reset: &PasswordResetToken,
+    password: &ResetPassword<'_>,
+) -> Result<User, DataError> {
+    password.validate()?;
+
+    let user = User::find_by_id(conn, reset.user_id)
+        .await?
+        .ok_or_else(|| DataError::Other("That reset link is no longer valid".to_string()))?;
+
+    // Check the password strength and against known breaches
+    let strength = checker.validate_strength(password.password, &[&user.username, &user.email]);
+    if let Err(e) = strength {
+        let mut errors = ValidationErrors::new();
+        errors.add("password", e);
+        return Err(errors.into());
+    }
+    if let Err(e) = checker.validate(password.password).await {
+        let mut errors = ValidationErrors::new();
+        errors.add("password", e);
+        return Err(errors.into());
+    }
+
+    let password_hash = bcrypt::hash(password.password, bcrypt::DEFAULT_COST)?;
+    user.set_password_hash(conn, &password_hash).await?;
+    PasswordResetToken::destroy_by_user(conn, user.id).await?;
+    UserSession::destroy_all_by_user(conn, user.id).await?;
+
+    Ok(user)
+}
+
+#[derive(FromForm, Validate, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChangeUsername<'r> {
+    // validation happens in the user model
+    pub new_username: &'r str,
+    pub password: &'r str,
+}
+
+/// How long an old username redirects to its new profile, and can't be taken by anyone else.
+pub const USERNAME_GRACE_DAYS: i64 = 30;
+
+/// Returns an error if the username belongs to someone, or was given up by someone other than
+/// `user_id` during the grace period.
+async fn check_username_available(
+    conn: &mut Connection<WishlistDb>,
+    username: &str,
+    user_id: Option<i64>,
+) -> Result<(), DataError> {
+    if User::find_by_username(conn, username).await?.is_some() {
+        return Err(DataError::Other("That username is taken".to_string()));
+    }
+
+    let since = (chrono::Utc::now() - chrono::Duration::days(USERNAME_GRACE_DAYS)).naive_utc();
+    match UsernameHistory::find_recent(conn, username, since).await? {
+        Some(history) if Some(history.user_id) != user_id => Err(DataError::Other(
+            "That username was recently used by someone else, try again later".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Changes the user's username after checking their password.
+pub async fn change_username(
+    conn: &mut Connection<WishlistDb>,
+    user: &User,
+    change: &ChangeUsername<'_>,
+) -> Result<User, DataError> {
+    if user.is_from_directory() {
+        return Err(DataError::Other(
+            "Your username is managed by your organization's directory".to_string(),
+        ));
+    }
+    if !bcrypt::verify(change.password, &user.password_hash)? {
+        return Err(DataError::Other("Incorrect password".to_string()));
+    }
+    if change.new_username == user.username {
+        return Err(DataError::Other("That's already your username".to_string()));
+    }
+
+    check_username_available(conn, change.new_username, Some(user.id)).await?;
+
+    user.rename(conn, change.new_username).await
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket_db_pools::sqlx;
+
+    use crate::db::models::DIRECTORY_AUTH_SOURCE;
+    use crate::testing::TestApp;
+
+    #[rocket::async_test]
+    async fn directory_users_keep_their_directory_username() {
+        let app = TestApp::new().await;
+        app.login("alice").await;
+        sqlx::query(r#"UPDATE users SET auth_source = $1 WHERE username = 'alice'"#)
+            .bind(DIRECTORY_AUTH_SOURCE)
+            .execute(app.pool())
+            .await
+            .expect("users can be changed");
+
+        let form = "new_username=alicia&password=password";
+        let (_, _, page) = app.post_form("/account/username", form).await;
+        assert!(
+            page.contains("managed by your organization"),
+            "the name is the directory's"
+        );
+        let renamed: Option<i64> =
+            sqlx::query_scalar(r#"SELECT id FROM users WHERE username = 'alicia'"#)
+                .fetch_optional(app.pool())
+                .await
+                .expect("users can be read");
+        assert_eq!(renamed, None);
+    }
+}
@@ -0,0 +1,168 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{Claim, ClaimEvent, Item, List, SharePermission};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::features::{flags, Enabled};
+use crate::notify::Dispatcher;
+use crate::surprise::{self, Access};
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants};
+use crate::web::{self, WebError};
+
+/// Returns the item if it's on a list the user can claim on and take part in gifting on. See
+/// `crate::surprise` and `ShareGrants::allows`.
+async fn find_item(
+    db: &mut Connection<WishlistDb>,
+    grants: &ShareGrants,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+) -> Result<(List, Item), WebError<Template>> {
+    let list = shared_list(db, grants, Some(user), list_key, SharePermission::Claim).await?;
+    let list = surprise::gifting_list(Some(list), user.user.id, Access::TakePart)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let item = Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok((list, item))
+}
+
+/// Claims the item, so other people know the user is getting it.
+#[post("/lists/<list_key>/items/<id>/claim")]
+pub async fn claim(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+    if !item.is_claimable() {
+        return Err(DataError::Other("This item can't be claimed".to_string()).into());
+    }
+
+    match Claim::find_by_item(&mut db, item.id).await? {
+        Some(claim) if claim.user_id == user.user.id => {}
+        Some(_) => {
+            return Err(DataError::Other("Someone already claimed this item".to_string()).into())
+        }
+        None => {
+            let mut tx = Transaction::begin(db).await?;
+            Claim::create(&mut tx, item.id, user.user.id).await?;
+            let event = DomainEvent::ItemClaimed {
+                list: ListRef::from(&list),
+                item: ItemRef::from(&item),
+                by: user.user.id,
+            };
+            dispatcher.dispatch(&mut tx, event).await?;
+            dispatcher.commit(tx).await?;
+        }
+    }
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+/// Gives up the user's claim on the item.
+#[delete("/lists/<list_key>/items/<id>/claim")]
+pub async fn unclaim(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+
+    let mut tx = Transaction::begin(db).await?;
+    if Claim::destroy_by_user(&mut tx, item.id, user.user.id).await? {
+        let event = DomainEvent::ItemUnclaimed {
+            list: ListRef::from(&list),
+            item: ItemRef::from(&item),
+            by: user.user.id,
+        };
+        dispatcher.dispatch(&mut tx, event).await?;
+    }
+    dispatcher.commit(tx).await?;
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+#[derive(FromForm)]
+pub struct MarkPurchased {
+    pub purchased: bool,
+}
+
+/// Records that the user bought the item they claimed, so they aren't reminded about it, or that
+/// they haven't after all. See `crate::jobs::claim_reminders`.
+#[post("/lists/<list_key>/items/<id>/claim/purchased", format = "form", data = "<mark>")]
+pub async fn purchased(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    mark: Form<MarkPurchased>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+
+    let mut tx = Transaction::begin(db).await?;
+    if !Claim::set_purchased(&mut tx, item.id, user.user.id, mark.purchased).await? {
+        return Err(DataError::Other("You haven't claimed this item".to_string()).into());
+    }
+    tx.commit().await?;
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+/// Shows the item's claim history to someone who has claimed it, so they can see what happened
+/// to their claim. Other claimers stay anonymous, like they are on the item page.
+#[get("/lists/<list_key>/items/<id>/claim/history")]
+pub async fn history(
+    _enabled: Enabled<flags::ClaimHistory>,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Template, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+    if !ClaimEvent::has_claimed(&mut db, item.id, user.user.id).await? {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    let events = ClaimEvent::all_by_item(&mut db, item.id)
+        .await?
+        .into_iter()
+        .map(|event| {
+            context! {
+                kind: event.kind,
+                by_you: event.user_id == Some(user.user.id),
+                created_at: event.created_at,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "claims/history",
+        context! { user, list, item, events },
+    ))
+}
@@ -0,0 +1,334 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{GiftSplit, Item, ItemKind, List, Poll};
+use crate::db::{DataError, WishlistDb};
+use crate::locale::Locale;
+use crate::surprise::{self, Access, Viewer};
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants};
+use crate::web::WebError;
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StartPoll<'r> {
+    /// Dates in `YYYY-MM-DD` form, as sent by date inputs. Empty ones are skipped.
+    #[serde(borrow)]
+    pub dates: Vec<&'r str>,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Vote {
+    pub option_ids: Vec<i64>,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChooseDate {
+    /// The option to close the poll with, or `None` to reopen it.
+    pub option_id: Option<i64>,
+}
+
+/// A date on the poll page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DateSummary {
+    pub id: i64,
+    pub date: String,
+    pub vote_count: i64,
+    pub voted: bool,
+    pub chosen: bool,
+}
+
+/// Returns the experience item if it's on the list, the user can see the list or has a share for
+/// the access (see `ShareGrants::allows`), and they're allowed the access. See `crate::surprise`.
+async fn find_item(
+    db: &mut Connection<WishlistDb>,
+    grants: &ShareGrants,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+    access: Access,
+) -> Result<(List, Item), WebError<Template>> {
+    let list = shared_list(db, grants, Some(user), list_key, access.share_permission()).await?;
+    let list = surprise::gifting_list(Some(list), user.user.id, access)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let item = Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id && item.kind() == ItemKind::Experience)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok((list, item))
+}
+
+/// Whether the user is going in on the item. Only they can see and vote in its poll.
+async fn is_participant(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    item: &Item,
+) -> Result<bool, WebError<Template>> {
+    let split = match GiftSplit::find_by_item(db, item.id).await? {
+        Some(split) => split,
+        None => return Ok(false),
+    };
+
+    Ok(split
+        .contributors(db)
+        .await?
+        .iter()
+        .any(|c| c.user_id == user.user.id))
+}
+
+/// Returns the item's poll, if the user can use it.
+async fn find_poll(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    item: &Item,
+) -> Result<Poll, WebError<Template>> {
+    if !is_participant(db, user, item).await? {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    Poll::find_by_item(db, item.id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))
+}
+
+/// Shows the date poll for an experience item to the people going in on it, and to the owner if
+/// they turned on `List::reveal_gifting`.
+#[get("/lists/<list_key>/items/<id>/poll")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Template, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::View).await?;
+    let revealed_owner = Viewer::of(&list, Some(user.user.id)) == Viewer::RevealedOwner;
+
+    if !revealed_owner && !is_participant(&mut db, user, &item).await? {
+        return Ok(Template::render(
+            "date_polls/show",
+            context! { user, list, item, participant: false },
+        ));
+    }
+
+    let poll = match Poll::find_by_item(&mut db, item.id).await? {
+        Some(poll) => poll,
+        None => {
+            return Ok(Template::render(
+                "date_polls/show",
+                context! { user, list, item, participant: true, revealed_owner },
+            ))
+        }
+    };
+
+    let locale = Locale::new(list.language.as_deref());
+    let voted = poll.votes_by_user(&mut db, user.user.id).await?;
+    let dates = poll
+        .options(&mut db)
+        .await?
+        .into_iter()
+        .map(|option| DateSummary {
+            id: option.id,
+            date: chrono::NaiveDate::parse_from_str(&option.label, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|date| locale.format_date(date))
+                .unwrap_or(option.label),
+            vote_count: option.vote_count,
+            voted: voted.contains(&option.id),
+            chosen: poll.chosen_option_id == Some(option.id),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "date_polls/show",
+        context! {
+            user,
+            list,
+            item,
+            participant: true,
+            revealed_owner,
+            poll: context! {
+                closed: poll.chosen_option_id.is_some(),
+                is_creator: poll.created_by == user.user.id,
+            },
+            dates,
+        },
+    ))
+}
+
+/// Starts a date poll with the given dates.
+#[post("/lists/<list_key>/items/<id>/poll", format = "form", data = "<start>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    start: Form<StartPoll<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+
+    if !is_participant(&mut db, user, &item).await? {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    let mut dates = Vec::new();
+    for date in start
+        .dates
+        .iter()
+        .map(|date| date.trim())
+        .filter(|date| !date.is_empty())
+    {
+        let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| DataError::Other(format!("{} isn't a date", date)))?;
+        dates.push(date);
+    }
+    dates.sort();
+    dates.dedup();
+
+    let options = dates
+        .iter()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .collect::<Vec<_>>();
+
+    Poll::create(
+        &mut db,
+        item.id,
+        user.user.id,
+        "When should it happen?",
+        &options,
+    )
+    .await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Replaces the user's votes with the dates that work for them.
+#[post(
+    "/lists/<list_key>/items/<id>/poll/votes",
+    format = "form",
+    data = "<vote>"
+)]
+pub async fn vote(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    vote: Form<Vote>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let poll = find_poll(&mut db, user, &item).await?;
+
+    poll.set_votes(&mut db, user.user.id, &vote.option_ids)
+        .await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Settles on a date, or reopens the poll. Only whoever started the poll can do this.
+#[put(
+    "/lists/<list_key>/items/<id>/poll",
+    format = "form",
+    data = "<choice>"
+)]
+pub async fn choose(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    choice: Form<ChooseDate>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let mut poll = find_poll(&mut db, user, &item).await?;
+
+    if poll.created_by != user.user.id {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    if let Some(option_id) = choice.option_id {
+        let options = poll.options(&mut db).await?;
+        if !options.iter().any(|option| option.id == option_id) {
+            return Err(WebError::NotFound(Template::render("error/404", ())));
+        }
+    }
+
+    poll.set_chosen_option(&mut db, choice.option_id).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Deletes the poll. Only whoever started the poll can do this.
+#[delete("/lists/<list_key>/items/<id>/poll")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let mut poll = find_poll(&mut db, user, &item).await?;
+
+    if poll.created_by != user.user.id {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    poll.destroy(&mut db).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket::http::Status;
+    use rocket_db_pools::sqlx;
+
+    use crate::testing::TestApp;
+
+    #[rocket::async_test]
+    async fn polls_are_only_on_lists_you_can_see() {
+        let app = TestApp::new().await;
+        sqlx::query(r#"UPDATE items SET kind = 'experience'"#)
+            .execute(app.pool())
+            .await
+            .expect("items can be changed");
+        let birthday = app.list_key("Alice's Birthday").await;
+        let dune = app.item_id("Dune").await;
+        let ideas = app.list_key("Ideas for later").await;
+        let desk = app.item_id("Standing desk").await;
+        app.login("bob").await;
+
+        let poll = format!("/lists/{}/items/{}/poll", birthday, dune);
+        let response = app.client().get(poll.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "public lists can have polls");
+
+        // Ideas for later is private, and Bob has no share for it
+        let poll = format!("/lists/{}/items/{}/poll", ideas, desk);
+        let response = app.client().get(poll.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let (status, _, _) = app.post_form(&poll, "dates=2030-01-01&dates=2030-01-02").await;
+        assert_eq!(status, Status::NotFound);
+    }
+}
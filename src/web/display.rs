@@ -0,0 +1,35 @@
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::plain;
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DisplayChoice {
+    pub plain: bool,
+}
+
+#[get("/display")]
+pub fn show(cookies: &CookieJar<'_>) -> Template {
+    Template::render(
+        "display/show",
+        context! {
+            plain: plain::preference(cookies),
+        },
+    )
+}
+
+#[post("/display", format = "form", data = "<choice>")]
+pub fn update(
+    _csrf: CsrfVerified,
+    cookies: &CookieJar<'_>,
+    choice: Form<DisplayChoice>,
+) -> Redirect {
+    plain::set_preference(cookies, choice.plain);
+
+    Redirect::to(uri!(show))
+}
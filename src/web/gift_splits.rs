@@ -0,0 +1,333 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{GiftSplit, Item, ItemPrice, List};
+use crate::db::{DataError, WishlistDb};
+use crate::locale::Locale;
+use crate::sources::Price;
+use crate::surprise::{self, Access, Viewer};
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants};
+use crate::web::WebError;
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StartSplit<'r> {
+    pub total: &'r str,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MarkPaid {
+    pub paid: bool,
+}
+
+/// A contributor with their share, for the split page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ContributorSummary {
+    pub user_id: i64,
+    pub username: String,
+    pub share: String,
+    pub paid: bool,
+    pub is_organizer: bool,
+}
+
+/// Returns the item if it's on the list, the user can see the list or has a share for the access
+/// (see `ShareGrants::allows`), and they're allowed the access. See `crate::surprise`.
+async fn find_item(
+    db: &mut Connection<WishlistDb>,
+    grants: &ShareGrants,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+    access: Access,
+) -> Result<(List, Item), WebError<Template>> {
+    let list = shared_list(db, grants, Some(user), list_key, access.share_permission()).await?;
+    let list = surprise::gifting_list(Some(list), user.user.id, access)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let item = Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok((list, item))
+}
+
+/// Returns the item's split.
+async fn find_split(
+    db: &mut Connection<WishlistDb>,
+    item: &Item,
+) -> Result<GiftSplit, WebError<Template>> {
+    GiftSplit::find_by_item(db, item.id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))
+}
+
+/// Shows the item's split. Who has paid is only shown to contributors and owners who turned on
+/// `List::reveal_gifting`, everyone else just sees the total and how many people are chipping in.
+#[get("/lists/<list_key>/items/<id>/split")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Template, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::View).await?;
+    let locale = Locale::new(list.language.as_deref());
+    let revealed_owner = Viewer::of(&list, Some(user.user.id)) == Viewer::RevealedOwner;
+
+    let split = match GiftSplit::find_by_item(&mut db, item.id).await? {
+        Some(split) => split,
+        None => {
+            let price = ItemPrice::find_latest(&mut db, item.id)
+                .await?
+                .map(|price| locale.format_price(price.amount_cents, &price.currency));
+            return Ok(Template::render(
+                "gift_splits/show",
+                context! { user, list, item, price, revealed_owner },
+            ));
+        }
+    };
+
+    let contributors = split.contributors(&mut db).await?;
+    let shares = split.shares(contributors.len());
+    let is_contributor = contributors.iter().any(|c| c.user_id == user.user.id);
+    let total = locale.format_price(split.total_cents, &split.currency);
+
+    if !is_contributor && !revealed_owner {
+        return Ok(Template::render(
+            "gift_splits/show",
+            context! {
+                user,
+                list,
+                item,
+                split: context! { total, contributor_count: contributors.len() },
+            },
+        ));
+    }
+
+    let outstanding: i64 = contributors
+        .iter()
+        .zip(&shares)
+        .filter(|(c, _)| c.paid_at.is_none())
+        .map(|(_, share)| share)
+        .sum();
+
+    let contributors = contributors
+        .into_iter()
+        .zip(shares)
+        .map(|(c, share)| ContributorSummary {
+            user_id: c.user_id,
+            username: c.username,
+            share: locale.format_price(share, &split.currency),
+            paid: c.paid_at.is_some(),
+            is_organizer: c.user_id == split.organizer_id,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "gift_splits/show",
+        context! {
+            user,
+            list: &list,
+            item,
+            split: context! {
+                total,
+                contributor_count: contributors.len(),
+                outstanding: locale.format_price(outstanding, &split.currency),
+                settled: outstanding == 0,
+            },
+            contributors,
+            is_contributor,
+            revealed_owner,
+            is_organizer: split.organizer_id == user.user.id,
+        },
+    ))
+}
+
+/// Starts a split with the user as the organizer, or changes the total if they already are.
+#[post(
+    "/lists/<list_key>/items/<id>/split",
+    format = "form",
+    data = "<start>"
+)]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    start: Form<StartSplit<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let existing = GiftSplit::find_by_item(&mut db, item.id).await?;
+
+    let currency = match &existing {
+        Some(split) => split.currency.clone(),
+        None => ItemPrice::find_latest(&mut db, item.id)
+            .await?
+            .map(|price| price.currency)
+            .unwrap_or_else(|| "USD".to_string()),
+    };
+
+    let total = Price::parse(start.total, Some(&currency))
+        .ok_or_else(|| DataError::Other("Total must be a number".to_string()))?;
+
+    match existing {
+        Some(split) if split.organizer_id == user.user.id => {
+            split.set_total(&mut db, total.amount_cents).await?;
+        }
+        Some(_) => {
+            return Err(
+                DataError::Other("Someone is already organizing this gift".to_string()).into(),
+            )
+        }
+        None => {
+            GiftSplit::create(
+                &mut db,
+                item.id,
+                user.user.id,
+                total.amount_cents,
+                &total.currency,
+            )
+            .await?;
+        }
+    }
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Cancels the split. Only the organizer can do this.
+#[delete("/lists/<list_key>/items/<id>/split")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let mut split = find_split(&mut db, &item).await?;
+
+    if split.organizer_id != user.user.id {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    split.destroy(&mut db).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+#[post("/lists/<list_key>/items/<id>/split/contributors")]
+pub async fn join(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let split = find_split(&mut db, &item).await?;
+
+    split.join(&mut db, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Leaves the split. The organizer can't leave, and nobody can leave after they've paid.
+#[delete("/lists/<list_key>/items/<id>/split/contributors")]
+pub async fn leave(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let split = find_split(&mut db, &item).await?;
+
+    if split.organizer_id == user.user.id {
+        return Err(DataError::Other(
+            "The organizer can't leave, cancel the split instead".to_string(),
+        )
+        .into());
+    }
+
+    split.leave(&mut db, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+/// Marks whether a contributor has paid the organizer back. Only the organizer can do this.
+#[post(
+    "/lists/<list_key>/items/<id>/split/contributors/<user_id>/paid",
+    format = "form",
+    data = "<mark>"
+)]
+pub async fn paid(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    user_id: i64,
+    mark: Form<MarkPaid>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id, Access::TakePart).await?;
+    let split = find_split(&mut db, &item).await?;
+
+    if split.organizer_id != user.user.id {
+        return Err(WebError::NotFound(Template::render("error/404", ())));
+    }
+
+    split.set_paid(&mut db, user_id, mark.paid).await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::testing::TestApp;
+
+    #[rocket::async_test]
+    async fn splits_are_only_on_lists_you_can_see() {
+        let app = TestApp::new().await;
+        let birthday = app.list_key("Alice's Birthday").await;
+        let dune = app.item_id("Dune").await;
+        let ideas = app.list_key("Ideas for later").await;
+        let desk = app.item_id("Standing desk").await;
+        app.login("bob").await;
+
+        let split = format!("/lists/{}/items/{}/split", birthday, dune);
+        let response = app.client().get(split.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "public lists can be split on");
+
+        // Ideas for later is private, and Bob has no share for it
+        let split = format!("/lists/{}/items/{}/split", ideas, desk);
+        let response = app.client().get(split.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let (status, _, _) = app.post_form(&split, "total=%24400").await;
+        assert_eq!(status, Status::NotFound);
+    }
+}
@@ -0,0 +1,291 @@
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Header};
+use rocket::response::Redirect;
+use rocket::serde::Serialize;
+use rocket::tokio::fs::File;
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::Template;
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{Image, Item, List};
+use crate::db::{DataError, WishlistDb};
+use crate::features::{flags, Enabled, FeatureFlags};
+use crate::images::{ImageScanner, ImageSigner, ImageStore};
+use crate::quotas::Quotas;
+use crate::sources::SourceRegistry;
+use crate::util;
+use crate::web::auth::LoggedInUser;
+use crate::web::{self, WebError};
+
+/// Photos picked in a form.
+#[derive(FromForm)]
+pub struct ImageUpload<'r> {
+    pub images: Vec<TempFile<'r>>,
+}
+
+/// An image on a page, with a signed URL it can be fetched from.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ImageLink {
+    pub id: i64,
+    pub url: String,
+}
+
+/// An image's file, sent with the content type it was stored with.
+#[derive(Responder)]
+pub struct ImageFile {
+    file: File,
+    content_type: ContentType,
+    cache_control: Header<'static>,
+}
+
+/// Returns a signed URL for the image, see `ImageSigner`.
+pub fn signed_url(signer: &ImageSigner, image_id: i64) -> String {
+    let (signature, expires) = signer.sign(image_id);
+    uri!(show(image_id, Some(expires), Some(signature.as_str()))).to_string()
+}
+
+/// Returns the item's images, with signed URLs.
+pub async fn links_by_item(
+    db: &mut Connection<WishlistDb>,
+    signer: &ImageSigner,
+    item_id: i64,
+) -> Result<Vec<ImageLink>, DataError> {
+    Ok(Image::all_by_item(db, item_id)
+        .await?
+        .into_iter()
+        .map(|image| ImageLink {
+            url: signed_url(signer, image.id),
+            id: image.id,
+        })
+        .collect())
+}
+
+/// Stores an image and puts it on the item, returning why it couldn't be if it wasn't.
+async fn store_image(
+    db: &mut Connection<WishlistDb>,
+    store: &ImageStore,
+    scanner: &ImageScanner,
+    quotas: &Quotas,
+    user_id: i64,
+    item: &Item,
+    data: Vec<u8>,
+    source_url: Option<&str>,
+) -> Result<Option<String>, DataError> {
+    if Image::count_by_item(db, item.id).await? >= Image::MAX_PER_ITEM {
+        return Ok(Some(format!(
+            "items can have at most {} photos",
+            Image::MAX_PER_ITEM
+        )));
+    }
+
+    if let Some(message) = quotas.check_upload(db, user_id, data.len() as i64).await? {
+        return Ok(Some(message));
+    }
+
+    let (data, content_type) = match store.prepare(data, scanner).await {
+        Ok(prepared) => prepared,
+        Err(e) => return Ok(Some(e.to_string())),
+    };
+
+    let image = Image::create(db, user_id, content_type, data.len() as i64, source_url).await?;
+    if let Err(e) = store.write(image.id, &data).await {
+        error!("Failed to store image {}: {}", image.id, e);
+        image.destroy_if_unused(db).await?;
+        return Ok(Some(
+            "could not be saved, please try again later".to_string(),
+        ));
+    }
+    image.attach(db, item.id).await?;
+
+    Ok(None)
+}
+
+/// Stores the uploaded files and puts them on the item, returning a message for each one that
+/// couldn't be added. Empty files, which browsers send when nothing was picked, are ignored.
+pub async fn attach_files(
+    db: &mut Connection<WishlistDb>,
+    store: &ImageStore,
+    scanner: &ImageScanner,
+    quotas: &Quotas,
+    user_id: i64,
+    item: &Item,
+    files: &[TempFile<'_>],
+) -> Result<Vec<String>, DataError> {
+    let mut skipped = Vec::new();
+    for (n, file) in files.iter().filter(|file| file.len() > 0).enumerate() {
+        let name = file
+            .name()
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("Photo {}", n + 1));
+
+        let data = match util::read_temp_file(file).await {
+            Ok(data) => data,
+            Err(e) => {
+                skipped.push(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+
+        if let Some(message) =
+            store_image(db, store, scanner, quotas, user_id, item, data, None).await?
+        {
+            skipped.push(format!("{}: {}", name, message));
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Downloads an image found on the item's page and puts it on the item, returning why it
+/// couldn't be if it wasn't.
+pub async fn attach_url(
+    db: &mut Connection<WishlistDb>,
+    sources: &SourceRegistry,
+    store: &ImageStore,
+    scanner: &ImageScanner,
+    quotas: &Quotas,
+    user_id: i64,
+    item: &Item,
+    url: &str,
+) -> Result<Option<String>, DataError> {
+    let data = match sources.fetch_file(url, store.max_upload_size).await {
+        Ok(data) => data,
+        Err(e) => return Ok(Some(format!("The photo from the link: {}", e))),
+    };
+
+    Ok(
+        store_image(db, store, scanner, quotas, user_id, item, data, Some(url))
+            .await?
+            .map(|message| format!("The photo from the link: {}", message)),
+    )
+}
+
+/// Returns the list and item if the list is the user's.
+async fn find_own_item(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+) -> Result<(List, Item), WebError<Template>> {
+    let list = List::find_by_key(db, list_key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let item = Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok((list, item))
+}
+
+/// Sends an image's file. Images can be fetched by whoever uploaded them, or by anyone with a
+/// signed URL from a page showing them.
+#[get("/images/<id>?<expires>&<signature>")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    signer: &State<ImageSigner>,
+    store: &State<ImageStore>,
+    user: Option<&LoggedInUser>,
+    id: i64,
+    expires: Option<i64>,
+    signature: Option<&str>,
+) -> Result<ImageFile, WebError<Template>> {
+    let signed = match (expires, signature) {
+        (Some(expires), Some(signature)) => signer.verify(id, expires, signature),
+        _ => false,
+    };
+
+    let image = Image::find_by_id(&mut db, id)
+        .await?
+        .filter(|image| signed || user.map(|user| user.user.id) == Some(image.user_id))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let file = File::open(store.file_path(image.id))
+        .await
+        .map_err(|_| WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(ImageFile {
+        file,
+        content_type: ContentType::parse_flexible(&image.content_type)
+            .unwrap_or(ContentType::Binary),
+        cache_control: Header::new("Cache-Control", "private, max-age=3600"),
+    })
+}
+
+/// Adds photos to one of the user's items, from the edit page.
+#[post(
+    "/lists/<list_key>/items/<id>/images",
+    format = "multipart/form-data",
+    data = "<upload>"
+)]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    signer: &State<ImageSigner>,
+    store: &State<ImageStore>,
+    scanner: &State<ImageScanner>,
+    quotas: &State<Quotas>,
+    features: &State<FeatureFlags>,
+    _enabled: Enabled<flags::ImageUploads>,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+    upload: Form<ImageUpload<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_own_item(&mut db, user, list_key, id).await?;
+
+    let skipped = attach_files(
+        &mut db,
+        store,
+        scanner,
+        quotas,
+        user.user.id,
+        &item,
+        &upload.images,
+    )
+    .await?;
+    if !skipped.is_empty() {
+        let template = web::items::render_edit(
+            &mut db,
+            signer,
+            features,
+            Some(user.user.id),
+            list,
+            Some(item),
+            Some(skipped.join(". ")),
+        )
+        .await?;
+        return Err(WebError::Invalid(template));
+    }
+
+    Ok(Redirect::to(uri!(web::items::edit(list.key, item.id))))
+}
+
+/// Takes a photo off one of the user's items.
+#[delete("/lists/<list_key>/items/<id>/images/<image_id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    store: &State<ImageStore>,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+    image_id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_own_item(&mut db, user, list_key, id).await?;
+
+    let image = Image::find_by_item(&mut db, item.id, image_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    image.detach(&mut db, item.id).await?;
+    if image.destroy_if_unused(&mut db).await? {
+        store.remove(image.id).await;
+    }
+
+    Ok(Redirect::to(uri!(web::items::edit(list.key, item.id))))
+}
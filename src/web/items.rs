@@ -1,89 +1,416 @@
 use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::http::Header;
 use rocket::response::Redirect;
 use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
 use rocket_db_pools::Connection;
 use rocket_dyn_templates::{context, Template};
 
-use crate::db::models::{Item, List};
-use crate::db::{DataError, WishlistDb};
+use crate::affiliate::AffiliatePolicy;
+use crate::api::v1::items::{optional_url, parse_details, parse_kind, EditItem};
+use crate::csrf::CsrfVerified;
+use crate::db::models::{
+    Claim, ClaimEvent, FundLink, Image, Item, ItemContribution, ItemFilter, ItemGift, ItemKind,
+    ItemOrder, ItemPrice, List, PriceAlert, SharePermission, Tag,
+};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::features::{Feature, FeatureFlags};
+use crate::images::{ImageScanner, ImageSigner, ImageStore};
+use crate::locale::Locale;
+use crate::notify::Dispatcher;
+use crate::pagination::{Page, Pagination};
+use crate::privacy::Tracking;
+use crate::quotas::Quotas;
+use crate::sources::{Price, SourceError, SourceRegistry};
+use crate::surprise::{self, Access, Viewer};
+use crate::util::format_price;
+use crate::views::{ItemLinks, ItemView};
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants};
 use crate::web::{self, WebError};
 
-#[derive(FromForm, Deserialize, Serialize)]
-#[serde(crate = "rocket::serde")]
-pub struct CreateItem<'r> {
+/// The new item form. Like `CreateItem`, but it can also have photos.
+#[derive(FromForm)]
+pub struct NewItemUpload<'r> {
     pub title: &'r str,
     pub description: &'r str,
+    pub url: &'r str,
+    /// See `ItemKind::as_str`.
+    pub kind: &'r str,
+    /// The gift card's value or the cash fund's goal, e.g. "$50".
+    pub amount: &'r str,
+    /// What the item costs, e.g. "$24.99" or "12.50 EUR". Can be filled in from the link, see
+    /// `fill`.
+    pub price: &'r str,
+    /// The high end of the item's price range, see `EditItem::price_max`.
+    pub price_max: &'r str,
+    /// How many are wanted. Defaults to one.
+    pub quantity: Option<i64>,
+    /// See `ItemPriority::as_str`.
+    pub priority: &'r str,
+    /// Comma separated, like "books, under $50". See `Tag::parse_names`.
+    pub tags: Option<&'r str>,
+    /// Photos to put on the item. Only lists with an owner can have photos, since they count
+    /// towards the owner's upload quota.
+    pub images: Vec<TempFile<'r>>,
+    /// A photo to download and put on the item, found on the page at its link.
+    pub image_url: Option<&'r str>,
+    /// Set by the "Fill in from link" button, to fill in the form from the page at `url` rather
+    /// than adding the item.
+    pub fill: bool,
 }
 
 #[derive(FromForm, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
-pub struct EditItem<'r> {
-    pub title: &'r str,
-    pub description: &'r str,
+pub struct MarkReceived {
+    pub received: bool,
+}
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Contribute<'r> {
+    pub amount: &'r str,
+}
+
+/// Formats the low and high ends of the price the item was added with. There's only a high end
+/// for price ranges.
+fn format_item_prices(locale: &Locale, item: &Item) -> (Option<String>, Option<String>) {
+    let format = |cents: Option<i64>| match (cents, &item.price_currency) {
+        (Some(cents), Some(currency)) => Some(locale.format_price(cents, currency)),
+        _ => None,
+    };
+    (format(item.price_cents), format(item.price_max_cents))
+}
+
+/// Formats the price the item was added with, as a range like "$20.00 – $40.00" if it has one.
+fn format_item_price(locale: &Locale, item: &Item) -> Option<String> {
+    match format_item_prices(locale, item) {
+        (Some(low), Some(high)) => Some(format!("{} – {}", low, high)),
+        (low, _) => low,
+    }
+}
+
+/// Formats a gift card's value or a cash fund's goal.
+fn format_amount(locale: &Locale, item: &Item) -> Option<String> {
+    match (item.amount_cents, &item.currency) {
+        (Some(amount_cents), Some(currency)) => Some(locale.format_price(amount_cents, currency)),
+        _ => None,
+    }
 }
 
-#[get("/lists/<list_key>/items")]
+/// Shows the list's items, or only the ones with `tag` if it's given. They're in the list's order
+/// unless `sort` is "price", see `ItemOrder`.
+#[get("/lists/<list_key>/items?<tag>&<sort>&<page..>")]
 pub async fn index(
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    affiliate: &State<AffiliatePolicy>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
+    tag: Option<&str>,
+    sort: Option<&str>,
+    page: Option<Page>,
 ) -> Result<Template, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let page = page.unwrap_or_default();
+    let order = sort.and_then(ItemOrder::parse).unwrap_or(ItemOrder::Position);
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::View).await?;
+
+    let viewer = Viewer::of(&list, user.map(|user| user.user.id));
+    let claimers = Claim::claimers_by_list(&mut db, list.id).await?;
+    let mut tags = Tag::names_by_list(&mut db, list.id).await?;
 
-    let items = Item::all_by_list(&mut db, list.id)
+    let tag = tag.map(Tag::normalize).filter(|tag| !tag.is_empty());
+    let filter = ItemFilter {
+        tag: tag.as_deref(),
+        order,
+        ..ItemFilter::default()
+    };
+    let total = Item::count_filtered(&mut db, list.id, &filter).await?;
+    let mut items = Item::page_filtered(&mut db, list.id, &filter, &page)
         .await
-        .unwrap_or(vec![])
+        .unwrap_or(vec![]);
+    let sort = Some(order.as_str()).filter(|_| order != ItemOrder::Position);
+    let pagination = Pagination::new(&page, total)
+        .with_filter("tag", tag.as_deref())
+        .with_filter("sort", sort);
+    affiliate.rewrite_items(&list, &mut items);
+    let locale = Locale::new(list.language.as_deref());
+    let items = ItemView::all(&ItemLinks::new(None, &list), items)
         .into_iter()
-        .map(|item| {
-            let link = uri!(show(&list.key, item.id)).to_string();
+        .map(|view| {
+            let tags = tags
+                .remove(&view.item.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|name| {
+                    let link = uri!(index(&list.key, Some(name.as_str()), sort, _)).to_string();
+                    context! { name, link }
+                })
+                .collect::<Vec<_>>();
             context! {
-                id: item.id,
-                title: item.title,
-                description: item.description,
-                link,
+                kind_label: view.item.kind().label(),
+                claimed: viewer.conceal(claimers.contains_key(&view.item.id)),
+                price: format_item_price(&locale, &view.item),
+                tags,
+                item: view,
             }
         })
         .collect::<Vec<_>>();
 
+    let all_link = uri!(index(&list.key, _, sort, _)).to_string();
+    let sort_links = context! {
+        position: uri!(index(&list.key, tag.as_deref(), _, _)).to_string(),
+        price: uri!(index(&list.key, tag.as_deref(), Some("price"), _)).to_string(),
+    };
     Ok(Template::render(
         "items/index",
-        context! { list, items: items },
+        context! {
+            lang: locale.tag(),
+            dir: locale.dir(),
+            list: &list,
+            items: items,
+            tag,
+            all_link,
+            sort: order.as_str(),
+            sort_links,
+            pagination,
+        },
     ))
 }
 
 #[get("/lists/<list_key>/items/new")]
 pub async fn new(
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    features: &State<FeatureFlags>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
 ) -> Result<Template, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::Edit).await?;
+
+    let can_add_images = images_allowed(features, &list, user.map(|user| user.user.id));
+    Ok(Template::render(
+        "items/new",
+        context! { list, can_add_images },
+    ))
+}
+
+/// The new item form's values, for showing it again.
+fn new_item_values<'a>(item: &'a NewItemUpload<'_>) -> impl Serialize + 'a {
+    context! {
+        title: item.title,
+        description: item.description,
+        url: item.url,
+        kind: item.kind,
+        amount: item.amount,
+        price: item.price,
+        price_max: item.price_max,
+        quantity: item.quantity,
+        priority: item.priority,
+        tags: item.tags,
+        image_url: item.image_url,
+    }
+}
+
+/// Fills in the new item form from the page at its link, keeping anything already entered.
+async fn fill_from_link(
+    sources: &SourceRegistry,
+    list: List,
+    item: &NewItemUpload<'_>,
+    can_add_images: bool,
+) -> Result<Template, WebError<Template>> {
+    let metadata = match optional_url(item.url) {
+        Some(url) => sources.fetch_metadata(url).await,
+        None => Err(SourceError::NotFound),
+    };
+    let metadata = match metadata {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            return Err(WebError::Invalid(Template::render(
+                "items/new",
+                context! {
+                    list,
+                    item: new_item_values(item),
+                    can_add_images,
+                    error_message: format!("Couldn't fill in the item from its link: {}", e),
+                },
+            )))
+        }
+    };
 
-    Ok(Template::render("items/new", context! { list }))
+    let keep = |entered: &str| Some(entered.to_string()).filter(|entered| !entered.is_empty());
+    Ok(Template::render(
+        "items/new",
+        context! {
+            list,
+            item: context! {
+                title: keep(item.title).or(metadata.title),
+                description: keep(item.description).or(metadata.description),
+                url: item.url,
+                kind: item.kind,
+                amount: item.amount,
+                quantity: item.quantity,
+                priority: item.priority,
+                tags: item.tags,
+                price_max: item.price_max,
+                price: keep(item.price).or_else(|| {
+                    metadata
+                        .price
+                        .map(|price| format_price(price.amount_cents, &price.currency))
+                }),
+                image_url: item.image_url.and_then(keep).or(metadata.image_url),
+            },
+            can_add_images,
+        },
+    ))
 }
 
-#[post("/lists/<list_key>/items", format = "form", data = "<item>")]
+/// What adding an item responds with, see `create`.
+#[derive(Responder)]
+pub enum AddItem {
+    Added(Redirect),
+    Filled(Template),
+}
+
+/// Adds an item to the list. Takes a plain or a multipart form, so photos can be picked with the
+/// rest of the item on the user's own lists. When the form's "Fill in from link" button is used,
+/// the form is shown again filled in from the item's link instead.
+#[post("/lists/<list_key>/items", data = "<item>")]
 pub async fn create(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    sources: &State<SourceRegistry>,
+    signer: &State<ImageSigner>,
+    store: &State<ImageStore>,
+    scanner: &State<ImageScanner>,
+    features: &State<FeatureFlags>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
-    item: Form<CreateItem<'_>>,
-) -> Result<Redirect, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    item: Form<NewItemUpload<'_>>,
+) -> Result<AddItem, WebError<Template>> {
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::Edit).await?;
+
+    let can_add_images = images_allowed(features, &list, user.map(|user| user.user.id));
+
+    if item.fill {
+        let template = fill_from_link(sources, list, &item, can_add_images).await?;
+        return Ok(AddItem::Filled(template));
+    }
+
+    if let Some(message) = quotas.check_item(&mut db, &list).await? {
+        return Err(WebError::Invalid(Template::render(
+            "items/new",
+            context! {
+                list,
+                item: new_item_values(&item),
+                can_add_images,
+                error_message: message,
+            },
+        )));
+    }
+
+    let parsed = parse_kind(item.kind, item.amount).and_then(|kind| {
+        let details = parse_details(item.price, item.price_max, item.quantity, item.priority)?;
+        let tags = Tag::parse_names(item.tags.unwrap_or_default())?;
+        Ok((kind, details, tags))
+    });
+    let mut tx = Transaction::begin(db).await?;
+    let saved = match parsed {
+        Ok(((kind, amount), details, tags)) => {
+            let mut new_item = Item::new(
+                list.id,
+                item.title.to_string(),
+                item.description.to_string(),
+                optional_url(item.url).map(|url| url.to_string()),
+            );
+            new_item.set_kind(
+                kind,
+                amount.as_ref().map(|price| price.amount_cents),
+                amount.as_ref().map(|price| price.currency.as_str()),
+            );
+            details.apply(&mut new_item);
+            match new_item.save(&mut tx, user.map(|user| user.user.id)).await {
+                Ok(new_item) => Tag::set_for_item(&mut tx, new_item.id, &tags)
+                    .await
+                    .map(|_| new_item),
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    match saved {
+        Ok(new_item) => {
+            let event = DomainEvent::ItemAdded {
+                list: ListRef::from(&list),
+                item: ItemRef::from(&new_item),
+                by: user.map(|user| user.user.id),
+            };
+            dispatcher.dispatch(&mut tx, event).await?;
+            let mut db = dispatcher.commit(tx).await?;
+
+            // The item is saved either way, so photos that couldn't be added are shown on its
+            // edit page where they can be tried again
+            if let Some(user) = user.filter(|_| can_add_images) {
+                let mut skipped = web::images::attach_files(
+                    &mut db,
+                    store,
+                    scanner,
+                    quotas,
+                    user.user.id,
+                    &new_item,
+                    &item.images,
+                )
+                .await?;
+                if let Some(image_url) = item.image_url.and_then(optional_url) {
+                    let message = web::images::attach_url(
+                        &mut db,
+                        sources,
+                        store,
+                        scanner,
+                        quotas,
+                        user.user.id,
+                        &new_item,
+                        image_url,
+                    )
+                    .await?;
+                    skipped.extend(message);
+                }
+                if !skipped.is_empty() {
+                    let template = render_edit(
+                        &mut db,
+                        signer,
+                        features,
+                        Some(user.user.id),
+                        list,
+                        Some(new_item),
+                        Some(skipped.join(". ")),
+                    )
+                    .await?;
+                    return Err(WebError::Invalid(template));
+                }
+            }
 
-    match Item::create(&mut db, list.id, item.title, item.description).await {
-        Ok(item) => Ok(Redirect::to(uri!(web::items::show(list.key, item.id)))),
+            Ok(AddItem::Added(Redirect::to(uri!(web::items::show(
+                list.key,
+                new_item.id
+            )))))
+        }
         Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
             "items/new",
             context! {
                 list,
-                item: context! {
-                    title: item.title,
-                    description: item.description,
-                },
+                item: new_item_values(&item),
+                can_add_images,
                 error_message: "Fix your errors",
                 errors: e,
             },
@@ -92,10 +419,8 @@ pub async fn create(
             "items/new",
             context! {
                 list,
-                item: context! {
-                    title: item.title,
-                    description: item.description,
-                },
+                item: new_item_values(&item),
+                can_add_images,
                 error_message: e.to_string()
             },
         ))),
@@ -105,53 +430,289 @@ pub async fn create(
 #[get("/lists/<list_key>/items/<id>", rank = 2)]
 pub async fn show(
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    affiliate: &State<AffiliatePolicy>,
+    signer: &State<ImageSigner>,
+    features: &State<FeatureFlags>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
     id: i64,
 ) -> Result<Template, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::View).await?;
+
+    let mut item = Item::find_by_id(&mut db, id)
         .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+        .filter(|item| item.list_id == list.id);
+    if let Some(item) = &mut item {
+        affiliate.rewrite_item(&list, item);
+    }
+
+    let locale = Locale::new(list.language.as_deref());
+    // Tracked prices are more up to date than the one the item was added with
+    let price = match &item {
+        Some(item) => ItemPrice::find_latest(&mut db, item.id)
+            .await?
+            .map(|price| locale.format_price(price.amount_cents, &price.currency))
+            .or_else(|| format_item_price(&locale, item)),
+        None => None,
+    };
+
+    let alert = match (user, &item) {
+        (Some(user), Some(item)) => {
+            PriceAlert::find_by_item_and_user(&mut db, item.id, user.user.id)
+                .await?
+                .map(|alert| {
+                    context! {
+                        target_price: locale.format_price(alert.target_cents, &alert.currency),
+                        triggered_on: alert.triggered_at.map(|date| locale.format_date(date)),
+                    }
+                })
+        }
+        _ => None,
+    };
+
+    // Items that are still wanted get the owner's fund link, if they have one
+    let fundable = match (list.user_id, &item) {
+        (Some(owner_id), Some(item)) if item.received_at.is_none() => {
+            FundLink::find_by_user(&mut db, owner_id).await?.is_some()
+        }
+        _ => false,
+    };
+
+    let amount = item.as_ref().and_then(|item| format_amount(&locale, item));
+
+    // Cash funds show how far along they are, and take contributions from anyone but the owner
+    let viewer = Viewer::of(&list, user.map(|user| user.user.id));
+    let cash_fund = match &item {
+        Some(item) if item.kind() == ItemKind::CashFund => {
+            let currency = item.currency.as_deref().unwrap_or("USD");
+            let raised = ItemContribution::total_by_item(&mut db, item.id, currency).await?;
+            let contributions = ItemContribution::count_by_item(&mut db, item.id).await?;
+            let percent = item
+                .amount_cents
+                .map(|goal| (raised * 100 / goal).clamp(0, 100));
+            Some(context! {
+                raised: locale.format_price(raised, currency),
+                contributions,
+                percent,
+                can_contribute: user.is_some()
+                    && viewer.allows(Access::TakePart)
+                    && item.received_at.is_none(),
+            })
+        }
+        _ => None,
+    };
+
+    // Claims are hidden from the owner like the rest of the gifting activity
+    let claim = match &item {
+        Some(item) => {
+            let claim = Claim::find_by_item(&mut db, item.id).await?;
+            let claimer = claim.as_ref().map(|claim| claim.user_id);
+            let by_you = claimer.is_some() && claimer == user.map(|user| user.user.id);
+            let history = match user {
+                Some(user) if features.is_enabled(Feature::ClaimHistory) => {
+                    ClaimEvent::has_claimed(&mut db, item.id, user.user.id).await?
+                }
+                _ => false,
+            };
+            Some(context! {
+                claimed: claimer.is_some(),
+                by_you,
+                purchased: by_you && claim.is_some_and(|claim| claim.purchased_at.is_some()),
+                can_claim: user.is_some()
+                    && viewer.allows(Access::TakePart)
+                    && item.is_claimable(),
+                history,
+            })
+        }
+        None => None,
+    };
+
+    let images = match &item {
+        Some(item) => web::images::links_by_item(&mut db, signer, item.id).await?,
+        None => vec![],
+    };
+
+    // Consumables are given again and again, so they show how many times so far
+    let times_given = match &item {
+        Some(item) if item.kind().is_recurring() => {
+            Some(ItemGift::count_by_item(&mut db, item.id).await?)
+        }
+        _ => None,
+    };
 
-    let item = Item::find_by_id(&mut db, id).await?;
+    let dates = item.as_ref().map(|item| {
+        context! {
+            link_checked_on: item.link_checked_at.map(|date| locale.format_date(date)),
+            received_on: item.received_at.map(|date| locale.format_date(date)),
+        }
+    });
 
-    Ok(Template::render("items/show", context! { list, item }))
+    let is_owner = list.is_owned_by(user.map(|user| user.user.id));
+    Ok(Template::render(
+        "items/show",
+        context! {
+            lang: locale.tag(),
+            dir: locale.dir(),
+            is_owner,
+            list: &list,
+            item: &item,
+            images,
+            dates,
+            price,
+            alert,
+            fundable,
+            kind: item.as_ref().map(|item| item.kind()),
+            times_given,
+            priority: item.as_ref().map(|item| item.priority().label()),
+            amount,
+            cash_fund: viewer.conceal(cash_fund),
+            claim: viewer.conceal(claim),
+            gifting: viewer.conceal(user.is_some()),
+            logged_in: user.is_some(),
+        },
+    ))
+}
+
+/// Whether photos can be added to items on the list. Only the list's owner can add them, and
+/// only while image uploads are turned on.
+fn images_allowed(features: &FeatureFlags, list: &List, user_id: Option<i64>) -> bool {
+    list.is_owned_by(user_id) && features.is_enabled(Feature::ImageUploads)
+}
+
+/// Renders the edit page for the item, with its photos and an error if there is one. See
+/// `images_allowed`.
+pub async fn render_edit(
+    db: &mut Connection<WishlistDb>,
+    signer: &ImageSigner,
+    features: &FeatureFlags,
+    user_id: Option<i64>,
+    list: List,
+    item: Option<Item>,
+    error_message: Option<String>,
+) -> Result<Template, WebError<Template>> {
+    let locale = Locale::new(list.language.as_deref());
+    let amount = item.as_ref().and_then(|item| format_amount(&locale, item));
+    let (price, price_max) = item
+        .as_ref()
+        .map(|item| format_item_prices(&locale, item))
+        .unwrap_or_default();
+    let images = match &item {
+        Some(item) => web::images::links_by_item(db, signer, item.id).await?,
+        None => vec![],
+    };
+    let tags = match &item {
+        Some(item) => Tag::all_by_item(db, item.id)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => String::new(),
+    };
+    let can_add_images = images_allowed(features, &list, user_id);
+
+    Ok(Template::render(
+        "items/edit",
+        context! {
+            list,
+            item,
+            amount,
+            price,
+            price_max,
+            tags,
+            images,
+            can_add_images,
+            error_message,
+        },
+    ))
 }
 
 #[get("/lists/<list_key>/items/<id>/edit")]
 pub async fn edit(
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    signer: &State<ImageSigner>,
+    features: &State<FeatureFlags>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
     id: i64,
 ) -> Result<Template, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::Edit).await?;
 
-    let item = Item::find_by_id(&mut db, id).await?;
+    let item = Item::find_by_id(&mut db, id)
+        .await?
+        .filter(|item| item.list_id == list.id);
 
-    Ok(Template::render("items/edit", context! { list, item }))
+    let user_id = user.map(|user| user.user.id);
+    render_edit(&mut db, signer, features, user_id, list, item, None).await
 }
 
 #[put("/lists/<list_key>/items/<id>", format = "form", data = "<item>")]
 pub async fn update(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     list_key: &str,
     id: i64,
     item: Form<EditItem<'_>>,
 ) -> Result<Redirect, WebError<Template>> {
-    let list = List::find_by_key(&mut db, list_key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::Edit).await?;
 
     let mut old_item = Item::find_by_id(&mut db, id)
         .await?
+        .filter(|item| item.list_id == list.id)
         .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
 
-    match old_item
-        .update(&mut db, &item.title, &item.description)
-        .await
-    {
-        Ok(item) => Ok(Redirect::to(uri!(web::items::show(list.key, item.id)))),
+    let parsed = parse_kind(item.kind, item.amount).and_then(|kind| {
+        let details = parse_details(item.price, item.price_max, item.quantity, item.priority)?;
+        let tags = item.tags.map(Tag::parse_names).transpose()?;
+        Ok((kind, details, tags))
+    });
+    let mut tx = Transaction::begin(db).await?;
+    let saved = match parsed {
+        Ok(((kind, amount), details, tags)) => {
+            old_item.set_kind(
+                kind,
+                amount.as_ref().map(|price| price.amount_cents),
+                amount.as_ref().map(|price| price.currency.as_str()),
+            );
+            details.apply(&mut old_item);
+            let updated = old_item
+                .update(
+                    &mut tx,
+                    item.title,
+                    item.description,
+                    optional_url(item.url),
+                    user.map(|user| user.user.id),
+                )
+                .await;
+            match (updated, tags) {
+                (Ok(item), Some(tags)) => Tag::set_for_item(&mut tx, item.id, &tags)
+                    .await
+                    .map(|_| item),
+                (updated, _) => updated,
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    match saved {
+        Ok(updated) => {
+            let event = DomainEvent::ItemUpdated {
+                list: ListRef::from(&list),
+                item: ItemRef::from(&updated),
+                by: user.map(|user| user.user.id),
+            };
+            dispatcher.dispatch(&mut tx, event).await?;
+            dispatcher.commit(tx).await?;
+            Ok(Redirect::to(uri!(web::items::show(list.key, updated.id))))
+        }
         Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
             "items/edit",
             context! {
@@ -160,7 +721,15 @@ pub async fn update(
                     id,
                     title: item.title,
                     description: item.description,
+                    url: item.url,
+                    kind: item.kind,
+                    quantity: item.quantity,
+                    priority: item.priority,
                 },
+                amount: item.amount,
+                price: item.price,
+                price_max: item.price_max,
+                tags: item.tags,
                 error_message: "Fix your errors",
                 errors: e,
             },
@@ -173,7 +742,15 @@ pub async fn update(
                     id,
                     title: item.title,
                     description: item.description,
+                    url: item.url,
+                    kind: item.kind,
+                    quantity: item.quantity,
+                    priority: item.priority,
                 },
+                amount: item.amount,
+                price: item.price,
+                price_max: item.price_max,
+                tags: item.tags,
                 error_message: e.to_string()
             },
         ))),
@@ -182,19 +759,292 @@ pub async fn update(
 
 #[delete("/lists/<list_key>/items/<id>")]
 pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    store: &State<ImageStore>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::Edit).await?;
+
+    let mut item = Item::find_by_id(&mut db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    // Photos only on this item go with it
+    let images = Image::all_by_item(&mut db, item.id).await?;
+    let event = DomainEvent::ItemDeleted {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&item),
+        by: user.map(|user| user.user.id),
+    };
+    let mut tx = Transaction::begin(db).await?;
+    item.destroy(&mut tx, user.map(|user| user.user.id)).await?;
+    let mut unused = vec![];
+    for image in images {
+        if image.destroy_if_unused(&mut tx).await? {
+            unused.push(image.id);
+        }
+    }
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
+
+    // Files are only removed once it's certain their images are gone
+    for image_id in unused {
+        store.remove(image_id).await;
+    }
+
+    Ok(Redirect::to(uri!(web::items::index(list.key, _, _, _))))
+}
+
+/// Where an outbound link goes: straight to the store, or via a warning page first.
+#[derive(Responder)]
+pub enum Outbound {
+    Redirect(Redirect, Header<'static>),
+    Interstitial(Template, Header<'static>),
+}
+
+#[post("/lists/<list_key>/items/<id>/received", format = "form", data = "<mark>")]
+pub async fn received(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    dispatcher: &State<Dispatcher>,
+    user: Option<&LoggedInUser>,
     list_key: &str,
     id: i64,
+    mark: Form<MarkReceived>,
 ) -> Result<Redirect, WebError<Template>> {
+    // Only the owner knows what they've been given
     let list = List::find_by_key(&mut db, list_key)
         .await?
+        .filter(|list| list.is_owned_by(user.map(|user| user.user.id)))
         .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
 
     let mut item = Item::find_by_id(&mut db, id)
         .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    // Consumables stay wanted, so there's nothing to undo, and each time they're received is
+    // counted instead
+    let recurring = item.kind().is_recurring();
+    if recurring && !mark.received {
+        return Ok(Redirect::to(uri!(web::items::show(list.key, item.id))));
+    }
+
+    let mut tx = Transaction::begin(db).await?;
+    if recurring {
+        ItemGift::receive(&mut tx, &item, user.map(|user| user.user.id)).await?;
+    } else {
+        item.set_received(&mut tx, mark.received).await?;
+    }
+
+    let event = DomainEvent::ItemReceived {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&item),
+        received: mark.received,
+        by: user.map(|user| user.user.id),
+    };
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+/// Follows an item's link. Going through here keeps private list URLs out of the store's
+/// `Referer` header and lets the list owner see how often the link is used.
+#[get("/lists/<list_key>/items/<id>/out?<confirm>")]
+pub async fn out(
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    grants: ShareGrants,
+    user: Option<&LoggedInUser>,
+    affiliate: &State<AffiliatePolicy>,
+    tracking: Tracking,
+    list_key: &str,
+    id: i64,
+    confirm: Option<bool>,
+) -> Result<Outbound, WebError<Template>> {
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::View).await?;
+    let item = Item::find_by_id(&mut db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
         .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
 
-    item.destroy(&mut db).await?;
+    let url = item
+        .url
+        .as_deref()
+        .map(|url| affiliate.rewrite(&list, url))
+        .and_then(|url| url::Url::parse(&url).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let no_referrer = Header::new("Referrer-Policy", "no-referrer");
+
+    // Warn before sending people somewhere unencrypted or unusual
+    let is_suspicious = url.scheme() != "https"
+        || !url.username().is_empty()
+        || url.password().is_some()
+        || matches!(url.host(), Some(url::Host::Ipv4(_)) | Some(url::Host::Ipv6(_)));
+    if is_suspicious && confirm != Some(true) {
+        return Ok(Outbound::Interstitial(
+            Template::render(
+                "items/out",
+                context! {
+                    title: &item.title,
+                    back: uri!(web::items::show(&list.key, item.id)).to_string(),
+                    confirm: uri!(web::items::out(&list.key, item.id, Some(true))).to_string(),
+                    url: url.to_string(),
+                    host: url.host_str(),
+                },
+            ),
+            no_referrer,
+        ));
+    }
 
-    Ok(Redirect::to(uri!(web::items::index(list.key))))
+    if tracking.allowed {
+        Item::record_click(&mut db, item.id).await?;
+    }
+
+    Ok(Outbound::Redirect(Redirect::found(url.to_string()), no_referrer))
+}
+
+/// Follows the list owner's fund link for an item, counting the click the same way as item links.
+#[get("/lists/<list_key>/items/<id>/fund")]
+pub async fn fund(
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    grants: ShareGrants,
+    user: Option<&LoggedInUser>,
+    tracking: Tracking,
+    list_key: &str,
+    id: i64,
+) -> Result<Outbound, WebError<Template>> {
+    let list = shared_list(&mut db, &grants, user, list_key, SharePermission::View).await?;
+    // Only items still wanted on the list have fund links
+    Item::find_by_id(&mut db, id)
+        .await?
+        .filter(|item| item.list_id == list.id && item.received_at.is_none())
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let link = match list.user_id {
+        Some(owner_id) => FundLink::find_by_user(&mut db, owner_id).await?,
+        None => None,
+    }
+    .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    if tracking.allowed {
+        FundLink::record_click(&mut db, link.id).await?;
+    }
+
+    Ok(Outbound::Redirect(
+        Redirect::found(link.url),
+        Header::new("Referrer-Policy", "no-referrer"),
+    ))
+}
+
+/// Puts money towards a cash fund. The same person can contribute as many times as they like.
+#[post("/lists/<list_key>/items/<id>/contributions", format = "form", data = "<contribution>")]
+pub async fn contribute(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+    contribution: Form<Contribute<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = surprise::gifting_list(
+        List::find_by_key(&mut db, list_key).await?,
+        user.user.id,
+        Access::TakePart,
+    )
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let item = Item::find_by_id(&mut db, id)
+        .await?
+        .filter(|item| item.list_id == list.id && item.kind() == ItemKind::CashFund)
+        .filter(|item| item.received_at.is_none())
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    // Contributions are in the fund's currency, so they can be added up
+    let currency = item.currency.as_deref().unwrap_or("USD");
+    let amount = Price::parse(contribution.amount, Some(currency))
+        .ok_or_else(|| DataError::Other("Amount must be a number".to_string()))?;
+
+    ItemContribution::create(&mut db, item.id, user.user.id, amount.amount_cents, &amount.currency)
+        .await?;
+
+    Ok(Redirect::to(uri!(show(list.key, item.id))))
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::testing::TestApp;
+
+    #[rocket::async_test]
+    async fn items_are_only_found_through_their_own_list() {
+        let app = TestApp::new().await;
+        // Bob can edit his own list, but the desk is on Alice's private one
+        let key = app.list_key("Bob's Housewarming").await;
+        let desk = app.item_id("Standing desk").await;
+        let item = format!("/lists/{}/items/{}", key, desk);
+        app.login("bob").await;
+
+        let response = app.client().get(item.as_str()).dispatch().await;
+        let page = response.into_string().await.unwrap_or_default();
+        assert!(!page.contains("Standing desk"), "the item isn't shown");
+
+        let response = app.client().get(format!("{}/edit", item)).dispatch().await;
+        let page = response.into_string().await.unwrap_or_default();
+        assert!(!page.contains("Standing desk"), "the item can't be edited");
+
+        let form = "_method=PUT&title=Mine+now&description=&url=&kind=physical&amount=&price=&\
+                    price_max=&priority=normal";
+        let (status, _, _) = app.post_form(&item, form).await;
+        assert_eq!(status, Status::NotFound, "the item can't be updated");
+
+        let (status, _, _) = app.post_form(&item, "_method=DELETE").await;
+        assert_eq!(status, Status::NotFound, "the item can't be deleted");
+
+        assert_eq!(app.item_id("Standing desk").await, desk, "the item is untouched");
+    }
+
+    #[rocket::async_test]
+    async fn seeing_a_list_doesnt_let_you_change_it() {
+        let app = TestApp::new().await;
+        let key = app.list_key("Alice's Birthday").await;
+        let dune = app.item_id("Dune").await;
+        let items = format!("/lists/{}/items", key);
+        let item = format!("{}/{}", items, dune);
+        app.login("bob").await;
+
+        let response = app.client().get(format!("{}/new", items)).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound, "items can't be added");
+        let form = "title=Socks&description=&url=&kind=physical&amount=&price=&price_max=&\
+                    priority=normal";
+        let (status, _, _) = app.post_form(&items, form).await;
+        assert_eq!(status, Status::NotFound, "items can't be added");
+
+        let response = app.client().get(format!("{}/edit", item)).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound, "items can't be edited");
+        let (status, _, _) = app.post_form(&item, "_method=DELETE").await;
+        assert_eq!(status, Status::NotFound, "items can't be deleted");
+        let (status, _, _) = app.post_form(&format!("{}/received", item), "received=true").await;
+        assert_eq!(status, Status::NotFound, "items can't be marked received");
+
+        // Bob can still do what any guest can
+        let response = app.client().get(item.as_str()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let (status, _, _) = app.post_form(&format!("{}/claim", item), "").await;
+        assert_eq!(status, Status::SeeOther);
+    }
 }
@@ -0,0 +1,277 @@
+use rocket::http::{ContentType, Header};
+use rocket::response::stream::ByteStream;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::Template;
+
+use crate::affiliate::AffiliatePolicy;
+use crate::db::models::{Item, List};
+use crate::db::{DataError, WishlistDb};
+use crate::export_schema;
+use crate::images::ImageSigner;
+use crate::util::SiteUrl;
+use crate::views::ItemLinks;
+use crate::web::auth::LoggedInUser;
+use crate::web::{self, WebError};
+
+/// The columns of a CSV export, in order. See `csv_row`.
+const CSV_COLUMNS: [&str; 15] = [
+    "title",
+    "description",
+    "url",
+    "kind",
+    "amount_cents",
+    "currency",
+    "price_cents",
+    "price_max_cents",
+    "price_currency",
+    "quantity",
+    "priority",
+    "received_at",
+    "created_at",
+    "images",
+    "link",
+];
+
+/// A list as it's exported, without anything that only makes sense on this instance.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ExportedList<'a> {
+    key: &'a str,
+    title: &'a str,
+    description: &'a str,
+    language: Option<&'a str>,
+    privacy: &'a str,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// An item as it's exported. Claims are left out, see `crate::surprise`.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ExportedItem {
+    title: String,
+    description: String,
+    url: Option<String>,
+    kind: String,
+    amount_cents: Option<i64>,
+    currency: Option<String>,
+    price_cents: Option<i64>,
+    price_max_cents: Option<i64>,
+    price_currency: Option<String>,
+    quantity: i64,
+    priority: String,
+    received_at: Option<chrono::NaiveDateTime>,
+    created_at: chrono::NaiveDateTime,
+    /// Signed links to the item's photos. Like the photos on the list's pages, they expire.
+    images: Vec<String>,
+    /// The item's page on this instance.
+    link: String,
+}
+
+/// A list export, sent as a download.
+#[derive(Responder)]
+pub struct ListDownload<S> {
+    body: S,
+    content_type: ContentType,
+    disposition: Header<'static>,
+}
+
+/// Returns the list if the user can see it, with its items as they're exported.
+///
+/// Item links are exported as they were entered for the list's owner, and as they're shown on the
+/// list for everyone else, see `AffiliatePolicy`.
+async fn exported_items(
+    db: &mut Connection<WishlistDb>,
+    affiliate: &AffiliatePolicy,
+    signer: &ImageSigner,
+    site: &SiteUrl,
+    user: Option<&LoggedInUser>,
+    key: &str,
+) -> Result<(List, Vec<ExportedItem>), WebError<Template>> {
+    let user_id = user.map(|user| user.user.id);
+    let list = List::find_by_key(db, key)
+        .await?
+        .filter(|list| list.is_visible_to(user_id))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let mut items = Item::all_by_list(db, list.id).await?;
+    if !list.is_owned_by(user_id) {
+        affiliate.rewrite_items(&list, &mut items);
+    }
+
+    let links = ItemLinks::new(Some(site), &list);
+    let mut exported = Vec::with_capacity(items.len());
+    for item in items {
+        let images = web::images::links_by_item(db, signer, item.id)
+            .await?
+            .into_iter()
+            .map(|image| site.url(&image.url))
+            .collect();
+        exported.push(ExportedItem {
+            title: item.title,
+            description: item.description,
+            url: item.url,
+            kind: item.kind,
+            amount_cents: item.amount_cents,
+            currency: item.currency,
+            price_cents: item.price_cents,
+            price_max_cents: item.price_max_cents,
+            price_currency: item.price_currency,
+            quantity: item.quantity,
+            priority: item.priority,
+            received_at: item.received_at,
+            created_at: item.created_at,
+            images,
+            link: links.link(item.id),
+        });
+    }
+
+    Ok((list, exported))
+}
+
+/// A published JSON Schema, see `schema`.
+#[derive(Responder)]
+pub struct SchemaDocument {
+    body: &'static str,
+    content_type: ContentType,
+    cors: Header<'static>,
+}
+
+fn attachment(list: &List, extension: &str) -> Header<'static> {
+    Header::new(
+        "Content-Disposition",
+        format!("attachment; filename=\"{}.{}\"", list.key, extension),
+    )
+}
+
+/// Exports the list and its items as JSON, for backing up or moving to another instance. Items
+/// are streamed one at a time.
+#[get("/lists/<key>/export.json")]
+pub async fn json(
+    mut db: Connection<WishlistDb>,
+    affiliate: &State<AffiliatePolicy>,
+    signer: &State<ImageSigner>,
+    site: &State<SiteUrl>,
+    user: Option<&LoggedInUser>,
+    key: &str,
+) -> Result<ListDownload<ByteStream![Vec<u8>]>, WebError<Template>> {
+    let (list, items) = exported_items(&mut db, affiliate, signer, site, user, key).await?;
+
+    let exported_list = ExportedList {
+        key: &list.key,
+        title: &list.title,
+        description: &list.description,
+        language: list.language.as_deref(),
+        privacy: &list.privacy,
+        created_at: list.created_at,
+    };
+    let mut head = br#"{"list":"#.to_vec();
+    serde_json::to_writer(&mut head, &exported_list)
+        .map_err(|e| DataError::Other(e.to_string()))?;
+    head.extend_from_slice(br#","items":["#);
+
+    Ok(ListDownload {
+        body: ByteStream! {
+            yield head;
+            for (n, item) in items.iter().enumerate() {
+                let mut chunk = if n == 0 { vec![] } else { b",".to_vec() };
+                if let Err(e) = serde_json::to_writer(&mut chunk, item) {
+                    error!("Failed to export an item: {}", e);
+                    break;
+                }
+                yield chunk;
+            }
+            yield b"]}".to_vec();
+        },
+        content_type: ContentType::JSON,
+        disposition: attachment(&list, "json"),
+    })
+}
+
+/// Serves the JSON Schemas for the export format, e.g. `export.json` for a whole export. Other
+/// sites can read them too, so tools can check files in the browser. See `crate::export_schema`.
+#[get("/.well-known/wishlist-schema/v1/<name>")]
+pub fn schema(name: &str) -> Option<SchemaDocument> {
+    export_schema::document(name).map(|body| SchemaDocument {
+        body,
+        content_type: ContentType::new("application", "schema+json"),
+        cors: Header::new("Access-Control-Allow-Origin", "*"),
+    })
+}
+
+/// Exports the list's items as CSV, one row per item, for opening in a spreadsheet. Rows are
+/// streamed one at a time.
+#[get("/lists/<key>/export.csv")]
+pub async fn csv(
+    mut db: Connection<WishlistDb>,
+    affiliate: &State<AffiliatePolicy>,
+    signer: &State<ImageSigner>,
+    site: &State<SiteUrl>,
+    user: Option<&LoggedInUser>,
+    key: &str,
+) -> Result<ListDownload<ByteStream![Vec<u8>]>, WebError<Template>> {
+    let (list, items) = exported_items(&mut db, affiliate, signer, site, user, key).await?;
+
+    Ok(ListDownload {
+        body: ByteStream! {
+            yield csv_row(CSV_COLUMNS.iter().map(|column| column.to_string()));
+            for item in items {
+                yield csv_row(item_columns(item));
+            }
+        },
+        content_type: ContentType::CSV,
+        disposition: attachment(&list, "csv"),
+    })
+}
+
+/// The item's fields in the order of `CSV_COLUMNS`. Photos are separated by spaces, since their
+/// links can't contain any.
+fn item_columns(item: ExportedItem) -> [String; 15] {
+    let optional = |value: Option<i64>| value.map(|value| value.to_string()).unwrap_or_default();
+    let timestamp = |time: chrono::NaiveDateTime| time.format("%Y-%m-%d %H:%M:%S").to_string();
+    [
+        item.title,
+        item.description,
+        item.url.unwrap_or_default(),
+        item.kind,
+        optional(item.amount_cents),
+        item.currency.unwrap_or_default(),
+        optional(item.price_cents),
+        optional(item.price_max_cents),
+        item.price_currency.unwrap_or_default(),
+        item.quantity.to_string(),
+        item.priority,
+        item.received_at.map(timestamp).unwrap_or_default(),
+        timestamp(item.created_at),
+        item.images.join(" "),
+        item.link,
+    ]
+}
+
+/// Formats a CSV row, ending in CRLF as RFC 4180 asks.
+fn csv_row(fields: impl IntoIterator<Item = String>) -> Vec<u8> {
+    let mut row = fields
+        .into_iter()
+        .map(|field| csv_field(&field))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row.into_bytes()
+}
+
+/// Quotes a CSV field if it needs it. Text that a spreadsheet would run as a formula gets a
+/// leading apostrophe, so a malicious title can't run anything when the export is opened.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
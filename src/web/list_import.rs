@@ -0,0 +1,94 @@
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::response::Redirect;
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::List;
+use crate::db::{Transaction, WishlistDb};
+use crate::list_import::{self, ListImportError, MAX_FILE_SIZE};
+use crate::plugins::PluginRegistry;
+use crate::quotas::Quotas;
+use crate::util;
+use crate::web::auth::LoggedInUser;
+use crate::web::{self, WebError};
+
+#[derive(FromForm)]
+pub struct ItemsUpload<'r> {
+    pub file: TempFile<'r>,
+}
+
+/// Returns the list if the user owns it, so handlers can 404 otherwise.
+async fn owned_list(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+) -> Result<List, WebError<Template>> {
+    List::find_by_key(db, key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))
+}
+
+#[get("/lists/<key>/import")]
+pub async fn new(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+) -> Result<Template, WebError<Template>> {
+    let list = owned_list(&mut db, user, key).await?;
+
+    Ok(Template::render("lists/import", context! { user, list }))
+}
+
+/// Adds the items in an uploaded CSV or JSON file to the list, like the ones from its exports.
+/// If any row can't be imported, nothing is, and the rows' errors are shown.
+#[post(
+    "/lists/<key>/import",
+    format = "multipart/form-data",
+    data = "<upload>"
+)]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    quotas: &State<Quotas>,
+    plugins: &State<PluginRegistry>,
+    user: &LoggedInUser,
+    key: &str,
+    upload: Form<ItemsUpload<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = owned_list(&mut db, user, key).await?;
+
+    if upload.file.len() > MAX_FILE_SIZE {
+        let error_message = format!("Files can be at most {} MB", MAX_FILE_SIZE / 1024 / 1024);
+        return Err(import_error(user, &list, error_message));
+    }
+    let data = util::read_temp_file(&upload.file)
+        .await
+        .map_err(|e| import_error(user, &list, e.to_string()))?;
+
+    let mut tx = Transaction::begin(db).await?;
+    let report = match list_import::import_items(&mut tx, quotas, plugins, &list, &data).await {
+        Ok(report) => report,
+        Err(ListImportError::Data(e)) => return Err(e.into()),
+        Err(e) => return Err(import_error(user, &list, e.to_string())),
+    };
+    if !report.errors.is_empty() {
+        return Err(WebError::Invalid(Template::render(
+            "lists/import",
+            context! { user, list, report },
+        )));
+    }
+    tx.commit().await?;
+
+    Ok(Redirect::to(uri!(web::lists::show(list.key))))
+}
+
+fn import_error(user: &LoggedInUser, list: &List, error_message: String) -> WebError<Template> {
+    WebError::Invalid(Template::render(
+        "lists/import",
+        context! { user, list, error_message },
+    ))
+}
@@ -0,0 +1,256 @@
+//! Passwords on private lists.
+//!
+//! An owner can set a password on a private list, so people they give it to can see the list
+//! without an account. Visitors who haven't unlocked it are stopped by the `PasswordGate` guard,
+//! which the `locked` catcher turns into a password prompt. The right password is remembered in a
+//! private cookie for `UNLOCK_TTL_HOURS`, and lets them see the list and claim items like a claim
+//! share would, see `ShareGrants`.
+
+use std::net::IpAddr;
+
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::Redirect;
+use rocket::serde::{json, Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{List, SharePermission};
+use crate::db::WishlistDb;
+use crate::rate_limits::{self, RateLimiter};
+use crate::util;
+use crate::web::auth::LoggedInUser;
+use crate::web::shares::ShareGrants;
+use crate::web::{self, WebError};
+
+/// The private cookie that remembers the lists a visitor has unlocked, as a JSON array.
+static UNLOCKS_COOKIE: &str = "unlocked_lists";
+
+/// How long an unlocked list stays unlocked, in hours.
+const UNLOCK_TTL_HOURS: i64 = 12;
+
+/// The most unlocked lists remembered at once. Unlocking another forgets the oldest.
+const MAX_REMEMBERED_UNLOCKS: usize = 20;
+
+#[derive(FromForm)]
+pub struct UnlockList<'r> {
+    pub password: &'r str,
+}
+
+#[derive(FromForm)]
+pub struct ViewPassword<'r> {
+    /// The new password, or empty to take it off.
+    pub view_password: &'r str,
+}
+
+/// A list the visitor unlocked with its password.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Unlock {
+    key: String,
+    /// Part of a hash of the password hash, so changing the password locks the list again.
+    tag: String,
+    /// When it stops working, as a unix timestamp.
+    expires_at: i64,
+}
+
+impl Unlock {
+    fn new(list: &List) -> Option<Unlock> {
+        Some(Unlock {
+            key: list.key.clone(),
+            tag: password_tag(list.view_password_hash.as_deref()?),
+            expires_at: (chrono::Utc::now() + chrono::Duration::hours(UNLOCK_TTL_HOURS))
+                .timestamp(),
+        })
+    }
+
+    /// Whether this unlocks the list with the password it has now.
+    pub fn opens(&self, list: &List) -> bool {
+        self.key == list.key
+            && self.expires_at > chrono::Utc::now().timestamp()
+            && list.view_password_hash.as_deref().map(password_tag).as_deref()
+                == Some(self.tag.as_str())
+    }
+}
+
+fn password_tag(hash: &str) -> String {
+    util::hash_token(hash)[..16].to_string()
+}
+
+/// The lists the visitor has unlocked that haven't expired, oldest first.
+pub fn unlocked(cookies: &CookieJar<'_>) -> Vec<Unlock> {
+    let now = chrono::Utc::now().timestamp();
+    cookies
+        .get_private(UNLOCKS_COOKIE)
+        .and_then(|cookie| json::from_str::<Vec<Unlock>>(cookie.value()).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|unlock| unlock.expires_at > now)
+        .collect()
+}
+
+/// Remembers that the visitor unlocked the list. The cookie lasts as long as the browser keeps
+/// the session, but each unlock expires on its own.
+fn remember(cookies: &CookieJar<'_>, list: &List) {
+    let unlock = match Unlock::new(list) {
+        Some(unlock) => unlock,
+        None => return,
+    };
+
+    let mut unlocks = unlocked(cookies);
+    unlocks.retain(|remembered| remembered.key != unlock.key);
+    unlocks.push(unlock);
+    if unlocks.len() > MAX_REMEMBERED_UNLOCKS {
+        unlocks.drain(..unlocks.len() - MAX_REMEMBERED_UNLOCKS);
+    }
+
+    if let Ok(value) = json::to_string(&unlocks) {
+        let mut cookie = Cookie::new(UNLOCKS_COOKIE, value);
+        cookie.set_same_site(SameSite::Lax);
+        cookie.set_http_only(true);
+        cookies.add_private(cookie);
+    }
+}
+
+/// A list the visitor needs a password for, shown on the prompt.
+#[derive(Serialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct LockedList {
+    pub key: String,
+    pub title: String,
+}
+
+impl From<&List> for LockedList {
+    fn from(list: &List) -> Self {
+        LockedList {
+            key: list.key.clone(),
+            title: list.title.clone(),
+        }
+    }
+}
+
+/// Stops visitors at the password prompt on a password protected list they haven't unlocked,
+/// for routes under `/lists/<key>`. Lists that don't need a password, or that don't exist, are
+/// left to the route.
+pub struct PasswordGate;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PasswordGate {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = match request.routed_segment(1) {
+            Some(key) => key,
+            None => return Outcome::Success(PasswordGate),
+        };
+
+        let mut db = match request.guard::<Connection<WishlistDb>>().await.succeeded() {
+            Some(db) => db,
+            None => return Outcome::Failure((Status::ServiceUnavailable, ())),
+        };
+        let list = match List::find_by_key(&mut db, key).await {
+            Ok(Some(list)) if list.is_password_protected() => list,
+            Ok(_) => return Outcome::Success(PasswordGate),
+            Err(e) => {
+                error!("Failed to load list {}: {}", key, e);
+                return Outcome::Failure((Status::InternalServerError, ()));
+            }
+        };
+
+        let user_id = request
+            .guard::<&LoggedInUser>()
+            .await
+            .succeeded()
+            .map(|user| user.user.id);
+        let grants = ShareGrants::of(request);
+        match grants
+            .allows(&mut db, &list, user_id, SharePermission::View)
+            .await
+        {
+            Ok(true) => Outcome::Success(PasswordGate),
+            Ok(false) => {
+                request.local_cache(|| Some(LockedList::from(&list)));
+                Outcome::Failure((Status::Unauthorized, ()))
+            }
+            Err(e) => {
+                error!("Failed to check shares for list {}: {}", key, e);
+                Outcome::Failure((Status::InternalServerError, ()))
+            }
+        }
+    }
+}
+
+/// Shows the password prompt for the list `PasswordGate` stopped the visitor at.
+#[catch(401)]
+pub fn locked(request: &Request<'_>) -> Template {
+    match request.local_cache(|| None::<LockedList>) {
+        Some(list) => Template::render("lists/unlock", context! { list }),
+        None => Template::render("error/404", ()),
+    }
+}
+
+/// Unlocks a list with its password, then shows it.
+#[post("/lists/<key>/unlock", format = "form", data = "<unlock>")]
+pub async fn unlock(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    cookies: &CookieJar<'_>,
+    limiter: &State<RateLimiter>,
+    ip: Option<IpAddr>,
+    key: &str,
+    unlock: Form<UnlockList<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = List::find_by_key(&mut db, key)
+        .await?
+        .filter(List::is_password_protected)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    if let Err(wait) = limiter.check_unlock(ip, &list.key) {
+        return Err(WebError::too_many_requests(
+            Template::render(
+                "lists/unlock",
+                context! {
+                    list: LockedList::from(&list),
+                    error_message: rate_limits::wait_message(wait),
+                },
+            ),
+            wait,
+        ));
+    }
+
+    if !list.check_view_password(unlock.password) {
+        return Err(WebError::Invalid(Template::render(
+            "lists/unlock",
+            context! {
+                list: LockedList::from(&list),
+                error_message: "That password isn't right",
+            },
+        )));
+    }
+
+    remember(cookies, &list);
+
+    Ok(Redirect::to(uri!(web::lists::show(list.key))))
+}
+
+/// Sets the password visitors need to see one of the user's lists, or takes it off.
+#[post("/lists/<key>/view-password", format = "form", data = "<password>")]
+pub async fn update(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+    password: Form<ViewPassword<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let mut list = List::find_by_key(&mut db, key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let view_password = Some(password.view_password).filter(|password| !password.is_empty());
+    list.set_view_password(&mut db, view_password).await?;
+
+    Ok(Redirect::to(uri!(web::lists::edit(list.key))))
+}
@@ -1,120 +1,443 @@
+use std::net::IpAddr;
+
 use rocket::form::Form;
+use rocket::http::ContentType;
+use rocket::response::content::RawHtml;
 use rocket::response::Redirect;
+use rocket::serde::Serialize;
+use rocket::State;
 use rocket_db_pools::Connection;
-use rocket_dyn_templates::{context, Template};
+use rocket_dyn_templates::{context, Metadata, Template};
+use validator::ValidationErrors;
 
-use crate::api::v1::lists::{CreateList, EditList};
-use crate::db::models::{Item, List};
-use crate::db::{DataError, WishlistDb};
+use crate::affiliate::AffiliatePolicy;
+use crate::api::v1::lists::{optional_language, CreateList, EditList};
+use crate::csrf::CsrfVerified;
+use crate::db::models::{
+    AuditEvent, Claim, Item, ItemPrice, List, ListShare, ListWatcher, ListWebhook, SharePermission,
+    User,
+};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::{DomainEvent, ListRef};
+use crate::features::{flags, Enabled};
+use crate::feeds::{Feed, FeedEntry};
+use crate::locale::Locale;
+use crate::notify::Dispatcher;
+use crate::pagination::{Page, Pagination};
+use crate::plugins::PluginRegistry;
+use crate::quotas::Quotas;
+use crate::render_cache::{CachedPage, RenderCache};
+use crate::surprise::Viewer;
+use crate::throttle::{Challenge, ListThrottle};
+use crate::util::{self, SiteUrl};
+use crate::views::{ItemLinks, ListView};
+use crate::web::auth::{self, LoggedInUser};
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants, ShareSummary};
+use crate::web::webhooks::WebhookSummary;
 use crate::web::{self, WebError};
 
-#[get("/lists")]
-pub async fn index(mut db: Connection<WishlistDb>) -> Result<Template, WebError<Template>> {
-    let lists = List::all_public(&mut db)
-        .await?
-        .into_iter()
-        .map(|list| {
-            let link = uri!(show(&list.key)).to_string();
-            context! {
-                id: list.id,
-                key: list.key,
-                title: list.title,
-                description: list.description,
-                link,
-            }
-        })
-        .collect::<Vec<_>>();
+#[get("/lists?<page..>")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    _enabled: Enabled<flags::PublicIndex>,
+    page: Option<Page>,
+) -> Result<Template, WebError<Template>> {
+    let page = page.unwrap_or_default();
+    let pagination = Pagination::new(&page, List::count_public(&mut db).await?);
+    let lists = ListView::all(None, List::all_public(&mut db, &page).await?);
 
-    Ok(Template::render("lists/index", context! { lists: lists }))
+    Ok(Template::render(
+        "lists/index",
+        context! { lists: lists, pagination },
+    ))
 }
 
 #[get("/lists/new")]
-pub fn new() -> Template {
-    Template::render("lists/new", context! { list: List::default() })
+pub fn new(throttle: &State<ListThrottle>, user: Option<&LoggedInUser>) -> Template {
+    let challenge = user.is_none().then(|| throttle.challenge()).flatten();
+    Template::render(
+        "lists/new",
+        context! { user, list: List::default(), challenge },
+    )
 }
 
 #[post("/lists", format = "form", data = "<list>")]
 pub async fn create(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    throttle: &State<ListThrottle>,
+    ip: Option<IpAddr>,
+    user: Option<&LoggedInUser>,
     list: Form<CreateList<'_>>,
 ) -> Result<Redirect, WebError<Template>> {
-    match List::create(&mut db, list.is_private, list.title, list.description).await {
-        Ok(list) => Ok(Redirect::to(uri!(web::lists::show(list.key)))),
-        Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
-            "lists/new",
-            context! {
-                list: context! {
-                    is_private: list.is_private,
-                    title: list.title,
-                    description: list.description,
-                },
-                error_message: "Fix your errors",
-                errors: e,
-            },
-        ))),
-        Err(e) => Err(WebError::Invalid(Template::render(
-            "lists/new",
-            context! {
-                list: context! {
-                    is_private: list.is_private,
-                    title: list.title,
-                    description: list.description,
-                },
-                error_message: e.to_string()
-            },
-        ))),
+    // Anyone who has to try again gets a new challenge, since answers only work once
+    let challenge = user.is_none().then(|| throttle.challenge()).flatten();
+
+    if user.is_none() {
+        if let Err(e) = throttle.check(ip, &list.challenge_answer()).await {
+            return Err(new_list_error(&list, user, challenge, e.to_string(), None));
+        }
     }
+
+    if let Some(message) = quotas.check_list(&mut db, user.map(|u| u.user.id)).await? {
+        return Err(new_list_error(&list, user, challenge, message, None));
+    }
+
+    if let Some(user) = user {
+        if let Err(e) = auth::check_can_publish(&user.user, list.privacy()) {
+            return Err(new_list_error(
+                &list,
+                Some(user),
+                challenge,
+                e.to_string(),
+                None,
+            ));
+        }
+    }
+
+    let mut tx = Transaction::begin(db).await?;
+    match List::create(
+        &mut tx,
+        user.map(|u| u.user.id),
+        list.privacy(),
+        list.title,
+        list.description,
+        list.affiliate_opt_out,
+        optional_language(list.language),
+    )
+    .await
+    {
+        Ok(new_list) => {
+            let event = DomainEvent::ListCreated {
+                list: ListRef::from(&new_list),
+                by: user.map(|u| u.user.id),
+            };
+            dispatcher.dispatch(&mut tx, event).await?;
+            dispatcher.commit(tx).await?;
+            Ok(Redirect::to(uri!(web::lists::show(new_list.key))))
+        }
+        Err(DataError::Validation(e)) => Err(new_list_error(
+            &list,
+            user,
+            challenge,
+            "Fix your errors".to_string(),
+            Some(e),
+        )),
+        Err(e) => Err(new_list_error(&list, user, challenge, e.to_string(), None)),
+    }
+}
+
+/// Shows the new list form again with what was entered.
+fn new_list_error(
+    list: &CreateList<'_>,
+    user: Option<&LoggedInUser>,
+    challenge: Option<Challenge>,
+    error_message: String,
+    errors: Option<ValidationErrors>,
+) -> WebError<Template> {
+    WebError::Invalid(Template::render(
+        "lists/new",
+        context! {
+            user,
+            list: context! {
+                privacy: list.privacy(),
+                title: list.title,
+                description: list.description,
+                affiliate_opt_out: list.affiliate_opt_out,
+                language: list.language,
+            },
+            challenge,
+            error_message,
+            errors,
+        },
+    ))
+}
+
+/// Returns the list if the user owns it, so handlers can 404 otherwise.
+async fn owned_list(
+    db: &mut Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+) -> Result<List, WebError<Template>> {
+    List::find_by_key(db, key)
+        .await?
+        .filter(|list| list.is_owned_by(Some(user.user.id)))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))
 }
 
+/// Public lists are served from the `RenderCache` to anonymous visitors.
 #[get("/lists/<key>")]
 pub async fn show(
     mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    affiliate: &State<AffiliatePolicy>,
+    plugins: &State<PluginRegistry>,
+    metadata: Metadata<'_>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
     key: &str,
-) -> Result<Template, WebError<Template>> {
+) -> Result<CachedPage, WebError<Template>> {
+    let user_id = user.map(|user| user.user.id);
+    let list = shared_list(&mut db, &grants, user, key, SharePermission::View).await?;
+
+    let cache = RenderCache::current().filter(|_| user.is_none() && list.privacy().is_open());
+    if let Some(html) = cache.and_then(|cache| cache.get(list.id)) {
+        return Ok(CachedPage::Cached(RawHtml(html)));
+    }
+
+    let mut items = Item::all_by_list(&mut db, list.id).await?;
+    affiliate.rewrite_items(&list, &mut items);
+
+    // Claims are hidden from the owner, see `crate::surprise`
+    let viewer = Viewer::of(&list, user_id);
+    let claimers = Claim::claimers_by_list(&mut db, list.id).await?;
+    let items = items
+        .into_iter()
+        .map(|item| {
+            let claimer = claimers.get(&item.id).copied();
+            let claim = context! {
+                claimed: claimer.is_some(),
+                by_you: claimer.is_some() && claimer == user_id,
+            };
+            context! { claim: viewer.conceal(claim), item }
+        })
+        .collect::<Vec<_>>();
+
+    let locale = Locale::new(list.language.as_deref());
+    let is_owner = list.is_owned_by(user_id);
+    let watching = match user_id {
+        Some(user_id) => ListWatcher::is_watching(&mut db, list.id, user_id).await?,
+        None => false,
+    };
+    let list_id = list.id;
+    let plugins = plugins.list_context(&list).await;
+    let context = context! {
+        lang: locale.tag(),
+        dir: locale.dir(),
+        is_owner,
+        logged_in: user.is_some(),
+        watching,
+        privacy: list.privacy().label(),
+        open: list.privacy().is_open(),
+        list: &list,
+        items,
+        plugins,
+    };
+    if let Some(cache) = cache {
+        if let Some((_, html)) = metadata.render("lists/show", &context) {
+            cache.insert(list_id, html.clone());
+            return Ok(CachedPage::Cached(RawHtml(html)));
+        }
+    }
+    Ok(CachedPage::Rendered(Template::render(
+        "lists/show",
+        context,
+    )))
+}
+
+#[get("/lists/<key>/price-drops.rss")]
+pub async fn price_drops(
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    site: &State<SiteUrl>,
+    user: Option<&LoggedInUser>,
+    grants: ShareGrants,
+    key: &str,
+) -> Result<(ContentType, String), WebError<Template>> {
+    let list = shared_list(&mut db, &grants, user, key, SharePermission::View).await?;
+
+    let links = ItemLinks::new(Some(site), &list);
+    let entries = ItemPrice::drops_by_list(&mut db, list.id)
+        .await?
+        .into_iter()
+        .map(|drop| FeedEntry {
+            id: format!("price-drop-{}", drop.id),
+            title: format!(
+                "{} is now {}",
+                drop.item_title,
+                util::format_price(drop.amount_cents, &drop.currency)
+            ),
+            link: links.link(drop.item_id),
+            summary: format!(
+                "{} is now {}. It was {} when tracking started.",
+                drop.item_title,
+                util::format_price(drop.amount_cents, &drop.currency),
+                util::format_price(drop.baseline_cents, &drop.currency)
+            ),
+            published: drop.created_at,
+            updated: drop.created_at,
+        })
+        .collect();
+
+    let feed = Feed {
+        title: format!("Price drops for {}", list.title),
+        link: site.url(&uri!(show(&list.key)).to_string()),
+        description: format!("Price drops for items on {}", list.title),
+        entries,
+    };
+
+    Ok((ContentType::new("application", "rss+xml"), feed.to_rss()))
+}
+
+/// The most items in a list's Atom feed.
+const FEED_ITEMS: i64 = 50;
+
+/// An Atom feed of the items most recently added to a list anyone with the link can see, for
+/// following it.
+#[get("/lists/<key>/feed.atom")]
+pub async fn feed(
+    mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    key: &str,
+) -> Result<(ContentType, String), WebError<Template>> {
     let list = List::find_by_key(&mut db, key)
         .await?
+        .filter(|list| list.privacy().is_open())
         .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
 
-    let items = Item::all_by_list(&mut db, list.id).await?;
+    let links = ItemLinks::new(Some(site), &list);
+    let entries = Item::recent_by_list(&mut db, list.id, FEED_ITEMS)
+        .await?
+        .into_iter()
+        .map(|item| FeedEntry {
+            id: links.link(item.id),
+            link: links.link(item.id),
+            title: item.title,
+            summary: item.description,
+            published: item.created_at,
+            updated: item.updated_at,
+        })
+        .collect();
+
+    let author = match list.user_id {
+        Some(user_id) => User::find_by_id(&mut db, user_id)
+            .await?
+            .map(|user| user.username),
+        None => None,
+    };
+    let feed = Feed {
+        title: list.title.clone(),
+        link: site.url(&uri!(show(&list.key)).to_string()),
+        description: format!("Items added to {}", list.title),
+        entries,
+    };
+    let feed_url = site.url(&uri!(feed(&list.key)).to_string());
 
-    Ok(Template::render("lists/show", context! { list, items }))
+    Ok((
+        ContentType::new("application", "atom+xml"),
+        feed.to_atom(&feed_url, author.as_deref().unwrap_or(&list.title)),
+    ))
+}
+
+/// The template context for an entry in a list's history or the admin audit log.
+pub fn audit_entry(event: AuditEvent) -> impl Serialize {
+    context! {
+        title: event.title(),
+        changes: event.changes(),
+        username: event.username,
+        entity_type: event.entity_type,
+        entity_id: event.entity_id,
+        list_id: event.list_id,
+        action: event.action,
+        created_at: event.created_at,
+    }
+}
+
+#[get("/lists/<key>/history?<page..>")]
+pub async fn history(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+    page: Option<Page>,
+) -> Result<Template, WebError<Template>> {
+    let list = owned_list(&mut db, user, key).await?;
+
+    let page = page.unwrap_or_default();
+    let pagination = Pagination::new(&page, AuditEvent::count_by_list(&mut db, list.id).await?);
+    let events = AuditEvent::all_by_list(&mut db, list.id, &page)
+        .await?
+        .into_iter()
+        .map(audit_entry)
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "lists/history",
+        context! { user, list, events, pagination },
+    ))
 }
 
 #[get("/lists/<key>/edit")]
 pub async fn edit(
     mut db: Connection<WishlistDb>,
+    site: &State<SiteUrl>,
+    user: &LoggedInUser,
     key: &str,
 ) -> Result<Template, WebError<Template>> {
-    let list = List::find_by_key(&mut db, key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let list = owned_list(&mut db, user, key).await?;
+
+    let webhooks = ListWebhook::all_by_list(&mut db, list.id).await?;
+    let shares = ListShare::all_by_list(&mut db, list.id).await?;
+
+    Ok(Template::render(
+        "lists/edit",
+        context! {
+            webhooks: WebhookSummary::all(&webhooks),
+            webhook_events: web::webhooks::event_choices(&[]),
+            shares: ShareSummary::all(site, &shares),
+            share_permissions: web::shares::permission_choices(),
+            has_view_password: list.view_password_hash.is_some(),
+            list,
+        },
+    ))
+}
 
-    Ok(Template::render("lists/edit", context! { list }))
+#[get("/lists/<_key>/edit", rank = 2)]
+pub fn edit_2(_key: &str) -> Redirect {
+    Redirect::to(uri!(web::account::login))
 }
 
 #[put("/lists/<key>", format = "form", data = "<list>")]
 pub async fn update(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
     key: &str,
     list: Form<EditList<'_>>,
 ) -> Result<Redirect, WebError<Template>> {
-    let mut old_list = List::find_by_key(&mut db, key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let mut old_list = owned_list(&mut db, user, key).await?;
+    let privacy = list.privacy(old_list.privacy());
 
-    match old_list
-        .update(&mut db, list.is_private, &list.title, &list.description)
-        .await
-    {
+    let updated = match auth::check_can_publish(&user.user, privacy) {
+        Ok(()) => {
+            old_list
+                .update(
+                    &mut db,
+                    privacy,
+                    list.title,
+                    list.description,
+                    list.affiliate_opt_out,
+                    optional_language(list.language),
+                    Some(user.user.id),
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    match updated {
         Ok(list) => Ok(Redirect::to(uri!(web::lists::show(list.key)))),
         Err(DataError::Validation(e)) => Err(WebError::Invalid(Template::render(
             "lists/edit",
             context! {
                list: context! {
                     key,
-                    is_private: list.is_private,
+                    privacy,
                     title: list.title,
                     description: list.description,
+                    affiliate_opt_out: list.affiliate_opt_out,
+                    language: list.language,
                },
                error_message: "Fix your errors",
                errors: e,
@@ -125,9 +448,11 @@ pub async fn update(
             context! {
                 list: context! {
                     key,
-                    is_private: list.is_private,
+                    privacy,
                     title: list.title,
                     description: list.description,
+                    affiliate_opt_out: list.affiliate_opt_out,
+                    language: list.language,
                 },
                 error_message: e.to_string()
             },
@@ -135,16 +460,77 @@ pub async fn update(
     }
 }
 
+#[derive(FromForm)]
+pub struct RevealGifting {
+    pub reveal_gifting: bool,
+}
+
+/// Lets the owner see gifting activity on their list, or hides it again. See `crate::surprise`.
+#[post("/lists/<key>/reveal", format = "form", data = "<reveal>")]
+pub async fn reveal(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+    reveal: Form<RevealGifting>,
+) -> Result<Redirect, WebError<Template>> {
+    let mut list = owned_list(&mut db, user, key).await?;
+
+    list.set_reveal_gifting(&mut db, reveal.reveal_gifting).await?;
+
+    Ok(Redirect::to(uri!(edit(list.key))))
+}
+
+#[derive(FromForm)]
+pub struct EventDate<'r> {
+    /// A date like "2023-12-25", or empty to clear it.
+    #[field(validate = with(
+        |date| date.is_empty() || parse_event_date(date).is_some(),
+        "Event date must be a date like 2023-12-25"
+    ))]
+    pub event_date: &'r str,
+}
+
+fn parse_event_date(date: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Sets the day the list is for, so people who claimed items get reminded ahead of it. See
+/// `crate::api::v1::me::claims_calendar`.
+#[post("/lists/<key>/event-date", format = "form", data = "<event_date>")]
+pub async fn event_date(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    key: &str,
+    event_date: Form<EventDate<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let mut list = owned_list(&mut db, user, key).await?;
+
+    list.set_event_date(&mut db, parse_event_date(event_date.event_date))
+        .await?;
+
+    Ok(Redirect::to(uri!(edit(list.key))))
+}
+
 #[delete("/lists/<key>")]
 pub async fn destroy(
+    _csrf: CsrfVerified,
     mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: &LoggedInUser,
     key: &str,
 ) -> Result<Redirect, WebError<Template>> {
-    let mut list = List::find_by_key(&mut db, key)
-        .await?
-        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let mut list = owned_list(&mut db, user, key).await?;
 
-    list.destroy(&mut db).await?;
+    let event = DomainEvent::ListDeleted {
+        list: ListRef::from(&list),
+        by: Some(user.user.id),
+    };
+    let mut tx = Transaction::begin(db).await?;
+    list.destroy(&mut tx, Some(user.user.id)).await?;
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
 
-    Ok(Redirect::to(uri!(web::lists::index)))
+    Ok(Redirect::to(uri!(web::lists::index(_))))
 }
@@ -0,0 +1,117 @@
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event as StreamEvent, EventStream};
+use rocket::serde::Serialize;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Shutdown, State};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::Template;
+
+use crate::db::models::{Event, List};
+use crate::db::WishlistDb;
+use crate::notify::Dispatcher;
+use crate::surprise::Viewer;
+use crate::web::auth::LoggedInUser;
+use crate::web::WebError;
+
+/// The most missed events sent to a client when it reconnects.
+const MAX_REPLAY: i64 = 100;
+
+/// The `Last-Event-ID` header browsers send when reconnecting to an event stream.
+pub struct LastEventId(pub Option<i64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(LastEventId(
+            request
+                .headers()
+                .get_one("Last-Event-ID")
+                .and_then(|id| id.parse().ok()),
+        ))
+    }
+}
+
+/// What a list page is told about a change. Just enough to know what to refresh; who made the
+/// change isn't sent.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LiveUpdate<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    item_id: Option<i64>,
+}
+
+/// Whether the viewer should be told about the event. Gifting activity is left out for owners
+/// who can't see it, see `crate::surprise`.
+fn is_shown(viewer: Viewer, event: &Event) -> bool {
+    viewer.sees_gifting()
+        || !event
+            .domain_event()
+            .map(|event| event.is_gifting())
+            .unwrap_or(true)
+}
+
+fn live_update(event: &Event) -> StreamEvent {
+    StreamEvent::json(&LiveUpdate {
+        kind: &event.kind,
+        item_id: event.item_id,
+    })
+    .id(event.id.to_string())
+}
+
+/// Streams changes to a list as server-sent events, so its page can update without reloading.
+/// Clients that reconnect are sent the events they missed first.
+#[get("/lists/<key>/events")]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    user: Option<&LoggedInUser>,
+    last_event_id: LastEventId,
+    key: &str,
+    mut end: Shutdown,
+) -> Result<EventStream![], WebError<Template>> {
+    let user_id = user.map(|user| user.user.id);
+    let list = List::find_by_key(&mut db, key)
+        .await?
+        .filter(|list| list.is_visible_to(user_id))
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let viewer = Viewer::of(&list, user_id);
+
+    // Subscribe before catching up, so nothing logged in between is missed
+    let mut live = dispatcher.subscribe();
+    let missed = match last_event_id.0 {
+        Some(after_id) => Event::all_by_list_after(&mut db, list.id, after_id, MAX_REPLAY).await?,
+        None => vec![],
+    };
+    let mut last_id = missed
+        .last()
+        .map(|event| event.id)
+        .or(last_event_id.0)
+        .unwrap_or(0);
+
+    Ok(EventStream! {
+        for event in missed.iter().filter(|event| is_shown(viewer, event)) {
+            yield live_update(event);
+        }
+
+        loop {
+            let event = select! {
+                event = live.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut end => break,
+            };
+            if event.list_id != Some(list.id) || event.id <= last_id {
+                continue;
+            }
+            last_id = event.id;
+            if is_shown(viewer, &event) {
+                yield live_update(&event);
+            }
+        }
+    })
+}
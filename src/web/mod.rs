@@ -1,10 +1,32 @@
+use rocket::http::Header;
 use rocket_dyn_templates::{context, Template};
 
 use crate::db::DataError;
 
+pub mod admin;
+pub mod api_tokens;
 pub mod auth;
+pub mod claims;
+pub mod date_polls;
+pub mod display;
+pub mod gift_splits;
+pub mod images;
 pub mod items;
+pub mod list_export;
+pub mod list_import;
+pub mod list_passwords;
 pub mod lists;
+pub mod live;
+pub mod notifications;
+pub mod passkeys;
+pub mod price_alerts;
+pub mod privacy;
+pub mod quick;
+pub mod saved_searches;
+pub mod search;
+pub mod shares;
+pub mod users;
+pub mod webhooks;
 pub mod account;
 
 #[derive(Responder)]
@@ -15,6 +37,16 @@ pub enum WebError<T> {
     NotFound(T),
     #[response(status = 500)]
     Internal(T),
+    /// Too many attempts, with a `Retry-After` header, see `crate::rate_limits`.
+    #[response(status = 429)]
+    TooManyRequests(T, Header<'static>),
+}
+
+impl<T> WebError<T> {
+    /// Answers a rate limited request, which can try again in `wait_secs`.
+    pub fn too_many_requests(body: T, wait_secs: u64) -> WebError<T> {
+        WebError::TooManyRequests(body, Header::new("Retry-After", wait_secs.to_string()))
+    }
 }
 
 impl From<sqlx::Error> for WebError<String> {
@@ -0,0 +1,131 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{ListWatcher, NotificationPreferences, SharePermission};
+use crate::db::WishlistDb;
+use crate::web::auth::LoggedInUser;
+use crate::web::shares::{self, ShareGrants};
+use crate::web::{self, WebError};
+
+/// Unchecked boxes aren't sent, and missing fields are false.
+#[derive(FromForm)]
+pub struct EditPreferences {
+    pub claim_emails: bool,
+    pub activity_emails: bool,
+}
+
+/// Starts emailing the user about new items on the list, see `crate::notify`. Private lists can
+/// be watched through a share, for as long as it works.
+#[post("/lists/<key>/watch")]
+pub async fn watch(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    key: &str,
+) -> Result<Redirect, WebError<Template>> {
+    let list =
+        shares::shared_list(&mut db, &grants, Some(user), key, SharePermission::View).await?;
+
+    let share_id = if list.is_visible_to(Some(user.user.id)) {
+        None
+    } else {
+        grants.share(&mut db, &list).await?.map(|share| share.id)
+    };
+    ListWatcher::watch(&mut db, list.id, user.user.id, share_id).await?;
+
+    Ok(Redirect::to(uri!(web::lists::show(list.key))))
+}
+
+#[delete("/lists/<key>/watch")]
+pub async fn unwatch(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    key: &str,
+) -> Result<Redirect, WebError<Template>> {
+    let list =
+        shares::shared_list(&mut db, &grants, Some(user), key, SharePermission::View).await?;
+
+    ListWatcher::unwatch(&mut db, list.id, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(web::lists::show(list.key))))
+}
+
+#[get("/account/notifications")]
+pub async fn edit(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let preferences = NotificationPreferences::for_user(&mut db, user.user.id).await?;
+
+    Ok(Template::render(
+        "account/notifications",
+        context! { user, preferences },
+    ))
+}
+
+#[post("/account/notifications", format = "form", data = "<changes>")]
+pub async fn update(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    changes: Form<EditPreferences>,
+) -> Result<Redirect, WebError<Template>> {
+    let mut preferences = NotificationPreferences::for_user(&mut db, user.user.id).await?;
+    preferences
+        .update(&mut db, changes.claim_emails, changes.activity_emails)
+        .await?;
+
+    Ok(Redirect::to(uri!(edit)))
+}
+
+/// The unsubscribe link from activity emails. It asks before unsubscribing, so link scanners
+/// opening it don't do it. `kind` is `NotificationPreferences::CLAIMS` or `ACTIVITY`.
+#[get("/unsubscribe/<token>?<kind>")]
+pub async fn unsubscribe(
+    mut db: Connection<WishlistDb>,
+    token: &str,
+    kind: Option<&str>,
+) -> Result<Template, WebError<Template>> {
+    let preferences = NotificationPreferences::find_by_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(Template::render(
+        "notifications/unsubscribe",
+        context! {
+            token: &preferences.unsubscribe_token,
+            kind,
+            claims: kind == Some(NotificationPreferences::CLAIMS),
+            done: false,
+        },
+    ))
+}
+
+#[post("/unsubscribe/<token>?<kind>")]
+pub async fn do_unsubscribe(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    token: &str,
+    kind: Option<&str>,
+) -> Result<Template, WebError<Template>> {
+    let mut preferences = NotificationPreferences::find_by_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    let kind = kind.unwrap_or(NotificationPreferences::ACTIVITY);
+    preferences.unsubscribe(&mut db, kind).await?;
+
+    Ok(Template::render(
+        "notifications/unsubscribe",
+        context! {
+            claims: kind == NotificationPreferences::CLAIMS,
+            done: true,
+        },
+    ))
+}
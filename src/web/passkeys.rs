@@ -0,0 +1,256 @@
+use rocket::http::CookieJar;
+use rocket::response::status::Created;
+use rocket::response::Redirect;
+use rocket::serde::json::{self, Json};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey as Credential, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Uuid,
+    WebauthnError,
+};
+use webauthn_rs::Webauthn;
+
+use crate::api::{ApiError, ApiGenericError};
+use crate::csrf::CsrfVerified;
+use crate::db::models::{Passkey, User};
+use crate::db::{Transaction, WishlistDb};
+use crate::notify::Dispatcher;
+use crate::passkeys::{self, AUTHENTICATION_COOKIE, REGISTRATION_COOKIE};
+use crate::util::SiteUrl;
+use crate::web::auth::{self, DeviceInfo, LoggedInUser};
+use crate::web::WebError;
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FinishRegistration {
+    pub name: String,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StartLogin<'r> {
+    pub username: &'r str,
+}
+
+/// Where the browser should go after a passkey login.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LoginResult {
+    pub redirect: String,
+}
+
+/// The state of a login ceremony, with the user it was started for.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct LoginState {
+    user_id: i64,
+    authentication: PasskeyAuthentication,
+}
+
+fn ceremony_failed(e: WebauthnError) -> ApiError {
+    ApiError::Conflict(Json(ApiGenericError {
+        message: format!("Passkey check failed: {}", e),
+    }))
+}
+
+fn ceremony_expired() -> ApiError {
+    ApiError::Conflict(Json(ApiGenericError {
+        message: "This request expired, try again".to_string(),
+    }))
+}
+
+/// The same error for unknown users and users without passkeys, so usernames can't be probed.
+fn login_failed() -> ApiError {
+    ApiError::NotFound(Json(ApiGenericError {
+        message: "Couldn't log in with a passkey".to_string(),
+    }))
+}
+
+fn parse_credential(passkey: &Passkey) -> Result<Credential, ApiError> {
+    json::from_str(&passkey.passkey).map_err(|e| {
+        ApiError::Internal(Json(ApiGenericError {
+            message: format!("Stored passkey {} is invalid: {}", passkey.id, e),
+        }))
+    })
+}
+
+#[get("/account/passkeys")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let passkeys = Passkey::all_by_user(&mut db, user.user.id).await?;
+
+    Ok(Template::render("account/passkeys", context! { user, passkeys }))
+}
+
+/// Starts registering a new passkey, returning the options for `navigator.credentials.create()`.
+#[post("/account/passkeys/register/start")]
+pub async fn register_start(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    webauthn: &State<Webauthn>,
+    cookies: &CookieJar<'_>,
+    user: &LoggedInUser,
+) -> Result<Json<CreationChallengeResponse>, ApiError> {
+    // Don't let the same authenticator be registered twice
+    let mut existing = Vec::new();
+    for passkey in Passkey::all_by_user(&mut db, user.user.id).await? {
+        existing.push(parse_credential(&passkey)?.cred_id().clone());
+    }
+
+    let (challenge, registration) = webauthn
+        .start_passkey_registration(
+            Uuid::from_u128(user.user.id as u128),
+            &user.user.username,
+            &user.user.username,
+            Some(existing),
+        )
+        .map_err(ceremony_failed)?;
+
+    passkeys::store_state(cookies, REGISTRATION_COOKIE, &registration);
+
+    Ok(Json(challenge))
+}
+
+/// Finishes registering a passkey with the authenticator's response.
+#[post("/account/passkeys/register/finish", format = "json", data = "<registration>")]
+pub async fn register_finish(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    webauthn: &State<Webauthn>,
+    cookies: &CookieJar<'_>,
+    user: &LoggedInUser,
+    registration: Json<FinishRegistration>,
+) -> Result<Created<Json<Passkey>>, ApiError> {
+    let state: PasskeyRegistration =
+        passkeys::take_state(cookies, REGISTRATION_COOKIE).ok_or_else(ceremony_expired)?;
+
+    let credential = webauthn
+        .finish_passkey_registration(&registration.credential, &state)
+        .map_err(ceremony_failed)?;
+
+    let serialized = json::to_string(&credential).map_err(|e| {
+        ApiError::Internal(Json(ApiGenericError {
+            message: e.to_string(),
+        }))
+    })?;
+
+    let passkey = Passkey::create(
+        &mut db,
+        user.user.id,
+        &registration.name,
+        &credential.cred_id().to_string(),
+        &serialized,
+    )
+    .await?;
+
+    Ok(Created::new(uri!(index).to_string()).body(Json(passkey)))
+}
+
+#[delete("/account/passkeys/<id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    Passkey::destroy_by_user(&mut db, user.user.id, id).await?;
+
+    Ok(Redirect::to(uri!(index)))
+}
+
+/// Starts a passkey login for the user, returning the options for `navigator.credentials.get()`.
+#[post("/login/passkey/start", format = "json", data = "<login>")]
+pub async fn login_start(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    webauthn: &State<Webauthn>,
+    cookies: &CookieJar<'_>,
+    login: Json<StartLogin<'_>>,
+) -> Result<Json<RequestChallengeResponse>, ApiError> {
+    let user = User::find_by_username(&mut db, login.username)
+        .await?
+        .ok_or_else(login_failed)?;
+
+    let mut credentials = Vec::new();
+    for passkey in Passkey::all_by_user(&mut db, user.id).await? {
+        credentials.push(parse_credential(&passkey)?);
+    }
+    if credentials.is_empty() {
+        return Err(login_failed());
+    }
+
+    let (challenge, authentication) = webauthn
+        .start_passkey_authentication(&credentials)
+        .map_err(ceremony_failed)?;
+
+    let state = LoginState {
+        user_id: user.id,
+        authentication,
+    };
+    passkeys::store_state(cookies, AUTHENTICATION_COOKIE, &state);
+
+    Ok(Json(challenge))
+}
+
+/// Finishes a passkey login and starts a session, the same as a password login.
+#[post("/login/passkey/finish", format = "json", data = "<credential>")]
+pub async fn login_finish(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    webauthn: &State<Webauthn>,
+    cookies: &CookieJar<'_>,
+    dispatcher: &State<Dispatcher>,
+    site: &State<SiteUrl>,
+    device: DeviceInfo,
+    credential: Json<PublicKeyCredential>,
+) -> Result<Json<LoginResult>, ApiError> {
+    let state: LoginState =
+        passkeys::take_state(cookies, AUTHENTICATION_COOKIE).ok_or_else(ceremony_expired)?;
+
+    let result = webauthn
+        .finish_passkey_authentication(&credential, &state.authentication)
+        .map_err(ceremony_failed)?;
+
+    let mut passkey = Passkey::find_by_credential_id(&mut db, &result.cred_id().to_string())
+        .await?
+        .filter(|passkey| passkey.user_id == state.user_id)
+        .ok_or_else(login_failed)?;
+
+    // Keep the signature counter current so cloned authenticators can be detected
+    let mut stored = parse_credential(&passkey)?;
+    stored.update_credential(&result);
+    let serialized = json::to_string(&stored).map_err(|e| {
+        ApiError::Internal(Json(ApiGenericError {
+            message: e.to_string(),
+        }))
+    })?;
+    passkey.record_use(&mut db, &serialized).await?;
+
+    let user = User::find_by_id(&mut db, state.user_id)
+        .await?
+        .ok_or_else(login_failed)?;
+
+    // There's no remember me option for passkeys, the cookie lasts as long as the session
+    let remember_for = chrono::Duration::days(auth::SESSION_TTL_DAYS);
+    let session =
+        auth::create_user_session(&mut db, cookies, &user, &device, Some(remember_for)).await?;
+    let mut tx = Transaction::begin(db).await?;
+    auth::notify_new_device(&mut tx, site, &user, &session).await?;
+    dispatcher.commit(tx).await?;
+
+    let redirect = if user.is_suspended() {
+        uri!(crate::web::account::suspended)
+    } else {
+        uri!(crate::web_index)
+    };
+
+    Ok(Json(LoginResult {
+        redirect: redirect.to_string(),
+    }))
+}
@@ -0,0 +1,108 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::Template;
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{Item, ItemPrice, List, PriceAlert, SharePermission};
+use crate::db::{DataError, WishlistDb};
+use crate::sources::Price;
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::PasswordGate;
+use crate::web::shares::{shared_list, ShareGrants};
+use crate::web::{self, WebError};
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SetPriceAlert<'r> {
+    pub target_price: &'r str,
+}
+
+/// Returns the item if it's on a list the user can see. See `ShareGrants::allows`.
+async fn find_item(
+    db: &mut Connection<WishlistDb>,
+    grants: &ShareGrants,
+    user: &LoggedInUser,
+    list_key: &str,
+    id: i64,
+) -> Result<(List, Item), WebError<Template>> {
+    let list = shared_list(db, grants, Some(user), list_key, SharePermission::View).await?;
+
+    let item = Item::find_by_id(db, id)
+        .await?
+        .filter(|item| item.list_id == list.id)
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok((list, item))
+}
+
+#[post("/lists/<list_key>/items/<id>/price-alert", format = "form", data = "<alert>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+    alert: Form<SetPriceAlert<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+
+    // Targets are in the same currency the item is tracked in
+    let currency = ItemPrice::find_latest(&mut db, item.id)
+        .await?
+        .map(|price| price.currency)
+        .unwrap_or_else(|| "USD".to_string());
+
+    let target = Price::parse(alert.target_price, Some(&currency))
+        .ok_or_else(|| DataError::Other("Target price must be a number".to_string()))?;
+
+    PriceAlert::set(&mut db, item.id, user.user.id, target.amount_cents, &target.currency).await?;
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+#[delete("/lists/<list_key>/items/<id>/price-alert")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    _gate: PasswordGate,
+    user: &LoggedInUser,
+    grants: ShareGrants,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let (list, item) = find_item(&mut db, &grants, user, list_key, id).await?;
+
+    PriceAlert::destroy_by_item_and_user(&mut db, item.id, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use rocket::http::Status;
+
+    use crate::testing::TestApp;
+
+    #[rocket::async_test]
+    async fn alerts_are_only_on_lists_you_can_see() {
+        let app = TestApp::new().await;
+        let ideas = app.list_key("Ideas for later").await;
+        let desk = app.item_id("Standing desk").await;
+        let alert = format!("/lists/{}/items/{}/price-alert", ideas, desk);
+
+        // Ideas for later is private, and Bob has no share for it
+        app.login("bob").await;
+        let (status, _, _) = app.post_form(&alert, "target_price=100").await;
+        assert_eq!(status, Status::NotFound);
+        let (status, _, _) = app.post_form(&alert, "_method=DELETE").await;
+        assert_eq!(status, Status::NotFound);
+
+        app.login("alice").await;
+        let (status, _, _) = app.post_form(&alert, "target_price=100").await;
+        assert_eq!(status, Status::SeeOther);
+    }
+}
@@ -0,0 +1,45 @@
+use rocket::form::Form;
+use rocket::http::CookieJar;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::privacy::{self, Consent, PrivacyConfig};
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConsentChoice {
+    pub accept: bool,
+}
+
+#[get("/privacy")]
+pub fn show(config: &State<PrivacyConfig>, cookies: &CookieJar<'_>) -> Template {
+    let consent = privacy::consent(cookies).map(|consent| consent == Consent::Accepted);
+
+    Template::render(
+        "privacy/show",
+        context! {
+            minimal_mode: config.minimal_mode,
+            require_consent: config.require_consent,
+            consent,
+        },
+    )
+}
+
+#[post("/privacy/consent", format = "form", data = "<choice>")]
+pub fn consent(
+    _csrf: CsrfVerified,
+    cookies: &CookieJar<'_>,
+    choice: Form<ConsentChoice>,
+) -> Redirect {
+    let consent = if choice.accept {
+        Consent::Accepted
+    } else {
+        Consent::Rejected
+    };
+    privacy::set_consent(cookies, consent);
+
+    Redirect::to(uri!(show))
+}
@@ -0,0 +1,185 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+use url::Url;
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{Item, ItemPrice, List, ListPrivacy};
+use crate::db::{DataError, Transaction, WishlistDb};
+use crate::events::{DomainEvent, ItemRef, ListRef};
+use crate::notify::Dispatcher;
+use crate::quotas::Quotas;
+use crate::web::auth::LoggedInUser;
+use crate::web::{self, WebError};
+
+/// The title of the list quick adds go to when the user doesn't have one yet.
+static QUICK_LIST_TITLE: &str = "Quick adds";
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct QuickAdd<'r> {
+    /// A single line like "Nintendo Switch game $60 https://...".
+    pub line: &'r str,
+    /// The key of the list to add to. Defaults to the user's most recently changed list.
+    pub list: Option<&'r str>,
+}
+
+/// An item picked out of a quick add line.
+#[derive(Debug, PartialEq, Eq)]
+struct QuickItem {
+    title: String,
+    url: Option<String>,
+    /// The price in cents and its currency code.
+    price: Option<(i64, &'static str)>,
+}
+
+/// Splits a quick add line into a title, price and link. The first http(s) link and the first
+/// amount with a currency symbol are taken out, and what's left is the title. When only a link
+/// is given, its host is used as the title.
+fn parse_line(line: &str) -> QuickItem {
+    let mut url = None;
+    let mut price = None;
+    let mut words = Vec::new();
+
+    for word in line.split_whitespace() {
+        let word_price = if price.is_none() { parse_price(word) } else { None };
+        if url.is_none() && (word.starts_with("http://") || word.starts_with("https://")) {
+            url = Some(word.to_string());
+        } else if word_price.is_some() {
+            price = word_price;
+        } else {
+            words.push(word);
+        }
+    }
+
+    let mut title = words.join(" ");
+    if title.is_empty() {
+        if let Some(host) = url
+            .as_deref()
+            .and_then(|url| Url::parse(url).ok())
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+        {
+            title = host;
+        }
+    }
+
+    QuickItem { title, url, price }
+}
+
+/// Parses an amount like "$60", "€19.99" or "£1,299.00" into cents and a currency code.
+fn parse_price(word: &str) -> Option<(i64, &'static str)> {
+    let mut chars = word.chars();
+    let currency = match chars.next()? {
+        '$' => "USD",
+        '€' => "EUR",
+        '£' => "GBP",
+        _ => return None,
+    };
+
+    let amount = chars.as_str().trim_end_matches(['.', ',', '!', ';']);
+    let amount = amount.replace(',', "");
+    let (whole, cents) = match amount.split_once('.') {
+        Some((whole, cents)) if cents.len() <= 2 => (whole, format!("{:0<2}", cents)),
+        Some(_) => return None,
+        None => (amount.as_str(), "00".to_string()),
+    };
+
+    if whole.is_empty() || !whole.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !cents.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let whole: i64 = whole.parse().ok()?;
+    let cents: i64 = cents.parse().ok()?;
+    Some((whole.checked_mul(100)?.checked_add(cents)?, currency))
+}
+
+/// Returns the list items go to when none is given: the user's most recently changed list, or a
+/// new private one if they don't have any.
+pub async fn default_list(db: &mut Connection<WishlistDb>, user_id: i64) -> Result<List, DataError> {
+    match List::latest_by_user(db, user_id).await? {
+        Some(list) => Ok(list),
+        None => {
+            let privacy = ListPrivacy::Private.as_str();
+            List::create(db, Some(user_id), privacy, QUICK_LIST_TITLE, "", false, None).await
+        }
+    }
+}
+
+#[get("/quick")]
+pub fn new(user: &LoggedInUser) -> Template {
+    Template::render("quick/new", context! { user })
+}
+
+/// Adds an item from a single line of text. Meant for keyboard users and bots.
+#[post("/quick", format = "form", data = "<quick>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    pool: &State<WishlistDb>,
+    dispatcher: &State<Dispatcher>,
+    quotas: &State<Quotas>,
+    user: &LoggedInUser,
+    quick: Form<QuickAdd<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = match quick.list {
+        Some(key) => List::find_by_key(&mut db, key)
+            .await?
+            .filter(|list| list.user_id == Some(user.user.id))
+            .ok_or(WebError::NotFound(Template::render("error/404", ())))?,
+        None => default_list(&mut db, user.user.id).await?,
+    };
+
+    if let Some(message) = quotas.check_item(&mut db, &list).await? {
+        return Err(WebError::Invalid(Template::render(
+            "quick/new",
+            context! { user, line: quick.line, error_message: message },
+        )));
+    }
+
+    let parsed = parse_line(quick.line);
+    let mut tx = Transaction::begin(db).await?;
+    let item = match Item::create(
+        &mut tx,
+        list.id,
+        &parsed.title,
+        "",
+        parsed.url.as_deref(),
+        Some(user.user.id),
+    )
+    .await
+    {
+        Ok(item) => item,
+        Err(DataError::Validation(e)) => {
+            return Err(WebError::Invalid(Template::render(
+                "quick/new",
+                context! {
+                    user,
+                    line: quick.line,
+                    error_message: "Couldn't make an item from that line",
+                    errors: e,
+                },
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let event = DomainEvent::ItemAdded {
+        list: ListRef::from(&list),
+        item: ItemRef::from(&item),
+        by: Some(user.user.id),
+    };
+    dispatcher.dispatch(&mut tx, event).await?;
+    dispatcher.commit(tx).await?;
+
+    if let Some((amount_cents, currency)) = parsed.price {
+        ItemPrice::create(pool, item.id, amount_cents, currency).await?;
+    }
+
+    Ok(Redirect::to(uri!(web::items::show(list.key, item.id))))
+}
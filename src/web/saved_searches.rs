@@ -0,0 +1,109 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::SavedSearch;
+use crate::db::{DataError, WishlistDb};
+use crate::sources::Price;
+use crate::util;
+use crate::web::auth::LoggedInUser;
+use crate::web::WebError;
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SaveSearch<'r> {
+    pub query: &'r str,
+    /// The lowest price, or empty for no lower bound.
+    pub min_price: &'r str,
+    /// The highest price, or empty for no upper bound.
+    pub max_price: &'r str,
+    pub currency: &'r str,
+}
+
+/// Parses a bound of the price range, which can be left empty.
+fn parse_price(text: &str, currency: &str) -> Result<Option<Price>, DataError> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    Price::parse(text, Some(currency))
+        .map(Some)
+        .ok_or_else(|| DataError::Other("Prices must be numbers".to_string()))
+}
+
+#[get("/searches")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+) -> Result<Template, WebError<Template>> {
+    let searches = SavedSearch::all_by_user(&mut db, user.user.id)
+        .await?
+        .into_iter()
+        .map(|search| {
+            let price = |cents: Option<i64>| {
+                cents
+                    .zip(search.currency.as_deref())
+                    .map(|(cents, currency)| util::format_price(cents, currency))
+            };
+            context! {
+                id: search.id,
+                min_price: price(search.min_price_cents),
+                max_price: price(search.max_price_cents),
+                query: search.query,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Template::render(
+        "saved_searches/index",
+        context! { user, searches },
+    ))
+}
+
+/// Saves a search, emailing the user when new items match it.
+#[post("/searches", format = "form", data = "<search>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    search: Form<SaveSearch<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let currency = search.currency.trim().to_ascii_uppercase();
+    let min_price = parse_price(search.min_price, &currency)?;
+    let max_price = parse_price(search.max_price, &currency)?;
+
+    let currency = if min_price.is_some() || max_price.is_some() {
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(DataError::Other("Currency must be a code like USD".to_string()).into());
+        }
+        Some(currency)
+    } else {
+        None
+    };
+
+    SavedSearch::create(
+        &mut db,
+        user.user.id,
+        search.query,
+        min_price.map(|price| price.amount_cents),
+        max_price.map(|price| price.amount_cents),
+        currency.as_deref(),
+    )
+    .await?;
+
+    Ok(Redirect::to(uri!(index)))
+}
+
+#[delete("/searches/<id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: &LoggedInUser,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    SavedSearch::destroy_by_user(&mut db, id, user.user.id).await?;
+
+    Ok(Redirect::to(uri!(index)))
+}
@@ -0,0 +1,23 @@
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::api::v1::search::MAX_RESULTS;
+use crate::db::models::SearchHit;
+use crate::db::WishlistDb;
+use crate::web::auth::LoggedInUser;
+use crate::web::WebError;
+
+#[get("/search?<q>")]
+pub async fn index(
+    mut db: Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    q: Option<&str>,
+) -> Result<Template, WebError<Template>> {
+    let q = q.unwrap_or_default();
+    let hits = SearchHit::search(&mut db, q, user.map(|u| u.user.id), MAX_RESULTS).await?;
+
+    Ok(Template::render(
+        "search/index",
+        context! { user, q, searched: !q.trim().is_empty(), hits },
+    ))
+}
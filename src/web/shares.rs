@@ -0,0 +1,284 @@
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::Redirect;
+use rocket::serde::{json, Serialize};
+use rocket_db_pools::{sqlx, Connection};
+use rocket_dyn_templates::Template;
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{List, ListShare, SharePermission};
+use crate::db::WishlistDb;
+use crate::util::SiteUrl;
+use crate::web::auth::LoggedInUser;
+use crate::web::list_passwords::{self, Unlock};
+use crate::web::{self, WebError};
+
+/// The private cookie that remembers the shares a visitor has opened, as a JSON array of tokens.
+static SHARES_COOKIE: &str = "list_shares";
+
+/// The most shares remembered at once. Opening another forgets the oldest.
+const MAX_REMEMBERED_SHARES: usize = 20;
+
+#[derive(FromForm)]
+pub struct CreateShare<'r> {
+    /// See `SharePermission::as_str`.
+    pub permission: &'r str,
+    /// How many days the link works for. Empty means it doesn't expire.
+    pub expires_in_days: Option<u32>,
+}
+
+/// A share as shown on the list's edit page.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ShareSummary {
+    pub id: i64,
+    pub link: String,
+    pub permission: &'static str,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub expired: bool,
+}
+
+impl ShareSummary {
+    pub fn all(site: &SiteUrl, shares: &[ListShare]) -> Vec<ShareSummary> {
+        shares
+            .iter()
+            .map(|share| ShareSummary {
+                id: share.id,
+                link: site.url(&uri!(open(&share.token)).to_string()),
+                permission: share.permission().label(),
+                expires_at: share.expires_at,
+                expired: share.is_expired(),
+            })
+            .collect()
+    }
+}
+
+/// A permission that can be picked for a new share.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PermissionChoice {
+    pub value: &'static str,
+    pub label: &'static str,
+}
+
+/// Every permission that can be picked for a new share.
+pub fn permission_choices() -> Vec<PermissionChoice> {
+    SharePermission::ALL
+        .iter()
+        .map(|permission| PermissionChoice {
+            value: permission.as_str(),
+            label: permission.label(),
+        })
+        .collect()
+}
+
+/// The shares the visitor has opened, see `open`, and the lists they've unlocked with a
+/// password, see `crate::web::list_passwords`.
+pub struct ShareGrants {
+    tokens: Vec<String>,
+    unlocks: Vec<Unlock>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ShareGrants {
+    type Error = ();
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ShareGrants::of(request))
+    }
+}
+
+impl ShareGrants {
+    /// Reads the visitor's shares and unlocked lists from their cookies.
+    pub fn of(request: &Request<'_>) -> ShareGrants {
+        ShareGrants {
+            tokens: remembered(request.cookies()),
+            unlocks: list_passwords::unlocked(request.cookies()),
+        }
+    }
+
+    /// The most the visitor's shares let them do on the list, or `None` if they haven't opened
+    /// one of its shares or it's expired since. Unlocking the list with its password counts as a
+    /// share that allows claiming.
+    pub async fn permission(
+        &self,
+        db: &mut Connection<WishlistDb>,
+        list: &List,
+    ) -> Result<Option<SharePermission>, sqlx::Error> {
+        let mut best = None;
+        for token in &self.tokens {
+            if let Some(share) = ListShare::find_by_token(db, token).await? {
+                if share.list_id == list.id {
+                    best = best.max(Some(share.permission()));
+                }
+            }
+        }
+        if list.is_password_protected() && self.unlocks.iter().any(|unlock| unlock.opens(list)) {
+            best = best.max(Some(SharePermission::Claim));
+        }
+        Ok(best)
+    }
+
+    /// The share that lets the visitor do the most on the list, or `None` if they haven't opened
+    /// one of its shares or it's expired since.
+    pub async fn share(
+        &self,
+        db: &mut Connection<WishlistDb>,
+        list: &List,
+    ) -> Result<Option<ListShare>, sqlx::Error> {
+        let mut best: Option<ListShare> = None;
+        for token in &self.tokens {
+            if let Some(share) = ListShare::find_by_token(db, token).await? {
+                let better = best
+                    .as_ref()
+                    .is_none_or(|best| share.permission() > best.permission());
+                if share.list_id == list.id && better {
+                    best = Some(share);
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// Whether the user can do `needed` on the list. Owners can do anything, and so can anyone
+    /// on lists made without logging in, which have no owner. Lists they can see anyway (see
+    /// `List::is_visible_to`) can be viewed and claimed from like they always could, anything
+    /// else needs a share that allows it.
+    pub async fn allows(
+        &self,
+        db: &mut Connection<WishlistDb>,
+        list: &List,
+        user_id: Option<i64>,
+        needed: SharePermission,
+    ) -> Result<bool, sqlx::Error> {
+        if list.user_id.is_none() || list.is_owned_by(user_id) {
+            return Ok(true);
+        }
+        if needed < SharePermission::Edit && list.is_visible_to(user_id) {
+            return Ok(true);
+        }
+        Ok(self
+            .permission(db, list)
+            .await?
+            .is_some_and(|permission| permission >= needed))
+    }
+}
+
+/// The tokens of the shares the visitor has opened, oldest first.
+fn remembered(cookies: &CookieJar<'_>) -> Vec<String> {
+    cookies
+        .get_private(SHARES_COOKIE)
+        .and_then(|cookie| json::from_str(cookie.value()).ok())
+        .unwrap_or_default()
+}
+
+/// Remembers that the visitor opened the share, for as long as the browser keeps the session.
+fn remember(cookies: &CookieJar<'_>, token: &str) {
+    let mut tokens = remembered(cookies);
+    tokens.retain(|remembered| remembered != token);
+    tokens.push(token.to_string());
+    if tokens.len() > MAX_REMEMBERED_SHARES {
+        tokens.drain(..tokens.len() - MAX_REMEMBERED_SHARES);
+    }
+
+    if let Ok(value) = json::to_string(&tokens) {
+        let mut cookie = Cookie::new(SHARES_COOKIE, value);
+        cookie.set_same_site(SameSite::Lax);
+        cookie.set_http_only(true);
+        cookies.add_private(cookie);
+    }
+}
+
+/// Returns the list if the user can do `needed` on it, so handlers can 404 otherwise. See
+/// `ShareGrants::allows`.
+pub async fn shared_list(
+    db: &mut Connection<WishlistDb>,
+    grants: &ShareGrants,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+    needed: SharePermission,
+) -> Result<List, WebError<Template>> {
+    let list = List::find_by_key(db, list_key)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    if grants
+        .allows(db, &list, user.map(|user| user.user.id), needed)
+        .await?
+    {
+        Ok(list)
+    } else {
+        Err(WebError::NotFound(Template::render("error/404", ())))
+    }
+}
+
+/// Returns the list if the user may change its shares. Lists with an owner can only be changed by
+/// them.
+async fn find_list(
+    db: &mut Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+) -> Result<List, WebError<Template>> {
+    let list = List::find_by_key(db, list_key)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    match list.user_id {
+        Some(owner) if user.map(|user| user.user.id) != Some(owner) => {
+            Err(WebError::NotFound(Template::render("error/404", ())))
+        }
+        _ => Ok(list),
+    }
+}
+
+/// Opens a secret link, then shows its list.
+#[get("/s/<token>")]
+pub async fn open(
+    mut db: Connection<WishlistDb>,
+    cookies: &CookieJar<'_>,
+    token: &str,
+) -> Result<Redirect, WebError<Template>> {
+    let share = ListShare::find_by_token(&mut db, token)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let list = List::find_by_id(&mut db, share.list_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    remember(cookies, &share.token);
+
+    Ok(Redirect::to(uri!(web::lists::show(list.key))))
+}
+
+#[post("/lists/<list_key>/shares", format = "form", data = "<share>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+    share: Form<CreateShare<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = find_list(&mut db, user, list_key).await?;
+
+    let expires_at = share
+        .expires_in_days
+        .map(|days| (chrono::Utc::now() + chrono::Duration::days(days.into())).naive_utc());
+    ListShare::create(&mut db, list.id, share.permission, expires_at).await?;
+
+    Ok(Redirect::to(uri!(web::lists::edit(list.key))))
+}
+
+#[delete("/lists/<list_key>/shares/<id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let list = find_list(&mut db, user, list_key).await?;
+
+    ListShare::destroy_by_list(&mut db, list.id, id).await?;
+
+    Ok(Redirect::to(uri!(web::lists::edit(list.key))))
+}
@@ -0,0 +1,55 @@
+use rocket::request::FromParam;
+use rocket::response::Redirect;
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::db::models::{List, User, UsernameHistory};
+use crate::db::WishlistDb;
+use crate::web::auth::USERNAME_GRACE_DAYS;
+use crate::web::WebError;
+
+/// A `@username` path segment.
+pub struct Handle<'r>(pub &'r str);
+
+impl<'r> FromParam<'r> for Handle<'r> {
+    type Error = &'r str;
+
+    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+        match param.strip_prefix('@') {
+            Some(username) if !username.is_empty() => Ok(Handle(username)),
+            _ => Err(param),
+        }
+    }
+}
+
+#[derive(Responder)]
+pub enum Profile {
+    Page(Template),
+    Moved(Redirect),
+}
+
+/// Shows a user's public lists. Old usernames redirect to the new profile for a while after a
+/// rename.
+#[get("/<handle>", rank = 20)]
+pub async fn show(
+    mut db: Connection<WishlistDb>,
+    handle: Handle<'_>,
+) -> Result<Profile, WebError<Template>> {
+    if let Some(user) = User::find_by_username(&mut db, handle.0).await? {
+        let lists = List::all_public_by_user(&mut db, user.id).await?;
+        return Ok(Profile::Page(Template::render(
+            "users/show",
+            context! { profile: user, lists },
+        )));
+    }
+
+    let since = (chrono::Utc::now() - chrono::Duration::days(USERNAME_GRACE_DAYS)).naive_utc();
+    let history = UsernameHistory::find_recent(&mut db, handle.0, since)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+    let user = User::find_by_id(&mut db, history.user_id)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    Ok(Profile::Moved(Redirect::moved(format!("/@{}", user.username))))
+}
@@ -0,0 +1,168 @@
+use rocket::form::Form;
+use rocket::response::Redirect;
+use rocket::serde::{Deserialize, Serialize};
+use rocket_db_pools::Connection;
+use rocket_dyn_templates::{context, Template};
+
+use crate::csrf::CsrfVerified;
+use crate::db::models::{List, ListWebhook};
+use crate::db::{DataError, WishlistDb};
+use crate::events::DomainEvent;
+use crate::web::auth::LoggedInUser;
+use crate::web::{self, WebError};
+
+#[derive(FromForm, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateWebhook<'r> {
+    pub url: &'r str,
+    pub format: &'r str,
+    /// The events picked for an automation webhook. None picked means all of them.
+    pub events: Vec<&'r str>,
+}
+
+/// A webhook as shown on the list's edit page, without its secret URL.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct WebhookSummary {
+    pub id: i64,
+    pub url: String,
+    pub format: String,
+    /// What an automation webhook is sent, e.g. "Item claimed, Item unclaimed".
+    pub events: Option<String>,
+    /// The key an automation webhook's posts are signed with, so the owner can check them.
+    pub secret: Option<String>,
+}
+
+impl WebhookSummary {
+    pub fn all(webhooks: &[ListWebhook]) -> Vec<WebhookSummary> {
+        webhooks
+            .iter()
+            .map(|webhook| WebhookSummary {
+                id: webhook.id,
+                url: webhook.masked_url(),
+                format: webhook.format.clone(),
+                events: (webhook.format == ListWebhook::JSON).then(|| {
+                    match webhook.events.as_str() {
+                        "" => "All events".to_string(),
+                        events => events
+                            .split(',')
+                            .map(event_label)
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    }
+                }),
+                secret: (webhook.format == ListWebhook::JSON && !webhook.secret.is_empty())
+                    .then(|| webhook.secret.clone()),
+            })
+            .collect()
+    }
+}
+
+/// An event that can be picked for an automation webhook.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EventChoice {
+    pub kind: &'static str,
+    pub label: &'static str,
+    pub checked: bool,
+}
+
+/// Every event that can be picked for an automation webhook, with the given ones checked.
+pub fn event_choices(checked: &[&str]) -> Vec<EventChoice> {
+    DomainEvent::KINDS
+        .iter()
+        .map(|&kind| EventChoice {
+            kind,
+            label: event_label(kind),
+            checked: checked.contains(&kind),
+        })
+        .collect()
+}
+
+fn event_label(kind: &str) -> &'static str {
+    match kind {
+        "list_created" => "List created",
+        "list_deleted" => "List deleted",
+        "item_added" => "Item added",
+        "item_updated" => "Item updated",
+        "item_deleted" => "Item deleted",
+        "item_received" => "Item received",
+        "item_claimed" => "Item claimed",
+        "item_unclaimed" => "Item unclaimed",
+        _ => "Unknown event",
+    }
+}
+
+/// Returns the list if the user may change its webhooks. Lists with an owner can only be changed
+/// by them, since a webhook sends the list's contents somewhere else.
+async fn find_list(
+    db: &mut Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+) -> Result<List, WebError<Template>> {
+    let list = List::find_by_key(db, list_key)
+        .await?
+        .ok_or(WebError::NotFound(Template::render("error/404", ())))?;
+
+    match list.user_id {
+        Some(owner) if user.map(|user| user.user.id) != Some(owner) => {
+            Err(WebError::NotFound(Template::render("error/404", ())))
+        }
+        _ => Ok(list),
+    }
+}
+
+#[post("/lists/<list_key>/webhooks", format = "form", data = "<webhook>")]
+pub async fn create(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+    webhook: Form<CreateWebhook<'_>>,
+) -> Result<Redirect, WebError<Template>> {
+    let list = find_list(&mut db, user, list_key).await?;
+
+    match ListWebhook::create(
+        &mut db,
+        list.id,
+        webhook.url,
+        webhook.format,
+        &webhook.events,
+    )
+    .await
+    {
+        Ok(_) => Ok(Redirect::to(uri!(web::lists::edit(list.key)))),
+        Err(DataError::Validation(e)) => {
+            let webhooks = ListWebhook::all_by_list(&mut db, list.id).await?;
+            Err(WebError::Invalid(Template::render(
+                "lists/edit",
+                context! {
+                    webhooks: WebhookSummary::all(&webhooks),
+                    list,
+                    webhook: context! {
+                        url: webhook.url,
+                        format: webhook.format,
+                    },
+                    webhook_events: event_choices(&webhook.events),
+                    webhook_errors: e,
+                },
+            )))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[delete("/lists/<list_key>/webhooks/<id>")]
+pub async fn destroy(
+    _csrf: CsrfVerified,
+    mut db: Connection<WishlistDb>,
+    user: Option<&LoggedInUser>,
+    list_key: &str,
+    id: i64,
+) -> Result<Redirect, WebError<Template>> {
+    let list = find_list(&mut db, user, list_key).await?;
+
+    ListWebhook::destroy_by_list(&mut db, list.id, id).await?;
+
+    Ok(Redirect::to(uri!(web::lists::edit(list.key))))
+}